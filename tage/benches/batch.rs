@@ -0,0 +1,72 @@
+//! Compares row-at-a-time with batch execution on a multi-million-row table.
+//!
+//! Run with `cargo bench --bench batch`; `TAGE_BENCH_ROWS` overrides the table size.
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use tage::database::{Database, QueryResult};
+use tage::execution::ExecutionMode;
+use tage::storage::MemoryStore;
+
+const QUERIES: &[&str] = &[
+    "select count(*), sum(v) from t where v * 2 > 100 and id % 7 <> 0",
+    "select k, count(*), sum(id), avg(v), max(id) from t group by k",
+    "select count(*) from t where s like 'name1%'",
+    "select id + k, v / 2 from t where k < 3",
+];
+
+fn main() {
+    let rows: usize = env::var("TAGE_BENCH_ROWS")
+        .ok()
+        .and_then(|rows| rows.parse().ok())
+        .unwrap_or(2_000_000);
+    let mut database = Database::open(MemoryStore::new()).unwrap();
+    database
+        .execute("create table t (id bigint, k integer, v double, s text)")
+        .unwrap();
+
+    let start = Instant::now();
+    for chunk in (0..rows).collect::<Vec<_>>().chunks(10_000) {
+        let values: Vec<String> = chunk
+            .iter()
+            .map(|id| {
+                format!(
+                    "({}, {}, {}, 'name{}')",
+                    id,
+                    id % 100,
+                    (id % 1000) as f64 / 4.0,
+                    id % 37
+                )
+            })
+            .collect();
+        database
+            .execute(&format!("insert into t values {}", values.join(", ")))
+            .unwrap();
+    }
+    println!("loaded {} rows in {:.2?}", rows, start.elapsed());
+
+    for query in QUERIES {
+        let (row, row_result) = run(&mut database, ExecutionMode::Row, query);
+        let (batch, batch_result) = run(&mut database, ExecutionMode::Batch, query);
+        assert_eq!(row_result, batch_result, "{}", query);
+        println!(
+            "{}\n  row: {:.2?}  batch: {:.2?}  speedup: {:.2}x",
+            query,
+            row,
+            batch,
+            row.as_secs_f64() / batch.as_secs_f64()
+        );
+    }
+}
+
+fn run(
+    database: &mut Database<MemoryStore>,
+    mode: ExecutionMode,
+    query: &str,
+) -> (Duration, Vec<QueryResult>) {
+    database.set_execution_mode(mode);
+    let start = Instant::now();
+    let results = database.execute(query).unwrap();
+    (start.elapsed(), results)
+}
@@ -0,0 +1,481 @@
+//! Checks where aggregate and window functions may appear, and that a grouped query
+//! references columns outside of aggregates only when they are grouped.
+//!
+//! A column is also considered grouped when the primary key of its table is, as the key
+//! determines the rest of the row.
+
+use crate::analyser::binder::qualified_name;
+use crate::analyser::bound::*;
+use crate::analyser::AnalyseError;
+use crate::functions::FunctionRef;
+
+pub fn check_aggregates(query: &BoundQuery) -> Result<(), AnalyseError> {
+    match query {
+        BoundQuery::Select(select) => check_select(select),
+        BoundQuery::Insert(insert) => {
+            match &insert.source {
+                BoundInsertSource::Values(rows) => {
+                    for expr in rows.iter().flatten() {
+                        check_ungrouped(expr, "VALUES")?;
+                    }
+                }
+                BoundInsertSource::Select(select) => check_select(select)?,
+                BoundInsertSource::DefaultValues => {}
+            }
+            if let Some(BoundOnConflict {
+                action:
+                    BoundConflictAction::DoUpdate {
+                        assignments,
+                        filter,
+                        ..
+                    },
+                ..
+            }) = &insert.on_conflict
+            {
+                for assignment in assignments {
+                    check_ungrouped(&assignment.value, "UPDATE")?;
+                }
+                check_ungrouped_condition(filter.as_ref(), "WHERE")?;
+            }
+            check_returning(&insert.returning)
+        }
+        BoundQuery::Update(update) => {
+            for assignment in &update.assignments {
+                check_ungrouped(&assignment.value, "UPDATE")?;
+            }
+            check_from(update.from.as_ref())?;
+            check_ungrouped_condition(update.filter.as_ref(), "WHERE")?;
+            check_returning(&update.returning)
+        }
+        BoundQuery::Delete(delete) => {
+            check_from(delete.using.as_ref())?;
+            check_ungrouped_condition(delete.filter.as_ref(), "WHERE")?;
+            check_returning(&delete.returning)
+        }
+        BoundQuery::Explain { query, .. } => check_aggregates(query),
+        BoundQuery::Utility(_) => Ok(()),
+    }
+}
+
+/// Call of an aggregate of the function registry, `sum(x) over (...)` is a window
+/// function instead.
+pub fn is_aggregate(function: &BoundFunction) -> bool {
+    function.over.is_none() && matches!(function.function, Some(FunctionRef::Aggregate(_)))
+}
+
+fn check_select(select: &BoundSelect) -> Result<(), AnalyseError> {
+    check_from(select.from.as_ref())?;
+    check_ungrouped_condition(select.filter.as_ref(), "WHERE")?;
+    for expr in &select.group_by {
+        check_ungrouped(expr, "GROUP BY")?;
+    }
+    if let Some(having) = &select.having {
+        check_subqueries(having)?;
+        check_no_windows(having, "HAVING")?;
+    }
+    let outputs = || {
+        select
+            .projection
+            .iter()
+            .map(|item| &item.expr)
+            .chain(&select.having)
+            .chain(select.order_by.iter().map(|(expr, _)| expr))
+    };
+    for expr in outputs() {
+        check_subqueries(expr)?;
+        check_nesting(expr, None)?;
+    }
+    let grouped =
+        !select.group_by.is_empty() || select.having.is_some() || outputs().any(contains_aggregate);
+    if grouped {
+        let grouping = Grouping::new(select);
+        for expr in outputs() {
+            grouping.check(expr)?;
+        }
+    }
+    for union in &select.unions {
+        check_select(&union.query)?;
+    }
+    Ok(())
+}
+
+fn check_from(from: Option<&BoundFrom>) -> Result<(), AnalyseError> {
+    for join in from.iter().flat_map(|from| &from.joins) {
+        check_ungrouped_condition(join.on.as_ref(), "JOIN conditions")?;
+    }
+    Ok(())
+}
+
+fn check_returning(returning: &[ProjectionItem]) -> Result<(), AnalyseError> {
+    for item in returning {
+        check_ungrouped(&item.expr, "RETURNING")?;
+    }
+    Ok(())
+}
+
+fn check_ungrouped_condition(
+    condition: Option<&BoundExpr>,
+    context: &'static str,
+) -> Result<(), AnalyseError> {
+    condition.map_or(Ok(()), |condition| check_ungrouped(condition, context))
+}
+
+/// Neither aggregate nor window functions may appear in `context`.
+fn check_ungrouped(expr: &BoundExpr, context: &'static str) -> Result<(), AnalyseError> {
+    check_subqueries(expr)?;
+    check_no_windows(expr, context)?;
+    match find_function(expr, &is_aggregate) {
+        Some(function) => Err(AnalyseError::AggregateNotAllowed {
+            context,
+            span: function.span,
+        }),
+        None => Ok(()),
+    }
+}
+
+fn check_no_windows(expr: &BoundExpr, context: &'static str) -> Result<(), AnalyseError> {
+    match find_function(expr, &|function| function.over.is_some()) {
+        Some(function) => Err(AnalyseError::WindowFunctionNotAllowed {
+            context,
+            span: function.span,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Subqueries are checked as queries of their own.
+fn check_subqueries(expr: &BoundExpr) -> Result<(), AnalyseError> {
+    match expr {
+        BoundExpr::InSubquery { subquery, .. }
+        | BoundExpr::Exists { subquery, .. }
+        | BoundExpr::Subquery(subquery) => check_select(subquery)?,
+        _ => {}
+    }
+    expr.children().into_iter().try_for_each(check_subqueries)
+}
+
+/// Aggregates can't contain aggregates or window functions, window functions can't contain
+/// window functions. Aggregates inside of a window function are computed before it.
+fn check_nesting(expr: &BoundExpr, outer: Option<&BoundFunction>) -> Result<(), AnalyseError> {
+    let mut outer = outer;
+    if let BoundExpr::Function(function) = expr {
+        match outer {
+            Some(outer) if is_aggregate(outer) && function.over.is_some() => {
+                return Err(AnalyseError::WindowFunctionNotAllowed {
+                    context: "aggregate function calls",
+                    span: function.span,
+                })
+            }
+            Some(outer) if is_aggregate(outer) && is_aggregate(function) => {
+                return Err(AnalyseError::NestedAggregate {
+                    span: function.span,
+                })
+            }
+            Some(_) if function.over.is_some() => {
+                return Err(AnalyseError::NestedWindowFunction {
+                    span: function.span,
+                })
+            }
+            _ => {}
+        }
+        if is_aggregate(function) || function.over.is_some() {
+            outer = Some(function);
+        }
+    }
+    expr.children()
+        .into_iter()
+        .try_for_each(|child| check_nesting(child, outer))
+}
+
+fn contains_aggregate(expr: &BoundExpr) -> bool {
+    find_function(expr, &is_aggregate).is_some()
+}
+
+fn find_function<'e>(
+    expr: &'e BoundExpr,
+    predicate: &dyn Fn(&BoundFunction) -> bool,
+) -> Option<&'e BoundFunction> {
+    match expr {
+        BoundExpr::Function(function) if predicate(function) => Some(function),
+        _ => expr
+            .children()
+            .into_iter()
+            .find_map(|child| find_function(child, predicate)),
+    }
+}
+
+/// Columns a grouped query may reference outside of aggregates.
+struct Grouping<'s> {
+    group_by: &'s [BoundExpr],
+    /// Relations of the `from` list of the query, columns of enclosing queries are constant
+    /// within a group
+    relations: Vec<&'s BoundRelation>,
+    /// Relations whose primary key is grouped
+    dependent: Vec<RelationId>,
+}
+
+impl<'s> Grouping<'s> {
+    fn new(select: &'s BoundSelect) -> Self {
+        let relations: Vec<_> = select
+            .from
+            .iter()
+            .flat_map(|from| {
+                from.relations
+                    .iter()
+                    .chain(from.joins.iter().map(|join| &join.relation))
+            })
+            .collect();
+        let is_grouped = |relation: &BoundRelation, column_id| {
+            select.group_by.iter().any(|expr| {
+                matches!(expr, BoundExpr::Column(column)
+                    if column.relation == relation.id && column.column_id == column_id)
+            })
+        };
+        let dependent = relations
+            .iter()
+            .filter(|relation| {
+                let primary_key = relation.table.primary_key();
+                !primary_key.is_empty() && primary_key.iter().all(|id| is_grouped(relation, *id))
+            })
+            .map(|relation| relation.id)
+            .collect();
+        Grouping {
+            group_by: &select.group_by,
+            relations,
+            dependent,
+        }
+    }
+
+    fn check(&self, expr: &BoundExpr) -> Result<(), AnalyseError> {
+        if self.group_by.contains(expr) {
+            return Ok(());
+        }
+        match expr {
+            BoundExpr::Column(column) => self.check_column(column),
+            BoundExpr::Function(function) if is_aggregate(function) => Ok(()),
+            BoundExpr::Exists { subquery, .. } | BoundExpr::Subquery(subquery) => {
+                self.check_subquery(subquery)
+            }
+            BoundExpr::InSubquery { expr, subquery, .. } => {
+                self.check(expr)?;
+                self.check_subquery(subquery)
+            }
+            _ => expr
+                .children()
+                .into_iter()
+                .try_for_each(|child| self.check(child)),
+        }
+    }
+
+    fn check_column(&self, column: &BoundColumn) -> Result<(), AnalyseError> {
+        let Some(relation) = self
+            .relations
+            .iter()
+            .find(|relation| relation.id == column.relation)
+        else {
+            return Ok(());
+        };
+        if self.dependent.contains(&relation.id) {
+            return Ok(());
+        }
+        Err(AnalyseError::UngroupedColumn {
+            column_name: qualified_name(Some(&relation.name), &column.name),
+            span: column.span,
+        })
+    }
+
+    /// A subquery may reference the columns of this query only if they are grouped.
+    fn check_subquery(&self, subquery: &BoundSelect) -> Result<(), AnalyseError> {
+        let from = subquery.from.iter().flat_map(|from| &from.joins);
+        from.filter_map(|join| join.on.as_ref())
+            .chain(subquery.projection.iter().map(|item| &item.expr))
+            .chain(&subquery.filter)
+            .chain(&subquery.group_by)
+            .chain(&subquery.having)
+            .chain(subquery.order_by.iter().map(|(expr, _)| expr))
+            .try_for_each(|expr| self.check(expr))?;
+        for union in &subquery.unions {
+            self.check_subquery(&union.query)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::analyse;
+    use crate::catalog::MemoryCatalog;
+    use crate::parser::lexer::Span;
+    use crate::parser::Queries;
+    use crate::value::DataType;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        let users = catalog.create_table(
+            "public",
+            "users",
+            &[
+                ("id", DataType::Integer),
+                ("name", DataType::Text),
+                ("age", DataType::Integer),
+            ],
+        );
+        catalog.set_primary_key(users, &["id"]);
+        catalog.create_table(
+            "public",
+            "orders",
+            &[
+                ("id", DataType::Integer),
+                ("user_id", DataType::Integer),
+                ("total", DataType::Double),
+            ],
+        );
+        catalog
+    }
+
+    fn check(input: &str) -> Result<(), AnalyseError> {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        analyse(&query, &catalog()).map(|_| ())
+    }
+
+    fn ungrouped(column_name: &str, start: usize, end: usize) -> Result<(), AnalyseError> {
+        Err(AnalyseError::UngroupedColumn {
+            column_name: column_name.to_string(),
+            span: Span { start, end },
+        })
+    }
+
+    #[test]
+    fn accepts_grouped_queries() {
+        for input in [
+            "select count(*) from orders",
+            "select user_id, sum(total) from orders group by user_id",
+            "select total + 1 from orders group by total + 1",
+            "select total + 1, count(*) from orders group by total having max(id) > 1",
+            "select user_id from orders group by 1 order by count(*) desc",
+            "select o.user_id, count(*) from orders o group by o.user_id order by o.user_id",
+            "select id, name, age from users group by id",
+            "select u.name, sum(o.total) from users u join orders o on o.user_id = u.id \
+             group by u.id",
+            "select user_id, (select count(*) from users where id = orders.user_id) \
+             from orders group by user_id",
+            "select rank() over (order by sum(total)) from orders group by user_id",
+            "select sum(total) over (partition by user_id) from orders",
+            "select name from users where exists (select count(*) from orders having \
+             sum(total) > users.age)",
+        ] {
+            assert_eq!(check(input), Ok(()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn reports_ungrouped_columns() {
+        assert_eq!(
+            check("select user_id, total from orders group by user_id"),
+            ungrouped("orders.total", 16, 21)
+        );
+        assert_eq!(
+            check("select total, count(*) from orders"),
+            ungrouped("orders.total", 7, 12)
+        );
+        assert_eq!(
+            check("select total + 1 from orders group by total + 2"),
+            ungrouped("orders.total", 7, 12)
+        );
+        assert_eq!(
+            check("select user_id from orders o group by user_id having o.total > 1"),
+            ungrouped("o.total", 53, 60)
+        );
+        assert_eq!(
+            check("select id from orders group by user_id order by id"),
+            ungrouped("orders.id", 7, 9)
+        );
+        assert_eq!(
+            check(
+                "select u.id, o.total from users u join orders o on o.user_id = u.id group by u.id"
+            ),
+            ungrouped("o.total", 13, 20)
+        );
+        assert_eq!(
+            check("select count(*) over (partition by total) from orders group by user_id"),
+            ungrouped("orders.total", 35, 40)
+        );
+        assert_eq!(
+            check("select (select name from users where age = total) from orders group by user_id"),
+            ungrouped("orders.total", 43, 48)
+        );
+    }
+
+    #[test]
+    fn reports_misplaced_functions() {
+        assert_eq!(
+            check("select id from orders where sum(total) > 1"),
+            Err(AnalyseError::AggregateNotAllowed {
+                context: "WHERE",
+                span: Span { start: 28, end: 31 },
+            })
+        );
+        assert_eq!(
+            check("select count(*) from orders group by count(*)"),
+            Err(AnalyseError::AggregateNotAllowed {
+                context: "GROUP BY",
+                span: Span { start: 37, end: 42 },
+            })
+        );
+        assert_eq!(
+            check("select 1 from users join orders on count(*) = 1"),
+            Err(AnalyseError::AggregateNotAllowed {
+                context: "JOIN conditions",
+                span: Span { start: 35, end: 40 },
+            })
+        );
+        assert_eq!(
+            check("update orders set total = max(total)"),
+            Err(AnalyseError::AggregateNotAllowed {
+                context: "UPDATE",
+                span: Span { start: 26, end: 29 },
+            })
+        );
+        assert_eq!(
+            check("select max(sum(total)) from orders"),
+            Err(AnalyseError::NestedAggregate {
+                span: Span { start: 11, end: 14 },
+            })
+        );
+        assert_eq!(
+            check("select user_id from orders group by rank() over (order by id)"),
+            Err(AnalyseError::WindowFunctionNotAllowed {
+                context: "GROUP BY",
+                span: Span { start: 36, end: 40 },
+            })
+        );
+        assert_eq!(
+            check("select 1 from orders having rank() over () > 1"),
+            Err(AnalyseError::WindowFunctionNotAllowed {
+                context: "HAVING",
+                span: Span { start: 28, end: 32 },
+            })
+        );
+        assert_eq!(
+            check("select sum(rank() over ()) from orders"),
+            Err(AnalyseError::WindowFunctionNotAllowed {
+                context: "aggregate function calls",
+                span: Span { start: 11, end: 15 },
+            })
+        );
+        assert_eq!(
+            check("select sum(rank() over ()) over () from orders"),
+            Err(AnalyseError::NestedWindowFunction {
+                span: Span { start: 11, end: 15 },
+            })
+        );
+        assert_eq!(
+            check("select id from users where id in (select max(total) from orders where max(id) > 1)"),
+            Err(AnalyseError::AggregateNotAllowed {
+                context: "WHERE",
+                span: Span { start: 70, end: 73 },
+            })
+        );
+    }
+}
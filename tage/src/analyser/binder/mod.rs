@@ -0,0 +1,1160 @@
+//! Resolves tables and columns of a statement against the catalog.
+//!
+//! Every `select` opens a scope with the relations of its `from`, a column is looked up
+//! in the innermost scope first, so subqueries may reference columns of enclosing queries.
+
+use crate::analyser::aggregates::check_aggregates;
+use crate::analyser::bound::*;
+use crate::analyser::typecheck::{argument_types, check_types};
+use crate::analyser::AnalyseError;
+use crate::catalog::{Catalog, Column, ColumnId, Table, View};
+use crate::parser::expr::{ColumnIdentifier, Expr, FrameBound, Literal, Window};
+use crate::parser::lexer::Span;
+use crate::parser::*;
+use crate::planner::DERIVED_TABLE_ID;
+
+pub(crate) struct Binder<'c> {
+    catalog: &'c dyn Catalog,
+    /// Relations of the current query and of the enclosing ones, innermost last
+    scopes: Vec<Vec<BoundRelation>>,
+    relations: usize,
+}
+
+impl<'c> Binder<'c> {
+    pub(crate) fn new(catalog: &'c dyn Catalog) -> Self {
+        Self {
+            catalog,
+            scopes: vec![],
+            relations: 0,
+        }
+    }
+
+    pub(crate) fn bind_query(&mut self, query: &Query) -> Result<BoundQuery, AnalyseError> {
+        match query {
+            Query::Select(query) => Ok(BoundQuery::Select(self.bind_select(query)?)),
+            Query::Insert(query) => {
+                Ok(BoundQuery::Insert(self.in_scope(|b| b.bind_insert(query))?))
+            }
+            Query::Update(query) => {
+                Ok(BoundQuery::Update(self.in_scope(|b| b.bind_update(query))?))
+            }
+            Query::Delete(query) => {
+                Ok(BoundQuery::Delete(self.in_scope(|b| b.bind_delete(query))?))
+            }
+            Query::Explain(ExplainQuery { analyze, query }) => Ok(BoundQuery::Explain {
+                analyze: *analyze,
+                query: Box::new(self.bind_query(query)?),
+            }),
+            _ => Ok(BoundQuery::Utility(query.clone())),
+        }
+    }
+
+    fn in_scope<T>(
+        &mut self,
+        bind: impl FnOnce(&mut Self) -> Result<T, AnalyseError>,
+    ) -> Result<T, AnalyseError> {
+        self.scopes.push(vec![]);
+        let result = bind(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// The queries of a union are bound in scopes of their own, its `order by` only sees
+    /// the result columns.
+    fn bind_select(&mut self, query: &SelectQuery) -> Result<BoundSelect, AnalyseError> {
+        let mut select = self.bind_select_clauses(query)?;
+        for union in &query.unions {
+            let arm = self.bind_select(&union.query)?;
+            if arm.projection.len() != select.projection.len() {
+                return Err(AnalyseError::UnionColumnCount {
+                    expected: select.projection.len(),
+                    found: arm.projection.len(),
+                });
+            }
+            select.unions.push(BoundUnion {
+                all: union.all,
+                query: arm,
+            });
+        }
+        if !query.unions.is_empty() {
+            for column in query.order_by_statement.iter().flat_map(|o| &o.columns) {
+                let expr = union_order_key(&column.expr, &select.projection)?;
+                select.order_by.push((expr, column.order));
+            }
+        }
+        Ok(select)
+    }
+
+    fn bind_select_clauses(&mut self, query: &SelectQuery) -> Result<BoundSelect, AnalyseError> {
+        self.in_scope(|binder| {
+            let from = binder.bind_from(query.from_statement.as_ref())?;
+            let filter =
+                binder.bind_condition(query.where_statement.as_ref().map(|w| &w.condition))?;
+            let projection = binder.bind_projection(&query.select_statement.columns)?;
+            let mut group_by = vec![];
+            for expr in query.group_by_statement.iter().flat_map(|g| &g.columns) {
+                group_by.push(binder.bind_output_reference(expr, &projection, true)?);
+            }
+            let having =
+                binder.bind_condition(query.having_statement.as_ref().map(|h| &h.condition))?;
+            let mut order_by = vec![];
+            // the order by of a union is bound with the union
+            let order_by_statement = query.order_by_statement.iter();
+            let order_by_statement = order_by_statement.filter(|_| query.unions.is_empty());
+            for column in order_by_statement.flat_map(|o| &o.columns) {
+                let expr = binder.bind_output_reference(&column.expr, &projection, false)?;
+                order_by.push((expr, column.order));
+            }
+            let limit = query.limit_statement.as_ref();
+            Ok(BoundSelect {
+                from,
+                projection,
+                distinct: query.select_statement.distinct,
+                filter,
+                group_by,
+                having,
+                unions: vec![],
+                order_by,
+                limit: limit.and_then(|l| l.limit),
+                offset: limit.and_then(|l| l.offset),
+            })
+        })
+    }
+
+    /// Values and the select are bound before the target table is visible,
+    /// `on conflict do update` sees the target and `excluded`.
+    fn bind_insert(&mut self, query: &InsertQuery) -> Result<BoundInsert, AnalyseError> {
+        let span = query.table.span;
+        let table = self.target(&query.table)?;
+        let columns = if query.columns.is_empty() {
+            table.table.columns.clone()
+        } else {
+            target_columns(&table, &query.columns, span)?
+        };
+        // fewer values than columns is fine only when the columns aren't listed
+        let check_count = |found: usize| {
+            if found > columns.len() || (!query.columns.is_empty() && found < columns.len()) {
+                return Err(AnalyseError::ColumnCountMismatch {
+                    expected: columns.len(),
+                    found,
+                    span,
+                });
+            }
+            Ok(())
+        };
+        let source = match &query.source {
+            InsertSource::Values(rows) => {
+                let mut bound_rows = vec![];
+                for row in rows {
+                    check_count(row.len())?;
+                    bound_rows.push(self.bind_exprs(row)?);
+                }
+                BoundInsertSource::Values(bound_rows)
+            }
+            InsertSource::Select(select) => {
+                let select = self.bind_select(select)?;
+                check_count(select.projection.len())?;
+                BoundInsertSource::Select(Box::new(select))
+            }
+            InsertSource::DefaultValues => BoundInsertSource::DefaultValues,
+        };
+
+        self.add_relation(&table, span)?;
+        let returning = self.bind_projection(&query.returning)?;
+        let on_conflict = match &query.on_conflict {
+            Some(OnConflict { columns, action }) => {
+                let columns = target_columns(&table, columns, span)?;
+                let action = match action {
+                    ConflictAction::DoNothing => BoundConflictAction::DoNothing,
+                    ConflictAction::DoUpdate {
+                        set_statement,
+                        where_statement,
+                    } => {
+                        let excluded = BoundRelation {
+                            id: self.next_relation_id(),
+                            table: table.table.clone(),
+                            function: None,
+                            view: None,
+                            name: "excluded".to_string(),
+                            span,
+                        };
+                        self.add_relation(&excluded, span)?;
+                        BoundConflictAction::DoUpdate {
+                            excluded: Box::new(excluded),
+                            assignments: self.bind_assignments(&table, set_statement, span)?,
+                            filter: self
+                                .bind_condition(where_statement.as_ref().map(|w| &w.condition))?,
+                        }
+                    }
+                };
+                Some(BoundOnConflict { columns, action })
+            }
+            None => None,
+        };
+        Ok(BoundInsert {
+            table,
+            columns,
+            source,
+            on_conflict,
+            returning,
+        })
+    }
+
+    fn bind_update(&mut self, query: &UpdateQuery) -> Result<BoundUpdate, AnalyseError> {
+        let table = self.target(&query.table)?;
+        self.add_relation(&table, query.table.span)?;
+        let from = self.bind_from(query.from_statement.as_ref())?;
+        Ok(BoundUpdate {
+            assignments: self.bind_assignments(&table, &query.set_statement, query.table.span)?,
+            from,
+            filter: self.bind_condition(query.where_statement.as_ref().map(|w| &w.condition))?,
+            returning: self.bind_projection(&query.returning)?,
+            table,
+        })
+    }
+
+    fn bind_delete(&mut self, query: &DeleteQuery) -> Result<BoundDelete, AnalyseError> {
+        let table = self.target(&query.table)?;
+        self.add_relation(&table, query.table.span)?;
+        Ok(BoundDelete {
+            using: self.bind_from(query.using_statement.as_ref())?,
+            filter: self.bind_condition(query.where_statement.as_ref().map(|w| &w.condition))?,
+            returning: self.bind_projection(&query.returning)?,
+            table,
+        })
+    }
+
+    /// A join condition sees only the tables joined so far.
+    fn bind_from(
+        &mut self,
+        from: Option<&FromStatement>,
+    ) -> Result<Option<BoundFrom>, AnalyseError> {
+        let Some(from) = from else {
+            return Ok(None);
+        };
+        let mut relations = vec![];
+        for table in &from.tables {
+            let relation = self.relation(table)?;
+            self.add_relation(&relation, table.span)?;
+            relations.push(relation);
+        }
+        let mut joins = vec![];
+        for join in &from.joins {
+            let relation = self.relation(&join.table)?;
+            self.add_relation(&relation, join.table.span)?;
+            joins.push(BoundJoin {
+                relation,
+                join_type: join.join_type,
+                on: self.bind_condition(join.on.as_ref())?,
+            });
+        }
+        Ok(Some(BoundFrom { relations, joins }))
+    }
+
+    fn relation(&mut self, table: &TableStatement) -> Result<BoundRelation, AnalyseError> {
+        if let Some(arguments) = &table.arguments {
+            return self.function_relation(table, arguments);
+        }
+        let schema_name = table
+            .schema_name
+            .as_deref()
+            .unwrap_or(self.catalog.default_schema());
+        let catalog = self.catalog;
+        let found = match catalog.table(schema_name, &table.table_name) {
+            Some(found) => found.clone(),
+            None => match catalog.view(schema_name, &table.table_name) {
+                Some(view) => return self.view_relation(table, view),
+                None => {
+                    return Err(AnalyseError::UnknownTable {
+                        table_name: qualified_name(table.schema_name.as_deref(), &table.table_name),
+                        span: table.span,
+                    })
+                }
+            },
+        };
+        Ok(BoundRelation {
+            id: self.next_relation_id(),
+            table: found,
+            function: None,
+            view: None,
+            name: table
+                .alias
+                .clone()
+                .unwrap_or_else(|| table.table_name.clone()),
+            span: table.span,
+        })
+    }
+
+    /// The relation of a table function call, its columns depend on the argument types.
+    /// The arguments are bound without the relations of the query, which they can't
+    /// reference.
+    fn function_relation(
+        &mut self,
+        table: &TableStatement,
+        arguments: &[Expr],
+    ) -> Result<BoundRelation, AnalyseError> {
+        let scopes = std::mem::take(&mut self.scopes);
+        let arguments = self.in_scope(|binder| binder.bind_exprs(arguments));
+        self.scopes = scopes;
+        let arguments = arguments?;
+        let types = argument_types(&arguments);
+        let undefined = || AnalyseError::UndefinedFunction {
+            name: table.table_name.clone(),
+            arguments: types.clone(),
+            span: table.span,
+        };
+        let function = self
+            .catalog
+            .functions()
+            .table(&table.table_name)
+            .ok_or_else(undefined)?
+            .clone();
+        let columns = function.columns(&types).map_err(|_| undefined())?;
+        Ok(BoundRelation {
+            id: self.next_relation_id(),
+            table: Table {
+                id: DERIVED_TABLE_ID,
+                schema_name: String::new(),
+                name: table.table_name.clone(),
+                columns: columns
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, (name, data_type))| Column {
+                        id: ColumnId(position as u32 + 1),
+                        name,
+                        data_type,
+                        nullable: true,
+                        default: None,
+                    })
+                    .collect(),
+                constraints: vec![],
+            },
+            function: Some(Box::new(BoundTableFunction {
+                function,
+                arguments,
+            })),
+            view: None,
+            name: table
+                .alias
+                .clone()
+                .unwrap_or_else(|| table.table_name.clone()),
+            span: table.span,
+        })
+    }
+
+    /// The relation of a view, its query is analysed on its own like a statement, it can't
+    /// reference the relations of the query which reads the view.
+    fn view_relation(
+        &mut self,
+        table: &TableStatement,
+        view: &View,
+    ) -> Result<BoundRelation, AnalyseError> {
+        let scopes = std::mem::take(&mut self.scopes);
+        let query = self.bind_select(&view.query);
+        self.scopes = scopes;
+        let mut query = BoundQuery::Select(query?);
+        check_aggregates(&query)?;
+        check_types(&mut query)?;
+        let columns = query
+            .result_columns()
+            .into_iter()
+            .enumerate()
+            .map(|(position, column)| Column {
+                id: ColumnId(position as u32 + 1),
+                name: column.name,
+                data_type: column.data_type,
+                nullable: true,
+                default: None,
+            })
+            .collect();
+        let BoundQuery::Select(query) = query else {
+            unreachable!("a view is a select");
+        };
+        Ok(BoundRelation {
+            id: self.next_relation_id(),
+            table: Table {
+                id: DERIVED_TABLE_ID,
+                schema_name: view.schema_name.clone(),
+                name: view.name.clone(),
+                columns,
+                constraints: vec![],
+            },
+            function: None,
+            view: Some(Box::new(query)),
+            name: table
+                .alias
+                .clone()
+                .unwrap_or_else(|| table.table_name.clone()),
+            span: table.span,
+        })
+    }
+
+    /// The table a statement writes to, views are read only.
+    fn target(&mut self, table: &TableStatement) -> Result<BoundRelation, AnalyseError> {
+        let relation = self.relation(table)?;
+        if relation.view.is_some() {
+            return Err(AnalyseError::ViewNotWritable {
+                view_name: qualified_name(table.schema_name.as_deref(), &table.table_name),
+                span: table.span,
+            });
+        }
+        Ok(relation)
+    }
+
+    fn next_relation_id(&mut self) -> RelationId {
+        self.relations += 1;
+        RelationId(self.relations)
+    }
+
+    /// Tables of different schemas may share a name unless one of them is aliased.
+    fn add_relation(&mut self, relation: &BoundRelation, span: Span) -> Result<(), AnalyseError> {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("relations are added inside of a scope");
+        let duplicate = scope.iter().any(|other| {
+            other.name == relation.name
+                && (is_aliased(other)
+                    || is_aliased(relation)
+                    || other.table.schema_name == relation.table.schema_name)
+        });
+        if duplicate {
+            return Err(AnalyseError::DuplicateTableName {
+                table_name: relation.name.clone(),
+                span,
+            });
+        }
+        scope.push(relation.clone());
+        Ok(())
+    }
+
+    fn bind_assignments(
+        &mut self,
+        table: &BoundRelation,
+        set_statement: &SetStatement,
+        span: Span,
+    ) -> Result<Vec<BoundAssignment>, AnalyseError> {
+        let mut assignments = vec![];
+        for assignment in &set_statement.assignments {
+            let columns = target_columns(table, &assignment.columns, span)?;
+            if let Expr::Tuple(values) = &assignment.value {
+                if columns.len() > 1 && values.len() != columns.len() {
+                    return Err(AnalyseError::ColumnCountMismatch {
+                        expected: columns.len(),
+                        found: values.len(),
+                        span,
+                    });
+                }
+            }
+            assignments.push(BoundAssignment {
+                columns,
+                value: self.bind_expr(&assignment.value)?,
+            });
+        }
+        Ok(assignments)
+    }
+
+    fn bind_projection(
+        &mut self,
+        columns: &[ColumnStatement],
+    ) -> Result<Vec<ProjectionItem>, AnalyseError> {
+        let mut projection = vec![];
+        for column in columns {
+            match column {
+                ColumnStatement::Wildcard => {
+                    let scope = self.scopes.last().map(Vec::as_slice).unwrap_or_default();
+                    if scope.is_empty() {
+                        return Err(AnalyseError::WildcardWithoutTables);
+                    }
+                    projection.extend(scope.iter().flat_map(expand));
+                }
+                ColumnStatement::QualifiedWildcard {
+                    schema_name,
+                    table_name,
+                    span,
+                } => {
+                    let scope = self.scopes.last().map(Vec::as_slice).unwrap_or_default();
+                    let relation = find_relation(scope, schema_name.as_deref(), table_name, *span)?
+                        .ok_or_else(|| AnalyseError::UnknownTable {
+                            table_name: qualified_name(schema_name.as_deref(), table_name),
+                            span: *span,
+                        })?;
+                    projection.extend(expand(relation));
+                }
+                ColumnStatement::Expr { expr, alias } => projection.push(ProjectionItem {
+                    expr: self.bind_expr(expr)?,
+                    name: alias.clone().unwrap_or_else(|| output_name(expr)),
+                }),
+            }
+        }
+        Ok(projection)
+    }
+
+    /// `group by` and `order by` may reference output columns by position or alias.
+    /// An alias shadows an input column in `order by`, but not in `group by`.
+    fn bind_output_reference(
+        &mut self,
+        expr: &Expr,
+        projection: &[ProjectionItem],
+        input_first: bool,
+    ) -> Result<BoundExpr, AnalyseError> {
+        if let Expr::Literal(Literal::Numeric(position)) = expr {
+            if let Ok(position) = position.parse::<usize>() {
+                return position
+                    .checked_sub(1)
+                    .and_then(|index| projection.get(index))
+                    .map(|item| item.expr.clone())
+                    .ok_or(AnalyseError::PositionOutOfRange { position });
+            }
+        }
+        let Expr::Column(ColumnIdentifier {
+            table_name: None,
+            column_name,
+            span,
+            ..
+        }) = expr
+        else {
+            return self.bind_expr(expr);
+        };
+        if !input_first {
+            if let Some(output) = output_column(projection, column_name, *span)? {
+                return Ok(output);
+            }
+        }
+        match self.bind_expr(expr) {
+            Err(error @ AnalyseError::UnknownColumn { .. }) if input_first => {
+                output_column(projection, column_name, *span)?.ok_or(error)
+            }
+            result => result,
+        }
+    }
+
+    fn bind_condition(
+        &mut self,
+        condition: Option<&Expr>,
+    ) -> Result<Option<BoundExpr>, AnalyseError> {
+        condition
+            .map(|condition| self.bind_expr(condition))
+            .transpose()
+    }
+
+    fn bind_exprs(&mut self, exprs: &[Expr]) -> Result<Vec<BoundExpr>, AnalyseError> {
+        exprs.iter().map(|expr| self.bind_expr(expr)).collect()
+    }
+
+    fn bind_order_by(
+        &mut self,
+        columns: &[OrderByColumn],
+    ) -> Result<Vec<(BoundExpr, Order)>, AnalyseError> {
+        let mut order_by = vec![];
+        for column in columns {
+            order_by.push((self.bind_expr(&column.expr)?, column.order));
+        }
+        Ok(order_by)
+    }
+
+    fn bind_window(&mut self, window: &Window) -> Result<BoundWindow, AnalyseError> {
+        let frame = match &window.frame {
+            Some(frame) => Some(BoundWindowFrame {
+                units: frame.units,
+                start: self.bind_frame_bound(&frame.start)?,
+                end: self.bind_frame_bound(&frame.end)?,
+            }),
+            None => None,
+        };
+        Ok(BoundWindow {
+            partition_by: self.bind_exprs(&window.partition_by)?,
+            order_by: self.bind_order_by(&window.order_by)?,
+            frame,
+        })
+    }
+
+    fn bind_frame_bound(&mut self, bound: &FrameBound) -> Result<BoundFrameBound, AnalyseError> {
+        Ok(match bound {
+            FrameBound::UnboundedPreceding => BoundFrameBound::UnboundedPreceding,
+            FrameBound::Preceding(offset) => BoundFrameBound::Preceding(self.bind_box(offset)?),
+            FrameBound::CurrentRow => BoundFrameBound::CurrentRow,
+            FrameBound::Following(offset) => BoundFrameBound::Following(self.bind_box(offset)?),
+            FrameBound::UnboundedFollowing => BoundFrameBound::UnboundedFollowing,
+        })
+    }
+
+    fn bind_box(&mut self, expr: &Expr) -> Result<Box<BoundExpr>, AnalyseError> {
+        Ok(Box::new(self.bind_expr(expr)?))
+    }
+
+    fn bind_expr(&mut self, expr: &Expr) -> Result<BoundExpr, AnalyseError> {
+        Ok(match expr {
+            Expr::Column(column) => BoundExpr::Column(self.resolve_column(column)?),
+            Expr::Literal(literal) => BoundExpr::Literal(literal.clone()),
+            Expr::TypedString { data_type, value } => BoundExpr::Cast {
+                expr: Box::new(BoundExpr::Literal(Literal::String(value.clone()))),
+                data_type: *data_type,
+            },
+            Expr::Function(function) => BoundExpr::Function(BoundFunction {
+                name: function.name.clone(),
+                function: self
+                    .catalog
+                    .functions()
+                    .lookup(&function.name, function.over.is_some()),
+                arguments: self.bind_exprs(&function.arguments)?,
+                distinct: function.distinct,
+                within_group: self.bind_order_by(&function.within_group)?,
+                over: function
+                    .over
+                    .as_ref()
+                    .map(|window| self.bind_window(window).map(Box::new))
+                    .transpose()?,
+                span: function.span,
+            }),
+            Expr::Wildcard => BoundExpr::Wildcard,
+            Expr::Default => BoundExpr::Default,
+            Expr::Parameter(parameter) => BoundExpr::Parameter(parameter.clone()),
+            Expr::Unary {
+                operator,
+                expr,
+                span,
+            } => BoundExpr::Unary {
+                operator: *operator,
+                expr: self.bind_box(expr)?,
+                span: *span,
+            },
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => BoundExpr::Binary {
+                left: self.bind_box(left)?,
+                operator: *operator,
+                right: self.bind_box(right)?,
+                span: *span,
+            },
+            Expr::IsNull { expr, negated } => BoundExpr::IsNull {
+                expr: self.bind_box(expr)?,
+                negated: *negated,
+            },
+            Expr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => BoundExpr::IsDistinctFrom {
+                left: self.bind_box(left)?,
+                right: self.bind_box(right)?,
+                negated: *negated,
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => BoundExpr::InList {
+                expr: self.bind_box(expr)?,
+                list: self.bind_exprs(list)?,
+                negated: *negated,
+            },
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => BoundExpr::InSubquery {
+                expr: self.bind_box(expr)?,
+                subquery: Box::new(self.bind_select(subquery)?),
+                negated: *negated,
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => BoundExpr::Between {
+                expr: self.bind_box(expr)?,
+                low: self.bind_box(low)?,
+                high: self.bind_box(high)?,
+                negated: *negated,
+            },
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => BoundExpr::Like {
+                expr: self.bind_box(expr)?,
+                pattern: self.bind_box(pattern)?,
+                escape: match escape {
+                    Some(escape) => Some(self.bind_box(escape)?),
+                    None => None,
+                },
+                kind: *kind,
+                negated: *negated,
+            },
+            Expr::Exists { subquery, negated } => BoundExpr::Exists {
+                subquery: Box::new(self.bind_select(subquery)?),
+                negated: *negated,
+            },
+            Expr::Subquery(subquery) => BoundExpr::Subquery(Box::new(self.bind_select(subquery)?)),
+            Expr::Tuple(exprs) => BoundExpr::Tuple(self.bind_exprs(exprs)?),
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                let operand = match operand {
+                    Some(operand) => Some(self.bind_box(operand)?),
+                    None => None,
+                };
+                let mut bound_when_then = vec![];
+                for (condition, result) in when_then {
+                    bound_when_then.push((self.bind_expr(condition)?, self.bind_expr(result)?));
+                }
+                let else_result = match else_result {
+                    Some(else_result) => Some(self.bind_box(else_result)?),
+                    None => None,
+                };
+                BoundExpr::Case {
+                    operand,
+                    when_then: bound_when_then,
+                    else_result,
+                }
+            }
+        })
+    }
+
+    /// Looks through the scopes from the innermost one, the first scope with a match wins.
+    fn resolve_column(&self, identifier: &ColumnIdentifier) -> Result<BoundColumn, AnalyseError> {
+        let ColumnIdentifier {
+            schema_name,
+            table_name,
+            column_name,
+            span,
+        } = identifier;
+        for scope in self.scopes.iter().rev() {
+            match table_name {
+                None => {
+                    let mut found = scope.iter().filter_map(|relation| {
+                        let column = relation.table.column(column_name)?;
+                        Some(relation.column(column))
+                    });
+                    if let Some(column) = found.next() {
+                        if found.next().is_some() {
+                            return Err(AnalyseError::AmbiguousColumn {
+                                column_name: column_name.clone(),
+                                span: *span,
+                            });
+                        }
+                        return Ok(BoundColumn {
+                            span: *span,
+                            ..column
+                        });
+                    }
+                }
+                Some(table_name) => {
+                    let Some(relation) =
+                        find_relation(scope, schema_name.as_deref(), table_name, *span)?
+                    else {
+                        continue;
+                    };
+                    let column = relation.table.column(column_name).ok_or_else(|| {
+                        AnalyseError::UnknownColumn {
+                            table_name: Some(qualified_name(schema_name.as_deref(), table_name)),
+                            column_name: column_name.clone(),
+                            span: *span,
+                        }
+                    })?;
+                    return Ok(BoundColumn {
+                        span: *span,
+                        ..relation.column(column)
+                    });
+                }
+            }
+        }
+        match table_name {
+            None => Err(AnalyseError::UnknownColumn {
+                table_name: None,
+                column_name: column_name.clone(),
+                span: *span,
+            }),
+            Some(table_name) => Err(AnalyseError::UnknownTable {
+                table_name: qualified_name(schema_name.as_deref(), table_name),
+                span: *span,
+            }),
+        }
+    }
+}
+
+fn is_aliased(relation: &BoundRelation) -> bool {
+    relation.name != relation.table.name
+}
+
+/// An aliased table is visible only by its alias, others also by `schema.table`.
+fn find_relation<'s>(
+    scope: &'s [BoundRelation],
+    schema_name: Option<&str>,
+    table_name: &str,
+    span: Span,
+) -> Result<Option<&'s BoundRelation>, AnalyseError> {
+    let mut found = scope.iter().filter(|relation| {
+        relation.name == table_name
+            && schema_name.is_none_or(|schema_name| {
+                !is_aliased(relation) && relation.table.schema_name == schema_name
+            })
+    });
+    let relation = found.next();
+    if relation.is_some() && found.next().is_some() {
+        return Err(AnalyseError::AmbiguousTable {
+            table_name: table_name.to_string(),
+            span,
+        });
+    }
+    Ok(relation)
+}
+
+fn target_columns(
+    table: &BoundRelation,
+    names: &[String],
+    span: Span,
+) -> Result<Vec<Column>, AnalyseError> {
+    names
+        .iter()
+        .map(|name| {
+            table
+                .table
+                .column(name)
+                .cloned()
+                .ok_or_else(|| AnalyseError::UnknownColumn {
+                    table_name: Some(table.name.clone()),
+                    column_name: name.clone(),
+                    span,
+                })
+        })
+        .collect()
+}
+
+/// Expression of the result column an `order by` key of a union names.
+fn union_order_key(expr: &Expr, projection: &[ProjectionItem]) -> Result<BoundExpr, AnalyseError> {
+    match expr {
+        Expr::Literal(Literal::Numeric(position)) => {
+            let position = position
+                .parse::<usize>()
+                .map_err(|_| AnalyseError::UnionOrderBy)?;
+            position
+                .checked_sub(1)
+                .and_then(|index| projection.get(index))
+                .map(|item| item.expr.clone())
+                .ok_or(AnalyseError::PositionOutOfRange { position })
+        }
+        Expr::Column(ColumnIdentifier {
+            table_name: None,
+            column_name,
+            span,
+            ..
+        }) => output_column(projection, column_name, *span)?.ok_or(AnalyseError::UnionOrderBy),
+        _ => Err(AnalyseError::UnionOrderBy),
+    }
+}
+
+fn output_column(
+    projection: &[ProjectionItem],
+    name: &str,
+    span: Span,
+) -> Result<Option<BoundExpr>, AnalyseError> {
+    let mut found = projection.iter().filter(|item| item.name == name);
+    let Some(item) = found.next() else {
+        return Ok(None);
+    };
+    if found.any(|other| other.expr != item.expr) {
+        return Err(AnalyseError::AmbiguousColumn {
+            column_name: name.to_string(),
+            span,
+        });
+    }
+    Ok(Some(item.expr.clone()))
+}
+
+fn expand(relation: &BoundRelation) -> impl Iterator<Item = ProjectionItem> + '_ {
+    relation.table.columns.iter().map(|column| ProjectionItem {
+        expr: BoundExpr::Column(relation.column(column)),
+        name: column.name.clone(),
+    })
+}
+
+/// Name of an output column without an alias, as PostgreSQL names it.
+fn output_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Column(column) => column.column_name.clone(),
+        Expr::Function(function) => function.name.clone(),
+        _ => "?column?".to_string(),
+    }
+}
+
+pub(crate) fn qualified_name(schema_name: Option<&str>, table_name: &str) -> String {
+    match schema_name {
+        Some(schema_name) => format!("{}.{}", schema_name, table_name),
+        None => table_name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnId, MemoryCatalog, TableId};
+    use crate::parser::expr::OperatorSpan;
+    use crate::value::DataType;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        catalog.create_table(
+            "public",
+            "users",
+            &[
+                ("id", DataType::Integer),
+                ("name", DataType::Text),
+                ("age", DataType::Integer),
+            ],
+        );
+        catalog.create_table(
+            "public",
+            "orders",
+            &[
+                ("id", DataType::Integer),
+                ("user_id", DataType::Integer),
+                ("total", DataType::Double),
+            ],
+        );
+        catalog.create_table(
+            "sales",
+            "orders",
+            &[("id", DataType::Integer), ("amount", DataType::Double)],
+        );
+        catalog
+    }
+
+    fn bind(input: &str) -> Result<BoundQuery, AnalyseError> {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        Binder::new(&catalog()).bind_query(&query)
+    }
+
+    fn bind_select(input: &str) -> BoundSelect {
+        match bind(input) {
+            Ok(BoundQuery::Select(select)) => select,
+            other => panic!("expected a select, got {:?}", other),
+        }
+    }
+
+    fn column(relation: usize, table: u32, column: u32, name: &str) -> BoundExpr {
+        BoundExpr::Column(BoundColumn {
+            relation: RelationId(relation),
+            table_id: TableId(table),
+            column_id: ColumnId(column),
+            name: name.to_string(),
+            data_type: match name {
+                "name" => DataType::Text,
+                "total" | "amount" => DataType::Double,
+                _ => DataType::Integer,
+            },
+            span: Span::default(),
+        })
+    }
+
+    fn names(projection: &[ProjectionItem]) -> Vec<&str> {
+        projection.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    fn span(error: Result<BoundQuery, AnalyseError>) -> Option<Span> {
+        error.unwrap_err().span()
+    }
+
+    #[test]
+    fn resolves_columns() {
+        let select = bind_select("select u.name, age as years, 1 from users u where id = 1");
+        assert_eq!(
+            select.projection,
+            vec![
+                ProjectionItem {
+                    expr: column(1, 1, 2, "name"),
+                    name: "name".to_string(),
+                },
+                ProjectionItem {
+                    expr: column(1, 1, 3, "age"),
+                    name: "years".to_string(),
+                },
+                ProjectionItem {
+                    expr: BoundExpr::Literal(Literal::Numeric("1".to_string())),
+                    name: "?column?".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            select.filter,
+            Some(BoundExpr::Binary {
+                left: Box::new(column(1, 1, 1, "id")),
+                operator: crate::parser::expr::Operator::Equal,
+                right: Box::new(BoundExpr::Literal(Literal::Numeric("1".to_string()))),
+                span: OperatorSpan::default(),
+            })
+        );
+
+        let select = bind_select(
+            "select public.users.name, sales.orders.amount, orders.id from users, sales.orders",
+        );
+        assert_eq!(
+            select
+                .projection
+                .iter()
+                .map(|item| &item.expr)
+                .collect::<Vec<_>>(),
+            vec![
+                &column(1, 1, 2, "name"),
+                &column(2, 3, 2, "amount"),
+                &column(2, 3, 1, "id"),
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_wildcards() {
+        let select = bind_select("select *, o.* from users join orders o on users.id = o.user_id");
+        assert_eq!(
+            names(&select.projection),
+            vec!["id", "name", "age", "id", "user_id", "total", "id", "user_id", "total"]
+        );
+        assert_eq!(select.projection[3].expr, column(2, 2, 1, "id"));
+        let select = bind_select("select public.users.* from users");
+        assert_eq!(names(&select.projection), vec!["id", "name", "age"]);
+        assert_eq!(bind("select *"), Err(AnalyseError::WildcardWithoutTables));
+    }
+
+    #[test]
+    fn reports_names_with_spans() {
+        let span_of = |start, end| Some(Span { start, end });
+        assert_eq!(span(bind("select id from users, orders")), span_of(7, 9));
+        assert_eq!(span(bind("select nme from users")), span_of(7, 10));
+        assert_eq!(span(bind("select * from userz")), span_of(14, 19));
+        assert_eq!(span(bind("select x.id from users")), span_of(7, 11));
+        assert_eq!(span(bind("select x.* from users")), span_of(7, 10));
+        assert_eq!(span(bind("select u.nope from users u")), span_of(7, 13));
+        assert_eq!(span(bind("select * from users, users")), span_of(21, 26));
+        assert_eq!(
+            bind("select users.id from users u"),
+            Err(AnalyseError::UnknownTable {
+                table_name: "users".to_string(),
+                span: Span { start: 7, end: 15 },
+            })
+        );
+        assert!(matches!(
+            bind("select orders.id from orders, sales.orders"),
+            Err(AnalyseError::AmbiguousTable { .. })
+        ));
+        // a join condition can't see tables joined later
+        assert!(matches!(
+            bind("select * from users u join orders o on o.id = p.id join orders p on true"),
+            Err(AnalyseError::UnknownTable { .. })
+        ));
+    }
+
+    #[test]
+    fn resolves_correlated_subqueries() {
+        let select = bind_select(
+            "select name from users u where exists (select 1 from orders where user_id = u.id and id = 1)",
+        );
+        let Some(BoundExpr::Exists { subquery, .. }) = select.filter else {
+            panic!("expected exists");
+        };
+        assert_eq!(
+            subquery.filter,
+            Some(BoundExpr::Binary {
+                left: Box::new(BoundExpr::Binary {
+                    left: Box::new(column(2, 2, 2, "user_id")),
+                    operator: crate::parser::expr::Operator::Equal,
+                    right: Box::new(column(1, 1, 1, "id")),
+                    span: OperatorSpan::default(),
+                }),
+                operator: crate::parser::expr::Operator::And,
+                right: Box::new(BoundExpr::Binary {
+                    left: Box::new(column(2, 2, 1, "id")),
+                    operator: crate::parser::expr::Operator::Equal,
+                    right: Box::new(BoundExpr::Literal(Literal::Numeric("1".to_string()))),
+                    span: OperatorSpan::default(),
+                }),
+                span: OperatorSpan::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn resolves_output_columns() {
+        let select = bind_select(
+            "select age as years, count(*) from users group by 1 order by years desc, 2",
+        );
+        assert_eq!(select.group_by, vec![column(1, 1, 3, "age")]);
+        assert_eq!(select.order_by[0], (column(1, 1, 3, "age"), Order::DESC));
+        assert!(matches!(select.order_by[1].0, BoundExpr::Function(_)));
+        // an input column wins in group by, an output one in order by
+        let select = bind_select("select name as age from users group by age order by age");
+        assert_eq!(select.group_by, vec![column(1, 1, 3, "age")]);
+        assert_eq!(select.order_by[0].0, column(1, 1, 2, "name"));
+        assert_eq!(
+            bind("select name from users order by 2"),
+            Err(AnalyseError::PositionOutOfRange { position: 2 })
+        );
+    }
+
+    #[test]
+    fn binds_data_modification() {
+        let Ok(BoundQuery::Insert(insert)) = bind(
+            "insert into users (id, name) values (1, 'a') \
+             on conflict (id) do update set name = excluded.name returning *",
+        ) else {
+            panic!("expected an insert");
+        };
+        assert_eq!(insert.columns.len(), 2);
+        assert_eq!(names(&insert.returning), vec!["id", "name", "age"]);
+        let Some(BoundOnConflict {
+            action: BoundConflictAction::DoUpdate { assignments, .. },
+            ..
+        }) = insert.on_conflict
+        else {
+            panic!("expected do update");
+        };
+        assert_eq!(assignments[0].value, column(2, 1, 2, "name"));
+
+        let insert_span = Some(Span { start: 12, end: 17 });
+        assert_eq!(
+            span(bind("insert into users (nope) values (1)")),
+            insert_span
+        );
+        assert_eq!(
+            bind("insert into users (id) values (1, 2)"),
+            Err(AnalyseError::ColumnCountMismatch {
+                expected: 1,
+                found: 2,
+                span: Span { start: 12, end: 17 },
+            })
+        );
+        assert!(bind("insert into users values (1)").is_ok());
+        assert!(bind("insert into users (id, age) select id, user_id from orders").is_ok());
+
+        let Ok(BoundQuery::Update(update)) = bind(
+            "update users set age = o.total from orders o where o.user_id = users.id returning users.*",
+        ) else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.assignments[0].value, column(2, 2, 3, "total"));
+        assert_eq!(names(&update.returning), vec!["id", "name", "age"]);
+        assert!(matches!(
+            bind("update users set (id, age) = (1, 2, 3)"),
+            Err(AnalyseError::ColumnCountMismatch { .. })
+        ));
+
+        let Ok(BoundQuery::Delete(delete)) =
+            bind("delete from orders using users where user_id = users.id and users.age > 1")
+        else {
+            panic!("expected a delete");
+        };
+        assert_eq!(delete.using.unwrap().relations[0].name, "users");
+        assert!(matches!(
+            bind("delete from orders using orders"),
+            Err(AnalyseError::DuplicateTableName { .. })
+        ));
+    }
+}
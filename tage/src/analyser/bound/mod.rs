@@ -0,0 +1,502 @@
+//! Bound tree is the parse tree with every name resolved against the catalog.
+//! Wildcards are expanded and every column points at a relation of the statement.
+
+use crate::analyser::aggregates::is_aggregate;
+use crate::analyser::typecheck::{call_type, function_type, literal_type};
+use crate::catalog::{Column, ColumnId, Table, TableId};
+use crate::functions::table::TableFunction;
+use crate::functions::{builtins, operator_function, FunctionRef, ScalarFunction};
+use crate::parser::expr::{
+    FrameUnits, Literal, Operator, OperatorSpan, Parameter, PatternKind, UnaryOperator,
+};
+use crate::parser::lexer::Span;
+use crate::parser::{JoinType, Order, Query};
+use crate::value::DataType;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoundQuery {
+    Select(BoundSelect),
+    Insert(BoundInsert),
+    Update(BoundUpdate),
+    Delete(BoundDelete),
+    Explain {
+        analyze: bool,
+        query: Box<BoundQuery>,
+    },
+    /// Transaction control, session variables and prepared statements, there is nothing to resolve
+    Utility(Query),
+}
+
+impl BoundQuery {
+    /// Columns of the rows the statement returns, as they are described to the client.
+    /// Columns of unknown type, e.g. `select null`, are sent as text.
+    pub fn result_columns(&self) -> Vec<ResultColumn> {
+        let projection = match self {
+            BoundQuery::Select(select) => &select.projection,
+            BoundQuery::Insert(insert) => &insert.returning,
+            BoundQuery::Update(update) => &update.returning,
+            BoundQuery::Delete(delete) => &delete.returning,
+            BoundQuery::Explain { .. } => {
+                return vec![ResultColumn {
+                    name: "QUERY PLAN".to_string(),
+                    data_type: DataType::Text,
+                }]
+            }
+            BoundQuery::Utility(_) => return vec![],
+        };
+        projection
+            .iter()
+            .map(|item| ResultColumn {
+                name: item.name.clone(),
+                data_type: item.expr.data_type().unwrap_or(DataType::Text),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResultColumn {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+/// Unique within a statement, subqueries included, so correlated columns can be told apart.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct RelationId(pub usize);
+
+/// A table as it is referenced in the statement, the same table may appear several times.
+#[derive(Debug, Clone)]
+pub struct BoundRelation {
+    pub id: RelationId,
+    pub table: Table,
+    /// Call of the table function the rows come from, `None` for a table
+    pub function: Option<Box<BoundTableFunction>>,
+    /// Query of the view the rows come from, `None` for a table
+    pub view: Option<Box<BoundSelect>>,
+    /// Alias or name of the table
+    pub name: String,
+    /// Position of the table name in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl PartialEq for BoundRelation {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.table == other.table
+            && self.function == other.function
+            && self.view == other.view
+            && self.name == other.name
+    }
+}
+
+/// Call of a table function in `from`, the table of its relation has the columns of the
+/// result.
+#[derive(Debug, Clone)]
+pub struct BoundTableFunction {
+    pub function: Arc<dyn TableFunction>,
+    pub arguments: Vec<BoundExpr>,
+}
+
+impl PartialEq for BoundTableFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.function.name() == other.function.name() && self.arguments == other.arguments
+    }
+}
+
+impl BoundRelation {
+    pub fn column(&self, column: &Column) -> BoundColumn {
+        BoundColumn {
+            relation: self.id,
+            table_id: self.table.id,
+            column_id: column.id,
+            name: column.name.clone(),
+            data_type: column.data_type,
+            span: Span::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundColumn {
+    pub relation: RelationId,
+    pub table_id: TableId,
+    pub column_id: ColumnId,
+    pub name: String,
+    pub data_type: DataType,
+    /// Position of the reference in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl PartialEq for BoundColumn {
+    fn eq(&self, other: &Self) -> bool {
+        self.relation == other.relation
+            && self.table_id == other.table_id
+            && self.column_id == other.column_id
+            && self.name == other.name
+            && self.data_type == other.data_type
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoundExpr {
+    Column(BoundColumn),
+    Literal(Literal),
+    Function(BoundFunction),
+    /// `*` inside of a function call, e.g. `count(*)`
+    Wildcard,
+    Default,
+    Parameter(Parameter),
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<BoundExpr>,
+        span: OperatorSpan,
+    },
+    Binary {
+        left: Box<BoundExpr>,
+        operator: Operator,
+        right: Box<BoundExpr>,
+        span: OperatorSpan,
+    },
+    IsNull {
+        expr: Box<BoundExpr>,
+        negated: bool,
+    },
+    IsDistinctFrom {
+        left: Box<BoundExpr>,
+        right: Box<BoundExpr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<BoundExpr>,
+        list: Vec<BoundExpr>,
+        negated: bool,
+    },
+    InSubquery {
+        expr: Box<BoundExpr>,
+        subquery: Box<BoundSelect>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<BoundExpr>,
+        low: Box<BoundExpr>,
+        high: Box<BoundExpr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<BoundExpr>,
+        pattern: Box<BoundExpr>,
+        escape: Option<Box<BoundExpr>>,
+        kind: PatternKind,
+        negated: bool,
+    },
+    Exists {
+        subquery: Box<BoundSelect>,
+        negated: bool,
+    },
+    Subquery(Box<BoundSelect>),
+    Tuple(Vec<BoundExpr>),
+    Case {
+        operand: Option<Box<BoundExpr>>,
+        when_then: Vec<(BoundExpr, BoundExpr)>,
+        else_result: Option<Box<BoundExpr>>,
+    },
+    /// Implicit cast added by the type checker
+    Cast {
+        expr: Box<BoundExpr>,
+        data_type: DataType,
+    },
+}
+
+impl BoundExpr {
+    /// Type of an expression the type checker has accepted, `None` if it is unknown,
+    /// e.g. for `NULL` or a parameter.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            BoundExpr::Column(column) => Some(column.data_type),
+            BoundExpr::Literal(literal) => literal_type(literal),
+            BoundExpr::Function(function) if function.is_scalar() => {
+                let arguments: Vec<_> = function.arguments.iter().collect();
+                call_type(function.scalar()?, &arguments)
+            }
+            BoundExpr::Function(function) => {
+                let arguments: Vec<_> = function
+                    .arguments
+                    .iter()
+                    .chain(function.within_group.iter().map(|(expr, _)| expr))
+                    .map(BoundExpr::data_type)
+                    .collect();
+                function_type(function.function.as_ref()?, &arguments)
+                    .ok()
+                    .flatten()
+            }
+            BoundExpr::Wildcard | BoundExpr::Default | BoundExpr::Parameter(_) => None,
+            BoundExpr::Tuple(_) => None,
+            BoundExpr::Unary {
+                operator: UnaryOperator::Not,
+                ..
+            } => Some(DataType::Boolean),
+            BoundExpr::Unary { expr, .. } => expr.data_type(),
+            BoundExpr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => match operator {
+                Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::Concat => {
+                    let (left_type, right_type) = (left.data_type(), right.data_type());
+                    match operator_function(*operator, left_type, right_type) {
+                        Some(name) => call_type(builtins().get(name)?, &[left, right]),
+                        // a result of decimals has no precision, see `check_binary`
+                        None => match left_type.or(right_type) {
+                            Some(DataType::Decimal(_)) => Some(DataType::Decimal(None)),
+                            data_type => data_type,
+                        },
+                    }
+                }
+                _ => Some(DataType::Boolean),
+            },
+            BoundExpr::IsNull { .. }
+            | BoundExpr::IsDistinctFrom { .. }
+            | BoundExpr::InList { .. }
+            | BoundExpr::InSubquery { .. }
+            | BoundExpr::Between { .. }
+            | BoundExpr::Like { .. }
+            | BoundExpr::Exists { .. } => Some(DataType::Boolean),
+            BoundExpr::Subquery(subquery) => subquery.projection.first()?.expr.data_type(),
+            BoundExpr::Case {
+                when_then,
+                else_result,
+                ..
+            } => when_then
+                .iter()
+                .map(|(_, result)| result)
+                .chain(else_result.as_deref())
+                .find_map(BoundExpr::data_type),
+            BoundExpr::Cast { data_type, .. } => Some(*data_type),
+        }
+    }
+
+    /// Operands of the expression, window clauses included. Subqueries are not entered.
+    pub fn children(&self) -> Vec<&BoundExpr> {
+        match self {
+            BoundExpr::Column(_)
+            | BoundExpr::Literal(_)
+            | BoundExpr::Wildcard
+            | BoundExpr::Default
+            | BoundExpr::Parameter(_)
+            | BoundExpr::Exists { .. }
+            | BoundExpr::Subquery(_) => vec![],
+            BoundExpr::Function(function) => {
+                let mut children: Vec<_> = function.arguments.iter().collect();
+                children.extend(function.within_group.iter().map(|(expr, _)| expr));
+                if let Some(window) = &function.over {
+                    children.extend(&window.partition_by);
+                    children.extend(window.order_by.iter().map(|(expr, _)| expr));
+                }
+                children
+            }
+            BoundExpr::Unary { expr, .. }
+            | BoundExpr::IsNull { expr, .. }
+            | BoundExpr::InSubquery { expr, .. }
+            | BoundExpr::Cast { expr, .. } => vec![expr],
+            BoundExpr::Binary { left, right, .. }
+            | BoundExpr::IsDistinctFrom { left, right, .. } => vec![left, right],
+            BoundExpr::InList { expr, list, .. } => std::iter::once(&**expr).chain(list).collect(),
+            BoundExpr::Between {
+                expr, low, high, ..
+            } => vec![expr, low, high],
+            BoundExpr::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => [&**expr, pattern]
+                .into_iter()
+                .chain(escape.as_deref())
+                .collect(),
+            BoundExpr::Tuple(exprs) => exprs.iter().collect(),
+            BoundExpr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => operand
+                .as_deref()
+                .into_iter()
+                .chain(when_then.iter().flat_map(|(when, then)| [when, then]))
+                .chain(else_result.as_deref())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundFunction {
+    pub name: String,
+    /// What the binder found in the registry under the name, `None` for an unknown function
+    pub function: Option<FunctionRef>,
+    pub arguments: Vec<BoundExpr>,
+    pub distinct: bool,
+    /// Ordering of an ordered-set aggregate, `WITHIN GROUP (ORDER BY ...)`
+    pub within_group: Vec<(BoundExpr, Order)>,
+    pub over: Option<Box<BoundWindow>>,
+    /// Position of the name in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl BoundFunction {
+    /// Whether the call resolves through the function registry, it is neither an
+    /// aggregate nor a window function.
+    pub fn is_scalar(&self) -> bool {
+        self.over.is_none() && !is_aggregate(self)
+    }
+
+    /// The scalar function of the registry the call refers to.
+    pub fn scalar(&self) -> Option<&Arc<ScalarFunction>> {
+        match &self.function {
+            Some(FunctionRef::Scalar(function)) if self.over.is_none() => Some(function),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for BoundFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.arguments == other.arguments
+            && self.distinct == other.distinct
+            && self.within_group == other.within_group
+            && self.over == other.over
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BoundWindow {
+    pub partition_by: Vec<BoundExpr>,
+    pub order_by: Vec<(BoundExpr, Order)>,
+    /// `None` for the default frame: from the start of the partition to the last peer of
+    /// the row, the whole partition without `ORDER BY`
+    pub frame: Option<BoundWindowFrame>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundWindowFrame {
+    pub units: FrameUnits,
+    pub start: BoundFrameBound,
+    pub end: BoundFrameBound,
+}
+
+/// Offsets are constant expressions.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoundFrameBound {
+    UnboundedPreceding,
+    Preceding(Box<BoundExpr>),
+    CurrentRow,
+    Following(Box<BoundExpr>),
+    UnboundedFollowing,
+}
+
+/// Output column of a select list or `returning`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProjectionItem {
+    pub expr: BoundExpr,
+    /// Alias, column or function name, `?column?` otherwise
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundFrom {
+    pub relations: Vec<BoundRelation>,
+    pub joins: Vec<BoundJoin>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundJoin {
+    pub relation: BoundRelation,
+    pub join_type: JoinType,
+    pub on: Option<BoundExpr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundSelect {
+    pub from: Option<BoundFrom>,
+    pub projection: Vec<ProjectionItem>,
+    pub distinct: bool,
+    pub filter: Option<BoundExpr>,
+    pub group_by: Vec<BoundExpr>,
+    pub having: Option<BoundExpr>,
+    /// Selects whose rows are added to the rows of this one, with the types of its columns
+    pub unions: Vec<BoundUnion>,
+    /// With unions, an expression of the projection
+    pub order_by: Vec<(BoundExpr, Order)>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundUnion {
+    pub all: bool,
+    pub query: BoundSelect,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundInsert {
+    pub table: BoundRelation,
+    /// All columns of the table when the statement doesn't list them
+    pub columns: Vec<Column>,
+    pub source: BoundInsertSource,
+    pub on_conflict: Option<BoundOnConflict>,
+    pub returning: Vec<ProjectionItem>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoundInsertSource {
+    Values(Vec<Vec<BoundExpr>>),
+    Select(Box<BoundSelect>),
+    DefaultValues,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundOnConflict {
+    pub columns: Vec<Column>,
+    pub action: BoundConflictAction,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoundConflictAction {
+    DoNothing,
+    /// `excluded` is the row which wasn't inserted
+    DoUpdate {
+        excluded: Box<BoundRelation>,
+        assignments: Vec<BoundAssignment>,
+        filter: Option<BoundExpr>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundAssignment {
+    pub columns: Vec<Column>,
+    pub value: BoundExpr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundUpdate {
+    pub table: BoundRelation,
+    pub assignments: Vec<BoundAssignment>,
+    pub from: Option<BoundFrom>,
+    pub filter: Option<BoundExpr>,
+    pub returning: Vec<ProjectionItem>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundDelete {
+    pub table: BoundRelation,
+    pub using: Option<BoundFrom>,
+    pub filter: Option<BoundExpr>,
+    pub returning: Vec<ProjectionItem>,
+}
@@ -0,0 +1,410 @@
+//! Checks the parse tree before it is planned.
+
+pub mod aggregates;
+pub mod binder;
+pub mod bound;
+pub mod parameters;
+pub mod typecheck;
+
+use crate::analyser::aggregates::check_aggregates;
+use crate::analyser::binder::Binder;
+use crate::analyser::bound::BoundQuery;
+use crate::analyser::typecheck::check_types;
+use crate::catalog::Catalog;
+use crate::parser::expr::Parameter;
+use crate::parser::lexer::Span;
+use crate::parser::Query;
+use crate::value::DataType;
+use std::fmt;
+
+/// Resolves the names of the query against the catalog, checks the use of aggregates and
+/// the types.
+pub fn analyse(query: &Query, catalog: &dyn Catalog) -> Result<BoundQuery, AnalyseError> {
+    let mut bound = Binder::new(catalog).bind_query(query)?;
+    check_aggregates(&bound)?;
+    check_types(&mut bound)?;
+    Ok(bound)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AnalyseError {
+    /// The parameter is used where values of different types are expected.
+    ParameterTypeConflict {
+        parameter: Parameter,
+        first: DataType,
+        second: DataType,
+    },
+    /// Neither the catalog nor the `from` list has the table.
+    UnknownTable {
+        table_name: String,
+        span: Span,
+    },
+    UnknownColumn {
+        table_name: Option<String>,
+        column_name: String,
+        span: Span,
+    },
+    /// More than one table in the scope has the column.
+    AmbiguousColumn {
+        column_name: String,
+        span: Span,
+    },
+    /// Tables of different schemas share the name used as a qualifier.
+    AmbiguousTable {
+        table_name: String,
+        span: Span,
+    },
+    /// Rows of a view can't be inserted, updated or deleted.
+    ViewNotWritable {
+        view_name: String,
+        span: Span,
+    },
+    /// The same name is given to two tables of a `from` list.
+    DuplicateTableName {
+        table_name: String,
+        span: Span,
+    },
+    /// Number of values doesn't match number of target columns.
+    ColumnCountMismatch {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    /// `select *` without `from`
+    WildcardWithoutTables,
+    /// `order by` or `group by` position is not in the select list.
+    PositionOutOfRange {
+        position: usize,
+    },
+    /// E.g. a `where` condition which isn't boolean
+    TypeMismatch {
+        expected: DataType,
+        found: DataType,
+        span: Span,
+    },
+    /// `None` is the type of `NULL` or of a parameter.
+    OperatorTypeMismatch {
+        operator: String,
+        left: Option<DataType>,
+        right: Option<DataType>,
+        span: Span,
+    },
+    UnaryOperatorTypeMismatch {
+        operator: String,
+        operand: DataType,
+        span: Span,
+    },
+    /// Values of `IN`, `BETWEEN` or `CASE` have no common type.
+    IncompatibleTypes {
+        context: &'static str,
+        first: DataType,
+        second: DataType,
+        span: Span,
+    },
+    /// String literal isn't a valid value of the type it is cast to, e.g. `'2024-02-30'`.
+    InvalidLiteral {
+        data_type: DataType,
+        literal: String,
+        span: Span,
+    },
+    /// No function of the name takes arguments of the types, `None` for an unknown type.
+    UndefinedFunction {
+        name: String,
+        arguments: Vec<Option<DataType>>,
+        span: Span,
+    },
+    /// More than one signature of the function fits the arguments equally well.
+    AmbiguousFunction {
+        name: String,
+        arguments: Vec<Option<DataType>>,
+        span: Span,
+    },
+    AmbiguousOperator {
+        operator: String,
+        left: Option<DataType>,
+        right: Option<DataType>,
+        span: Span,
+    },
+    AssignmentTypeMismatch {
+        column_name: String,
+        expected: DataType,
+        found: DataType,
+        span: Span,
+    },
+    /// A subquery used as a value returns more or less than one column.
+    SubqueryColumnCount {
+        found: usize,
+    },
+    /// The queries of a `UNION` return different numbers of columns.
+    UnionColumnCount {
+        expected: usize,
+        found: usize,
+    },
+    /// `order by` of a `UNION` which isn't a name or position of an output column.
+    UnionOrderBy,
+    /// A grouped query references a column outside of an aggregate which isn't grouped.
+    UngroupedColumn {
+        column_name: String,
+        span: Span,
+    },
+    /// E.g. an aggregate in `where`, the context is the clause
+    AggregateNotAllowed {
+        context: &'static str,
+        span: Span,
+    },
+    WindowFunctionNotAllowed {
+        context: &'static str,
+        span: Span,
+    },
+    NestedAggregate {
+        span: Span,
+    },
+    NestedWindowFunction {
+        span: Span,
+    },
+    /// A window function, e.g. `rank()`, called without `OVER`.
+    MissingOver {
+        name: String,
+        span: Span,
+    },
+    /// `OVER` after a function which is neither a window function nor an aggregate.
+    NotWindowFunction {
+        name: String,
+        span: Span,
+    },
+    /// An ordered-set aggregate, e.g. `percentile_cont`, called without `WITHIN GROUP`.
+    MissingWithinGroup {
+        name: String,
+        span: Span,
+    },
+    UnexpectedWithinGroup {
+        name: String,
+        span: Span,
+    },
+    InvalidWindowFrame {
+        reason: &'static str,
+        span: Span,
+    },
+}
+
+impl AnalyseError {
+    /// Position in the query text the error points to, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            AnalyseError::UnknownTable { span, .. }
+            | AnalyseError::UnknownColumn { span, .. }
+            | AnalyseError::AmbiguousColumn { span, .. }
+            | AnalyseError::AmbiguousTable { span, .. }
+            | AnalyseError::ViewNotWritable { span, .. }
+            | AnalyseError::DuplicateTableName { span, .. }
+            | AnalyseError::ColumnCountMismatch { span, .. }
+            | AnalyseError::TypeMismatch { span, .. }
+            | AnalyseError::OperatorTypeMismatch { span, .. }
+            | AnalyseError::UnaryOperatorTypeMismatch { span, .. }
+            | AnalyseError::IncompatibleTypes { span, .. }
+            | AnalyseError::InvalidLiteral { span, .. }
+            | AnalyseError::UndefinedFunction { span, .. }
+            | AnalyseError::AmbiguousFunction { span, .. }
+            | AnalyseError::AmbiguousOperator { span, .. }
+            | AnalyseError::AssignmentTypeMismatch { span, .. }
+            | AnalyseError::UngroupedColumn { span, .. }
+            | AnalyseError::AggregateNotAllowed { span, .. }
+            | AnalyseError::WindowFunctionNotAllowed { span, .. }
+            | AnalyseError::NestedAggregate { span }
+            | AnalyseError::NestedWindowFunction { span }
+            | AnalyseError::MissingOver { span, .. }
+            | AnalyseError::NotWindowFunction { span, .. }
+            | AnalyseError::MissingWithinGroup { span, .. }
+            | AnalyseError::UnexpectedWithinGroup { span, .. }
+            | AnalyseError::InvalidWindowFrame { span, .. } => Some(*span),
+            AnalyseError::ParameterTypeConflict { .. }
+            | AnalyseError::WildcardWithoutTables
+            | AnalyseError::PositionOutOfRange { .. }
+            | AnalyseError::SubqueryColumnCount { .. }
+            | AnalyseError::UnionColumnCount { .. }
+            | AnalyseError::UnionOrderBy => None,
+        }
+    }
+}
+
+impl fmt::Display for AnalyseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyseError::ParameterTypeConflict {
+                parameter,
+                first,
+                second,
+            } => write!(
+                f,
+                "parameter {} is used both as {} and as {}",
+                parameter, first, second
+            ),
+            AnalyseError::UnknownTable { table_name, .. } => {
+                write!(f, "table \"{}\" does not exist", table_name)
+            }
+            AnalyseError::UnknownColumn {
+                table_name: Some(table_name),
+                column_name,
+                ..
+            } => write!(
+                f,
+                "column \"{}.{}\" does not exist",
+                table_name, column_name
+            ),
+            AnalyseError::UnknownColumn { column_name, .. } => {
+                write!(f, "column \"{}\" does not exist", column_name)
+            }
+            AnalyseError::AmbiguousColumn { column_name, .. } => {
+                write!(f, "column reference \"{}\" is ambiguous", column_name)
+            }
+            AnalyseError::AmbiguousTable { table_name, .. } => {
+                write!(f, "table reference \"{}\" is ambiguous", table_name)
+            }
+            AnalyseError::ViewNotWritable { view_name, .. } => {
+                write!(f, "cannot change rows of view \"{}\"", view_name)
+            }
+            AnalyseError::DuplicateTableName { table_name, .. } => {
+                write!(f, "table name \"{}\" specified more than once", table_name)
+            }
+            AnalyseError::ColumnCountMismatch {
+                expected, found, ..
+            } => write!(f, "expected {} values, found {}", expected, found),
+            AnalyseError::WildcardWithoutTables => {
+                f.write_str("select * with no tables specified is not valid")
+            }
+            AnalyseError::PositionOutOfRange { position } => {
+                write!(f, "position {} is not in select list", position)
+            }
+            AnalyseError::TypeMismatch {
+                expected, found, ..
+            } => write!(f, "expected {}, found {}", expected, found),
+            AnalyseError::OperatorTypeMismatch {
+                operator,
+                left,
+                right,
+                ..
+            } => write!(
+                f,
+                "operator does not exist: {} {} {}",
+                type_name(left),
+                operator,
+                type_name(right)
+            ),
+            AnalyseError::UnaryOperatorTypeMismatch {
+                operator, operand, ..
+            } => write!(f, "operator does not exist: {} {}", operator, operand),
+            AnalyseError::IncompatibleTypes {
+                context,
+                first,
+                second,
+                ..
+            } => write!(
+                f,
+                "{} types {} and {} cannot be matched",
+                context, first, second
+            ),
+            AnalyseError::InvalidLiteral {
+                data_type, literal, ..
+            } => write!(
+                f,
+                "invalid input syntax for type {}: \"{}\"",
+                data_type, literal
+            ),
+            AnalyseError::UndefinedFunction {
+                name, arguments, ..
+            } => write!(
+                f,
+                "function {}({}) does not exist",
+                name,
+                type_names(arguments)
+            ),
+            AnalyseError::AmbiguousFunction {
+                name, arguments, ..
+            } => write!(
+                f,
+                "function {}({}) is not unique",
+                name,
+                type_names(arguments)
+            ),
+            AnalyseError::AmbiguousOperator {
+                operator,
+                left,
+                right,
+                ..
+            } => write!(
+                f,
+                "operator is not unique: {} {} {}",
+                type_name(left),
+                operator,
+                type_name(right)
+            ),
+            AnalyseError::AssignmentTypeMismatch {
+                column_name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "column \"{}\" is of type {} but expression is of type {}",
+                column_name, expected, found
+            ),
+            AnalyseError::SubqueryColumnCount { found } => {
+                write!(f, "subquery must return one column, it returns {}", found)
+            }
+            AnalyseError::UnionColumnCount { expected, found } => write!(
+                f,
+                "each UNION query must have the same number of columns, expected {}, found {}",
+                expected, found
+            ),
+            AnalyseError::UnionOrderBy => f.write_str(
+                "ORDER BY of a UNION can only use names or positions of the result columns",
+            ),
+            AnalyseError::UngroupedColumn { column_name, .. } => write!(
+                f,
+                "column \"{}\" must appear in the GROUP BY clause or be used in an aggregate function",
+                column_name
+            ),
+            AnalyseError::AggregateNotAllowed { context, .. } => {
+                write!(f, "aggregate functions are not allowed in {}", context)
+            }
+            AnalyseError::WindowFunctionNotAllowed { context, .. } => {
+                write!(f, "window functions are not allowed in {}", context)
+            }
+            AnalyseError::NestedAggregate { .. } => {
+                f.write_str("aggregate function calls cannot be nested")
+            }
+            AnalyseError::NestedWindowFunction { .. } => {
+                f.write_str("window function calls cannot be nested")
+            }
+            AnalyseError::MissingOver { name, .. } => {
+                write!(f, "window function {} requires an OVER clause", name)
+            }
+            AnalyseError::NotWindowFunction { name, .. } => write!(
+                f,
+                "OVER specified, but {} is not a window function nor an aggregate function",
+                name
+            ),
+            AnalyseError::MissingWithinGroup { name, .. } => {
+                write!(f, "WITHIN GROUP is required for ordered-set aggregate {}", name)
+            }
+            AnalyseError::UnexpectedWithinGroup { name, .. } => write!(
+                f,
+                "{} is not an ordered-set aggregate, so it cannot have WITHIN GROUP",
+                name
+            ),
+            AnalyseError::InvalidWindowFrame { reason, .. } => f.write_str(reason),
+        }
+    }
+}
+
+fn type_name(data_type: &Option<DataType>) -> String {
+    match data_type {
+        Some(data_type) => data_type.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn type_names(data_types: &[Option<DataType>]) -> String {
+    let names: Vec<_> = data_types.iter().map(type_name).collect();
+    names.join(", ")
+}
@@ -0,0 +1,512 @@
+//! Infers types of prepared statement parameters from the expressions they are used in,
+//! e.g. `$1` in `$1 + 1` is an integer, `:name` in `name like :name` is a string and `$1`
+//! in `id = $1` is of the type of the column. Parameters used only where any type fits
+//! (e.g. `select $1`) stay untyped.
+
+use crate::analyser::analyse;
+use crate::analyser::bound::{
+    BoundConflictAction, BoundExpr, BoundFrom, BoundInsertSource, BoundQuery, BoundSelect,
+};
+use crate::analyser::typecheck::literal_type;
+use crate::analyser::AnalyseError;
+use crate::catalog::Catalog;
+use crate::parser::expr::{Expr, Literal, Operator, Parameter, UnaryOperator};
+use crate::parser::visitor::*;
+use crate::parser::*;
+use crate::value::DataType;
+use std::collections::HashMap;
+
+/// Infers the types starting from already known ones, e.g. declared by `prepare`. The
+/// statement is analysed against the catalog, a parameter compared with or assigned to a
+/// column gets the type the type checker casts it to.
+pub fn infer_parameter_types(
+    query: &Query,
+    known: HashMap<Parameter, DataType>,
+    catalog: &dyn Catalog,
+) -> Result<HashMap<Parameter, DataType>, AnalyseError> {
+    let mut inference = Inference {
+        types: known,
+        error: None,
+    };
+    let mut casts = Casts::default();
+    casts.query(&analyse(query, catalog)?);
+    for (parameter, data_type) in casts.0 {
+        inference.expect_parameter(&parameter, data_type);
+    }
+    // a type found late in the statement may help an earlier use, e.g. `$1 = $2 and $2 = 1`
+    loop {
+        let found = inference.types.clone();
+        inference.visit_query(query);
+        if let Some(error) = inference.error {
+            return Err(error);
+        }
+        if inference.types == found {
+            return Ok(inference.types);
+        }
+    }
+}
+
+struct Inference {
+    types: HashMap<Parameter, DataType>,
+    error: Option<AnalyseError>,
+}
+
+impl Inference {
+    /// Type of the expression if it can be told without the catalog.
+    fn type_of(&self, expr: &Expr) -> Option<DataType> {
+        match expr {
+            Expr::Literal(literal @ Literal::Numeric(_)) => literal_type(literal),
+            Expr::Literal(Literal::String(_)) => Some(DataType::Text),
+            Expr::Literal(Literal::Boolean(_)) => Some(DataType::Boolean),
+            Expr::TypedString { data_type, .. } => Some(*data_type),
+            Expr::Parameter(parameter) => self.types.get(parameter).copied(),
+            Expr::Unary {
+                operator: UnaryOperator::Not,
+                ..
+            } => Some(DataType::Boolean),
+            Expr::Unary { expr, .. } => self.type_of(expr),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => match operator {
+                Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo => numeric(self.type_of(left), self.type_of(right)),
+                Operator::Concat => Some(DataType::Text),
+                _ => Some(DataType::Boolean),
+            },
+            Expr::IsNull { .. }
+            | Expr::IsDistinctFrom { .. }
+            | Expr::InList { .. }
+            | Expr::InSubquery { .. }
+            | Expr::Between { .. }
+            | Expr::Like { .. }
+            | Expr::Exists { .. } => Some(DataType::Boolean),
+            Expr::Case {
+                when_then,
+                else_result,
+                ..
+            } => self.first_type(
+                when_then
+                    .iter()
+                    .map(|(_, result)| result)
+                    .chain(else_result.as_deref()),
+            ),
+            Expr::Literal(Literal::Null)
+            | Expr::Column(_)
+            | Expr::Function(_)
+            | Expr::Wildcard
+            | Expr::Default
+            | Expr::Subquery(_)
+            | Expr::Tuple(_) => None,
+        }
+    }
+
+    fn first_type<'e>(&self, exprs: impl IntoIterator<Item = &'e Expr>) -> Option<DataType> {
+        exprs.into_iter().find_map(|expr| self.type_of(expr))
+    }
+
+    /// Records the type if the expression is a parameter.
+    fn expect(&mut self, expr: &Expr, data_type: Option<DataType>) {
+        if let (Expr::Parameter(parameter), Some(data_type)) = (expr, data_type) {
+            self.expect_parameter(parameter, data_type);
+        }
+    }
+
+    fn expect_parameter(&mut self, parameter: &Parameter, data_type: DataType) {
+        match self.types.get(parameter).copied() {
+            None => {
+                self.types.insert(parameter.clone(), data_type);
+            }
+            // e.g. an integer parameter used in decimal arithmetic becomes a decimal one
+            Some(known) => match known.common_type(data_type) {
+                Some(common) => {
+                    self.types.insert(parameter.clone(), common);
+                }
+                None => {
+                    self.error
+                        .get_or_insert(AnalyseError::ParameterTypeConflict {
+                            parameter: parameter.clone(),
+                            first: known,
+                            second: data_type,
+                        });
+                }
+            },
+        }
+    }
+
+    fn expect_all<'e>(
+        &mut self,
+        exprs: impl IntoIterator<Item = &'e Expr>,
+        data_type: Option<DataType>,
+    ) {
+        for expr in exprs {
+            self.expect(expr, data_type);
+        }
+    }
+
+    fn condition(&mut self, condition: Option<&Expr>) {
+        if let Some(condition) = condition {
+            self.expect(condition, Some(DataType::Boolean));
+        }
+    }
+}
+
+/// Result type of arithmetic, `None` unless both sides are numbers or one is unknown.
+fn numeric(left: Option<DataType>, right: Option<DataType>) -> Option<DataType> {
+    match (left, right) {
+        (Some(left), Some(right)) if left.is_numeric() && right.is_numeric() => {
+            left.common_type(right)
+        }
+        (Some(data_type), None) | (None, Some(data_type)) if data_type.is_numeric() => {
+            Some(data_type)
+        }
+        _ => None,
+    }
+}
+
+impl Visitor for Inference {
+    fn visit_select(&mut self, query: &SelectQuery) {
+        self.condition(query.where_statement.as_ref().map(|w| &w.condition));
+        self.condition(query.having_statement.as_ref().map(|h| &h.condition));
+        walk_select(self, query);
+    }
+
+    fn visit_insert(&mut self, query: &InsertQuery) {
+        if let Some(OnConflict {
+            action: ConflictAction::DoUpdate {
+                where_statement, ..
+            },
+            ..
+        }) = &query.on_conflict
+        {
+            self.condition(where_statement.as_ref().map(|w| &w.condition));
+        }
+        walk_insert(self, query);
+    }
+
+    fn visit_update(&mut self, query: &UpdateQuery) {
+        self.condition(query.where_statement.as_ref().map(|w| &w.condition));
+        walk_update(self, query);
+    }
+
+    fn visit_delete(&mut self, query: &DeleteQuery) {
+        self.condition(query.where_statement.as_ref().map(|w| &w.condition));
+        walk_delete(self, query);
+    }
+
+    fn visit_join(&mut self, join: &JoinStatement) {
+        self.condition(join.on.as_ref());
+        walk_join(self, join);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Unary {
+                operator: UnaryOperator::Not,
+                expr,
+                ..
+            } => self.expect(expr, Some(DataType::Boolean)),
+            Expr::Binary {
+                left,
+                operator: Operator::And | Operator::Or,
+                right,
+                ..
+            } => self.expect_all([&**left, &**right], Some(DataType::Boolean)),
+            Expr::Binary {
+                left,
+                operator: Operator::Concat,
+                right,
+                ..
+            } => self.expect_all([&**left, &**right], Some(DataType::Text)),
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => self.expect_all(
+                [&**expr, pattern].into_iter().chain(escape.as_deref()),
+                Some(DataType::Text),
+            ),
+            Expr::Binary { left, right, .. } | Expr::IsDistinctFrom { left, right, .. } => {
+                let left_type = self.type_of(left);
+                let right_type = self.type_of(right);
+                self.expect(left, right_type);
+                self.expect(right, left_type);
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                let exprs = [&**expr, &**low, &**high];
+                let data_type = self.first_type(exprs);
+                self.expect_all(exprs, data_type);
+            }
+            Expr::InList { expr, list, .. } => {
+                let data_type = self.first_type(std::iter::once(&**expr).chain(list));
+                self.expect_all(std::iter::once(&**expr).chain(list), data_type);
+            }
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                let conditions = when_then.iter().map(|(condition, _)| condition);
+                match operand {
+                    Some(operand) => {
+                        let operands = std::iter::once(&**operand).chain(conditions);
+                        let data_type = self.first_type(operands.clone());
+                        self.expect_all(operands, data_type);
+                    }
+                    None => self.expect_all(conditions, Some(DataType::Boolean)),
+                }
+                let data_type = self.type_of(expr);
+                let results = when_then.iter().map(|(_, result)| result);
+                self.expect_all(results.chain(else_result.as_deref()), data_type);
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Parameters the type checker cast to a type, with the type.
+#[derive(Default)]
+struct Casts(Vec<(Parameter, DataType)>);
+
+impl Casts {
+    fn query(&mut self, query: &BoundQuery) {
+        match query {
+            BoundQuery::Select(select) => self.select(select),
+            BoundQuery::Insert(insert) => {
+                match &insert.source {
+                    BoundInsertSource::Values(rows) => self.exprs(rows.iter().flatten()),
+                    BoundInsertSource::Select(select) => self.select(select),
+                    BoundInsertSource::DefaultValues => {}
+                }
+                if let Some(on_conflict) = &insert.on_conflict {
+                    if let BoundConflictAction::DoUpdate {
+                        assignments,
+                        filter,
+                        ..
+                    } = &on_conflict.action
+                    {
+                        self.exprs(assignments.iter().map(|assignment| &assignment.value));
+                        self.exprs(filter);
+                    }
+                }
+                self.exprs(insert.returning.iter().map(|item| &item.expr));
+            }
+            BoundQuery::Update(update) => {
+                self.from(update.from.as_ref());
+                self.exprs(
+                    update
+                        .assignments
+                        .iter()
+                        .map(|assignment| &assignment.value),
+                );
+                self.exprs(&update.filter);
+                self.exprs(update.returning.iter().map(|item| &item.expr));
+            }
+            BoundQuery::Delete(delete) => {
+                self.from(delete.using.as_ref());
+                self.exprs(&delete.filter);
+                self.exprs(delete.returning.iter().map(|item| &item.expr));
+            }
+            BoundQuery::Explain { query, .. } => self.query(query),
+            BoundQuery::Utility(_) => {}
+        }
+    }
+
+    fn select(&mut self, select: &BoundSelect) {
+        self.from(select.from.as_ref());
+        self.exprs(select.projection.iter().map(|item| &item.expr));
+        self.exprs(&select.filter);
+        self.exprs(&select.group_by);
+        self.exprs(&select.having);
+        self.exprs(select.order_by.iter().map(|(expr, _)| expr));
+        for union in &select.unions {
+            self.select(&union.query);
+        }
+    }
+
+    fn from(&mut self, from: Option<&BoundFrom>) {
+        let Some(from) = from else {
+            return;
+        };
+        let relations = from
+            .relations
+            .iter()
+            .chain(from.joins.iter().map(|join| &join.relation));
+        for relation in relations {
+            self.exprs(relation.function.iter().flat_map(|f| &f.arguments));
+        }
+        self.exprs(from.joins.iter().flat_map(|join| &join.on));
+    }
+
+    fn exprs<'e>(&mut self, exprs: impl IntoIterator<Item = &'e BoundExpr>) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &BoundExpr) {
+        match expr {
+            BoundExpr::Cast {
+                expr: parameter,
+                data_type,
+            } => {
+                if let BoundExpr::Parameter(parameter) = &**parameter {
+                    self.0.push((parameter.clone(), *data_type));
+                }
+            }
+            BoundExpr::InSubquery { subquery, .. }
+            | BoundExpr::Exists { subquery, .. }
+            | BoundExpr::Subquery(subquery) => self.select(subquery),
+            _ => {}
+        }
+        self.exprs(expr.children());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        catalog.create_table(
+            "public",
+            "t",
+            &[
+                ("a", DataType::Integer),
+                ("b", DataType::Integer),
+                ("c", DataType::Text),
+                ("name", DataType::Text),
+                ("born", DataType::Date),
+            ],
+        );
+        catalog.create_table("public", "s", &[("x", DataType::BigInt)]);
+        catalog
+    }
+
+    fn infer(input: &str) -> Result<HashMap<Parameter, DataType>, AnalyseError> {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        infer_parameter_types(&query, HashMap::new(), &catalog())
+    }
+
+    fn positional(types: &[(usize, DataType)]) -> HashMap<Parameter, DataType> {
+        types
+            .iter()
+            .map(|(position, data_type)| (Parameter::Positional(*position), *data_type))
+            .collect()
+    }
+
+    #[test]
+    fn infers_types_from_context() {
+        assert_eq!(
+            infer(
+                "select $1 from t join s on $2 where a = $3 + 1.5 and name like $4 \
+                 and b between 1 and $5 and c in ('x', $6) and not $7"
+            ),
+            Ok(positional(&[
+                (2, DataType::Boolean),
+                (3, DataType::Decimal(None)),
+                (4, DataType::Text),
+                (5, DataType::Integer),
+                (6, DataType::Text),
+                (7, DataType::Boolean),
+            ]))
+        );
+        assert_eq!(
+            infer("update t set a = case $1 when 'x' then $2 else 0 end where $1 = $3"),
+            Ok(positional(&[
+                (1, DataType::Text),
+                (2, DataType::Integer),
+                (3, DataType::Text),
+            ]))
+        );
+    }
+
+    #[test]
+    fn propagates_types_between_parameters() {
+        assert_eq!(
+            infer("delete from t where ? = ? and ? = 'x' and :flag"),
+            Ok(HashMap::from([
+                (Parameter::Anonymous(3), DataType::Text),
+                (Parameter::Named("flag".to_string()), DataType::Boolean),
+            ]))
+        );
+        assert_eq!(
+            infer("select * from t where $1 = $2 and $2 = 1 and $2 < 2.5"),
+            Ok(positional(&[
+                (1, DataType::Decimal(None)),
+                (2, DataType::Decimal(None))
+            ]))
+        );
+    }
+
+    #[test]
+    fn infers_types_from_the_catalog() {
+        assert_eq!(
+            infer(
+                "select * from t join s on x = $1 where a = $2 and born < $3 \
+                 and c in (select name from t where b = $4)"
+            ),
+            Ok(positional(&[
+                (1, DataType::BigInt),
+                (2, DataType::Integer),
+                (3, DataType::Date),
+                (4, DataType::Integer),
+            ]))
+        );
+        assert_eq!(
+            infer("insert into t (a, c) values ($1, $2), (1, $3)"),
+            Ok(positional(&[
+                (1, DataType::Integer),
+                (2, DataType::Text),
+                (3, DataType::Text),
+            ]))
+        );
+        assert_eq!(
+            infer("update t set b = $1 where a = $2 and $2 = $3"),
+            Ok(positional(&[
+                (1, DataType::Integer),
+                (2, DataType::Integer),
+                (3, DataType::Integer),
+            ]))
+        );
+        assert_eq!(
+            infer("select * from t where a = $1 and c = $1"),
+            Err(AnalyseError::ParameterTypeConflict {
+                parameter: Parameter::Positional(1),
+                first: DataType::Integer,
+                second: DataType::Text,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_uses() {
+        assert_eq!(
+            infer("select * from t where $1 = 1 and $1 like 'a%'"),
+            Err(AnalyseError::ParameterTypeConflict {
+                parameter: Parameter::Positional(1),
+                first: DataType::Integer,
+                second: DataType::Text,
+            })
+        );
+        let query = Queries::parse("select * from t where $1 = 1")
+            .unwrap()
+            .queries
+            .remove(0);
+        assert!(
+            infer_parameter_types(&query, positional(&[(1, DataType::Boolean)]), &catalog())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,1217 @@
+//! Works out types of the bound tree and adds implicit casts where types meet.
+//!
+//! Numbers are cast along `tinyint -> smallint -> integer -> bigint -> decimal -> real -> double`,
+//! a date to a timestamp, and a string literal to the temporal type it meets.
+//! Anything else has to match, e.g. `'abc' + 1` is an error.
+//!
+//! Calls of scalar functions, `||` and the arithmetic of dates, times and intervals
+//! resolve through the function registry, their arguments are cast to the parameter types.
+//! Aggregates and window functions are looked up in the registry as well, they take
+//! their arguments as they are.
+
+use crate::analyser::bound::*;
+use crate::analyser::AnalyseError;
+use crate::catalog::Column;
+use crate::functions::{builtins, operator_function, FunctionRef, ResolveError, ScalarFunction};
+use crate::parser::expr::{FrameUnits, Literal, Operator, UnaryOperator};
+use crate::parser::lexer::Span;
+use crate::value::{temporal, DataType};
+use std::sync::Arc;
+
+/// Checks the bound query in place, the casts it adds are `BoundExpr::Cast`.
+pub fn check_types(query: &mut BoundQuery) -> Result<(), AnalyseError> {
+    match query {
+        BoundQuery::Select(select) => check_select(select),
+        BoundQuery::Insert(insert) => check_insert(insert),
+        BoundQuery::Update(update) => check_update(update),
+        BoundQuery::Delete(delete) => check_delete(delete),
+        BoundQuery::Explain { query, .. } => check_types(query),
+        BoundQuery::Utility(_) => Ok(()),
+    }
+}
+
+/// Integers are `integer` unless they don't fit, numbers with a fraction or an exponent are decimals.
+/// String literals are `text` until they meet another type.
+pub(crate) fn literal_type(literal: &Literal) -> Option<DataType> {
+    match literal {
+        Literal::Numeric(value) if value.contains(['.', 'e', 'E']) => Some(DataType::Decimal(None)),
+        Literal::Numeric(value) => Some(if value.parse::<i32>().is_ok() {
+            DataType::Integer
+        } else if value.parse::<i64>().is_ok() {
+            DataType::BigInt
+        } else {
+            DataType::Decimal(None)
+        }),
+        Literal::String(_) => Some(DataType::Text),
+        Literal::Boolean(_) => Some(DataType::Boolean),
+        Literal::Null => None,
+    }
+}
+
+/// Result type of a call of an aggregate or a window function, scalar functions resolve
+/// through `ScalarFunction::resolve`. The argument types of an ordered-set aggregate end
+/// with the type of its `WITHIN GROUP` ordering.
+pub(crate) fn function_type(
+    function: &FunctionRef,
+    arguments: &[Option<DataType>],
+) -> Result<Option<DataType>, ResolveError> {
+    match function {
+        FunctionRef::Aggregate(function) => function.return_type(arguments),
+        FunctionRef::Window(function) => function.return_type(arguments),
+        FunctionRef::Scalar(_) => Err(ResolveError::Undefined),
+    }
+}
+
+fn check_select(select: &mut BoundSelect) -> Result<(), AnalyseError> {
+    check_from(select.from.as_mut())?;
+    expect_boolean(select.filter.as_mut())?;
+    for item in &mut select.projection {
+        check_expr(&mut item.expr)?;
+    }
+    for expr in &mut select.group_by {
+        check_expr(expr)?;
+    }
+    expect_boolean(select.having.as_mut())?;
+    for (expr, _) in &mut select.order_by {
+        check_expr(expr)?;
+    }
+    if select.unions.is_empty() {
+        return Ok(());
+    }
+    for union in &mut select.unions {
+        check_select(&mut union.query)?;
+    }
+    keeping_union_order(select, |select| {
+        for position in 0..select.projection.len() {
+            let exprs = union_column(select, position);
+            let span = exprs.iter().find_map(|expr| span_of(expr));
+            let common = unify(exprs, span.unwrap_or_default(), incompatible("UNION"))?;
+            // the `NULL`s of the column are of its type as well
+            if let Some(common) = common {
+                for expr in union_column(select, position) {
+                    if expr.data_type().is_none() {
+                        cast(expr, common);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The expressions of the column at `position` in every query of a union.
+fn union_column(select: &mut BoundSelect, position: usize) -> Vec<&mut BoundExpr> {
+    let mut exprs = vec![&mut select.projection[position].expr];
+    for union in &mut select.unions {
+        exprs.push(&mut union.query.projection[position].expr);
+    }
+    exprs
+}
+
+/// Runs `change` on the columns of a union, its `order by` keys are then set to the
+/// changed result columns they name.
+fn keeping_union_order(
+    select: &mut BoundSelect,
+    change: impl FnOnce(&mut BoundSelect) -> Result<(), AnalyseError>,
+) -> Result<(), AnalyseError> {
+    let positions: Vec<_> = select
+        .order_by
+        .iter()
+        .map(|(expr, _)| select.projection.iter().position(|item| &item.expr == expr))
+        .collect();
+    change(select)?;
+    if !select.unions.is_empty() {
+        for ((expr, _), position) in select.order_by.iter_mut().zip(positions) {
+            if let Some(position) = position {
+                *expr = select.projection[position].expr.clone();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_insert(insert: &mut BoundInsert) -> Result<(), AnalyseError> {
+    let span = insert.table.span;
+    match &mut insert.source {
+        BoundInsertSource::Values(rows) => {
+            for row in rows {
+                for (expr, column) in row.iter_mut().zip(&insert.columns) {
+                    assign(expr, column, span)?;
+                }
+            }
+        }
+        BoundInsertSource::Select(select) => {
+            check_select(select)?;
+            keeping_union_order(select, |select| {
+                let unions = select.unions.iter_mut();
+                let arms = unions.map(|union| &mut union.query.projection);
+                for projection in std::iter::once(&mut select.projection).chain(arms) {
+                    for (item, column) in projection.iter_mut().zip(&insert.columns) {
+                        assign(&mut item.expr, column, span)?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        BoundInsertSource::DefaultValues => {}
+    }
+    if let Some(BoundOnConflict {
+        action:
+            BoundConflictAction::DoUpdate {
+                assignments,
+                filter,
+                ..
+            },
+        ..
+    }) = &mut insert.on_conflict
+    {
+        check_assignments(assignments, span)?;
+        expect_boolean(filter.as_mut())?;
+    }
+    check_projection(&mut insert.returning)
+}
+
+fn check_update(update: &mut BoundUpdate) -> Result<(), AnalyseError> {
+    check_from(update.from.as_mut())?;
+    check_assignments(&mut update.assignments, update.table.span)?;
+    expect_boolean(update.filter.as_mut())?;
+    check_projection(&mut update.returning)
+}
+
+fn check_delete(delete: &mut BoundDelete) -> Result<(), AnalyseError> {
+    check_from(delete.using.as_mut())?;
+    expect_boolean(delete.filter.as_mut())?;
+    check_projection(&mut delete.returning)
+}
+
+/// Checks the arguments of the table functions and the join conditions.
+fn check_from(from: Option<&mut BoundFrom>) -> Result<(), AnalyseError> {
+    let Some(from) = from else {
+        return Ok(());
+    };
+    let relations = from
+        .relations
+        .iter_mut()
+        .chain(from.joins.iter_mut().map(|join| &mut join.relation));
+    for relation in relations {
+        for argument in relation.function.iter_mut().flat_map(|f| &mut f.arguments) {
+            check_expr(argument)?;
+        }
+    }
+    for join in &mut from.joins {
+        expect_boolean(join.on.as_mut())?;
+    }
+    Ok(())
+}
+
+fn check_projection(projection: &mut [ProjectionItem]) -> Result<(), AnalyseError> {
+    for item in projection {
+        check_expr(&mut item.expr)?;
+    }
+    Ok(())
+}
+
+/// `(a, b) = (1, 2)` and `(a, b) = (select ...)` assign values pairwise.
+fn check_assignments(assignments: &mut [BoundAssignment], span: Span) -> Result<(), AnalyseError> {
+    for assignment in assignments {
+        match (&mut assignment.value, assignment.columns.as_slice()) {
+            (BoundExpr::Tuple(values), columns) if columns.len() > 1 => {
+                for (value, column) in values.iter_mut().zip(columns) {
+                    assign(value, column, span)?;
+                }
+            }
+            (BoundExpr::Subquery(subquery), columns) if columns.len() > 1 => {
+                check_select(subquery)?;
+                for (item, column) in subquery.projection.iter_mut().zip(columns) {
+                    assign(&mut item.expr, column, span)?;
+                }
+            }
+            (value, [column]) => assign(value, column, span)?,
+            (value, _) => {
+                check_expr(value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Stores the value into a column. Any number fits a numeric column,
+/// it is rounded or checked for overflow when the value is written.
+fn assign(expr: &mut BoundExpr, column: &Column, span: Span) -> Result<(), AnalyseError> {
+    let Some(found) = check_expr(expr)? else {
+        // like in comparisons a parameter is of the type it is used as
+        if matches!(expr, BoundExpr::Parameter(_)) {
+            cast(expr, column.data_type);
+        }
+        return Ok(());
+    };
+    let expected = column.data_type;
+    let span = span_of(expr).unwrap_or(span);
+    if found == expected || (found.is_string() && expected.is_string()) {
+        return Ok(());
+    }
+    if is_string_literal(expr) && (expected.is_temporal() || expected == DataType::Interval) {
+        check_literal(expr, expected, span)?;
+    } else if !(found.is_numeric() && expected.is_numeric()
+        || found.is_temporal() && found.common_type(expected).is_some())
+    {
+        return Err(AnalyseError::AssignmentTypeMismatch {
+            column_name: column.name.clone(),
+            expected,
+            found,
+            span,
+        });
+    }
+    cast(expr, expected);
+    Ok(())
+}
+
+/// Offsets of a frame are constants, integers for `ROWS`. `RANGE` offsets are added to
+/// and subtracted from the single, numeric `ORDER BY` key.
+fn check_frame(window: &mut BoundWindow, span: Span) -> Result<(), AnalyseError> {
+    let Some(frame) = &mut window.frame else {
+        return Ok(());
+    };
+    let invalid = |reason| Err(AnalyseError::InvalidWindowFrame { reason, span });
+    match (&frame.start, &frame.end) {
+        (BoundFrameBound::CurrentRow, BoundFrameBound::Preceding(_)) => {
+            return invalid("frame starting from current row cannot have preceding rows")
+        }
+        (
+            BoundFrameBound::Following(_),
+            BoundFrameBound::Preceding(_) | BoundFrameBound::CurrentRow,
+        ) => return invalid("frame starting from following row cannot have preceding rows"),
+        _ => {}
+    }
+    for bound in [&mut frame.start, &mut frame.end] {
+        let (BoundFrameBound::Preceding(offset) | BoundFrameBound::Following(offset)) = bound
+        else {
+            continue;
+        };
+        let data_type = check_expr(offset)?;
+        if contains_column(offset) {
+            return invalid("argument of a frame offset must not contain variables");
+        }
+        match frame.units {
+            FrameUnits::Rows if !data_type.is_none_or(|t| t.is_integer()) => {
+                return invalid("argument of ROWS must be an integer")
+            }
+            FrameUnits::Rows => {}
+            FrameUnits::Range => {
+                let [(key, _)] = &window.order_by[..] else {
+                    return invalid(
+                        "RANGE with offset PRECEDING/FOLLOWING requires exactly one ORDER BY column",
+                    );
+                };
+                let numeric = |t: Option<DataType>| t.is_none_or(|t| t.is_numeric());
+                if !numeric(key.data_type()) || !numeric(data_type) {
+                    return invalid(
+                        "RANGE with offset PRECEDING/FOLLOWING is only supported for numeric columns",
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn contains_column(expr: &BoundExpr) -> bool {
+    matches!(expr, BoundExpr::Column(_)) || expr.children().into_iter().any(contains_column)
+}
+
+fn expect_boolean(expr: Option<&mut BoundExpr>) -> Result<(), AnalyseError> {
+    let Some(expr) = expr else {
+        return Ok(());
+    };
+    match check_expr(expr)? {
+        None | Some(DataType::Boolean) => Ok(()),
+        Some(found) => Err(AnalyseError::TypeMismatch {
+            expected: DataType::Boolean,
+            found,
+            span: span_of(expr).unwrap_or_default(),
+        }),
+    }
+}
+
+/// Checks the expression and returns its type, `None` if it is unknown, e.g. for `NULL`.
+fn check_expr(expr: &mut BoundExpr) -> Result<Option<DataType>, AnalyseError> {
+    let data_type = match expr {
+        BoundExpr::Column(_)
+        | BoundExpr::Literal(_)
+        | BoundExpr::Wildcard
+        | BoundExpr::Default
+        | BoundExpr::Parameter(_) => expr.data_type(),
+        BoundExpr::Function(function) => {
+            let mut arguments = vec![];
+            for argument in &mut function.arguments {
+                arguments.push(check_expr(argument)?);
+            }
+            for (expr, _) in &mut function.within_group {
+                arguments.push(check_expr(expr)?);
+            }
+            if let Some(window) = &mut function.over {
+                for expr in &mut window.partition_by {
+                    check_expr(expr)?;
+                }
+                for (expr, _) in &mut window.order_by {
+                    check_expr(expr)?;
+                }
+                check_frame(window, function.span)?;
+            }
+            let (name, span) = (&function.name, function.span);
+            let window = matches!(function.function, Some(FunctionRef::Window(_)));
+            let aggregate = match &function.function {
+                Some(FunctionRef::Aggregate(aggregate)) => Some(aggregate),
+                _ => None,
+            };
+            if window && function.over.is_none() {
+                return Err(AnalyseError::MissingOver {
+                    name: name.clone(),
+                    span,
+                });
+            }
+            if function.over.is_some() && !window && aggregate.is_none() {
+                return Err(AnalyseError::NotWindowFunction {
+                    name: name.clone(),
+                    span,
+                });
+            }
+            let ordered_set = aggregate.is_some_and(|aggregate| aggregate.ordered_set());
+            if ordered_set && function.within_group.is_empty() {
+                return Err(AnalyseError::MissingWithinGroup {
+                    name: name.clone(),
+                    span,
+                });
+            }
+            if !ordered_set && !function.within_group.is_empty() {
+                return Err(AnalyseError::UnexpectedWithinGroup {
+                    name: name.clone(),
+                    span,
+                });
+            }
+            if function.is_scalar() {
+                let scalar = function.scalar().cloned();
+                let arguments = function.arguments.iter_mut().collect();
+                check_call(
+                    scalar.as_ref(),
+                    arguments,
+                    span,
+                    |error, arguments| match error {
+                        ResolveError::Undefined => AnalyseError::UndefinedFunction {
+                            name: name.clone(),
+                            arguments,
+                            span,
+                        },
+                        ResolveError::Ambiguous => AnalyseError::AmbiguousFunction {
+                            name: name.clone(),
+                            arguments,
+                            span,
+                        },
+                    },
+                )?
+            } else {
+                let function = function.function.as_ref();
+                function
+                    .ok_or(ResolveError::Undefined)
+                    .and_then(|function| function_type(function, &arguments))
+                    .map_err(|_| AnalyseError::UndefinedFunction {
+                        name: name.clone(),
+                        arguments,
+                        span,
+                    })?
+            }
+        }
+        BoundExpr::Unary {
+            operator: UnaryOperator::Not,
+            expr,
+            ..
+        } => {
+            expect_boolean(Some(&mut **expr))?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::Unary {
+            operator,
+            expr,
+            span,
+        } => match check_expr(expr)? {
+            Some(operand) if !operand.is_numeric() => {
+                return Err(AnalyseError::UnaryOperatorTypeMismatch {
+                    operator: if *operator == UnaryOperator::Minus {
+                        "-"
+                    } else {
+                        "+"
+                    }
+                    .to_string(),
+                    operand,
+                    span: span.0,
+                })
+            }
+            operand => operand,
+        },
+        BoundExpr::Binary {
+            left,
+            operator,
+            right,
+            span,
+        } => check_binary(left, *operator, right, span.0)?,
+        BoundExpr::IsNull { expr, .. } => {
+            check_expr(expr)?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::IsDistinctFrom { left, right, .. } => {
+            check_expr(left)?;
+            check_expr(right)?;
+            let span = span_of(left).unwrap_or_default();
+            unify(
+                vec![&mut **left, &mut **right],
+                span,
+                incompatible("IS DISTINCT FROM"),
+            )?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::InList { expr, list, .. } => {
+            check_expr(expr)?;
+            for item in list.iter_mut() {
+                check_expr(item)?;
+            }
+            let span = span_of(expr).unwrap_or_default();
+            let mut exprs = vec![&mut **expr];
+            exprs.extend(list.iter_mut());
+            unify(exprs, span, incompatible("IN"))?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::InSubquery { expr, subquery, .. } => {
+            check_expr(expr)?;
+            check_select(subquery)?;
+            let span = span_of(expr).unwrap_or_default();
+            let column = single_column(subquery)?;
+            unify(vec![&mut **expr, column], span, incompatible("IN"))?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::Between {
+            expr, low, high, ..
+        } => {
+            for expr in [&mut *expr, &mut *low, &mut *high] {
+                check_expr(expr)?;
+            }
+            let span = span_of(expr).unwrap_or_default();
+            unify(
+                vec![&mut **expr, &mut **low, &mut **high],
+                span,
+                incompatible("BETWEEN"),
+            )?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::Like {
+            expr,
+            pattern,
+            escape,
+            kind,
+            negated,
+        } => {
+            let left = check_expr(expr)?;
+            let mut right = check_expr(pattern)?;
+            if let Some(escape) = escape {
+                let escape = check_expr(escape)?;
+                if !escape.is_none_or(|t| t.is_string()) {
+                    right = escape;
+                }
+            }
+            if !left.is_none_or(|t| t.is_string()) || !right.is_none_or(|t| t.is_string()) {
+                return Err(AnalyseError::OperatorTypeMismatch {
+                    operator: kind.operator(*negated).to_string(),
+                    left,
+                    right,
+                    span: span_of(expr).unwrap_or_default(),
+                });
+            }
+            Some(DataType::Boolean)
+        }
+        BoundExpr::Exists { subquery, .. } => {
+            check_select(subquery)?;
+            Some(DataType::Boolean)
+        }
+        BoundExpr::Subquery(subquery) => {
+            check_select(subquery)?;
+            check_expr(single_column(subquery)?)?
+        }
+        BoundExpr::Tuple(exprs) => {
+            for expr in exprs {
+                check_expr(expr)?;
+            }
+            None
+        }
+        BoundExpr::Case {
+            operand,
+            when_then,
+            else_result,
+        } => {
+            let span = operand
+                .as_deref()
+                .or(when_then.first().map(|(condition, _)| condition))
+                .and_then(span_of)
+                .unwrap_or_default();
+            let (mut conditions, mut results): (Vec<_>, Vec<_>) =
+                when_then.iter_mut().map(|(c, r)| (c, r)).unzip();
+            for result in &mut results {
+                check_expr(result)?;
+            }
+            match operand {
+                Some(operand) => {
+                    check_expr(operand)?;
+                    for condition in &mut conditions {
+                        check_expr(condition)?;
+                    }
+                    let mut operands = vec![&mut **operand];
+                    operands.extend(conditions);
+                    unify(operands, span, incompatible("CASE"))?;
+                }
+                None => {
+                    for condition in conditions {
+                        expect_boolean(Some(condition))?;
+                    }
+                }
+            }
+            if let Some(else_result) = else_result {
+                check_expr(else_result)?;
+                results.push(else_result);
+            }
+            unify(results, span, incompatible("CASE"))?
+        }
+        BoundExpr::Cast { expr, data_type } => {
+            check_expr(expr)?;
+            check_literal(expr, *data_type, span_of(expr).unwrap_or_default())?;
+            Some(*data_type)
+        }
+    };
+    Ok(data_type)
+}
+
+fn check_binary(
+    left: &mut BoundExpr,
+    operator: Operator,
+    right: &mut BoundExpr,
+    span: Span,
+) -> Result<Option<DataType>, AnalyseError> {
+    if matches!(operator, Operator::And | Operator::Or) {
+        expect_boolean(Some(left))?;
+        expect_boolean(Some(right))?;
+        return Ok(Some(DataType::Boolean));
+    }
+    let left_type = check_expr(left)?;
+    let right_type = check_expr(right)?;
+    if let Some(name) = operator_function(operator, left_type, right_type) {
+        return check_call(
+            builtins().get(name),
+            vec![left, right],
+            span,
+            |error, arguments| {
+                let (left, right) = (arguments[0], arguments[1]);
+                let operator = operator.to_string();
+                match error {
+                    ResolveError::Undefined => AnalyseError::OperatorTypeMismatch {
+                        operator,
+                        left,
+                        right,
+                        span,
+                    },
+                    ResolveError::Ambiguous => AnalyseError::AmbiguousOperator {
+                        operator,
+                        left,
+                        right,
+                        span,
+                    },
+                }
+            },
+        );
+    }
+    let mismatch = || AnalyseError::OperatorTypeMismatch {
+        operator: operator.to_string(),
+        left: left_type,
+        right: right_type,
+        span,
+    };
+    match operator {
+        Operator::Plus
+        | Operator::Minus
+        | Operator::Multiply
+        | Operator::Divide
+        | Operator::Modulo
+        | Operator::Concat => match (left_type, right_type) {
+            (left_type, right_type)
+                if left_type.is_none_or(|t| t.is_numeric())
+                    && right_type.is_none_or(|t| t.is_numeric()) =>
+            {
+                // like postgres, a result of decimals has no precision: its digits may
+                // outgrow those of the operands
+                Ok(
+                    match unify(vec![left, right], span, |_, _, _| mismatch())? {
+                        Some(DataType::Decimal(_)) => Some(DataType::Decimal(None)),
+                        data_type => data_type,
+                    },
+                )
+            }
+            _ => Err(mismatch()),
+        },
+        _ => {
+            unify(vec![left, right], span, |_, _, _| mismatch())?;
+            Ok(Some(DataType::Boolean))
+        }
+    }
+}
+
+/// Types of the arguments of a registry function call, a string literal is of unknown
+/// type until the call resolves.
+pub(crate) fn argument_types<'e>(
+    arguments: impl IntoIterator<Item = &'e BoundExpr>,
+) -> Vec<Option<DataType>> {
+    let argument_type = |argument: &BoundExpr| match is_string_literal(argument) {
+        true => None,
+        false => argument.data_type(),
+    };
+    arguments.into_iter().map(argument_type).collect()
+}
+
+/// Result type of a call of a scalar function, `None` if it doesn't resolve.
+pub(crate) fn call_type(
+    function: &Arc<ScalarFunction>,
+    arguments: &[&BoundExpr],
+) -> Option<DataType> {
+    let resolved = function
+        .resolve(&argument_types(arguments.iter().copied()))
+        .ok()?;
+    // e.g. `coalesce('a', NULL)`
+    let has_literal = arguments.iter().any(|argument| is_string_literal(argument));
+    resolved.returns.or(has_literal.then_some(DataType::Text))
+}
+
+/// Resolves a call of a scalar function, `None` if there is none of the name, and casts
+/// the arguments to the parameter types of the signature.
+fn check_call(
+    function: Option<&Arc<ScalarFunction>>,
+    arguments: Vec<&mut BoundExpr>,
+    span: Span,
+    error: impl FnOnce(ResolveError, Vec<Option<DataType>>) -> AnalyseError,
+) -> Result<Option<DataType>, AnalyseError> {
+    let types = argument_types(arguments.iter().map(|argument| &**argument));
+    let Some(function) = function else {
+        return Err(error(ResolveError::Undefined, types));
+    };
+    let resolved = match function.resolve(&types) {
+        Ok(resolved) => resolved,
+        Err(resolve_error) => return Err(error(resolve_error, types)),
+    };
+    let returns = call_type(
+        function,
+        &arguments
+            .iter()
+            .map(|argument| &**argument)
+            .collect::<Vec<_>>(),
+    );
+    for (argument, target) in arguments.into_iter().zip(resolved.arguments) {
+        let Some(target) = target else {
+            continue;
+        };
+        check_literal(argument, target, span_of(argument).unwrap_or(span))?;
+        if !argument
+            .data_type()
+            .is_some_and(|t| t.is_string() && target.is_string())
+        {
+            cast(argument, target);
+        }
+    }
+    Ok(returns)
+}
+
+fn incompatible(context: &'static str) -> impl Fn(DataType, DataType, Span) -> AnalyseError {
+    move |first, second, span| AnalyseError::IncompatibleTypes {
+        context,
+        first,
+        second,
+        span,
+    }
+}
+
+/// Casts the expressions, which are already checked, to their common type and returns it.
+/// String literals take the type of the temporal expressions they meet.
+fn unify(
+    mut exprs: Vec<&mut BoundExpr>,
+    span: Span,
+    mismatch: impl Fn(DataType, DataType, Span) -> AnalyseError,
+) -> Result<Option<DataType>, AnalyseError> {
+    let mut common: Option<DataType> = None;
+    for expr in exprs.iter_mut() {
+        let Some(data_type) = expr.data_type() else {
+            continue;
+        };
+        if is_string_literal(expr) {
+            continue;
+        }
+        common = match common {
+            None => Some(data_type),
+            Some(common) => Some(
+                common
+                    .common_type(data_type)
+                    .ok_or_else(|| mismatch(common, data_type, span_of(expr).unwrap_or(span)))?,
+            ),
+        };
+    }
+    let has_literal = exprs.iter().any(|expr| is_string_literal(expr));
+    let common = match common {
+        None if has_literal => return Ok(Some(DataType::Text)),
+        None => return Ok(None),
+        Some(common) => common,
+    };
+    for expr in exprs {
+        if is_string_literal(expr) {
+            if common.is_temporal() {
+                check_literal(expr, common, span)?;
+            } else if !common.is_string() {
+                return Err(mismatch(common, DataType::Text, span));
+            }
+        }
+        // a parameter is of the type it is used as, its value is cast when the statement
+        // executes
+        if matches!(expr, BoundExpr::Parameter(_))
+            || expr
+                .data_type()
+                .is_some_and(|t| t != common && !(t.is_string() && common.is_string()))
+        {
+            cast(expr, common);
+        }
+    }
+    Ok(Some(common))
+}
+
+fn cast(expr: &mut BoundExpr, data_type: DataType) {
+    if expr.data_type() == Some(data_type) {
+        return;
+    }
+    let inner = std::mem::replace(expr, BoundExpr::Wildcard);
+    *expr = BoundExpr::Cast {
+        expr: Box::new(inner),
+        data_type,
+    };
+}
+
+fn single_column(subquery: &mut BoundSelect) -> Result<&mut BoundExpr, AnalyseError> {
+    match subquery.projection.as_mut_slice() {
+        [item] => Ok(&mut item.expr),
+        projection => Err(AnalyseError::SubqueryColumnCount {
+            found: projection.len(),
+        }),
+    }
+}
+
+fn is_string_literal(expr: &BoundExpr) -> bool {
+    matches!(expr, BoundExpr::Literal(Literal::String(_)))
+}
+
+/// Checks that a string literal is a valid value of the date, time, timestamp or
+/// interval type it is cast to.
+fn check_literal(expr: &BoundExpr, data_type: DataType, span: Span) -> Result<(), AnalyseError> {
+    let BoundExpr::Literal(Literal::String(text)) = expr else {
+        return Ok(());
+    };
+    if !(data_type.is_temporal() || data_type == DataType::Interval)
+        || temporal::parse(text.trim(), data_type).is_some()
+    {
+        return Ok(());
+    }
+    Err(AnalyseError::InvalidLiteral {
+        data_type,
+        literal: text.clone(),
+        span,
+    })
+}
+
+/// Position of the expression in the query text if it is known.
+fn span_of(expr: &BoundExpr) -> Option<Span> {
+    match expr {
+        BoundExpr::Column(column) => Some(column.span),
+        BoundExpr::Function(function) => Some(function.span),
+        BoundExpr::Unary { span, .. } | BoundExpr::Binary { span, .. } => Some(span.0),
+        BoundExpr::IsNull { expr, .. }
+        | BoundExpr::InList { expr, .. }
+        | BoundExpr::InSubquery { expr, .. }
+        | BoundExpr::Between { expr, .. }
+        | BoundExpr::Like { expr, .. }
+        | BoundExpr::Cast { expr, .. }
+        | BoundExpr::IsDistinctFrom { left: expr, .. } => span_of(expr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::analyse;
+    use crate::catalog::MemoryCatalog;
+    use crate::parser::Queries;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        catalog.create_table(
+            "public",
+            "t",
+            &[
+                ("qty", DataType::SmallInt),
+                ("num", DataType::Integer),
+                ("total", DataType::BigInt),
+                ("price", DataType::Decimal(Some((10, 2)))),
+                ("ratio", DataType::Double),
+                ("name", DataType::VarChar(Some(20))),
+                ("note", DataType::Text),
+                ("born", DataType::Date),
+                ("created", DataType::Timestamp),
+                ("active", DataType::Boolean),
+            ],
+        );
+        catalog
+    }
+
+    fn check(input: &str) -> Result<BoundQuery, AnalyseError> {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        analyse(&query, &catalog())
+    }
+
+    fn result_types(input: &str) -> Vec<DataType> {
+        check(input)
+            .unwrap()
+            .result_columns()
+            .into_iter()
+            .map(|column| column.data_type)
+            .collect()
+    }
+
+    fn projection(input: &str) -> Vec<BoundExpr> {
+        match check(input) {
+            Ok(BoundQuery::Select(select)) => select
+                .projection
+                .into_iter()
+                .map(|item| item.expr)
+                .collect(),
+            other => panic!("expected a select, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn infers_result_column_types() {
+        assert_eq!(
+            result_types(
+                "select qty + num, num + total, total * price, price / ratio, 1, 3000000000, 1.5, \
+                 name || 'x', name, num > 1, -qty, born - 1, born - born, null from t"
+            ),
+            vec![
+                DataType::Integer,
+                DataType::BigInt,
+                DataType::Decimal(None),
+                DataType::Double,
+                DataType::Integer,
+                DataType::BigInt,
+                DataType::Decimal(None),
+                DataType::Text,
+                DataType::VarChar(Some(20)),
+                DataType::Boolean,
+                DataType::SmallInt,
+                DataType::Date,
+                DataType::Integer,
+                DataType::Text,
+            ]
+        );
+        assert_eq!(
+            result_types(
+                "select count(*), sum(num), sum(total), avg(num), avg(ratio), max(name), \
+                 case when active then num else total end, (select max(born) from t) from t \
+                 group by active, num, total"
+            ),
+            vec![
+                DataType::BigInt,
+                DataType::BigInt,
+                DataType::Decimal(None),
+                DataType::Decimal(None),
+                DataType::Double,
+                DataType::VarChar(Some(20)),
+                DataType::BigInt,
+                DataType::Date,
+            ]
+        );
+        assert_eq!(
+            result_types("insert into t (num) values (1) returning num, note"),
+            vec![DataType::Integer, DataType::Text]
+        );
+    }
+
+    #[test]
+    fn resolves_function_calls() {
+        assert_eq!(
+            result_types(
+                "select lower(name), length(note), abs(qty), round(price, 1), coalesce(num, total), \
+                 coalesce(born, '2024-01-01'), nullif(name, 'x'), concat(num, note), now(), \
+                 extract(year from created), date_trunc('day', born), created - born, \
+                 created + interval '1 day', born + 1 from t"
+            ),
+            vec![
+                DataType::Text,
+                DataType::Integer,
+                DataType::SmallInt,
+                DataType::Decimal(None),
+                DataType::BigInt,
+                DataType::Date,
+                DataType::VarChar(Some(20)),
+                DataType::Text,
+                DataType::Timestamp,
+                DataType::Decimal(None),
+                DataType::Timestamp,
+                DataType::Interval,
+                DataType::Timestamp,
+                DataType::Date,
+            ]
+        );
+        let [BoundExpr::Function(round), BoundExpr::Binary { right, .. }] =
+            &projection("select round(num), created - '2024-01-01' from t")[..]
+        else {
+            panic!("expected a function and a binary expression");
+        };
+        assert!(matches!(
+            round.arguments[..],
+            [BoundExpr::Cast {
+                data_type: DataType::Decimal(None),
+                ..
+            }]
+        ));
+        assert_eq!(
+            **right,
+            BoundExpr::Cast {
+                expr: Box::new(BoundExpr::Literal(Literal::String(
+                    "2024-01-01".to_string()
+                ))),
+                data_type: DataType::Timestamp,
+            }
+        );
+        assert_eq!(
+            check("select lower(num) from t"),
+            Err(AnalyseError::UndefinedFunction {
+                name: "lower".to_string(),
+                arguments: vec![Some(DataType::Integer)],
+                span: Span { start: 7, end: 12 },
+            })
+        );
+        assert_eq!(
+            check("select no_such_function('a', null)")
+                .unwrap_err()
+                .to_string(),
+            "function no_such_function(unknown, unknown) does not exist"
+        );
+        assert_eq!(
+            check("select abs(null)").unwrap_err().to_string(),
+            "function abs(unknown) is not unique"
+        );
+        assert_eq!(
+            check("select born + '1 day' from t")
+                .unwrap_err()
+                .to_string(),
+            "operator is not unique: DATE + unknown"
+        );
+        assert_eq!(
+            check("select born + interval '1 parsec' from t")
+                .unwrap_err()
+                .to_string(),
+            "invalid input syntax for type INTERVAL: \"1 parsec\""
+        );
+        assert!(check("select created * 2 from t").is_err());
+        assert!(check("select substring(note, total) from t").is_err());
+    }
+
+    #[test]
+    fn checks_aggregates_and_windows() {
+        assert_eq!(
+            result_types(
+                "select bool_and(active), string_agg(note, ','), array_agg(num), stddev(num), \
+                 variance(ratio), percentile_cont(0.5) within group (order by price), \
+                 percentile_disc(0.5) within group (order by born) from t"
+            ),
+            vec![
+                DataType::Boolean,
+                DataType::Text,
+                DataType::Text,
+                DataType::Double,
+                DataType::Double,
+                DataType::Double,
+                DataType::Date,
+            ]
+        );
+        assert_eq!(
+            result_types(
+                "select row_number() over (), ntile(2) over (order by num), \
+                 lag(name, 1, 'x') over (order by num), first_value(born) over (partition by active), \
+                 sum(num) over (order by num rows between 1 preceding and current row), \
+                 avg(price) over (order by total range between 10 preceding and 5 following) from t"
+            ),
+            vec![
+                DataType::BigInt,
+                DataType::Integer,
+                DataType::Text,
+                DataType::Date,
+                DataType::BigInt,
+                DataType::Decimal(None),
+            ]
+        );
+        let error = |input| check(input).unwrap_err().to_string();
+        assert_eq!(
+            error("select rank() from t"),
+            "window function rank requires an OVER clause"
+        );
+        assert_eq!(
+            error("select lower(note) over () from t"),
+            "OVER specified, but lower is not a window function nor an aggregate function"
+        );
+        assert_eq!(
+            error("select percentile_cont(0.5) from t"),
+            "WITHIN GROUP is required for ordered-set aggregate percentile_cont"
+        );
+        assert_eq!(
+            error("select sum(num) within group (order by num) from t"),
+            "sum is not an ordered-set aggregate, so it cannot have WITHIN GROUP"
+        );
+        assert_eq!(
+            error("select percentile_cont(0.5) within group (order by name) from t"),
+            "function percentile_cont(DECIMAL, VARCHAR(20)) does not exist"
+        );
+        assert_eq!(
+            error("select bool_or(num) from t"),
+            "function bool_or(INTEGER) does not exist"
+        );
+        assert_eq!(
+            error("select sum(num) over (rows between num preceding and current row) from t"),
+            "argument of a frame offset must not contain variables"
+        );
+        assert_eq!(
+            error("select sum(num) over (rows 1.5 preceding) from t"),
+            "argument of ROWS must be an integer"
+        );
+        assert_eq!(
+            error("select sum(num) over (range 1 preceding) from t"),
+            "RANGE with offset PRECEDING/FOLLOWING requires exactly one ORDER BY column"
+        );
+        assert_eq!(
+            error("select sum(num) over (order by name range 1 preceding) from t"),
+            "RANGE with offset PRECEDING/FOLLOWING is only supported for numeric columns"
+        );
+        assert_eq!(
+            error("select sum(num) over (rows between 1 following and current row) from t"),
+            "frame starting from following row cannot have preceding rows"
+        );
+        assert_eq!(
+            check("select ntile(num, 2) over () from t")
+                .unwrap_err()
+                .span(),
+            Some(Span { start: 7, end: 12 })
+        );
+    }
+
+    #[test]
+    fn adds_implicit_casts() {
+        let cast = |expr: BoundExpr, data_type| BoundExpr::Cast {
+            expr: Box::new(expr),
+            data_type,
+        };
+        let [BoundExpr::Binary { left, right, .. }] = &projection("select num + ratio from t")[..]
+        else {
+            panic!("expected a binary expression");
+        };
+        assert!(matches!(
+            **left,
+            BoundExpr::Cast {
+                data_type: DataType::Double,
+                ..
+            }
+        ));
+        assert!(matches!(**right, BoundExpr::Column(_)));
+
+        let [BoundExpr::Binary { right, .. }] =
+            &projection("select born < '2024-02-29' from t")[..]
+        else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(
+            **right,
+            cast(
+                BoundExpr::Literal(Literal::String("2024-02-29".to_string())),
+                DataType::Date
+            )
+        );
+
+        let Ok(BoundQuery::Insert(insert)) =
+            check("insert into t (qty, created) values (1, '2024-01-01 10:00:00')")
+        else {
+            panic!("expected an insert");
+        };
+        let BoundInsertSource::Values(rows) = insert.source else {
+            panic!("expected values");
+        };
+        assert_eq!(
+            rows[0],
+            vec![
+                cast(
+                    BoundExpr::Literal(Literal::Numeric("1".to_string())),
+                    DataType::SmallInt
+                ),
+                cast(
+                    BoundExpr::Literal(Literal::String("2024-01-01 10:00:00".to_string())),
+                    DataType::Timestamp
+                ),
+            ]
+        );
+        assert!(check("select * from t where born between '2024-01-01' and created").is_ok());
+        assert!(check("select * from t where num in (1, 2.5, total)").is_ok());
+        assert!(check("update t set (num, note) = (ratio, name) where active").is_ok());
+    }
+
+    #[test]
+    fn reports_type_errors_with_spans() {
+        assert_eq!(
+            check("select 'abc' + 1"),
+            Err(AnalyseError::OperatorTypeMismatch {
+                operator: "+".to_string(),
+                left: Some(DataType::Text),
+                right: Some(DataType::Integer),
+                span: Span { start: 13, end: 14 },
+            })
+        );
+        let span = |input| check(input).unwrap_err().span();
+        let span_of = |start, end| Some(Span { start, end });
+        assert_eq!(span("select * from t where num = name"), span_of(26, 27));
+        assert_eq!(span("select * from t where num"), span_of(22, 25));
+        assert_eq!(span("select * from t where not note"), span_of(26, 30));
+        assert_eq!(span("select -note from t"), span_of(7, 8));
+        assert_eq!(span("select sum(name) from t"), span_of(7, 10));
+        assert_eq!(
+            span("select * from t where born = '2024-02-30'"),
+            span_of(27, 28)
+        );
+        assert_eq!(span("select * from t where note like 1"), span_of(22, 26));
+        assert_eq!(
+            span("select * from t where num in (1, note)"),
+            span_of(33, 37)
+        );
+        assert_eq!(span("insert into t (born) values (1)"), span_of(12, 13));
+        assert_eq!(span("update t set num = active"), span_of(19, 25));
+        assert_eq!(
+            check("select case when active then 1 else 'x' end from t"),
+            Err(AnalyseError::IncompatibleTypes {
+                context: "CASE",
+                first: DataType::Integer,
+                second: DataType::Text,
+                span: Span { start: 17, end: 23 },
+            })
+        );
+        assert_eq!(
+            check("select * from t where num in (select num, total from t)"),
+            Err(AnalyseError::SubqueryColumnCount { found: 2 })
+        );
+        assert!(check("select active + 1 from t").is_err());
+        assert!(check("select 1 || 2").is_err());
+        assert!(check("select * from t where created > '10:00'").is_err());
+    }
+}
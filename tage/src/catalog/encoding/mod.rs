@@ -0,0 +1,464 @@
+//! Binary encoding of catalog objects, every object is stored as one value.
+//!
+//! Numbers are little endian, strings and lists are prefixed with their length, values
+//! have the encoding of rows. Types, default expressions and view queries are stored as
+//! SQL text and parsed back.
+
+use crate::catalog::statistics::{ColumnStatistics, TableStatistics};
+use crate::catalog::*;
+use crate::parser::{Parser, SelectQuery};
+use crate::value::{encoding, Value};
+
+pub trait Record: Sized {
+    fn encode(&self, encoder: &mut Encoder);
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError>;
+}
+
+pub fn encode<R: Record>(record: &R) -> Vec<u8> {
+    let mut encoder = Encoder { bytes: vec![] };
+    record.encode(&mut encoder);
+    encoder.bytes
+}
+
+pub fn decode<R: Record>(bytes: &[u8]) -> Result<R, CatalogError> {
+    let mut decoder = Decoder { bytes, position: 0 };
+    let record = R::decode(&mut decoder)?;
+    if decoder.position != bytes.len() {
+        return Err(corrupted("trailing bytes"));
+    }
+    Ok(record)
+}
+
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn i64(&mut self, value: i64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend(value.as_bytes());
+    }
+
+    pub fn list<T>(&mut self, items: &[T], mut encode: impl FnMut(&mut Self, &T)) {
+        self.u32(items.len() as u32);
+        for item in items {
+            encode(self, item);
+        }
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    fn column_ids(&mut self, columns: &[ColumnId]) {
+        self.list(columns, |encoder, column| encoder.u32(column.0));
+    }
+
+    pub fn value(&mut self, value: &Value) {
+        encoding::encode_value(&mut self.bytes, value);
+    }
+}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], CatalogError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + N)
+            .ok_or_else(|| corrupted("unexpected end of record"))?;
+        self.position += N;
+        Ok(bytes.try_into().expect("slice has N bytes"))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, CatalogError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, CatalogError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn i64(&mut self) -> Result<i64, CatalogError> {
+        self.take().map(i64::from_le_bytes)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, CatalogError> {
+        match self.take::<1>()? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(corrupted("invalid boolean")),
+        }
+    }
+
+    pub fn str(&mut self) -> Result<String, CatalogError> {
+        let length = self.u32()? as usize;
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or_else(|| corrupted("unexpected end of record"))?;
+        self.position += length;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupted("invalid string"))
+    }
+
+    pub fn list<T>(
+        &mut self,
+        mut decode: impl FnMut(&mut Self) -> Result<T, CatalogError>,
+    ) -> Result<Vec<T>, CatalogError> {
+        let length = self.u32()?;
+        (0..length).map(|_| decode(self)).collect()
+    }
+
+    pub fn f64(&mut self) -> Result<f64, CatalogError> {
+        self.u64().map(f64::from_bits)
+    }
+
+    fn column_ids(&mut self) -> Result<Vec<ColumnId>, CatalogError> {
+        self.list(|decoder| decoder.u32().map(ColumnId))
+    }
+
+    pub fn value(&mut self) -> Result<Value, CatalogError> {
+        encoding::decode_value(self.bytes, &mut self.position)
+            .map_err(|error| corrupted(&error.to_string()))
+    }
+
+    /// A value which is stored as `NULL` when there is none.
+    fn optional_value(&mut self) -> Result<Option<Value>, CatalogError> {
+        self.value().map(|value| match value {
+            Value::Null => None,
+            value => Some(value),
+        })
+    }
+
+    /// SQL text parsed with `parse`, which has to consume all of it.
+    fn sql<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Parser) -> Result<T, crate::parser::ParseError>,
+    ) -> Result<T, CatalogError> {
+        let text = self.str()?;
+        let mut parser = Parser::new(&text).map_err(|_| corrupted("invalid SQL"))?;
+        match parse(&mut parser) {
+            Ok(value) if parser.is_finished() => Ok(value),
+            _ => Err(corrupted("invalid SQL")),
+        }
+    }
+}
+
+fn corrupted(reason: &str) -> CatalogError {
+    CatalogError::Corrupted(reason.to_string())
+}
+
+impl Record for Schema {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.name);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Schema {
+            id: ObjectId(decoder.u32()?),
+            name: decoder.str()?,
+        })
+    }
+}
+
+impl Record for Column {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.name);
+        encoder.str(&self.data_type.to_string());
+        encoder.bool(self.nullable);
+        encoder.bool(self.default.is_some());
+        if let Some(default) = &self.default {
+            encoder.str(&default.to_string());
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Column {
+            id: ColumnId(decoder.u32()?),
+            name: decoder.str()?,
+            data_type: decoder.sql(DataType::parse)?,
+            nullable: decoder.bool()?,
+            default: match decoder.bool()? {
+                true => Some(decoder.sql(Expr::parse)?),
+                false => None,
+            },
+        })
+    }
+}
+
+impl Record for Constraint {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.name);
+        match &self.kind {
+            ConstraintKind::PrimaryKey(columns) => {
+                encoder.u32(0);
+                encoder.column_ids(columns);
+            }
+            ConstraintKind::Unique(columns) => {
+                encoder.u32(1);
+                encoder.column_ids(columns);
+            }
+            ConstraintKind::ForeignKey {
+                columns,
+                referenced_table,
+                referenced_columns,
+            } => {
+                encoder.u32(2);
+                encoder.column_ids(columns);
+                encoder.u32(referenced_table.0);
+                encoder.column_ids(referenced_columns);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        let id = ObjectId(decoder.u32()?);
+        let name = decoder.str()?;
+        let kind = match decoder.u32()? {
+            0 => ConstraintKind::PrimaryKey(decoder.column_ids()?),
+            1 => ConstraintKind::Unique(decoder.column_ids()?),
+            2 => ConstraintKind::ForeignKey {
+                columns: decoder.column_ids()?,
+                referenced_table: TableId(decoder.u32()?),
+                referenced_columns: decoder.column_ids()?,
+            },
+            _ => return Err(corrupted("invalid constraint kind")),
+        };
+        Ok(Constraint { id, name, kind })
+    }
+}
+
+impl Record for Table {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.schema_name);
+        encoder.str(&self.name);
+        encoder.list(&self.columns, |encoder, column| column.encode(encoder));
+        encoder.list(&self.constraints, |encoder, constraint| {
+            constraint.encode(encoder)
+        });
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Table {
+            id: TableId(decoder.u32()?),
+            schema_name: decoder.str()?,
+            name: decoder.str()?,
+            columns: decoder.list(Column::decode)?,
+            constraints: decoder.list(Constraint::decode)?,
+        })
+    }
+}
+
+impl Record for Index {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.schema_name);
+        encoder.str(&self.name);
+        encoder.u32(self.table.0);
+        encoder.column_ids(&self.columns);
+        encoder.bool(self.unique);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Index {
+            id: ObjectId(decoder.u32()?),
+            schema_name: decoder.str()?,
+            name: decoder.str()?,
+            table: TableId(decoder.u32()?),
+            columns: decoder.column_ids()?,
+            unique: decoder.bool()?,
+        })
+    }
+}
+
+impl Record for View {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.schema_name);
+        encoder.str(&self.name);
+        encoder.str(&self.query.to_string());
+        encoder.list(&self.columns, |encoder, column| column.encode(encoder));
+        encoder.list(&self.tables, |encoder, table| encoder.u32(table.0));
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(View {
+            id: ObjectId(decoder.u32()?),
+            schema_name: decoder.str()?,
+            name: decoder.str()?,
+            query: decoder.sql(SelectQuery::parse)?,
+            columns: decoder.list(Column::decode)?,
+            tables: decoder.list(|decoder| decoder.u32().map(TableId))?,
+        })
+    }
+}
+
+impl Record for Sequence {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.id.0);
+        encoder.str(&self.schema_name);
+        encoder.str(&self.name);
+        encoder.i64(self.start);
+        encoder.i64(self.increment);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Sequence {
+            id: ObjectId(decoder.u32()?),
+            schema_name: decoder.str()?,
+            name: decoder.str()?,
+            start: decoder.i64()?,
+            increment: decoder.i64()?,
+        })
+    }
+}
+
+impl Record for TableStatistics {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.table.0);
+        encoder.u64(self.row_count);
+        encoder.list(&self.columns, |encoder, column| {
+            encoder.u32(column.column.0);
+            encoder.u64(column.distinct_count);
+            encoder.f64(column.null_fraction);
+            encoder.value(column.min.as_ref().unwrap_or(&Value::Null));
+            encoder.value(column.max.as_ref().unwrap_or(&Value::Null));
+            encoder.list(&column.histogram, Encoder::value);
+        });
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(TableStatistics {
+            table: TableId(decoder.u32()?),
+            row_count: decoder.u64()?,
+            columns: decoder.list(|decoder| {
+                Ok(ColumnStatistics {
+                    column: ColumnId(decoder.u32()?),
+                    distinct_count: decoder.u64()?,
+                    null_fraction: decoder.f64()?,
+                    min: decoder.optional_value()?,
+                    max: decoder.optional_value()?,
+                    histogram: decoder.list(Decoder::value)?,
+                })
+            })?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Queries;
+
+    #[test]
+    fn round_trips_objects() {
+        let table = Table {
+            id: TableId(16384),
+            schema_name: "public".to_string(),
+            name: "users".to_string(),
+            columns: vec![
+                Column {
+                    id: ColumnId(1),
+                    name: "id".to_string(),
+                    data_type: DataType::Integer,
+                    nullable: false,
+                    default: None,
+                },
+                Column {
+                    id: ColumnId(2),
+                    name: "name".to_string(),
+                    data_type: DataType::VarChar(Some(20)),
+                    nullable: true,
+                    default: Some(Expr::string("it's")),
+                },
+            ],
+            constraints: vec![
+                Constraint {
+                    id: ObjectId(16385),
+                    name: "users_pkey".to_string(),
+                    kind: ConstraintKind::PrimaryKey(vec![ColumnId(1)]),
+                },
+                Constraint {
+                    id: ObjectId(16386),
+                    name: "users_id_fkey".to_string(),
+                    kind: ConstraintKind::ForeignKey {
+                        columns: vec![ColumnId(1)],
+                        referenced_table: TableId(16384),
+                        referenced_columns: vec![ColumnId(1)],
+                    },
+                },
+            ],
+        };
+        assert_eq!(decode::<Table>(&encode(&table)), Ok(table));
+
+        let Some(crate::parser::Query::Select(query)) = Queries::parse("select 1 + 2 as three")
+            .unwrap()
+            .queries
+            .pop()
+        else {
+            panic!("expected a select");
+        };
+        let view = View {
+            id: ObjectId(16390),
+            schema_name: "public".to_string(),
+            name: "v".to_string(),
+            query,
+            columns: vec![],
+            tables: vec![TableId(16384)],
+        };
+        assert_eq!(decode::<View>(&encode(&view)), Ok(view));
+
+        let sequence = Sequence {
+            id: ObjectId(16391),
+            schema_name: "public".to_string(),
+            name: "s".to_string(),
+            start: -5,
+            increment: 2,
+        };
+        let bytes = encode(&sequence);
+        assert_eq!(decode::<Sequence>(&bytes), Ok(sequence));
+        assert!(decode::<Sequence>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode::<Schema>(&bytes).is_err());
+
+        let statistics = TableStatistics {
+            table: TableId(16384),
+            row_count: 3,
+            columns: vec![ColumnStatistics {
+                column: ColumnId(2),
+                distinct_count: 2,
+                null_fraction: 1.0 / 3.0,
+                min: Some(Value::from("a")),
+                max: None,
+                histogram: vec![Value::Float(0.5), Value::Boolean(true), Value::Integer(-7)],
+            }],
+        };
+        assert_eq!(
+            decode::<TableStatistics>(&encode(&statistics)),
+            Ok(statistics)
+        );
+    }
+}
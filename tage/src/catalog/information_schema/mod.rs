@@ -0,0 +1,123 @@
+//! `information_schema.tables` and `information_schema.columns`, read-only tables whose rows
+//! are computed from the catalog, so tools can introspect it with plain queries.
+
+use crate::catalog::system::CatalogSnapshot;
+use crate::catalog::{Column, ColumnId, ObjectId, Table, TableId};
+use crate::value::{DataType, Value};
+use lazy_static::lazy_static;
+
+pub const SCHEMA_NAME: &str = "information_schema";
+pub const SCHEMA_ID: ObjectId = ObjectId(1);
+pub const TABLES_ID: TableId = TableId(2);
+pub const COLUMNS_ID: TableId = TableId(3);
+
+lazy_static! {
+    static ref TABLES: [Table; 2] = [
+        table(
+            TABLES_ID,
+            "tables",
+            &["table_schema", "table_name", "table_type"],
+        ),
+        table(
+            COLUMNS_ID,
+            "columns",
+            &[
+                "table_schema",
+                "table_name",
+                "column_name",
+                "ordinal_position",
+                "column_default",
+                "is_nullable",
+                "data_type",
+            ],
+        ),
+    ];
+}
+
+fn table(id: TableId, name: &str, columns: &[&str]) -> Table {
+    Table {
+        id,
+        schema_name: SCHEMA_NAME.to_string(),
+        name: name.to_string(),
+        columns: columns
+            .iter()
+            .enumerate()
+            .map(|(position, name)| Column {
+                id: ColumnId(position as u32 + 1),
+                name: name.to_string(),
+                data_type: match *name {
+                    "ordinal_position" => DataType::Integer,
+                    _ => DataType::Text,
+                },
+                nullable: true,
+                default: None,
+            })
+            .collect(),
+        constraints: vec![],
+    }
+}
+
+pub fn tables() -> &'static [Table] {
+    TABLES.as_slice()
+}
+
+/// Rows of `information_schema.tables` or `.columns`, `None` for any other table.
+pub fn rows(catalog: &CatalogSnapshot, table: TableId) -> Option<Vec<Vec<Value>>> {
+    let mut relations: Vec<(&str, &str, &str, &[Column])> = catalog
+        .tables()
+        .iter()
+        .chain(tables())
+        .map(|table| {
+            let table_type = match table.schema_name.as_str() {
+                SCHEMA_NAME => "VIEW",
+                _ => "BASE TABLE",
+            };
+            (
+                table.schema_name.as_str(),
+                table.name.as_str(),
+                table_type,
+                table.columns.as_slice(),
+            )
+        })
+        .chain(catalog.views().iter().map(|view| {
+            (
+                view.schema_name.as_str(),
+                view.name.as_str(),
+                "VIEW",
+                view.columns.as_slice(),
+            )
+        }))
+        .collect();
+    relations.sort_by_key(|(schema_name, name, ..)| (*schema_name, *name));
+
+    let text = |value: &str| Value::String(value.to_string());
+    let rows = match table {
+        TABLES_ID => relations
+            .iter()
+            .map(|(schema_name, name, table_type, _)| {
+                vec![text(schema_name), text(name), text(table_type)]
+            })
+            .collect(),
+        COLUMNS_ID => relations
+            .iter()
+            .flat_map(|(schema_name, name, _, columns)| {
+                columns.iter().enumerate().map(move |(position, column)| {
+                    vec![
+                        text(schema_name),
+                        text(name),
+                        text(&column.name),
+                        Value::Integer(position as i64 + 1),
+                        column
+                            .default
+                            .as_ref()
+                            .map_or(Value::Null, |default| text(&default.to_string())),
+                        text(if column.nullable { "YES" } else { "NO" }),
+                        text(&column.data_type.to_string().to_lowercase()),
+                    ]
+                })
+            })
+            .collect(),
+        _ => return None,
+    };
+    Some(rows)
+}
@@ -0,0 +1,380 @@
+//! Describes the tables the analyser resolves names against.
+//!
+//! `MemoryCatalog` is filled by hand, `system::SystemCatalog` is the catalog of the database
+//! which is changed by DDL statements and persisted in the storage.
+
+pub mod encoding;
+pub mod information_schema;
+pub mod statistics;
+pub mod system;
+
+use crate::analyser::AnalyseError;
+use crate::catalog::statistics::TableStatistics;
+use crate::functions::{builtins, FunctionRegistry};
+use crate::parser::expr::Expr;
+use crate::parser::{ObjectType, SelectQuery};
+use crate::storage::StorageError;
+use crate::value::{DataType, Value};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TableId(pub u32);
+
+/// Unique within its table.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct ColumnId(pub u32);
+
+/// Id of a schema, constraint, index, view or sequence. Ids of all objects, tables included,
+/// come from one counter, so they never collide and are never reused.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct ObjectId(pub u32);
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Column {
+    pub id: ColumnId,
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Table {
+    pub id: TableId,
+    pub schema_name: String,
+    pub name: String,
+    /// In the order of `select *`
+    pub columns: Vec<Column>,
+    pub constraints: Vec<Constraint>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Empty when the table has no primary key.
+    pub fn primary_key(&self) -> &[ColumnId] {
+        self.constraints
+            .iter()
+            .find_map(|constraint| match &constraint.kind {
+                ConstraintKind::PrimaryKey(columns) => Some(columns.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Constraint {
+    pub id: ObjectId,
+    /// Unique within its table
+    pub name: String,
+    pub kind: ConstraintKind,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConstraintKind {
+    PrimaryKey(Vec<ColumnId>),
+    Unique(Vec<ColumnId>),
+    ForeignKey {
+        columns: Vec<ColumnId>,
+        referenced_table: TableId,
+        referenced_columns: Vec<ColumnId>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Schema {
+    pub id: ObjectId,
+    pub name: String,
+}
+
+/// Indexes live in the schema of their table.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Index {
+    pub id: ObjectId,
+    pub schema_name: String,
+    pub name: String,
+    pub table: TableId,
+    pub columns: Vec<ColumnId>,
+    pub unique: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct View {
+    pub id: ObjectId,
+    pub schema_name: String,
+    pub name: String,
+    pub query: SelectQuery,
+    /// Output columns of the query when the view was created
+    pub columns: Vec<Column>,
+    /// Tables the query reads, they can't be dropped while the view exists
+    pub tables: Vec<TableId>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sequence {
+    pub id: ObjectId,
+    pub schema_name: String,
+    pub name: String,
+    pub start: i64,
+    pub increment: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CatalogError {
+    /// Tables, views, indexes and sequences share the names of a schema.
+    DuplicateRelation {
+        name: String,
+    },
+    DuplicateSchema {
+        name: String,
+    },
+    UnknownObject {
+        object_type: ObjectType,
+        name: String,
+    },
+    /// E.g. `drop table` of a view
+    WrongObjectType {
+        object_type: ObjectType,
+        name: String,
+    },
+    UnknownColumn {
+        table_name: String,
+        column_name: String,
+    },
+    DuplicateColumn {
+        column_name: String,
+    },
+    MultiplePrimaryKeys {
+        table_name: String,
+    },
+    /// The referenced columns of a foreign key are neither a primary key nor unique.
+    NoUniqueConstraint {
+        table_name: String,
+    },
+    ForeignKeyColumnCount,
+    DefaultReferencesColumn {
+        column_name: String,
+    },
+    ZeroIncrement,
+    /// The object is still used, e.g. a table referenced by a view.
+    DependentObjects {
+        name: String,
+        dependent: String,
+    },
+    /// Objects of `information_schema` can't be changed.
+    SystemObject {
+        name: String,
+    },
+    InvalidView(AnalyseError),
+    /// Only DDL statements change the catalog.
+    NotDdl,
+    /// Another transaction changed the catalog after this one started.
+    SerializationFailure,
+    /// Stored catalog can't be decoded.
+    Corrupted(String),
+    /// The store the catalog is kept in failed.
+    Storage(StorageError),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::DuplicateRelation { name } => {
+                write!(f, "relation \"{}\" already exists", name)
+            }
+            CatalogError::DuplicateSchema { name } => {
+                write!(f, "schema \"{}\" already exists", name)
+            }
+            CatalogError::UnknownObject { object_type, name } => write!(
+                f,
+                "{} \"{}\" does not exist",
+                object_type.to_string().to_lowercase(),
+                name
+            ),
+            CatalogError::WrongObjectType { object_type, name } => write!(
+                f,
+                "\"{}\" is not a {}",
+                name,
+                object_type.to_string().to_lowercase()
+            ),
+            CatalogError::UnknownColumn {
+                table_name,
+                column_name,
+            } => write!(
+                f,
+                "column \"{}\" of relation \"{}\" does not exist",
+                column_name, table_name
+            ),
+            CatalogError::DuplicateColumn { column_name } => {
+                write!(f, "column \"{}\" specified more than once", column_name)
+            }
+            CatalogError::MultiplePrimaryKeys { table_name } => write!(
+                f,
+                "multiple primary keys for table \"{}\" are not allowed",
+                table_name
+            ),
+            CatalogError::NoUniqueConstraint { table_name } => write!(
+                f,
+                "there is no unique constraint matching given keys for referenced table \"{}\"",
+                table_name
+            ),
+            CatalogError::ForeignKeyColumnCount => {
+                f.write_str("number of referencing and referenced columns for foreign key disagree")
+            }
+            CatalogError::DefaultReferencesColumn { column_name } => write!(
+                f,
+                "cannot use column reference \"{}\" in DEFAULT expression",
+                column_name
+            ),
+            CatalogError::ZeroIncrement => f.write_str("INCREMENT must not be zero"),
+            CatalogError::DependentObjects { name, dependent } => write!(
+                f,
+                "cannot drop \"{}\" because \"{}\" depends on it",
+                name, dependent
+            ),
+            CatalogError::SystemObject { name } => {
+                write!(f, "\"{}\" is a system object and can't be changed", name)
+            }
+            CatalogError::InvalidView(error) => write!(f, "invalid view: {}", error),
+            CatalogError::NotDdl => f.write_str("statement doesn't change the catalog"),
+            CatalogError::SerializationFailure => {
+                f.write_str("could not serialize access due to concurrent DDL")
+            }
+            CatalogError::Corrupted(reason) => write!(f, "catalog is corrupted: {}", reason),
+            CatalogError::Storage(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<StorageError> for CatalogError {
+    fn from(error: StorageError) -> Self {
+        CatalogError::Storage(error)
+    }
+}
+
+pub trait Catalog: Sync {
+    fn table(&self, schema_name: &str, table_name: &str) -> Option<&Table>;
+
+    /// A view is looked up when there is no table of the name.
+    fn view(&self, _schema_name: &str, _name: &str) -> Option<&View> {
+        None
+    }
+
+    /// Schema of the tables referenced without one.
+    fn default_schema(&self) -> &str {
+        "public"
+    }
+
+    /// Statistics of the last `analyze` of the table, `None` if it was never analyzed.
+    fn statistics(&self, _table: TableId) -> Option<&TableStatistics> {
+        None
+    }
+
+    /// Indexes of the table, the unique indexes of its constraints included.
+    fn indexes(&self, _table: TableId) -> Vec<&Index> {
+        vec![]
+    }
+
+    /// Rows of a table the catalog computes instead of storing them, e.g. of
+    /// `information_schema.tables`. `None` for a table with stored rows.
+    fn system_rows(&self, _table: TableId) -> Option<Vec<Vec<Value>>> {
+        None
+    }
+
+    /// Functions calls resolve to.
+    fn functions(&self) -> &FunctionRegistry {
+        builtins()
+    }
+}
+
+/// Catalog which lives only as long as the process, e.g. for tests.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryCatalog {
+    tables: Vec<Table>,
+    statistics: Vec<TableStatistics>,
+    last_id: u32,
+}
+
+impl MemoryCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Columns get ids in the given order.
+    pub fn create_table(
+        &mut self,
+        schema_name: &str,
+        name: &str,
+        columns: &[(&str, DataType)],
+    ) -> TableId {
+        let id = TableId(self.next_id());
+        self.tables.push(Table {
+            id,
+            schema_name: schema_name.to_string(),
+            name: name.to_string(),
+            columns: columns
+                .iter()
+                .enumerate()
+                .map(|(position, (name, data_type))| Column {
+                    id: ColumnId(position as u32 + 1),
+                    name: name.to_string(),
+                    data_type: *data_type,
+                    nullable: true,
+                    default: None,
+                })
+                .collect(),
+            constraints: vec![],
+        });
+        id
+    }
+
+    /// # Panics
+    ///
+    /// If the table or one of the columns doesn't exist.
+    pub fn set_primary_key(&mut self, table: TableId, columns: &[&str]) {
+        let id = ObjectId(self.next_id());
+        let table = self
+            .tables
+            .iter_mut()
+            .find(|candidate| candidate.id == table)
+            .expect("table exists");
+        let columns = columns
+            .iter()
+            .map(|name| table.column(name).expect("column exists").id)
+            .collect();
+        table.constraints.push(Constraint {
+            id,
+            name: format!("{}_pkey", table.name),
+            kind: ConstraintKind::PrimaryKey(columns),
+        });
+    }
+
+    /// Replaces the statistics of their table.
+    pub fn set_statistics(&mut self, statistics: TableStatistics) {
+        self.statistics
+            .retain(|existing| existing.table != statistics.table);
+        self.statistics.push(statistics);
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
+    }
+}
+
+impl Catalog for MemoryCatalog {
+    fn table(&self, schema_name: &str, table_name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.schema_name == schema_name && table.name == table_name)
+    }
+
+    fn statistics(&self, table: TableId) -> Option<&TableStatistics> {
+        self.statistics
+            .iter()
+            .find(|statistics| statistics.table == table)
+    }
+}
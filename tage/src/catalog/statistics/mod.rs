@@ -0,0 +1,206 @@
+//! Statistics of the rows of a table, the optimizer estimates the sizes of results with them.
+//!
+//! `analyze` gathers them from all rows of the table and stores them in the catalog. They
+//! describe the table as it was at that time and are replaced by the next `analyze`.
+
+use crate::catalog::{ColumnId, Table, TableId};
+use crate::value::Value;
+use std::cmp::Ordering;
+
+/// Number of buckets of a histogram.
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableStatistics {
+    pub table: TableId,
+    pub row_count: u64,
+    /// In the order of the columns of the table
+    pub columns: Vec<ColumnStatistics>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColumnStatistics {
+    pub column: ColumnId,
+    /// Number of different values, `NULL` isn't counted
+    pub distinct_count: u64,
+    /// Fraction of the rows where the column is `NULL`
+    pub null_fraction: f64,
+    /// `None` when all values are `NULL`
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// Bounds of an equi-depth histogram of the values which aren't `NULL`: bucket `i`
+    /// holds the values from `histogram[i]` to `histogram[i + 1]` and all buckets hold
+    /// about the same number of values. A value which is very common bounds several
+    /// buckets. Empty when all values are `NULL`.
+    pub histogram: Vec<Value>,
+}
+
+impl TableStatistics {
+    /// Statistics of the rows, which have a value for every column of the table.
+    pub fn gather(table: &Table, rows: impl IntoIterator<Item = Vec<Value>>) -> Self {
+        let mut values: Vec<Vec<Value>> = vec![vec![]; table.columns.len()];
+        let mut row_count = 0;
+        for row in rows {
+            row_count += 1;
+            for (column, value) in values.iter_mut().zip(row) {
+                column.push(value);
+            }
+        }
+        let columns = table
+            .columns
+            .iter()
+            .zip(values)
+            .map(|(column, values)| ColumnStatistics::gather(column.id, row_count, values))
+            .collect();
+        TableStatistics {
+            table: table.id,
+            row_count,
+            columns,
+        }
+    }
+
+    pub fn column(&self, column: ColumnId) -> Option<&ColumnStatistics> {
+        self.columns
+            .iter()
+            .find(|statistics| statistics.column == column)
+    }
+}
+
+impl ColumnStatistics {
+    fn gather(column: ColumnId, row_count: u64, values: Vec<Value>) -> Self {
+        let mut values: Vec<_> = values
+            .into_iter()
+            .filter(|value| value != &Value::Null)
+            .collect();
+        values.sort_by(|left, right| left.partial_cmp(right).unwrap_or(Ordering::Equal));
+        let distinct_count = match values.first() {
+            Some(_) => 1 + values.windows(2).filter(|pair| pair[0] != pair[1]).count(),
+            None => 0,
+        };
+        let null_fraction = match row_count {
+            0 => 0.0,
+            rows => (rows - values.len() as u64) as f64 / rows as f64,
+        };
+        let histogram = match values.len() {
+            0 => vec![],
+            count => (0..=HISTOGRAM_BUCKETS)
+                .map(|bucket| values[bucket * (count - 1) / HISTOGRAM_BUCKETS].clone())
+                .collect(),
+        };
+        ColumnStatistics {
+            column,
+            distinct_count: distinct_count as u64,
+            null_fraction,
+            min: values.first().cloned(),
+            max: values.last().cloned(),
+            histogram,
+        }
+    }
+
+    /// Estimated fraction of the values which aren't `NULL` and are less than the value,
+    /// `None` if the value can't be compared with the values of the column.
+    pub fn fraction_below(&self, value: &Value) -> Option<f64> {
+        let (first, last) = (self.histogram.first()?, self.histogram.last()?);
+        // values of different kinds are ordered, but not by what they mean
+        value
+            .data_type()
+            .zip(first.data_type())
+            .and_then(|(value, first)| value.common_type(first))?;
+        if value.partial_cmp(first)? != Ordering::Greater {
+            return Some(0.0);
+        }
+        if value.partial_cmp(last)? == Ordering::Greater {
+            return Some(1.0);
+        }
+        let buckets = self.histogram.len() - 1;
+        // the first bucket whose upper bound isn't below the value holds it
+        let bucket = self.histogram[1..]
+            .iter()
+            .position(|bound| bound >= value)
+            .unwrap_or(buckets - 1);
+        let (low, high) = (&self.histogram[bucket], &self.histogram[bucket + 1]);
+        let within = match (number(low), number(high), number(value)) {
+            (Some(low), Some(high), Some(value)) if high > low => (value - low) / (high - low),
+            _ => 0.5,
+        };
+        Some((bucket as f64 + within) / buckets as f64)
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Float(value) => Some(*value),
+        Value::Decimal(value) => Some(value.to_f64()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Catalog, MemoryCatalog};
+    use crate::value::DataType;
+
+    #[test]
+    fn gathers_statistics() {
+        let mut catalog = MemoryCatalog::new();
+        catalog.create_table(
+            "public",
+            "t",
+            &[("id", DataType::Integer), ("tag", DataType::Text)],
+        );
+        let table = catalog.table("public", "t").unwrap();
+        let rows = (0..100).map(|id| {
+            let tag = match id % 4 {
+                0 => Value::Null,
+                tag => Value::String(format!("t{}", tag)),
+            };
+            vec![Value::Integer(id), tag]
+        });
+        let statistics = TableStatistics::gather(table, rows);
+        assert_eq!(statistics.row_count, 100);
+
+        let id = statistics.column(ColumnId(1)).unwrap();
+        assert_eq!(id.distinct_count, 100);
+        assert_eq!(id.null_fraction, 0.0);
+        assert_eq!(
+            (id.min.clone(), id.max.clone()),
+            (Some(Value::Integer(0)), Some(Value::Integer(99)))
+        );
+        assert_eq!(id.histogram.len(), HISTOGRAM_BUCKETS + 1);
+        assert_eq!(id.histogram[8], Value::Integer(49));
+
+        let tag = statistics.column(ColumnId(2)).unwrap();
+        assert_eq!(tag.distinct_count, 3);
+        assert_eq!(tag.null_fraction, 0.25);
+        assert_eq!(tag.min, Some(Value::from("t1")));
+
+        let empty = TableStatistics::gather(table, vec![]);
+        assert_eq!(empty.row_count, 0);
+        assert_eq!(empty.columns[0].min, None);
+        assert!(empty.columns[0].histogram.is_empty());
+    }
+
+    #[test]
+    fn estimates_fractions_from_histogram() {
+        let statistics =
+            ColumnStatistics::gather(ColumnId(1), 1000, (0..1000).map(Value::Integer).collect());
+        let below = |value: Value| statistics.fraction_below(&value).unwrap();
+        assert_eq!(below(Value::Integer(-5)), 0.0);
+        assert_eq!(below(Value::Integer(5000)), 1.0);
+        assert!((below(Value::Integer(250)) - 0.25).abs() < 0.01);
+        assert!((below(Value::Float(900.5)) - 0.9).abs() < 0.01);
+        assert_eq!(statistics.fraction_below(&Value::from("a")), None);
+
+        // skewed values fill most buckets with the common value
+        let skewed = ColumnStatistics::gather(
+            ColumnId(1),
+            100,
+            (0..100)
+                .map(|i| Value::Integer(if i < 90 { 1 } else { i }))
+                .collect(),
+        );
+        assert!(skewed.fraction_below(&Value::Integer(2)).unwrap() > 0.8);
+    }
+}
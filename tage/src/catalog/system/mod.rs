@@ -0,0 +1,1344 @@
+//! Catalog of the database, changed by DDL statements and persisted in the storage.
+//!
+//! Every committed state of the catalog is an immutable `CatalogSnapshot` with a version.
+//! A transaction changes a private copy of the snapshot it started from, and its commit
+//! replaces the current snapshot unless another transaction committed DDL in the meantime.
+//! Queries keep reading the snapshot they started with.
+//!
+//! Each object is stored under `catalog/<kind>/<id>`, a commit writes only the objects
+//! which changed.
+
+use crate::analyser::analyse;
+use crate::analyser::bound::{BoundExpr, BoundQuery, BoundSelect};
+use crate::catalog::encoding::{decode, encode, Decoder, Encoder, Record};
+use crate::catalog::information_schema::{self, SCHEMA_ID, SCHEMA_NAME};
+use crate::catalog::statistics::TableStatistics;
+use crate::catalog::*;
+use crate::parser::expr::ColumnIdentifier;
+use crate::parser::visitor::Visitor;
+use crate::parser::{
+    AnalyzeQuery, ColumnConstraint, CreateIndexQuery, CreateSchemaQuery, CreateSequenceQuery,
+    CreateTableQuery, CreateViewQuery, DropQuery, ObjectName, Query, TableConstraint,
+    TableConstraintKind,
+};
+use crate::storage::{KvStore, StorageError};
+use crate::value::Value;
+use std::sync::Arc;
+
+pub const PUBLIC_SCHEMA_ID: ObjectId = ObjectId(4);
+/// Ids below are reserved for the built-in objects.
+const FIRST_OBJECT_ID: u32 = 16384;
+
+const META_KEY: &[u8] = b"catalog/meta";
+const KEY_PREFIX: &[u8] = b"catalog/";
+/// First key after all keys starting with `catalog/`
+const KEY_PREFIX_END: &[u8] = b"catalog0";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatalogSnapshot {
+    version: u64,
+    next_id: u32,
+    schemas: Vec<Schema>,
+    tables: Vec<Table>,
+    indexes: Vec<Index>,
+    views: Vec<View>,
+    sequences: Vec<Sequence>,
+    statistics: Vec<TableStatistics>,
+}
+
+impl CatalogSnapshot {
+    fn empty() -> Self {
+        CatalogSnapshot {
+            version: 0,
+            next_id: FIRST_OBJECT_ID,
+            schemas: vec![],
+            tables: vec![],
+            indexes: vec![],
+            views: vec![],
+            sequences: vec![],
+            statistics: vec![],
+        }
+    }
+
+    /// Catalog of a new database, it has only the built-in schemas.
+    fn bootstrap() -> Self {
+        let mut snapshot = Self::empty();
+        snapshot.schemas = vec![
+            Schema {
+                id: SCHEMA_ID,
+                name: SCHEMA_NAME.to_string(),
+            },
+            Schema {
+                id: PUBLIC_SCHEMA_ID,
+                name: "public".to_string(),
+            },
+        ];
+        snapshot
+    }
+
+    /// Incremented by every commit which changes the catalog.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn schemas(&self) -> &[Schema] {
+        &self.schemas
+    }
+
+    /// Tables created by DDL, in the order of creation.
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    pub fn sequences(&self) -> &[Sequence] {
+        &self.sequences
+    }
+
+    /// Statistics of the analyzed tables.
+    pub fn all_statistics(&self) -> &[TableStatistics] {
+        &self.statistics
+    }
+
+    pub fn schema(&self, name: &str) -> Option<&Schema> {
+        self.schemas.iter().find(|schema| schema.name == name)
+    }
+
+    pub fn index(&self, schema_name: &str, name: &str) -> Option<&Index> {
+        self.indexes
+            .iter()
+            .find(|index| index.schema_name == schema_name && index.name == name)
+    }
+
+    pub fn view(&self, schema_name: &str, name: &str) -> Option<&View> {
+        self.views
+            .iter()
+            .find(|view| view.schema_name == schema_name && view.name == name)
+    }
+
+    pub fn sequence(&self, schema_name: &str, name: &str) -> Option<&Sequence> {
+        self.sequences
+            .iter()
+            .find(|sequence| sequence.schema_name == schema_name && sequence.name == name)
+    }
+
+    /// Type of the table, view, index or sequence with the name.
+    fn relation_type(&self, schema_name: &str, name: &str) -> Option<ObjectType> {
+        if self.table(schema_name, name).is_some() {
+            Some(ObjectType::Table)
+        } else if self.view(schema_name, name).is_some() {
+            Some(ObjectType::View)
+        } else if self.index(schema_name, name).is_some() {
+            Some(ObjectType::Index)
+        } else if self.sequence(schema_name, name).is_some() {
+            Some(ObjectType::Sequence)
+        } else {
+            None
+        }
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn execute(&mut self, query: &Query) -> Result<(), CatalogError> {
+        match query {
+            Query::CreateSchema(query) => self.create_schema(query),
+            Query::CreateTable(query) => self.create_table(query),
+            Query::CreateIndex(query) => self.create_index(query),
+            Query::CreateView(query) => self.create_view(query),
+            Query::CreateSequence(query) => self.create_sequence(query),
+            Query::Drop(query) => self.drop(query),
+            _ => Err(CatalogError::NotDdl),
+        }
+    }
+
+    /// Schema a new object is created in, it has to exist and can't be a system schema.
+    fn target_schema(&self, name: &ObjectName) -> Result<String, CatalogError> {
+        let schema_name = name
+            .schema_name
+            .clone()
+            .unwrap_or_else(|| self.default_schema().to_string());
+        if schema_name == SCHEMA_NAME {
+            return Err(CatalogError::SystemObject { name: schema_name });
+        }
+        if self.schema(&schema_name).is_none() {
+            return Err(CatalogError::UnknownObject {
+                object_type: ObjectType::Schema,
+                name: schema_name,
+            });
+        }
+        Ok(schema_name)
+    }
+
+    /// `Ok(false)` when the name is taken and the statement has `if not exists`.
+    fn check_new_relation(
+        &self,
+        schema_name: &str,
+        name: &str,
+        if_not_exists: bool,
+    ) -> Result<bool, CatalogError> {
+        match self.relation_type(schema_name, name) {
+            Some(_) if if_not_exists => Ok(false),
+            Some(_) => Err(CatalogError::DuplicateRelation {
+                name: name.to_string(),
+            }),
+            None => Ok(true),
+        }
+    }
+
+    fn create_schema(&mut self, query: &CreateSchemaQuery) -> Result<(), CatalogError> {
+        if self.schema(&query.name).is_some() {
+            if query.if_not_exists {
+                return Ok(());
+            }
+            return Err(CatalogError::DuplicateSchema {
+                name: query.name.clone(),
+            });
+        }
+        let id = ObjectId(self.next_id());
+        self.schemas.push(Schema {
+            id,
+            name: query.name.clone(),
+        });
+        Ok(())
+    }
+
+    fn create_table(&mut self, query: &CreateTableQuery) -> Result<(), CatalogError> {
+        let schema_name = self.target_schema(&query.name)?;
+        if !self.check_new_relation(&schema_name, &query.name.name, query.if_not_exists)? {
+            return Ok(());
+        }
+        let mut table = Table {
+            id: TableId(self.next_id()),
+            schema_name,
+            name: query.name.name.clone(),
+            columns: vec![],
+            constraints: vec![],
+        };
+        let mut constraints = vec![];
+        for (position, definition) in query.columns.iter().enumerate() {
+            if table.column(&definition.name).is_some() {
+                return Err(CatalogError::DuplicateColumn {
+                    column_name: definition.name.clone(),
+                });
+            }
+            let mut column = Column {
+                id: ColumnId(position as u32 + 1),
+                name: definition.name.clone(),
+                data_type: definition.data_type,
+                nullable: true,
+                default: None,
+            };
+            for constraint in &definition.constraints {
+                let columns = vec![definition.name.clone()];
+                let kind = match constraint {
+                    ColumnConstraint::NotNull => {
+                        column.nullable = false;
+                        continue;
+                    }
+                    ColumnConstraint::Null => {
+                        column.nullable = true;
+                        continue;
+                    }
+                    ColumnConstraint::Default(expr) => {
+                        let mut references = ColumnReferences(None);
+                        references.visit_expr(expr);
+                        if let Some(column_name) = references.0 {
+                            return Err(CatalogError::DefaultReferencesColumn { column_name });
+                        }
+                        column.default = Some(expr.clone());
+                        continue;
+                    }
+                    ColumnConstraint::PrimaryKey => TableConstraintKind::PrimaryKey(columns),
+                    ColumnConstraint::Unique => TableConstraintKind::Unique(columns),
+                    ColumnConstraint::References { table, column } => {
+                        TableConstraintKind::ForeignKey {
+                            columns,
+                            table: table.clone(),
+                            referenced_columns: column.iter().cloned().collect(),
+                        }
+                    }
+                };
+                constraints.push(TableConstraint { name: None, kind });
+            }
+            table.columns.push(column);
+        }
+        constraints.extend(query.constraints.iter().cloned());
+
+        let mut indexes = vec![];
+        for constraint in &constraints {
+            let constraint = self.constraint(&mut table, constraint)?;
+            if let ConstraintKind::PrimaryKey(columns) | ConstraintKind::Unique(columns) =
+                &constraint.kind
+            {
+                // Unique constraints are checked with an index of the same name
+                let name = &constraint.name;
+                if self.relation_type(&table.schema_name, name).is_some()
+                    || indexes.iter().any(|index: &Index| &index.name == name)
+                {
+                    return Err(CatalogError::DuplicateRelation { name: name.clone() });
+                }
+                indexes.push(Index {
+                    id: ObjectId(self.next_id()),
+                    schema_name: table.schema_name.clone(),
+                    name: name.clone(),
+                    table: table.id,
+                    columns: columns.clone(),
+                    unique: true,
+                });
+            }
+            table.constraints.push(constraint);
+        }
+        self.tables.push(table);
+        self.indexes.extend(indexes);
+        Ok(())
+    }
+
+    /// Resolves the columns of the constraint, the table may reference itself.
+    fn constraint(
+        &mut self,
+        table: &mut Table,
+        constraint: &TableConstraint,
+    ) -> Result<Constraint, CatalogError> {
+        let (kind, suffix) = match &constraint.kind {
+            TableConstraintKind::PrimaryKey(columns) => {
+                if !table.primary_key().is_empty() {
+                    return Err(CatalogError::MultiplePrimaryKeys {
+                        table_name: table.name.clone(),
+                    });
+                }
+                let columns = column_ids(table, columns)?;
+                for column in &mut table.columns {
+                    if columns.contains(&column.id) {
+                        column.nullable = false;
+                    }
+                }
+                (ConstraintKind::PrimaryKey(columns), "pkey")
+            }
+            TableConstraintKind::Unique(columns) => {
+                (ConstraintKind::Unique(column_ids(table, columns)?), "key")
+            }
+            TableConstraintKind::ForeignKey {
+                columns,
+                table: referenced_name,
+                referenced_columns,
+            } => {
+                let schema_name = referenced_name
+                    .schema_name
+                    .as_deref()
+                    .unwrap_or(self.default_schema());
+                let referenced =
+                    if schema_name == table.schema_name && referenced_name.name == table.name {
+                        &*table
+                    } else {
+                        self.table(schema_name, &referenced_name.name)
+                            .ok_or_else(|| CatalogError::UnknownObject {
+                                object_type: ObjectType::Table,
+                                name: referenced_name.to_string(),
+                            })?
+                    };
+                let referenced_columns = if referenced_columns.is_empty() {
+                    referenced.primary_key().to_vec()
+                } else {
+                    column_ids(referenced, referenced_columns)?
+                };
+                let is_unique = referenced.constraints.iter().any(|constraint| {
+                    matches!(&constraint.kind,
+                        ConstraintKind::PrimaryKey(key) | ConstraintKind::Unique(key)
+                            if same_columns(key, &referenced_columns))
+                });
+                if !is_unique {
+                    return Err(CatalogError::NoUniqueConstraint {
+                        table_name: referenced.name.clone(),
+                    });
+                }
+                if columns.len() != referenced_columns.len() {
+                    return Err(CatalogError::ForeignKeyColumnCount);
+                }
+                let kind = ConstraintKind::ForeignKey {
+                    columns: column_ids(table, columns)?,
+                    referenced_table: referenced.id,
+                    referenced_columns,
+                };
+                (kind, "fkey")
+            }
+        };
+        let name = constraint.name.clone().unwrap_or_else(|| match &kind {
+            ConstraintKind::PrimaryKey(_) => format!("{}_{}", table.name, suffix),
+            ConstraintKind::Unique(columns) | ConstraintKind::ForeignKey { columns, .. } => {
+                let names: Vec<_> = columns
+                    .iter()
+                    .filter_map(|id| table.columns.iter().find(|column| column.id == *id))
+                    .map(|column| column.name.as_str())
+                    .collect();
+                format!("{}_{}_{}", table.name, names.join("_"), suffix)
+            }
+        });
+        Ok(Constraint {
+            id: ObjectId(self.next_id()),
+            name,
+            kind,
+        })
+    }
+
+    fn create_index(&mut self, query: &CreateIndexQuery) -> Result<(), CatalogError> {
+        let table = self.existing_table(&query.table)?;
+        let (schema_name, table_id) = (table.schema_name.clone(), table.id);
+        let columns = column_ids(table, &query.columns)?;
+        if !self.check_new_relation(&schema_name, &query.name, query.if_not_exists)? {
+            return Ok(());
+        }
+        let id = ObjectId(self.next_id());
+        self.indexes.push(Index {
+            id,
+            schema_name,
+            name: query.name.clone(),
+            table: table_id,
+            columns,
+            unique: query.unique,
+        });
+        Ok(())
+    }
+
+    fn create_view(&mut self, query: &CreateViewQuery) -> Result<(), CatalogError> {
+        let schema_name = self.target_schema(&query.name)?;
+        self.check_new_relation(&schema_name, &query.name.name, false)?;
+        let bound = analyse(&Query::Select(query.query.clone()), self)
+            .map_err(CatalogError::InvalidView)?;
+        let columns = bound
+            .result_columns()
+            .into_iter()
+            .enumerate()
+            .map(|(position, column)| Column {
+                id: ColumnId(position as u32 + 1),
+                name: column.name,
+                data_type: column.data_type,
+                nullable: true,
+                default: None,
+            })
+            .collect();
+        let mut tables = vec![];
+        if let BoundQuery::Select(select) = &bound {
+            referenced_tables(select, &mut tables);
+        }
+        let id = ObjectId(self.next_id());
+        self.views.push(View {
+            id,
+            schema_name,
+            name: query.name.name.clone(),
+            query: query.query.clone(),
+            columns,
+            tables,
+        });
+        Ok(())
+    }
+
+    fn create_sequence(&mut self, query: &CreateSequenceQuery) -> Result<(), CatalogError> {
+        let schema_name = self.target_schema(&query.name)?;
+        if !self.check_new_relation(&schema_name, &query.name.name, query.if_not_exists)? {
+            return Ok(());
+        }
+        let increment = query.increment.unwrap_or(1);
+        if increment == 0 {
+            return Err(CatalogError::ZeroIncrement);
+        }
+        let id = ObjectId(self.next_id());
+        self.sequences.push(Sequence {
+            id,
+            schema_name,
+            name: query.name.name.clone(),
+            start: query.start.unwrap_or(increment.signum()),
+            increment,
+        });
+        Ok(())
+    }
+
+    /// Table named in a statement which changes it.
+    fn existing_table(&self, name: &ObjectName) -> Result<&Table, CatalogError> {
+        let schema_name = name.schema_name.as_deref().unwrap_or(self.default_schema());
+        if schema_name == SCHEMA_NAME {
+            return Err(CatalogError::SystemObject {
+                name: name.to_string(),
+            });
+        }
+        match self.relation_type(schema_name, &name.name) {
+            Some(ObjectType::Table) => Ok(self
+                .tables
+                .iter()
+                .find(|table| table.schema_name == schema_name && table.name == name.name)
+                .expect("relation is a table")),
+            Some(_) => Err(CatalogError::WrongObjectType {
+                object_type: ObjectType::Table,
+                name: name.to_string(),
+            }),
+            None => Err(CatalogError::UnknownObject {
+                object_type: ObjectType::Table,
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    fn drop(&mut self, query: &DropQuery) -> Result<(), CatalogError> {
+        for name in &query.names {
+            let schema_name = match query.object_type {
+                ObjectType::Schema => name.name.clone(),
+                _ => name
+                    .schema_name
+                    .clone()
+                    .unwrap_or_else(|| self.default_schema().to_string()),
+            };
+            if schema_name == SCHEMA_NAME {
+                return Err(CatalogError::SystemObject {
+                    name: name.to_string(),
+                });
+            }
+            let found = match query.object_type {
+                ObjectType::Schema => self.schema(&schema_name).map(|_| ObjectType::Schema),
+                _ => self.relation_type(&schema_name, &name.name),
+            };
+            match found {
+                None if query.if_exists => continue,
+                None => {
+                    return Err(CatalogError::UnknownObject {
+                        object_type: query.object_type,
+                        name: name.to_string(),
+                    })
+                }
+                Some(object_type) if object_type != query.object_type => {
+                    return Err(CatalogError::WrongObjectType {
+                        object_type: query.object_type,
+                        name: name.to_string(),
+                    })
+                }
+                Some(_) => {}
+            }
+            match query.object_type {
+                ObjectType::Schema => self.drop_schema(&schema_name)?,
+                ObjectType::Table => self.drop_table(&schema_name, &name.name)?,
+                ObjectType::View => self
+                    .views
+                    .retain(|view| view.schema_name != schema_name || view.name != name.name),
+                ObjectType::Index => self.drop_index(&schema_name, &name.name)?,
+                ObjectType::Sequence => self.sequences.retain(|sequence| {
+                    sequence.schema_name != schema_name || sequence.name != name.name
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Only an empty schema can be dropped.
+    fn drop_schema(&mut self, name: &str) -> Result<(), CatalogError> {
+        let dependent = self
+            .tables
+            .iter()
+            .map(|table| (&table.schema_name, &table.name))
+            .chain(
+                self.views
+                    .iter()
+                    .map(|view| (&view.schema_name, &view.name)),
+            )
+            .chain(
+                self.indexes
+                    .iter()
+                    .map(|index| (&index.schema_name, &index.name)),
+            )
+            .chain(
+                self.sequences
+                    .iter()
+                    .map(|sequence| (&sequence.schema_name, &sequence.name)),
+            )
+            .find(|(schema_name, _)| *schema_name == name);
+        if let Some((_, dependent)) = dependent {
+            return Err(CatalogError::DependentObjects {
+                name: name.to_string(),
+                dependent: dependent.clone(),
+            });
+        }
+        self.schemas.retain(|schema| schema.name != name);
+        Ok(())
+    }
+
+    /// Indexes of the table are dropped with it, views and foreign keys of other tables
+    /// which reference it prevent the drop.
+    fn drop_table(&mut self, schema_name: &str, name: &str) -> Result<(), CatalogError> {
+        let id = self.table(schema_name, name).expect("table exists").id;
+        let referencing = self.tables.iter().find(|table| {
+            table.id != id
+                && table.constraints.iter().any(|constraint| {
+                    matches!(constraint.kind,
+                        ConstraintKind::ForeignKey { referenced_table, .. } if referenced_table == id)
+                })
+        });
+        let dependent = referencing.map(|table| &table.name).or_else(|| {
+            self.views
+                .iter()
+                .find(|view| view.tables.contains(&id))
+                .map(|view| &view.name)
+        });
+        if let Some(dependent) = dependent {
+            return Err(CatalogError::DependentObjects {
+                name: name.to_string(),
+                dependent: dependent.clone(),
+            });
+        }
+        self.tables.retain(|table| table.id != id);
+        self.indexes.retain(|index| index.table != id);
+        self.statistics.retain(|statistics| statistics.table != id);
+        Ok(())
+    }
+
+    /// Indexes of primary keys and unique constraints go away only with the table.
+    fn drop_index(&mut self, schema_name: &str, name: &str) -> Result<(), CatalogError> {
+        let index = self.index(schema_name, name).expect("index exists");
+        let constraint = self
+            .tables
+            .iter()
+            .filter(|table| table.id == index.table)
+            .flat_map(|table| &table.constraints)
+            .find(|constraint| constraint.name == name);
+        if let Some(constraint) = constraint {
+            return Err(CatalogError::DependentObjects {
+                name: name.to_string(),
+                dependent: constraint.name.clone(),
+            });
+        }
+        let id = index.id;
+        self.indexes.retain(|index| index.id != id);
+        Ok(())
+    }
+}
+
+impl Catalog for CatalogSnapshot {
+    fn table(&self, schema_name: &str, table_name: &str) -> Option<&Table> {
+        let tables = match schema_name {
+            SCHEMA_NAME => information_schema::tables(),
+            _ => &self.tables,
+        };
+        tables
+            .iter()
+            .find(|table| table.schema_name == schema_name && table.name == table_name)
+    }
+
+    fn view(&self, schema_name: &str, name: &str) -> Option<&View> {
+        CatalogSnapshot::view(self, schema_name, name)
+    }
+
+    fn statistics(&self, table: TableId) -> Option<&TableStatistics> {
+        self.statistics
+            .iter()
+            .find(|statistics| statistics.table == table)
+    }
+
+    fn indexes(&self, table: TableId) -> Vec<&Index> {
+        self.indexes
+            .iter()
+            .filter(|index| index.table == table)
+            .collect()
+    }
+
+    fn system_rows(&self, table: TableId) -> Option<Vec<Vec<Value>>> {
+        information_schema::rows(self, table)
+    }
+}
+
+fn column_ids(table: &Table, names: &[String]) -> Result<Vec<ColumnId>, CatalogError> {
+    names
+        .iter()
+        .map(|name| {
+            table
+                .column(name)
+                .map(|column| column.id)
+                .ok_or_else(|| CatalogError::UnknownColumn {
+                    table_name: table.name.clone(),
+                    column_name: name.clone(),
+                })
+        })
+        .collect()
+}
+
+/// The same columns in any order.
+fn same_columns(first: &[ColumnId], second: &[ColumnId]) -> bool {
+    first.len() == second.len() && first.iter().all(|column| second.contains(column))
+}
+
+/// Finds the first column a default expression references.
+struct ColumnReferences(Option<String>);
+
+impl Visitor for ColumnReferences {
+    fn visit_column(&mut self, column: &ColumnIdentifier) {
+        self.0.get_or_insert_with(|| column.column_name.clone());
+    }
+}
+
+/// Tables the query and its subqueries read, the ones of the views it reads included.
+fn referenced_tables(select: &BoundSelect, tables: &mut Vec<TableId>) {
+    if let Some(from) = &select.from {
+        let relations = from
+            .relations
+            .iter()
+            .chain(from.joins.iter().map(|join| &join.relation));
+        for relation in relations {
+            match &relation.view {
+                Some(view) => referenced_tables(view, tables),
+                None if !tables.contains(&relation.table.id) => tables.push(relation.table.id),
+                None => {}
+            }
+        }
+    }
+    let joins = select.from.iter().flat_map(|from| &from.joins);
+    let exprs = joins
+        .filter_map(|join| join.on.as_ref())
+        .chain(select.projection.iter().map(|item| &item.expr))
+        .chain(&select.filter)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(select.order_by.iter().map(|(expr, _)| expr));
+    for expr in exprs {
+        subquery_tables(expr, tables);
+    }
+    for union in &select.unions {
+        referenced_tables(&union.query, tables);
+    }
+}
+
+fn subquery_tables(expr: &BoundExpr, tables: &mut Vec<TableId>) {
+    match expr {
+        BoundExpr::InSubquery { subquery, .. }
+        | BoundExpr::Exists { subquery, .. }
+        | BoundExpr::Subquery(subquery) => referenced_tables(subquery, tables),
+        _ => {}
+    }
+    for child in expr.children() {
+        subquery_tables(child, tables);
+    }
+}
+
+/// A DDL transaction, dropping it without a commit rolls it back.
+#[derive(Debug, Clone)]
+pub struct CatalogTransaction {
+    base: Arc<CatalogSnapshot>,
+    working: CatalogSnapshot,
+}
+
+impl CatalogTransaction {
+    /// Applies a DDL statement, a statement which fails leaves the transaction unchanged.
+    pub fn execute(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let mut working = self.working.clone();
+        working.execute(query)?;
+        self.working = working;
+        Ok(())
+    }
+
+    /// Replaces the statistics of the table of an `analyze`, or of all tables when it names
+    /// none, with statistics of the rows the scan returns for the table. An error of the
+    /// scan stops the `analyze`.
+    pub fn analyze<E: From<CatalogError>>(
+        &mut self,
+        query: &AnalyzeQuery,
+        mut scan: impl FnMut(&Table) -> Result<Vec<Vec<Value>>, E>,
+    ) -> Result<(), E> {
+        let tables = match &query.table {
+            Some(name) => vec![self.working.existing_table(name)?.clone()],
+            None => self.working.tables.clone(),
+        };
+        for table in &tables {
+            let statistics = TableStatistics::gather(table, scan(table)?);
+            let working = &mut self.working.statistics;
+            working.retain(|existing| existing.table != table.id);
+            working.push(statistics);
+        }
+        Ok(())
+    }
+
+    /// The catalog as the transaction sees it, its own changes included.
+    pub fn catalog(&self) -> &CatalogSnapshot {
+        &self.working
+    }
+}
+
+#[derive(Debug)]
+pub struct SystemCatalog {
+    current: Arc<CatalogSnapshot>,
+}
+
+impl SystemCatalog {
+    /// Reads the catalog from the store, an empty store gets the catalog of a new database.
+    pub fn open(store: &mut dyn KvStore) -> Result<Self, CatalogError> {
+        let snapshot = match store.get_by_key(META_KEY)? {
+            Some(meta) => load(store, decode(&meta)?)?,
+            None => {
+                let snapshot = CatalogSnapshot::bootstrap();
+                write_changes(store, &CatalogSnapshot::empty(), &snapshot)?;
+                snapshot
+            }
+        };
+        Ok(SystemCatalog {
+            current: Arc::new(snapshot),
+        })
+    }
+
+    /// The last committed state.
+    pub fn snapshot(&self) -> Arc<CatalogSnapshot> {
+        self.current.clone()
+    }
+
+    pub fn begin(&self) -> CatalogTransaction {
+        CatalogTransaction {
+            base: self.current.clone(),
+            working: (*self.current).clone(),
+        }
+    }
+
+    /// Writes the changes of the transaction to the store and makes them visible. Fails if
+    /// another transaction changed the catalog since this one began.
+    pub fn commit(
+        &mut self,
+        transaction: CatalogTransaction,
+        store: &mut dyn KvStore,
+    ) -> Result<(), CatalogError> {
+        let CatalogTransaction { base, mut working } = transaction;
+        if working == *base {
+            return Ok(());
+        }
+        if base.version != self.current.version {
+            return Err(CatalogError::SerializationFailure);
+        }
+        working.version = base.version + 1;
+        write_changes(store, &self.current, &working)?;
+        self.current = Arc::new(working);
+        Ok(())
+    }
+}
+
+/// Version and id counter of the catalog.
+struct Meta {
+    version: u64,
+    next_id: u32,
+}
+
+impl Record for Meta {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u64(self.version);
+        encoder.u32(self.next_id);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, CatalogError> {
+        Ok(Meta {
+            version: decoder.u64()?,
+            next_id: decoder.u32()?,
+        })
+    }
+}
+
+fn object_key(kind: &str, id: u32) -> Vec<u8> {
+    format!("catalog/{}/{:010}", kind, id).into_bytes()
+}
+
+fn load(store: &dyn KvStore, meta: Meta) -> Result<CatalogSnapshot, CatalogError> {
+    let mut snapshot = CatalogSnapshot::empty();
+    snapshot.version = meta.version;
+    snapshot.next_id = meta.next_id;
+    for entry in store.scan(KEY_PREFIX, KEY_PREFIX_END) {
+        let (key, value) = entry?;
+        let kind = key[KEY_PREFIX.len()..]
+            .split(|byte| *byte == b'/')
+            .next()
+            .unwrap_or_default();
+        match kind {
+            b"meta" => {}
+            b"schema" => snapshot.schemas.push(decode(&value)?),
+            b"table" => snapshot.tables.push(decode(&value)?),
+            b"index" => snapshot.indexes.push(decode(&value)?),
+            b"view" => snapshot.views.push(decode(&value)?),
+            b"sequence" => snapshot.sequences.push(decode(&value)?),
+            b"statistics" => snapshot.statistics.push(decode(&value)?),
+            _ => {
+                return Err(CatalogError::Corrupted(format!(
+                    "unknown key {}",
+                    String::from_utf8_lossy(&key)
+                )))
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+fn write_changes(
+    store: &mut dyn KvStore,
+    old: &CatalogSnapshot,
+    new: &CatalogSnapshot,
+) -> Result<(), StorageError> {
+    write_objects(store, "schema", &old.schemas, &new.schemas, |schema| {
+        schema.id.0
+    })?;
+    write_objects(store, "table", &old.tables, &new.tables, |table| table.id.0)?;
+    write_objects(store, "index", &old.indexes, &new.indexes, |index| {
+        index.id.0
+    })?;
+    write_objects(store, "view", &old.views, &new.views, |view| view.id.0)?;
+    write_objects(
+        store,
+        "sequence",
+        &old.sequences,
+        &new.sequences,
+        |sequence| sequence.id.0,
+    )?;
+    write_objects(
+        store,
+        "statistics",
+        &old.statistics,
+        &new.statistics,
+        |statistics| statistics.table.0,
+    )?;
+    let meta = Meta {
+        version: new.version,
+        next_id: new.next_id,
+    };
+    store.put(META_KEY, &encode(&meta))
+}
+
+fn write_objects<R: Record + PartialEq>(
+    store: &mut dyn KvStore,
+    kind: &str,
+    old: &[R],
+    new: &[R],
+    id: impl Fn(&R) -> u32,
+) -> Result<(), StorageError> {
+    for object in old {
+        if !new.iter().any(|other| id(other) == id(object)) {
+            store.delete(&object_key(kind, id(object)))?;
+        }
+    }
+    for object in new {
+        if !old.contains(object) {
+            store.put(&object_key(kind, id(object)), &encode(object))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Queries;
+    use crate::storage::MemoryStore;
+
+    fn execute(transaction: &mut CatalogTransaction, input: &str) -> Result<(), CatalogError> {
+        for query in Queries::parse(input).unwrap().queries {
+            transaction.execute(&query)?;
+        }
+        Ok(())
+    }
+
+    /// A committed catalog with the given DDL.
+    fn catalog(store: &mut MemoryStore, input: &str) -> SystemCatalog {
+        let mut catalog = SystemCatalog::open(store).unwrap();
+        let mut transaction = catalog.begin();
+        execute(&mut transaction, input).unwrap();
+        catalog.commit(transaction, store).unwrap();
+        catalog
+    }
+
+    const SCHEMA: &str = "
+        create table users (
+            id integer primary key,
+            name varchar(20) not null unique,
+            age integer default 18
+        );
+        create table orders (
+            id bigint,
+            user_id integer references users,
+            total decimal(10, 2),
+            constraint orders_pk primary key (id)
+        );
+        create index orders_user on orders (user_id);
+        create view adults as select name from users where age >= 18;
+        create sequence order_ids increment by 10 start with 100;
+    ";
+
+    #[test]
+    fn creates_objects() {
+        let catalog = catalog(&mut MemoryStore::new(), SCHEMA).snapshot();
+        assert_eq!(catalog.version(), 1);
+        let users = catalog.table("public", "users").unwrap();
+        let orders = catalog.table("public", "orders").unwrap();
+        assert_eq!(users.primary_key(), &[ColumnId(1)]);
+        assert!(!users.column("id").unwrap().nullable);
+        assert!(!users.column("name").unwrap().nullable);
+        assert!(users.column("age").unwrap().nullable);
+        assert_eq!(
+            users.column("age").unwrap().default,
+            Some(crate::parser::expr::Expr::number("18"))
+        );
+        let names: Vec<_> = users.constraints.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["users_pkey", "users_name_key"]);
+        assert_eq!(
+            orders.constraints[0].kind,
+            ConstraintKind::ForeignKey {
+                columns: vec![ColumnId(2)],
+                referenced_table: users.id,
+                referenced_columns: vec![ColumnId(1)],
+            }
+        );
+        assert_eq!(orders.constraints[0].name, "orders_user_id_fkey");
+        assert_eq!(orders.primary_key(), &[ColumnId(1)]);
+        let indexes: Vec<_> = catalog.indexes().iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(
+            indexes,
+            vec!["users_pkey", "users_name_key", "orders_pk", "orders_user"]
+        );
+        assert!(!catalog.index("public", "orders_user").unwrap().unique);
+        let adults = catalog.view("public", "adults").unwrap();
+        assert_eq!(adults.columns[0].name, "name");
+        assert_eq!(adults.columns[0].data_type, DataType::VarChar(Some(20)));
+        assert_eq!(adults.tables, vec![users.id]);
+        let sequence = catalog.sequence("public", "order_ids").unwrap();
+        assert_eq!((sequence.start, sequence.increment), (100, 10));
+
+        let mut ids: Vec<_> = catalog.tables().iter().map(|table| table.id.0).collect();
+        ids.extend(catalog.indexes().iter().map(|index| index.id.0));
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+        assert!(ids.iter().all(|id| *id >= FIRST_OBJECT_ID));
+    }
+
+    #[test]
+    fn reports_invalid_ddl() {
+        let mut store = MemoryStore::new();
+        let catalog = catalog(&mut store, SCHEMA);
+        let check = |input: &str| execute(&mut catalog.begin(), input);
+        let unknown = |object_type, name: &str| {
+            Err(CatalogError::UnknownObject {
+                object_type,
+                name: name.to_string(),
+            })
+        };
+        assert_eq!(
+            check("create table users (id int)"),
+            Err(CatalogError::DuplicateRelation {
+                name: "users".to_string()
+            })
+        );
+        assert_eq!(check("create table if not exists users (id int)"), Ok(()));
+        assert_eq!(
+            check("create sequence orders_user"),
+            Err(CatalogError::DuplicateRelation {
+                name: "orders_user".to_string()
+            })
+        );
+        assert_eq!(
+            check("create table t (a int, a text)"),
+            Err(CatalogError::DuplicateColumn {
+                column_name: "a".to_string()
+            })
+        );
+        assert_eq!(
+            check("create table t (a int primary key, b int, primary key (b))"),
+            Err(CatalogError::MultiplePrimaryKeys {
+                table_name: "t".to_string()
+            })
+        );
+        assert_eq!(
+            check("create table t (a int references users (age))"),
+            Err(CatalogError::NoUniqueConstraint {
+                table_name: "users".to_string()
+            })
+        );
+        assert_eq!(
+            check("create table t (a int, b int, foreign key (a, b) references users)"),
+            Err(CatalogError::ForeignKeyColumnCount)
+        );
+        assert_eq!(
+            check("create table t (a int references missing)"),
+            unknown(ObjectType::Table, "missing")
+        );
+        assert_eq!(
+            check("create table t (a int default b + 1)"),
+            Err(CatalogError::DefaultReferencesColumn {
+                column_name: "b".to_string()
+            })
+        );
+        assert_eq!(
+            check("create table nowhere.t (a int)"),
+            unknown(ObjectType::Schema, "nowhere")
+        );
+        assert_eq!(
+            check("create table information_schema.t (a int)"),
+            Err(CatalogError::SystemObject {
+                name: "information_schema".to_string()
+            })
+        );
+        assert_eq!(
+            check("create index i on users (nope)"),
+            Err(CatalogError::UnknownColumn {
+                table_name: "users".to_string(),
+                column_name: "nope".to_string()
+            })
+        );
+        assert_eq!(
+            check("create index i on adults (name)"),
+            Err(CatalogError::WrongObjectType {
+                object_type: ObjectType::Table,
+                name: "adults".to_string()
+            })
+        );
+        assert!(matches!(
+            check("create view v as select nope from users"),
+            Err(CatalogError::InvalidView(_))
+        ));
+        assert_eq!(
+            check("create sequence s increment 0"),
+            Err(CatalogError::ZeroIncrement)
+        );
+        assert_eq!(
+            check("drop table users"),
+            Err(CatalogError::DependentObjects {
+                name: "users".to_string(),
+                dependent: "orders".to_string()
+            })
+        );
+        assert_eq!(
+            check("drop table orders, users"),
+            Err(CatalogError::DependentObjects {
+                name: "users".to_string(),
+                dependent: "adults".to_string()
+            })
+        );
+        assert_eq!(check("drop view adults; drop table orders, users"), Ok(()));
+        assert_eq!(
+            check("drop index users_pkey"),
+            Err(CatalogError::DependentObjects {
+                name: "users_pkey".to_string(),
+                dependent: "users_pkey".to_string()
+            })
+        );
+        assert_eq!(
+            check("drop view users"),
+            Err(CatalogError::WrongObjectType {
+                object_type: ObjectType::View,
+                name: "users".to_string()
+            })
+        );
+        assert_eq!(
+            check("drop table missing"),
+            unknown(ObjectType::Table, "missing")
+        );
+        assert_eq!(check("drop table if exists missing"), Ok(()));
+        assert_eq!(
+            check("drop schema public"),
+            Err(CatalogError::DependentObjects {
+                name: "public".to_string(),
+                dependent: "users".to_string()
+            })
+        );
+        assert_eq!(check("select 1"), Err(CatalogError::NotDdl));
+    }
+
+    #[test]
+    fn ddl_is_transactional() {
+        let mut store = MemoryStore::new();
+        let mut catalog = catalog(&mut store, "create table users (id int)");
+        let before = catalog.snapshot();
+
+        let mut first = catalog.begin();
+        execute(
+            &mut first,
+            "create schema sales; create table sales.orders (id int)",
+        )
+        .unwrap();
+        assert!(first.catalog().table("sales", "orders").is_some());
+        assert!(catalog.snapshot().table("sales", "orders").is_none());
+
+        // a failed statement leaves the earlier ones of the transaction in place
+        assert!(execute(&mut first, "create table sales.t (id int); drop table nope").is_err());
+        assert!(first.catalog().table("sales", "t").is_some());
+        let snapshot = first.catalog().clone();
+        assert!(first
+            .execute(&Queries::parse("drop table nope").unwrap().queries[0])
+            .is_err());
+        assert_eq!(first.catalog(), &snapshot);
+
+        let mut second = catalog.begin();
+        execute(&mut second, "drop table users").unwrap();
+        catalog.commit(first, &mut store).unwrap();
+        assert_eq!(
+            catalog.commit(second, &mut store),
+            Err(CatalogError::SerializationFailure)
+        );
+
+        let current = catalog.snapshot();
+        assert_eq!(current.version(), before.version() + 1);
+        assert!(current.table("sales", "orders").is_some());
+        assert!(current.table("public", "users").is_some());
+        assert!(before.table("sales", "orders").is_none());
+
+        // dropping a transaction rolls it back
+        let mut rolled_back = catalog.begin();
+        execute(&mut rolled_back, "drop table users").unwrap();
+        drop(rolled_back);
+        assert!(catalog.snapshot().table("public", "users").is_some());
+    }
+
+    #[test]
+    fn persists_catalog() {
+        let mut store = MemoryStore::new();
+        let mut catalog = catalog(&mut store, SCHEMA);
+        assert_eq!(
+            SystemCatalog::open(&mut store).unwrap().snapshot(),
+            catalog.snapshot()
+        );
+
+        let mut transaction = catalog.begin();
+        execute(
+            &mut transaction,
+            "drop view adults; drop index orders_user; create schema sales",
+        )
+        .unwrap();
+        catalog.commit(transaction, &mut store).unwrap();
+        let reopened = SystemCatalog::open(&mut store).unwrap().snapshot();
+        assert_eq!(reopened, catalog.snapshot());
+        assert!(reopened.view("public", "adults").is_none());
+        assert!(reopened.schema("sales").is_some());
+        assert_eq!(reopened.version(), 2);
+
+        store.put(b"catalog/table/0000099999", b"\x01").unwrap();
+        assert!(matches!(
+            SystemCatalog::open(&mut store),
+            Err(CatalogError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn analyzes_tables() {
+        let mut store = MemoryStore::new();
+        let mut catalog = catalog(&mut store, SCHEMA);
+        let analyze = |input: &str| match Queries::parse(input).unwrap().queries.remove(0) {
+            Query::Analyze(query) => query,
+            query => panic!("not analyze: {:?}", query),
+        };
+        let rows = |table: &Table| -> Result<Vec<Vec<Value>>, CatalogError> {
+            Ok((0..table.id.0 as i64 - 16380)
+                .map(|id| table.columns.iter().map(|_| Value::Integer(id)).collect())
+                .collect())
+        };
+
+        let mut transaction = catalog.begin();
+        transaction
+            .analyze(&analyze("analyze orders"), rows)
+            .unwrap();
+        assert_eq!(transaction.catalog().all_statistics().len(), 1);
+        transaction.analyze(&analyze("analyze"), rows).unwrap();
+        assert_eq!(
+            transaction.analyze(&analyze("analyze adults"), rows),
+            Err(CatalogError::WrongObjectType {
+                object_type: ObjectType::Table,
+                name: "adults".to_string()
+            })
+        );
+        let corrupted = CatalogError::Corrupted("row of users".to_string());
+        assert_eq!(
+            transaction.analyze(&analyze("analyze users"), |_| Err(corrupted.clone())),
+            Err(corrupted)
+        );
+        catalog.commit(transaction, &mut store).unwrap();
+
+        let snapshot = catalog.snapshot();
+        let users = snapshot.table("public", "users").unwrap();
+        let statistics = snapshot.statistics(users.id).unwrap();
+        assert_eq!(statistics.row_count, users.id.0 as u64 - 16380);
+        assert_eq!(statistics.columns.len(), 3);
+        assert_eq!(
+            SystemCatalog::open(&mut store).unwrap().snapshot(),
+            snapshot
+        );
+
+        let mut transaction = catalog.begin();
+        execute(&mut transaction, "drop view adults; drop table orders").unwrap();
+        catalog.commit(transaction, &mut store).unwrap();
+        assert_eq!(catalog.snapshot().all_statistics().len(), 1);
+        assert_eq!(
+            SystemCatalog::open(&mut store).unwrap().snapshot(),
+            catalog.snapshot()
+        );
+    }
+
+    #[test]
+    fn information_schema() {
+        let catalog = catalog(
+            &mut MemoryStore::new(),
+            "create table users (id integer primary key, name text default 'x');
+             create view names as select name from users",
+        )
+        .snapshot();
+        let query = Queries::parse(
+            "select table_name, column_name from information_schema.columns \
+             where table_schema = 'public' order by ordinal_position",
+        )
+        .unwrap()
+        .queries
+        .remove(0);
+        assert!(analyse(&query, &*catalog).is_ok());
+
+        let text = |value: &str| Value::String(value.to_string());
+        let tables = information_schema::rows(&catalog, information_schema::TABLES_ID).unwrap();
+        assert_eq!(
+            tables,
+            vec![
+                vec![text("information_schema"), text("columns"), text("VIEW")],
+                vec![text("information_schema"), text("tables"), text("VIEW")],
+                vec![text("public"), text("names"), text("VIEW")],
+                vec![text("public"), text("users"), text("BASE TABLE")],
+            ]
+        );
+        let columns = information_schema::rows(&catalog, information_schema::COLUMNS_ID).unwrap();
+        let public: Vec<_> = columns
+            .into_iter()
+            .filter(|row| row[0] == text("public"))
+            .collect();
+        assert_eq!(
+            public,
+            vec![
+                vec![
+                    text("public"),
+                    text("names"),
+                    text("name"),
+                    Value::Integer(1),
+                    Value::Null,
+                    text("YES"),
+                    text("text"),
+                ],
+                vec![
+                    text("public"),
+                    text("users"),
+                    text("id"),
+                    Value::Integer(1),
+                    Value::Null,
+                    text("NO"),
+                    text("integer"),
+                ],
+                vec![
+                    text("public"),
+                    text("users"),
+                    text("name"),
+                    Value::Integer(2),
+                    text("'x'"),
+                    text("YES"),
+                    text("text"),
+                ],
+            ]
+        );
+        assert_eq!(information_schema::rows(&catalog, TableId(99)), None);
+    }
+}
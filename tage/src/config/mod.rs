@@ -0,0 +1,179 @@
+//! Configuration file of the database, a `key = value` setting per line.
+//!
+//! ```text
+//! # memory a query may use before its operators spill to disk
+//! memory_budget = 64MB
+//! temp_directory = "/var/tmp/tage"
+//! # threads a query is executed by, sessions may change it with `set`
+//! max_parallel_workers = 8
+//! ```
+//!
+//! Lines starting with `#` are comments, values may be quoted. Sizes are in bytes or
+//! have one of the units `kB`, `MB` and `GB` (powers of 1024).
+
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, thread};
+
+/// Workers a query may have per processor of the machine.
+const WORKERS_PER_PROCESSOR: usize = 4;
+
+/// Most workers a query may have, more are reduced to it.
+pub fn max_workers() -> usize {
+    thread::available_parallelism().map_or(1, |processors| processors.get()) * WORKERS_PER_PROCESSOR
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    /// Bytes the sorts, hash joins and hash aggregates of a query may hold in memory
+    pub memory_budget: usize,
+    /// Directory the files of spilling operators are created in
+    pub temp_directory: PathBuf,
+    /// Threads the scans, joins and aggregations of a query are split across, fewer
+    /// than two execute queries serially, at most [`max_workers`]
+    pub max_parallel_workers: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            memory_budget: 64 * 1024 * 1024,
+            temp_directory: env::temp_dir(),
+            max_parallel_workers: 0,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration file, settings it doesn't have keep their defaults.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let input = fs::read_to_string(path.as_ref())
+            .map_err(|error| ConfigError::Io(format!("{}: {}", path.as_ref().display(), error)))?;
+        Config::parse(&input)
+    }
+
+    pub fn parse(input: &str) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| ConfigError::Invalid {
+                line: index + 1,
+                message: message.to_string(),
+            };
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `key = value`"))?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            match key.trim() {
+                "memory_budget" => {
+                    config.memory_budget = size(value).ok_or_else(|| invalid("invalid size"))?
+                }
+                "temp_directory" => config.temp_directory = PathBuf::from(value),
+                "max_parallel_workers" => {
+                    let workers: usize = value.parse().map_err(|_| invalid("invalid number"))?;
+                    config.max_parallel_workers = workers.min(max_workers());
+                }
+                key => return Err(invalid(&format!("unknown setting \"{}\"", key))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Bytes of a size like `512`, `8kB` or `1GB`.
+fn size(value: &str) -> Option<usize> {
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "kB" | "KB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConfigError {
+    Io(String),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(reason) => write!(f, "can't read the configuration: {}", reason),
+            ConfigError::Invalid { line, message } => {
+                write!(f, "invalid configuration at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings() {
+        let config = Config::parse(
+            "# spilling\n\nmemory_budget = 16MB\ntemp_directory = \"/var/tmp/tage\"\nmax_parallel_workers = 8\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                memory_budget: 16 * 1024 * 1024,
+                temp_directory: PathBuf::from("/var/tmp/tage"),
+                max_parallel_workers: 8.min(max_workers()),
+            }
+        );
+        assert_eq!(
+            Config::parse("max_parallel_workers = 100000")
+                .unwrap()
+                .max_parallel_workers,
+            max_workers()
+        );
+        assert_eq!(
+            Config::parse("memory_budget=512").unwrap().memory_budget,
+            512
+        );
+        assert_eq!(
+            Config::parse("memory_budget = 2 kB").unwrap().memory_budget,
+            2048
+        );
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn reports_invalid_settings() {
+        assert_eq!(
+            Config::parse("memory_budget = 16MB\nmemory_budget = lots"),
+            Err(ConfigError::Invalid {
+                line: 2,
+                message: "invalid size".to_string()
+            })
+        );
+        assert_eq!(
+            Config::parse("work_mem = 4MB").unwrap_err().to_string(),
+            "invalid configuration at line 1: unknown setting \"work_mem\""
+        );
+        assert!(matches!(
+            Config::parse("memory_budget"),
+            Err(ConfigError::Invalid { line: 1, .. })
+        ));
+        assert!(matches!(
+            Config::load("/nonexistent/tage.conf"),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
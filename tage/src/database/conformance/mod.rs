@@ -0,0 +1,264 @@
+//! Conformance of the engine to SQL's `NULL` semantics: three-valued logic, comparisons,
+//! the `IS` tests, grouping, ordering, `IN` and joins. Each case runs a row and a batch at
+//! a time, rows are compared as their values written as SQL literals.
+
+use super::*;
+use crate::storage::MemoryStore;
+
+/// `truth` has every pair of `TRUE`, `FALSE` and `NULL`, in `t` `a` and `b` are equal
+/// in rows 1 and 5, unknown in 2, 3 and 4 and unequal in 6.
+fn database() -> Database<MemoryStore> {
+    let mut database = Database::open(MemoryStore::new()).unwrap();
+    database
+        .execute(
+            "create table truth (id integer primary key, x boolean, y boolean);
+             insert into truth values (1, true, true), (2, true, false), (3, true, null),
+                (4, false, true), (5, false, false), (6, false, null),
+                (7, null, true), (8, null, false), (9, null, null);
+             create table t (id integer primary key, a integer, b integer, s text);
+             insert into t values (1, 1, 1, 'x'), (2, 1, null, 'y'), (3, null, 2, null),
+                (4, null, null, 'x'), (5, 2, 2, 'z'), (6, 3, 1, 'y');",
+        )
+        .unwrap();
+    database
+}
+
+fn check(database: &mut Database<MemoryStore>, input: &str, expected: &[&str]) {
+    for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+        database.set_execution_mode(mode);
+        let rows: Vec<_> = match database.execute(input) {
+            Ok(mut results) => results.pop().unwrap().rows,
+            Err(error) => panic!("{} failed: {}", input, error),
+        };
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                let values: Vec<_> = row.iter().map(Value::to_string).collect();
+                values.join(", ")
+            })
+            .collect();
+        assert_eq!(rows, expected, "{} in {:?}", input, mode);
+    }
+}
+
+#[test]
+fn logical_operators() {
+    check(
+        &mut database(),
+        "select x and y, x or y, not x from truth order by id",
+        &[
+            "TRUE, TRUE, FALSE",
+            "FALSE, TRUE, FALSE",
+            "NULL, TRUE, FALSE",
+            "FALSE, TRUE, TRUE",
+            "FALSE, FALSE, TRUE",
+            "FALSE, NULL, TRUE",
+            "NULL, TRUE, NULL",
+            "FALSE, NULL, NULL",
+            "NULL, NULL, NULL",
+        ],
+    );
+}
+
+#[test]
+fn comparisons() {
+    let mut database = database();
+    check(
+        &mut database,
+        "select a = b, a <> b, a < b, a >= b, a + b, s || '!' from t order by id",
+        &[
+            "TRUE, FALSE, FALSE, TRUE, 2, 'x!'",
+            "NULL, NULL, NULL, NULL, NULL, 'y!'",
+            "NULL, NULL, NULL, NULL, NULL, NULL",
+            "NULL, NULL, NULL, NULL, NULL, 'x!'",
+            "TRUE, FALSE, FALSE, TRUE, 4, 'z!'",
+            "FALSE, TRUE, FALSE, TRUE, 4, 'y!'",
+        ],
+    );
+    check(
+        &mut database,
+        "select null = null, null <> 1, 1 < null",
+        &["NULL, NULL, NULL"],
+    );
+    // a filter keeps only the rows for which the condition is true
+    check(&mut database, "select id from t where a = b", &["1", "5"]);
+    check(&mut database, "select id from t where not (a = b)", &["6"]);
+    check(
+        &mut database,
+        "select id from t where a = b or a is null order by id",
+        &["1", "3", "4", "5"],
+    );
+    check(
+        &mut database,
+        "select id from t where b between 1 and a order by id",
+        &["1", "5", "6"],
+    );
+    check(
+        &mut database,
+        "select id from t where b not between 2 and a order by id",
+        &["1", "6"],
+    );
+    check(
+        &mut database,
+        "select case when a = b then 'eq' when not (a = b) then 'ne' else 'unknown' end
+         from t order by id",
+        &[
+            "'eq'",
+            "'unknown'",
+            "'unknown'",
+            "'unknown'",
+            "'eq'",
+            "'ne'",
+        ],
+    );
+}
+
+#[test]
+fn is_tests() {
+    let mut database = database();
+    check(
+        &mut database,
+        "select id from t where a is null order by id",
+        &["3", "4"],
+    );
+    check(
+        &mut database,
+        "select id from t where b is not null order by id",
+        &["1", "3", "5", "6"],
+    );
+    check(
+        &mut database,
+        "select a is distinct from b, a is not distinct from b from t order by id",
+        &[
+            "FALSE, TRUE",
+            "TRUE, FALSE",
+            "TRUE, FALSE",
+            "FALSE, TRUE",
+            "FALSE, TRUE",
+            "TRUE, FALSE",
+        ],
+    );
+    check(
+        &mut database,
+        "select null is null, null is distinct from null, 1 is distinct from null,
+            null is not distinct from 1, (x and y) is null
+         from truth where id = 3",
+        &["TRUE, FALSE, TRUE, FALSE, TRUE"],
+    );
+    check(
+        &mut database,
+        "select l.id, r.id from t l join t r on l.a is not distinct from r.b
+         where l.a is null order by 1, 2",
+        &["3, 2", "3, 4", "4, 2", "4, 4"],
+    );
+}
+
+#[test]
+fn grouping_and_aggregates() {
+    let mut database = database();
+    check(
+        &mut database,
+        "select a, count(*), count(b) from t group by a order by a",
+        &["1, 2, 1", "2, 1, 1", "3, 1, 1", "NULL, 2, 1"],
+    );
+    check(
+        &mut database,
+        "select distinct a from t order by a",
+        &["1", "2", "3", "NULL"],
+    );
+    check(
+        &mut database,
+        "select count(*), count(a), sum(a), min(b), max(b) from t",
+        &["6, 4, 7, 1, 2"],
+    );
+    check(
+        &mut database,
+        "select count(a), sum(a), max(s) from t where id > 10",
+        &["0, NULL, NULL"],
+    );
+}
+
+#[test]
+fn ordering() {
+    let mut database = database();
+    check(
+        &mut database,
+        "select id from t order by a, id",
+        &["1", "2", "5", "6", "3", "4"],
+    );
+    check(
+        &mut database,
+        "select id from t order by a desc, id",
+        &["3", "4", "6", "5", "1", "2"],
+    );
+    check(
+        &mut database,
+        "select id from t order by a nulls first, id",
+        &["3", "4", "1", "2", "5", "6"],
+    );
+    check(
+        &mut database,
+        "select id from t order by a desc nulls last, id desc",
+        &["6", "5", "2", "1", "4", "3"],
+    );
+    check(
+        &mut database,
+        "select id, row_number() over (order by b nulls first, id) from t order by id",
+        &["1, 3", "2, 1", "3, 5", "4, 2", "5, 6", "6, 4"],
+    );
+}
+
+#[test]
+fn in_lists_and_subqueries() {
+    let mut database = database();
+    check(
+        &mut database,
+        "select a in (1, null), a not in (1, null), a in (1, 2), a not in (1, 2)
+         from t order by id",
+        &[
+            "TRUE, FALSE, TRUE, FALSE",
+            "TRUE, FALSE, TRUE, FALSE",
+            "NULL, NULL, NULL, NULL",
+            "NULL, NULL, NULL, NULL",
+            "NULL, NULL, TRUE, FALSE",
+            "NULL, NULL, FALSE, TRUE",
+        ],
+    );
+    check(
+        &mut database,
+        "select id from t where a in (select b from t) order by id",
+        &["1", "2", "5"],
+    );
+    check(
+        &mut database,
+        "select id from t where a not in (select b from t where b is not null)",
+        &["6"],
+    );
+    // a `NULL` in the subquery makes `NOT IN` unknown for every row it doesn't contain
+    check(
+        &mut database,
+        "select id from t where a not in (select b from t)",
+        &[],
+    );
+    check(
+        &mut database,
+        "select id from t where a not in (select b from t where false) order by id",
+        &["1", "2", "3", "4", "5", "6"],
+    );
+}
+
+#[test]
+fn joins() {
+    let mut database = database();
+    // `NULL` keys match nothing
+    check(
+        &mut database,
+        "select l.id, r.id from t l join t r on l.a = r.b order by 1, 2",
+        &["1, 1", "1, 6", "2, 1", "2, 6", "5, 3", "5, 5"],
+    );
+    check(
+        &mut database,
+        "select l.id from t l left join t r on l.a = r.b where r.id is null order by 1",
+        &["3", "4", "6"],
+    );
+}
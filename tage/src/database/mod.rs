@@ -0,0 +1,1914 @@
+//! A database over a key-value store, the statements of a SQL string are parsed,
+//! analysed, planned and executed one after the other.
+//!
+//! DDL changes the catalog in a transaction of its own. Queries and DML are planned
+//! against the committed catalog, optimized and executed by the engine, the writes of a
+//! statement which fails are undone. Calls resolve to the built-in functions and to the
+//! functions registered with the database. Statements prepared with `prepare` are kept
+//! until they are deallocated, `execute` runs them with the values of their parameters.
+//! Statements prepared with [`Database::prepare`] belong to the caller, they are run by
+//! [`Database::execute_prepared`].
+
+use crate::analyser::bound::{BoundQuery, ResultColumn};
+use crate::analyser::{analyse, AnalyseError};
+use crate::catalog::statistics::TableStatistics;
+use crate::catalog::system::{CatalogSnapshot, SystemCatalog};
+use crate::catalog::{Catalog, CatalogError, Index, Table, TableId, View};
+use crate::config::{max_workers, Config};
+use crate::execution::parallel::parallelize;
+use crate::execution::pattern::PatternCache;
+use crate::execution::physical::PhysicalPlan;
+use crate::execution::spill::QueryMemory;
+use crate::execution::table::{
+    drop_index, drop_table, index_lookup, index_values, insert_index_entry, scan_rows, RowScan,
+};
+use crate::execution::{execute_in, ExecutionContext, ExecutionError, ExecutionMode, Row};
+use crate::functions::aggregate::AggregateFunction;
+use crate::functions::table::TableFunction;
+use crate::functions::{FunctionRegistry, ScalarFunction};
+use crate::optimizer::optimize;
+use crate::parser::expr::{Expr, Literal, Parameter};
+use crate::parser::{ParseError, Queries, Query, SetVariableQuery, ShowQuery};
+use crate::planner::{build_plan, explain};
+use crate::prepared::{
+    BindError, BoundStatement, PrepareError, PreparedStatement, PreparedStatements,
+};
+use crate::storage::{KvStore, UndoStore};
+use crate::value::{DataType, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub struct Database<S: KvStore> {
+    store: S,
+    catalog: SystemCatalog,
+    config: Config,
+    mode: ExecutionMode,
+    /// Threads a query is executed by, `set max_parallel_workers` changes it for the session
+    max_parallel_workers: usize,
+    /// The built-in functions and the registered ones
+    functions: FunctionRegistry,
+    /// Statements the session prepared with `prepare`
+    prepared: PreparedStatements,
+}
+
+/// The committed catalog with the functions of the database, statements are planned and
+/// executed against it.
+struct DatabaseCatalog<'d> {
+    snapshot: &'d CatalogSnapshot,
+    functions: &'d FunctionRegistry,
+}
+
+impl Catalog for DatabaseCatalog<'_> {
+    fn table(&self, schema_name: &str, table_name: &str) -> Option<&Table> {
+        self.snapshot.table(schema_name, table_name)
+    }
+
+    fn view(&self, schema_name: &str, name: &str) -> Option<&View> {
+        self.snapshot.view(schema_name, name)
+    }
+
+    fn default_schema(&self) -> &str {
+        self.snapshot.default_schema()
+    }
+
+    fn statistics(&self, table: TableId) -> Option<&TableStatistics> {
+        self.snapshot.statistics(table)
+    }
+
+    fn indexes(&self, table: TableId) -> Vec<&Index> {
+        Catalog::indexes(self.snapshot, table)
+    }
+
+    fn system_rows(&self, table: TableId) -> Option<Vec<Vec<Value>>> {
+        self.snapshot.system_rows(table)
+    }
+
+    fn functions(&self) -> &FunctionRegistry {
+        self.functions
+    }
+}
+
+/// Result of a statement, statements which return no rows have no columns.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QueryResult {
+    pub columns: Vec<ResultColumn>,
+    pub rows: Vec<Row>,
+    /// Rows an `insert`, `update` or `delete` changed
+    pub rows_affected: u64,
+}
+
+impl QueryResult {
+    fn empty() -> Self {
+        QueryResult {
+            columns: vec![],
+            rows: vec![],
+            rows_affected: 0,
+        }
+    }
+}
+
+impl<S: KvStore> Database<S> {
+    /// Opens the database in the store, an empty store gets a new database.
+    pub fn open(store: S) -> Result<Self, DatabaseError> {
+        Database::open_with_config(store, Config::default())
+    }
+
+    pub fn open_with_config(mut store: S, config: Config) -> Result<Self, DatabaseError> {
+        let catalog = SystemCatalog::open(&mut store)?;
+        Ok(Database {
+            store,
+            catalog,
+            max_parallel_workers: config.max_parallel_workers.min(max_workers()),
+            config,
+            mode: ExecutionMode::default(),
+            functions: FunctionRegistry::builtin(),
+            prepared: PreparedStatements::default(),
+        })
+    }
+
+    /// Makes the function callable in the following statements, it replaces a function
+    /// of the same name, a built-in one included.
+    pub fn register_function(&mut self, function: ScalarFunction) {
+        self.functions.register(function);
+    }
+
+    pub fn register_aggregate(&mut self, function: impl AggregateFunction + 'static) {
+        self.functions.register_aggregate(Arc::new(function));
+    }
+
+    pub fn register_table_function(&mut self, function: impl TableFunction + 'static) {
+        self.functions.register_table(Arc::new(function));
+    }
+
+    /// Executes the following queries a row or a batch of rows at a time.
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    /// Executes the statements of the input, the first one which fails stops the rest.
+    pub fn execute(&mut self, input: &str) -> Result<Vec<QueryResult>, DatabaseError> {
+        Queries::parse(input)?
+            .queries
+            .iter()
+            .map(|query| self.execute_query(query))
+            .collect()
+    }
+
+    /// Parses a single `select`, `insert`, `update` or `delete` and infers the types of its
+    /// parameters against the committed catalog.
+    pub fn prepare(&self, input: &str) -> Result<PreparedStatement, DatabaseError> {
+        let snapshot = self.catalog.snapshot();
+        let catalog = DatabaseCatalog {
+            snapshot: &snapshot,
+            functions: &self.functions,
+        };
+        Ok(PreparedStatement::new(input, &catalog)?)
+    }
+
+    /// Executes a statement of [`Database::prepare`] with the values bound to it.
+    pub fn execute_prepared(
+        &mut self,
+        statement: &BoundStatement,
+    ) -> Result<QueryResult, DatabaseError> {
+        self.execute_statement(statement.query(), &statement.parameters()?)
+    }
+
+    pub fn execute_query(&mut self, query: &Query) -> Result<QueryResult, DatabaseError> {
+        match query {
+            Query::Select(_) | Query::Insert(_) | Query::Update(_) | Query::Delete(_) => {}
+            Query::Prepare(prepare) => {
+                let snapshot = self.catalog.snapshot();
+                let catalog = DatabaseCatalog {
+                    snapshot: &snapshot,
+                    functions: &self.functions,
+                };
+                self.prepared.prepare(prepare, &catalog)?;
+                return Ok(QueryResult::empty());
+            }
+            Query::Execute(execute) => {
+                let bound = self.prepared.execute(execute)?;
+                let (query, parameters) = (bound.query().clone(), bound.parameters()?);
+                return self.execute_statement(&query, &parameters);
+            }
+            Query::Deallocate(deallocate) => {
+                self.prepared.deallocate(deallocate)?;
+                return Ok(QueryResult::empty());
+            }
+            Query::Explain(explain) => return self.explain(&explain.query, explain.analyze),
+            Query::SetVariable(set) => return self.set(set),
+            Query::Show(show) => return self.show(show),
+            Query::Analyze(analyze) => {
+                let mut transaction = self.catalog.begin();
+                let store = &self.store;
+                transaction.analyze(analyze, |table| -> Result<_, DatabaseError> {
+                    let rows = scan_rows(store, table)?;
+                    Ok(rows.into_iter().map(|(_, row)| row).collect())
+                })?;
+                self.catalog.commit(transaction, &mut self.store)?;
+                return Ok(QueryResult::empty());
+            }
+            query => return self.execute_ddl(query),
+        }
+        self.execute_statement(query, &[])
+    }
+
+    /// Executes a `select`, `insert`, `update` or `delete` with the values of its
+    /// parameters. A statement which fails leaves the stored rows as they were.
+    fn execute_statement(
+        &mut self,
+        query: &Query,
+        parameters: &[(Parameter, Value)],
+    ) -> Result<QueryResult, DatabaseError> {
+        let snapshot = self.catalog.snapshot();
+        let catalog = DatabaseCatalog {
+            snapshot: &snapshot,
+            functions: &self.functions,
+        };
+        let bound = analyse(query, &catalog)?;
+        let plan = optimize(build_plan(&bound).map_err(ExecutionError::from)?, &catalog);
+        let mut physical = PhysicalPlan::build(&plan, &catalog)?;
+        if let BoundQuery::Select(_) = &bound {
+            physical = parallelize(physical, self.max_parallel_workers);
+        }
+        let mut store = UndoStore::new(&mut self.store);
+        let mut context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::new(self.config.memory_budget, &self.config.temp_directory),
+            worker: None,
+            start_time: SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters,
+        };
+        let result = execute_in(physical, self.mode, &mut context);
+        drop(context);
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(error) => {
+                store.rollback().map_err(ExecutionError::from)?;
+                return Err(error.into());
+            }
+        };
+        Ok(match &bound {
+            BoundQuery::Select(_) => QueryResult {
+                columns: bound.result_columns(),
+                rows,
+                rows_affected: 0,
+            },
+            _ => QueryResult {
+                columns: bound.result_columns(),
+                rows_affected: rows.len() as u64,
+                rows: match bound.result_columns().is_empty() {
+                    true => vec![],
+                    false => rows,
+                },
+            },
+        })
+    }
+
+    /// The optimized plan of a statement, one row per line.
+    fn explain(&mut self, query: &Query, analyze: bool) -> Result<QueryResult, DatabaseError> {
+        if analyze {
+            return Err(ExecutionError::Unsupported("EXPLAIN ANALYZE".to_string()).into());
+        }
+        let snapshot = self.catalog.snapshot();
+        let catalog = DatabaseCatalog {
+            snapshot: &snapshot,
+            functions: &self.functions,
+        };
+        let bound = analyse(query, &catalog)?;
+        let plan = optimize(build_plan(&bound).map_err(ExecutionError::from)?, &catalog);
+        Ok(QueryResult {
+            columns: vec![ResultColumn {
+                name: "QUERY PLAN".to_string(),
+                data_type: DataType::Text,
+            }],
+            rows: explain(&plan)
+                .lines()
+                .map(|line| vec![Value::String(line.to_string())])
+                .collect(),
+            rows_affected: 0,
+        })
+    }
+
+    /// Changes a setting of the session.
+    fn set(&mut self, set: &SetVariableQuery) -> Result<QueryResult, DatabaseError> {
+        let value = match &set.value {
+            Expr::Literal(Literal::Numeric(value) | Literal::String(value)) => value.as_str(),
+            value => return Err(invalid_value(&set.name, &value.to_string())),
+        };
+        match set.name.as_str() {
+            "max_parallel_workers" => {
+                let workers: usize = value.parse().map_err(|_| invalid_value(&set.name, value))?;
+                self.max_parallel_workers = workers.min(max_workers());
+            }
+            name => return Err(DatabaseError::UnknownSetting(name.to_string())),
+        }
+        Ok(QueryResult::empty())
+    }
+
+    /// The value of a setting, `show all` returns every setting with its name.
+    fn show(&self, show: &ShowQuery) -> Result<QueryResult, DatabaseError> {
+        let settings = [(
+            "max_parallel_workers",
+            self.max_parallel_workers.to_string(),
+        )];
+        let column = |name: &str| ResultColumn {
+            name: name.to_string(),
+            data_type: DataType::Text,
+        };
+        Ok(match &show.name {
+            None => QueryResult {
+                columns: vec![column("name"), column("setting")],
+                rows: settings
+                    .into_iter()
+                    .map(|(name, value)| vec![Value::from(name), Value::String(value)])
+                    .collect(),
+                rows_affected: 0,
+            },
+            Some(name) => {
+                let (name, value) = settings
+                    .into_iter()
+                    .find(|(setting, _)| setting == name)
+                    .ok_or_else(|| DatabaseError::UnknownSetting(name.clone()))?;
+                QueryResult {
+                    columns: vec![column(name)],
+                    rows: vec![vec![Value::String(value)]],
+                    rows_affected: 0,
+                }
+            }
+        })
+    }
+
+    /// Changes the catalog and the stored rows and index entries of the tables and indexes
+    /// the statement created or dropped. The entries of new indexes are written before the
+    /// catalog is committed, a statement which fails to write them changes nothing.
+    fn execute_ddl(&mut self, query: &Query) -> Result<QueryResult, DatabaseError> {
+        let before = self.catalog.snapshot();
+        let mut transaction = self.catalog.begin();
+        match transaction.execute(query) {
+            Err(CatalogError::NotDdl) => {
+                return Err(ExecutionError::Unsupported(format!("statement {}", query)).into())
+            }
+            result => result?,
+        }
+        let mut store = UndoStore::new(&mut self.store);
+        let result = fill_indexes(&mut store, &before, transaction.catalog())
+            .and_then(|()| Ok(self.catalog.commit(transaction, &mut store)?));
+        if let Err(error) = result {
+            store.rollback().map_err(ExecutionError::from)?;
+            return Err(error);
+        }
+        let after = self.catalog.snapshot();
+        for table in before.tables() {
+            if !after.tables().iter().any(|kept| kept.id == table.id) {
+                drop_table(&mut self.store, table.id)?;
+            }
+        }
+        for index in before.indexes() {
+            if !after.indexes().iter().any(|kept| kept.id == index.id) {
+                drop_index(&mut self.store, index.id)?;
+            }
+        }
+        Ok(QueryResult::empty())
+    }
+}
+
+/// Writes the entries of the rows of their tables to the indexes `after` has and `before`
+/// doesn't. Fails if a unique index gets the same values twice.
+fn fill_indexes(
+    store: &mut dyn KvStore,
+    before: &CatalogSnapshot,
+    after: &CatalogSnapshot,
+) -> Result<(), DatabaseError> {
+    for index in after.indexes() {
+        if before
+            .indexes()
+            .iter()
+            .any(|existing| existing.id == index.id)
+        {
+            continue;
+        }
+        let table: &Table = after
+            .tables()
+            .iter()
+            .find(|table| table.id == index.table)
+            .expect("table of the index");
+        let mut rows = RowScan::new(store, table)?;
+        while let Some((row_id, row)) = rows.next(store)? {
+            let values = index_values(index, table, &row);
+            if index.unique
+                && !values.contains(&Value::Null)
+                && !index_lookup(store, index, &values)?.is_empty()
+            {
+                return Err(ExecutionError::UniqueViolation {
+                    constraint_name: index.name.clone(),
+                }
+                .into());
+            }
+            insert_index_entry(store, index, &values, row_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DatabaseError {
+    Parse(ParseError),
+    Analyse(AnalyseError),
+    Catalog(CatalogError),
+    Execution(ExecutionError),
+    Prepare(PrepareError),
+    Bind(BindError),
+    UnknownSetting(String),
+    InvalidSetting { name: String, value: String },
+}
+
+fn invalid_value(name: &str, value: &str) -> DatabaseError {
+    DatabaseError::InvalidSetting {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+impl From<ParseError> for DatabaseError {
+    fn from(error: ParseError) -> Self {
+        DatabaseError::Parse(error)
+    }
+}
+
+impl From<AnalyseError> for DatabaseError {
+    fn from(error: AnalyseError) -> Self {
+        DatabaseError::Analyse(error)
+    }
+}
+
+impl From<CatalogError> for DatabaseError {
+    fn from(error: CatalogError) -> Self {
+        DatabaseError::Catalog(error)
+    }
+}
+
+impl From<ExecutionError> for DatabaseError {
+    fn from(error: ExecutionError) -> Self {
+        DatabaseError::Execution(error)
+    }
+}
+
+impl From<PrepareError> for DatabaseError {
+    fn from(error: PrepareError) -> Self {
+        DatabaseError::Prepare(error)
+    }
+}
+
+impl From<BindError> for DatabaseError {
+    fn from(error: BindError) -> Self {
+        DatabaseError::Bind(error)
+    }
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Parse(error) => write!(f, "{}", error),
+            DatabaseError::Analyse(error) => write!(f, "{}", error),
+            DatabaseError::Catalog(error) => write!(f, "{}", error),
+            DatabaseError::Execution(error) => write!(f, "{}", error),
+            DatabaseError::Prepare(error) => write!(f, "{}", error),
+            DatabaseError::Bind(error) => write!(f, "{}", error),
+            DatabaseError::UnknownSetting(name) => {
+                write!(f, "unrecognized configuration parameter \"{}\"", name)
+            }
+            DatabaseError::InvalidSetting { name, value } => {
+                write!(f, "invalid value for parameter \"{}\": \"{}\"", name, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod conformance;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::expr::ScalarExpr;
+    use crate::execution::spill::tests::{spilled_files, temp_directory};
+    use crate::functions::aggregate::AggregateState;
+    use crate::functions::{NullHandling, ResolveError, Signature, Volatility};
+    use crate::storage::{DiskStore, MemoryStore};
+    use std::fs;
+    use std::ops::Bound;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    fn database() -> Database<MemoryStore> {
+        let mut database = Database::open(MemoryStore::new()).unwrap();
+        database
+            .execute(
+                "create table users (id integer primary key, name text not null, age integer);
+                 create table orders (id integer primary key, user_id integer, total integer);
+                 insert into users values (1, 'ann', 1), (2, 'bob', 30), (3, 'cid', 1), (4, 'dan', null);
+                 insert into orders values (1, 1, 10), (2, 1, 20), (3, 2, 5), (4, 9, 1);",
+            )
+            .unwrap();
+        database
+    }
+
+    fn query(database: &mut Database<MemoryStore>, input: &str) -> Vec<Row> {
+        database.execute(input).unwrap().pop().unwrap().rows
+    }
+
+    fn row(values: &[Value]) -> Row {
+        values.to_vec()
+    }
+
+    fn decimal(text: &str) -> Value {
+        crate::value::Decimal::parse(text).unwrap().into()
+    }
+
+    fn timestamp(text: &str) -> Value {
+        crate::value::temporal::parse(text, DataType::Timestamp).unwrap()
+    }
+
+    #[test]
+    fn selects_rows() {
+        let mut database = database();
+        let result = database
+            .execute("SELECT * FROM users WHERE age = 1;")
+            .unwrap();
+        assert_eq!(
+            result[0]
+                .columns
+                .iter()
+                .map(|column| column.name.as_str())
+                .collect::<Vec<_>>(),
+            ["id", "name", "age"]
+        );
+        assert_eq!(
+            result[0].rows,
+            [
+                row(&[1.into(), "ann".into(), 1.into()]),
+                row(&[3.into(), "cid".into(), 1.into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_groups_and_sorts() {
+        let mut database = database();
+        assert_eq!(
+            query(
+                &mut database,
+                "select u.name, count(*), sum(o.total) from users u join orders o on o.user_id = u.id
+                 group by u.name order by u.name"
+            ),
+            [
+                row(&["ann".into(), 2.into(), 30.into()]),
+                row(&["bob".into(), 1.into(), 5.into()]),
+            ]
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select u.name, o.id from users u left join orders o on o.user_id = u.id
+                 where o.id is null order by 1 desc limit 1"
+            ),
+            [row(&["dan".into(), Value::Null])]
+        );
+        assert_eq!(
+            query(&mut database, "select distinct age from users order by age"),
+            [row(&[1.into()]), row(&[30.into()]), row(&[Value::Null])]
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select name from users u where exists (select 1 from orders o where o.user_id = u.id and o.total > 15)"
+            ),
+            [row(&["ann".into()])]
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select count(*), max(age), avg(age) from users where age > 100"
+            ),
+            [row(&[0.into(), Value::Null, Value::Null])]
+        );
+    }
+
+    #[test]
+    fn changes_rows() {
+        let mut database = database();
+        let result = database
+            .execute("update users set age = age + 1 where age = 1 returning name, age")
+            .unwrap();
+        assert_eq!(result[0].rows_affected, 2);
+        assert_eq!(
+            result[0].rows,
+            [
+                row(&["ann".into(), 2.into()]),
+                row(&["cid".into(), 2.into()])
+            ]
+        );
+        let result = database
+            .execute("delete from orders where user_id = 1")
+            .unwrap();
+        assert_eq!(result[0].rows_affected, 2);
+        assert_eq!(
+            query(&mut database, "select id from orders order by id"),
+            [row(&[3.into()]), row(&[4.into()])]
+        );
+        assert_eq!(
+            database.execute("insert into users values (1, 'eve', 5)"),
+            Err(DatabaseError::Execution(ExecutionError::UniqueViolation {
+                constraint_name: "users_pkey".to_string()
+            }))
+        );
+        assert_eq!(
+            database.execute("insert into users (id) values (5)"),
+            Err(DatabaseError::Execution(ExecutionError::NotNullViolation {
+                column_name: "name".to_string()
+            }))
+        );
+        database
+            .execute(
+                "insert into users values (1, 'eve', 5) on conflict (id) do update set name = excluded.name;
+                 insert into users values (2, 'eve', 5) on conflict do nothing;",
+            )
+            .unwrap();
+        assert_eq!(
+            query(
+                &mut database,
+                "select name from users where id <= 2 order by id"
+            ),
+            [row(&["eve".into()]), row(&["bob".into()])]
+        );
+    }
+
+    #[test]
+    fn failed_statements_change_nothing() {
+        let mut database = database();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            assert!(matches!(
+                database.execute("insert into users values (5, 'eve', 1), (5, 'fay', 2)"),
+                Err(DatabaseError::Execution(
+                    ExecutionError::UniqueViolation { .. }
+                ))
+            ));
+            assert_eq!(
+                database.execute("update users set age = age / (id - 2)"),
+                Err(DatabaseError::Execution(ExecutionError::DivisionByZero))
+            );
+            assert_eq!(
+                database.execute(
+                    "insert into users values (6, 'gus', 1), (6, 'hal', 2) \
+                     on conflict (id) do update set name = excluded.name"
+                ),
+                Err(DatabaseError::Execution(ExecutionError::RowAffectedTwice))
+            );
+            assert_eq!(
+                database.execute(
+                    "insert into users values (1, 'x', 1), (1, 'y', 2) \
+                     on conflict (id) do update set name = excluded.name"
+                ),
+                Err(DatabaseError::Execution(ExecutionError::RowAffectedTwice))
+            );
+            assert_eq!(
+                query(&mut database, "select * from users order by id"),
+                [
+                    row(&[1.into(), "ann".into(), 1.into()]),
+                    row(&[2.into(), "bob".into(), 30.into()]),
+                    row(&[3.into(), "cid".into(), 1.into()]),
+                    row(&[4.into(), "dan".into(), Value::Null]),
+                ]
+            );
+        }
+        // the same row may still be inserted twice when the second one is skipped
+        database
+            .execute("insert into users values (7, 'ida', 1), (7, 'jo', 2) on conflict do nothing")
+            .unwrap();
+
+        assert_eq!(
+            database.execute("create unique index users_age on users (age)"),
+            Err(DatabaseError::Execution(ExecutionError::UniqueViolation {
+                constraint_name: "users_age".to_string()
+            }))
+        );
+        assert!(database
+            .catalog
+            .snapshot()
+            .index("public", "users_age")
+            .is_none());
+        database
+            .execute("create unique index users_name on users (name)")
+            .unwrap();
+        assert_eq!(
+            database.execute("insert into users values (8, 'ann', 1)"),
+            Err(DatabaseError::Execution(ExecutionError::UniqueViolation {
+                constraint_name: "users_name".to_string()
+            }))
+        );
+        // nothing is left of the index which failed
+        database
+            .execute("create index users_age on users (age)")
+            .unwrap();
+        assert_eq!(
+            query(&mut database, "select name from users where age = 30"),
+            [row(&["bob".into()])]
+        );
+    }
+
+    #[test]
+    fn uses_indexes() {
+        let mut database = database();
+        database
+            .execute("create index users_age on users (age); analyze;")
+            .unwrap();
+        let snapshot = database.catalog.snapshot();
+        let queries = Queries::parse("select name from users where age >= 2").unwrap();
+        let bound = analyse(&queries.queries[0], snapshot.as_ref()).unwrap();
+        let plan = build_plan(&bound).unwrap();
+        let physical = PhysicalPlan::build(&optimize(plan, snapshot.as_ref()), snapshot.as_ref());
+        assert!(matches!(
+            physical,
+            Ok(PhysicalPlan::Project { input, .. })
+                if matches!(*input, PhysicalPlan::Filter { ref input, .. }
+                    if matches!(**input, PhysicalPlan::IndexScan { .. }))
+        ));
+        assert_eq!(
+            query(
+                &mut database,
+                "select name from users where age >= 2 and age < 40"
+            ),
+            [row(&["bob".into()])]
+        );
+        database
+            .execute("update users set age = 40 where id = 2")
+            .unwrap();
+        assert!(query(
+            &mut database,
+            "select name from users where age >= 2 and age < 40"
+        )
+        .is_empty());
+        database.execute("drop table users").unwrap();
+        assert!(database.execute("select * from users").is_err());
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let mut database = database();
+        database
+            .execute(
+                "insert into users values (5, 'B_b', 2), (6, 'bo%', 3);
+                 create index users_name on users (name); analyze;",
+            )
+            .unwrap();
+        let snapshot = database.catalog.snapshot();
+        let queries = Queries::parse("select id from users where name like 'bo%'").unwrap();
+        let bound = analyse(&queries.queries[0], snapshot.as_ref()).unwrap();
+        let plan = build_plan(&bound).unwrap();
+        let physical = PhysicalPlan::build(&optimize(plan, snapshot.as_ref()), snapshot.as_ref());
+        assert!(matches!(
+            physical,
+            Ok(PhysicalPlan::Project { input, .. })
+                if matches!(*input, PhysicalPlan::Filter { ref input, .. }
+                    if matches!(**input, PhysicalPlan::IndexScan { ref low, .. }
+                        if matches!(low, Bound::Included(ScalarExpr::Literal(Value::String(prefix)))
+                            if prefix == "bo")))
+        ));
+        let plan: Vec<Row> = [
+            "Project: users.id",
+            "  Filter: users.name LIKE 'bo%'",
+            "    Scan: public.users (id, name) [index users_name: users.name >= 'bo' AND users.name < 'bp']",
+        ]
+        .into_iter()
+        .map(|line| row(&[line.into()]))
+        .collect();
+        assert_eq!(
+            query(
+                &mut database,
+                "explain select id from users where name like 'bo%'"
+            ),
+            plan
+        );
+        for (input, expected) in [
+            ("name like 'bo%'", vec![2, 6]),
+            ("name like 'bo\\%'", vec![6]),
+            ("name like 'bo#%' escape '#'", vec![6]),
+            ("name not like '%n'", vec![2, 3, 5, 6]),
+            ("name ilike 'b%'", vec![2, 5, 6]),
+            ("name like '_o_'", vec![2, 6]),
+            ("name like 'B\\_b'", vec![5]),
+            ("name similar to '(a|c)%'", vec![1, 3]),
+            ("name similar to 'b_%' escape ''", vec![2, 6]),
+            ("name ~ '^[a-c].[dn]$'", vec![1, 3]),
+            ("name ~* 'b$'", vec![2, 5]),
+            ("name !~ 'n'", vec![2, 3, 5, 6]),
+            ("name like substring(name from 1 for 1) || '%n'", vec![1, 4]),
+        ] {
+            let input = format!("select id from users where {} order by id", input);
+            for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+                database.set_execution_mode(mode);
+                let ids: Vec<_> = expected.iter().map(|id| row(&[(*id).into()])).collect();
+                assert_eq!(query(&mut database, &input), ids, "{}", input);
+            }
+        }
+        assert_eq!(
+            query(&mut database, "select null like 'a', 'a' ~ null"),
+            [row(&[Value::Null, Value::Null])]
+        );
+        for input in [
+            "select name ~ '(' from users",
+            "select name like 'a' escape 'ab' from users",
+            "select name like 'a\\' from users",
+        ] {
+            assert!(database.execute(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn stores_typed_values() {
+        let mut database = database();
+        database
+            .execute(
+                "create table payments (id integer primary key, amount decimal(10, 2), day date,
+                    at timestamp with time zone);
+                 create index payments_amount on payments (amount);
+                 create index payments_day on payments (day);
+                 insert into payments values
+                    (1, 0.1, '2024-03-01', '2024-03-01 10:00:00+02'),
+                    (2, 0.2, '2024-02-29', '2024-03-01 08:00:00Z'),
+                    (3, -12.345, '2024-03-01', '2024-03-01 09:30:00-01:00'),
+                    (4, null, null, null);",
+            )
+            .unwrap();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            // decimals are exact and rounded to the scale of the column
+            assert_eq!(
+                query(
+                    &mut database,
+                    "select sum(amount) from payments where id < 3"
+                )[0][0]
+                    .to_string(),
+                "0.30"
+            );
+            assert_eq!(
+                query(&mut database, "select amount from payments where id = 3"),
+                [row(&[decimal("-12.35")])]
+            );
+            assert_eq!(
+                query(
+                    &mut database,
+                    "select id from payments where amount >= 0.1 and amount < 1 order by amount desc"
+                ),
+                [row(&[2.into()]), row(&[1.into()])]
+            );
+            assert_eq!(
+                query(
+                    &mut database,
+                    "select day, count(*) from payments group by day order by day"
+                ),
+                [
+                    row(&[Value::Date(19782), 1.into()]),
+                    row(&[Value::Date(19783), 2.into()]),
+                    row(&[Value::Null, 1.into()]),
+                ]
+            );
+            // time zones are converted to UTC, the same instant is equal
+            assert_eq!(
+                query(
+                    &mut database,
+                    "select id from payments where at = timestamptz '2024-03-01 08:00:00+00'
+                     order by id"
+                ),
+                [row(&[1.into()]), row(&[2.into()])]
+            );
+        }
+        assert_eq!(
+            query(&mut database, "select at from payments where id = 3")[0][0].text(),
+            "2024-03-01 10:30:00+00"
+        );
+        assert!(database
+            .execute("insert into payments values (5, 123456789.5, null, null)")
+            .is_err());
+    }
+
+    #[test]
+    fn types_decimal_results_by_their_digits() {
+        let mut database = database();
+        let results = database
+            .execute(
+                "create table n (n numeric(4, 1)); insert into n values (1.3), (2.5);
+                 select n * n, n / 4, n + n, n % 1 from n order by n",
+            )
+            .unwrap();
+        let result = results.last().unwrap();
+        assert!(result
+            .columns
+            .iter()
+            .all(|column| column.data_type == DataType::Decimal(None)));
+        assert_eq!(
+            result.rows,
+            [
+                row(&[
+                    decimal("1.69"),
+                    decimal("0.325"),
+                    decimal("2.6"),
+                    decimal("0.3")
+                ]),
+                row(&[
+                    decimal("6.25"),
+                    decimal("0.625"),
+                    decimal("5.0"),
+                    decimal("0.5")
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_integer_results_against_their_types() {
+        let mut database = database();
+        database
+            .execute(
+                "create table i (a integer, b smallint);
+                 insert into i values (2147483647, 32767), (1, 1)",
+            )
+            .unwrap();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            for (input, data_type) in [
+                ("select a + 1 from i", DataType::Integer),
+                ("select a * 2 from i", DataType::Integer),
+                ("select -b - b from i", DataType::SmallInt),
+                ("select b + b from i", DataType::SmallInt),
+            ] {
+                assert_eq!(
+                    database.execute(input).unwrap_err(),
+                    DatabaseError::Execution(ExecutionError::OutOfRange { data_type }),
+                    "{} {:?}",
+                    input,
+                    mode
+                );
+            }
+            assert_eq!(
+                query(&mut database, "select a + 1, b * 2 from i where a = 1"),
+                [row(&[2.into(), 2.into()])]
+            );
+            assert_eq!(
+                query(&mut database, "select a + 3000000000 from i where a > 1"),
+                [row(&[5147483647i64.into()])]
+            );
+        }
+    }
+
+    #[test]
+    fn reads_numbers_with_exponents() {
+        let mut database = database();
+        assert_eq!(
+            query(&mut database, "select 1e5, 2.5E-1 * 2"),
+            [row(&[decimal("100000"), decimal("0.50")])]
+        );
+        database
+            .execute("create table f (x double); insert into f values (1e308), (-2e-3)")
+            .unwrap();
+        assert_eq!(
+            query(&mut database, "select x from f order by x"),
+            [row(&[Value::Float(-0.002)]), row(&[Value::Float(1e308)])]
+        );
+    }
+
+    #[test]
+    fn computes_decimals_alike_in_batches() {
+        let mut database = database();
+        database
+            .execute("create table t (a integer); insert into t values (1), (2), (3)")
+            .unwrap();
+        for (input, expected) in [
+            (
+                "select a from t where a + 0.1 = 1.1",
+                vec![row(&[1.into()])],
+            ),
+            ("select sum(a * 0.1) from t", vec![row(&[decimal("0.6")])]),
+            (
+                "select a * 1.5 from t where a / 2.0 < 1 order by a",
+                vec![row(&[decimal("1.5")])],
+            ),
+            (
+                "select count(*) from t where a * 0.1 >= 0.2",
+                vec![row(&[2.into()])],
+            ),
+        ] {
+            for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+                database.set_execution_mode(mode);
+                assert_eq!(
+                    query(&mut database, input),
+                    expected,
+                    "{} {:?}",
+                    input,
+                    mode
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn executes_batches_like_rows() {
+        let mut database = database();
+        database
+            .execute("insert into orders values (5, null, 7), (6, 2, null), (7, 3, 40)")
+            .unwrap();
+        for input in [
+            "SELECT * FROM users WHERE age = 1;",
+            "select user_id, count(*), sum(total), avg(total), min(total), max(total), count(distinct total)
+             from orders group by user_id order by user_id",
+            "select count(*), sum(total * 2) from orders where total % 2 = 0 or user_id is null",
+            "select name || '!' from users where name like '%a%' and age <> 30",
+            "select u.name, o.total from users u join orders o on o.user_id = u.id
+             where o.total > 5 order by o.total limit 2 offset 1",
+            "select age, count(*) from users group by age order by age",
+        ] {
+            database.set_execution_mode(ExecutionMode::Row);
+            let rows = query(&mut database, input);
+            database.set_execution_mode(ExecutionMode::Batch);
+            assert_eq!(query(&mut database, input), rows, "{}", input);
+        }
+        assert_eq!(
+            query(
+                &mut database,
+                "select count(*), sum(total) from orders where total > 100"
+            ),
+            [row(&[0.into(), Value::Null])]
+        );
+    }
+
+    #[test]
+    fn groups_by_primary_keys() {
+        let mut database = database();
+        database
+            .execute(
+                "create table u (id int primary key, name text);
+                 insert into u values (1, 'ann'), (2, 'bob');",
+            )
+            .unwrap();
+        let cases: [(&str, Vec<Row>); 2] = [
+            (
+                "select id, name, count(*) from u group by id order by id",
+                vec![
+                    row(&[1.into(), "ann".into(), 1.into()]),
+                    row(&[2.into(), "bob".into(), 1.into()]),
+                ],
+            ),
+            (
+                "select u.name || '!', count(o.id) from users u left join orders o on o.user_id = u.id
+                 group by u.id having u.age is not null order by u.name",
+                vec![
+                    row(&["ann!".into(), 2.into()]),
+                    row(&["bob!".into(), 1.into()]),
+                    row(&["cid!".into(), 0.into()]),
+                ],
+            ),
+        ];
+        for (input, expected) in cases {
+            for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+                database.set_execution_mode(mode);
+                assert_eq!(query(&mut database, input), expected, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn inserts_the_rows_of_the_same_table() {
+        let mut database = database();
+        for (mode, offset) in [(ExecutionMode::Row, 10), (ExecutionMode::Batch, 100)] {
+            database.set_execution_mode(mode);
+            let input = format!(
+                "insert into orders select id + {}, user_id, total from orders where id < 10",
+                offset
+            );
+            assert_eq!(database.execute(&input).unwrap()[0].rows_affected, 4);
+        }
+        assert_eq!(
+            query(&mut database, "select count(*) from orders"),
+            [row(&[12.into()])]
+        );
+    }
+
+    #[test]
+    fn executes_prepared_statements() {
+        let mut database = database();
+        database
+            .execute(
+                "prepare find (integer) as select name from users where id = $1;
+                 prepare older as select name from users where age > ? order by name;
+                 prepare spent as select sum(total) from orders where user_id = :user;
+                 prepare place as insert into orders values ($1, $2, $3 * 2)",
+            )
+            .unwrap();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            for (input, expected) in [
+                ("execute find (2)", vec![row(&["bob".into()])]),
+                ("execute find (4)", vec![row(&["dan".into()])]),
+                (
+                    "execute older (0)",
+                    vec![
+                        row(&["ann".into()]),
+                        row(&["bob".into()]),
+                        row(&["cid".into()]),
+                    ],
+                ),
+                ("execute older (10)", vec![row(&["bob".into()])]),
+                ("execute spent (1)", vec![row(&[30.into()])]),
+            ] {
+                assert_eq!(
+                    query(&mut database, input),
+                    expected,
+                    "{} {:?}",
+                    input,
+                    mode
+                );
+            }
+        }
+        assert_eq!(
+            database.execute("execute place (5, 2, 7)").unwrap()[0].rows_affected,
+            1
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select user_id, total from orders where id = 5"
+            ),
+            [row(&[2.into(), 14.into()])]
+        );
+        // declared and inferred parameters take strings alike
+        for input in ["execute older ('1')", "execute find ('2')"] {
+            assert_eq!(
+                database.execute(input),
+                Err(DatabaseError::Bind(BindError::TypeMismatch {
+                    position: 1,
+                    expected: DataType::Integer,
+                    found: DataType::Text
+                })),
+                "{}",
+                input
+            );
+        }
+        database
+            .execute("deallocate find; prepare find as select 1")
+            .unwrap();
+        assert_eq!(query(&mut database, "execute find"), [row(&[1.into()])]);
+        assert_eq!(
+            database.execute("prepare find as select 2"),
+            Err(DatabaseError::Prepare(PrepareError::AlreadyExists(
+                "find".to_string()
+            )))
+        );
+        database.execute("deallocate all").unwrap();
+        assert_eq!(
+            database.execute("execute older (1)"),
+            Err(DatabaseError::Bind(BindError::NotFound(
+                "older".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn executes_statements_prepared_by_the_caller() {
+        let mut database = database();
+        let find = database
+            .prepare("select name from users where id = $1")
+            .unwrap();
+        assert_eq!(find.parameter_types(), &[Some(DataType::Integer)]);
+        let place = database
+            .prepare("insert into orders values (?, ?, ?)")
+            .unwrap();
+        for (id, name) in [(2, "bob"), (4, "dan")] {
+            let result = database
+                .execute_prepared(&find.bind(vec![id.into()]).unwrap())
+                .unwrap();
+            assert_eq!(result.rows, [row(&[name.into()])]);
+        }
+        let result = database
+            .execute_prepared(&place.bind(vec![9.into(), 2.into(), 5.into()]).unwrap())
+            .unwrap();
+        assert_eq!(result.rows_affected, 1);
+        assert_eq!(
+            query(
+                &mut database,
+                "select user_id, total from orders where id = 9"
+            ),
+            [row(&[2.into(), 5.into()])]
+        );
+        assert!(matches!(
+            database.prepare("select * from nowhere where id = $1"),
+            Err(DatabaseError::Prepare(PrepareError::Analyse(
+                AnalyseError::UnknownTable { .. }
+            )))
+        ));
+    }
+
+    #[test]
+    fn reads_views() {
+        let mut database = database();
+        database
+            .execute(
+                "create view adults as select id, name, age * 2 as doubled from users \
+                 where age > 18;
+                 create view spent as select user_id, sum(total) as total from orders \
+                 group by user_id;
+                 create view adult_names as select name from adults",
+            )
+            .unwrap();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            for (input, expected) in [
+                (
+                    "select * from adults",
+                    vec![row(&[2.into(), "bob".into(), 60.into()])],
+                ),
+                ("select * from adult_names", vec![row(&["bob".into()])]),
+                (
+                    "select u.name, s.total from users u join spent s on s.user_id = u.id \
+                     where s.total > 1 order by u.name",
+                    vec![
+                        row(&["ann".into(), 30.into()]),
+                        row(&["bob".into(), 5.into()]),
+                    ],
+                ),
+                (
+                    "select name from users where id in (select id from adults)",
+                    vec![row(&["bob".into()])],
+                ),
+                ("select count(*) from spent", vec![row(&[3.into()])]),
+            ] {
+                assert_eq!(
+                    query(&mut database, input),
+                    expected,
+                    "{} {:?}",
+                    input,
+                    mode
+                );
+            }
+        }
+        let plan: Vec<Row> = [
+            "Project: adults.id, adults.name, adults.doubled",
+            "  SubqueryAlias: adults",
+            "    Project: users.id, users.name, users.age * 2 AS doubled",
+            "      Filter: users.age > 18",
+            "        Scan: public.users",
+        ]
+        .into_iter()
+        .map(|line| row(&[line.into()]))
+        .collect();
+        assert_eq!(query(&mut database, "explain select * from adults"), plan);
+        assert!(matches!(
+            database.execute("insert into adults values (5, 'eve', 40)"),
+            Err(DatabaseError::Analyse(AnalyseError::ViewNotWritable { .. }))
+        ));
+        assert!(matches!(
+            database.execute("delete from adult_names"),
+            Err(DatabaseError::Analyse(AnalyseError::ViewNotWritable { .. }))
+        ));
+    }
+
+    #[test]
+    fn unions_rows() {
+        let mut database = database();
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            for (input, expected) in [
+                (
+                    "select id from users union all select user_id from orders order by 1",
+                    [1, 1, 1, 2, 2, 3, 4, 9].map(|id| row(&[id.into()])).to_vec(),
+                ),
+                (
+                    "select id from users union select user_id from orders \
+                     order by id desc limit 3",
+                    [9, 4, 3].map(|id| row(&[id.into()])).to_vec(),
+                ),
+                (
+                    "select name from users where id in (select user_id from orders union select 3) \
+                     order by name",
+                    ["ann", "bob", "cid"].map(|name| row(&[name.into()])).to_vec(),
+                ),
+                (
+                    "select id, name from users where id = 1 union all select 2.5, null \
+                     union all select id, name from users where id = 2 order by 1",
+                    vec![
+                        row(&[decimal("1"), "ann".into()]),
+                        row(&[decimal("2"), "bob".into()]),
+                        row(&[decimal("2.5"), Value::Null]),
+                    ],
+                ),
+            ] {
+                assert_eq!(
+                    query(&mut database, input),
+                    expected,
+                    "{} {:?}",
+                    input,
+                    mode
+                );
+            }
+        }
+        database
+            .execute(
+                "create view ids as select id from users union select user_id from orders;
+                 insert into orders select id + 10, id, 0 from users where id < 3 \
+                 union all select 20, 1, 1",
+            )
+            .unwrap();
+        assert_eq!(
+            query(
+                &mut database,
+                "select count(*), sum(total) from orders where id > 10"
+            ),
+            [row(&[3.into(), 1.into()])]
+        );
+        assert_eq!(
+            query(&mut database, "select count(*) from ids"),
+            [row(&[5.into()])]
+        );
+        let plan: Vec<Row> = [
+            "Sort: users.id",
+            "  Union",
+            "    Project: users.id",
+            "      Scan: public.users (id)",
+            "    Project: orders.user_id",
+            "      Scan: public.orders (user_id)",
+        ]
+        .into_iter()
+        .map(|line| row(&[line.into()]))
+        .collect();
+        assert_eq!(
+            query(
+                &mut database,
+                "explain select id from users union select user_id from orders order by id"
+            ),
+            plan
+        );
+        assert!(matches!(
+            database.execute("select id from users union select id, name from users"),
+            Err(DatabaseError::Analyse(AnalyseError::UnionColumnCount {
+                expected: 1,
+                found: 2
+            }))
+        ));
+        assert!(matches!(
+            database.execute("select id from users union select 1 order by id + 1"),
+            Err(DatabaseError::Analyse(AnalyseError::UnionOrderBy))
+        ));
+        assert!(matches!(
+            database.execute("select id from users union select name from users"),
+            Err(DatabaseError::Analyse(AnalyseError::IncompatibleTypes {
+                context: "UNION",
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn executes_statements_nested_to_the_limit() {
+        let mut database = database();
+        let depth = crate::parser::MAX_NESTING - 2;
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            let input = format!(
+                "select {}id{} from users where id = 1",
+                "(".repeat(depth),
+                ")".repeat(depth)
+            );
+            assert_eq!(query(&mut database, &input), [row(&[1.into()])]);
+            let input = format!(
+                "select id{} from users where id = 1",
+                " + 1".repeat(depth - 1)
+            );
+            assert_eq!(
+                query(&mut database, &input),
+                [row(&[(depth as i64).into()])]
+            );
+        }
+        assert_eq!(
+            database.execute(&format!("select 1{}", " + 1".repeat(10_000))),
+            Err(DatabaseError::Parse(ParseError::TooDeep))
+        );
+    }
+
+    #[test]
+    fn calls_functions() {
+        let mut database = database();
+        let input = "select upper(name), substring(name from 2 for 1), length(name) + age,
+                coalesce(age, 0), nullif(age, 1), round(age / 7.0, 2), name || age
+             from users order by id";
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            assert_eq!(
+                query(&mut database, input),
+                [
+                    row(&[
+                        "ANN".into(),
+                        "n".into(),
+                        4.into(),
+                        1.into(),
+                        Value::Null,
+                        decimal("0.14"),
+                        "ann1".into()
+                    ]),
+                    row(&[
+                        "BOB".into(),
+                        "o".into(),
+                        33.into(),
+                        30.into(),
+                        30.into(),
+                        decimal("4.29"),
+                        "bob30".into()
+                    ]),
+                    row(&[
+                        "CID".into(),
+                        "i".into(),
+                        4.into(),
+                        1.into(),
+                        Value::Null,
+                        decimal("0.14"),
+                        "cid1".into()
+                    ]),
+                    row(&[
+                        "DAN".into(),
+                        "a".into(),
+                        Value::Null,
+                        0.into(),
+                        Value::Null,
+                        Value::Null,
+                        Value::Null
+                    ]),
+                ],
+                "{:?}",
+                mode
+            );
+        }
+        assert_eq!(
+            query(
+                &mut database,
+                "select date '2024-03-01' + interval '1 day', extract(year from date '2024-03-01'),
+                    date_trunc('month', timestamp '2024-03-05 10:00:00'), trim(both 'x' from 'xax')"
+            ),
+            [row(&[
+                timestamp("2024-03-02 00:00:00"),
+                decimal("2024"),
+                timestamp("2024-03-01 00:00:00"),
+                "a".into()
+            ])]
+        );
+        assert!(database.execute("select lower(age) from users").is_err());
+        assert!(database.execute("select sqrt(-1.0)").is_err());
+    }
+
+    /// `product(x)`, the product of the integers of the group.
+    struct Product;
+
+    impl AggregateFunction for Product {
+        fn name(&self) -> &str {
+            "product"
+        }
+
+        fn return_type(
+            &self,
+            arguments: &[Option<DataType>],
+        ) -> Result<Option<DataType>, ResolveError> {
+            match arguments {
+                [None | Some(DataType::Integer)] => Ok(Some(DataType::Integer)),
+                _ => Err(ResolveError::Undefined),
+            }
+        }
+
+        fn init(&self) -> AggregateState {
+            vec![Value::Integer(1)]
+        }
+
+        fn update(
+            &self,
+            state: &mut AggregateState,
+            arguments: &[Value],
+        ) -> Result<(), ExecutionError> {
+            self.merge(state, arguments)
+        }
+
+        fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+            if let (Value::Integer(product), Value::Integer(value)) = (&mut state[0], &other[0]) {
+                *product *= value;
+            }
+            Ok(())
+        }
+
+        fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+            Ok(state[0].clone())
+        }
+    }
+
+    #[test]
+    fn registers_functions() {
+        let mut database = database();
+        database.register_function(ScalarFunction::new(
+            "twice",
+            vec![Signature::exact(
+                &[DataType::Integer],
+                DataType::Integer,
+                |arguments, _| match arguments[0] {
+                    Value::Integer(value) => Ok(Value::Integer(value * 2)),
+                    _ => unreachable!(),
+                },
+            )],
+        ));
+        database.register_function(
+            ScalarFunction::new(
+                "is_missing",
+                vec![Signature::exact(
+                    &[DataType::Integer],
+                    DataType::Boolean,
+                    |arguments, _| Ok(Value::Boolean(arguments[0] == Value::Null)),
+                )],
+            )
+            .null_handling(NullHandling::CalledOnNull),
+        );
+        let counter = Arc::new(AtomicI64::new(0));
+        let next = counter.clone();
+        database.register_function(
+            ScalarFunction::new(
+                "next_id",
+                vec![Signature::exact(&[], DataType::Integer, move |_, _| {
+                    Ok(Value::Integer(next.fetch_add(1, Ordering::SeqCst) + 1))
+                })],
+            )
+            .volatility(Volatility::Volatile),
+        );
+        database.register_aggregate(Product);
+
+        assert_eq!(
+            query(
+                &mut database,
+                "select twice(age), is_missing(age), next_id() from users order by id"
+            ),
+            [
+                row(&[2.into(), false.into(), 1.into()]),
+                row(&[60.into(), false.into(), 2.into()]),
+                row(&[2.into(), false.into(), 3.into()]),
+                row(&[Value::Null, true.into(), 4.into()]),
+            ]
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select product(age), product(id) from users where age is not null"
+            ),
+            [row(&[30.into(), 6.into()])]
+        );
+        let plan = query(&mut database, "explain select twice(21), next_id()");
+        assert!(
+            plan.iter()
+                .any(|row| row[0] == "Project: 42 AS twice, next_id()".into()),
+            "{:?}",
+            plan
+        );
+        assert_eq!(counter.load(Ordering::SeqCst), 4);
+
+        assert_eq!(
+            query(
+                &mut database,
+                "select s.generate_series, u.name from generate_series(3, 1, -1) as s
+                 join users u on u.id = s.generate_series order by 1"
+            ),
+            [
+                row(&[1.into(), "ann".into()]),
+                row(&[2.into(), "bob".into()]),
+                row(&[3.into(), "cid".into()]),
+            ]
+        );
+        assert_eq!(
+            query(
+                &mut database,
+                "select count(*) from generate_series(1, null)"
+            ),
+            [row(&[0.into()])]
+        );
+        assert!(database
+            .execute("select * from generate_series('a', 2)")
+            .is_err());
+        assert!(database
+            .execute("select * from no_such_function(1)")
+            .is_err());
+    }
+
+    #[test]
+    fn computes_aggregates_and_windows() {
+        let mut database = database();
+        let aggregates = "select user_id, count(*), sum(total), string_agg('' || total, '+'),
+                array_agg(id), var_samp(total), stddev_pop(total),
+                percentile_cont(0.25) within group (order by total)
+             from orders group by user_id order by user_id";
+        let windows = "select name, row_number() over (order by id), rank() over (order by age),
+                dense_rank() over (order by age), lag(name) over (order by id),
+                lead(age, 1, 0) over (order by id),
+                first_value(name) over (partition by age order by id),
+                last_value(name) over (order by id rows between current row and 1 following),
+                ntile(2) over (order by id),
+                sum(age) over (order by id rows between 1 preceding and current row),
+                count(*) over (),
+                avg(age) over (order by age range between 10 preceding and current row)
+             from users order by id";
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            database.set_execution_mode(mode);
+            assert_eq!(
+                query(&mut database, aggregates),
+                [
+                    row(&[
+                        1.into(),
+                        2.into(),
+                        30.into(),
+                        "10+20".into(),
+                        "{1,2}".into(),
+                        50.0.into(),
+                        5.0.into(),
+                        12.5.into()
+                    ]),
+                    row(&[
+                        2.into(),
+                        1.into(),
+                        5.into(),
+                        "5".into(),
+                        "{3}".into(),
+                        Value::Null,
+                        0.0.into(),
+                        5.0.into()
+                    ]),
+                    row(&[
+                        9.into(),
+                        1.into(),
+                        1.into(),
+                        "1".into(),
+                        "{4}".into(),
+                        Value::Null,
+                        0.0.into(),
+                        1.0.into()
+                    ]),
+                ],
+                "{:?}",
+                mode
+            );
+            assert_eq!(
+                query(&mut database, windows),
+                [
+                    row(&[
+                        "ann".into(),
+                        1.into(),
+                        1.into(),
+                        1.into(),
+                        Value::Null,
+                        30.into(),
+                        "ann".into(),
+                        "bob".into(),
+                        1.into(),
+                        1.into(),
+                        4.into(),
+                        1.0.into()
+                    ]),
+                    row(&[
+                        "bob".into(),
+                        2.into(),
+                        3.into(),
+                        2.into(),
+                        "ann".into(),
+                        1.into(),
+                        "bob".into(),
+                        "cid".into(),
+                        1.into(),
+                        31.into(),
+                        4.into(),
+                        30.0.into()
+                    ]),
+                    row(&[
+                        "cid".into(),
+                        3.into(),
+                        1.into(),
+                        1.into(),
+                        "bob".into(),
+                        Value::Null,
+                        "ann".into(),
+                        "dan".into(),
+                        2.into(),
+                        31.into(),
+                        4.into(),
+                        1.0.into()
+                    ]),
+                    row(&[
+                        "dan".into(),
+                        4.into(),
+                        4.into(),
+                        3.into(),
+                        "cid".into(),
+                        0.into(),
+                        "dan".into(),
+                        "dan".into(),
+                        2.into(),
+                        1.into(),
+                        4.into(),
+                        Value::Null
+                    ]),
+                ],
+                "{:?}",
+                mode
+            );
+        }
+        assert_eq!(
+            query(
+                &mut database,
+                "select count(*), bool_and(age > 0), bool_or(age > 10) from users"
+            ),
+            [row(&[4.into(), true.into(), true.into()])]
+        );
+        assert_eq!(
+            database
+                .execute("select percentile_disc(2) within group (order by age) from users")
+                .unwrap_err()
+                .to_string(),
+            "percentile value 2 is not between 0 and 1"
+        );
+        assert!(database
+            .execute("select ntile(0) over () from users")
+            .is_err());
+    }
+
+    #[test]
+    fn keeps_data_on_disk() {
+        let directory = temp_directory("database-store");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("data");
+        let mut database = Database::open(DiskStore::open(&path).unwrap()).unwrap();
+        database
+            .execute(
+                "create table users (id integer primary key, name text);
+                 create index users_name on users (name);
+                 insert into users values (1, 'ann'), (2, 'bob'), (3, 'cid');
+                 delete from users where id = 2;",
+            )
+            .unwrap();
+        drop(database);
+        let mut database = Database::open(DiskStore::open(&path).unwrap()).unwrap();
+        let rows = |database: &mut Database<DiskStore>, input: &str| {
+            database.execute(input).unwrap().pop().unwrap().rows
+        };
+        assert_eq!(
+            rows(
+                &mut database,
+                "select name from users where name >= 'b' order by id"
+            ),
+            [row(&["cid".into()])]
+        );
+        assert!(database
+            .execute("insert into users values (3, 'dan')")
+            .is_err());
+        assert_eq!(
+            rows(
+                &mut database,
+                "insert into users values (4, 'dan') returning id"
+            ),
+            [row(&[4.into()])]
+        );
+    }
+
+    #[test]
+    fn spills_to_disk() {
+        let directory = temp_directory("database");
+        let config = Config {
+            memory_budget: 16 * 1024,
+            temp_directory: directory.clone(),
+            ..Config::default()
+        };
+        let mut spilling = Database::open_with_config(MemoryStore::new(), config).unwrap();
+        let mut database = database();
+        for database in [&mut spilling, &mut database] {
+            database
+                .execute("create table t (id integer, k integer, s text)")
+                .unwrap();
+            let values: Vec<_> = (0..3000)
+                .map(|id| format!("({}, {}, 'name{}')", id, id * 7 % 301, id % 13))
+                .collect();
+            database
+                .execute(&format!("insert into t values {}", values.join(", ")))
+                .unwrap();
+        }
+        for input in [
+            "select * from t order by s, k desc",
+            "select k, count(*), sum(id), min(s) from t group by k order by k",
+            "select a.id, b.id from t a join t b on a.k = b.id where b.s <> 'name1' order by 1, 2",
+            "select count(*) from t a left join t b on a.id = b.k + 1",
+        ] {
+            for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+                spilling.set_execution_mode(mode);
+                assert_eq!(
+                    query(&mut spilling, input),
+                    query(&mut database, input),
+                    "{}",
+                    input
+                );
+            }
+            assert_eq!(spilled_files(&directory), 0);
+        }
+        // the files are removed when the query fails or stops reading the rows
+        assert_eq!(
+            spilling.execute("select * from t order by 10 / (id - 2999)"),
+            Err(ExecutionError::DivisionByZero.into())
+        );
+        assert_eq!(
+            query(&mut spilling, "select id from t order by s limit 1"),
+            [row(&[0.into()])]
+        );
+        assert_eq!(spilled_files(&directory), 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn executes_in_parallel() {
+        let mut parallel = database();
+        let mut serial = database();
+        for database in [&mut parallel, &mut serial] {
+            database
+                .execute(
+                    "create table t (id integer, k integer, s text); create table e (id integer)",
+                )
+                .unwrap();
+            let values: Vec<_> = (0..5000)
+                .map(|id| format!("({}, {}, 'name{}')", id, id * 7 % 301, id % 13))
+                .collect();
+            database
+                .execute(&format!("insert into t values {}", values.join(", ")))
+                .unwrap();
+        }
+        parallel.execute("SET max_parallel_workers = 4").unwrap();
+        assert_eq!(
+            query(&mut parallel, "show max_parallel_workers"),
+            [row(&["4".into()])]
+        );
+        for input in [
+            "select * from t where k < 50 and s <> 'name3'",
+            "select id + k from t",
+            "select k, count(*), sum(id), avg(id), min(s), max(s) from t group by k",
+            "select count(*), sum(k) from t where id % 3 = 0",
+            "select s, count(distinct k) from t group by s",
+            "select count(distinct k), count(*) from t",
+            "select k, bool_and(id < 4990), bool_or(s = 'name1'), array_agg(distinct s is null) from t group by k",
+            "select s, percentile_disc(0.5) within group (order by id), percentile_cont(0.9) within group (order by k desc) from t group by s",
+            "select id, rank() over (partition by s order by k), sum(k) over (order by id rows 2 preceding) from t where id < 300",
+            "select distinct s from t",
+            "select a.id, b.id from t a join t b on a.k = b.id where b.s <> 'name1'",
+            "select a.id, b.k from t a left join t b on a.id = b.k + 4000",
+            "select count(*), max(b.id) from t a full join t b on a.id = b.k * 2",
+            "select u.name, o.total from users u join orders o on o.user_id = u.id",
+            "select count(*), sum(id) from e",
+            "select id from t where id > 4990 order by id desc limit 3",
+        ] {
+            for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+                parallel.set_execution_mode(mode);
+                let mut rows = query(&mut parallel, input);
+                let mut expected = query(&mut serial, input);
+                rows.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+                expected.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+                assert_eq!(rows, expected, "{}", input);
+            }
+        }
+        assert_eq!(
+            parallel.execute("select 10 / (id - 2500) from t"),
+            Err(ExecutionError::DivisionByZero.into())
+        );
+        // writes are executed serially
+        assert_eq!(
+            parallel.execute("delete from t where k = 0").unwrap()[0].rows_affected,
+            117
+        );
+
+        parallel.execute("set max_parallel_workers to '1'").unwrap();
+        assert_eq!(
+            query(&mut parallel, "show all"),
+            [row(&["max_parallel_workers".into(), "1".into()])]
+        );
+        assert_eq!(
+            parallel
+                .execute("set max_parallel_workers = lots")
+                .unwrap_err()
+                .to_string(),
+            "invalid value for parameter \"max_parallel_workers\": \"lots\""
+        );
+        parallel
+            .execute("set max_parallel_workers = 1000000000")
+            .unwrap();
+        assert_eq!(
+            query(&mut parallel, "show max_parallel_workers"),
+            [row(&[max_workers().to_string().into()])]
+        );
+        assert_eq!(
+            parallel.execute("show work_mem"),
+            Err(DatabaseError::UnknownSetting("work_mem".to_string()))
+        );
+    }
+}
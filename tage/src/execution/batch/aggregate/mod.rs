@@ -0,0 +1,280 @@
+//! Vectorized hash aggregation: the group of every selected row is looked up first,
+//! then every aggregate is updated a column at a time.
+
+use crate::execution::batch::{BatchOperator, ColumnVector, RecordBatch, Vector, BATCH_SIZE};
+use crate::execution::expr::ScalarExpr;
+use crate::execution::operators::aggregate::{Aggregate, AggregatePhase};
+use crate::execution::spill::{
+    row_size, MemoryReservation, Partitions, SpillFile, SpillReader, MAX_LEVEL,
+};
+use crate::execution::table::encode_key;
+use crate::execution::{ExecutionContext, ExecutionError, Row};
+use crate::functions::aggregate::AggregateState;
+use crate::value::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+
+/// Groups of the rows seen so far, a single integer key is hashed without encoding it.
+#[derive(Default)]
+struct Groups {
+    keys: Vec<Row>,
+    integers: HashMap<Option<i64>, usize>,
+    encoded: HashMap<Vec<u8>, usize>,
+}
+
+impl Groups {
+    /// Group of every selected row of the batch, in the order of the selection.
+    fn assign(&mut self, keys: &[ColumnVector], batch: &RecordBatch) -> Vec<usize> {
+        let selected = batch.selection.ones();
+        let Groups {
+            keys: groups,
+            integers,
+            encoded,
+        } = self;
+        match keys {
+            [ColumnVector {
+                values: Vector::Integer(values),
+                validity,
+            }] => selected
+                .map(|index| {
+                    let key = validity.get(index).then_some(values[index]);
+                    *integers.entry(key).or_insert_with(|| {
+                        groups.push(vec![key.map_or(Value::Null, Value::Integer)]);
+                        groups.len() - 1
+                    })
+                })
+                .collect(),
+            keys => selected
+                .map(|index| {
+                    let key: Row = keys.iter().map(|key| key.value(index)).collect();
+                    *encoded.entry(encode_key(&key)).or_insert_with(|| {
+                        groups.push(key);
+                        groups.len() - 1
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Key of a selected row of the batch when its group isn't known yet.
+    fn unknown(&self, keys: &[ColumnVector], index: usize) -> Option<Row> {
+        let key: Row = keys.iter().map(|key| key.value(index)).collect();
+        let known = match keys {
+            [ColumnVector {
+                values: Vector::Integer(values),
+                validity,
+            }] => self
+                .integers
+                .contains_key(&validity.get(index).then_some(values[index])),
+            _ => self.encoded.contains_key(&encode_key(&key)),
+        };
+        (!known).then_some(key)
+    }
+}
+
+/// Reads all batches of the input when it is opened, then returns the groups in the
+/// order they were first seen. Without group keys there is a single group.
+///
+/// Spills like the row `HashAggregate`: once a batch brings the groups over the memory
+/// budget, the rows of the following batches whose groups aren't in memory are
+/// partitioned.
+pub struct BatchHashAggregate {
+    input: Box<dyn BatchOperator>,
+    group_by: Vec<ScalarExpr>,
+    aggregates: Vec<Aggregate>,
+    /// `Single` or `Partial`, the final phase merges few rows and isn't vectorized
+    phase: AggregatePhase,
+    output: VecDeque<RecordBatch>,
+    /// Partitions still to aggregate, with the level they were partitioned at
+    partitions: Vec<(SpillFile, usize)>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl BatchHashAggregate {
+    pub fn new(
+        input: Box<dyn BatchOperator>,
+        group_by: Vec<ScalarExpr>,
+        aggregates: Vec<Aggregate>,
+        phase: AggregatePhase,
+    ) -> Self {
+        BatchHashAggregate {
+            input,
+            group_by,
+            aggregates,
+            phase,
+            output: VecDeque::new(),
+            partitions: vec![],
+            reservation: None,
+        }
+    }
+
+    /// Groups the batches of the input or the rows of a partition of the given level, the
+    /// output batches are queued.
+    fn aggregate(
+        &mut self,
+        mut partition: Option<SpillReader>,
+        level: usize,
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        let mut reservation = context.memory.reservation();
+        let mut spilled: Option<Partitions> = None;
+        let mut groups = Groups::default();
+        if self.group_by.is_empty() {
+            groups.keys.push(vec![]);
+            groups.encoded.insert(vec![], 0);
+        }
+        let mut states: Vec<Vec<AggregateState>> = vec![vec![]; self.aggregates.len()];
+        // groups and encoded arguments seen by each `distinct` aggregate
+        let mut seen: Vec<HashSet<(usize, Vec<u8>)>> = vec![HashSet::new(); self.aggregates.len()];
+        loop {
+            let batch = match &mut partition {
+                Some(rows) => read_batch(rows)?,
+                None => self.input.next_batch(context)?,
+            };
+            let Some(mut batch) = batch else {
+                break;
+            };
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| expr.eval_batch(&batch, context))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(partitions) = &mut spilled {
+                let selected: Vec<_> = batch.selection.ones().collect();
+                for index in selected {
+                    if let Some(key) = groups.unknown(&keys, index) {
+                        partitions.write(&encode_key(&key), &batch.row(index))?;
+                        batch.selection.set(index, false);
+                    }
+                }
+            }
+            let known = groups.keys.len();
+            let group_ids = groups.assign(&keys, &batch);
+            let size = groups.keys[known..]
+                .iter()
+                .map(|key| row_size(key) + self.aggregates.len() * size_of::<AggregateState>())
+                .sum();
+            if !reservation.try_grow(size) {
+                reservation.grow(size);
+                if level < MAX_LEVEL && spilled.is_none() {
+                    spilled = Some(Partitions::new(&mut context.memory, level)?);
+                }
+            }
+            let rows: Vec<_> = batch.selection.ones().zip(group_ids).collect();
+            for ((aggregate, states), seen) in
+                self.aggregates.iter().zip(&mut states).zip(&mut seen)
+            {
+                states.resize_with(groups.keys.len(), || aggregate.function.init());
+                let arguments = aggregate
+                    .arguments
+                    .iter()
+                    .map(|argument| argument.eval_batch(&batch, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                update(aggregate, &arguments, &rows, states, seen)?;
+            }
+        }
+        let mut rows = groups
+            .keys
+            .into_iter()
+            .enumerate()
+            .map(|(group, mut row)| {
+                for (aggregate, states) in self.aggregates.iter().zip(&states) {
+                    row.extend(match states.get(group) {
+                        Some(state) => aggregate.output(state, self.phase)?,
+                        None => aggregate.output(&aggregate.function.init(), self.phase)?,
+                    });
+                }
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>, ExecutionError>>()?
+            .into_iter()
+            .peekable();
+        while rows.peek().is_some() {
+            self.output.push_back(RecordBatch::from_rows(
+                rows.by_ref().take(BATCH_SIZE).collect(),
+            ));
+        }
+        if let Some(partitions) = spilled {
+            self.partitions.extend(
+                partitions
+                    .into_files()
+                    .into_iter()
+                    .map(|file| (file, level + 1)),
+            );
+        }
+        self.reservation = Some(reservation);
+        Ok(())
+    }
+}
+
+/// The next rows of a partition as a batch.
+fn read_batch(rows: &mut SpillReader) -> Result<Option<RecordBatch>, ExecutionError> {
+    let mut batch = vec![];
+    while batch.len() < BATCH_SIZE {
+        match rows.next_row()? {
+            Some(row) => batch.push(row),
+            None => break,
+        }
+    }
+    Ok((!batch.is_empty()).then(|| RecordBatch::from_rows(batch)))
+}
+
+/// Adds the arguments of the rows, given as their index in the batch and their group, to
+/// the states of the groups. `distinct` aggregates skip the arguments they have seen in
+/// the group.
+fn update(
+    aggregate: &Aggregate,
+    arguments: &[ColumnVector],
+    rows: &[(usize, usize)],
+    states: &mut [AggregateState],
+    seen: &mut HashSet<(usize, Vec<u8>)>,
+) -> Result<(), ExecutionError> {
+    if !aggregate.distinct {
+        return aggregate.function.update_batch(states, rows, arguments);
+    }
+    for &(index, group) in rows {
+        let values: Row = arguments
+            .iter()
+            .map(|argument| argument.value(index))
+            .collect();
+        if aggregate.function.null_handling().skips(&values)
+            || !seen.insert((group, encode_key(&values)))
+        {
+            continue;
+        }
+        aggregate.function.update(&mut states[group], &values)?;
+    }
+    Ok(())
+}
+
+impl BatchOperator for BatchHashAggregate {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.close();
+        self.input.open(context)?;
+        let result = self.aggregate(None, 0, context);
+        self.input.close();
+        result
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        loop {
+            if let Some(batch) = self.output.pop_front() {
+                return Ok(Some(batch));
+            }
+            self.reservation = None;
+            let Some((partition, level)) = self.partitions.pop() else {
+                return Ok(None);
+            };
+            self.aggregate(Some(partition.into_reader()?), level, context)?;
+        }
+    }
+
+    fn close(&mut self) {
+        self.output.clear();
+        self.partitions.clear();
+        self.reservation = None;
+    }
+}
@@ -0,0 +1,593 @@
+//! Vectorized evaluation of expressions. Values of rows which aren't selected are
+//! computed where that is cheaper than skipping them, but never raise an error: a
+//! division by zero in a row the filter below removed isn't one.
+
+use crate::execution::batch::{Bitmap, ColumnVector, RecordBatch, Vector};
+use crate::execution::expr::{binary, cast, ScalarExpr};
+use crate::execution::pattern::escape_char;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::parser::expr::{Operator, UnaryOperator};
+use crate::value::{DataType, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+impl ScalarExpr {
+    /// Values of the expression for the rows of the batch, `NULL` for the rows which
+    /// aren't selected unless a kernel computes them anyway.
+    pub fn eval_batch(
+        &self,
+        batch: &RecordBatch,
+        context: &mut ExecutionContext,
+    ) -> Result<ColumnVector, ExecutionError> {
+        self.eval_vector(&batch.columns, &batch.selection, context)
+    }
+
+    fn eval_vector(
+        &self,
+        columns: &[ColumnVector],
+        selection: &Bitmap,
+        context: &mut ExecutionContext,
+    ) -> Result<ColumnVector, ExecutionError> {
+        let len = selection.len();
+        Ok(match self {
+            ScalarExpr::Column(position) => columns[*position].clone(),
+            ScalarExpr::Literal(value) => ColumnVector::constant(value, len),
+            ScalarExpr::Binary {
+                left,
+                operator: operator @ (Operator::And | Operator::Or),
+                right,
+            } => {
+                let left = left.eval_vector(columns, selection, context)?;
+                let Some((left_true, left_false)) = truth_bitmaps(&left) else {
+                    return self.eval_rows(columns, selection, context);
+                };
+                // the right operand is only evaluated where the left one doesn't decide
+                let decided = match operator {
+                    Operator::And => &left_false,
+                    _ => &left_true,
+                };
+                let right = right.eval_vector(columns, &selection.and_not(decided), context)?;
+                let Some((right_true, right_false)) = truth_bitmaps(&right) else {
+                    return self.eval_rows(columns, selection, context);
+                };
+                let (is_true, is_false) = match operator {
+                    Operator::And => (left_true.and(&right_true), left_false.or(&right_false)),
+                    _ => (left_true.or(&right_true), left_false.and(&right_false)),
+                };
+                boolean_vector(&is_true, is_true.or(&is_false))
+            }
+            ScalarExpr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = left.eval_vector(columns, selection, context)?;
+                let right = right.eval_vector(columns, selection, context)?;
+                binary_kernel(&left, *operator, &right, selection)?
+            }
+            ScalarExpr::Unary { operator, expr } => {
+                let vector = expr.eval_vector(columns, selection, context)?;
+                let active = vector.validity.and(selection);
+                match (operator, vector.values) {
+                    (UnaryOperator::Not, Vector::Boolean(values)) => ColumnVector::new(
+                        Vector::Boolean(values.into_iter().map(|value| !value).collect()),
+                        vector.validity,
+                    ),
+                    (UnaryOperator::Minus, Vector::Integer(values)) => {
+                        let mut negated = Vec::with_capacity(values.len());
+                        for (index, value) in values.into_iter().enumerate() {
+                            negated.push(match value.checked_neg() {
+                                Some(value) => value,
+                                None if active.get(index) => {
+                                    return Err(ExecutionError::OutOfRange {
+                                        data_type: DataType::BigInt,
+                                    })
+                                }
+                                None => 0,
+                            });
+                        }
+                        ColumnVector::new(Vector::Integer(negated), vector.validity)
+                    }
+                    (UnaryOperator::Minus, Vector::Float(values)) => ColumnVector::new(
+                        Vector::Float(values.into_iter().map(|value| -value).collect()),
+                        vector.validity,
+                    ),
+                    (UnaryOperator::Plus, values) => ColumnVector::new(values, vector.validity),
+                    _ => return self.eval_rows(columns, selection, context),
+                }
+            }
+            ScalarExpr::IsNull { expr, negated } => {
+                let vector = expr.eval_vector(columns, selection, context)?;
+                let is_null = match negated {
+                    true => vector.validity,
+                    false => vector.validity.not(),
+                };
+                boolean_vector(&is_null, Bitmap::new(len, true))
+            }
+            ScalarExpr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => match (pattern.as_ref(), escape.as_deref()) {
+                (
+                    ScalarExpr::Literal(Value::String(pattern)),
+                    None | Some(ScalarExpr::Literal(Value::String(_))),
+                ) => {
+                    let vector = expr.eval_vector(columns, selection, context)?;
+                    let Vector::String(values) = &vector.values else {
+                        return self.eval_rows(columns, selection, context);
+                    };
+                    let escape = match (kind.escapes(), escape.as_deref()) {
+                        (true, Some(ScalarExpr::Literal(Value::String(escape)))) => {
+                            escape_char(Some(escape))?
+                        }
+                        (true, _) => escape_char(None)?,
+                        (false, _) => None,
+                    };
+                    let pattern = context.patterns.get(*kind, pattern, escape)?;
+                    let active = vector.validity.and(selection);
+                    let mut matches = Bitmap::new(len, false);
+                    for index in active.ones() {
+                        matches.set(index, pattern.matches(&values[index]) != *negated);
+                    }
+                    boolean_vector(&matches, vector.validity)
+                }
+                _ => return self.eval_rows(columns, selection, context),
+            },
+            ScalarExpr::Cast { expr, data_type } => {
+                let vector = expr.eval_vector(columns, selection, context)?;
+                match (vector.values, data_type) {
+                    // decimals are exact, they are cast like a row does
+                    (Vector::Integer(values), DataType::Real | DataType::Double) => {
+                        ColumnVector::new(
+                            Vector::Float(values.into_iter().map(|value| value as f64).collect()),
+                            vector.validity,
+                        )
+                    }
+                    (
+                        Vector::Integer(values),
+                        DataType::TinyInt | DataType::SmallInt | DataType::Integer,
+                    ) => {
+                        for index in vector.validity.and(selection).ones() {
+                            cast(Value::Integer(values[index]), *data_type)?;
+                        }
+                        ColumnVector::new(Vector::Integer(values), vector.validity)
+                    }
+                    (values @ Vector::Integer(_), DataType::BigInt)
+                    | (values @ Vector::Float(_), DataType::Double)
+                    | (values @ Vector::Boolean(_), DataType::Boolean)
+                    | (values @ Vector::String(_), DataType::Text | DataType::VarChar(None)) => {
+                        ColumnVector::new(values, vector.validity)
+                    }
+                    (values, data_type) => {
+                        let vector = ColumnVector::new(values, vector.validity);
+                        let mut cast_values = vec![Value::Null; len];
+                        for index in vector.validity.and(selection).ones() {
+                            cast_values[index] = cast(vector.value(index), *data_type)?;
+                        }
+                        ColumnVector::from_values(cast_values)
+                    }
+                }
+            }
+            // arguments are vectorized, the function is called a row at a time
+            ScalarExpr::Function {
+                function,
+                signature,
+                arguments,
+            } => {
+                let vectors = arguments
+                    .iter()
+                    .map(|argument| argument.eval_vector(columns, selection, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut values = vec![Value::Null; len];
+                for index in selection.ones() {
+                    let arguments: Vec<_> =
+                        vectors.iter().map(|vector| vector.value(index)).collect();
+                    values[index] = function.call(*signature, &arguments, context)?;
+                }
+                ColumnVector::from_values(values)
+            }
+            _ => return self.eval_rows(columns, selection, context),
+        })
+    }
+
+    /// Row-at-a-time evaluation of the selected rows, for expressions without a kernel.
+    fn eval_rows(
+        &self,
+        columns: &[ColumnVector],
+        selection: &Bitmap,
+        context: &mut ExecutionContext,
+    ) -> Result<ColumnVector, ExecutionError> {
+        let mut values = vec![Value::Null; selection.len()];
+        for index in selection.ones() {
+            let row: Vec<_> = columns.iter().map(|column| column.value(index)).collect();
+            values[index] = self.eval(&row, context)?;
+        }
+        Ok(ColumnVector::from_values(values))
+    }
+}
+
+/// The rows which are true and the rows which are false, `None` if the values aren't
+/// booleans.
+fn truth_bitmaps(vector: &ColumnVector) -> Option<(Bitmap, Bitmap)> {
+    match &vector.values {
+        Vector::Boolean(values) => {
+            let values = Bitmap::from_bools(values.iter().copied());
+            Some((
+                values.and(&vector.validity),
+                vector.validity.and_not(&values),
+            ))
+        }
+        Vector::Values(_) if vector.validity.count() == 0 => {
+            let none = Bitmap::new(vector.len(), false);
+            Some((none.clone(), none))
+        }
+        _ => None,
+    }
+}
+
+fn boolean_vector(values: &Bitmap, validity: Bitmap) -> ColumnVector {
+    ColumnVector::new(
+        Vector::Boolean((0..values.len()).map(|index| values.get(index)).collect()),
+        validity,
+    )
+}
+
+/// Arithmetic, comparison and `||` of two columns.
+fn binary_kernel(
+    left: &ColumnVector,
+    operator: Operator,
+    right: &ColumnVector,
+    selection: &Bitmap,
+) -> Result<ColumnVector, ExecutionError> {
+    let validity = left.validity.and(&right.validity);
+    let active = validity.and(selection);
+    let test = comparison(operator);
+    let arithmetic = matches!(
+        operator,
+        Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide | Operator::Modulo
+    );
+    let values = match (&left.values, &right.values) {
+        (Vector::Integer(left), Vector::Integer(right)) => match test {
+            Some(test) => Some(Vector::Boolean(compare_all(left, right, |left, right| {
+                test(left.cmp(right))
+            }))),
+            None if arithmetic => Some(Vector::Integer(integer_arithmetic(
+                left, operator, right, &active,
+            )?)),
+            None => None,
+        },
+        (Vector::Integer(_) | Vector::Float(_), Vector::Integer(_) | Vector::Float(_)) => {
+            let (left, right) = (floats(&left.values), floats(&right.values));
+            match test {
+                Some(test) => Some(Vector::Boolean(compare_all(
+                    &left,
+                    &right,
+                    |left, right| test(left.total_cmp(right)),
+                ))),
+                None if arithmetic => Some(Vector::Float(float_arithmetic(
+                    &left, operator, &right, &active,
+                )?)),
+                None => None,
+            }
+        }
+        (Vector::String(left), Vector::String(right)) => match test {
+            Some(test) => Some(Vector::Boolean(compare_all(left, right, |left, right| {
+                test(left.cmp(right))
+            }))),
+            None if operator == Operator::Concat => Some(Vector::String(
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| format!("{}{}", left, right))
+                    .collect(),
+            )),
+            None => None,
+        },
+        (Vector::Boolean(left), Vector::Boolean(right)) => test.map(|test| {
+            Vector::Boolean(compare_all(left, right, |left, right| {
+                test(left.cmp(right))
+            }))
+        }),
+        _ => None,
+    };
+    match values {
+        Some(values) => Ok(ColumnVector::new(values, validity)),
+        None => {
+            let mut values = vec![Value::Null; selection.len()];
+            for index in active.ones() {
+                values[index] = binary(&left.value(index), operator, &right.value(index))?;
+            }
+            Ok(ColumnVector::from_values(values))
+        }
+    }
+}
+
+/// The test of the ordering of two values a comparison operator makes.
+fn comparison(operator: Operator) -> Option<fn(Ordering) -> bool> {
+    Some(match operator {
+        Operator::Equal => Ordering::is_eq,
+        Operator::NotEqual => Ordering::is_ne,
+        Operator::LessThan => Ordering::is_lt,
+        Operator::LessThanOrEqual => Ordering::is_le,
+        Operator::GreaterThan => Ordering::is_gt,
+        Operator::GreaterThanOrEqual => Ordering::is_ge,
+        _ => return None,
+    })
+}
+
+fn compare_all<T>(left: &[T], right: &[T], test: impl Fn(&T, &T) -> bool) -> Vec<bool> {
+    left.iter()
+        .zip(right)
+        .map(|(left, right)| test(left, right))
+        .collect()
+}
+
+fn floats(values: &Vector) -> Cow<'_, [f64]> {
+    match values {
+        Vector::Float(values) => Cow::Borrowed(values),
+        Vector::Integer(values) => Cow::Owned(values.iter().map(|value| *value as f64).collect()),
+        _ => unreachable!("numbers"),
+    }
+}
+
+/// Checked integer arithmetic, an overflow or a division by zero in a row which isn't
+/// active gives a placeholder.
+fn integer_arithmetic(
+    left: &[i64],
+    operator: Operator,
+    right: &[i64],
+    active: &Bitmap,
+) -> Result<Vec<i64>, ExecutionError> {
+    let apply: fn(i64, i64) -> Option<i64> = match operator {
+        Operator::Plus => i64::checked_add,
+        Operator::Minus => i64::checked_sub,
+        Operator::Multiply => i64::checked_mul,
+        Operator::Divide => i64::checked_div,
+        _ => i64::checked_rem,
+    };
+    let mut values = Vec::with_capacity(left.len());
+    let mut failed = None;
+    for (index, (left, right)) in left.iter().zip(right).enumerate() {
+        values.push(match apply(*left, *right) {
+            Some(value) => value,
+            None => {
+                if failed.is_none() && active.get(index) {
+                    failed = Some(*right);
+                }
+                0
+            }
+        });
+    }
+    match failed {
+        Some(0) if matches!(operator, Operator::Divide | Operator::Modulo) => {
+            Err(ExecutionError::DivisionByZero)
+        }
+        Some(_) => Err(ExecutionError::OutOfRange {
+            data_type: DataType::BigInt,
+        }),
+        None => Ok(values),
+    }
+}
+
+fn float_arithmetic(
+    left: &[f64],
+    operator: Operator,
+    right: &[f64],
+    active: &Bitmap,
+) -> Result<Vec<f64>, ExecutionError> {
+    if matches!(operator, Operator::Divide | Operator::Modulo)
+        && active.ones().any(|index| right[index] == 0.0)
+    {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    let apply: fn(f64, f64) -> f64 = match operator {
+        Operator::Plus => |left, right| left + right,
+        Operator::Minus => |left, right| left - right,
+        Operator::Multiply => |left, right| left * right,
+        Operator::Divide => |left, right| left / right,
+        _ => |left, right| left % right,
+    };
+    Ok(left
+        .iter()
+        .zip(right)
+        .map(|(left, right)| apply(*left, *right))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+    use crate::execution::pattern::PatternCache;
+    use crate::execution::spill::QueryMemory;
+    use crate::parser::expr::PatternKind;
+    use crate::storage::MemoryStore;
+
+    fn column(position: usize) -> Box<ScalarExpr> {
+        Box::new(ScalarExpr::Column(position))
+    }
+
+    fn literal(value: impl Into<Value>) -> Box<ScalarExpr> {
+        Box::new(ScalarExpr::Literal(value.into()))
+    }
+
+    fn rows() -> Vec<Vec<Value>> {
+        (0..150)
+            .map(|index: i64| {
+                vec![
+                    if index % 7 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Integer(index - 75)
+                    },
+                    Value::Float(index as f64 / 4.0),
+                    Value::String(format!("name{}", index % 12)),
+                    if index % 5 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Boolean(index % 3 == 0)
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    /// Vectorized and row-at-a-time evaluation give the same values for the selected rows.
+    fn check(expr: ScalarExpr) {
+        let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+        let mut context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            start_time: std::time::SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let mut batch = RecordBatch::from_rows(rows());
+        batch.selection = Bitmap::from_bools((0..150).map(|index| index % 4 != 1));
+        let vector = expr.eval_batch(&batch, &mut context).unwrap();
+        for (index, row) in rows().iter().enumerate() {
+            if batch.selection.get(index) {
+                assert_eq!(
+                    vector.value(index),
+                    expr.eval(row, &mut context).unwrap(),
+                    "{:?} of row {}",
+                    expr,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn evaluates_like_rows() {
+        let binary = |left, operator, right| ScalarExpr::Binary {
+            left,
+            operator,
+            right,
+        };
+        for operator in [
+            Operator::Plus,
+            Operator::Minus,
+            Operator::Multiply,
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::LessThan,
+            Operator::GreaterThanOrEqual,
+        ] {
+            check(binary(column(0), operator, literal(3)));
+            check(binary(column(0), operator, column(1)));
+            check(binary(column(1), operator, literal(2.5)));
+        }
+        check(binary(column(0), Operator::Divide, literal(4)));
+        check(binary(column(0), Operator::Modulo, literal(-4)));
+        check(binary(column(2), Operator::LessThan, literal("name3")));
+        check(binary(column(2), Operator::Concat, column(2)));
+        check(binary(column(3), Operator::And, column(3)));
+        check(binary(
+            Box::new(binary(column(0), Operator::GreaterThan, literal(0))),
+            Operator::Or,
+            column(3),
+        ));
+        check(ScalarExpr::Unary {
+            operator: UnaryOperator::Not,
+            expr: column(3),
+        });
+        check(ScalarExpr::Unary {
+            operator: UnaryOperator::Minus,
+            expr: column(0),
+        });
+        check(ScalarExpr::IsNull {
+            expr: column(0),
+            negated: true,
+        });
+        for pattern in ["name1%", "%1", "%me1%", "name1", "n_me_", "%"] {
+            check(ScalarExpr::Like {
+                expr: column(2),
+                pattern: literal(pattern),
+                escape: None,
+                kind: PatternKind::Like,
+                negated: false,
+            });
+        }
+        check(ScalarExpr::Like {
+            expr: column(2),
+            pattern: literal("NAME1_"),
+            escape: Some(literal("")),
+            kind: PatternKind::ILike,
+            negated: true,
+        });
+        check(ScalarExpr::Like {
+            expr: column(2),
+            pattern: literal("e[0-4]$"),
+            escape: None,
+            kind: PatternKind::Regex,
+            negated: false,
+        });
+        check(ScalarExpr::Cast {
+            expr: column(0),
+            data_type: DataType::Double,
+        });
+        check(ScalarExpr::InList {
+            expr: column(0),
+            list: vec![
+                ScalarExpr::Literal(Value::Integer(1)),
+                ScalarExpr::Literal(Value::Null),
+            ],
+            negated: false,
+        });
+    }
+
+    #[test]
+    fn raises_errors_of_selected_rows_only() {
+        let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+        let mut context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            start_time: std::time::SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let mut batch =
+            RecordBatch::from_rows(vec![vec![Value::Integer(0)], vec![Value::Integer(2)]]);
+        let divide = ScalarExpr::Binary {
+            left: literal(10),
+            operator: Operator::Divide,
+            right: column(0),
+        };
+        assert_eq!(
+            divide.eval_batch(&batch, &mut context),
+            Err(ExecutionError::DivisionByZero)
+        );
+        batch.selection.set(0, false);
+        assert_eq!(
+            divide.eval_batch(&batch, &mut context).unwrap().value(1),
+            Value::Integer(5)
+        );
+        // the right operand isn't evaluated where the left one is false
+        let guarded = ScalarExpr::Binary {
+            left: Box::new(ScalarExpr::Binary {
+                left: column(0),
+                operator: Operator::NotEqual,
+                right: literal(0),
+            }),
+            operator: Operator::And,
+            right: Box::new(ScalarExpr::Binary {
+                left: Box::new(divide),
+                operator: Operator::GreaterThan,
+                right: literal(1),
+            }),
+        };
+        batch.selection.set(0, true);
+        let vector = guarded.eval_batch(&batch, &mut context).unwrap();
+        assert_eq!(
+            (vector.value(0), vector.value(1)),
+            (Value::Boolean(false), Value::Boolean(true))
+        );
+    }
+}
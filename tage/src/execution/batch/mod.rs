@@ -0,0 +1,373 @@
+//! Batch-oriented execution: operators exchange batches of rows stored as column
+//! vectors, with a selection bitmap of the rows which are still part of the result.
+//!
+//! A filter only clears bits of the selection, the columns are not copied. Expressions
+//! are evaluated a column at a time with kernels for the common types, other
+//! expressions fall back to row-at-a-time evaluation of the selected rows.
+
+pub mod aggregate;
+pub mod expr;
+pub mod operators;
+
+use crate::execution::{ExecutionContext, ExecutionError, Row};
+use crate::value::Value;
+
+/// Rows of a batch read by a scan, small enough for the columns to stay in the cache.
+pub const BATCH_SIZE: usize = 1024;
+
+/// One bit per row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn new(len: usize, set: bool) -> Self {
+        let word = if set { u64::MAX } else { 0 };
+        let mut bitmap = Bitmap {
+            words: vec![word; len.div_ceil(64)],
+            len,
+        };
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    pub fn from_bools(bits: impl IntoIterator<Item = bool>) -> Self {
+        let mut bitmap = Bitmap::new(0, false);
+        for bit in bits {
+            bitmap.push(bit);
+        }
+        bitmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 64] >> (index % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, index: usize, bit: bool) {
+        let mask = 1 << (index % 64);
+        if bit {
+            self.words[index / 64] |= mask;
+        } else {
+            self.words[index / 64] &= !mask;
+        }
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, bit);
+    }
+
+    pub fn and(&self, other: &Bitmap) -> Bitmap {
+        self.zip(other, |left, right| left & right)
+    }
+
+    pub fn or(&self, other: &Bitmap) -> Bitmap {
+        self.zip(other, |left, right| left | right)
+    }
+
+    /// Bits set here and not in the other bitmap.
+    pub fn and_not(&self, other: &Bitmap) -> Bitmap {
+        self.zip(other, |left, right| left & !right)
+    }
+
+    pub fn not(&self) -> Bitmap {
+        let mut bitmap = Bitmap {
+            words: self.words.iter().map(|word| !word).collect(),
+            len: self.len,
+        };
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    /// Number of set bits.
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn all(&self) -> bool {
+        self.count() == self.len
+    }
+
+    /// Positions of the set bits in increasing order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(position, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    position * 64 + bit
+                })
+            })
+        })
+    }
+
+    fn zip(&self, other: &Bitmap, f: impl Fn(u64, u64) -> u64) -> Bitmap {
+        debug_assert_eq!(self.len, other.len);
+        let mut bitmap = Bitmap {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(left, right)| f(*left, *right))
+                .collect(),
+            len: self.len,
+        };
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    /// Bits past the length stay unset, so words can be counted as they are.
+    fn clear_tail(&mut self) {
+        if !self.len.is_multiple_of(64) {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << (self.len % 64)) - 1;
+            }
+        }
+    }
+}
+
+/// Values of a column, a kernel is chosen by the variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vector {
+    Boolean(Vec<bool>),
+    Integer(Vec<i64>),
+    Float(Vec<f64>),
+    String(Vec<String>),
+    /// Values of no single kind or of a kind without a vector of its own, e.g. a column
+    /// of only `NULL`s or of decimals
+    Values(Vec<Value>),
+}
+
+/// A column of a batch. The values of the rows which are `NULL` are placeholders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnVector {
+    pub values: Vector,
+    /// Set for the rows which aren't `NULL`
+    pub validity: Bitmap,
+}
+
+impl ColumnVector {
+    pub fn new(values: Vector, validity: Bitmap) -> Self {
+        ColumnVector { values, validity }
+    }
+
+    /// The column of the values, integers are widened to floats when both are mixed.
+    pub fn from_values(values: Vec<Value>) -> Self {
+        let validity = Bitmap::from_bools(values.iter().map(|value| *value != Value::Null));
+        #[derive(PartialEq)]
+        enum Kind {
+            Boolean,
+            Integer,
+            Float,
+            String,
+            Mixed,
+        }
+        let mut kind = None;
+        for value in &values {
+            let next = match value {
+                Value::Null => continue,
+                Value::Boolean(_) => Kind::Boolean,
+                Value::Integer(_) => Kind::Integer,
+                Value::Float(_) => Kind::Float,
+                Value::String(_) => Kind::String,
+                _ => Kind::Mixed,
+            };
+            kind = Some(match kind {
+                None => next,
+                Some(kind) if kind == next => kind,
+                Some(Kind::Integer | Kind::Float)
+                    if matches!(next, Kind::Integer | Kind::Float) =>
+                {
+                    Kind::Float
+                }
+                Some(_) => Kind::Mixed,
+            });
+        }
+        let values = match kind {
+            Some(Kind::Boolean) => Vector::Boolean(
+                values
+                    .iter()
+                    .map(|value| *value == Value::Boolean(true))
+                    .collect(),
+            ),
+            Some(Kind::Integer) => Vector::Integer(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Integer(value) => *value,
+                        _ => 0,
+                    })
+                    .collect(),
+            ),
+            Some(Kind::Float) => Vector::Float(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Integer(value) => *value as f64,
+                        Value::Float(value) => *value,
+                        _ => 0.0,
+                    })
+                    .collect(),
+            ),
+            Some(Kind::String) => Vector::String(
+                values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::String(value) => value,
+                        _ => String::new(),
+                    })
+                    .collect(),
+            ),
+            None | Some(Kind::Mixed) => Vector::Values(values),
+        };
+        ColumnVector { values, validity }
+    }
+
+    /// The value repeated for every row.
+    pub fn constant(value: &Value, len: usize) -> Self {
+        let validity = Bitmap::new(len, *value != Value::Null);
+        let values = match value {
+            Value::Null => Vector::Values(vec![Value::Null; len]),
+            Value::Boolean(value) => Vector::Boolean(vec![*value; len]),
+            Value::Integer(value) => Vector::Integer(vec![*value; len]),
+            Value::Float(value) => Vector::Float(vec![*value; len]),
+            Value::String(value) => Vector::String(vec![value.clone(); len]),
+            value => Vector::Values(vec![value.clone(); len]),
+        };
+        ColumnVector { values, validity }
+    }
+
+    pub fn len(&self) -> usize {
+        self.validity.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn value(&self, index: usize) -> Value {
+        if !self.validity.get(index) {
+            return Value::Null;
+        }
+        match &self.values {
+            Vector::Boolean(values) => Value::Boolean(values[index]),
+            Vector::Integer(values) => Value::Integer(values[index]),
+            Vector::Float(values) => Value::Float(values[index]),
+            Vector::String(values) => Value::String(values[index].clone()),
+            Vector::Values(values) => values[index].clone(),
+        }
+    }
+}
+
+/// Rows of a batch, column by column. Only the rows in the selection belong to the
+/// result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordBatch {
+    pub columns: Vec<ColumnVector>,
+    pub selection: Bitmap,
+}
+
+impl RecordBatch {
+    /// A batch of the rows, all of them are selected.
+    pub fn from_rows(rows: Vec<Row>) -> Self {
+        let selection = Bitmap::new(rows.len(), true);
+        let width = rows.first().map_or(0, Vec::len);
+        let mut columns = vec![Vec::with_capacity(rows.len()); width];
+        for row in rows {
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.push(value);
+            }
+        }
+        RecordBatch {
+            columns: columns.into_iter().map(ColumnVector::from_values).collect(),
+            selection,
+        }
+    }
+
+    /// Number of rows, selected or not.
+    pub fn len(&self) -> usize {
+        self.selection.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn row(&self, index: usize) -> Row {
+        self.columns
+            .iter()
+            .map(|column| column.value(index))
+            .collect()
+    }
+
+    /// The selected rows.
+    pub fn rows(&self) -> Vec<Row> {
+        self.selection.ones().map(|index| self.row(index)).collect()
+    }
+}
+
+/// An operator which returns batches instead of rows, see `Operator`.
+pub trait BatchOperator {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError>;
+
+    /// The next batch, it may have no selected rows. `None` once all rows are returned.
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError>;
+
+    fn close(&mut self);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_bitmaps() {
+        let left = Bitmap::from_bools((0..130).map(|index| index % 2 == 0));
+        let right = Bitmap::from_bools((0..130).map(|index| index % 3 == 0));
+        assert_eq!(left.count(), 65);
+        assert_eq!(left.and(&right).ones().collect::<Vec<_>>().len(), 22);
+        assert_eq!(left.or(&right).count(), 87);
+        assert_eq!(left.not().count(), 65);
+        assert_eq!(left.and_not(&right).count(), 43);
+        assert_eq!(Bitmap::new(130, true).not().count(), 0);
+        assert!(Bitmap::new(70, true).all());
+        assert_eq!(right.ones().take(3).collect::<Vec<_>>(), [0, 3, 6]);
+    }
+
+    #[test]
+    fn converts_rows_to_columns() {
+        let rows = vec![
+            vec![Value::Integer(1), Value::Float(0.5), Value::Null],
+            vec![Value::Null, Value::Integer(2), Value::Null],
+        ];
+        let batch = RecordBatch::from_rows(rows.clone());
+        assert_eq!(batch.columns[0].values, Vector::Integer(vec![1, 0]));
+        assert_eq!(batch.columns[1].values, Vector::Float(vec![0.5, 2.0]));
+        assert_eq!(
+            batch.columns[2].values,
+            Vector::Values(vec![Value::Null; 2])
+        );
+        assert_eq!(batch.row(0), rows[0]);
+        assert_eq!(batch.row(1), [Value::Null, Value::Float(2.0), Value::Null]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Batch versions of the pipelined operators, and adapters between batch and row
+//! operators for the operators which only work a row at a time.
+
+use crate::catalog::Table;
+use crate::execution::batch::{BatchOperator, Bitmap, RecordBatch, Vector, BATCH_SIZE};
+use crate::execution::expr::ScalarExpr;
+use crate::execution::operators::scan::TableScan;
+use crate::execution::physical::ScanColumn;
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::value::Value;
+use std::collections::VecDeque;
+
+/// All rows of a table in batches of `BATCH_SIZE` rows.
+pub struct BatchScan {
+    table: Table,
+    columns: Vec<ScanColumn>,
+    rows: Option<TableScan>,
+}
+
+impl BatchScan {
+    pub fn new(table: Table, columns: Vec<ScanColumn>) -> Self {
+        BatchScan {
+            table,
+            columns,
+            rows: None,
+        }
+    }
+}
+
+impl BatchOperator for BatchScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.rows = Some(TableScan::start(&self.table, context)?);
+        Ok(())
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        let rows = self.rows.as_mut().expect("opened scan");
+        let mut columns = vec![Vec::with_capacity(BATCH_SIZE); self.columns.len()];
+        let mut len = 0;
+        while len < BATCH_SIZE {
+            let Some((row_id, mut row)) = rows.next(context)? else {
+                break;
+            };
+            for (values, column) in columns.iter_mut().zip(&self.columns) {
+                values.push(match column {
+                    ScanColumn::Column(position) => {
+                        std::mem::replace(&mut row[*position], Value::Null)
+                    }
+                    ScanColumn::RowId => Value::Integer(row_id as i64),
+                });
+            }
+            len += 1;
+        }
+        Ok((len > 0).then(|| RecordBatch {
+            columns: columns
+                .into_iter()
+                .map(crate::execution::batch::ColumnVector::from_values)
+                .collect(),
+            selection: Bitmap::new(len, true),
+        }))
+    }
+
+    fn close(&mut self) {
+        self.rows = None;
+    }
+}
+
+/// Clears the rows for which the predicate isn't true from the selection.
+pub struct BatchFilter {
+    input: Box<dyn BatchOperator>,
+    predicate: ScalarExpr,
+}
+
+impl BatchFilter {
+    pub fn new(input: Box<dyn BatchOperator>, predicate: ScalarExpr) -> Self {
+        BatchFilter { input, predicate }
+    }
+}
+
+impl BatchOperator for BatchFilter {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        while let Some(mut batch) = self.input.next_batch(context)? {
+            let predicate = self.predicate.eval_batch(&batch, context)?;
+            let is_true = match &predicate.values {
+                Vector::Boolean(values) => Bitmap::from_bools(values.iter().copied()),
+                _ => Bitmap::from_bools(
+                    (0..batch.len()).map(|index| predicate.value(index) == Value::Boolean(true)),
+                ),
+            };
+            batch.selection = batch.selection.and(&is_true.and(&predicate.validity));
+            // batches without a selected row aren't passed on
+            if batch.selection.count() > 0 {
+                return Ok(Some(batch));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+pub struct BatchProject {
+    input: Box<dyn BatchOperator>,
+    exprs: Vec<ScalarExpr>,
+}
+
+impl BatchProject {
+    pub fn new(input: Box<dyn BatchOperator>, exprs: Vec<ScalarExpr>) -> Self {
+        BatchProject { input, exprs }
+    }
+}
+
+impl BatchOperator for BatchProject {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        let Some(batch) = self.input.next_batch(context)? else {
+            return Ok(None);
+        };
+        let columns = self
+            .exprs
+            .iter()
+            .map(|expr| expr.eval_batch(&batch, context))
+            .collect::<Result<_, _>>()?;
+        Ok(Some(RecordBatch {
+            columns,
+            selection: batch.selection,
+        }))
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// Skips `offset` selected rows and keeps at most `limit` of the rest selected.
+pub struct BatchLimit {
+    input: Box<dyn BatchOperator>,
+    limit: Option<u64>,
+    offset: u64,
+    skipped: u64,
+    returned: u64,
+}
+
+impl BatchLimit {
+    pub fn new(input: Box<dyn BatchOperator>, limit: Option<u64>, offset: u64) -> Self {
+        BatchLimit {
+            input,
+            limit,
+            offset,
+            skipped: 0,
+            returned: 0,
+        }
+    }
+}
+
+impl BatchOperator for BatchLimit {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.skipped = 0;
+        self.returned = 0;
+        self.input.open(context)
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        if self.limit.is_some_and(|limit| self.returned >= limit) {
+            return Ok(None);
+        }
+        let Some(mut batch) = self.input.next_batch(context)? else {
+            return Ok(None);
+        };
+        let mut selection = Bitmap::new(batch.len(), false);
+        for index in batch.selection.ones() {
+            if self.skipped < self.offset {
+                self.skipped += 1;
+            } else if self.limit.is_none_or(|limit| self.returned < limit) {
+                self.returned += 1;
+                selection.set(index, true);
+            }
+        }
+        batch.selection = selection;
+        Ok(Some(batch))
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// Batches of every input, one after the other.
+pub struct BatchAppend {
+    inputs: Vec<Box<dyn BatchOperator>>,
+    current: usize,
+}
+
+impl BatchAppend {
+    pub fn new(inputs: Vec<Box<dyn BatchOperator>>) -> Self {
+        BatchAppend { inputs, current: 0 }
+    }
+}
+
+impl BatchOperator for BatchAppend {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.current = 0;
+        match self.inputs.first_mut() {
+            Some(input) => input.open(context),
+            None => Ok(()),
+        }
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        while let Some(input) = self.inputs.get_mut(self.current) {
+            if let Some(batch) = input.next_batch(context)? {
+                return Ok(Some(batch));
+            }
+            input.close();
+            self.current += 1;
+            if let Some(next) = self.inputs.get_mut(self.current) {
+                next.open(context)?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        for input in &mut self.inputs {
+            input.close();
+        }
+    }
+}
+
+/// Rows of a row operator in batches.
+pub struct RowBatches {
+    input: Box<dyn Operator>,
+}
+
+impl RowBatches {
+    pub fn new(input: Box<dyn Operator>) -> Self {
+        RowBatches { input }
+    }
+}
+
+impl BatchOperator for RowBatches {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        let mut rows = vec![];
+        while rows.len() < BATCH_SIZE {
+            match self.input.next(context)? {
+                Some(row) => rows.push(row),
+                None => break,
+            }
+        }
+        Ok((!rows.is_empty()).then(|| RecordBatch::from_rows(rows)))
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// Selected rows of a batch operator, one at a time.
+pub struct BatchRows {
+    input: Box<dyn BatchOperator>,
+    rows: VecDeque<Row>,
+}
+
+impl BatchRows {
+    pub fn new(input: Box<dyn BatchOperator>) -> Self {
+        BatchRows {
+            input,
+            rows: VecDeque::new(),
+        }
+    }
+}
+
+impl Operator for BatchRows {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.rows.clear();
+        self.input.open(context)
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.rows.pop_front() {
+                return Ok(Some(row));
+            }
+            match self.input.next_batch(context)? {
+                Some(batch) => self.rows = batch.rows().into(),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.rows.clear();
+        self.input.close();
+    }
+}
@@ -0,0 +1,1004 @@
+//! Expressions compiled against the rows of an operator, columns are read by position.
+//!
+//! Evaluation follows SQL's three-valued logic: an operator with a `NULL` operand is
+//! `NULL`, except `AND` and `OR` when the other operand decides the result, and the
+//! `IS NULL` and `IS DISTINCT FROM` tests.
+
+use crate::analyser::aggregates::is_aggregate;
+use crate::analyser::bound::*;
+use crate::execution::pattern::escape_char;
+use crate::execution::physical::PhysicalPlan;
+use crate::execution::{execute, ExecutionContext, ExecutionError, Row};
+use crate::functions::{builtins, operator_function, ResolveError, ScalarFunction};
+use crate::optimizer::{optimize, referenced_columns};
+use crate::parser::expr::{Literal, Operator, Parameter, PatternKind, UnaryOperator};
+use crate::planner::builder::build_select;
+use crate::planner::PlanColumn;
+use crate::value::temporal::{self, MICROS_PER_DAY};
+use crate::value::{DataType, Decimal, Value};
+use std::cmp::Ordering;
+use std::sync::{Arc, OnceLock};
+
+#[derive(Debug, Clone)]
+pub enum ScalarExpr {
+    Column(usize),
+    Literal(Value),
+    /// Value the executed prepared statement binds to the parameter
+    Parameter(Parameter),
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<ScalarExpr>,
+    },
+    Binary {
+        left: Box<ScalarExpr>,
+        operator: Operator,
+        right: Box<ScalarExpr>,
+    },
+    IsNull {
+        expr: Box<ScalarExpr>,
+        negated: bool,
+    },
+    IsDistinctFrom {
+        left: Box<ScalarExpr>,
+        right: Box<ScalarExpr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<ScalarExpr>,
+        list: Vec<ScalarExpr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<ScalarExpr>,
+        low: Box<ScalarExpr>,
+        high: Box<ScalarExpr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<ScalarExpr>,
+        pattern: Box<ScalarExpr>,
+        escape: Option<Box<ScalarExpr>>,
+        kind: PatternKind,
+        negated: bool,
+    },
+    Case {
+        operand: Option<Box<ScalarExpr>>,
+        when_then: Vec<(ScalarExpr, ScalarExpr)>,
+        else_result: Option<Box<ScalarExpr>>,
+    },
+    Cast {
+        expr: Box<ScalarExpr>,
+        data_type: DataType,
+    },
+    /// Call of a registry function, the arguments have the parameter types of the signature
+    Function {
+        function: Arc<ScalarFunction>,
+        signature: usize,
+        arguments: Vec<ScalarExpr>,
+    },
+    Subquery(Box<Subquery>),
+}
+
+/// A subquery is planned and executed when it is evaluated, with the values of the
+/// outer columns it reads in place of the columns.
+#[derive(Debug, Clone)]
+pub struct Subquery {
+    kind: SubqueryKind,
+    select: BoundSelect,
+    /// Columns of the enclosing query the subquery reads and their positions in the row
+    outer: Vec<(BoundColumn, usize)>,
+    /// Rows of a subquery which reads no outer columns, it is executed once
+    rows: OnceLock<Vec<Row>>,
+}
+
+#[derive(Debug, Clone)]
+enum SubqueryKind {
+    Exists {
+        negated: bool,
+    },
+    /// The operands are compared with the columns of a row
+    In {
+        operands: Vec<ScalarExpr>,
+        negated: bool,
+    },
+    Scalar,
+}
+
+impl ScalarExpr {
+    /// Integers are computed as `bigint`, a result of a narrower type is checked to fit it.
+    fn within_range(self, data_type: Option<DataType>) -> ScalarExpr {
+        match data_type {
+            Some(data_type @ (DataType::TinyInt | DataType::SmallInt | DataType::Integer)) => {
+                ScalarExpr::Cast {
+                    expr: Box::new(self),
+                    data_type,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// Columns are found in the schema by their expression, as a node of the logical
+    /// plan refers to the columns of its input.
+    pub fn compile(expr: &BoundExpr, schema: &[PlanColumn]) -> Result<ScalarExpr, ExecutionError> {
+        if let Some(position) = schema.iter().position(|column| &column.expr == expr) {
+            return Ok(ScalarExpr::Column(position));
+        }
+        let compile = |expr: &BoundExpr| ScalarExpr::compile(expr, schema).map(Box::new);
+        Ok(match expr {
+            BoundExpr::Column(column) => {
+                return Err(ExecutionError::Unsupported(format!(
+                    "reading column \"{}\" which the input doesn't return",
+                    column.name
+                )))
+            }
+            BoundExpr::Literal(literal) => ScalarExpr::Literal(Value::from_literal(literal)),
+            BoundExpr::Function(function) if is_aggregate(function) => {
+                return Err(ExecutionError::Unsupported(format!(
+                    "aggregate {} outside of an aggregation",
+                    function.name
+                )))
+            }
+            BoundExpr::Function(function) if function.over.is_some() => {
+                return Err(ExecutionError::Unsupported("window function".to_string()))
+            }
+            BoundExpr::Function(function) => {
+                let arguments: Vec<_> = function.arguments.iter().collect();
+                ScalarExpr::call(&function.name, function.scalar(), &arguments, schema)?
+            }
+            BoundExpr::Wildcard | BoundExpr::Default | BoundExpr::Tuple(_) => {
+                return Err(ExecutionError::Unsupported(format!(
+                    "expression {:?}",
+                    expr
+                )))
+            }
+            BoundExpr::Parameter(parameter) => ScalarExpr::Parameter(parameter.clone()),
+            BoundExpr::Unary {
+                operator,
+                expr: operand,
+                ..
+            } => ScalarExpr::Unary {
+                operator: *operator,
+                expr: compile(operand)?,
+            }
+            .within_range(expr.data_type()),
+            BoundExpr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => match operator_function(*operator, left.data_type(), right.data_type()) {
+                // the executor concatenates itself
+                Some(name) if *operator != Operator::Concat => {
+                    ScalarExpr::call(name, builtins().get(name), &[left, right], schema)?
+                }
+                _ => ScalarExpr::Binary {
+                    left: compile(left)?,
+                    operator: *operator,
+                    right: compile(right)?,
+                }
+                .within_range(expr.data_type()),
+            },
+            BoundExpr::IsNull { expr, negated } => ScalarExpr::IsNull {
+                expr: compile(expr)?,
+                negated: *negated,
+            },
+            BoundExpr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => ScalarExpr::IsDistinctFrom {
+                left: compile(left)?,
+                right: compile(right)?,
+                negated: *negated,
+            },
+            BoundExpr::InList {
+                expr,
+                list,
+                negated,
+            } => ScalarExpr::InList {
+                expr: compile(expr)?,
+                list: list
+                    .iter()
+                    .map(|item| ScalarExpr::compile(item, schema))
+                    .collect::<Result<_, _>>()?,
+                negated: *negated,
+            },
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => ScalarExpr::Between {
+                expr: compile(expr)?,
+                low: compile(low)?,
+                high: compile(high)?,
+                negated: *negated,
+            },
+            BoundExpr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => ScalarExpr::Like {
+                expr: compile(expr)?,
+                pattern: compile(pattern)?,
+                escape: escape.as_deref().map(compile).transpose()?,
+                kind: *kind,
+                negated: *negated,
+            },
+            BoundExpr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => ScalarExpr::Case {
+                operand: operand.as_deref().map(compile).transpose()?,
+                when_then: when_then
+                    .iter()
+                    .map(|(when, then)| {
+                        Ok((
+                            ScalarExpr::compile(when, schema)?,
+                            ScalarExpr::compile(then, schema)?,
+                        ))
+                    })
+                    .collect::<Result<_, ExecutionError>>()?,
+                else_result: else_result.as_deref().map(compile).transpose()?,
+            },
+            BoundExpr::Cast { expr, data_type } => ScalarExpr::Cast {
+                expr: compile(expr)?,
+                data_type: *data_type,
+            },
+            BoundExpr::Exists { subquery, negated } => {
+                Subquery::compile(SubqueryKind::Exists { negated: *negated }, subquery, schema)?
+            }
+            BoundExpr::InSubquery {
+                expr: operand,
+                subquery,
+                negated,
+            } => {
+                let operands = match operand.as_ref() {
+                    BoundExpr::Tuple(operands) => operands.iter().collect(),
+                    operand => vec![operand],
+                };
+                let kind = SubqueryKind::In {
+                    operands: operands
+                        .into_iter()
+                        .map(|operand| ScalarExpr::compile(operand, schema))
+                        .collect::<Result<_, _>>()?,
+                    negated: *negated,
+                };
+                Subquery::compile(kind, subquery, schema)?
+            }
+            BoundExpr::Subquery(subquery) => {
+                Subquery::compile(SubqueryKind::Scalar, subquery, schema)?
+            }
+        })
+    }
+
+    /// Call of the signature of the function the arguments resolve to, they already have
+    /// the parameter types as the type checker casts them.
+    fn call(
+        name: &str,
+        function: Option<&Arc<ScalarFunction>>,
+        arguments: &[&BoundExpr],
+        schema: &[PlanColumn],
+    ) -> Result<ScalarExpr, ExecutionError> {
+        let types: Vec<_> = arguments
+            .iter()
+            .map(|argument| argument.data_type())
+            .collect();
+        let resolved = function
+            .ok_or(ResolveError::Undefined)
+            .and_then(|function| function.resolve(&types))
+            .map_err(|_| ExecutionError::Unsupported(format!("function {}", name)))?;
+        Ok(ScalarExpr::Function {
+            function: resolved.function,
+            signature: resolved.signature,
+            arguments: arguments
+                .iter()
+                .map(|argument| ScalarExpr::compile(argument, schema))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Value of an expression which reads no columns.
+    pub fn constant(
+        expr: &BoundExpr,
+        context: &mut ExecutionContext,
+    ) -> Result<Value, ExecutionError> {
+        ScalarExpr::compile(expr, &[])?.eval(&[], context)
+    }
+
+    pub fn eval(
+        &self,
+        row: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<Value, ExecutionError> {
+        Ok(match self {
+            ScalarExpr::Column(position) => row[*position].clone(),
+            ScalarExpr::Literal(value) => value.clone(),
+            ScalarExpr::Parameter(parameter) => context
+                .parameters
+                .iter()
+                .find(|(bound, _)| bound == parameter)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| {
+                    ExecutionError::Unsupported("parameter without a value".to_string())
+                })?,
+            ScalarExpr::Unary { operator, expr } => match (operator, expr.eval(row, context)?) {
+                (_, Value::Null) => Value::Null,
+                (UnaryOperator::Not, value) => Value::Boolean(!truth(&value)?),
+                (UnaryOperator::Minus, Value::Integer(value)) => {
+                    Value::Integer(value.checked_neg().ok_or(ExecutionError::OutOfRange {
+                        data_type: DataType::BigInt,
+                    })?)
+                }
+                (UnaryOperator::Minus, Value::Float(value)) => Value::Float(-value),
+                (UnaryOperator::Minus, Value::Decimal(value)) => Value::Decimal(value.neg()),
+                (UnaryOperator::Minus, value) => return Err(mismatch(&value, "a number")),
+                (UnaryOperator::Plus, value) => value,
+            },
+            ScalarExpr::Binary {
+                left,
+                operator: Operator::And,
+                right,
+            } => match left.eval(row, context)? {
+                Value::Boolean(false) => Value::Boolean(false),
+                left => match (left, right.eval(row, context)?) {
+                    (_, Value::Boolean(false)) => Value::Boolean(false),
+                    (Value::Boolean(true), Value::Boolean(true)) => Value::Boolean(true),
+                    _ => Value::Null,
+                },
+            },
+            ScalarExpr::Binary {
+                left,
+                operator: Operator::Or,
+                right,
+            } => match left.eval(row, context)? {
+                Value::Boolean(true) => Value::Boolean(true),
+                left => match (left, right.eval(row, context)?) {
+                    (_, Value::Boolean(true)) => Value::Boolean(true),
+                    (Value::Boolean(false), Value::Boolean(false)) => Value::Boolean(false),
+                    _ => Value::Null,
+                },
+            },
+            ScalarExpr::Binary {
+                left,
+                operator,
+                right,
+            } => binary(
+                &left.eval(row, context)?,
+                *operator,
+                &right.eval(row, context)?,
+            )?,
+            ScalarExpr::IsNull { expr, negated } => {
+                Value::Boolean((expr.eval(row, context)? == Value::Null) != *negated)
+            }
+            ScalarExpr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => {
+                let (left, right) = (left.eval(row, context)?, right.eval(row, context)?);
+                Value::Boolean(distinct(&left, &right) != *negated)
+            }
+            ScalarExpr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.eval(row, context)?;
+                let mut result = Value::Boolean(false);
+                for item in list {
+                    match equal(&value, &item.eval(row, context)?) {
+                        Value::Boolean(true) => {
+                            result = Value::Boolean(true);
+                            break;
+                        }
+                        Value::Null => result = Value::Null,
+                        _ => {}
+                    }
+                }
+                negate(result, *negated)
+            }
+            ScalarExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expr.eval(row, context)?;
+                let low = binary(
+                    &value,
+                    Operator::GreaterThanOrEqual,
+                    &low.eval(row, context)?,
+                )?;
+                let high = binary(&value, Operator::LessThanOrEqual, &high.eval(row, context)?)?;
+                let result = match (low, high) {
+                    (Value::Boolean(false), _) | (_, Value::Boolean(false)) => {
+                        Value::Boolean(false)
+                    }
+                    (Value::Boolean(true), Value::Boolean(true)) => Value::Boolean(true),
+                    _ => Value::Null,
+                };
+                negate(result, *negated)
+            }
+            ScalarExpr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => {
+                let escape = match escape {
+                    Some(escape) => match escape.eval(row, context)? {
+                        Value::String(escape) => Some(escape),
+                        Value::Null => return Ok(Value::Null),
+                        value => return Err(mismatch(&value, "text")),
+                    },
+                    None => None,
+                };
+                match (expr.eval(row, context)?, pattern.eval(row, context)?) {
+                    (Value::String(text), Value::String(pattern)) => {
+                        let escape = match kind.escapes() {
+                            true => escape_char(escape.as_deref())?,
+                            false => None,
+                        };
+                        let pattern = context.patterns.get(*kind, &pattern, escape)?;
+                        Value::Boolean(pattern.matches(&text) != *negated)
+                    }
+                    (Value::Null, _) | (_, Value::Null) => Value::Null,
+                    (value, _) => return Err(mismatch(&value, "text")),
+                }
+            }
+            ScalarExpr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                let operand = match operand {
+                    Some(operand) => Some(operand.eval(row, context)?),
+                    None => None,
+                };
+                for (when, then) in when_then {
+                    let when = when.eval(row, context)?;
+                    let matched = match &operand {
+                        Some(operand) => equal(operand, &when),
+                        None => when,
+                    };
+                    if matched == Value::Boolean(true) {
+                        return then.eval(row, context);
+                    }
+                }
+                match else_result {
+                    Some(result) => result.eval(row, context)?,
+                    None => Value::Null,
+                }
+            }
+            ScalarExpr::Cast { expr, data_type } => cast(expr.eval(row, context)?, *data_type)?,
+            ScalarExpr::Function {
+                function,
+                signature,
+                arguments,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.eval(row, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(*signature, &arguments, context)?
+            }
+            ScalarExpr::Subquery(subquery) => subquery.eval(row, context)?,
+        })
+    }
+}
+
+impl Subquery {
+    fn compile(
+        kind: SubqueryKind,
+        select: &BoundSelect,
+        schema: &[PlanColumn],
+    ) -> Result<ScalarExpr, ExecutionError> {
+        // the operand of an `in` is compiled on its own
+        let outer = referenced_columns(&BoundExpr::Subquery(Box::new(select.clone())))
+            .into_iter()
+            .map(|column| {
+                let expr = BoundExpr::Column(column.clone());
+                match schema.iter().position(|candidate| candidate.expr == expr) {
+                    Some(position) => Ok((column, position)),
+                    None => Err(ExecutionError::Unsupported(format!(
+                        "reading column \"{}\" which the input doesn't return",
+                        column.name
+                    ))),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ScalarExpr::Subquery(Box::new(Subquery {
+            kind,
+            select: select.clone(),
+            outer,
+            rows: OnceLock::new(),
+        })))
+    }
+
+    fn eval(&self, row: &[Value], context: &mut ExecutionContext) -> Result<Value, ExecutionError> {
+        let rows = match self.rows.get() {
+            Some(rows) => rows.clone(),
+            None => {
+                let values: Vec<_> = self
+                    .outer
+                    .iter()
+                    .map(|(column, position)| (column.clone(), row[*position].clone()))
+                    .collect();
+                let select = replace_columns(&self.select, &values);
+                let plan = optimize(build_select(&select)?, context.catalog);
+                let rows = execute(PhysicalPlan::build(&plan, context.catalog)?, context)?;
+                if self.outer.is_empty() {
+                    let _ = self.rows.set(rows.clone());
+                }
+                rows
+            }
+        };
+        Ok(match &self.kind {
+            SubqueryKind::Exists { negated } => Value::Boolean(rows.is_empty() == *negated),
+            SubqueryKind::In { operands, negated } => {
+                let operands = operands
+                    .iter()
+                    .map(|operand| operand.eval(row, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut result = Value::Boolean(false);
+                for candidate in &rows {
+                    let mut matched = Value::Boolean(true);
+                    for (operand, value) in operands.iter().zip(candidate) {
+                        match equal(operand, value) {
+                            Value::Boolean(true) => {}
+                            Value::Null => matched = Value::Null,
+                            unequal => {
+                                matched = unequal;
+                                break;
+                            }
+                        }
+                    }
+                    match matched {
+                        Value::Boolean(true) => {
+                            result = matched;
+                            break;
+                        }
+                        Value::Null => result = Value::Null,
+                        _ => {}
+                    }
+                }
+                negate(result, *negated)
+            }
+            SubqueryKind::Scalar => match rows.len() {
+                0 => Value::Null,
+                1 => rows[0][0].clone(),
+                _ => return Err(ExecutionError::SubqueryRows),
+            },
+        })
+    }
+}
+
+/// The select with the columns replaced by their values, in its subqueries as well.
+fn replace_columns(select: &BoundSelect, values: &[(BoundColumn, Value)]) -> BoundSelect {
+    let replace = |expr: &BoundExpr| replace_in_expr(expr, values);
+    BoundSelect {
+        from: select.from.as_ref().map(|from| BoundFrom {
+            relations: from.relations.clone(),
+            joins: from
+                .joins
+                .iter()
+                .map(|join| BoundJoin {
+                    on: join.on.as_ref().map(replace),
+                    ..join.clone()
+                })
+                .collect(),
+        }),
+        projection: select
+            .projection
+            .iter()
+            .map(|item| ProjectionItem {
+                expr: replace(&item.expr),
+                name: item.name.clone(),
+            })
+            .collect(),
+        distinct: select.distinct,
+        filter: select.filter.as_ref().map(replace),
+        group_by: select.group_by.iter().map(replace).collect(),
+        having: select.having.as_ref().map(replace),
+        unions: select
+            .unions
+            .iter()
+            .map(|union| BoundUnion {
+                all: union.all,
+                query: replace_columns(&union.query, values),
+            })
+            .collect(),
+        order_by: select
+            .order_by
+            .iter()
+            .map(|(expr, order)| (replace(expr), *order))
+            .collect(),
+        limit: select.limit,
+        offset: select.offset,
+    }
+}
+
+fn replace_in_expr(expr: &BoundExpr, values: &[(BoundColumn, Value)]) -> BoundExpr {
+    match expr {
+        BoundExpr::Column(column) => match values.iter().find(|(candidate, _)| candidate == column)
+        {
+            Some((column, value)) => BoundExpr::Cast {
+                expr: Box::new(BoundExpr::Literal(literal(value))),
+                data_type: column.data_type,
+            },
+            None => expr.clone(),
+        },
+        BoundExpr::Exists { subquery, negated } => BoundExpr::Exists {
+            subquery: Box::new(replace_columns(subquery, values)),
+            negated: *negated,
+        },
+        BoundExpr::InSubquery {
+            expr,
+            subquery,
+            negated,
+        } => BoundExpr::InSubquery {
+            expr: Box::new(replace_in_expr(expr, values)),
+            subquery: Box::new(replace_columns(subquery, values)),
+            negated: *negated,
+        },
+        BoundExpr::Subquery(subquery) => {
+            BoundExpr::Subquery(Box::new(replace_columns(subquery, values)))
+        }
+        expr => crate::optimizer::map_children(expr, &mut |child| replace_in_expr(child, values)),
+    }
+}
+
+fn literal(value: &Value) -> Literal {
+    match value {
+        // infinities and `NaN` too, which `Value::from_literal` reads back
+        Value::Float(value) => Literal::Numeric(format!("{:?}", value)),
+        value => value.to_literal().expect("finite floats have a literal"),
+    }
+}
+
+fn mismatch(value: &Value, expected: &str) -> ExecutionError {
+    ExecutionError::Unsupported(format!("{} where {} is expected", value, expected))
+}
+
+/// Truth of a boolean value.
+fn truth(value: &Value) -> Result<bool, ExecutionError> {
+    match value {
+        Value::Boolean(value) => Ok(*value),
+        value => Err(mismatch(value, "a boolean")),
+    }
+}
+
+fn negate(value: Value, negated: bool) -> Value {
+    match value {
+        Value::Boolean(value) => Value::Boolean(value != negated),
+        value => value,
+    }
+}
+
+/// `=` of two values, `NULL` if one of them is `NULL`.
+fn equal(left: &Value, right: &Value) -> Value {
+    match compare(left, right) {
+        Some(ordering) => Value::Boolean(ordering == Ordering::Equal),
+        None => Value::Null,
+    }
+}
+
+/// `IS DISTINCT FROM` of two values, `<>` with `NULL` equal to `NULL` and unequal to
+/// anything else.
+pub(crate) fn distinct(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Null, Value::Null) => false,
+        (Value::Null, _) | (_, Value::Null) => true,
+        (left, right) => compare(left, right) != Some(Ordering::Equal),
+    }
+}
+
+/// Order of two values, `None` if one of them is `NULL`. Values of different kinds,
+/// which the type checker doesn't let meet, are ordered by kind.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (left, right) => Some(left.cmp(right)),
+    }
+}
+
+pub fn binary(left: &Value, operator: Operator, right: &Value) -> Result<Value, ExecutionError> {
+    if *left == Value::Null || *right == Value::Null {
+        return Ok(Value::Null);
+    }
+    let ordering = || compare(left, right).expect("values aren't null");
+    Ok(match operator {
+        Operator::Equal => Value::Boolean(ordering() == Ordering::Equal),
+        Operator::NotEqual => Value::Boolean(ordering() != Ordering::Equal),
+        Operator::LessThan => Value::Boolean(ordering() == Ordering::Less),
+        Operator::LessThanOrEqual => Value::Boolean(ordering() != Ordering::Greater),
+        Operator::GreaterThan => Value::Boolean(ordering() == Ordering::Greater),
+        Operator::GreaterThanOrEqual => Value::Boolean(ordering() != Ordering::Less),
+        Operator::And | Operator::Or => {
+            let (left, right) = (truth(left)?, truth(right)?);
+            Value::Boolean(match operator {
+                Operator::And => left && right,
+                _ => left || right,
+            })
+        }
+        Operator::Concat => Value::String(format!("{}{}", text(left), text(right))),
+        operator => arithmetic(left, operator, right)?,
+    })
+}
+
+fn arithmetic(left: &Value, operator: Operator, right: &Value) -> Result<Value, ExecutionError> {
+    match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => {
+            let result = match operator {
+                Operator::Plus => left.checked_add(*right),
+                Operator::Minus => left.checked_sub(*right),
+                Operator::Multiply => left.checked_mul(*right),
+                Operator::Divide | Operator::Modulo if *right == 0 => {
+                    return Err(ExecutionError::DivisionByZero)
+                }
+                Operator::Divide => left.checked_div(*right),
+                _ => left.checked_rem(*right),
+            };
+            result
+                .map(Value::Integer)
+                .ok_or(ExecutionError::OutOfRange {
+                    data_type: DataType::BigInt,
+                })
+        }
+        (Value::Integer(_) | Value::Decimal(_), Value::Integer(_) | Value::Decimal(_)) => {
+            let (left, right) = (decimal(left), decimal(right));
+            let result = match operator {
+                Operator::Plus => left.checked_add(&right),
+                Operator::Minus => left.checked_sub(&right),
+                Operator::Multiply => left.checked_mul(&right),
+                Operator::Divide | Operator::Modulo if right.is_zero() => {
+                    return Err(ExecutionError::DivisionByZero)
+                }
+                Operator::Divide => left.checked_div(&right, left.division_scale(&right)),
+                _ => left.checked_rem(&right),
+            };
+            result
+                .map(Value::Decimal)
+                .ok_or(ExecutionError::OutOfRange {
+                    data_type: DataType::Decimal(None),
+                })
+        }
+        (
+            Value::Integer(_) | Value::Float(_) | Value::Decimal(_),
+            Value::Integer(_) | Value::Float(_) | Value::Decimal(_),
+        ) => {
+            let (left, right) = (float(left), float(right));
+            Ok(Value::Float(match operator {
+                Operator::Plus => left + right,
+                Operator::Minus => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide | Operator::Modulo if right == 0.0 => {
+                    return Err(ExecutionError::DivisionByZero)
+                }
+                Operator::Divide => left / right,
+                _ => left % right,
+            }))
+        }
+        (Value::Integer(_) | Value::Float(_) | Value::Decimal(_), value) | (value, _) => {
+            Err(mismatch(value, "a number"))
+        }
+    }
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Float(value) => *value,
+        Value::Decimal(value) => value.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+fn decimal(value: &Value) -> Decimal {
+    match value {
+        Value::Integer(value) => Decimal::from_i64(*value),
+        Value::Decimal(value) => value.clone(),
+        _ => Decimal::zero(),
+    }
+}
+
+/// The value as text, as it is shown to a client.
+pub fn text(value: &Value) -> String {
+    value.text()
+}
+
+/// The value converted to the type, e.g. when it is stored in a column.
+pub fn cast(value: Value, data_type: DataType) -> Result<Value, ExecutionError> {
+    let invalid = |value: &Value| ExecutionError::InvalidCast {
+        value: text(value),
+        data_type,
+    };
+    let out_of_range = ExecutionError::OutOfRange { data_type };
+    Ok(match (value, data_type) {
+        (Value::Null, _) => Value::Null,
+        (Value::Boolean(value), DataType::Boolean) => Value::Boolean(value),
+        (Value::String(value), DataType::Boolean) => match value.trim().to_lowercase().as_str() {
+            "t" | "true" | "yes" | "on" | "1" => Value::Boolean(true),
+            "f" | "false" | "no" | "off" | "0" => Value::Boolean(false),
+            _ => return Err(invalid(&Value::String(value))),
+        },
+        (value, DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt) => {
+            let integer = match &value {
+                Value::Integer(integer) => *integer,
+                Value::Float(float) if float.is_finite() && float.abs() < 9.2e18 => {
+                    float.round() as i64
+                }
+                Value::Float(_) => return Err(out_of_range),
+                Value::Decimal(decimal) => decimal.to_i64().ok_or(out_of_range)?,
+                Value::String(text) => text.trim().parse().map_err(|_| invalid(&value))?,
+                Value::Boolean(value) => *value as i64,
+                _ => return Err(invalid(&value)),
+            };
+            let fits = match data_type {
+                DataType::TinyInt => i8::try_from(integer).is_ok(),
+                DataType::SmallInt => i16::try_from(integer).is_ok(),
+                DataType::Integer => i32::try_from(integer).is_ok(),
+                _ => true,
+            };
+            if !fits {
+                return Err(ExecutionError::OutOfRange { data_type });
+            }
+            Value::Integer(integer)
+        }
+        (value, DataType::Real | DataType::Double) => match &value {
+            Value::Integer(integer) => Value::Float(*integer as f64),
+            Value::Float(float) => Value::Float(*float),
+            Value::Decimal(decimal) => Value::Float(decimal.to_f64()),
+            Value::String(text) => Value::Float(text.trim().parse().map_err(|_| invalid(&value))?),
+            _ => return Err(invalid(&value)),
+        },
+        (value, DataType::Decimal(constraint)) => {
+            let decimal = match value {
+                Value::Integer(integer) => Decimal::from_i64(integer),
+                Value::Float(float) => Decimal::from_f64(float).ok_or_else(|| invalid(&value))?,
+                Value::Decimal(decimal) => decimal,
+                Value::String(ref text) => {
+                    Decimal::parse(text.trim()).ok_or_else(|| invalid(&value))?
+                }
+                value => return Err(invalid(&value)),
+            };
+            match constraint {
+                Some((precision, scale)) => {
+                    let decimal = decimal.round(scale);
+                    if decimal.integer_digits() > precision - scale {
+                        return Err(out_of_range);
+                    }
+                    Value::Decimal(decimal)
+                }
+                None => Value::Decimal(decimal),
+            }
+        }
+        (value, DataType::VarChar(Some(length))) => {
+            let value = text(&value);
+            if value.chars().count() > length as usize {
+                return Err(ExecutionError::OutOfRange { data_type });
+            }
+            Value::String(value)
+        }
+        (value, DataType::VarChar(None) | DataType::Text) => Value::String(text(&value)),
+        (Value::String(value), _) => match temporal::parse(value.trim(), data_type) {
+            Some(value) => value,
+            None => return Err(invalid(&Value::String(value))),
+        },
+        (value, DataType::Interval) => match value {
+            Value::Interval(interval) => Value::Interval(interval),
+            value => return Err(invalid(&value)),
+        },
+        // a timestamp is a timestamp with time zone in UTC
+        (value, DataType::Date | DataType::Time | DataType::Timestamp | DataType::TimestampTz) => {
+            let micros = match value {
+                Value::Date(days) => days as i64 * MICROS_PER_DAY,
+                Value::Timestamp(micros) | Value::TimestampTz(micros) => micros,
+                Value::Time(micros) if data_type == DataType::Time => micros,
+                value => return Err(invalid(&value)),
+            };
+            match data_type {
+                DataType::Date => Value::Date(micros.div_euclid(MICROS_PER_DAY) as i32),
+                DataType::Time => Value::Time(micros.rem_euclid(MICROS_PER_DAY)),
+                DataType::Timestamp => Value::Timestamp(micros),
+                _ => Value::TimestampTz(micros),
+            }
+        }
+        (value, DataType::Boolean) => return Err(invalid(&value)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_three_valued_logic() {
+        let null = Value::Null;
+        let (yes, no) = (Value::Boolean(true), Value::Boolean(false));
+        assert_eq!(binary(&null, Operator::Equal, &null), Ok(Value::Null));
+        assert_eq!(
+            binary(&Value::Integer(1), Operator::LessThan, &Value::Float(1.5)),
+            Ok(yes.clone())
+        );
+        assert_eq!(equal(&"a".into(), &"a".into()), yes);
+        assert_eq!(equal(&"a".into(), &null), null);
+        assert_eq!(negate(no.clone(), true), yes);
+        assert_eq!(negate(null.clone(), true), null);
+    }
+
+    #[test]
+    fn computes_arithmetic() {
+        let integer = |value: i64| Value::Integer(value);
+        assert_eq!(
+            binary(&integer(7), Operator::Divide, &integer(2)),
+            Ok(integer(3))
+        );
+        assert_eq!(
+            binary(&integer(7), Operator::Modulo, &integer(-2)),
+            Ok(integer(1))
+        );
+        assert_eq!(
+            binary(&integer(1), Operator::Plus, &Value::Float(0.5)),
+            Ok(Value::Float(1.5))
+        );
+        assert_eq!(
+            binary(&integer(1), Operator::Divide, &integer(0)),
+            Err(ExecutionError::DivisionByZero)
+        );
+        assert_eq!(
+            binary(&integer(i64::MAX), Operator::Plus, &integer(1)),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::BigInt
+            })
+        );
+        assert_eq!(
+            binary(&"a".into(), Operator::Concat, &integer(1)),
+            Ok(Value::from("a1"))
+        );
+    }
+
+    #[test]
+    fn casts_values() {
+        assert_eq!(
+            cast(Value::Integer(2), DataType::Double),
+            Ok(Value::Float(2.0))
+        );
+        assert_eq!(
+            cast(Value::Float(2.5), DataType::Integer),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            cast(" 42 ".into(), DataType::BigInt),
+            Ok(Value::Integer(42))
+        );
+        assert_eq!(
+            cast("t".into(), DataType::Boolean),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            cast(Value::Integer(7), DataType::Text),
+            Ok(Value::from("7"))
+        );
+        assert_eq!(
+            cast(Value::Integer(70000), DataType::SmallInt),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::SmallInt
+            })
+        );
+        assert_eq!(
+            cast("x".into(), DataType::Integer),
+            Err(ExecutionError::InvalidCast {
+                value: "x".to_string(),
+                data_type: DataType::Integer
+            })
+        );
+        assert!(cast("abc".into(), DataType::VarChar(Some(2))).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Executes a physical plan, a tree of operators which pull rows from their inputs.
+//!
+//! Every operator is opened, asked for rows with `next` until it has none left, and
+//! closed (the Volcano model). Blocking operators, e.g. a sort or the build side of a
+//! hash join, read all rows of their input when they are opened.
+
+pub mod batch;
+pub mod expr;
+pub mod operators;
+pub mod parallel;
+pub mod pattern;
+pub mod physical;
+pub mod spill;
+pub mod table;
+
+use crate::catalog::{Catalog, CatalogError};
+use crate::execution::parallel::Worker;
+use crate::execution::pattern::PatternCache;
+use crate::execution::physical::PhysicalPlan;
+use crate::execution::spill::QueryMemory;
+use crate::parser::expr::Parameter;
+use crate::planner::PlanError;
+use crate::storage::{KvStore, StorageError};
+use crate::value::encoding::DecodeError;
+use crate::value::{DataType, Value};
+use std::time::SystemTime;
+use std::{fmt, io};
+
+/// Values of a row in the order of the columns of the operator which returns it.
+pub type Row = Vec<Value>;
+
+/// What the operators of a statement share: the rows are read from and written to the
+/// store, subqueries are planned against the catalog, and the memory of the rows held by
+/// sorts, hash joins and hash aggregates is reserved from the budget of the query.
+pub struct ExecutionContext<'a> {
+    pub store: &'a mut dyn KvStore,
+    pub catalog: &'a dyn Catalog,
+    pub memory: QueryMemory,
+    /// What the exchange nodes read in a worker thread of a parallel plan
+    pub worker: Option<Worker>,
+    /// What `now()` returns, the same for the whole statement
+    pub start_time: SystemTime,
+    /// The `LIKE` patterns and regular expressions the statement compiled
+    pub patterns: PatternCache,
+    /// Values of the parameters of an executed prepared statement
+    pub parameters: &'a [(Parameter, Value)],
+}
+
+/// How a plan is executed, a row or a batch of rows at a time.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ExecutionMode {
+    #[default]
+    Row,
+    Batch,
+}
+
+pub trait Operator {
+    /// Prepares the operator to return rows, blocking operators read their input here.
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError>;
+
+    /// The next row, `None` once all rows are returned.
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError>;
+
+    /// Releases the rows the operator holds, it can be opened again.
+    fn close(&mut self);
+}
+
+/// Rows the plan returns, the operators are closed when execution fails as well.
+pub fn execute(
+    plan: PhysicalPlan,
+    context: &mut ExecutionContext,
+) -> Result<Vec<Row>, ExecutionError> {
+    execute_in(plan, ExecutionMode::Row, context)
+}
+
+pub fn execute_in(
+    plan: PhysicalPlan,
+    mode: ExecutionMode,
+    context: &mut ExecutionContext,
+) -> Result<Vec<Row>, ExecutionError> {
+    let mut operator = plan.operator(mode);
+    let mut rows = vec![];
+    let result = operator.open(context).and_then(|()| {
+        while let Some(row) = operator.next(context)? {
+            rows.push(row);
+        }
+        Ok(())
+    });
+    operator.close();
+    result.map(|()| rows)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExecutionError {
+    Plan(PlanError),
+    /// The plan has a node or expression the engine can't execute yet.
+    Unsupported(String),
+    DivisionByZero,
+    /// The value doesn't fit into the type, e.g. an integer overflow.
+    OutOfRange {
+        data_type: DataType,
+    },
+    InvalidCast {
+        value: String,
+        data_type: DataType,
+    },
+    /// A subquery used as an expression returned more than one row.
+    SubqueryRows,
+    NotNullViolation {
+        column_name: String,
+    },
+    UniqueViolation {
+        constraint_name: String,
+    },
+    /// `on conflict do update` conflicts with a row the statement inserted or updated.
+    RowAffectedTwice,
+    /// Stored rows can't be decoded.
+    Corrupted(String),
+    /// Rows can't be written to or read from a spill file.
+    Spill(String),
+    /// The thread of a worker of a parallel plan can't be started.
+    Worker(String),
+    /// A function argument is out of the function's domain, e.g. `sqrt(-1)`.
+    InvalidArgument(String),
+    /// The store the rows are kept in failed.
+    Storage(StorageError),
+}
+
+impl From<PlanError> for ExecutionError {
+    fn from(error: PlanError) -> Self {
+        ExecutionError::Plan(error)
+    }
+}
+
+impl From<CatalogError> for ExecutionError {
+    fn from(error: CatalogError) -> Self {
+        match error {
+            CatalogError::Storage(error) => ExecutionError::Storage(error),
+            error => ExecutionError::Corrupted(error.to_string()),
+        }
+    }
+}
+
+impl From<StorageError> for ExecutionError {
+    fn from(error: StorageError) -> Self {
+        ExecutionError::Storage(error)
+    }
+}
+
+impl From<DecodeError> for ExecutionError {
+    fn from(error: DecodeError) -> Self {
+        ExecutionError::Corrupted(error.to_string())
+    }
+}
+
+/// The engine only does I/O when operators spill.
+impl From<io::Error> for ExecutionError {
+    fn from(error: io::Error) -> Self {
+        ExecutionError::Spill(error.to_string())
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Plan(error) => write!(f, "{}", error),
+            ExecutionError::Unsupported(feature) => write!(f, "{} is not supported", feature),
+            ExecutionError::DivisionByZero => f.write_str("division by zero"),
+            ExecutionError::OutOfRange { data_type } => {
+                write!(f, "{} out of range", data_type.to_string().to_lowercase())
+            }
+            ExecutionError::InvalidCast { value, data_type } => write!(
+                f,
+                "invalid input syntax for type {}: \"{}\"",
+                data_type.to_string().to_lowercase(),
+                value
+            ),
+            ExecutionError::SubqueryRows => {
+                f.write_str("more than one row returned by a subquery used as an expression")
+            }
+            ExecutionError::NotNullViolation { column_name } => write!(
+                f,
+                "null value in column \"{}\" violates not-null constraint",
+                column_name
+            ),
+            ExecutionError::UniqueViolation { constraint_name } => write!(
+                f,
+                "duplicate key value violates unique constraint \"{}\"",
+                constraint_name
+            ),
+            ExecutionError::RowAffectedTwice => {
+                f.write_str("ON CONFLICT DO UPDATE command cannot affect row a second time")
+            }
+            ExecutionError::Corrupted(reason) => write!(f, "stored data is corrupted: {}", reason),
+            ExecutionError::Spill(reason) => write!(f, "can't spill to disk: {}", reason),
+            ExecutionError::Worker(reason) => write!(f, "can't start a worker: {}", reason),
+            ExecutionError::InvalidArgument(reason) => f.write_str(reason),
+            ExecutionError::Storage(error) => write!(f, "{}", error),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Grouping with a hash table on the group keys, `NULL` keys form a group of their own.
+
+use crate::execution::expr::ScalarExpr;
+use crate::execution::spill::{
+    row_size, MemoryReservation, Partitions, SpillFile, SpillReader, MAX_LEVEL,
+};
+use crate::execution::table::encode_key;
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::functions::aggregate::{AggregateFunction, AggregateState};
+use crate::value::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// Step of a two-phase aggregation. The workers of a parallel plan aggregate their rows
+/// partially, the partial groups are merged once they are gathered.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum AggregatePhase {
+    #[default]
+    Single,
+    /// The row of a group has the state of each aggregate instead of its result,
+    /// `distinct` and ordered-set aggregates can't be partial
+    Partial,
+    /// The input rows are partial groups, the group keys followed by the state of each
+    /// aggregate
+    Final,
+}
+
+/// Aggregate call, `count(*)` has no arguments.
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub function: Arc<dyn AggregateFunction>,
+    pub arguments: Vec<ScalarExpr>,
+    pub distinct: bool,
+}
+
+impl Aggregate {
+    /// Number of values of a partial state.
+    pub(crate) fn width(&self) -> usize {
+        self.function.init().len()
+    }
+
+    /// The result of the aggregate, or its state for a partial aggregate.
+    pub(crate) fn output(
+        &self,
+        state: &AggregateState,
+        phase: AggregatePhase,
+    ) -> Result<Vec<Value>, ExecutionError> {
+        match phase {
+            AggregatePhase::Partial => Ok(state.clone()),
+            _ => Ok(vec![self.function.finalize(state)?]),
+        }
+    }
+}
+
+/// State of an aggregate in a group.
+#[derive(Debug, Clone)]
+pub(crate) struct Accumulator {
+    pub(crate) state: AggregateState,
+    /// Arguments seen by a `distinct` aggregate
+    seen: HashSet<Vec<u8>>,
+}
+
+impl Accumulator {
+    pub(crate) fn new(aggregate: &Aggregate) -> Self {
+        Accumulator {
+            state: aggregate.function.init(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Adds the argument values of a row, rows with a `NULL` argument are skipped by
+    /// strict aggregates.
+    pub(crate) fn update(
+        &mut self,
+        aggregate: &Aggregate,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        if aggregate.function.null_handling().skips(arguments)
+            || (aggregate.distinct && !self.seen.insert(encode_key(arguments)))
+        {
+            return Ok(());
+        }
+        aggregate.function.update(&mut self.state, arguments)
+    }
+}
+
+/// Reads all rows of the input when it is opened and returns a row per group in the
+/// order the groups were first seen. Without group keys there is a single group, even
+/// for no rows.
+///
+/// Once the groups exceed the memory budget of the query, the rows of new groups are
+/// written to partitions while the groups in memory are still updated (hybrid hash
+/// aggregation). The partitions are aggregated one after the other when the groups in
+/// memory are returned.
+pub struct HashAggregate {
+    input: Box<dyn Operator>,
+    group_by: Vec<ScalarExpr>,
+    aggregates: Vec<Aggregate>,
+    phase: AggregatePhase,
+    output: VecDeque<Row>,
+    /// Partitions still to aggregate, with the level they were partitioned at
+    partitions: Vec<(SpillFile, usize)>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl HashAggregate {
+    pub fn new(
+        input: Box<dyn Operator>,
+        group_by: Vec<ScalarExpr>,
+        aggregates: Vec<Aggregate>,
+        phase: AggregatePhase,
+    ) -> Self {
+        HashAggregate {
+            input,
+            group_by,
+            aggregates,
+            phase,
+            output: VecDeque::new(),
+            partitions: vec![],
+            reservation: None,
+        }
+    }
+
+    fn accumulators(&self) -> Vec<Accumulator> {
+        self.aggregates.iter().map(Accumulator::new).collect()
+    }
+
+    /// Groups the rows of the input or of a partition of the given level, the rows of
+    /// groups which don't fit into memory are partitioned again.
+    fn aggregate(
+        &mut self,
+        mut partition: Option<SpillReader>,
+        level: usize,
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        let mut reservation = context.memory.reservation();
+        let mut positions = HashMap::new();
+        let mut groups: Vec<(Row, Vec<Accumulator>)> = vec![];
+        let mut spilled: Option<Partitions> = None;
+        if self.group_by.is_empty() {
+            groups.push((vec![], self.accumulators()));
+            positions.insert(vec![], 0);
+        }
+        while let Some(row) = match &mut partition {
+            Some(rows) => rows.next_row()?,
+            None => self.input.next(context)?,
+        } {
+            let key = self
+                .group_by
+                .iter()
+                .map(|expr| expr.eval(&row, context))
+                .collect::<Result<Row, _>>()?;
+            let encoded = encode_key(&key);
+            let position = match positions.get(&encoded) {
+                Some(position) => *position,
+                None => {
+                    if let Some(partitions) = &mut spilled {
+                        partitions.write(&encoded, &row)?;
+                        continue;
+                    }
+                    let size = row_size(&key)
+                        + encoded.len()
+                        + self.aggregates.len() * size_of::<Accumulator>();
+                    if !reservation.try_grow(size) {
+                        if level < MAX_LEVEL && !groups.is_empty() {
+                            let mut partitions = Partitions::new(&mut context.memory, level)?;
+                            partitions.write(&encoded, &row)?;
+                            spilled = Some(partitions);
+                            continue;
+                        }
+                        reservation.grow(size);
+                    }
+                    groups.push((key, self.accumulators()));
+                    positions.insert(encoded, groups.len() - 1);
+                    groups.len() - 1
+                }
+            };
+            let accumulators = &mut groups[position].1;
+            let mut state = self.group_by.len();
+            for (aggregate, accumulator) in self.aggregates.iter().zip(accumulators) {
+                if self.phase == AggregatePhase::Final {
+                    let width = aggregate.width();
+                    aggregate
+                        .function
+                        .merge(&mut accumulator.state, &row[state..state + width])?;
+                    state += width;
+                    continue;
+                }
+                let arguments = aggregate
+                    .arguments
+                    .iter()
+                    .map(|argument| argument.eval(&row, context))
+                    .collect::<Result<Row, _>>()?;
+                accumulator.update(aggregate, &arguments)?;
+            }
+        }
+        self.output = groups
+            .into_iter()
+            .map(|(mut key, accumulators)| {
+                for (aggregate, accumulator) in self.aggregates.iter().zip(&accumulators) {
+                    key.extend(aggregate.output(&accumulator.state, self.phase)?);
+                }
+                Ok(key)
+            })
+            .collect::<Result<_, ExecutionError>>()?;
+        if let Some(partitions) = spilled {
+            self.partitions.extend(
+                partitions
+                    .into_files()
+                    .into_iter()
+                    .map(|file| (file, level + 1)),
+            );
+        }
+        self.reservation = Some(reservation);
+        Ok(())
+    }
+}
+
+impl Operator for HashAggregate {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.close();
+        self.input.open(context)?;
+        let result = self.aggregate(None, 0, context);
+        self.input.close();
+        result
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.output.pop_front() {
+                return Ok(Some(row));
+            }
+            self.reservation = None;
+            let Some((partition, level)) = self.partitions.pop() else {
+                return Ok(None);
+            };
+            self.aggregate(Some(partition.into_reader()?), level, context)?;
+        }
+    }
+
+    fn close(&mut self) {
+        self.output.clear();
+        self.partitions.clear();
+        self.reservation = None;
+    }
+}
@@ -0,0 +1,507 @@
+//! Joins. The right input is read when the join is opened, the left rows are streamed
+//! and matched with the right rows the algorithm finds as candidates.
+//!
+//! Rows with a `NULL` key match no row, as `NULL = NULL` isn't true.
+
+use crate::execution::expr::{compare, ScalarExpr};
+use crate::execution::operators::drain;
+use crate::execution::physical::JoinSpec;
+use crate::execution::spill::{
+    row_size, MemoryReservation, Partitions, SpillFile, SpillReader, MAX_LEVEL,
+};
+use crate::execution::table::encode_key;
+use crate::execution::{ExecutionContext, ExecutionError, ExecutionMode, Operator, Row};
+use crate::planner::JoinType;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+
+/// Matching of the left rows with candidate right rows, the same for all algorithms.
+struct Matcher {
+    left: Box<dyn Operator>,
+    right: Box<dyn Operator>,
+    join_type: JoinType,
+    keys: Vec<(ScalarExpr, ScalarExpr)>,
+    condition: Option<ScalarExpr>,
+    right_rows: Vec<Row>,
+    /// Whether a right row matched, for right and full joins
+    right_matched: Vec<bool>,
+    left_width: usize,
+    right_width: usize,
+    output: VecDeque<Row>,
+    finished: bool,
+}
+
+impl Matcher {
+    fn new(join: JoinSpec, mode: ExecutionMode) -> Self {
+        Matcher {
+            left: join.left.operator(mode),
+            right: join.right.operator(mode),
+            join_type: join.join_type,
+            keys: join.keys,
+            condition: join.condition,
+            right_rows: vec![],
+            right_matched: vec![],
+            left_width: join.left_width,
+            right_width: join.right_width,
+            output: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.output.clear();
+        self.finished = false;
+        self.right.open(context)?;
+        self.right_rows = drain(self.right.as_mut(), context)?;
+        self.right.close();
+        self.right_matched = vec![false; self.right_rows.len()];
+        self.left.open(context)
+    }
+
+    /// Key of the row, `None` if a value of it is `NULL`.
+    fn key(
+        &self,
+        row: &[Value],
+        left: bool,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let mut key = vec![];
+        for (left_key, right_key) in &self.keys {
+            let value = if left { left_key } else { right_key }.eval(row, context)?;
+            if value == Value::Null {
+                return Ok(None);
+            }
+            key.push(value);
+        }
+        Ok(Some(key))
+    }
+
+    /// Matches the left row with the candidates, the output rows are queued.
+    fn probe(
+        &mut self,
+        left: Row,
+        candidates: &[usize],
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        let mut matched = false;
+        for &candidate in candidates {
+            let mut row = left.clone();
+            row.extend(self.right_rows[candidate].iter().cloned());
+            if let Some(condition) = &self.condition {
+                if condition.eval(&row, context)? != Value::Boolean(true) {
+                    continue;
+                }
+            }
+            matched = true;
+            self.right_matched[candidate] = true;
+            match self.join_type {
+                JoinType::Semi | JoinType::Anti => break,
+                _ => self.output.push_back(row),
+            }
+        }
+        match self.join_type {
+            JoinType::Semi if matched => self.output.push_back(left),
+            JoinType::Anti if !matched => self.output.push_back(left),
+            JoinType::Left | JoinType::Full if !matched => {
+                let mut row = left;
+                row.extend(std::iter::repeat_n(Value::Null, self.right_width));
+                self.output.push_back(row);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        self.unmatched();
+        self.finished = true;
+    }
+
+    /// Queues the right rows no left row matched, for right and full joins.
+    fn unmatched(&mut self) {
+        if matches!(self.join_type, JoinType::Right | JoinType::Full) {
+            for (row, matched) in self.right_rows.iter().zip(&self.right_matched) {
+                if !matched {
+                    let mut output = vec![Value::Null; self.left_width];
+                    output.extend(row.iter().cloned());
+                    self.output.push_back(output);
+                }
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.left.close();
+        self.right_rows.clear();
+        self.right_matched.clear();
+        self.output.clear();
+    }
+}
+
+/// Compares every left row with every right row.
+pub struct NestedLoopJoin {
+    matcher: Matcher,
+    all: Vec<usize>,
+}
+
+impl NestedLoopJoin {
+    pub fn new(join: JoinSpec, mode: ExecutionMode) -> Self {
+        NestedLoopJoin {
+            matcher: Matcher::new(join, mode),
+            all: vec![],
+        }
+    }
+}
+
+impl Operator for NestedLoopJoin {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.matcher.open(context)?;
+        self.all = (0..self.matcher.right_rows.len()).collect();
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.matcher.output.pop_front() {
+                return Ok(Some(row));
+            }
+            if self.matcher.finished {
+                return Ok(None);
+            }
+            match self.matcher.left.next(context)? {
+                Some(left) => {
+                    let candidates = match self.matcher.key(&left, true, context)? {
+                        Some(key) => self.equal_keys(&key, context)?,
+                        None => vec![],
+                    };
+                    self.matcher.probe(left, &candidates, context)?;
+                }
+                None => self.matcher.finish(),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.matcher.close();
+    }
+}
+
+impl NestedLoopJoin {
+    /// Right rows with the key, all of them without keys.
+    fn equal_keys(
+        &self,
+        key: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<Vec<usize>, ExecutionError> {
+        if key.is_empty() {
+            return Ok(self.all.clone());
+        }
+        let mut candidates = vec![];
+        for (position, row) in self.matcher.right_rows.iter().enumerate() {
+            if let Some(right) = self.matcher.key(row, false, context)? {
+                if compare_keys(key, &right) == Ordering::Equal {
+                    candidates.push(position);
+                }
+            }
+        }
+        Ok(candidates)
+    }
+}
+
+/// Builds a hash table of the right rows on their keys and probes it with the left rows.
+///
+/// When the right rows exceed the memory budget of the query, both inputs are
+/// partitioned on their keys and the pairs of partitions are joined one after the other
+/// (grace hash join). A pair whose right rows still don't fit is partitioned again.
+pub struct HashJoin {
+    matcher: Matcher,
+    table: HashMap<Vec<u8>, Vec<usize>>,
+    reservation: Option<MemoryReservation>,
+    /// Whether the inputs were partitioned
+    spilled: bool,
+    /// Pairs of right and left partitions still to join, with their level
+    partitions: Vec<(SpillFile, SpillFile, usize)>,
+    /// Left rows of the pair being joined
+    probe: Option<SpillReader>,
+}
+
+impl HashJoin {
+    pub fn new(join: JoinSpec, mode: ExecutionMode) -> Self {
+        HashJoin {
+            matcher: Matcher::new(join, mode),
+            table: HashMap::new(),
+            reservation: None,
+            spilled: false,
+            partitions: vec![],
+            probe: None,
+        }
+    }
+
+    /// Encoded key of a row, empty if a value of it is `NULL`.
+    fn encoded_key(
+        &self,
+        row: &[Value],
+        left: bool,
+        context: &mut ExecutionContext,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        Ok(self
+            .matcher
+            .key(row, left, context)?
+            .map(|key| encode_key(&key))
+            .unwrap_or_default())
+    }
+
+    /// Reads the right rows of the input or of a partition of the given level into the
+    /// hash table, the partitions are returned if they don't fit.
+    fn build(
+        &mut self,
+        mut partition: Option<SpillReader>,
+        level: usize,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<Partitions>, ExecutionError> {
+        self.table.clear();
+        self.matcher.right_rows.clear();
+        let mut reservation = context.memory.reservation();
+        let mut spilled: Option<Partitions> = None;
+        while let Some(row) = match &mut partition {
+            Some(rows) => rows.next_row()?,
+            None => self.matcher.right.next(context)?,
+        } {
+            let key = self.encoded_key(&row, false, context)?;
+            if let Some(partitions) = &mut spilled {
+                partitions.write(&key, &row)?;
+                continue;
+            }
+            let size = row_size(&row) + key.len();
+            if !reservation.try_grow(size) {
+                if level < MAX_LEVEL && !self.matcher.right_rows.is_empty() {
+                    let mut partitions = Partitions::new(&mut context.memory, level)?;
+                    for row in std::mem::take(&mut self.matcher.right_rows) {
+                        partitions.write(&self.encoded_key(&row, false, context)?, &row)?;
+                    }
+                    partitions.write(&key, &row)?;
+                    self.table.clear();
+                    reservation.free();
+                    spilled = Some(partitions);
+                    continue;
+                }
+                reservation.grow(size);
+            }
+            if !key.is_empty() {
+                let position = self.matcher.right_rows.len();
+                self.table.entry(key).or_default().push(position);
+            }
+            self.matcher.right_rows.push(row);
+        }
+        self.matcher.right_matched = vec![false; self.matcher.right_rows.len()];
+        self.reservation = Some(reservation);
+        Ok(spilled)
+    }
+
+    /// Partitions the left rows of the input or of a partition like the right rows, the
+    /// pairs of partitions are queued.
+    fn partition_left(
+        &mut self,
+        right: Partitions,
+        mut partition: Option<SpillReader>,
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        let level = right.level();
+        let mut left = Partitions::new(&mut context.memory, level)?;
+        while let Some(row) = match &mut partition {
+            Some(rows) => rows.next_row()?,
+            None => self.matcher.left.next(context)?,
+        } {
+            left.write(&self.encoded_key(&row, true, context)?, &row)?;
+        }
+        self.partitions.extend(
+            right
+                .into_files()
+                .into_iter()
+                .zip(left.into_files())
+                .filter(|(right, left)| !right.is_empty() || !left.is_empty())
+                .map(|(right, left)| (right, left, level + 1)),
+        );
+        Ok(())
+    }
+
+    /// Starts joining the next pair of partitions, `false` once all are joined.
+    fn next_partition(&mut self, context: &mut ExecutionContext) -> Result<bool, ExecutionError> {
+        while let Some((right, left, level)) = self.partitions.pop() {
+            self.reservation = None;
+            let spilled = self.build(Some(right.into_reader()?), level, context)?;
+            let left = left.into_reader()?;
+            match spilled {
+                Some(right) => self.partition_left(right, Some(left), context)?,
+                None => {
+                    self.probe = Some(left);
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Operator for HashJoin {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.close();
+        self.matcher.finished = false;
+        self.matcher.right.open(context)?;
+        let spilled = self.build(None, 0, context);
+        self.matcher.right.close();
+        self.matcher.left.open(context)?;
+        if let Some(right) = spilled? {
+            self.spilled = true;
+            self.partition_left(right, None, context)?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.matcher.output.pop_front() {
+                return Ok(Some(row));
+            }
+            if self.matcher.finished {
+                return Ok(None);
+            }
+            let left = match &mut self.probe {
+                Some(rows) => rows.next_row()?,
+                None if self.spilled => None,
+                None => self.matcher.left.next(context)?,
+            };
+            match left {
+                Some(left) => {
+                    let candidates = match self.matcher.key(&left, true, context)? {
+                        Some(key) => self
+                            .table
+                            .get(&encode_key(&key))
+                            .cloned()
+                            .unwrap_or_default(),
+                        None => vec![],
+                    };
+                    self.matcher.probe(left, &candidates, context)?;
+                }
+                None if self.spilled => {
+                    if self.probe.take().is_some() {
+                        self.matcher.unmatched();
+                    }
+                    if !self.next_partition(context)? {
+                        self.matcher.finished = true;
+                        self.reservation = None;
+                    }
+                }
+                None => {
+                    self.matcher.finish();
+                    self.reservation = None;
+                }
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.matcher.close();
+        self.table.clear();
+        self.reservation = None;
+        self.spilled = false;
+        self.partitions.clear();
+        self.probe = None;
+    }
+}
+
+/// Sorts both inputs on their keys and merges them, the left rows are read when the
+/// join is opened as well.
+pub struct SortMergeJoin {
+    matcher: Matcher,
+    /// Left rows with their keys in key order, rows with a `NULL` key at the end
+    left_rows: VecDeque<(Option<Vec<Value>>, Row)>,
+    /// Positions of the right rows with a key in key order
+    right_order: Vec<(Vec<Value>, usize)>,
+    /// First right row whose key isn't smaller than the key of the current left row
+    cursor: usize,
+}
+
+impl SortMergeJoin {
+    pub fn new(join: JoinSpec, mode: ExecutionMode) -> Self {
+        SortMergeJoin {
+            matcher: Matcher::new(join, mode),
+            left_rows: VecDeque::new(),
+            right_order: vec![],
+            cursor: 0,
+        }
+    }
+}
+
+impl Operator for SortMergeJoin {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.matcher.open(context)?;
+        self.right_order.clear();
+        for position in 0..self.matcher.right_rows.len() {
+            let row = &self.matcher.right_rows[position];
+            if let Some(key) = self.matcher.key(row, false, context)? {
+                self.right_order.push((key, position));
+            }
+        }
+        self.right_order
+            .sort_by(|(left, _), (right, _)| compare_keys(left, right));
+
+        let mut left_rows = vec![];
+        for row in drain(self.matcher.left.as_mut(), context)? {
+            left_rows.push((self.matcher.key(&row, true, context)?, row));
+        }
+        left_rows.sort_by(|(left, _), (right, _)| match (left, right) {
+            (Some(left), Some(right)) => compare_keys(left, right),
+            (left, right) => right.is_some().cmp(&left.is_some()),
+        });
+        self.left_rows = left_rows.into();
+        self.cursor = 0;
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.matcher.output.pop_front() {
+                return Ok(Some(row));
+            }
+            if self.matcher.finished {
+                return Ok(None);
+            }
+            match self.left_rows.pop_front() {
+                Some((Some(key), left)) => {
+                    while self
+                        .right_order
+                        .get(self.cursor)
+                        .is_some_and(|(right, _)| compare_keys(right, &key) == Ordering::Less)
+                    {
+                        self.cursor += 1;
+                    }
+                    let candidates: Vec<_> = self.right_order[self.cursor..]
+                        .iter()
+                        .take_while(|(right, _)| compare_keys(right, &key) == Ordering::Equal)
+                        .map(|(_, position)| *position)
+                        .collect();
+                    self.matcher.probe(left, &candidates, context)?;
+                }
+                Some((None, left)) => self.matcher.probe(left, &[], context)?,
+                None => self.matcher.finish(),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.matcher.close();
+        self.left_rows.clear();
+        self.right_order.clear();
+    }
+}
+
+/// Order of two keys without `NULL`s.
+fn compare_keys(left: &[Value], right: &[Value]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .map(|(left, right)| compare(left, right).unwrap_or(Ordering::Equal))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,212 @@
+//! Operators of the physical plan, the blocking ones and the joins have modules of their
+//! own.
+
+pub mod aggregate;
+pub mod join;
+pub mod modify;
+pub mod scan;
+pub mod sort;
+pub mod window;
+
+use crate::execution::expr::ScalarExpr;
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::value::Value;
+
+/// Rows of the input for which the predicate is true.
+pub struct Filter {
+    input: Box<dyn Operator>,
+    predicate: ScalarExpr,
+}
+
+impl Filter {
+    pub fn new(input: Box<dyn Operator>, predicate: ScalarExpr) -> Self {
+        Filter { input, predicate }
+    }
+}
+
+impl Operator for Filter {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(row) = self.input.next(context)? {
+            if self.predicate.eval(&row, context)? == Value::Boolean(true) {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// The values of the expressions for every row of the input.
+pub struct Project {
+    input: Box<dyn Operator>,
+    exprs: Vec<ScalarExpr>,
+}
+
+impl Project {
+    pub fn new(input: Box<dyn Operator>, exprs: Vec<ScalarExpr>) -> Self {
+        Project { input, exprs }
+    }
+}
+
+impl Operator for Project {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        match self.input.next(context)? {
+            Some(row) => Ok(Some(eval_all(&self.exprs, &row, context)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// Skips `offset` rows of the input and returns at most `limit` of the rest.
+pub struct Limit {
+    input: Box<dyn Operator>,
+    limit: Option<u64>,
+    offset: u64,
+    returned: u64,
+}
+
+impl Limit {
+    pub fn new(input: Box<dyn Operator>, limit: Option<u64>, offset: u64) -> Self {
+        Limit {
+            input,
+            limit,
+            offset,
+            returned: 0,
+        }
+    }
+}
+
+impl Operator for Limit {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.returned = 0;
+        self.input.open(context)?;
+        for _ in 0..self.offset {
+            if self.input.next(context)?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        if self.limit.is_some_and(|limit| self.returned >= limit) {
+            return Ok(None);
+        }
+        let row = self.input.next(context)?;
+        if row.is_some() {
+            self.returned += 1;
+        }
+        Ok(row)
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+    }
+}
+
+/// Rows of every input, one after the other. An input is opened once the one before it
+/// has returned all its rows.
+pub struct Append {
+    inputs: Vec<Box<dyn Operator>>,
+    current: usize,
+}
+
+impl Append {
+    pub fn new(inputs: Vec<Box<dyn Operator>>) -> Self {
+        Append { inputs, current: 0 }
+    }
+}
+
+impl Operator for Append {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.current = 0;
+        match self.inputs.first_mut() {
+            Some(input) => input.open(context),
+            None => Ok(()),
+        }
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(input) = self.inputs.get_mut(self.current) {
+            if let Some(row) = input.next(context)? {
+                return Ok(Some(row));
+            }
+            input.close();
+            self.current += 1;
+            if let Some(next) = self.inputs.get_mut(self.current) {
+                next.open(context)?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        for input in &mut self.inputs {
+            input.close();
+        }
+    }
+}
+
+/// Rows given as expressions, e.g. by `values`.
+pub struct Values {
+    rows: Vec<Vec<ScalarExpr>>,
+    position: usize,
+}
+
+impl Values {
+    pub fn new(rows: Vec<Vec<ScalarExpr>>) -> Self {
+        Values { rows, position: 0 }
+    }
+}
+
+impl Operator for Values {
+    fn open(&mut self, _context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        let Some(row) = self.rows.get(self.position) else {
+            return Ok(None);
+        };
+        self.position += 1;
+        eval_all(row, &[], context).map(Some)
+    }
+
+    fn close(&mut self) {}
+}
+
+fn eval_all(
+    exprs: &[ScalarExpr],
+    row: &[Value],
+    context: &mut ExecutionContext,
+) -> Result<Row, ExecutionError> {
+    exprs.iter().map(|expr| expr.eval(row, context)).collect()
+}
+
+/// Reads all rows of an operator, for the blocking operators.
+fn drain(
+    input: &mut dyn Operator,
+    context: &mut ExecutionContext,
+) -> Result<Vec<Row>, ExecutionError> {
+    let mut rows = vec![];
+    while let Some(row) = input.next(context)? {
+        rows.push(row);
+    }
+    Ok(rows)
+}
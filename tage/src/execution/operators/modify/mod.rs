@@ -0,0 +1,366 @@
+//! `insert`, `update` and `delete`. Every changed row is returned, the values of
+//! `returning` or an empty row, so the rows are counted. The indexes of the table are
+//! kept up to date and the constraints are checked row by row.
+
+use crate::catalog::Index;
+use crate::execution::expr::{cast, ScalarExpr};
+use crate::execution::operators::drain;
+use crate::execution::physical::{Conflict, Target};
+use crate::execution::table::{
+    delete_index_entry, delete_row, index_lookup, index_values, insert_index_entry, insert_row,
+    read_row, write_row, RowId,
+};
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::value::Value;
+use std::collections::{HashSet, VecDeque};
+
+impl Target {
+    /// The row returned for a changed table row.
+    fn output(
+        &self,
+        row: &[Value],
+        input: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<Row, ExecutionError> {
+        let Some(returning) = &self.returning else {
+            return Ok(vec![]);
+        };
+        let row: Row = row.iter().chain(input).cloned().collect();
+        returning
+            .iter()
+            .map(|expr| expr.eval(&row, context))
+            .collect()
+    }
+
+    fn check_not_null(&self, row: &[Value]) -> Result<(), ExecutionError> {
+        match self
+            .table
+            .columns
+            .iter()
+            .zip(row)
+            .find(|(column, value)| !column.nullable && **value == Value::Null)
+        {
+            Some((column, _)) => Err(ExecutionError::NotNullViolation {
+                column_name: column.name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if another row has the values of a unique index, values with a `NULL` are
+    /// never duplicates.
+    fn check_unique(
+        &self,
+        row: &[Value],
+        row_id: Option<RowId>,
+        context: &ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        for index in self.indexes.iter().filter(|index| index.unique) {
+            if existing_row(index, &self.table, row, context)?
+                .is_some_and(|existing| Some(existing) != row_id)
+            {
+                return Err(ExecutionError::UniqueViolation {
+                    constraint_name: index.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(
+        &self,
+        row: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<RowId, ExecutionError> {
+        self.check_not_null(row)?;
+        self.check_unique(row, None, context)?;
+        let row_id = insert_row(context.store, &self.table, row)?;
+        for index in &self.indexes {
+            insert_index_entry(
+                context.store,
+                index,
+                &index_values(index, &self.table, row),
+                row_id,
+            )?;
+        }
+        Ok(row_id)
+    }
+
+    fn update(
+        &self,
+        row_id: RowId,
+        old: &[Value],
+        new: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        self.check_not_null(new)?;
+        self.check_unique(new, Some(row_id), context)?;
+        for index in &self.indexes {
+            let (old, new) = (
+                index_values(index, &self.table, old),
+                index_values(index, &self.table, new),
+            );
+            if old != new {
+                delete_index_entry(context.store, index, &old, row_id)?;
+                insert_index_entry(context.store, index, &new, row_id)?;
+            }
+        }
+        write_row(context.store, &self.table, row_id, new)
+    }
+
+    fn delete(
+        &self,
+        row_id: RowId,
+        row: &[Value],
+        context: &mut ExecutionContext,
+    ) -> Result<(), ExecutionError> {
+        for index in &self.indexes {
+            delete_index_entry(
+                context.store,
+                index,
+                &index_values(index, &self.table, row),
+                row_id,
+            )?;
+        }
+        delete_row(context.store, &self.table, row_id)
+    }
+}
+
+/// The row with the values of the row in a unique index.
+fn existing_row(
+    index: &Index,
+    table: &crate::catalog::Table,
+    row: &[Value],
+    context: &ExecutionContext,
+) -> Result<Option<RowId>, ExecutionError> {
+    let values = index_values(index, table, row);
+    if values.contains(&Value::Null) {
+        return Ok(None);
+    }
+    Ok(index_lookup(context.store, index, &values)?
+        .first()
+        .copied())
+}
+
+/// Row id of a row the input returns, read from the row id column.
+fn row_id(row: &[Value], position: usize) -> Result<RowId, ExecutionError> {
+    match row[position] {
+        Value::Integer(row_id) => Ok(row_id as RowId),
+        _ => Err(ExecutionError::Corrupted(
+            "row without a row id".to_string(),
+        )),
+    }
+}
+
+/// A row `on conflict do update` would change a second time fails the statement.
+pub struct Insert {
+    input: Box<dyn Operator>,
+    target: Target,
+    columns: Vec<Option<usize>>,
+    defaults: Vec<ScalarExpr>,
+    on_conflict: Option<Conflict>,
+    /// Rows the statement inserted or updated
+    affected: HashSet<RowId>,
+}
+
+impl Insert {
+    pub fn new(
+        input: Box<dyn Operator>,
+        target: Target,
+        columns: Vec<Option<usize>>,
+        defaults: Vec<ScalarExpr>,
+        on_conflict: Option<Conflict>,
+    ) -> Self {
+        Insert {
+            input,
+            target,
+            columns,
+            defaults,
+            on_conflict,
+            affected: HashSet::new(),
+        }
+    }
+
+    /// The stored row the conflicting row is, with its values.
+    fn conflicting_row(
+        &self,
+        conflict: &Conflict,
+        row: &[Value],
+        context: &ExecutionContext,
+    ) -> Result<Option<(RowId, Row)>, ExecutionError> {
+        let indexes = match conflict.indexes.is_empty() {
+            true => &self.target.indexes,
+            false => &conflict.indexes,
+        };
+        for index in indexes.iter().filter(|index| index.unique) {
+            if let Some(row_id) = existing_row(index, &self.target.table, row, context)? {
+                if let Some(existing) = read_row(context.store, &self.target.table, row_id)? {
+                    return Ok(Some((row_id, existing)));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Operator for Insert {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.affected.clear();
+        self.input.open(context)
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(input) = self.input.next(context)? {
+            let mut row = vec![];
+            for (column, position) in self.target.table.columns.iter().zip(&self.columns) {
+                row.push(match position {
+                    Some(position) => cast(input[*position].clone(), column.data_type)?,
+                    None => self.defaults[row.len()].eval(&[], context)?,
+                });
+            }
+            if let Some(conflict) = &self.on_conflict {
+                if let Some((row_id, existing)) = self.conflicting_row(conflict, &row, context)? {
+                    let Some(update) = &conflict.update else {
+                        continue;
+                    };
+                    if self.affected.contains(&row_id) {
+                        return Err(ExecutionError::RowAffectedTwice);
+                    }
+                    let both: Row = existing.iter().chain(&row).cloned().collect();
+                    if let Some(filter) = &update.filter {
+                        if filter.eval(&both, context)? != Value::Boolean(true) {
+                            continue;
+                        }
+                    }
+                    let mut new = existing.clone();
+                    for (position, value) in &update.assignments {
+                        new[*position] = value.eval(&both, context)?;
+                    }
+                    self.target.update(row_id, &existing, &new, context)?;
+                    self.affected.insert(row_id);
+                    return self.target.output(&new, &[], context).map(Some);
+                }
+            }
+            let row_id = self.target.insert(&row, context)?;
+            if self.on_conflict.is_some() {
+                self.affected.insert(row_id);
+            }
+            return self.target.output(&row, &[], context).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.input.close();
+        self.affected.clear();
+    }
+}
+
+/// Reads the rows to change when it is opened, so its own changes aren't read again.
+/// A row the input returns more than once, e.g. through a join, is changed once.
+pub struct Update {
+    input: Box<dyn Operator>,
+    target: Target,
+    row_id: usize,
+    assignments: Vec<(usize, ScalarExpr)>,
+    rows: VecDeque<Row>,
+    changed: HashSet<RowId>,
+}
+
+impl Update {
+    pub fn new(
+        input: Box<dyn Operator>,
+        target: Target,
+        row_id: usize,
+        assignments: Vec<(usize, ScalarExpr)>,
+    ) -> Self {
+        Update {
+            input,
+            target,
+            row_id,
+            assignments,
+            rows: VecDeque::new(),
+            changed: HashSet::new(),
+        }
+    }
+}
+
+impl Operator for Update {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.changed.clear();
+        self.input.open(context)?;
+        let rows = drain(self.input.as_mut(), context);
+        self.input.close();
+        self.rows = rows?.into();
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(input) = self.rows.pop_front() {
+            let row_id = row_id(&input, self.row_id)?;
+            if !self.changed.insert(row_id) {
+                continue;
+            }
+            let Some(old) = read_row(context.store, &self.target.table, row_id)? else {
+                continue;
+            };
+            let mut new = old.clone();
+            for (position, value) in &self.assignments {
+                new[*position] = value.eval(&input, context)?;
+            }
+            self.target.update(row_id, &old, &new, context)?;
+            return self.target.output(&new, &input, context).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.rows.clear();
+        self.changed.clear();
+    }
+}
+
+pub struct Delete {
+    input: Box<dyn Operator>,
+    target: Target,
+    row_id: usize,
+    rows: VecDeque<Row>,
+}
+
+impl Delete {
+    pub fn new(input: Box<dyn Operator>, target: Target, row_id: usize) -> Self {
+        Delete {
+            input,
+            target,
+            row_id,
+            rows: VecDeque::new(),
+        }
+    }
+}
+
+impl Operator for Delete {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.input.open(context)?;
+        let rows = drain(self.input.as_mut(), context);
+        self.input.close();
+        self.rows = rows?.into();
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(input) = self.rows.pop_front() {
+            let row_id = row_id(&input, self.row_id)?;
+            // a row the input returns twice is gone the second time
+            let Some(row) = read_row(context.store, &self.target.table, row_id)? else {
+                continue;
+            };
+            self.target.delete(row_id, &row, context)?;
+            return self.target.output(&row, &input, context).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.rows.clear();
+    }
+}
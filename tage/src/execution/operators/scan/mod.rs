@@ -0,0 +1,197 @@
+//! Scans of the stored rows of a table, in the order of the row ids or of an index, and
+//! of the rows of a table function.
+
+use crate::catalog::{Index, Table};
+use crate::execution::expr::{cast, ScalarExpr};
+use crate::execution::physical::ScanColumn;
+use crate::execution::table::{index_range, read_row, RowId, RowScan};
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::functions::table::{TableFunction, TableRows};
+use crate::value::Value;
+use std::collections::VecDeque;
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// All rows of a table. The virtual tables of `information_schema` are computed from the
+/// catalog.
+pub struct SeqScan {
+    table: Table,
+    columns: Vec<ScanColumn>,
+    rows: Option<TableScan>,
+}
+
+impl SeqScan {
+    pub fn new(table: Table, columns: Vec<ScanColumn>) -> Self {
+        SeqScan {
+            table,
+            columns,
+            rows: None,
+        }
+    }
+}
+
+impl Operator for SeqScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.rows = Some(TableScan::start(&self.table, context)?);
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        let rows = self.rows.as_mut().expect("opened scan");
+        Ok(rows
+            .next(context)?
+            .map(|(row_id, row)| scanned_row(&self.columns, row_id, row)))
+    }
+
+    fn close(&mut self) {
+        self.rows = None;
+    }
+}
+
+/// Rows of a table as a scan reads them, the rows of a virtual table are computed when
+/// it starts.
+pub(crate) enum TableScan {
+    Virtual(VecDeque<(RowId, Row)>),
+    Stored(RowScan),
+}
+
+impl TableScan {
+    pub(crate) fn start(
+        table: &Table,
+        context: &mut ExecutionContext,
+    ) -> Result<Self, ExecutionError> {
+        Ok(match context.catalog.system_rows(table.id) {
+            Some(rows) => TableScan::Virtual((1..).zip(rows).collect()),
+            None => TableScan::Stored(RowScan::new(context.store, table)?),
+        })
+    }
+
+    pub(crate) fn next(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<(RowId, Row)>, ExecutionError> {
+        match self {
+            TableScan::Virtual(rows) => Ok(rows.pop_front()),
+            TableScan::Stored(rows) => rows.next(context.store),
+        }
+    }
+}
+
+/// Rows of a table function, called with the arguments when the scan opens.
+pub struct FunctionScan {
+    function: Arc<dyn TableFunction>,
+    arguments: Vec<ScalarExpr>,
+    rows: Option<TableRows>,
+}
+
+impl FunctionScan {
+    pub fn new(function: Arc<dyn TableFunction>, arguments: Vec<ScalarExpr>) -> Self {
+        FunctionScan {
+            function,
+            arguments,
+            rows: None,
+        }
+    }
+}
+
+impl Operator for FunctionScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|argument| argument.eval(&[], context))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.rows = match self.function.null_handling().skips(&arguments) {
+            true => None,
+            false => Some(self.function.call(&arguments, context)?),
+        };
+        Ok(())
+    }
+
+    fn next(&mut self, _context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        self.rows.as_mut().and_then(Iterator::next).transpose()
+    }
+
+    fn close(&mut self) {
+        self.rows = None;
+    }
+}
+
+/// Rows whose first indexed value is within the bounds, in the order of the index.
+pub struct IndexScan {
+    table: Table,
+    index: Index,
+    low: Bound<ScalarExpr>,
+    high: Bound<ScalarExpr>,
+    columns: Vec<ScanColumn>,
+    row_ids: VecDeque<RowId>,
+}
+
+impl IndexScan {
+    pub fn new(
+        table: Table,
+        index: Index,
+        low: Bound<ScalarExpr>,
+        high: Bound<ScalarExpr>,
+        columns: Vec<ScanColumn>,
+    ) -> Self {
+        IndexScan {
+            table,
+            index,
+            low,
+            high,
+            columns,
+            row_ids: VecDeque::new(),
+        }
+    }
+}
+
+impl Operator for IndexScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let column = self
+            .table
+            .columns
+            .iter()
+            .find(|column| Some(&column.id) == self.index.columns.first())
+            .expect("indexed column of the table");
+        // bounds are converted to the type of the column, an integer is stored like a bigint
+        let mut bound = |bound: &Bound<ScalarExpr>| -> Result<Bound<Value>, ExecutionError> {
+            Ok(match bound {
+                Bound::Included(expr) => {
+                    Bound::Included(cast(expr.eval(&[], context)?, column.data_type)?)
+                }
+                Bound::Excluded(expr) => {
+                    Bound::Excluded(cast(expr.eval(&[], context)?, column.data_type)?)
+                }
+                Bound::Unbounded => Bound::Unbounded,
+            })
+        };
+        let (low, high) = (bound(&self.low)?, bound(&self.high)?);
+        self.row_ids = index_range(context.store, &self.index, low.as_ref(), high.as_ref())?.into();
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        while let Some(row_id) = self.row_ids.pop_front() {
+            if let Some(row) = read_row(context.store, &self.table, row_id)? {
+                return Ok(Some(scanned_row(&self.columns, row_id, row)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) {
+        self.row_ids.clear();
+    }
+}
+
+/// Values of the scanned columns of a stored row.
+pub(crate) fn scanned_row(columns: &[ScanColumn], row_id: RowId, row: Row) -> Row {
+    columns
+        .iter()
+        .map(|column| match column {
+            ScanColumn::Column(position) => row[*position].clone(),
+            ScanColumn::RowId => Value::Integer(row_id as i64),
+        })
+        .collect()
+}
@@ -0,0 +1,216 @@
+//! Sorts the rows of the input in memory, or with an external merge sort once they
+//! exceed the memory budget of the query.
+
+use crate::execution::expr::{compare, ScalarExpr};
+use crate::execution::spill::{row_size, MemoryReservation, SpillFile, SpillReader};
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::parser::Order;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::vec;
+
+/// Runs merged at a time, more runs are merged into fewer ones first.
+const MERGE_WIDTH: usize = 16;
+
+/// `NULL`s sort after all values, first in descending order as in PostgreSQL. The sort
+/// is stable.
+///
+/// Rows are sorted in memory until they don't fit into the budget, then they are written
+/// to a file as a sorted run. The runs and the rows still in memory are merged when the
+/// rows are returned.
+pub struct Sort {
+    input: Box<dyn Operator>,
+    order_by: Vec<(ScalarExpr, Order)>,
+    output: VecDeque<Row>,
+    merge: Option<Merge>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl Sort {
+    pub fn new(input: Box<dyn Operator>, order_by: Vec<(ScalarExpr, Order)>) -> Self {
+        Sort {
+            input,
+            order_by,
+            output: VecDeque::new(),
+            merge: None,
+            reservation: None,
+        }
+    }
+
+    fn sort(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let mut reservation = context.memory.reservation();
+        let mut keyed = vec![];
+        let mut runs = vec![];
+        while let Some(row) = self.input.next(context)? {
+            let key = self
+                .order_by
+                .iter()
+                .map(|(expr, _)| expr.eval(&row, context))
+                .collect::<Result<Row, _>>()?;
+            let size = row_size(&key) + row_size(&row);
+            if !reservation.try_grow(size) {
+                if !keyed.is_empty() {
+                    self.sort_keyed(&mut keyed);
+                    let mut run = context.memory.spill_file()?;
+                    for (key, row) in keyed.drain(..) {
+                        write_keyed(&mut run, key, row)?;
+                    }
+                    runs.push(run);
+                    reservation.free();
+                }
+                reservation.grow(size);
+            }
+            keyed.push((key, row));
+        }
+        self.sort_keyed(&mut keyed);
+        if runs.is_empty() {
+            self.output = keyed.into_iter().map(|(_, row)| row).collect();
+        } else {
+            while runs.len() > MERGE_WIDTH {
+                let merged = runs
+                    .drain(..MERGE_WIDTH)
+                    .map(Run::read)
+                    .collect::<Result<_, _>>()?;
+                let mut merge = Merge::new(merged, self.width())?;
+                let mut run = context.memory.spill_file()?;
+                while let Some((key, row)) = merge.next(&self.order_by)? {
+                    write_keyed(&mut run, key, row)?;
+                }
+                runs.insert(0, run);
+            }
+            let mut runs: Vec<_> = runs.into_iter().map(Run::read).collect::<Result<_, _>>()?;
+            runs.push(Run::Memory(keyed.into_iter()));
+            self.merge = Some(Merge::new(runs, self.width())?);
+        }
+        self.reservation = Some(reservation);
+        Ok(())
+    }
+
+    fn sort_keyed(&self, keyed: &mut [(Row, Row)]) {
+        keyed.sort_by(|(left, _), (right, _)| compare_sort_keys(left, right, &self.order_by));
+    }
+
+    fn width(&self) -> usize {
+        self.order_by.len()
+    }
+}
+
+impl Operator for Sort {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.close();
+        self.input.open(context)?;
+        let result = self.sort(context);
+        self.input.close();
+        result
+    }
+
+    fn next(&mut self, _context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        let row = match &mut self.merge {
+            Some(merge) => merge.next(&self.order_by)?.map(|(_, row)| row),
+            None => self.output.pop_front(),
+        };
+        if row.is_none() {
+            self.close();
+        }
+        Ok(row)
+    }
+
+    fn close(&mut self) {
+        self.output.clear();
+        self.merge = None;
+        self.reservation = None;
+    }
+}
+
+/// A sorted run, the key of each row is written in front of it.
+enum Run {
+    File(SpillReader),
+    Memory(vec::IntoIter<(Row, Row)>),
+}
+
+fn write_keyed(file: &mut SpillFile, mut key: Row, row: Row) -> Result<(), ExecutionError> {
+    key.extend(row);
+    file.write(&key)
+}
+
+impl Run {
+    fn read(file: SpillFile) -> Result<Self, ExecutionError> {
+        Ok(Run::File(file.into_reader()?))
+    }
+
+    fn next(&mut self, width: usize) -> Result<Option<(Row, Row)>, ExecutionError> {
+        match self {
+            Run::File(reader) => Ok(reader.next_row()?.map(|mut key| {
+                let row = key.split_off(width);
+                (key, row)
+            })),
+            Run::Memory(rows) => Ok(rows.next()),
+        }
+    }
+}
+
+/// Merges sorted runs, a row of an earlier run comes first among equal keys.
+struct Merge {
+    runs: Vec<Run>,
+    /// Next row of every run
+    heads: Vec<Option<(Row, Row)>>,
+    width: usize,
+}
+
+impl Merge {
+    fn new(mut runs: Vec<Run>, width: usize) -> Result<Self, ExecutionError> {
+        let heads = runs
+            .iter_mut()
+            .map(|run| run.next(width))
+            .collect::<Result<_, _>>()?;
+        Ok(Merge { runs, heads, width })
+    }
+
+    fn next(
+        &mut self,
+        order_by: &[(ScalarExpr, Order)],
+    ) -> Result<Option<(Row, Row)>, ExecutionError> {
+        let mut first: Option<usize> = None;
+        for (position, head) in self.heads.iter().enumerate() {
+            if let Some((key, _)) = head {
+                let smaller = first.is_none_or(|first| {
+                    let (first, _) = self.heads[first].as_ref().expect("head of a run");
+                    compare_sort_keys(key, first, order_by) == Ordering::Less
+                });
+                if smaller {
+                    first = Some(position);
+                }
+            }
+        }
+        let Some(first) = first else {
+            return Ok(None);
+        };
+        let next = self.runs[first].next(self.width)?;
+        Ok(std::mem::replace(&mut self.heads[first], next))
+    }
+}
+
+pub fn compare_sort_keys(
+    left: &[Value],
+    right: &[Value],
+    order_by: &[(ScalarExpr, Order)],
+) -> Ordering {
+    for ((left, right), (_, order)) in left.iter().zip(right).zip(order_by) {
+        let ordering = match (left, right) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) if order.nulls_first => Ordering::Less,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) if order.nulls_first => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            (left, right) if order.descending => {
+                compare(left, right).unwrap_or(Ordering::Equal).reverse()
+            }
+            (left, right) => compare(left, right).unwrap_or(Ordering::Equal),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
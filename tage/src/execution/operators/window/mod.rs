@@ -0,0 +1,439 @@
+//! Window functions. The rows of the input are read when the operator is opened, then
+//! every call is computed over the partitions of its window, sorted by its `ORDER BY`.
+//! The rows are returned in the order of the input with the results appended.
+//!
+//! Aggregates are computed over the frame of every row: rows following the frame of the
+//! previous row are added to its state while the start of the frame stays, otherwise the
+//! state is built again.
+
+use crate::execution::expr::ScalarExpr;
+use crate::execution::operators::sort::compare_sort_keys;
+use crate::execution::spill::{row_size, MemoryReservation};
+use crate::execution::table::encode_key;
+use crate::execution::{ExecutionContext, ExecutionError, Operator, Row};
+use crate::functions::aggregate::AggregateFunction;
+use crate::functions::window::{WindowFunction, WindowPartition};
+use crate::parser::expr::FrameUnits;
+use crate::parser::Order;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum WindowFunctionKind {
+    /// An aggregate computed over the frame of every row
+    Aggregate(Arc<dyn AggregateFunction>),
+    Window(Arc<dyn WindowFunction>),
+}
+
+/// Call of a window function, its expressions are evaluated on the input rows.
+#[derive(Debug, Clone)]
+pub struct WindowCall {
+    pub function: WindowFunctionKind,
+    pub arguments: Vec<ScalarExpr>,
+    pub partition_by: Vec<ScalarExpr>,
+    pub order_by: Vec<(ScalarExpr, Order)>,
+    /// `None` for the default frame, from the start of the partition to the last peer of
+    /// the row
+    pub frame: Option<Frame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub units: FrameUnits,
+    pub start: FrameEdge,
+    pub end: FrameEdge,
+}
+
+/// Offsets are constants.
+#[derive(Debug, Clone)]
+pub enum FrameEdge {
+    UnboundedPreceding,
+    Preceding(ScalarExpr),
+    CurrentRow,
+    Following(ScalarExpr),
+    UnboundedFollowing,
+}
+
+/// A frame edge with its offset evaluated.
+#[derive(Clone, Copy)]
+enum Edge {
+    UnboundedPreceding,
+    Preceding(f64),
+    CurrentRow,
+    Following(f64),
+    UnboundedFollowing,
+}
+
+pub struct Window {
+    input: Box<dyn Operator>,
+    functions: Vec<WindowCall>,
+    output: VecDeque<Row>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl Window {
+    pub fn new(input: Box<dyn Operator>, functions: Vec<WindowCall>) -> Self {
+        Window {
+            input,
+            functions,
+            output: VecDeque::new(),
+            reservation: None,
+        }
+    }
+
+    fn compute(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let mut reservation = context.memory.reservation();
+        let mut rows = vec![];
+        while let Some(row) = self.input.next(context)? {
+            reservation.grow(row_size(&row));
+            rows.push(row);
+        }
+        let mut results = vec![];
+        for call in &self.functions {
+            results.push(evaluate(call, &rows, context)?);
+        }
+        self.output = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut row)| {
+                row.extend(
+                    results
+                        .iter_mut()
+                        .map(|values| std::mem::replace(&mut values[index], Value::Null)),
+                );
+                row
+            })
+            .collect();
+        self.reservation = Some(reservation);
+        Ok(())
+    }
+}
+
+impl Operator for Window {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.close();
+        self.input.open(context)?;
+        let result = self.compute(context);
+        self.input.close();
+        result
+    }
+
+    fn next(&mut self, _context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        let row = self.output.pop_front();
+        if row.is_none() {
+            self.close();
+        }
+        Ok(row)
+    }
+
+    fn close(&mut self) {
+        self.output.clear();
+        self.reservation = None;
+    }
+}
+
+/// Result of the call for every row.
+fn evaluate(
+    call: &WindowCall,
+    rows: &[Row],
+    context: &mut ExecutionContext,
+) -> Result<Vec<Value>, ExecutionError> {
+    let start = edge(call.frame.as_ref().map(|frame| &frame.start), true, context)?;
+    let end = edge(call.frame.as_ref().map(|frame| &frame.end), false, context)?;
+    let units = call
+        .frame
+        .as_ref()
+        .map_or(FrameUnits::Range, |frame| frame.units);
+    let mut partitions: Vec<Vec<usize>> = vec![];
+    let mut positions = HashMap::new();
+    let mut keys = vec![];
+    let mut arguments = vec![];
+    for (index, row) in rows.iter().enumerate() {
+        let partition = call
+            .partition_by
+            .iter()
+            .map(|expr| expr.eval(row, context))
+            .collect::<Result<Row, _>>()?;
+        let position = *positions.entry(encode_key(&partition)).or_insert_with(|| {
+            partitions.push(vec![]);
+            partitions.len() - 1
+        });
+        partitions[position].push(index);
+        keys.push(
+            call.order_by
+                .iter()
+                .map(|(expr, _)| expr.eval(row, context))
+                .collect::<Result<Row, _>>()?,
+        );
+        arguments.push(
+            call.arguments
+                .iter()
+                .map(|expr| expr.eval(row, context))
+                .collect::<Result<Row, _>>()?,
+        );
+    }
+    let mut results = vec![Value::Null; rows.len()];
+    for mut partition in partitions {
+        partition
+            .sort_by(|left, right| compare_sort_keys(&keys[*left], &keys[*right], &call.order_by));
+        let sorted_keys: Vec<&Row> = partition.iter().map(|index| &keys[*index]).collect();
+        let peers = peer_groups(&sorted_keys, &call.order_by);
+        let frames = match units {
+            FrameUnits::Rows => rows_frames(partition.len(), start, end),
+            FrameUnits::Range => range_frames(&sorted_keys, &peers, &call.order_by, start, end),
+        };
+        let partition_arguments: Vec<Row> = partition
+            .iter()
+            .map(|index| std::mem::take(&mut arguments[*index]))
+            .collect();
+        let values = match &call.function {
+            WindowFunctionKind::Window(function) => function.evaluate(&WindowPartition {
+                arguments: &partition_arguments,
+                peers: &peers,
+                frames: &frames,
+            })?,
+            WindowFunctionKind::Aggregate(function) => {
+                aggregate_frames(&**function, &partition_arguments, &frames)?
+            }
+        };
+        for (index, value) in partition.into_iter().zip(values) {
+            results[index] = value;
+        }
+    }
+    Ok(results)
+}
+
+/// The edge with its offset evaluated, the default start is the start of the partition
+/// and the default end is the last peer of the row.
+fn edge(
+    edge: Option<&FrameEdge>,
+    start: bool,
+    context: &mut ExecutionContext,
+) -> Result<Edge, ExecutionError> {
+    let offset = |expr: &ScalarExpr, context: &mut ExecutionContext| {
+        let name = if start { "starting" } else { "ending" };
+        match expr.eval(&[], context)? {
+            Value::Integer(offset) if offset >= 0 => Ok(offset as f64),
+            Value::Float(offset) if offset >= 0.0 => Ok(offset),
+            Value::Decimal(offset) if !offset.is_negative() => Ok(offset.to_f64()),
+            Value::Null => Err(ExecutionError::InvalidArgument(format!(
+                "frame {} offset must not be null",
+                name
+            ))),
+            _ => Err(ExecutionError::InvalidArgument(format!(
+                "frame {} offset must not be negative",
+                name
+            ))),
+        }
+    };
+    Ok(match edge {
+        None if start => Edge::UnboundedPreceding,
+        None => Edge::CurrentRow,
+        Some(FrameEdge::UnboundedPreceding) => Edge::UnboundedPreceding,
+        Some(FrameEdge::Preceding(expr)) => Edge::Preceding(offset(expr, context)?),
+        Some(FrameEdge::CurrentRow) => Edge::CurrentRow,
+        Some(FrameEdge::Following(expr)) => Edge::Following(offset(expr, context)?),
+        Some(FrameEdge::UnboundedFollowing) => Edge::UnboundedFollowing,
+    })
+}
+
+/// The peer group of every row of a sorted partition, all rows are peers without
+/// `ORDER BY`.
+fn peer_groups(keys: &[&Row], order_by: &[(ScalarExpr, Order)]) -> Vec<Range<usize>> {
+    let mut peers = vec![];
+    let mut start = 0;
+    for end in 1..=keys.len() {
+        if end == keys.len()
+            || compare_sort_keys(keys[start], keys[end], order_by) != Ordering::Equal
+        {
+            peers.extend(std::iter::repeat_n(start..end, end - start));
+            start = end;
+        }
+    }
+    peers
+}
+
+fn rows_frames(len: usize, start: Edge, end: Edge) -> Vec<Range<usize>> {
+    let position = |row: usize, edge: Edge, start: bool| match edge {
+        Edge::UnboundedPreceding => 0,
+        Edge::Preceding(offset) => (row + usize::from(!start)).saturating_sub(offset as usize),
+        Edge::CurrentRow => row + usize::from(!start),
+        Edge::Following(offset) => (row + usize::from(!start))
+            .saturating_add(offset as usize)
+            .min(len),
+        Edge::UnboundedFollowing => len,
+    };
+    (0..len)
+        .map(|row| {
+            let start = position(row, start, true);
+            start..position(row, end, false).max(start)
+        })
+        .collect()
+}
+
+/// Frames in `RANGE` units: the offsets are added to and subtracted from the single
+/// `ORDER BY` key. Rows with a `NULL` key take their peers for an offset edge.
+fn range_frames(
+    keys: &[&Row],
+    peers: &[Range<usize>],
+    order_by: &[(ScalarExpr, Order)],
+    start: Edge,
+    end: Edge,
+) -> Vec<Range<usize>> {
+    let descending = order_by.first().is_some_and(|(_, order)| order.descending);
+    // keys of the rows along the ordering, ascending
+    let values: Vec<Option<f64>> = keys
+        .iter()
+        .map(|key| match key.first() {
+            Some(Value::Integer(value)) => Some(*value as f64),
+            Some(Value::Float(value)) => Some(*value),
+            Some(Value::Decimal(value)) => Some(value.to_f64()),
+            _ => None,
+        })
+        .map(|value| value.map(|value| if descending { -value } else { value }))
+        .collect();
+    let len = keys.len();
+    // rows with a key, `NULL`s sort last ascending and first descending
+    let valid = match descending {
+        true => values.iter().position(Option::is_some).unwrap_or(len)..len,
+        false => 0..values.iter().position(Option::is_none).unwrap_or(len),
+    };
+    let position = |row: usize, edge: Edge, start: bool| {
+        let peers = &peers[row];
+        let bound = |target: f64, inclusive: bool| {
+            let keys = &values[valid.clone()];
+            valid.start
+                + keys.partition_point(|key| match inclusive {
+                    true => key.unwrap_or(f64::NAN) < target,
+                    false => key.unwrap_or(f64::NAN) <= target,
+                })
+        };
+        let offset_edge = |target: Option<f64>| match target {
+            Some(target) => bound(target, start),
+            None if start => peers.start,
+            None => peers.end,
+        };
+        match edge {
+            Edge::UnboundedPreceding => 0,
+            Edge::Preceding(offset) => offset_edge(values[row].map(|key| key - offset)),
+            Edge::CurrentRow if start => peers.start,
+            Edge::CurrentRow => peers.end,
+            Edge::Following(offset) => offset_edge(values[row].map(|key| key + offset)),
+            Edge::UnboundedFollowing => len,
+        }
+    };
+    (0..len)
+        .map(|row| {
+            let start = position(row, start, true);
+            start..position(row, end, false).max(start)
+        })
+        .collect()
+}
+
+/// Result of the aggregate over the frame of every row.
+fn aggregate_frames(
+    function: &dyn AggregateFunction,
+    arguments: &[Row],
+    frames: &[Range<usize>],
+) -> Result<Vec<Value>, ExecutionError> {
+    let mut state = function.init();
+    let mut current = 0..0;
+    let mut results = vec![];
+    for frame in frames {
+        if frame.start != current.start || frame.end < current.end {
+            state = function.init();
+            current = frame.start..frame.start;
+        }
+        for row in &arguments[current.end..frame.end] {
+            if !function.null_handling().skips(row) {
+                function.update(&mut state, row)?;
+            }
+        }
+        current.end = frame.end;
+        results.push(function.finalize(&state)?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(values: &[Value]) -> Vec<Row> {
+        values.iter().map(|value| vec![value.clone()]).collect()
+    }
+
+    fn frames(values: &[Value], order: Order, start: Edge, end: Edge) -> Vec<Range<usize>> {
+        let keys = keys(values);
+        let keys: Vec<&Row> = keys.iter().collect();
+        let order_by = [(ScalarExpr::Column(0), order)];
+        let peers = peer_groups(&keys, &order_by);
+        range_frames(&keys, &peers, &order_by, start, end)
+    }
+
+    #[test]
+    fn frames_rows() {
+        assert_eq!(
+            rows_frames(4, Edge::Preceding(1.0), Edge::Following(1.0)),
+            vec![0..2, 0..3, 1..4, 2..4]
+        );
+        assert_eq!(
+            rows_frames(3, Edge::CurrentRow, Edge::UnboundedFollowing),
+            vec![0..3, 1..3, 2..3]
+        );
+        assert_eq!(
+            rows_frames(3, Edge::Following(1.0), Edge::Following(2.0)),
+            vec![1..3, 2..3, 3..3]
+        );
+        assert_eq!(
+            rows_frames(3, Edge::UnboundedPreceding, Edge::Preceding(1.0)),
+            vec![0..0, 0..1, 0..2]
+        );
+    }
+
+    #[test]
+    fn frames_ranges() {
+        let values = [1.into(), 2.into(), 2.into(), 5.into(), Value::Null];
+        assert_eq!(
+            frames(
+                &values,
+                Order::ASC,
+                Edge::UnboundedPreceding,
+                Edge::CurrentRow
+            ),
+            vec![0..1, 0..3, 0..3, 0..4, 0..5]
+        );
+        assert_eq!(
+            frames(
+                &values,
+                Order::ASC,
+                Edge::Preceding(1.0),
+                Edge::Following(1.0)
+            ),
+            vec![0..3, 0..3, 0..3, 3..4, 4..5]
+        );
+        let descending = [Value::Null, 5.into(), 2.into(), 2.into(), 1.into()];
+        assert_eq!(
+            frames(
+                &descending,
+                Order::DESC,
+                Edge::Preceding(3.0),
+                Edge::CurrentRow
+            ),
+            vec![0..1, 1..2, 1..4, 1..4, 2..5]
+        );
+    }
+
+    #[test]
+    fn aggregates_frames() {
+        let sum = crate::functions::builtins().aggregate("sum").unwrap();
+        let arguments = keys(&[1.into(), 2.into(), Value::Null, 4.into()]);
+        assert_eq!(
+            aggregate_frames(&**sum, &arguments, &[0..1, 0..2, 0..4, 1..4, 3..3]),
+            Ok(vec![1.into(), 3.into(), 7.into(), 6.into(), Value::Null])
+        );
+    }
+}
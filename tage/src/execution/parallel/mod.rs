@@ -0,0 +1,737 @@
+//! Parallel execution. `parallelize` puts the parts of a plan which read tables under
+//! `Gather` nodes, which run their part in worker threads and return the rows of all
+//! workers:
+//!
+//! - the workers of a `ParallelScan` take morsels, ranges of row ids, of the table one
+//!   after the other until none are left
+//! - an aggregation is done partially by every worker, the partial groups are merged
+//!   once they are gathered
+//! - the inputs of a hash join are repartitioned on the join keys, each worker joins the
+//!   rows of one partition
+//!
+//! Only queries are executed in parallel, the workers read the store through a shared
+//! reference. A `Gather` runs its workers to completion when it is opened. Outside of the
+//! workers a `ParallelScan` reads the whole table and a `Repartition` returns the rows of
+//! its input, a plan with them can be executed serially as well.
+
+use crate::catalog::{Catalog, Table};
+use crate::execution::batch::{BatchOperator, RecordBatch};
+use crate::execution::expr::ScalarExpr;
+use crate::execution::operators::aggregate::{Aggregate, AggregatePhase};
+use crate::execution::operators::scan::scanned_row;
+use crate::execution::pattern::PatternCache;
+use crate::execution::physical::{JoinSpec, PhysicalPlan, ScanColumn};
+use crate::execution::spill::QueryMemory;
+use crate::execution::table::{encode_key, next_row_id, scan_row_range, RowId};
+use crate::execution::{
+    execute_in, ExecutionContext, ExecutionError, ExecutionMode, Operator, Row,
+};
+use crate::parser::expr::Parameter;
+use crate::storage::{KvStore, Scan, StorageError};
+use crate::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Scope, ScopedJoinHandle};
+use std::time::SystemTime;
+use std::{mem, panic};
+
+/// Row ids of a morsel.
+const MORSEL_SIZE: u64 = 2048;
+
+/// Rows a producer of a repartition sends to a partition at a time.
+const CHUNK_SIZE: usize = 256;
+
+/// Chunks of rows a partition holds before its producers wait for its worker.
+const CHANNEL_CHUNKS: usize = 16;
+
+/// The plan with the parts which read tables executed by `workers` threads, as it is
+/// for fewer than two workers.
+pub fn parallelize(plan: PhysicalPlan, workers: usize) -> PhysicalPlan {
+    if workers < 2 {
+        return plan;
+    }
+    Parallelizer { workers, nodes: 0 }.plan(plan)
+}
+
+struct Parallelizer {
+    workers: usize,
+    /// Exchange nodes so far, for their ids
+    nodes: usize,
+}
+
+impl Parallelizer {
+    fn plan(&mut self, plan: PhysicalPlan) -> PhysicalPlan {
+        if let PhysicalPlan::HashAggregate {
+            input,
+            group_by,
+            aggregates,
+            phase: AggregatePhase::Single,
+        } = &plan
+        {
+            if let Some(part) = self.part(input) {
+                return self.aggregate(part, group_by.clone(), aggregates.clone());
+            }
+        }
+        match self.part(&plan) {
+            Some(part) => self.gather(part),
+            None => plan.map_inputs(|input| self.plan(input)),
+        }
+    }
+
+    /// The plan as a part of a parallel plan, each worker of which returns some of its
+    /// rows. `None` if the plan can't be split.
+    fn part(&mut self, plan: &PhysicalPlan) -> Option<PhysicalPlan> {
+        Some(match plan {
+            PhysicalPlan::SeqScan { table, columns } => PhysicalPlan::ParallelScan {
+                table: table.clone(),
+                columns: columns.clone(),
+                id: self.id(),
+            },
+            PhysicalPlan::Filter { input, predicate } => PhysicalPlan::Filter {
+                input: Box::new(self.part(input)?),
+                predicate: predicate.clone(),
+            },
+            PhysicalPlan::Project { input, exprs } => PhysicalPlan::Project {
+                input: Box::new(self.part(input)?),
+                exprs: exprs.clone(),
+            },
+            PhysicalPlan::HashJoin { join } => {
+                let left = self.part(&join.left)?;
+                let right = self.part(&join.right)?;
+                let (left_keys, right_keys) = join.keys.iter().cloned().unzip();
+                PhysicalPlan::HashJoin {
+                    join: JoinSpec {
+                        left: Box::new(self.repartition(left, left_keys)),
+                        right: Box::new(self.repartition(right, right_keys)),
+                        join_type: join.join_type,
+                        keys: join.keys.clone(),
+                        condition: join.condition.clone(),
+                        left_width: join.left_width,
+                        right_width: join.right_width,
+                    },
+                }
+            }
+            PhysicalPlan::Append { inputs } => PhysicalPlan::Append {
+                inputs: inputs
+                    .iter()
+                    .map(|input| self.part(input))
+                    .collect::<Option<_>>()?,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Aggregation of the rows of a part. The workers aggregate partially, unless an
+    /// aggregate is `distinct` or ordered-set: then each worker aggregates whole groups.
+    fn aggregate(
+        &mut self,
+        part: PhysicalPlan,
+        group_by: Vec<ScalarExpr>,
+        aggregates: Vec<Aggregate>,
+    ) -> PhysicalPlan {
+        if aggregates
+            .iter()
+            .any(|aggregate| aggregate.distinct || aggregate.function.ordered_set())
+        {
+            if group_by.is_empty() {
+                return PhysicalPlan::HashAggregate {
+                    input: Box::new(self.gather(part)),
+                    group_by,
+                    aggregates,
+                    phase: AggregatePhase::Single,
+                };
+            }
+            let input = self.repartition(part, group_by.clone());
+            return self.gather(PhysicalPlan::HashAggregate {
+                input: Box::new(input),
+                group_by,
+                aggregates,
+                phase: AggregatePhase::Single,
+            });
+        }
+        let keys = group_by.len();
+        let partial = PhysicalPlan::HashAggregate {
+            input: Box::new(part),
+            group_by,
+            aggregates: aggregates.clone(),
+            phase: AggregatePhase::Partial,
+        };
+        PhysicalPlan::HashAggregate {
+            input: Box::new(self.gather(partial)),
+            group_by: (0..keys).map(ScalarExpr::Column).collect(),
+            aggregates,
+            phase: AggregatePhase::Final,
+        }
+    }
+
+    fn gather(&self, part: PhysicalPlan) -> PhysicalPlan {
+        PhysicalPlan::Gather {
+            input: Box::new(part),
+            workers: self.workers,
+        }
+    }
+
+    fn repartition(&mut self, part: PhysicalPlan, keys: Vec<ScalarExpr>) -> PhysicalPlan {
+        PhysicalPlan::Repartition {
+            input: Box::new(part),
+            keys,
+            id: self.id(),
+        }
+    }
+
+    fn id(&mut self) -> usize {
+        self.nodes += 1;
+        self.nodes
+    }
+}
+
+/// What the exchange nodes of a part read in a worker thread, by the ids of the nodes.
+#[derive(Default)]
+pub struct Worker {
+    morsels: HashMap<usize, Arc<Morsels>>,
+    partitions: HashMap<usize, Receiver<Vec<Row>>>,
+}
+
+/// Morsels of a table which the workers of a scan share.
+enum Morsels {
+    /// Ranges of the row ids below `end`, `next` is the start of the next one
+    RowIds { next: AtomicU64, end: RowId },
+    /// Rows of a virtual table, computed from the catalog
+    Rows(Mutex<VecDeque<Vec<(RowId, Row)>>>),
+}
+
+impl Morsels {
+    fn new(
+        table: &Table,
+        store: &dyn KvStore,
+        catalog: &dyn Catalog,
+    ) -> Result<Self, ExecutionError> {
+        Ok(match catalog.system_rows(table.id) {
+            Some(rows) => {
+                let rows: Vec<_> = (1..).zip(rows).collect();
+                Morsels::Rows(Mutex::new(
+                    rows.chunks(MORSEL_SIZE as usize)
+                        .map(<[_]>::to_vec)
+                        .collect(),
+                ))
+            }
+            None => Morsels::RowIds {
+                next: AtomicU64::new(1),
+                end: next_row_id(store, table)?,
+            },
+        })
+    }
+
+    /// Rows of the next morsel, `None` once all are taken.
+    fn next(
+        &self,
+        table: &Table,
+        store: &dyn KvStore,
+    ) -> Result<Option<Vec<(RowId, Row)>>, ExecutionError> {
+        match self {
+            Morsels::RowIds { next, end } => {
+                let start = next.fetch_add(MORSEL_SIZE, Ordering::Relaxed);
+                if start >= *end {
+                    return Ok(None);
+                }
+                scan_row_range(store, table, start, (start + MORSEL_SIZE).min(*end)).map(Some)
+            }
+            Morsels::Rows(morsels) => Ok(morsels.lock().expect("morsels").pop_front()),
+        }
+    }
+}
+
+/// Rows of the morsels of a table the worker takes, all rows outside of a worker. In
+/// batch mode a morsel is a batch.
+pub struct ParallelScan {
+    table: Table,
+    columns: Vec<ScanColumn>,
+    id: usize,
+    morsels: Option<Arc<Morsels>>,
+    rows: VecDeque<(RowId, Row)>,
+}
+
+impl ParallelScan {
+    pub fn new(table: Table, columns: Vec<ScanColumn>, id: usize) -> Self {
+        ParallelScan {
+            table,
+            columns,
+            id,
+            morsels: None,
+            rows: VecDeque::new(),
+        }
+    }
+
+    fn start(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.rows.clear();
+        let shared = context
+            .worker
+            .as_ref()
+            .and_then(|worker| worker.morsels.get(&self.id));
+        self.morsels = Some(match shared {
+            Some(morsels) => morsels.clone(),
+            None => Arc::new(Morsels::new(&self.table, context.store, context.catalog)?),
+        });
+        Ok(())
+    }
+
+    /// Rows of the next morsel with rows.
+    fn next_morsel(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<Vec<(RowId, Row)>>, ExecutionError> {
+        let morsels = self.morsels.as_ref().expect("opened scan");
+        loop {
+            match morsels.next(&self.table, context.store)? {
+                Some(rows) if rows.is_empty() => continue,
+                rows => return Ok(rows),
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        self.morsels = None;
+        self.rows.clear();
+    }
+}
+
+impl Operator for ParallelScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.start(context)
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some((row_id, row)) = self.rows.pop_front() {
+                return Ok(Some(scanned_row(&self.columns, row_id, row)));
+            }
+            match self.next_morsel(context)? {
+                Some(rows) => self.rows = rows.into(),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.stop();
+    }
+}
+
+impl BatchOperator for ParallelScan {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.start(context)
+    }
+
+    fn next_batch(
+        &mut self,
+        context: &mut ExecutionContext,
+    ) -> Result<Option<RecordBatch>, ExecutionError> {
+        Ok(self.next_morsel(context)?.map(|rows| {
+            RecordBatch::from_rows(
+                rows.into_iter()
+                    .map(|(row_id, row)| scanned_row(&self.columns, row_id, row))
+                    .collect(),
+            )
+        }))
+    }
+
+    fn close(&mut self) {
+        self.stop();
+    }
+}
+
+/// Rows of the partition of the worker, the producers of the repartition send each row
+/// of the input to the partition of the hash of its keys.
+pub struct Repartition {
+    input: PhysicalPlan,
+    id: usize,
+    mode: ExecutionMode,
+    source: Option<Source>,
+    rows: VecDeque<Row>,
+}
+
+enum Source {
+    Partition(Receiver<Vec<Row>>),
+    /// The input, outside of a worker
+    Input(Box<dyn Operator>),
+}
+
+impl Repartition {
+    pub fn new(input: PhysicalPlan, id: usize, mode: ExecutionMode) -> Self {
+        Repartition {
+            input,
+            id,
+            mode,
+            source: None,
+            rows: VecDeque::new(),
+        }
+    }
+}
+
+impl Operator for Repartition {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.rows.clear();
+        let partition = context
+            .worker
+            .as_mut()
+            .and_then(|worker| worker.partitions.remove(&self.id));
+        self.source = Some(match partition {
+            Some(partition) => Source::Partition(partition),
+            None => {
+                let mut input = self.input.clone().operator(self.mode);
+                input.open(context)?;
+                Source::Input(input)
+            }
+        });
+        Ok(())
+    }
+
+    fn next(&mut self, context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        match self.source.as_mut().expect("opened repartition") {
+            Source::Input(input) => input.next(context),
+            Source::Partition(partition) => loop {
+                if let Some(row) = self.rows.pop_front() {
+                    return Ok(Some(row));
+                }
+                match partition.recv() {
+                    Ok(rows) => self.rows = rows.into(),
+                    // all producers are done
+                    Err(_) => return Ok(None),
+                }
+            },
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(Source::Input(input)) = &mut self.source {
+            input.close();
+        }
+        self.source = None;
+        self.rows.clear();
+    }
+}
+
+/// Rows of the workers which run the input, in the order they finish.
+pub struct Gather {
+    input: PhysicalPlan,
+    workers: usize,
+    mode: ExecutionMode,
+    output: VecDeque<Row>,
+}
+
+impl Gather {
+    pub fn new(input: PhysicalPlan, workers: usize, mode: ExecutionMode) -> Self {
+        Gather {
+            input,
+            workers,
+            mode,
+            output: VecDeque::new(),
+        }
+    }
+}
+
+impl Operator for Gather {
+    fn open(&mut self, context: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        self.output = gather(&self.input, self.workers, self.mode, context)?.into();
+        Ok(())
+    }
+
+    fn next(&mut self, _context: &mut ExecutionContext) -> Result<Option<Row>, ExecutionError> {
+        Ok(self.output.pop_front())
+    }
+
+    fn close(&mut self) {
+        self.output.clear();
+    }
+}
+
+/// What the threads of a gather share.
+#[derive(Clone, Copy)]
+struct Shared<'a> {
+    store: &'a dyn KvStore,
+    catalog: &'a dyn Catalog,
+    memory: &'a QueryMemory,
+    start_time: SystemTime,
+    patterns: &'a PatternCache,
+    parameters: &'a [(Parameter, Value)],
+    mode: ExecutionMode,
+}
+
+impl Shared<'_> {
+    /// Context of a worker thread.
+    fn context<'a>(&'a self, store: &'a mut SharedStore, worker: Worker) -> ExecutionContext<'a> {
+        ExecutionContext {
+            store,
+            catalog: self.catalog,
+            memory: self.memory.worker(),
+            worker: Some(worker),
+            start_time: self.start_time,
+            patterns: self.patterns.clone(),
+            parameters: self.parameters,
+        }
+    }
+}
+
+/// The store as the workers see it, they only read.
+struct SharedStore<'a>(&'a dyn KvStore);
+
+impl KvStore for SharedStore<'_> {
+    fn get_by_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        self.0.get_by_key(key)
+    }
+
+    fn put(&mut self, _key: &[u8], _value: &[u8]) -> Result<(), StorageError> {
+        unreachable!("the workers of a parallel plan only read")
+    }
+
+    fn delete(&mut self, _key: &[u8]) -> Result<(), StorageError> {
+        unreachable!("the workers of a parallel plan only read")
+    }
+
+    fn scan(&self, start_key: &[u8], end_key: &[u8]) -> Scan<'_> {
+        self.0.scan(start_key, end_key)
+    }
+}
+
+/// Runs the workers of the part and returns their rows. The first error of a worker or
+/// of a producer of a repartition fails the gather.
+fn gather(
+    part: &PhysicalPlan,
+    workers: usize,
+    mode: ExecutionMode,
+    context: &mut ExecutionContext,
+) -> Result<Vec<Row>, ExecutionError> {
+    let shared = Shared {
+        store: context.store,
+        catalog: context.catalog,
+        memory: &context.memory,
+        start_time: context.start_time,
+        patterns: &context.patterns,
+        parameters: context.parameters,
+        mode,
+    };
+    thread::scope(|scope| {
+        let mut producers = vec![];
+        let consumers: Vec<_> = start(scope, part, workers, shared, &mut producers)?
+            .into_iter()
+            .map(|worker| {
+                let part = part.clone();
+                spawn(scope, move || {
+                    let mut store = SharedStore(shared.store);
+                    execute_in(part, mode, &mut shared.context(&mut store, worker))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut rows = vec![];
+        let mut errors = vec![];
+        for consumer in consumers {
+            match join(consumer) {
+                Ok(worker_rows) => rows.extend(worker_rows),
+                Err(error) => errors.push(error),
+            }
+        }
+        for producer in producers {
+            if let Err(error) = join(producer) {
+                errors.push(error);
+            }
+        }
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(rows),
+        }
+    })
+}
+
+/// Starts a thread of the scope, a thread the system can't start fails the query.
+fn spawn<'scope, T: Send + 'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    f: impl FnOnce() -> T + Send + 'scope,
+) -> Result<ScopedJoinHandle<'scope, T>, ExecutionError> {
+    thread::Builder::new()
+        .spawn_scoped(scope, f)
+        .map_err(|error| ExecutionError::Worker(error.to_string()))
+}
+
+fn join<T>(handle: ScopedJoinHandle<T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+type Producer<'scope> = ScopedJoinHandle<'scope, Result<(), ExecutionError>>;
+
+/// Inputs of the exchange nodes of a part for each of its workers, the producers of its
+/// repartitions are started.
+fn start<'scope, 'env>(
+    scope: &'scope Scope<'scope, 'env>,
+    part: &PhysicalPlan,
+    workers: usize,
+    shared: Shared<'env>,
+    producers: &mut Vec<Producer<'scope>>,
+) -> Result<Vec<Worker>, ExecutionError> {
+    let mut inputs: Vec<Worker> = (0..workers).map(|_| Worker::default()).collect();
+    let mut nodes = vec![];
+    exchanges(part, &mut nodes);
+    for node in nodes {
+        match node {
+            PhysicalPlan::ParallelScan { table, id, .. } => {
+                let morsels = Arc::new(Morsels::new(table, shared.store, shared.catalog)?);
+                for input in &mut inputs {
+                    input.morsels.insert(*id, morsels.clone());
+                }
+            }
+            PhysicalPlan::Repartition { input, keys, id } => {
+                let (senders, receivers): (Vec<_>, Vec<_>) = (0..workers)
+                    .map(|_| mpsc::sync_channel(CHANNEL_CHUNKS))
+                    .unzip();
+                for (worker, receiver) in inputs.iter_mut().zip(receivers) {
+                    worker.partitions.insert(*id, receiver);
+                }
+                for worker in start(scope, input, workers, shared, producers)? {
+                    let (input, keys, senders) =
+                        (input.as_ref().clone(), keys.clone(), senders.clone());
+                    producers.push(spawn(scope, move || {
+                        let mut store = SharedStore(shared.store);
+                        let mut context = shared.context(&mut store, worker);
+                        let mut operator = input.operator(shared.mode);
+                        let result = produce(operator.as_mut(), &keys, &senders, &mut context);
+                        operator.close();
+                        result
+                    })?);
+                }
+            }
+            _ => unreachable!("exchange node"),
+        }
+    }
+    Ok(inputs)
+}
+
+/// The scans and repartitions of a part, not those of the inputs of its repartitions.
+fn exchanges<'a>(plan: &'a PhysicalPlan, nodes: &mut Vec<&'a PhysicalPlan>) {
+    match plan {
+        PhysicalPlan::ParallelScan { .. } | PhysicalPlan::Repartition { .. } => nodes.push(plan),
+        // a gather in a part runs workers of its own
+        PhysicalPlan::Gather { .. } => {}
+        plan => {
+            for input in plan.inputs() {
+                exchanges(input, nodes);
+            }
+        }
+    }
+}
+
+/// Sends the rows of a producer to the partitions of their keys.
+fn produce(
+    operator: &mut dyn Operator,
+    keys: &[ScalarExpr],
+    partitions: &[SyncSender<Vec<Row>>],
+    context: &mut ExecutionContext,
+) -> Result<(), ExecutionError> {
+    operator.open(context)?;
+    let mut chunks = vec![vec![]; partitions.len()];
+    while let Some(row) = operator.next(context)? {
+        let key = keys
+            .iter()
+            .map(|key| key.eval(&row, context))
+            .collect::<Result<Row, _>>()?;
+        let partition = partition(&encode_key(&key), partitions.len());
+        chunks[partition].push(row);
+        if chunks[partition].len() == CHUNK_SIZE
+            && partitions[partition]
+                .send(mem::take(&mut chunks[partition]))
+                .is_err()
+        {
+            // the worker of the partition failed, the gather fails with its error
+            return Ok(());
+        }
+    }
+    for (chunk, partition) in chunks.into_iter().zip(partitions) {
+        if !chunk.is_empty() {
+            let _ = partition.send(chunk);
+        }
+    }
+    Ok(())
+}
+
+/// Partition of an encoded key, hashed differently from the partitions of spilling
+/// operators so that those split the rows of a worker as well.
+fn partition(key: &[u8], partitions: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    ("repartition", key).hash(&mut hasher);
+    hasher.finish() as usize % partitions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::optimize;
+    use crate::optimizer::tests::{catalog, plan_in};
+
+    /// The nodes of the plan, with the phase of aggregations.
+    fn shape(plan: &PhysicalPlan) -> String {
+        let node = match plan {
+            PhysicalPlan::HashAggregate { phase, .. } => format!("{:?}Aggregate", phase),
+            plan => format!("{:?}", plan)
+                .split([' ', '{', '('])
+                .next()
+                .unwrap()
+                .to_string(),
+        };
+        let inputs: Vec<_> = plan.inputs().into_iter().map(shape).collect();
+        match inputs.is_empty() {
+            true => node,
+            false => format!("{}({})", node, inputs.join(", ")),
+        }
+    }
+
+    fn parallel(input: &str, workers: usize) -> String {
+        let catalog = catalog();
+        let plan = optimize(plan_in(&catalog, input), &catalog);
+        shape(&parallelize(
+            PhysicalPlan::build(&plan, &catalog).unwrap(),
+            workers,
+        ))
+    }
+
+    #[test]
+    fn parallelizes_plans() {
+        assert_eq!(
+            parallel("select name from users where age > 1", 4),
+            "Gather(Project(Filter(ParallelScan)))"
+        );
+        assert_eq!(
+            parallel("select name from users where age > 1", 1),
+            "Project(Filter(SeqScan))"
+        );
+        assert_eq!(
+            parallel("select age, count(*) from users group by age order by 2", 4),
+            "Project(Sort(FinalAggregate(Gather(PartialAggregate(ParallelScan)))))"
+        );
+        assert_eq!(
+            parallel(
+                "select age, count(distinct name) from users group by age",
+                4
+            ),
+            "Project(Gather(SingleAggregate(Repartition(ParallelScan))))"
+        );
+        assert_eq!(
+            parallel("select count(distinct name) from users", 4),
+            "Project(SingleAggregate(Gather(ParallelScan)))"
+        );
+        assert_eq!(
+            parallel(
+                "select u.name, o.total from users u join orders o on o.user_id = u.id limit 5",
+                4
+            ),
+            "Project(Limit(Gather(HashJoin(Repartition(ParallelScan), Repartition(ParallelScan)))))"
+        );
+        assert_eq!(
+            parallel("select name from users union select name from users", 4),
+            "FinalAggregate(Gather(PartialAggregate(Append(Project(ParallelScan), Project(ParallelScan)))))"
+        );
+    }
+}
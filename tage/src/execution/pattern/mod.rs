@@ -0,0 +1,462 @@
+//! Pattern matching of `LIKE`, `ILIKE`, `SIMILAR TO` and the regular expression operators
+//! `~` and `~*`. A pattern is compiled once per statement and kept in the statement's
+//! [`PatternCache`], so a pattern taken from a column is compiled once per distinct value.
+//!
+//! `LIKE` and `SIMILAR TO` escape with `\` unless `ESCAPE` names another char, and
+//! `ESCAPE ''` turns escaping off. `SIMILAR TO` is translated to a regular expression
+//! which has to match the whole text.
+
+mod regex;
+
+use crate::execution::ExecutionError;
+use crate::parser::expr::PatternKind;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Most patterns a statement keeps compiled, more are compiled on every use
+const CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug)]
+pub struct Pattern {
+    matcher: Matcher,
+    /// Whether the text is lowered before it is matched, `ILIKE` patterns are lowered
+    lower: bool,
+}
+
+/// `LIKE` patterns which only have a `%` at their ends are matched without the general
+/// matcher.
+#[derive(Debug)]
+enum Matcher {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    Like(Vec<LikeToken>),
+    Regex(Regex),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LikeToken {
+    Char(char),
+    /// `_`
+    One,
+    /// `%`
+    Any,
+}
+
+impl Pattern {
+    pub fn new(
+        kind: PatternKind,
+        pattern: &str,
+        escape: Option<char>,
+    ) -> Result<Pattern, ExecutionError> {
+        let regex = |pattern: &str, ignore_case| {
+            let regex = Regex::new(pattern, ignore_case).map_err(|error| {
+                ExecutionError::InvalidArgument(format!("invalid regular expression: {}", error))
+            })?;
+            Ok(Pattern {
+                matcher: Matcher::Regex(regex),
+                lower: false,
+            })
+        };
+        match kind {
+            PatternKind::Like => Ok(Pattern {
+                matcher: like_matcher(like_tokens(pattern, escape)?),
+                lower: false,
+            }),
+            PatternKind::ILike => {
+                let tokens = like_tokens(pattern, escape)?
+                    .into_iter()
+                    .flat_map(|token| match token {
+                        LikeToken::Char(c) => c.to_lowercase().map(LikeToken::Char).collect(),
+                        token => vec![token],
+                    })
+                    .collect();
+                Ok(Pattern {
+                    matcher: like_matcher(tokens),
+                    lower: true,
+                })
+            }
+            PatternKind::SimilarTo => regex(&similar_to_regex(pattern, escape)?, false),
+            PatternKind::Regex => regex(pattern, false),
+            PatternKind::RegexIgnoreCase => regex(pattern, true),
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        if self.lower {
+            let text: String = text.chars().flat_map(char::to_lowercase).collect();
+            return self.matcher.matches(&text);
+        }
+        self.matcher.matches(text)
+    }
+}
+
+impl Matcher {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Matcher::Exact(pattern) => text == pattern,
+            Matcher::Prefix(pattern) => text.starts_with(pattern.as_str()),
+            Matcher::Suffix(pattern) => text.ends_with(pattern.as_str()),
+            Matcher::Contains(pattern) => text.contains(pattern.as_str()),
+            Matcher::Like(tokens) => {
+                let text: Vec<_> = text.chars().collect();
+                like(&text, tokens)
+            }
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// The escape char of `ESCAPE`, `\` without it and none for `ESCAPE ''`.
+pub fn escape_char(escape: Option<&str>) -> Result<Option<char>, ExecutionError> {
+    let Some(escape) = escape else {
+        return Ok(Some('\\'));
+    };
+    let mut chars = escape.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(c), None) => Ok(Some(c)),
+        _ => Err(ExecutionError::InvalidArgument(
+            "invalid escape string".to_string(),
+        )),
+    }
+}
+
+/// The texts a `LIKE` pattern can match are at least the literal text before its first
+/// wildcard and less than the text after it, e.g. `'abc%'` matches texts from `'abc'` to
+/// `'abd'`. The upper bound is `None` if there is no text after the prefix, and there is
+/// no range for a pattern which starts with a wildcard or isn't valid.
+pub fn like_range(pattern: &str, escape: Option<char>) -> Option<(String, Option<String>)> {
+    let tokens = like_tokens(pattern, escape).ok()?;
+    let prefix: String = tokens
+        .iter()
+        .map_while(|token| match token {
+            LikeToken::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    if prefix.is_empty() {
+        return None;
+    }
+    // a pattern without wildcards only matches itself
+    if prefix.chars().count() == tokens.len() {
+        let end = format!("{}\0", prefix);
+        return Some((prefix, Some(end)));
+    }
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some((prefix, Some(chars.into_iter().collect())));
+        }
+    }
+    Some((prefix, None))
+}
+
+fn like_tokens(pattern: &str, escape: Option<char>) -> Result<Vec<LikeToken>, ExecutionError> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(c) => LikeToken::Char(c),
+                None => {
+                    return Err(ExecutionError::InvalidArgument(
+                        "LIKE pattern must not end with escape character".to_string(),
+                    ))
+                }
+            },
+            '%' => LikeToken::Any,
+            '_' => LikeToken::One,
+            c => LikeToken::Char(c),
+        };
+        // `%%` matches what `%` matches
+        if !(token == LikeToken::Any && tokens.last() == Some(&LikeToken::Any)) {
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn like_matcher(tokens: Vec<LikeToken>) -> Matcher {
+    let starts_any = tokens.first() == Some(&LikeToken::Any);
+    let inner = &tokens[starts_any as usize..];
+    let ends_any = inner.last() == Some(&LikeToken::Any);
+    let inner = &inner[..inner.len() - ends_any as usize];
+    let text: Option<String> = inner
+        .iter()
+        .map(|token| match token {
+            LikeToken::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    match (text, starts_any, ends_any) {
+        (Some(text), false, false) => Matcher::Exact(text),
+        (Some(text), false, true) => Matcher::Prefix(text),
+        (Some(text), true, false) => Matcher::Suffix(text),
+        (Some(text), true, true) => Matcher::Contains(text),
+        (None, _, _) => Matcher::Like(tokens),
+    }
+}
+
+/// Whether the text matches the tokens. After a `%` fails, the text it skips grows by a
+/// char, only the last `%` is retried, so matching takes at most the product of the
+/// lengths.
+fn like(text: &[char], tokens: &[LikeToken]) -> bool {
+    let (mut position, mut index) = (0, 0);
+    // the tokens after the last `%` and the text it skipped to
+    let mut retry = None;
+    while position < text.len() {
+        match tokens.get(index) {
+            Some(LikeToken::Any) => {
+                index += 1;
+                retry = Some((index, position));
+            }
+            Some(LikeToken::One) => (position, index) = (position + 1, index + 1),
+            Some(LikeToken::Char(c)) if *c == text[position] => {
+                (position, index) = (position + 1, index + 1)
+            }
+            _ => match retry {
+                Some((after, skipped)) => {
+                    retry = Some((after, skipped + 1));
+                    (position, index) = (skipped + 1, after);
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[index..].iter().all(|token| *token == LikeToken::Any)
+}
+
+/// The regular expression of a `SIMILAR TO` pattern: `%` and `_` are `.*` and `.`, `.`,
+/// `^`, `$` and `\` are literal chars, and the rest is as in a regular expression.
+fn similar_to_regex(pattern: &str, escape: Option<char>) -> Result<String, ExecutionError> {
+    let mut regex = String::from("^(?:");
+    let mut chars = pattern.chars().peekable();
+    let mut in_bracket = false;
+    while let Some(c) = chars.next() {
+        if Some(c) == escape {
+            let c = chars.next().ok_or_else(|| {
+                ExecutionError::InvalidArgument(
+                    "SIMILAR TO pattern must not end with escape character".to_string(),
+                )
+            })?;
+            if !c.is_alphanumeric() {
+                regex.push('\\');
+            }
+            regex.push(c);
+            continue;
+        }
+        match c {
+            ']' if in_bracket => {
+                in_bracket = false;
+                regex.push(c);
+            }
+            '\\' => regex.push_str("\\\\"),
+            c if in_bracket => regex.push(c),
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            '.' | '^' | '$' => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            '[' => {
+                in_bracket = true;
+                regex.push(c);
+                // `^` and `]` right after `[` belong to the bracket
+                for first in ['^', ']'] {
+                    if chars.next_if_eq(&first).is_some() {
+                        regex.push(first);
+                    }
+                }
+            }
+            c => regex.push(c),
+        }
+    }
+    regex.push_str(")$");
+    Ok(regex)
+}
+
+/// Compiled patterns by their kind and escape char, then by their text.
+type Patterns = HashMap<(PatternKind, Option<char>), HashMap<String, Arc<Pattern>>>;
+
+/// Patterns compiled by a statement, shared by its operators and its worker threads.
+#[derive(Debug, Clone, Default)]
+pub struct PatternCache {
+    patterns: Arc<Mutex<Patterns>>,
+}
+
+impl PatternCache {
+    /// The compiled pattern, compiled now if the statement didn't use it before.
+    pub fn get(
+        &self,
+        kind: PatternKind,
+        pattern: &str,
+        escape: Option<char>,
+    ) -> Result<Arc<Pattern>, ExecutionError> {
+        let cached = |patterns: &Patterns| {
+            patterns
+                .get(&(kind, escape))
+                .and_then(|patterns| patterns.get(pattern))
+                .cloned()
+        };
+        if let Some(compiled) = cached(&self.patterns.lock().expect("patterns")) {
+            return Ok(compiled);
+        }
+        let compiled = Arc::new(Pattern::new(kind, pattern, escape)?);
+        let mut patterns = self.patterns.lock().expect("patterns");
+        if patterns.values().map(HashMap::len).sum::<usize>() < CACHE_CAPACITY {
+            patterns
+                .entry((kind, escape))
+                .or_default()
+                .insert(pattern.to_string(), compiled.clone());
+        }
+        Ok(compiled)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        let patterns = self.patterns.lock().expect("patterns");
+        patterns.values().map(HashMap::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(kind: PatternKind, pattern: &str, text: &str) -> bool {
+        Pattern::new(kind, pattern, Some('\\'))
+            .unwrap()
+            .matches(text)
+    }
+
+    #[test]
+    fn matches_like_patterns() {
+        use PatternKind::{ILike, Like};
+        for (pattern, text, expected) in [
+            ("abc", "abc", true),
+            ("abc", "abcd", false),
+            ("a%", "abc", true),
+            ("%c", "abc", true),
+            ("%b%", "abc", true),
+            ("a_c", "abc", true),
+            ("a_c", "ac", false),
+            ("%a%b%c%", "xaybzc", true),
+            ("%a%b%c%", "xaybz", false),
+            ("a%%c", "ac", true),
+            ("", "", true),
+            ("%", "", true),
+            ("a\\%", "a%", true),
+            ("a\\%", "ab", false),
+            ("\\_%", "_x", true),
+            ("ü_", "üß", true),
+        ] {
+            assert_eq!(
+                matches(Like, pattern, text),
+                expected,
+                "{} {}",
+                pattern,
+                text
+            );
+        }
+        // as fast as the text is long, where backtracking takes exponential time
+        let pattern = format!("{}b", "%a".repeat(50));
+        assert!(!matches(Like, &pattern, &"a".repeat(5000)));
+        assert!(matches(ILike, "A%c", "abC"));
+        assert!(matches(ILike, "ÄB_", "äbc"));
+        assert!(!matches(Like, "A%c", "abC"));
+        let pattern = Pattern::new(Like, "a#%", Some('#')).unwrap();
+        assert!(pattern.matches("a%") && !pattern.matches("a#%"));
+        let pattern = Pattern::new(Like, "a\\%", None).unwrap();
+        assert!(pattern.matches("a\\x"));
+        assert_eq!(
+            Pattern::new(Like, "a\\", Some('\\')).unwrap_err(),
+            ExecutionError::InvalidArgument(
+                "LIKE pattern must not end with escape character".to_string()
+            )
+        );
+        assert_eq!(escape_char(Some("")), Ok(None));
+        assert!(escape_char(Some("ab")).is_err());
+    }
+
+    #[test]
+    fn matches_similar_to_and_regular_expressions() {
+        use PatternKind::{Regex, RegexIgnoreCase, SimilarTo};
+        for (pattern, text, expected) in [
+            ("abc", "abc", true),
+            ("a", "abc", false),
+            ("%(b|d)%", "abc", true),
+            ("(a|b)*c", "ababc", true),
+            ("a_c", "abc", true),
+            ("a.c", "abc", false),
+            ("a.c", "a.c", true),
+            ("[^a-b]%", "cab", true),
+            ("[]x]+", "]x", true),
+            ("a\\%", "a%", true),
+            ("x{2}", "xx", true),
+        ] {
+            assert_eq!(
+                matches(SimilarTo, pattern, text),
+                expected,
+                "{} {}",
+                pattern,
+                text
+            );
+        }
+        assert!(matches(Regex, "b.", "abc"));
+        assert!(!matches(Regex, "^b", "abc"));
+        assert!(matches(RegexIgnoreCase, "^AB", "abc"));
+        assert_eq!(
+            Pattern::new(Regex, "(a", None).unwrap_err(),
+            ExecutionError::InvalidArgument(
+                "invalid regular expression: parentheses () not balanced".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn finds_ranges_of_prefixes() {
+        let range = |pattern| like_range(pattern, Some('\\'));
+        assert_eq!(
+            range("abc%"),
+            Some(("abc".to_string(), Some("abd".to_string())))
+        );
+        assert_eq!(
+            range("ab_d%"),
+            Some(("ab".to_string(), Some("ac".to_string())))
+        );
+        assert_eq!(
+            range("ab"),
+            Some(("ab".to_string(), Some("ab\0".to_string())))
+        );
+        assert_eq!(
+            range("a\\%%"),
+            Some(("a%".to_string(), Some("a&".to_string())))
+        );
+        assert_eq!(
+            range("a\u{10FFFF}%"),
+            Some(("a\u{10FFFF}".to_string(), Some("b".to_string())))
+        );
+        assert_eq!(range("\u{10FFFF}%"), Some(("\u{10FFFF}".to_string(), None)));
+        assert_eq!(range("%abc"), None);
+        assert_eq!(range("_"), None);
+    }
+
+    #[test]
+    fn caches_patterns() {
+        let cache = PatternCache::default();
+        let first = cache.get(PatternKind::Like, "a%", Some('\\')).unwrap();
+        let second = cache
+            .clone()
+            .get(PatternKind::Like, "a%", Some('\\'))
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        cache.get(PatternKind::ILike, "a%", Some('\\')).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(PatternKind::Regex, "(", None).is_err());
+        assert_eq!(cache.len(), 2);
+    }
+}
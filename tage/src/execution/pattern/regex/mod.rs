@@ -0,0 +1,594 @@
+//! POSIX-style regular expressions as `~` and `SIMILAR TO` use them: alternation,
+//! groups, the quantifiers `*`, `+`, `?` and `{m,n}`, `.`, bracket expressions with
+//! ranges and classes such as `[:alpha:]`, the escapes `\d`, `\w` and `\s` and the
+//! anchors `^` and `$`.
+//!
+//! An expression is compiled to the instructions of a Pike VM, which runs all threads in
+//! lockstep over the text, so matching takes time linear in the length of the text for
+//! every expression.
+
+use std::fmt;
+
+/// Most repetitions of a bound, as in POSIX
+const MAX_REPETITIONS: u32 = 255;
+/// Most instructions of an expression, a bound repeats the instructions of its operand
+const MAX_INSTRUCTIONS: usize = 100_000;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RegexError(&'static str);
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Instruction>,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Char(char),
+    /// `.`
+    Any,
+    Class(Class),
+    /// `^`
+    Start,
+    /// `$`
+    End,
+    /// Continues at both positions
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ClassItem {
+    Range(char, char),
+    Named(NamedClass),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NamedClass {
+    Alpha,
+    Digit,
+    Alnum,
+    Upper,
+    Lower,
+    Space,
+    Punct,
+    XDigit,
+    /// `\w`, letters, digits and `_`
+    Word,
+}
+
+impl NamedClass {
+    fn contains(self, c: char) -> bool {
+        match self {
+            NamedClass::Alpha => c.is_alphabetic(),
+            NamedClass::Digit => c.is_ascii_digit(),
+            NamedClass::Alnum => c.is_alphanumeric(),
+            NamedClass::Upper => c.is_uppercase(),
+            NamedClass::Lower => c.is_lowercase(),
+            NamedClass::Space => c.is_whitespace(),
+            NamedClass::Punct => c.is_ascii_punctuation(),
+            NamedClass::XDigit => c.is_ascii_hexdigit(),
+            NamedClass::Word => c.is_alphanumeric() || c == '_',
+        }
+    }
+
+    fn lookup(name: &str) -> Option<NamedClass> {
+        Some(match name {
+            "alpha" => NamedClass::Alpha,
+            "digit" => NamedClass::Digit,
+            "alnum" => NamedClass::Alnum,
+            "upper" => NamedClass::Upper,
+            "lower" => NamedClass::Lower,
+            "space" => NamedClass::Space,
+            "punct" => NamedClass::Punct,
+            "xdigit" => NamedClass::XDigit,
+            _ => return None,
+        })
+    }
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match *item {
+            ClassItem::Range(low, high) => (low..=high).contains(&c),
+            ClassItem::Named(class) => class.contains(c),
+        });
+        found != self.negated
+    }
+
+    /// `\d`, `\w`, `\s` and their negations `\D`, `\W`, `\S`.
+    fn escape(c: char) -> Option<Class> {
+        let class = match c.to_ascii_lowercase() {
+            'd' => NamedClass::Digit,
+            'w' => NamedClass::Word,
+            's' => NamedClass::Space,
+            _ => return None,
+        };
+        Some(Class {
+            negated: c.is_ascii_uppercase(),
+            items: vec![ClassItem::Named(class)],
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+impl Regex {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Regex, RegexError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.position < parser.chars.len() {
+            return Err(RegexError("parentheses () not balanced"));
+        }
+        let mut program = vec![];
+        compile(&node, &mut program)?;
+        program.push(Instruction::Match);
+        Ok(Regex {
+            program,
+            ignore_case,
+        })
+    }
+
+    /// Whether the expression matches a part of the text, it is anchored by `^` and `$`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        for position in 0..=chars.len() {
+            // a match may start at every position
+            self.add(&mut current, 0, position, chars.len());
+            if current.list.is_empty() {
+                continue;
+            }
+            let c = chars.get(position).copied();
+            for index in 0..current.list.len() {
+                let pc = current.list[index];
+                let accepted = match (&self.program[pc], c) {
+                    (Instruction::Match, _) => return true,
+                    (Instruction::Char(expected), Some(c)) => self.accepts(c, |c| c == *expected),
+                    (Instruction::Any, Some(_)) => true,
+                    (Instruction::Class(class), Some(c)) => self.accepts(c, |c| class.contains(c)),
+                    _ => false,
+                };
+                if accepted {
+                    self.add(&mut next, pc + 1, position + 1, chars.len());
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        false
+    }
+
+    /// Whether the test accepts the char, or one of its cases when case is ignored.
+    fn accepts(&self, c: char, test: impl Fn(char) -> bool) -> bool {
+        test(c)
+            || (self.ignore_case
+                && [single(c.to_lowercase()), single(c.to_uppercase())]
+                    .into_iter()
+                    .flatten()
+                    .any(test))
+    }
+
+    /// Adds the thread and the threads it reaches without reading a char.
+    fn add(&self, threads: &mut Threads, pc: usize, position: usize, len: usize) {
+        // a stack rather than recursion, a bound repeats empty operands many times
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !threads.insert(pc) {
+                continue;
+            }
+            match self.program[pc] {
+                Instruction::Jump(target) => stack.push(target),
+                Instruction::Split(first, second) => stack.extend([second, first]),
+                Instruction::Start if position == 0 => stack.push(pc + 1),
+                Instruction::End if position == len => stack.push(pc + 1),
+                Instruction::Start | Instruction::End => {}
+                _ => threads.list.push(pc),
+            }
+        }
+    }
+}
+
+/// The only char of a case mapping, `None` if it maps to several.
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Threads of one step, each instruction is run once.
+struct Threads {
+    list: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: vec![],
+            seen: vec![false; len],
+        }
+    }
+
+    /// Whether the instruction wasn't seen in the step before.
+    fn insert(&mut self, pc: usize) -> bool {
+        !std::mem::replace(&mut self.seen[pc], true)
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.fill(false);
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Instruction>) -> Result<(), RegexError> {
+    if program.len() > MAX_INSTRUCTIONS {
+        return Err(RegexError("regular expression is too complex"));
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Instruction::Char(*c)),
+        Node::Any => program.push(Instruction::Any),
+        Node::Class(class) => program.push(Instruction::Class(class.clone())),
+        Node::Start => program.push(Instruction::Start),
+        Node::End => program.push(Instruction::End),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alternate(nodes) => {
+            // split to the first alternative and the rest, each jumps to the end
+            let mut jumps = vec![];
+            for (index, node) in nodes.iter().enumerate() {
+                if index + 1 < nodes.len() {
+                    let split = program.len();
+                    program.push(Instruction::Split(split + 1, 0));
+                    compile(node, program)?;
+                    jumps.push(program.len());
+                    program.push(Instruction::Jump(0));
+                    program[split] = Instruction::Split(split + 1, program.len());
+                } else {
+                    compile(node, program)?;
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Instruction::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Instruction::Split(split + 1, 0));
+                    compile(node, program)?;
+                    program.push(Instruction::Jump(split));
+                    program[split] = Instruction::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Instruction::Split(program.len() + 1, 0));
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Instruction::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// `<concatenation> | ...`
+    fn alternation(&mut self) -> Result<Node, RegexError> {
+        let mut alternatives = vec![self.concatenation()?];
+        while self.consume('|') {
+            alternatives.push(self.concatenation()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().expect("an alternative"),
+            _ => Node::Alternate(alternatives),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().expect("a node"),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, RegexError> {
+        let c = self.next().expect("a char");
+        Ok(match c {
+            '(' => {
+                // a group which doesn't capture is the same, nothing is captured
+                if self.peek() == Some('?') && self.chars.get(self.position + 1) == Some(&':') {
+                    self.position += 2;
+                }
+                let node = self.alternation()?;
+                if !self.consume(')') {
+                    return Err(RegexError("parentheses () not balanced"));
+                }
+                node
+            }
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '[' => Node::Class(self.bracket()?),
+            '\\' => match self.next() {
+                Some(c) => match Class::escape(c) {
+                    Some(class) => Node::Class(class),
+                    None => Node::Char(escaped(c)?),
+                },
+                None => return Err(RegexError("invalid escape \\ sequence")),
+            },
+            '*' | '+' | '?' | '{' => return Err(RegexError("quantifier operand invalid")),
+            c => Node::Char(c),
+        })
+    }
+
+    /// The atom followed by its quantifiers, e.g. `a*` or `(ab){2,3}`.
+    fn quantified(&mut self, mut atom: Node) -> Result<Node, RegexError> {
+        loop {
+            let (min, max) = match self.next() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => self.bound()?,
+                Some(_) => {
+                    self.position -= 1;
+                    return Ok(atom);
+                }
+                None => return Ok(atom),
+            };
+            if matches!(atom, Node::Start | Node::End) {
+                return Err(RegexError("quantifier operand invalid"));
+            }
+            // a lazy quantifier matches the same texts
+            self.consume('?');
+            atom = Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+            };
+        }
+    }
+
+    /// `m}`, `m,}` or `m,n}` after `{`.
+    fn bound(&mut self) -> Result<(u32, Option<u32>), RegexError> {
+        let invalid = RegexError("invalid repetition count(s)");
+        let min = self.number().ok_or(invalid.clone())?;
+        let max = match self.consume(',') {
+            true => match self.peek() {
+                Some('}') => None,
+                _ => Some(self.number().ok_or(invalid.clone())?),
+            },
+            false => Some(min),
+        };
+        if !self.consume('}') || max.is_some_and(|max| max < min) {
+            return Err(invalid);
+        }
+        Ok((min, max))
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits
+            .parse()
+            .ok()
+            .filter(|number| *number <= MAX_REPETITIONS)
+    }
+
+    /// A bracket expression after `[`, e.g. `^a-z[:digit:]]`.
+    fn bracket(&mut self) -> Result<Class, RegexError> {
+        let unbalanced = RegexError("brackets [] not balanced");
+        let negated = self.consume('^');
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or(unbalanced.clone())?;
+            let low = match c {
+                ']' if !first => break,
+                '[' if self.consume(':') => {
+                    let start = self.position;
+                    while self.peek().is_some_and(|c| c != ':') {
+                        self.position += 1;
+                    }
+                    let name: String = self.chars[start..self.position].iter().collect();
+                    if !self.consume(':') || !self.consume(']') {
+                        return Err(unbalanced);
+                    }
+                    let class =
+                        NamedClass::lookup(&name).ok_or(RegexError("invalid character class"))?;
+                    items.push(ClassItem::Named(class));
+                    first = false;
+                    continue;
+                }
+                '\\' => {
+                    let c = self.next().ok_or(unbalanced.clone())?;
+                    match Class::escape(c) {
+                        Some(Class {
+                            negated: false,
+                            items: escaped,
+                        }) => {
+                            items.extend(escaped);
+                            first = false;
+                            continue;
+                        }
+                        Some(_) => return Err(RegexError("invalid escape \\ sequence")),
+                        None => escaped(c)?,
+                    }
+                }
+                c => c,
+            };
+            first = false;
+            let high = match (self.peek(), self.chars.get(self.position + 1)) {
+                (Some('-'), Some(&high)) if high != ']' => {
+                    self.position += 2;
+                    match high {
+                        '\\' => escaped(self.next().ok_or(unbalanced.clone())?)?,
+                        high => high,
+                    }
+                }
+                _ => low,
+            };
+            if high < low {
+                return Err(RegexError("invalid character range"));
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+        Ok(Class { negated, items })
+    }
+}
+
+/// The char an escape stands for, letters and digits only escape as `\n`, `\t` or `\r`.
+fn escaped(c: char) -> Result<char, RegexError> {
+    Ok(match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c if c.is_alphanumeric() => return Err(RegexError("invalid escape \\ sequence")),
+        c => c,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn matches_parts_of_texts() {
+        assert!(matches("b", "abc"));
+        assert!(!matches("^b", "abc"));
+        assert!(matches("^a.c$", "abc"));
+        assert!(matches("^(ab|cd)+$", "abcdab"));
+        assert!(!matches("^(ab|cd)+$", "abca"));
+        assert!(matches("^a{2,3}$", "aaa"));
+        assert!(!matches("^a{2,3}$", "aaaa"));
+        assert!(matches("^a{2,}b?$", "aaaaab"));
+        assert!(matches("^(ab){1}*$", "ababab"));
+        assert!(matches("^[a-c[:digit:]_]+$", "ab1_c9"));
+        assert!(!matches("^[^a-c]", "b"));
+        assert!(matches("^[]a]+$", "]a]"));
+        assert!(matches("\\d+\\.\\d", "pi is 3.14"));
+        assert!(matches("^\\w+\\s\\S$", "ab_1 x"));
+        assert!(matches("^(?:x|)y*?$", "yy"));
+        assert!(matches("^$", ""));
+        assert!(matches("x*", ""));
+        assert!(matches("^ü.$", "üß"));
+        assert!(Regex::new("^ABC[d-f]$", true).unwrap().is_match("abcE"));
+        // runs in linear time where backtracking takes exponential time
+        assert!(!matches("^(a*)*b$", &"a".repeat(5000)));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for (pattern, error) in [
+            ("(a", "parentheses () not balanced"),
+            ("a)", "parentheses () not balanced"),
+            ("[a", "brackets [] not balanced"),
+            ("*a", "quantifier operand invalid"),
+            ("a{3,2}", "invalid repetition count(s)"),
+            ("a{256}", "invalid repetition count(s)"),
+            ("[z-a]", "invalid character range"),
+            ("[[:klingon:]]", "invalid character class"),
+            ("\\q", "invalid escape \\ sequence"),
+            ("a\\", "invalid escape \\ sequence"),
+            (
+                "(((a{255}){255}){255})",
+                "regular expression is too complex",
+            ),
+        ] {
+            assert_eq!(
+                Regex::new(pattern, false).unwrap_err().to_string(),
+                error,
+                "{}",
+                pattern
+            );
+        }
+    }
+}
@@ -0,0 +1,1049 @@
+//! Physical plan, the logical plan with an algorithm chosen for every node and its
+//! expressions compiled against the rows of its inputs.
+
+use crate::analyser::analyse;
+use crate::analyser::bound::{
+    BoundAssignment, BoundConflictAction, BoundExpr, BoundFrameBound, BoundFunction, BoundQuery,
+    BoundRelation, ProjectionItem,
+};
+use crate::catalog::{Catalog, Column, Index, Table};
+use crate::execution::batch::aggregate::BatchHashAggregate;
+use crate::execution::batch::operators::{
+    BatchAppend, BatchFilter, BatchLimit, BatchProject, BatchRows, BatchScan, RowBatches,
+};
+use crate::execution::batch::BatchOperator;
+use crate::execution::expr::ScalarExpr;
+use crate::execution::operators::aggregate::{Aggregate, AggregatePhase, HashAggregate};
+use crate::execution::operators::join::{HashJoin, NestedLoopJoin, SortMergeJoin};
+use crate::execution::operators::modify::{Delete, Insert, Update};
+use crate::execution::operators::scan::{FunctionScan, IndexScan, SeqScan};
+use crate::execution::operators::sort::Sort;
+use crate::execution::operators::window::{
+    Frame, FrameEdge, Window, WindowCall, WindowFunctionKind,
+};
+use crate::execution::operators::{Append, Filter, Limit, Project, Values};
+use crate::execution::parallel::{Gather, ParallelScan, Repartition};
+use crate::execution::{ExecutionError, ExecutionMode, Operator};
+use crate::functions::table::TableFunction;
+use crate::functions::FunctionRef;
+use crate::optimizer::{conjunction, conjuncts, evaluable, referenced_columns};
+use crate::parser::expr::Operator as BinaryOperator;
+use crate::parser::{Order, Queries};
+use crate::planner::{JoinAlgorithm, JoinType, LogicalPlan, PlanColumn, ROW_ID_COLUMN_ID};
+use crate::value::Value;
+use std::ops::Bound;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum PhysicalPlan {
+    SeqScan {
+        table: Table,
+        columns: Vec<ScanColumn>,
+    },
+    /// Rows of the table whose first indexed value is within the bounds
+    IndexScan {
+        table: Table,
+        index: Index,
+        low: Bound<ScalarExpr>,
+        high: Bound<ScalarExpr>,
+        columns: Vec<ScanColumn>,
+    },
+    Filter {
+        input: Box<PhysicalPlan>,
+        predicate: ScalarExpr,
+    },
+    Project {
+        input: Box<PhysicalPlan>,
+        exprs: Vec<ScalarExpr>,
+    },
+    NestedLoopJoin {
+        join: JoinSpec,
+    },
+    HashJoin {
+        join: JoinSpec,
+    },
+    SortMergeJoin {
+        join: JoinSpec,
+    },
+    /// One row per group with the group keys followed by the aggregates
+    HashAggregate {
+        input: Box<PhysicalPlan>,
+        group_by: Vec<ScalarExpr>,
+        aggregates: Vec<Aggregate>,
+        phase: AggregatePhase,
+    },
+    Sort {
+        input: Box<PhysicalPlan>,
+        order_by: Vec<(ScalarExpr, Order)>,
+    },
+    /// Rows of the input with the value of every window function appended
+    Window {
+        input: Box<PhysicalPlan>,
+        functions: Vec<WindowCall>,
+    },
+    Limit {
+        input: Box<PhysicalPlan>,
+        limit: Option<u64>,
+        offset: u64,
+    },
+    /// Rows of every input, one after the other
+    Append {
+        inputs: Vec<PhysicalPlan>,
+    },
+    Values {
+        rows: Vec<Vec<ScalarExpr>>,
+    },
+    FunctionScan {
+        function: Arc<dyn TableFunction>,
+        arguments: Vec<ScalarExpr>,
+    },
+    Insert {
+        input: Box<PhysicalPlan>,
+        target: Target,
+        /// Position of every column of the table in the input rows
+        columns: Vec<Option<usize>>,
+        /// Values of the columns the input doesn't have, `NULL` without a default
+        defaults: Vec<ScalarExpr>,
+        on_conflict: Option<Conflict>,
+    },
+    Update {
+        input: Box<PhysicalPlan>,
+        target: Target,
+        /// Position of the row id in the input rows
+        row_id: usize,
+        /// Positions of the changed columns in the table row and their new values, read
+        /// from the input row
+        assignments: Vec<(usize, ScalarExpr)>,
+    },
+    Delete {
+        input: Box<PhysicalPlan>,
+        target: Target,
+        row_id: usize,
+    },
+    /// Rows of the morsels of the table a worker takes, see `parallel`
+    ParallelScan {
+        table: Table,
+        columns: Vec<ScanColumn>,
+        /// Identifies the scan among the exchange nodes of the plan
+        id: usize,
+    },
+    /// Rows of the input whose keys hash to the partition of the worker
+    Repartition {
+        input: Box<PhysicalPlan>,
+        keys: Vec<ScalarExpr>,
+        id: usize,
+    },
+    /// Rows of the input executed by the workers
+    Gather {
+        input: Box<PhysicalPlan>,
+        workers: usize,
+    },
+}
+
+/// Where a scan reads the value of a column from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanColumn {
+    /// Position of the column in the stored row
+    Column(usize),
+    RowId,
+}
+
+/// Inputs of a join and how their rows are matched.
+#[derive(Debug, Clone)]
+pub struct JoinSpec {
+    pub left: Box<PhysicalPlan>,
+    pub right: Box<PhysicalPlan>,
+    pub join_type: JoinType,
+    /// Equality keys, evaluated on the left and the right rows
+    pub keys: Vec<(ScalarExpr, ScalarExpr)>,
+    /// The rest of the join condition, evaluated on the left row followed by the right one
+    pub condition: Option<ScalarExpr>,
+    /// Number of columns of the inputs, for the `NULL`s of an outer join
+    pub left_width: usize,
+    pub right_width: usize,
+}
+
+/// The table a statement changes.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub table: Table,
+    pub indexes: Vec<Index>,
+    /// Evaluated on the changed table row followed by the input row, for the old row of
+    /// a `delete`
+    pub returning: Option<Vec<ScalarExpr>>,
+}
+
+/// `on conflict` of an `insert`, the conflicting row is found through a unique index on
+/// the conflict columns.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub indexes: Vec<Index>,
+    /// `None` for `do nothing`. Evaluated on the existing row followed by the excluded one.
+    pub update: Option<ConflictUpdate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConflictUpdate {
+    pub assignments: Vec<(usize, ScalarExpr)>,
+    pub filter: Option<ScalarExpr>,
+}
+
+impl PhysicalPlan {
+    pub fn build(
+        plan: &LogicalPlan,
+        catalog: &dyn Catalog,
+    ) -> Result<PhysicalPlan, ExecutionError> {
+        let build = |plan: &LogicalPlan| PhysicalPlan::build(plan, catalog).map(Box::new);
+        Ok(match plan {
+            LogicalPlan::Scan {
+                relation,
+                schema,
+                index: None,
+            } => PhysicalPlan::SeqScan {
+                table: relation.table.clone(),
+                columns: scan_columns(&relation.table, schema)?,
+            },
+            LogicalPlan::Scan {
+                relation,
+                schema,
+                index: Some(range),
+            } => PhysicalPlan::IndexScan {
+                table: relation.table.clone(),
+                index: range.index.clone(),
+                low: compile_bound(&range.low)?,
+                high: compile_bound(&range.high)?,
+                columns: scan_columns(&relation.table, schema)?,
+            },
+            LogicalPlan::TableFunction { relation, .. } => {
+                let Some(call) = &relation.function else {
+                    return Err(ExecutionError::Unsupported(format!(
+                        "function scan of {}",
+                        relation.name
+                    )));
+                };
+                PhysicalPlan::FunctionScan {
+                    function: call.function.clone(),
+                    arguments: compile_all(&call.arguments, &[])?,
+                }
+            }
+            LogicalPlan::Filter { input, predicate } => PhysicalPlan::Filter {
+                predicate: ScalarExpr::compile(predicate, input.schema())?,
+                input: build(input)?,
+            },
+            LogicalPlan::Project { input, items, .. } => PhysicalPlan::Project {
+                exprs: compile_items(items, input.schema())?,
+                input: build(input)?,
+            },
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                on,
+                algorithm,
+                ..
+            } => {
+                let mut keys = vec![];
+                let mut rest = vec![];
+                for conjunct in on.iter().flat_map(conjuncts) {
+                    match equi_key(&conjunct, left, right) {
+                        Some(key) => keys.push(key),
+                        None => rest.push(conjunct),
+                    }
+                }
+                let schema: Vec<_> = left
+                    .schema()
+                    .iter()
+                    .chain(right.schema())
+                    .cloned()
+                    .collect();
+                let condition = match conjunction(rest) {
+                    Some(condition) => Some(ScalarExpr::compile(&condition, &schema)?),
+                    None => None,
+                };
+                let keys = keys
+                    .into_iter()
+                    .map(|(left_key, right_key)| {
+                        Ok((
+                            ScalarExpr::compile(&left_key, left.schema())?,
+                            ScalarExpr::compile(&right_key, right.schema())?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, ExecutionError>>()?;
+                let algorithm = match algorithm {
+                    Some(_) if keys.is_empty() => JoinAlgorithm::NestedLoop,
+                    Some(algorithm) => *algorithm,
+                    None if keys.is_empty() => JoinAlgorithm::NestedLoop,
+                    None => JoinAlgorithm::Hash,
+                };
+                let join = JoinSpec {
+                    left_width: left.schema().len(),
+                    right_width: right.schema().len(),
+                    left: build(left)?,
+                    right: build(right)?,
+                    join_type: *join_type,
+                    keys,
+                    condition,
+                };
+                match algorithm {
+                    JoinAlgorithm::Hash => PhysicalPlan::HashJoin { join },
+                    JoinAlgorithm::Merge => PhysicalPlan::SortMergeJoin { join },
+                    JoinAlgorithm::NestedLoop => PhysicalPlan::NestedLoopJoin { join },
+                }
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+                ..
+            } => PhysicalPlan::HashAggregate {
+                group_by: compile_all(group_by, input.schema())?,
+                aggregates: aggregates
+                    .iter()
+                    .map(|expr| aggregate(expr, input.schema()))
+                    .collect::<Result<_, _>>()?,
+                input: build(input)?,
+                phase: AggregatePhase::Single,
+            },
+            LogicalPlan::Sort { input, order_by } => PhysicalPlan::Sort {
+                order_by: order_by
+                    .iter()
+                    .map(|(expr, order)| Ok((ScalarExpr::compile(expr, input.schema())?, *order)))
+                    .collect::<Result<_, ExecutionError>>()?,
+                input: build(input)?,
+            },
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => PhysicalPlan::Limit {
+                input: build(input)?,
+                limit: *limit,
+                offset: *offset,
+            },
+            LogicalPlan::Distinct { input } => PhysicalPlan::HashAggregate {
+                group_by: (0..input.schema().len()).map(ScalarExpr::Column).collect(),
+                aggregates: vec![],
+                input: build(input)?,
+                phase: AggregatePhase::Single,
+            },
+            LogicalPlan::Values { rows, .. } => PhysicalPlan::Values {
+                rows: rows
+                    .iter()
+                    .map(|row| compile_all(row, &[]))
+                    .collect::<Result<_, _>>()?,
+            },
+            LogicalPlan::SubqueryAlias { input, .. } => PhysicalPlan::build(input, catalog)?,
+            LogicalPlan::Insert {
+                input,
+                table,
+                columns,
+                on_conflict,
+                returning,
+                ..
+            } => {
+                let defaults = table
+                    .table
+                    .columns
+                    .iter()
+                    .map(|column| default_value(column, catalog))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let input = match input.as_ref() {
+                    // `default` in `values` is the default of the column
+                    LogicalPlan::Values { rows, .. } => PhysicalPlan::Values {
+                        rows: rows
+                            .iter()
+                            .map(|row| {
+                                row.iter()
+                                    .zip(columns)
+                                    .map(|(expr, column)| match expr {
+                                        BoundExpr::Default => {
+                                            Ok(defaults[position(&table.table, column)].clone())
+                                        }
+                                        expr => ScalarExpr::compile(expr, &[]),
+                                    })
+                                    .collect()
+                            })
+                            .collect::<Result<_, _>>()?,
+                    },
+                    input => PhysicalPlan::build(input, catalog)?,
+                };
+                let on_conflict = match on_conflict {
+                    Some(on_conflict) => Some(conflict(table, on_conflict, catalog)?),
+                    None => None,
+                };
+                PhysicalPlan::Insert {
+                    input: Box::new(input),
+                    target: target(table, returning, &[], catalog)?,
+                    columns: table
+                        .table
+                        .columns
+                        .iter()
+                        .map(|column| columns.iter().position(|inserted| inserted.id == column.id))
+                        .collect(),
+                    defaults,
+                    on_conflict,
+                }
+            }
+            LogicalPlan::Update {
+                input,
+                table,
+                assignments,
+                returning,
+                ..
+            } => PhysicalPlan::Update {
+                row_id: row_id_position(table, input.schema())?,
+                assignments: compile_assignments(&table.table, assignments, input.schema())?,
+                target: target(table, returning, input.schema(), catalog)?,
+                input: build(input)?,
+            },
+            LogicalPlan::Delete {
+                input,
+                table,
+                returning,
+                ..
+            } => PhysicalPlan::Delete {
+                row_id: row_id_position(table, input.schema())?,
+                target: target(table, returning, input.schema(), catalog)?,
+                input: build(input)?,
+            },
+            LogicalPlan::Union {
+                inputs,
+                all,
+                schema,
+            } => {
+                let append = PhysicalPlan::Append {
+                    inputs: inputs
+                        .iter()
+                        .map(|input| PhysicalPlan::build(input, catalog))
+                        .collect::<Result<_, _>>()?,
+                };
+                match all {
+                    true => append,
+                    false => PhysicalPlan::HashAggregate {
+                        group_by: (0..schema.len()).map(ScalarExpr::Column).collect(),
+                        aggregates: vec![],
+                        input: Box::new(append),
+                        phase: AggregatePhase::Single,
+                    },
+                }
+            }
+            LogicalPlan::Window {
+                input, functions, ..
+            } => PhysicalPlan::Window {
+                functions: functions
+                    .iter()
+                    .map(|expr| window_call(expr, input.schema()))
+                    .collect::<Result<_, _>>()?,
+                input: build(input)?,
+            },
+        })
+    }
+
+    pub fn into_operator(self) -> Box<dyn Operator> {
+        self.operator(ExecutionMode::Row)
+    }
+
+    /// The operator of the node. In batch mode the nodes which have a batch operator are
+    /// executed a batch at a time, below row operators as well.
+    pub(crate) fn operator(self, mode: ExecutionMode) -> Box<dyn Operator> {
+        if mode == ExecutionMode::Batch && self.is_vectorized() {
+            return Box::new(BatchRows::new(self.into_batch_operator()));
+        }
+        let child = |input: Box<PhysicalPlan>| input.operator(mode);
+        match self {
+            PhysicalPlan::SeqScan { table, columns } => Box::new(SeqScan::new(table, columns)),
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                low,
+                high,
+                columns,
+            } => Box::new(IndexScan::new(table, index, low, high, columns)),
+            PhysicalPlan::Filter { input, predicate } => {
+                Box::new(Filter::new(child(input), predicate))
+            }
+            PhysicalPlan::Project { input, exprs } => Box::new(Project::new(child(input), exprs)),
+            PhysicalPlan::NestedLoopJoin { join } => Box::new(NestedLoopJoin::new(join, mode)),
+            PhysicalPlan::HashJoin { join } => Box::new(HashJoin::new(join, mode)),
+            PhysicalPlan::SortMergeJoin { join } => Box::new(SortMergeJoin::new(join, mode)),
+            PhysicalPlan::HashAggregate {
+                input,
+                group_by,
+                aggregates,
+                phase,
+            } => Box::new(HashAggregate::new(
+                child(input),
+                group_by,
+                aggregates,
+                phase,
+            )),
+            PhysicalPlan::Sort { input, order_by } => Box::new(Sort::new(child(input), order_by)),
+            PhysicalPlan::Window { input, functions } => {
+                Box::new(Window::new(child(input), functions))
+            }
+            PhysicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => Box::new(Limit::new(child(input), limit, offset)),
+            PhysicalPlan::Append { inputs } => Box::new(Append::new(
+                inputs
+                    .into_iter()
+                    .map(|input| input.operator(mode))
+                    .collect(),
+            )),
+            PhysicalPlan::Values { rows } => Box::new(Values::new(rows)),
+            PhysicalPlan::FunctionScan {
+                function,
+                arguments,
+            } => Box::new(FunctionScan::new(function, arguments)),
+            PhysicalPlan::Insert {
+                input,
+                target,
+                columns,
+                defaults,
+                on_conflict,
+            } => Box::new(Insert::new(
+                child(input),
+                target,
+                columns,
+                defaults,
+                on_conflict,
+            )),
+            PhysicalPlan::Update {
+                input,
+                target,
+                row_id,
+                assignments,
+            } => Box::new(Update::new(child(input), target, row_id, assignments)),
+            PhysicalPlan::Delete {
+                input,
+                target,
+                row_id,
+            } => Box::new(Delete::new(child(input), target, row_id)),
+            PhysicalPlan::ParallelScan { table, columns, id } => {
+                Box::new(ParallelScan::new(table, columns, id))
+            }
+            PhysicalPlan::Repartition { input, id, .. } => {
+                Box::new(Repartition::new(*input, id, mode))
+            }
+            PhysicalPlan::Gather { input, workers } => Box::new(Gather::new(*input, workers, mode)),
+        }
+    }
+
+    /// The batch operator of the node, nodes without one are executed a row at a time
+    /// and their rows are put into batches.
+    pub fn into_batch_operator(self) -> Box<dyn BatchOperator> {
+        match self {
+            PhysicalPlan::SeqScan { table, columns } => Box::new(BatchScan::new(table, columns)),
+            PhysicalPlan::Filter { input, predicate } => {
+                Box::new(BatchFilter::new(input.into_batch_operator(), predicate))
+            }
+            PhysicalPlan::Project { input, exprs } => {
+                Box::new(BatchProject::new(input.into_batch_operator(), exprs))
+            }
+            PhysicalPlan::HashAggregate {
+                input,
+                group_by,
+                aggregates,
+                phase,
+            } if phase != AggregatePhase::Final => Box::new(BatchHashAggregate::new(
+                input.into_batch_operator(),
+                group_by,
+                aggregates,
+                phase,
+            )),
+            PhysicalPlan::ParallelScan { table, columns, id } => {
+                Box::new(ParallelScan::new(table, columns, id))
+            }
+            PhysicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => Box::new(BatchLimit::new(input.into_batch_operator(), limit, offset)),
+            PhysicalPlan::Append { inputs } => Box::new(BatchAppend::new(
+                inputs
+                    .into_iter()
+                    .map(PhysicalPlan::into_batch_operator)
+                    .collect(),
+            )),
+            plan => Box::new(RowBatches::new(plan.operator(ExecutionMode::Batch))),
+        }
+    }
+
+    fn is_vectorized(&self) -> bool {
+        matches!(
+            self,
+            PhysicalPlan::SeqScan { .. }
+                | PhysicalPlan::ParallelScan { .. }
+                | PhysicalPlan::Filter { .. }
+                | PhysicalPlan::Project { .. }
+                | PhysicalPlan::Limit { .. }
+        ) || matches!(self, PhysicalPlan::HashAggregate { phase, .. } if *phase != AggregatePhase::Final)
+            || matches!(self, PhysicalPlan::Append { inputs } if inputs.iter().all(Self::is_vectorized))
+    }
+
+    /// The inputs of the node.
+    pub fn inputs(&self) -> Vec<&PhysicalPlan> {
+        match self {
+            PhysicalPlan::Filter { input, .. }
+            | PhysicalPlan::Project { input, .. }
+            | PhysicalPlan::HashAggregate { input, .. }
+            | PhysicalPlan::Sort { input, .. }
+            | PhysicalPlan::Window { input, .. }
+            | PhysicalPlan::Limit { input, .. }
+            | PhysicalPlan::Insert { input, .. }
+            | PhysicalPlan::Update { input, .. }
+            | PhysicalPlan::Delete { input, .. }
+            | PhysicalPlan::Repartition { input, .. }
+            | PhysicalPlan::Gather { input, .. } => vec![input],
+            PhysicalPlan::NestedLoopJoin { join }
+            | PhysicalPlan::HashJoin { join }
+            | PhysicalPlan::SortMergeJoin { join } => vec![&join.left, &join.right],
+            PhysicalPlan::Append { inputs } => inputs.iter().collect(),
+            PhysicalPlan::SeqScan { .. }
+            | PhysicalPlan::IndexScan { .. }
+            | PhysicalPlan::Values { .. }
+            | PhysicalPlan::FunctionScan { .. }
+            | PhysicalPlan::ParallelScan { .. } => vec![],
+        }
+    }
+
+    /// The node with its inputs replaced by `f` of them.
+    pub fn map_inputs(self, mut f: impl FnMut(PhysicalPlan) -> PhysicalPlan) -> PhysicalPlan {
+        let mut map = |input: Box<PhysicalPlan>| Box::new(f(*input));
+        match self {
+            PhysicalPlan::Filter { input, predicate } => PhysicalPlan::Filter {
+                input: map(input),
+                predicate,
+            },
+            PhysicalPlan::Project { input, exprs } => PhysicalPlan::Project {
+                input: map(input),
+                exprs,
+            },
+            PhysicalPlan::HashAggregate {
+                input,
+                group_by,
+                aggregates,
+                phase,
+            } => PhysicalPlan::HashAggregate {
+                input: map(input),
+                group_by,
+                aggregates,
+                phase,
+            },
+            PhysicalPlan::Sort { input, order_by } => PhysicalPlan::Sort {
+                input: map(input),
+                order_by,
+            },
+            PhysicalPlan::Window { input, functions } => PhysicalPlan::Window {
+                input: map(input),
+                functions,
+            },
+            PhysicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => PhysicalPlan::Limit {
+                input: map(input),
+                limit,
+                offset,
+            },
+            PhysicalPlan::Insert {
+                input,
+                target,
+                columns,
+                defaults,
+                on_conflict,
+            } => PhysicalPlan::Insert {
+                input: map(input),
+                target,
+                columns,
+                defaults,
+                on_conflict,
+            },
+            PhysicalPlan::Update {
+                input,
+                target,
+                row_id,
+                assignments,
+            } => PhysicalPlan::Update {
+                input: map(input),
+                target,
+                row_id,
+                assignments,
+            },
+            PhysicalPlan::Delete {
+                input,
+                target,
+                row_id,
+            } => PhysicalPlan::Delete {
+                input: map(input),
+                target,
+                row_id,
+            },
+            PhysicalPlan::Repartition { input, keys, id } => PhysicalPlan::Repartition {
+                input: map(input),
+                keys,
+                id,
+            },
+            PhysicalPlan::Gather { input, workers } => PhysicalPlan::Gather {
+                input: map(input),
+                workers,
+            },
+            PhysicalPlan::NestedLoopJoin { join } => PhysicalPlan::NestedLoopJoin {
+                join: join.map_inputs(map),
+            },
+            PhysicalPlan::HashJoin { join } => PhysicalPlan::HashJoin {
+                join: join.map_inputs(map),
+            },
+            PhysicalPlan::SortMergeJoin { join } => PhysicalPlan::SortMergeJoin {
+                join: join.map_inputs(map),
+            },
+            PhysicalPlan::Append { inputs } => PhysicalPlan::Append {
+                inputs: inputs.into_iter().map(f).collect(),
+            },
+            plan @ (PhysicalPlan::SeqScan { .. }
+            | PhysicalPlan::IndexScan { .. }
+            | PhysicalPlan::Values { .. }
+            | PhysicalPlan::FunctionScan { .. }
+            | PhysicalPlan::ParallelScan { .. }) => plan,
+        }
+    }
+}
+
+impl JoinSpec {
+    fn map_inputs(self, mut map: impl FnMut(Box<PhysicalPlan>) -> Box<PhysicalPlan>) -> Self {
+        JoinSpec {
+            left: map(self.left),
+            right: map(self.right),
+            ..self
+        }
+    }
+}
+
+fn compile_all(
+    exprs: &[BoundExpr],
+    schema: &[PlanColumn],
+) -> Result<Vec<ScalarExpr>, ExecutionError> {
+    exprs
+        .iter()
+        .map(|expr| ScalarExpr::compile(expr, schema))
+        .collect()
+}
+
+fn compile_items(
+    items: &[ProjectionItem],
+    schema: &[PlanColumn],
+) -> Result<Vec<ScalarExpr>, ExecutionError> {
+    items
+        .iter()
+        .map(|item| ScalarExpr::compile(&item.expr, schema))
+        .collect()
+}
+
+fn scan_columns(table: &Table, schema: &[PlanColumn]) -> Result<Vec<ScanColumn>, ExecutionError> {
+    schema
+        .iter()
+        .map(|column| match &column.expr {
+            BoundExpr::Column(column) if column.column_id == ROW_ID_COLUMN_ID => {
+                Ok(ScanColumn::RowId)
+            }
+            BoundExpr::Column(column) => table
+                .columns
+                .iter()
+                .position(|candidate| candidate.id == column.column_id)
+                .map(ScanColumn::Column)
+                .ok_or_else(|| {
+                    ExecutionError::Unsupported(format!("column \"{}\" of no table", column.name))
+                }),
+            expr => Err(ExecutionError::Unsupported(format!("scan of {:?}", expr))),
+        })
+        .collect()
+}
+
+/// A bound of an index range, its constant compiled.
+fn compile_bound(bound: &Bound<BoundExpr>) -> Result<Bound<ScalarExpr>, ExecutionError> {
+    Ok(match bound {
+        Bound::Included(expr) => Bound::Included(ScalarExpr::compile(expr, &[])?),
+        Bound::Excluded(expr) => Bound::Excluded(ScalarExpr::compile(expr, &[])?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
+/// `left = right` with one side on each input, as (left key, right key).
+fn equi_key(
+    conjunct: &BoundExpr,
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+) -> Option<(BoundExpr, BoundExpr)> {
+    let BoundExpr::Binary {
+        left: first,
+        operator: BinaryOperator::Equal,
+        right: second,
+        ..
+    } = conjunct
+    else {
+        return None;
+    };
+    let on = |expr: &BoundExpr, plan: &LogicalPlan| {
+        !referenced_columns(expr).is_empty() && evaluable(plan, expr)
+    };
+    if on(first, left) && on(second, right) {
+        Some(((**first).clone(), (**second).clone()))
+    } else if on(first, right) && on(second, left) {
+        Some(((**second).clone(), (**first).clone()))
+    } else {
+        None
+    }
+}
+
+fn aggregate(expr: &BoundExpr, schema: &[PlanColumn]) -> Result<Aggregate, ExecutionError> {
+    let BoundExpr::Function(function) = expr else {
+        return Err(ExecutionError::Unsupported(format!("aggregate {:?}", expr)));
+    };
+    let Some(FunctionRef::Aggregate(aggregate)) = &function.function else {
+        return Err(ExecutionError::Unsupported(format!(
+            "aggregate {}",
+            function.name
+        )));
+    };
+    Ok(Aggregate {
+        function: aggregate.clone(),
+        arguments: aggregate_arguments(function, schema)?,
+        distinct: function.distinct,
+    })
+}
+
+/// Call of a window function or of an aggregate over a window. `DISTINCT` and ordered-set
+/// aggregates aren't computed over windows.
+fn window_call(expr: &BoundExpr, schema: &[PlanColumn]) -> Result<WindowCall, ExecutionError> {
+    let BoundExpr::Function(
+        function @ BoundFunction {
+            over: Some(window), ..
+        },
+    ) = expr
+    else {
+        return Err(ExecutionError::Unsupported(format!(
+            "window function {:?}",
+            expr
+        )));
+    };
+    let kind = match &function.function {
+        Some(FunctionRef::Window(window)) => WindowFunctionKind::Window(window.clone()),
+        Some(FunctionRef::Aggregate(aggregate))
+            if !function.distinct && !aggregate.ordered_set() =>
+        {
+            WindowFunctionKind::Aggregate(aggregate.clone())
+        }
+        _ => {
+            return Err(ExecutionError::Unsupported(format!(
+                "window function {}",
+                function.name
+            )))
+        }
+    };
+    let edge = |edge: &BoundFrameBound| -> Result<FrameEdge, ExecutionError> {
+        Ok(match edge {
+            BoundFrameBound::UnboundedPreceding => FrameEdge::UnboundedPreceding,
+            BoundFrameBound::Preceding(offset) => {
+                FrameEdge::Preceding(ScalarExpr::compile(offset, &[])?)
+            }
+            BoundFrameBound::CurrentRow => FrameEdge::CurrentRow,
+            BoundFrameBound::Following(offset) => {
+                FrameEdge::Following(ScalarExpr::compile(offset, &[])?)
+            }
+            BoundFrameBound::UnboundedFollowing => FrameEdge::UnboundedFollowing,
+        })
+    };
+    let frame = match &window.frame {
+        Some(frame) => Some(Frame {
+            units: frame.units,
+            start: edge(&frame.start)?,
+            end: edge(&frame.end)?,
+        }),
+        None => None,
+    };
+    Ok(WindowCall {
+        function: kind,
+        arguments: aggregate_arguments(function, schema)?,
+        partition_by: compile_all(&window.partition_by, schema)?,
+        order_by: window
+            .order_by
+            .iter()
+            .map(|(expr, order)| Ok((ScalarExpr::compile(expr, schema)?, *order)))
+            .collect::<Result<_, ExecutionError>>()?,
+        frame,
+    })
+}
+
+/// Arguments of an aggregate call, those of an ordered-set aggregate are followed by its
+/// ordering expression and whether the ordering is descending.
+fn aggregate_arguments(
+    function: &BoundFunction,
+    schema: &[PlanColumn],
+) -> Result<Vec<ScalarExpr>, ExecutionError> {
+    let mut arguments = vec![];
+    for argument in &function.arguments {
+        if *argument != BoundExpr::Wildcard {
+            arguments.push(ScalarExpr::compile(argument, schema)?);
+        }
+    }
+    for (expr, order) in &function.within_group {
+        arguments.push(ScalarExpr::compile(expr, schema)?);
+        arguments.push(ScalarExpr::Literal(Value::Boolean(order.descending)));
+    }
+    Ok(arguments)
+}
+
+fn position(table: &Table, column: &Column) -> usize {
+    table
+        .columns
+        .iter()
+        .position(|candidate| candidate.id == column.id)
+        .expect("column of the table")
+}
+
+/// The default of the column as an expression, converted to the type of the column.
+fn default_value(column: &Column, catalog: &dyn Catalog) -> Result<ScalarExpr, ExecutionError> {
+    let Some(default) = &column.default else {
+        return Ok(ScalarExpr::Literal(crate::value::Value::Null));
+    };
+    let invalid = || ExecutionError::Unsupported(format!("default of column \"{}\"", column.name));
+    let query = Queries::parse(&format!("SELECT {}", default))
+        .map_err(|_| invalid())?
+        .queries
+        .pop()
+        .ok_or_else(invalid)?;
+    let BoundQuery::Select(select) = analyse(&query, catalog).map_err(|_| invalid())? else {
+        return Err(invalid());
+    };
+    let expr = BoundExpr::Cast {
+        expr: Box::new(select.projection[0].expr.clone()),
+        data_type: column.data_type,
+    };
+    ScalarExpr::compile(&expr, &[])
+}
+
+fn row_id_position(table: &BoundRelation, schema: &[PlanColumn]) -> Result<usize, ExecutionError> {
+    let row_id = BoundExpr::Column(crate::planner::row_id_column(table));
+    schema
+        .iter()
+        .position(|column| column.expr == row_id)
+        .ok_or_else(|| {
+            ExecutionError::Unsupported("changing rows without their row id".to_string())
+        })
+}
+
+/// Columns of the table as the schema of a stored row.
+fn table_schema(table: &BoundRelation) -> Vec<PlanColumn> {
+    table
+        .table
+        .columns
+        .iter()
+        .map(|column| PlanColumn::new(BoundExpr::Column(table.column(column)), column.name.clone()))
+        .collect()
+}
+
+fn target(
+    table: &BoundRelation,
+    returning: &[ProjectionItem],
+    input: &[PlanColumn],
+    catalog: &dyn Catalog,
+) -> Result<Target, ExecutionError> {
+    let schema: Vec<_> = table_schema(table)
+        .into_iter()
+        .chain(input.iter().cloned())
+        .collect();
+    Ok(Target {
+        table: table.table.clone(),
+        indexes: catalog
+            .indexes(table.table.id)
+            .into_iter()
+            .cloned()
+            .collect(),
+        returning: match returning.is_empty() {
+            true => None,
+            false => Some(compile_items(returning, &schema)?),
+        },
+    })
+}
+
+/// Assignments as the positions of the changed columns in the table row and their values,
+/// `(a, b) = (1, 2)` assigns every column its own value.
+fn compile_assignments(
+    table: &Table,
+    assignments: &[BoundAssignment],
+    schema: &[PlanColumn],
+) -> Result<Vec<(usize, ScalarExpr)>, ExecutionError> {
+    let mut compiled = vec![];
+    for assignment in assignments {
+        let values = match (&assignment.value, assignment.columns.len()) {
+            (_, 1) => vec![&assignment.value],
+            (BoundExpr::Tuple(values), _) => values.iter().collect(),
+            _ => {
+                return Err(ExecutionError::Unsupported(
+                    "assigning a subquery to several columns".to_string(),
+                ))
+            }
+        };
+        for (column, value) in assignment.columns.iter().zip(values) {
+            let value = BoundExpr::Cast {
+                expr: Box::new(value.clone()),
+                data_type: column.data_type,
+            };
+            compiled.push((
+                position(table, column),
+                ScalarExpr::compile(&value, schema)?,
+            ));
+        }
+    }
+    Ok(compiled)
+}
+
+fn conflict(
+    table: &BoundRelation,
+    on_conflict: &crate::analyser::bound::BoundOnConflict,
+    catalog: &dyn Catalog,
+) -> Result<Conflict, ExecutionError> {
+    let indexes: Vec<Index> = catalog
+        .indexes(table.table.id)
+        .into_iter()
+        .filter(|index| {
+            index.unique
+                && (on_conflict.columns.is_empty()
+                    || (index.columns.len() == on_conflict.columns.len()
+                        && on_conflict
+                            .columns
+                            .iter()
+                            .all(|column| index.columns.contains(&column.id))))
+        })
+        .cloned()
+        .collect();
+    if indexes.is_empty() && !on_conflict.columns.is_empty() {
+        return Err(ExecutionError::Unsupported(
+            "ON CONFLICT without a unique index on the columns".to_string(),
+        ));
+    }
+    let update = match &on_conflict.action {
+        BoundConflictAction::DoNothing => None,
+        BoundConflictAction::DoUpdate {
+            excluded,
+            assignments,
+            filter,
+        } => {
+            let schema: Vec<_> = table_schema(table)
+                .into_iter()
+                .chain(table_schema(excluded))
+                .collect();
+            Some(ConflictUpdate {
+                assignments: compile_assignments(&table.table, assignments, &schema)?,
+                filter: match filter {
+                    Some(filter) => Some(ScalarExpr::compile(filter, &schema)?),
+                    None => None,
+                },
+            })
+        }
+    };
+    Ok(Conflict { indexes, update })
+}
@@ -0,0 +1,462 @@
+//! Memory budget of a query and the temporary files its operators spill rows to once
+//! they would exceed it.
+//!
+//! The operators reserve the memory of the rows they hold from the budget shared by the
+//! query. The files of a query are in a directory of its own, created with the first
+//! file. A file is removed when it is dropped and the directory with the `QueryMemory`,
+//! so nothing is left behind when a query fails or is abandoned before all its rows are
+//! read.
+
+use crate::execution::table::{decode_row, encode_row};
+use crate::execution::{ExecutionError, Row};
+use crate::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{env, process};
+
+/// Files a partitioning operator distributes its rows over.
+pub(crate) const PARTITIONS: usize = 8;
+
+/// Partitions of this level aren't partitioned again, their rows are held in memory
+/// whatever the budget, e.g. when they all have the same key.
+pub(crate) const MAX_LEVEL: usize = 4;
+
+/// Number of query directories created by the process, for their names.
+static DIRECTORIES: AtomicUsize = AtomicUsize::new(0);
+
+struct MemoryPool {
+    budget: usize,
+    used: AtomicUsize,
+}
+
+/// The memory budget of a query and the directory of its spill files.
+pub struct QueryMemory {
+    pool: Arc<MemoryPool>,
+    temp_directory: PathBuf,
+    directory: Option<PathBuf>,
+    files: usize,
+}
+
+impl QueryMemory {
+    /// Budget of `budget` bytes, the spill files are created in the temporary directory.
+    pub fn new(budget: usize, temp_directory: impl Into<PathBuf>) -> Self {
+        QueryMemory {
+            pool: Arc::new(MemoryPool {
+                budget,
+                used: AtomicUsize::new(0),
+            }),
+            temp_directory: temp_directory.into(),
+            directory: None,
+            files: 0,
+        }
+    }
+
+    /// A budget operators never exceed, they don't spill.
+    pub fn unlimited() -> Self {
+        QueryMemory::new(usize::MAX, env::temp_dir())
+    }
+
+    /// Memory of a worker thread of the query, it shares the budget of the query and
+    /// spills to a directory of its own.
+    pub fn worker(&self) -> QueryMemory {
+        QueryMemory {
+            pool: self.pool.clone(),
+            temp_directory: self.temp_directory.clone(),
+            directory: None,
+            files: 0,
+        }
+    }
+
+    /// A reservation of no memory yet.
+    pub fn reservation(&self) -> MemoryReservation {
+        MemoryReservation {
+            pool: self.pool.clone(),
+            size: 0,
+        }
+    }
+
+    /// Bytes reserved by the operators of the query.
+    pub fn used(&self) -> usize {
+        self.pool.used.load(Ordering::Relaxed)
+    }
+
+    /// Number of files spilled to so far.
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// A new empty file in the directory of the query.
+    pub fn spill_file(&mut self) -> Result<SpillFile, ExecutionError> {
+        let directory = match &self.directory {
+            Some(directory) => directory.clone(),
+            None => {
+                let directory = self.temp_directory.join(format!(
+                    "tage-{}-{}",
+                    process::id(),
+                    DIRECTORIES.fetch_add(1, Ordering::Relaxed)
+                ));
+                fs::create_dir_all(&directory)?;
+                self.directory = Some(directory.clone());
+                directory
+            }
+        };
+        self.files += 1;
+        let path = directory.join(format!("{}.spill", self.files));
+        Ok(SpillFile {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            rows: 0,
+        })
+    }
+}
+
+impl Drop for QueryMemory {
+    fn drop(&mut self) {
+        if let Some(directory) = &self.directory {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
+
+/// Memory an operator holds, returned to the budget when the reservation is dropped.
+pub struct MemoryReservation {
+    pool: Arc<MemoryPool>,
+    size: usize,
+}
+
+impl MemoryReservation {
+    /// Reserves more memory, `false` without reserving any if the budget doesn't allow it.
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let pool = &self.pool;
+        let reserved = pool
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|used| *used <= pool.budget)
+            })
+            .is_ok();
+        if reserved {
+            self.size += bytes;
+        }
+        reserved
+    }
+
+    /// Reserves more memory even if it exceeds the budget, for rows which can't be spilled.
+    pub fn grow(&mut self, bytes: usize) {
+        self.pool.used.fetch_add(bytes, Ordering::Relaxed);
+        self.size += bytes;
+    }
+
+    /// Returns all the memory of the reservation to the budget.
+    pub fn free(&mut self) {
+        self.pool.used.fetch_sub(self.size, Ordering::Relaxed);
+        self.size = 0;
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+/// Approximate bytes a row takes in memory.
+pub fn row_size(row: &[Value]) -> usize {
+    size_of::<Row>()
+        + row
+            .iter()
+            .map(|value| match value {
+                Value::String(string) => size_of::<Value>() + string.capacity(),
+                _ => size_of::<Value>(),
+            })
+            .sum::<usize>()
+}
+
+/// Rows written to a temporary file, the file is removed when it is dropped.
+pub struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    rows: usize,
+}
+
+impl SpillFile {
+    pub fn write(&mut self, row: &[Value]) -> Result<(), ExecutionError> {
+        let bytes = encode_row(row);
+        self.writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Number of rows written.
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Reads the rows back in the order they were written.
+    pub fn into_reader(mut self) -> Result<SpillReader, ExecutionError> {
+        self.writer.flush()?;
+        Ok(SpillReader {
+            reader: BufReader::new(File::open(&self.path)?),
+            _file: self,
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub struct SpillReader {
+    reader: BufReader<File>,
+    /// Removes the file once it is read
+    _file: SpillFile,
+}
+
+impl SpillReader {
+    pub fn next_row(&mut self) -> Result<Option<Row>, ExecutionError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let mut length = [0; 4];
+        self.reader.read_exact(&mut length)?;
+        let mut bytes = vec![0; u32::from_be_bytes(length) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        decode_row(&bytes).map(Some)
+    }
+}
+
+/// Files rows are distributed over by the hash of their keys, the rows with the same key
+/// are in the same file. Every level hashes differently, so that a partition which is
+/// still too large is split when it is partitioned again.
+pub(crate) struct Partitions {
+    files: Vec<SpillFile>,
+    level: usize,
+}
+
+impl Partitions {
+    pub(crate) fn new(memory: &mut QueryMemory, level: usize) -> Result<Self, ExecutionError> {
+        Ok(Partitions {
+            files: (0..PARTITIONS)
+                .map(|_| memory.spill_file())
+                .collect::<Result<_, _>>()?,
+            level,
+        })
+    }
+
+    /// Writes the row to the partition of its encoded key.
+    pub(crate) fn write(&mut self, key: &[u8], row: &[Value]) -> Result<(), ExecutionError> {
+        let mut hasher = DefaultHasher::new();
+        (self.level, key).hash(&mut hasher);
+        let partition = hasher.finish() as usize % self.files.len();
+        self.files[partition].write(row)
+    }
+
+    pub(crate) fn level(&self) -> usize {
+        self.level
+    }
+
+    pub(crate) fn into_files(self) -> Vec<SpillFile> {
+        self.files
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+    use crate::execution::expr::ScalarExpr;
+    use crate::execution::operators::aggregate::{Aggregate, AggregatePhase};
+    use crate::execution::pattern::PatternCache;
+    use crate::execution::physical::{JoinSpec, PhysicalPlan};
+    use crate::execution::{execute_in, ExecutionContext, ExecutionMode};
+    use crate::functions::builtins;
+    use crate::parser::Order;
+    use crate::planner::JoinType;
+    use crate::storage::MemoryStore;
+
+    pub(crate) fn temp_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("tage-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    /// Files in the query directories of the temporary directory.
+    pub(crate) fn spilled_files(directory: &std::path::Path) -> usize {
+        fs::read_dir(directory)
+            .into_iter()
+            .flatten()
+            .map(|query| fs::read_dir(query.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    #[test]
+    fn reserves_memory_within_the_budget() {
+        let memory = QueryMemory::new(100, env::temp_dir());
+        let mut first = memory.reservation();
+        let mut second = memory.reservation();
+        assert!(first.try_grow(60));
+        assert!(!second.try_grow(50));
+        assert!(second.try_grow(40));
+        second.grow(10);
+        assert_eq!((memory.used(), second.size()), (110, 50));
+        first.free();
+        assert_eq!(memory.used(), 50);
+        drop(second);
+        assert_eq!(memory.used(), 0);
+        assert!(QueryMemory::unlimited().reservation().try_grow(usize::MAX));
+    }
+
+    #[test]
+    fn spills_rows_to_files() {
+        let directory = temp_directory("spill");
+        let mut memory = QueryMemory::new(0, &directory);
+        let rows = vec![
+            vec![Value::Integer(1), Value::from("one"), Value::Null],
+            vec![Value::Float(2.5), Value::Boolean(false), Value::from("")],
+        ];
+        let mut file = memory.spill_file().unwrap();
+        for row in &rows {
+            file.write(row).unwrap();
+        }
+        assert_eq!(file.len(), 2);
+        let mut reader = file.into_reader().unwrap();
+        assert_eq!(reader.next_row().unwrap().as_ref(), Some(&rows[0]));
+        assert_eq!(reader.next_row().unwrap().as_ref(), Some(&rows[1]));
+        assert_eq!(reader.next_row().unwrap(), None);
+        assert_eq!(spilled_files(&directory), 1);
+        drop(reader);
+        assert_eq!(spilled_files(&directory), 0);
+
+        let mut partitions = Partitions::new(&mut memory, 0).unwrap();
+        for key in 0..100u8 {
+            partitions
+                .write(&[key], &[Value::Integer(key.into())])
+                .unwrap();
+            partitions
+                .write(&[key], &[Value::Integer(key.into())])
+                .unwrap();
+        }
+        let files = partitions.into_files();
+        assert_eq!(files.iter().map(SpillFile::len).sum::<usize>(), 200);
+        assert!(files.iter().all(|file| file.len() < 100));
+        assert_eq!(memory.files(), 1 + PARTITIONS);
+        drop(memory);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    fn values(rows: impl Iterator<Item = Vec<Value>>) -> Box<PhysicalPlan> {
+        Box::new(PhysicalPlan::Values {
+            rows: rows
+                .map(|row| row.into_iter().map(ScalarExpr::Literal).collect())
+                .collect(),
+        })
+    }
+
+    /// Rows of the plan with an unlimited budget, and with a small one and the number of
+    /// files spilled to.
+    fn execute_spilling(
+        plan: PhysicalPlan,
+        mode: ExecutionMode,
+        directory: &std::path::Path,
+    ) -> (Vec<Row>, Vec<Row>, usize) {
+        let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+        let mut context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            start_time: std::time::SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let mut expected = execute_in(plan.clone(), mode, &mut context).unwrap();
+        context.memory = QueryMemory::new(8 * 1024, directory);
+        let mut rows = execute_in(plan, mode, &mut context).unwrap();
+        assert_eq!(context.memory.used(), 0);
+        expected.sort_by_key(|row| format!("{:?}", row));
+        rows.sort_by_key(|row| format!("{:?}", row));
+        (expected, rows, context.memory.files())
+    }
+
+    #[test]
+    fn spills_operators_over_the_budget() {
+        let directory = temp_directory("operators");
+        let numbers =
+            |count: i64| (0..count).map(|n| vec![Value::Integer(n), Value::Integer(n * 7 % 501)]);
+        let sort = PhysicalPlan::Sort {
+            input: values(numbers(3000)),
+            order_by: vec![(ScalarExpr::Column(1), Order::DESC)],
+        };
+        let (expected, rows, files) =
+            execute_spilling(sort.clone(), ExecutionMode::Row, &directory);
+        // more runs than are merged at once
+        assert!(files > 16 + 1, "{} runs", files);
+        assert_eq!(rows, expected);
+
+        let aggregate = PhysicalPlan::HashAggregate {
+            input: values(numbers(3000)),
+            group_by: vec![ScalarExpr::Column(1)],
+            aggregates: vec![
+                Aggregate {
+                    function: builtins().aggregate("count").unwrap().clone(),
+                    arguments: vec![],
+                    distinct: false,
+                },
+                Aggregate {
+                    function: builtins().aggregate("sum").unwrap().clone(),
+                    arguments: vec![ScalarExpr::Column(0)],
+                    distinct: false,
+                },
+                Aggregate {
+                    function: builtins().aggregate("count").unwrap().clone(),
+                    arguments: vec![ScalarExpr::Column(0)],
+                    distinct: true,
+                },
+            ],
+            phase: AggregatePhase::Single,
+        };
+        for mode in [ExecutionMode::Row, ExecutionMode::Batch] {
+            let (expected, rows, files) = execute_spilling(aggregate.clone(), mode, &directory);
+            assert!(files >= PARTITIONS);
+            assert_eq!((rows.len(), rows), (501, expected));
+        }
+
+        for join_type in [JoinType::Inner, JoinType::Full, JoinType::Anti] {
+            let join = PhysicalPlan::HashJoin {
+                join: JoinSpec {
+                    left: values(numbers(800).chain([vec![Value::Integer(-1), Value::Null]])),
+                    right: values(
+                        (0..2000).map(|n| vec![Value::Integer(n % 600), Value::from("right")]),
+                    ),
+                    join_type,
+                    keys: vec![(ScalarExpr::Column(1), ScalarExpr::Column(0))],
+                    condition: None,
+                    left_width: 2,
+                    right_width: 2,
+                },
+            };
+            let (expected, rows, files) = execute_spilling(join, ExecutionMode::Row, &directory);
+            assert!(files >= 2 * PARTITIONS);
+            assert_eq!(rows, expected);
+        }
+        assert_eq!(spilled_files(&directory), 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,588 @@
+//! Rows of the tables and entries of their indexes in the key-value store.
+//!
+//! A row is stored under `table/<table id>/row/<row id>`, row ids are given out by a
+//! counter of the table and never reused. An index entry is the key
+//! `index/<index id>/<values><row id>` with an empty value. The values are encoded so
+//! that their bytes sort like the values, a range of values is a range of keys.
+
+use crate::catalog::{Index, ObjectId, Table, TableId};
+use crate::execution::{ExecutionError, Row};
+use crate::storage::KvStore;
+use crate::value::{encoding, Decimal, Value};
+use std::collections::VecDeque;
+use std::ops::Bound;
+
+pub type RowId = u64;
+
+fn table_prefix(table: TableId) -> Vec<u8> {
+    format!("table/{:010}/", table.0).into_bytes()
+}
+
+fn row_key(table: TableId, row_id: RowId) -> Vec<u8> {
+    let mut key = table_prefix(table);
+    key.extend(b"row/");
+    key.extend(row_id.to_be_bytes());
+    key
+}
+
+fn row_id_of(key: &[u8]) -> Result<RowId, ExecutionError> {
+    key.len()
+        .checked_sub(8)
+        .and_then(|start| key[start..].try_into().ok())
+        .map(RowId::from_be_bytes)
+        .ok_or_else(|| ExecutionError::Corrupted("key without a row id".to_string()))
+}
+
+fn counter_key(table: TableId) -> Vec<u8> {
+    let mut key = table_prefix(table);
+    key.extend(b"next_row_id");
+    key
+}
+
+/// Row id the next row of the table gets, the stored rows have smaller ones.
+pub fn next_row_id(store: &dyn KvStore, table: &Table) -> Result<RowId, ExecutionError> {
+    Ok(store
+        .get_by_key(&counter_key(table.id))?
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(1, RowId::from_be_bytes))
+}
+
+/// Stores a new row of the table, the values are in the order of its columns.
+pub fn insert_row(
+    store: &mut dyn KvStore,
+    table: &Table,
+    row: &[Value],
+) -> Result<RowId, ExecutionError> {
+    let row_id = next_row_id(store, table)?;
+    store.put(&counter_key(table.id), &(row_id + 1).to_be_bytes())?;
+    write_row(store, table, row_id, row)?;
+    Ok(row_id)
+}
+
+/// Replaces the values of a stored row.
+pub fn write_row(
+    store: &mut dyn KvStore,
+    table: &Table,
+    row_id: RowId,
+    row: &[Value],
+) -> Result<(), ExecutionError> {
+    Ok(store.put(&row_key(table.id, row_id), &encode_row(row))?)
+}
+
+pub fn read_row(
+    store: &dyn KvStore,
+    table: &Table,
+    row_id: RowId,
+) -> Result<Option<Row>, ExecutionError> {
+    store
+        .get_by_key(&row_key(table.id, row_id))?
+        .map(|bytes| decode_row(&bytes))
+        .transpose()
+}
+
+pub fn delete_row(
+    store: &mut dyn KvStore,
+    table: &Table,
+    row_id: RowId,
+) -> Result<(), ExecutionError> {
+    Ok(store.delete(&row_key(table.id, row_id))?)
+}
+
+/// All rows of the table in the order of their row ids.
+pub fn scan_rows(store: &dyn KvStore, table: &Table) -> Result<Vec<(RowId, Row)>, ExecutionError> {
+    let mut start = table_prefix(table.id);
+    start.extend(b"row/");
+    store
+        .scan(&start, &prefix_end(&start))
+        .map(|entry| {
+            let (key, value) = entry?;
+            Ok((row_id_of(&key)?, decode_row(&value)?))
+        })
+        .collect()
+}
+
+/// Rows of the table with `start <= row id < end` in the order of their row ids.
+pub fn scan_row_range(
+    store: &dyn KvStore,
+    table: &Table,
+    start: RowId,
+    end: RowId,
+) -> Result<Vec<(RowId, Row)>, ExecutionError> {
+    store
+        .scan(&row_key(table.id, start), &row_key(table.id, end))
+        .map(|entry| {
+            let (key, value) = entry?;
+            Ok((row_id_of(&key)?, decode_row(&value)?))
+        })
+        .collect()
+}
+
+/// Rows a scan reads from the store at a time.
+const SCAN_CHUNK: usize = 1024;
+
+/// All rows of a table in the order of their row ids, read from the store a chunk at a
+/// time as they are asked for. The scan ends at the rows the table had when it started,
+/// a statement doesn't read the rows it inserts.
+#[derive(Debug)]
+pub struct RowScan {
+    table: TableId,
+    next: RowId,
+    end: RowId,
+    rows: VecDeque<(RowId, Row)>,
+}
+
+impl RowScan {
+    pub fn new(store: &dyn KvStore, table: &Table) -> Result<Self, ExecutionError> {
+        Ok(RowScan {
+            table: table.id,
+            next: 1,
+            end: next_row_id(store, table)?,
+            rows: VecDeque::new(),
+        })
+    }
+
+    pub fn next(&mut self, store: &dyn KvStore) -> Result<Option<(RowId, Row)>, ExecutionError> {
+        if self.rows.is_empty() && self.next < self.end {
+            let entries = store.scan(
+                &row_key(self.table, self.next),
+                &row_key(self.table, self.end),
+            );
+            for entry in entries.take(SCAN_CHUNK) {
+                let (key, value) = entry?;
+                self.rows.push_back((row_id_of(&key)?, decode_row(&value)?));
+            }
+            self.next = match self.rows.back() {
+                Some((row_id, _)) if self.rows.len() == SCAN_CHUNK => row_id + 1,
+                _ => self.end,
+            };
+        }
+        Ok(self.rows.pop_front())
+    }
+}
+
+/// Removes the rows and the row id counter of a dropped table.
+pub fn drop_table(store: &mut dyn KvStore, table: TableId) -> Result<(), ExecutionError> {
+    let prefix = table_prefix(table);
+    delete_range(store, &prefix, &prefix_end(&prefix))
+}
+
+/// Deletes the entries with `start <= key < end`.
+fn delete_range(store: &mut dyn KvStore, start: &[u8], end: &[u8]) -> Result<(), ExecutionError> {
+    // the keys are read before the first is deleted, the scan borrows the store
+    let keys = store
+        .scan(start, end)
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<Result<Vec<_>, _>>()?;
+    for key in keys {
+        store.delete(&key)?;
+    }
+    Ok(())
+}
+
+/// Bytes of the values of a row, as they are stored and spilled.
+pub(crate) fn encode_row(row: &[Value]) -> Vec<u8> {
+    encoding::encode_row(row)
+}
+
+pub(crate) fn decode_row(bytes: &[u8]) -> Result<Row, ExecutionError> {
+    Ok(encoding::decode_row(bytes)?)
+}
+
+fn index_prefix(index: ObjectId) -> Vec<u8> {
+    format!("index/{:010}/", index.0).into_bytes()
+}
+
+/// Values of the indexed columns of a row, in the order of the columns of the index.
+pub fn index_values(index: &Index, table: &Table, row: &[Value]) -> Vec<Value> {
+    index
+        .columns
+        .iter()
+        .map(|id| {
+            let position = table
+                .columns
+                .iter()
+                .position(|column| column.id == *id)
+                .expect("indexed column of the table");
+            row[position].clone()
+        })
+        .collect()
+}
+
+fn index_key(index: &Index, values: &[Value], row_id: RowId) -> Vec<u8> {
+    let mut key = index_prefix(index.id);
+    key.extend(encode_key(values));
+    key.extend(row_id.to_be_bytes());
+    key
+}
+
+pub fn insert_index_entry(
+    store: &mut dyn KvStore,
+    index: &Index,
+    values: &[Value],
+    row_id: RowId,
+) -> Result<(), ExecutionError> {
+    Ok(store.put(&index_key(index, values, row_id), &[])?)
+}
+
+pub fn delete_index_entry(
+    store: &mut dyn KvStore,
+    index: &Index,
+    values: &[Value],
+    row_id: RowId,
+) -> Result<(), ExecutionError> {
+    Ok(store.delete(&index_key(index, values, row_id))?)
+}
+
+/// Rows whose indexed values start with the values, in the order of the index.
+pub fn index_lookup(
+    store: &dyn KvStore,
+    index: &Index,
+    values: &[Value],
+) -> Result<Vec<RowId>, ExecutionError> {
+    let mut start = index_prefix(index.id);
+    start.extend(encode_key(values));
+    index_entries(store, &start, &prefix_end(&start))
+}
+
+/// Rows whose first indexed value is within the bounds, `NULL`s are never within.
+pub fn index_range(
+    store: &dyn KvStore,
+    index: &Index,
+    low: Bound<&Value>,
+    high: Bound<&Value>,
+) -> Result<Vec<RowId>, ExecutionError> {
+    let prefix = index_prefix(index.id);
+    let encoded = |value: &Value| {
+        let mut key = prefix.clone();
+        key.extend(encode_key(std::slice::from_ref(value)));
+        key
+    };
+    let start = match low {
+        Bound::Included(value) => encoded(value),
+        Bound::Excluded(value) => prefix_end(&encoded(value)),
+        Bound::Unbounded => {
+            let mut start = prefix.clone();
+            start.push(NULL_TAG + 1);
+            start
+        }
+    };
+    let end = match high {
+        Bound::Included(value) => prefix_end(&encoded(value)),
+        Bound::Excluded(value) => encoded(value),
+        Bound::Unbounded => prefix_end(&prefix),
+    };
+    index_entries(store, &start, &end)
+}
+
+/// Removes the entries of a dropped index.
+pub fn drop_index(store: &mut dyn KvStore, index: ObjectId) -> Result<(), ExecutionError> {
+    let prefix = index_prefix(index);
+    delete_range(store, &prefix, &prefix_end(&prefix))
+}
+
+fn index_entries(
+    store: &dyn KvStore,
+    start: &[u8],
+    end: &[u8],
+) -> Result<Vec<RowId>, ExecutionError> {
+    store
+        .scan(start, end)
+        .map(|entry| row_id_of(&entry?.0))
+        .collect()
+}
+
+const NULL_TAG: u8 = 0;
+const BOOLEAN_TAG: u8 = 1;
+const INTEGER_TAG: u8 = 2;
+const FLOAT_TAG: u8 = 3;
+const STRING_TAG: u8 = 4;
+const DECIMAL_TAG: u8 = 5;
+const DATE_TAG: u8 = 6;
+const TIME_TAG: u8 = 7;
+const TIMESTAMP_TAG: u8 = 8;
+const TIMESTAMPTZ_TAG: u8 = 9;
+const INTERVAL_TAG: u8 = 10;
+
+/// Bytes which sort like the values of a kind, `NULL` first, and which are equal for
+/// equal values, e.g. `1.5` and `1.50`. No encoded value is the prefix of another, so
+/// the values of a prefix of the columns select a range of keys.
+pub fn encode_key(values: &[Value]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for value in values {
+        match value {
+            Value::Null => bytes.push(NULL_TAG),
+            Value::Boolean(value) => bytes.extend([BOOLEAN_TAG, *value as u8]),
+            Value::Integer(value) => {
+                bytes.push(INTEGER_TAG);
+                bytes.extend((*value as u64 ^ 1 << 63).to_be_bytes());
+            }
+            Value::Float(value) => {
+                // -0 is 0 and there is one NaN, as they are equal
+                let value = match *value {
+                    0.0 => 0.0,
+                    value if value.is_nan() => f64::NAN,
+                    value => value,
+                };
+                // positive numbers get the sign bit, the bits of negative ones are flipped
+                let bits = value.to_bits();
+                let bits = if bits >> 63 == 1 {
+                    !bits
+                } else {
+                    bits | 1 << 63
+                };
+                bytes.push(FLOAT_TAG);
+                bytes.extend(bits.to_be_bytes());
+            }
+            Value::String(value) => {
+                bytes.push(STRING_TAG);
+                for byte in value.bytes() {
+                    match byte {
+                        0 => bytes.extend([0, 0xFF]),
+                        byte => bytes.push(byte),
+                    }
+                }
+                bytes.extend([0, 0]);
+            }
+            Value::Decimal(value) => {
+                bytes.push(DECIMAL_TAG);
+                encode_decimal_key(&mut bytes, value);
+            }
+            Value::Date(days) => {
+                bytes.push(DATE_TAG);
+                bytes.extend((*days as u32 ^ 1 << 31).to_be_bytes());
+            }
+            Value::Time(micros) | Value::Timestamp(micros) | Value::TimestampTz(micros) => {
+                bytes.push(match value {
+                    Value::Time(_) => TIME_TAG,
+                    Value::Timestamp(_) => TIMESTAMP_TAG,
+                    _ => TIMESTAMPTZ_TAG,
+                });
+                bytes.extend((*micros as u64 ^ 1 << 63).to_be_bytes());
+            }
+            Value::Interval(interval) => {
+                bytes.push(INTERVAL_TAG);
+                bytes.extend((interval.span() as u128 ^ 1 << 127).to_be_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// A sign byte, the position of the first significant digit and the significant
+/// digits, which end with a byte below the digits. The position and the digits of a
+/// negative number are flipped, it is greater the smaller they are.
+fn encode_decimal_key(bytes: &mut Vec<u8>, value: &Decimal) {
+    let digits = value.digits();
+    if digits.is_empty() {
+        bytes.push(1);
+        return;
+    }
+    let flip = |byte: u8| if value.is_negative() { !byte } else { byte };
+    bytes.push(if value.is_negative() { 0 } else { 2 });
+    // e.g. 2 for 12.5 and -1 for 0.05
+    let position = digits.len() as i32 - value.scale() as i32;
+    bytes.extend((position as u32 ^ 1 << 31).to_be_bytes().map(flip));
+    let zeros = digits.iter().take_while(|digit| **digit == 0).count();
+    bytes.extend(digits[zeros..].iter().rev().map(|digit| flip(digit + 1)));
+    bytes.push(flip(0));
+}
+
+/// The first key after all keys which start with the prefix.
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xFF {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // only 0xFF bytes, there is no key after them
+    vec![0xFF; prefix.len() + 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Column, ColumnId};
+    use crate::storage::MemoryStore;
+    use crate::value::DataType;
+
+    fn table() -> Table {
+        Table {
+            id: TableId(20000),
+            schema_name: "public".to_string(),
+            name: "t".to_string(),
+            columns: ["a", "b"]
+                .into_iter()
+                .enumerate()
+                .map(|(position, name)| Column {
+                    id: ColumnId(position as u32 + 1),
+                    name: name.to_string(),
+                    data_type: DataType::Integer,
+                    nullable: true,
+                    default: None,
+                })
+                .collect(),
+            constraints: vec![],
+        }
+    }
+
+    #[test]
+    fn stores_rows() {
+        let mut store = MemoryStore::new();
+        let table = table();
+        let first = insert_row(&mut store, &table, &[Value::Integer(1), Value::Null]).unwrap();
+        let second = insert_row(&mut store, &table, &[Value::Integer(2), "x".into()]).unwrap();
+        assert_eq!((first, second), (1, 2));
+        write_row(
+            &mut store,
+            &table,
+            first,
+            &[Value::Integer(3), Value::Float(0.5)],
+        )
+        .unwrap();
+        delete_row(&mut store, &table, second).unwrap();
+        assert_eq!(
+            scan_rows(&store, &table).unwrap(),
+            vec![(1, vec![Value::Integer(3), Value::Float(0.5)])]
+        );
+        assert_eq!(read_row(&store, &table, second).unwrap(), None);
+        assert_eq!(insert_row(&mut store, &table, &[]), Ok(3));
+        drop_table(&mut store, table.id).unwrap();
+        assert_eq!(scan_rows(&store, &table).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn scans_rows_a_chunk_at_a_time() {
+        let mut store = MemoryStore::new();
+        let table = table();
+        for value in 0..2500 {
+            insert_row(&mut store, &table, &[Value::Integer(value)]).unwrap();
+        }
+        delete_row(&mut store, &table, 1025).unwrap();
+        let mut rows = RowScan::new(&store, &table).unwrap();
+        let mut row_ids = vec![];
+        while let Some((row_id, row)) = rows.next(&store).unwrap() {
+            assert_eq!(row, [Value::Integer(row_id as i64 - 1)]);
+            row_ids.push(row_id);
+            // the rows of a scan are read as it goes, a new row is left out
+            if row_id == 10 {
+                insert_row(&mut store, &table, &[Value::Integer(-1)]).unwrap();
+                delete_row(&mut store, &table, 2000).unwrap();
+            }
+        }
+        let expected: Vec<RowId> = (1..=2500).filter(|id| ![1025, 2000].contains(id)).collect();
+        assert_eq!(row_ids, expected);
+        assert_eq!(rows.next(&store), Ok(None));
+    }
+
+    #[test]
+    fn keys_sort_like_values() {
+        let values = [
+            Value::Null,
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::Integer(i64::MIN),
+            Value::Integer(-1),
+            Value::Integer(0),
+            Value::Integer(7),
+            Value::Float(f64::NEG_INFINITY),
+            Value::Float(-2.5),
+            Value::Float(0.0),
+            Value::Float(1e300),
+            Value::String("".to_string()),
+            Value::String("a\0b".to_string()),
+            Value::String("ab".to_string()),
+            Value::String("b".to_string()),
+            Value::Decimal(Decimal::parse("-100").unwrap()),
+            Value::Decimal(Decimal::parse("-12.5").unwrap()),
+            Value::Decimal(Decimal::parse("-12").unwrap()),
+            Value::Decimal(Decimal::parse("-0.05").unwrap()),
+            Value::Decimal(Decimal::zero()),
+            Value::Decimal(Decimal::parse("0.05").unwrap()),
+            Value::Decimal(Decimal::parse("12").unwrap()),
+            Value::Decimal(Decimal::parse("12.5").unwrap()),
+            Value::Decimal(Decimal::parse("100").unwrap()),
+            Value::Date(-1),
+            Value::Date(0),
+            Value::Time(5),
+            Value::Timestamp(-5),
+            Value::TimestampTz(-5),
+        ];
+        for pair in values.windows(2) {
+            assert!(
+                encode_key(&pair[..1]) < encode_key(&pair[1..]),
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        assert!(encode_key(&["a".into(), Value::Integer(9)]) < encode_key(&["ab".into()]));
+        let decimal = |text: &str| Value::Decimal(Decimal::parse(text).unwrap());
+        assert_eq!(
+            encode_key(&[decimal("1.50")]),
+            encode_key(&[decimal("1.5")])
+        );
+        assert_eq!(
+            encode_key(&[Value::Float(-0.0)]),
+            encode_key(&[Value::Float(0.0)])
+        );
+    }
+
+    #[test]
+    fn looks_up_index_ranges() {
+        let mut store = MemoryStore::new();
+        let table = table();
+        let index = Index {
+            id: ObjectId(20001),
+            schema_name: "public".to_string(),
+            name: "t_a".to_string(),
+            table: table.id,
+            columns: vec![ColumnId(1)],
+            unique: false,
+        };
+        for (row_id, value) in [
+            (1, Value::Integer(5)),
+            (2, Value::Null),
+            (3, Value::Integer(-3)),
+            (4, Value::Integer(5)),
+        ] {
+            insert_index_entry(&mut store, &index, &[value], row_id).unwrap();
+        }
+        assert_eq!(
+            index_lookup(&store, &index, &[Value::Integer(5)]).unwrap(),
+            vec![1, 4]
+        );
+        assert_eq!(
+            index_lookup(&store, &index, &[Value::Null]).unwrap(),
+            vec![2]
+        );
+        let five = Value::Integer(5);
+        assert_eq!(
+            index_range(&store, &index, Bound::Unbounded, Bound::Excluded(&five)).unwrap(),
+            vec![3]
+        );
+        assert_eq!(
+            index_range(
+                &store,
+                &index,
+                Bound::Included(&Value::Integer(-3)),
+                Bound::Unbounded
+            )
+            .unwrap(),
+            vec![3, 1, 4]
+        );
+        delete_index_entry(&mut store, &index, &[Value::Integer(5)], 1).unwrap();
+        drop_index(&mut store, ObjectId(20002)).unwrap();
+        assert_eq!(
+            index_range(
+                &store,
+                &index,
+                Bound::Excluded(&Value::Integer(-3)),
+                Bound::Included(&five)
+            )
+            .unwrap(),
+            vec![4]
+        );
+    }
+}
@@ -0,0 +1,1055 @@
+//! Aggregate functions. An aggregate folds the rows of a group into a state, a few values
+//! which `finalize` turns into the result. `merge` combines states built from different
+//! rows of the same group, so the workers of a parallel plan aggregate their rows
+//! partially and the partial states are merged once they are gathered.
+//!
+//! The state has as many values as `init` returns, so a partial state takes a fixed
+//! number of columns of a row. Ordered-set aggregates, e.g. `percentile_cont(0.5) WITHIN
+//! GROUP (ORDER BY x)`, keep all values of the group instead and aren't aggregated
+//! partially. Arrays are returned in their text form, e.g. `{1,2,NULL}`.
+
+use crate::execution::batch::{ColumnVector, Vector};
+use crate::execution::expr::{binary, compare, text};
+use crate::execution::ExecutionError;
+use crate::functions::{NullHandling, ResolveError};
+use crate::parser::expr::Operator;
+use crate::value::{DataType, Decimal, Value};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Values an aggregate keeps for a group.
+pub type AggregateState = Vec<Value>;
+
+pub trait AggregateFunction: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Type of the result for arguments of the types, `None` standing for an unknown type.
+    fn return_type(&self, arguments: &[Option<DataType>])
+        -> Result<Option<DataType>, ResolveError>;
+
+    /// Strict aggregates skip the rows with a `NULL` argument, `update` isn't called for
+    /// them.
+    fn null_handling(&self) -> NullHandling {
+        NullHandling::Strict
+    }
+
+    /// Called `f(<direct arguments>) WITHIN GROUP (ORDER BY <expr>)`, the arguments of
+    /// `update` are the direct arguments, the value of the expression and whether the
+    /// ordering is descending.
+    fn ordered_set(&self) -> bool {
+        false
+    }
+
+    /// State of a group without rows.
+    fn init(&self) -> AggregateState;
+
+    /// Adds the argument values of a row to the state.
+    fn update(&self, state: &mut AggregateState, arguments: &[Value])
+        -> Result<(), ExecutionError>;
+
+    /// Adds the rows of another state of the group.
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError>;
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError>;
+
+    /// Updates the states of the groups with the rows of a batch, given as their index in
+    /// the batch and their group.
+    fn update_batch(
+        &self,
+        states: &mut [AggregateState],
+        rows: &[(usize, usize)],
+        arguments: &[ColumnVector],
+    ) -> Result<(), ExecutionError> {
+        for &(index, group) in rows {
+            let values: Vec<_> = arguments
+                .iter()
+                .map(|argument| argument.value(index))
+                .collect();
+            if !self.null_handling().skips(&values) {
+                self.update(&mut states[group], &values)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for dyn AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AggregateFunction({})", self.name())
+    }
+}
+
+pub(crate) fn functions() -> Vec<Box<dyn AggregateFunction>> {
+    vec![
+        Box::new(Count),
+        Box::new(Sum),
+        Box::new(Avg),
+        Box::new(Extreme::new("min", Ordering::Less)),
+        Box::new(Extreme::new("max", Ordering::Greater)),
+        Box::new(BoolAggregate::new("bool_and", true)),
+        Box::new(BoolAggregate::new("bool_or", false)),
+        Box::new(StringAgg),
+        Box::new(ArrayAgg),
+        Box::new(Variance::new("variance", false, false)),
+        Box::new(Variance::new("var_samp", false, false)),
+        Box::new(Variance::new("var_pop", true, false)),
+        Box::new(Variance::new("stddev", false, true)),
+        Box::new(Variance::new("stddev_samp", false, true)),
+        Box::new(Variance::new("stddev_pop", true, true)),
+        Box::new(Percentile::new("percentile_cont", true)),
+        Box::new(Percentile::new("percentile_disc", false)),
+    ]
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Float(value) => *value,
+        Value::Decimal(value) => value.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(value) => *value,
+        _ => 0,
+    }
+}
+
+/// The type of the only argument, `Undefined` for another number of arguments.
+fn single(arguments: &[Option<DataType>]) -> Result<Option<DataType>, ResolveError> {
+    match arguments {
+        [argument] => Ok(*argument),
+        _ => Err(ResolveError::Undefined),
+    }
+}
+
+/// Adds the value to a sum, which is `NULL` before the first value.
+fn add(sum: &mut Value, value: &Value) -> Result<(), ExecutionError> {
+    *sum = match &*sum {
+        Value::Null => value.clone(),
+        sum => binary(sum, Operator::Plus, value)?,
+    };
+    Ok(())
+}
+
+/// Adds the selected values of an integer or float vector to the sums of their groups.
+fn add_batch(
+    sums: &mut [AggregateState],
+    position: usize,
+    rows: &[(usize, usize)],
+    argument: &ColumnVector,
+) -> Result<bool, ExecutionError> {
+    let valid = rows
+        .iter()
+        .filter(|(index, _)| argument.validity.get(*index));
+    match &argument.values {
+        Vector::Integer(values) => {
+            for (index, group) in valid {
+                let sum = &mut sums[*group][position];
+                *sum = match &*sum {
+                    Value::Integer(sum) => Value::Integer(sum.checked_add(values[*index]).ok_or(
+                        ExecutionError::OutOfRange {
+                            data_type: DataType::BigInt,
+                        },
+                    )?),
+                    Value::Null => Value::Integer(values[*index]),
+                    sum => binary(sum, Operator::Plus, &Value::Integer(values[*index]))?,
+                };
+            }
+        }
+        Vector::Float(values) => {
+            for (index, group) in valid {
+                let sum = &mut sums[*group][position];
+                *sum = match &*sum {
+                    Value::Float(sum) => Value::Float(*sum + values[*index]),
+                    Value::Null => Value::Float(values[*index]),
+                    sum => binary(sum, Operator::Plus, &Value::Float(values[*index]))?,
+                };
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Increments the counts of the groups of the rows whose arguments aren't `NULL`.
+fn count_batch(counts: &mut [AggregateState], rows: &[(usize, usize)], arguments: &[ColumnVector]) {
+    let valid = rows.iter().filter(|(index, _)| {
+        arguments
+            .iter()
+            .all(|argument| argument.validity.get(*index))
+    });
+    for (_, group) in valid {
+        if let Value::Integer(count) = &mut counts[*group][0] {
+            *count += 1;
+        }
+    }
+}
+
+/// `count(*)` has no argument and counts all rows.
+struct Count;
+
+impl AggregateFunction for Count {
+    fn name(&self) -> &str {
+        "count"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments.len() {
+            0 | 1 => Ok(Some(DataType::BigInt)),
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Integer(0)]
+    }
+
+    fn update(&self, state: &mut AggregateState, _: &[Value]) -> Result<(), ExecutionError> {
+        state[0] = Value::Integer(integer(&state[0]) + 1);
+        Ok(())
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        state[0] = Value::Integer(integer(&state[0]) + integer(&other[0]));
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(state[0].clone())
+    }
+
+    fn update_batch(
+        &self,
+        states: &mut [AggregateState],
+        rows: &[(usize, usize)],
+        arguments: &[ColumnVector],
+    ) -> Result<(), ExecutionError> {
+        count_batch(states, rows, arguments);
+        Ok(())
+    }
+}
+
+struct Sum;
+
+impl AggregateFunction for Sum {
+    fn name(&self) -> &str {
+        "sum"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match single(arguments)? {
+            None => Ok(None),
+            Some(DataType::TinyInt | DataType::SmallInt | DataType::Integer) => {
+                Ok(Some(DataType::BigInt))
+            }
+            Some(DataType::BigInt | DataType::Decimal(_)) => Ok(Some(DataType::Decimal(None))),
+            Some(data_type @ (DataType::Real | DataType::Double)) => Ok(Some(data_type)),
+            Some(_) => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        add(&mut state[0], &arguments[0])
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        match &other[0] {
+            Value::Null => Ok(()),
+            sum => add(&mut state[0], sum),
+        }
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(state[0].clone())
+    }
+
+    fn update_batch(
+        &self,
+        states: &mut [AggregateState],
+        rows: &[(usize, usize)],
+        arguments: &[ColumnVector],
+    ) -> Result<(), ExecutionError> {
+        if !add_batch(states, 0, rows, &arguments[0])? {
+            for &(index, group) in rows {
+                let value = arguments[0].value(index);
+                if value != Value::Null {
+                    self.update(&mut states[group], &[value])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state is the count and the sum of the values.
+struct Avg;
+
+impl AggregateFunction for Avg {
+    fn name(&self) -> &str {
+        "avg"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match single(arguments)? {
+            None => Ok(None),
+            Some(data_type) if data_type.is_integer() => Ok(Some(DataType::Decimal(None))),
+            Some(DataType::Decimal(_)) => Ok(Some(DataType::Decimal(None))),
+            Some(DataType::Real | DataType::Double) => Ok(Some(DataType::Double)),
+            Some(_) => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Integer(0), Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        state[0] = Value::Integer(integer(&state[0]) + 1);
+        add(&mut state[1], &arguments[0])
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        state[0] = Value::Integer(integer(&state[0]) + integer(&other[0]));
+        match &other[1] {
+            Value::Null => Ok(()),
+            sum => add(&mut state[1], sum),
+        }
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(match (&state[0], &state[1]) {
+            (Value::Integer(0), _) => Value::Null,
+            (Value::Integer(count), Value::Float(sum)) => Value::Float(sum / *count as f64),
+            // the exact quotient of an integer or decimal sum
+            (Value::Integer(count), sum @ (Value::Integer(_) | Value::Decimal(_))) => binary(
+                sum,
+                Operator::Divide,
+                &Value::Decimal(Decimal::from_i64(*count)),
+            )?,
+            _ => Value::Null,
+        })
+    }
+
+    fn update_batch(
+        &self,
+        states: &mut [AggregateState],
+        rows: &[(usize, usize)],
+        arguments: &[ColumnVector],
+    ) -> Result<(), ExecutionError> {
+        if !add_batch(states, 1, rows, &arguments[0])? {
+            for &(index, group) in rows {
+                let value = arguments[0].value(index);
+                if value != Value::Null {
+                    self.update(&mut states[group], &[value])?;
+                }
+            }
+            return Ok(());
+        }
+        count_batch(states, rows, arguments);
+        Ok(())
+    }
+}
+
+/// `min` or `max`, the value whose ordering to the others is `wanted`.
+struct Extreme {
+    name: &'static str,
+    wanted: Ordering,
+}
+
+impl Extreme {
+    fn new(name: &'static str, wanted: Ordering) -> Self {
+        Extreme { name, wanted }
+    }
+
+    fn replace(&self, current: &mut Value, value: &Value) {
+        if *current == Value::Null || compare(value, current) == Some(self.wanted) {
+            *current = value.clone();
+        }
+    }
+}
+
+impl AggregateFunction for Extreme {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match single(arguments)? {
+            Some(DataType::Boolean) => Err(ResolveError::Undefined),
+            data_type => Ok(data_type),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        self.replace(&mut state[0], &arguments[0]);
+        Ok(())
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        if other[0] != Value::Null {
+            self.replace(&mut state[0], &other[0]);
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(state[0].clone())
+    }
+
+    fn update_batch(
+        &self,
+        states: &mut [AggregateState],
+        rows: &[(usize, usize)],
+        arguments: &[ColumnVector],
+    ) -> Result<(), ExecutionError> {
+        let argument = &arguments[0];
+        let valid = rows
+            .iter()
+            .filter(|(index, _)| argument.validity.get(*index));
+        match &argument.values {
+            Vector::Integer(values) => {
+                for (index, group) in valid {
+                    let current = &mut states[*group][0];
+                    match current {
+                        Value::Integer(current) if values[*index].cmp(current) == self.wanted => {
+                            *current = values[*index]
+                        }
+                        Value::Null => *current = Value::Integer(values[*index]),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {
+                for (index, group) in valid {
+                    self.replace(&mut states[*group][0], &argument.value(*index));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `bool_and` or `bool_or`.
+struct BoolAggregate {
+    name: &'static str,
+    and: bool,
+}
+
+impl BoolAggregate {
+    fn new(name: &'static str, and: bool) -> Self {
+        BoolAggregate { name, and }
+    }
+
+    fn combine(&self, current: &mut Value, value: &Value) {
+        *current = match (&*current, value) {
+            (Value::Boolean(current), Value::Boolean(value)) if self.and => {
+                Value::Boolean(*current && *value)
+            }
+            (Value::Boolean(current), Value::Boolean(value)) => Value::Boolean(*current || *value),
+            (Value::Null, value) => value.clone(),
+            (current, _) => current.clone(),
+        };
+    }
+}
+
+impl AggregateFunction for BoolAggregate {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match single(arguments)? {
+            None | Some(DataType::Boolean) => Ok(Some(DataType::Boolean)),
+            Some(_) => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        self.combine(&mut state[0], &arguments[0]);
+        Ok(())
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        self.combine(&mut state[0], &other[0]);
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(state[0].clone())
+    }
+}
+
+/// `string_agg(value, delimiter)`. The state is the text of the values, each preceded by
+/// its delimiter, and the length of the first delimiter which is left out of the result.
+struct StringAgg;
+
+impl AggregateFunction for StringAgg {
+    fn name(&self) -> &str {
+        "string_agg"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [value, delimiter]
+                if value.is_none_or(|t| t.is_string())
+                    && delimiter.is_none_or(|t| t.is_string()) =>
+            {
+                Ok(Some(DataType::Text))
+            }
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    /// `NULL` values are skipped, a `NULL` delimiter is empty.
+    fn null_handling(&self) -> NullHandling {
+        NullHandling::CalledOnNull
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null, Value::Integer(0)]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        if arguments[0] == Value::Null {
+            return Ok(());
+        }
+        let delimiter = match &arguments[1] {
+            Value::Null => String::new(),
+            delimiter => text(delimiter),
+        };
+        let value = text(&arguments[0]);
+        match &mut state[0] {
+            Value::String(text) => {
+                text.push_str(&delimiter);
+                text.push_str(&value);
+            }
+            _ => {
+                state[1] = Value::Integer(delimiter.len() as i64);
+                state[0] = Value::String(delimiter + &value);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        match (&mut state[0], &other[0]) {
+            (_, Value::Null) => {}
+            (Value::String(text), Value::String(other)) => text.push_str(other),
+            _ => state.clone_from_slice(other),
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(match &state[0] {
+            Value::String(text) => Value::String(text[integer(&state[1]) as usize..].to_string()),
+            _ => Value::Null,
+        })
+    }
+}
+
+/// `array_agg(value)`, `NULL`s included. The state is the text of the elements.
+struct ArrayAgg;
+
+impl ArrayAgg {
+    /// The element as it is written in the text of an array, quoted when it would be read
+    /// differently otherwise.
+    fn element(value: &Value) -> String {
+        match value {
+            Value::Null => "NULL".to_string(),
+            Value::Boolean(value) => if *value { "t" } else { "f" }.to_string(),
+            Value::String(value) => {
+                let special = |c: char| c.is_whitespace() || "{},\"\\".contains(c);
+                if value.is_empty() || value.eq_ignore_ascii_case("null") || value.contains(special)
+                {
+                    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+                } else {
+                    value.clone()
+                }
+            }
+            value => text(value),
+        }
+    }
+}
+
+impl AggregateFunction for ArrayAgg {
+    fn name(&self) -> &str {
+        "array_agg"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        single(arguments)?;
+        Ok(Some(DataType::Text))
+    }
+
+    fn null_handling(&self) -> NullHandling {
+        NullHandling::CalledOnNull
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        self.merge(state, &[Value::String(Self::element(&arguments[0]))])
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        match (&mut state[0], &other[0]) {
+            (_, Value::Null) => {}
+            (Value::String(elements), Value::String(other)) => {
+                elements.push(',');
+                elements.push_str(other);
+            }
+            (elements, other) => *elements = other.clone(),
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        Ok(match &state[0] {
+            Value::String(elements) => Value::String(format!("{{{}}}", elements)),
+            _ => Value::Null,
+        })
+    }
+}
+
+/// Variance or standard deviation of a sample or a population, a `DOUBLE` for every
+/// numeric type. The state is the count, the mean and the sum of the squared differences
+/// from the mean, updated with Welford's algorithm.
+struct Variance {
+    name: &'static str,
+    population: bool,
+    root: bool,
+}
+
+impl Variance {
+    fn new(name: &'static str, population: bool, root: bool) -> Self {
+        Variance {
+            name,
+            population,
+            root,
+        }
+    }
+}
+
+impl AggregateFunction for Variance {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match single(arguments)? {
+            None => Ok(None),
+            Some(data_type) if data_type.is_numeric() => Ok(Some(DataType::Double)),
+            Some(_) => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Integer(0), Value::Float(0.0), Value::Float(0.0)]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        let value = float(&arguments[0]);
+        self.merge(
+            state,
+            &[Value::Integer(1), Value::Float(value), Value::Float(0.0)],
+        )
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        let (count, mean, squares) = (integer(&state[0]), float(&state[1]), float(&state[2]));
+        let (other_count, other_mean, other_squares) =
+            (integer(&other[0]), float(&other[1]), float(&other[2]));
+        let total = count + other_count;
+        if total == 0 {
+            return Ok(());
+        }
+        let delta = other_mean - mean;
+        let weight = other_count as f64 / total as f64;
+        *state = vec![
+            Value::Integer(total),
+            Value::Float(mean + delta * weight),
+            Value::Float(squares + other_squares + delta * delta * count as f64 * weight),
+        ];
+        Ok(())
+    }
+
+    /// `NULL` for a sample of less than two values or an empty population.
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        let count = integer(&state[0]) - i64::from(!self.population);
+        if count <= 0 {
+            return Ok(Value::Null);
+        }
+        let variance = float(&state[2]) / count as f64;
+        Ok(Value::Float(match self.root {
+            true => variance.sqrt(),
+            false => variance,
+        }))
+    }
+}
+
+/// `percentile_cont` interpolates between the values around the fraction,
+/// `percentile_disc` returns the first value at least the fraction of the values come up
+/// to. The state is the fraction, whether the values are ordered descending, then the
+/// values.
+struct Percentile {
+    name: &'static str,
+    continuous: bool,
+}
+
+impl Percentile {
+    fn new(name: &'static str, continuous: bool) -> Self {
+        Percentile { name, continuous }
+    }
+}
+
+impl AggregateFunction for Percentile {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [fraction, _] if fraction.is_some_and(|t| !t.is_numeric()) => {
+                Err(ResolveError::Undefined)
+            }
+            [_, value] if self.continuous => match value {
+                None => Ok(Some(DataType::Double)),
+                Some(data_type) if data_type.is_numeric() => Ok(Some(DataType::Double)),
+                Some(_) => Err(ResolveError::Undefined),
+            },
+            [_, value] => Ok(*value),
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn ordered_set(&self) -> bool {
+        true
+    }
+
+    fn init(&self) -> AggregateState {
+        vec![Value::Null, Value::Null]
+    }
+
+    fn update(
+        &self,
+        state: &mut AggregateState,
+        arguments: &[Value],
+    ) -> Result<(), ExecutionError> {
+        if state[0] == Value::Null {
+            let fraction = float(&arguments[0]);
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(ExecutionError::InvalidArgument(format!(
+                    "percentile value {} is not between 0 and 1",
+                    fraction
+                )));
+            }
+            state[0] = Value::Float(fraction);
+            state[1] = arguments[2].clone();
+        }
+        state.push(arguments[1].clone());
+        Ok(())
+    }
+
+    fn merge(&self, state: &mut AggregateState, other: &[Value]) -> Result<(), ExecutionError> {
+        if state[0] == Value::Null {
+            state[..2].clone_from_slice(&other[..2]);
+        }
+        state.extend_from_slice(&other[2..]);
+        Ok(())
+    }
+
+    fn finalize(&self, state: &AggregateState) -> Result<Value, ExecutionError> {
+        let mut values = state[2..].to_vec();
+        if values.is_empty() {
+            return Ok(Value::Null);
+        }
+        values.sort_by(|left, right| {
+            let ordering = compare(left, right).unwrap_or(Ordering::Equal);
+            match state[1] {
+                Value::Boolean(true) => ordering.reverse(),
+                _ => ordering,
+            }
+        });
+        let fraction = float(&state[0]);
+        if !self.continuous {
+            let position = (fraction * values.len() as f64).ceil() as usize;
+            return Ok(values[position.max(1) - 1].clone());
+        }
+        let position = fraction * (values.len() - 1) as f64;
+        let (below, above) = (
+            float(&values[position.floor() as usize]),
+            float(&values[position.ceil() as usize]),
+        );
+        Ok(Value::Float(below + (above - below) * position.fract()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::builtins;
+
+    /// Result of the aggregate over the rows, half of them merged from a second state as
+    /// a parallel plan would.
+    fn aggregate(name: &str, rows: &[&[Value]]) -> Result<Value, ExecutionError> {
+        let function = builtins().aggregate(name).unwrap();
+        let (first, second) = rows.split_at(rows.len() / 2);
+        let mut states = [function.init(), function.init()];
+        for (state, rows) in states.iter_mut().zip([first, second]) {
+            for row in rows {
+                if !function.null_handling().skips(row) {
+                    function.update(state, row)?;
+                }
+            }
+        }
+        let [mut state, other] = states;
+        function.merge(&mut state, &other)?;
+        function.finalize(&state)
+    }
+
+    fn values(values: &[Value]) -> Vec<[Value; 1]> {
+        values.iter().map(|value| [value.clone()]).collect()
+    }
+
+    fn over(name: &str, rows: &[[Value; 1]]) -> Value {
+        let rows: Vec<&[Value]> = rows.iter().map(|row| &row[..]).collect();
+        aggregate(name, &rows).unwrap()
+    }
+
+    #[test]
+    fn counts_and_sums() {
+        let rows = values(&[1.into(), Value::Null, 3.into(), 4.into()]);
+        assert_eq!(over("count", &rows), 3.into());
+        assert_eq!(aggregate("count", &[&[], &[]]), Ok(2.into()));
+        assert_eq!(over("sum", &rows), 8.into());
+        assert_eq!(over("avg", &rows).to_string(), "2.6666666666666667");
+        assert_eq!(over("sum", &values(&[Value::Null])), Value::Null);
+        assert_eq!(over("avg", &[]), Value::Null);
+        assert!(aggregate("sum", &[&[i64::MAX.into()], &[1.into()]]).is_err());
+    }
+
+    #[test]
+    fn finds_extremes() {
+        let rows = values(&["b".into(), Value::Null, "a".into(), "c".into()]);
+        assert_eq!(over("min", &rows), "a".into());
+        assert_eq!(over("max", &rows), "c".into());
+        assert_eq!(over("max", &[]), Value::Null);
+    }
+
+    #[test]
+    fn combines_booleans() {
+        let rows = values(&[true.into(), Value::Null, false.into()]);
+        assert_eq!(over("bool_and", &rows), false.into());
+        assert_eq!(over("bool_or", &rows), true.into());
+        assert_eq!(over("bool_and", &values(&[true.into()])), true.into());
+        assert_eq!(over("bool_or", &[]), Value::Null);
+    }
+
+    #[test]
+    fn aggregates_strings_and_arrays() {
+        let rows: [&[Value]; 4] = [
+            &["a".into(), ", ".into()],
+            &[Value::Null, ";".into()],
+            &["b".into(), "; ".into()],
+            &["c".into(), Value::Null],
+        ];
+        assert_eq!(aggregate("string_agg", &rows), Ok("a; bc".into()));
+        assert_eq!(
+            aggregate("string_agg", &[&[Value::Null, ",".into()]]),
+            Ok(Value::Null)
+        );
+        let elements = values(&[1.into(), Value::Null, "a b".into(), true.into()]);
+        assert_eq!(over("array_agg", &elements), "{1,NULL,\"a b\",t}".into());
+        assert_eq!(
+            over("array_agg", &values(&["null".into()])),
+            "{\"null\"}".into()
+        );
+        assert_eq!(over("array_agg", &[]), Value::Null);
+    }
+
+    #[test]
+    fn computes_variances() {
+        let rows = values(&[
+            2.into(),
+            4.into(),
+            4.into(),
+            4.into(),
+            5.into(),
+            5.into(),
+            7.into(),
+            9.into(),
+        ]);
+        assert_eq!(over("var_pop", &rows), 4.0.into());
+        assert_eq!(over("stddev_pop", &rows), 2.0.into());
+        assert_eq!(over("variance", &rows), Value::Float(32.0 / 7.0));
+        assert_eq!(over("stddev", &rows), Value::Float((32.0f64 / 7.0).sqrt()));
+        assert_eq!(over("var_samp", &values(&[1.into()])), Value::Null);
+        assert_eq!(over("var_pop", &values(&[1.into()])), 0.0.into());
+        assert_eq!(over("var_pop", &[]), Value::Null);
+    }
+
+    #[test]
+    fn computes_percentiles() {
+        let rows = |fraction: f64, descending: bool, values: &[i64]| -> Vec<[Value; 3]> {
+            values
+                .iter()
+                .map(|value| [fraction.into(), (*value).into(), descending.into()])
+                .collect()
+        };
+        let percentile = |name: &str, rows: Vec<[Value; 3]>| {
+            let rows: Vec<&[Value]> = rows.iter().map(|row| &row[..]).collect();
+            aggregate(name, &rows)
+        };
+        let values = [4, 1, 3, 2];
+        assert_eq!(
+            percentile("percentile_cont", rows(0.5, false, &values)),
+            Ok(2.5.into())
+        );
+        assert_eq!(
+            percentile("percentile_cont", rows(0.25, false, &values)),
+            Ok(1.75.into())
+        );
+        assert_eq!(
+            percentile("percentile_cont", rows(1.0, false, &values)),
+            Ok(4.0.into())
+        );
+        assert_eq!(
+            percentile("percentile_disc", rows(0.5, false, &values)),
+            Ok(2.into())
+        );
+        assert_eq!(
+            percentile("percentile_disc", rows(0.0, false, &values)),
+            Ok(1.into())
+        );
+        assert_eq!(
+            percentile("percentile_disc", rows(0.5, true, &values)),
+            Ok(3.into())
+        );
+        assert_eq!(
+            percentile("percentile_cont", rows(0.5, false, &[])),
+            Ok(Value::Null)
+        );
+        assert_eq!(
+            percentile("percentile_cont", rows(1.5, false, &values)),
+            Err(ExecutionError::InvalidArgument(
+                "percentile value 1.5 is not between 0 and 1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn types_results() {
+        let return_type = |name: &str, arguments: &[Option<DataType>]| {
+            builtins().aggregate(name).unwrap().return_type(arguments)
+        };
+        assert_eq!(return_type("count", &[]), Ok(Some(DataType::BigInt)));
+        assert_eq!(
+            return_type("sum", &[Some(DataType::Integer)]),
+            Ok(Some(DataType::BigInt))
+        );
+        assert_eq!(
+            return_type("avg", &[Some(DataType::Real)]),
+            Ok(Some(DataType::Double))
+        );
+        assert_eq!(
+            return_type("stddev", &[Some(DataType::Integer)]),
+            Ok(Some(DataType::Double))
+        );
+        assert_eq!(
+            return_type("percentile_disc", &[None, Some(DataType::Date)]),
+            Ok(Some(DataType::Date))
+        );
+        assert_eq!(
+            return_type("sum", &[Some(DataType::Text)]),
+            Err(ResolveError::Undefined)
+        );
+        assert_eq!(
+            return_type("bool_or", &[Some(DataType::Integer)]),
+            Err(ResolveError::Undefined)
+        );
+        assert_eq!(
+            return_type("string_agg", &[Some(DataType::Text)]),
+            Err(ResolveError::Undefined)
+        );
+        assert_eq!(
+            return_type(
+                "percentile_cont",
+                &[Some(DataType::Text), Some(DataType::Integer)]
+            ),
+            Err(ResolveError::Undefined)
+        );
+    }
+}
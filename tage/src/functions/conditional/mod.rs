@@ -0,0 +1,62 @@
+//! Functions which take `NULL`s: `coalesce` and `nullif`.
+
+use crate::execution::expr::compare;
+use crate::functions::{NullHandling, ParameterType, ScalarFunction, Signature};
+use crate::value::Value;
+use std::cmp::Ordering;
+
+pub(crate) fn functions() -> Vec<ScalarFunction> {
+    use ParameterType::Element;
+    vec![
+        // the arguments are all evaluated, there are no side effects to skip
+        ScalarFunction::new(
+            "coalesce",
+            vec![Signature::new(vec![Element], Element, |arguments, _| {
+                let first = arguments.iter().find(|argument| **argument != Value::Null);
+                Ok(first.cloned().unwrap_or(Value::Null))
+            })
+            .variadic()],
+        )
+        .null_handling(NullHandling::CalledOnNull),
+        // `NULL` if the arguments are equal, else the first one
+        ScalarFunction::new(
+            "nullif",
+            vec![Signature::new(
+                vec![Element, Element],
+                Element,
+                |arguments, _| match compare(&arguments[0], &arguments[1]) {
+                    Some(Ordering::Equal) => Ok(Value::Null),
+                    _ => Ok(arguments[0].clone()),
+                },
+            )],
+        )
+        .null_handling(NullHandling::CalledOnNull),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::functions::tests::call;
+    use crate::value::Value;
+
+    #[test]
+    fn coalesces() {
+        assert_eq!(
+            call("coalesce", &[Value::Null, 1.into(), 2.into()]),
+            Ok(1.into())
+        );
+        assert_eq!(call("coalesce", &["a".into()]), Ok("a".into()));
+        assert_eq!(
+            call("coalesce", &[Value::Null, Value::Null]),
+            Ok(Value::Null)
+        );
+    }
+
+    #[test]
+    fn nulls_equal_values() {
+        assert_eq!(call("nullif", &[1.into(), 1.into()]), Ok(Value::Null));
+        assert_eq!(call("nullif", &[1.into(), 2.into()]), Ok(1.into()));
+        assert_eq!(call("nullif", &[1.into(), Value::Null]), Ok(1.into()));
+        assert_eq!(call("nullif", &[Value::Null, 1.into()]), Ok(Value::Null));
+    }
+}
@@ -0,0 +1,626 @@
+//! Functions and operators of dates, times, timestamps and intervals: `now()`,
+//! `extract`, `date_trunc` and their arithmetic, e.g. `date + integer` or
+//! `timestamp - interval`. A timestamp with time zone is computed in UTC.
+
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::functions::{ScalarFunction, Signature, Volatility};
+use crate::value::temporal::{
+    add_interval, civil_from_days, days_from_civil, is_valid_date, Interval, MICROS_PER_DAY,
+    MICROS_PER_HOUR, MICROS_PER_MINUTE, MICROS_PER_SECOND, MONTHS_PER_YEAR,
+};
+use crate::value::{DataType, Decimal, Value};
+use std::time::UNIX_EPOCH;
+
+fn string(value: &Value) -> &str {
+    match value {
+        Value::String(text) => text,
+        _ => "",
+    }
+}
+
+fn number_argument(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Float(value) => *value,
+        Value::Decimal(value) => value.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+/// Microseconds of a timestamp, with or without time zone, or of a date at midnight.
+fn timestamp(value: &Value) -> i64 {
+    match value {
+        Value::Timestamp(micros) | Value::TimestampTz(micros) => *micros,
+        Value::Date(days) => *days as i64 * MICROS_PER_DAY,
+        _ => 0,
+    }
+}
+
+/// A timestamp of the kind of the one it is computed from, a date's is without time
+/// zone.
+fn timestamp_like(value: &Value, micros: i64) -> Value {
+    match value {
+        Value::TimestampTz(_) => Value::TimestampTz(micros),
+        _ => Value::Timestamp(micros),
+    }
+}
+
+fn date(value: &Value) -> i64 {
+    match value {
+        Value::Date(days) => *days as i64,
+        _ => 0,
+    }
+}
+
+fn time(value: &Value) -> i64 {
+    match value {
+        Value::Time(micros) => *micros,
+        _ => 0,
+    }
+}
+
+fn interval(value: &Value) -> Interval {
+    match value {
+        Value::Interval(interval) => *interval,
+        _ => Interval::default(),
+    }
+}
+
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(value) => *value,
+        _ => 0,
+    }
+}
+
+fn date_value(days: i64) -> Result<Value, ExecutionError> {
+    match is_valid_date(days) {
+        true => Ok(Value::Date(days as i32)),
+        false => Err(ExecutionError::OutOfRange {
+            data_type: DataType::Date,
+        }),
+    }
+}
+
+fn interval_out_of_range() -> ExecutionError {
+    ExecutionError::OutOfRange {
+        data_type: DataType::Interval,
+    }
+}
+
+fn unsupported_unit(unit: &str, data_type: DataType) -> ExecutionError {
+    ExecutionError::InvalidArgument(format!(
+        "unit \"{}\" not recognized for type {}",
+        unit,
+        data_type.to_string().to_lowercase()
+    ))
+}
+
+/// `extract(<field> from <timestamp>)`
+fn extract_timestamp(field: &str, micros: i64) -> Result<f64, ExecutionError> {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let time = micros.rem_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    // 0 is Sunday
+    let weekday = (days + 4).rem_euclid(7);
+    Ok(match field {
+        "millennium" => ((year - 1) / 1000 + 1) as f64,
+        "century" => ((year - 1) / 100 + 1) as f64,
+        "decade" => (year / 10) as f64,
+        "year" => year as f64,
+        "quarter" => ((month - 1) / 3 + 1) as f64,
+        "month" => month as f64,
+        "week" => iso_week(days) as f64,
+        "day" => day as f64,
+        "dow" => weekday as f64,
+        "isodow" => (if weekday == 0 { 7 } else { weekday }) as f64,
+        "doy" => (days - days_from_civil(year, 1, 1) + 1) as f64,
+        "epoch" => micros as f64 / MICROS_PER_SECOND as f64,
+        field => {
+            return extract_time(field, time)
+                .map_err(|_| unsupported_unit(field, DataType::Timestamp))
+        }
+    })
+}
+
+/// Week of the ISO year, which starts with the week of its first Thursday.
+fn iso_week(days: i64) -> i64 {
+    // Thursday of the same week, Monday being the first day
+    let thursday = days - (days + 3).rem_euclid(7) + 3;
+    let (year, _, _) = civil_from_days(thursday);
+    (thursday - days_from_civil(year, 1, 1)) / 7 + 1
+}
+
+/// `extract(<field> from <time>)`
+fn extract_time(field: &str, micros: i64) -> Result<f64, ExecutionError> {
+    let seconds_micros = micros % MICROS_PER_MINUTE;
+    Ok(match field {
+        "hour" => (micros / MICROS_PER_HOUR) as f64,
+        "minute" => (micros / MICROS_PER_MINUTE % 60) as f64,
+        "second" => seconds_micros as f64 / MICROS_PER_SECOND as f64,
+        "millisecond" | "milliseconds" => seconds_micros as f64 / 1000.0,
+        "microsecond" | "microseconds" => seconds_micros as f64,
+        "epoch" => micros as f64 / MICROS_PER_SECOND as f64,
+        field => return Err(unsupported_unit(field, DataType::Time)),
+    })
+}
+
+/// `extract(<field> from <interval>)`
+fn extract_interval(field: &str, interval: Interval) -> Result<f64, ExecutionError> {
+    let years = interval.months / MONTHS_PER_YEAR;
+    Ok(match field {
+        "millennium" => (years / 1000) as f64,
+        "century" => (years / 100) as f64,
+        "decade" => (years / 10) as f64,
+        "year" => years as f64,
+        "quarter" => (interval.months % MONTHS_PER_YEAR / 3 + 1) as f64,
+        "month" => (interval.months % MONTHS_PER_YEAR) as f64,
+        "day" => interval.days as f64,
+        "epoch" => interval.epoch(),
+        field => {
+            return extract_time(field, interval.micros)
+                .map_err(|_| unsupported_unit(field, DataType::Interval))
+        }
+    })
+}
+
+/// `date_trunc(<field>, <timestamp>)`
+fn trunc_timestamp(field: &str, micros: i64) -> Result<i64, ExecutionError> {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, _) = civil_from_days(days);
+    let first_year = |years: i64| {
+        let year = match years {
+            1 => year,
+            // centuries and millennia start with their year 1
+            years if years >= 100 => (year - 1) / years * years + 1,
+            years => year / years * years,
+        };
+        days_from_civil(year, 1, 1) * MICROS_PER_DAY
+    };
+    let unit = match field {
+        "microseconds" => 1,
+        "milliseconds" => 1000,
+        "second" => MICROS_PER_SECOND,
+        "minute" => MICROS_PER_MINUTE,
+        "hour" => MICROS_PER_HOUR,
+        "day" => MICROS_PER_DAY,
+        "week" => return Ok((days - (days + 3).rem_euclid(7)) * MICROS_PER_DAY),
+        "month" => return Ok(days_from_civil(year, month, 1) * MICROS_PER_DAY),
+        "quarter" => return Ok(days_from_civil(year, (month - 1) / 3 * 3 + 1, 1) * MICROS_PER_DAY),
+        "year" => return Ok(first_year(1)),
+        "decade" => return Ok(first_year(10)),
+        "century" => return Ok(first_year(100)),
+        "millennium" => return Ok(first_year(1000)),
+        field => return Err(unsupported_unit(field, DataType::Timestamp)),
+    };
+    Ok(micros - micros.rem_euclid(unit))
+}
+
+/// `date_trunc(<field>, <interval>)`
+fn trunc_interval(field: &str, interval: Interval) -> Result<Interval, ExecutionError> {
+    let months = |per: i64| Interval {
+        months: interval.months / per * per,
+        days: 0,
+        micros: 0,
+    };
+    let unit = match field {
+        "microseconds" => 1,
+        "milliseconds" => 1000,
+        "second" => MICROS_PER_SECOND,
+        "minute" => MICROS_PER_MINUTE,
+        "hour" => MICROS_PER_HOUR,
+        "day" => {
+            return Ok(Interval {
+                micros: 0,
+                ..interval
+            })
+        }
+        "month" => {
+            return Ok(Interval {
+                days: 0,
+                micros: 0,
+                ..interval
+            })
+        }
+        "quarter" => return Ok(months(3)),
+        "year" => return Ok(months(12)),
+        "decade" => return Ok(months(120)),
+        "century" => return Ok(months(1200)),
+        "millennium" => return Ok(months(12000)),
+        field => return Err(unsupported_unit(field, DataType::Interval)),
+    };
+    Ok(Interval {
+        micros: interval.micros / unit * unit,
+        ..interval
+    })
+}
+
+fn field(value: &Value) -> String {
+    string(value).to_lowercase()
+}
+
+fn now(_: &[Value], context: &ExecutionContext) -> Result<Value, ExecutionError> {
+    let micros = context
+        .start_time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_micros() as i64);
+    Ok(Value::Timestamp(micros))
+}
+
+/// A timestamp, or a date at midnight, moved by an interval.
+fn timestamp_plus_interval(
+    timestamp_value: &Value,
+    interval: Interval,
+) -> Result<Value, ExecutionError> {
+    match add_interval(timestamp(timestamp_value), interval) {
+        Some(micros) => Ok(timestamp_like(timestamp_value, micros)),
+        None => Err(ExecutionError::OutOfRange {
+            data_type: DataType::Timestamp,
+        }),
+    }
+}
+
+/// A time moved by the microseconds of an interval, around midnight.
+fn time_plus_interval(time_value: &Value, interval: Interval) -> Result<Value, ExecutionError> {
+    Ok(Value::Time(
+        (time(time_value) + interval.micros).rem_euclid(MICROS_PER_DAY),
+    ))
+}
+
+fn scale(interval_value: &Value, factor: f64) -> Result<Value, ExecutionError> {
+    match interval(interval_value).scale(factor) {
+        Some(interval) => Ok(Value::Interval(interval)),
+        None => Err(interval_out_of_range()),
+    }
+}
+
+/// A field of `extract`, which is `numeric`.
+fn field_value(value: f64) -> Result<Value, ExecutionError> {
+    Ok(Value::Decimal(
+        Decimal::from_f64(value).unwrap_or_else(Decimal::zero),
+    ))
+}
+
+pub(crate) fn functions() -> Vec<ScalarFunction> {
+    use DataType::{Date, Decimal, Double, Integer, Interval, Text, Time, Timestamp, TimestampTz};
+    let mut plus = vec![
+        Signature::exact(&[Date, Integer], Date, |arguments, _| {
+            date_value(date(&arguments[0]) + integer(&arguments[1]))
+        }),
+        Signature::exact(&[Integer, Date], Date, |arguments, _| {
+            date_value(date(&arguments[1]) + integer(&arguments[0]))
+        }),
+        Signature::exact(&[Date, Interval], Timestamp, |arguments, _| {
+            timestamp_plus_interval(&arguments[0], interval(&arguments[1]))
+        }),
+        Signature::exact(&[Interval, Date], Timestamp, |arguments, _| {
+            timestamp_plus_interval(&arguments[1], interval(&arguments[0]))
+        }),
+        Signature::exact(&[Time, Interval], Time, |arguments, _| {
+            time_plus_interval(&arguments[0], interval(&arguments[1]))
+        }),
+        Signature::exact(&[Interval, Time], Time, |arguments, _| {
+            time_plus_interval(&arguments[1], interval(&arguments[0]))
+        }),
+        Signature::exact(&[Interval, Interval], Interval, |arguments, _| {
+            let sum = interval(&arguments[0]) + interval(&arguments[1]);
+            Ok(Value::Interval(sum))
+        }),
+    ];
+    let mut minus = vec![
+        Signature::exact(&[Date, Date], Integer, |arguments, _| {
+            Ok(Value::Integer(date(&arguments[0]) - date(&arguments[1])))
+        }),
+        Signature::exact(&[Date, Integer], Date, |arguments, _| {
+            date_value(date(&arguments[0]) - integer(&arguments[1]))
+        }),
+        Signature::exact(&[Date, Interval], Timestamp, |arguments, _| {
+            timestamp_plus_interval(&arguments[0], interval(&arguments[1]).negate())
+        }),
+        Signature::exact(&[Time, Time], Interval, |arguments, _| {
+            Ok(Value::Interval(self::Interval {
+                micros: time(&arguments[0]) - time(&arguments[1]),
+                ..self::Interval::default()
+            }))
+        }),
+        Signature::exact(&[Time, Interval], Time, |arguments, _| {
+            time_plus_interval(&arguments[0], interval(&arguments[1]).negate())
+        }),
+        Signature::exact(&[Interval, Interval], Interval, |arguments, _| {
+            let difference = interval(&arguments[0]) + interval(&arguments[1]).negate();
+            Ok(Value::Interval(difference))
+        }),
+    ];
+    let mut extract = vec![
+        Signature::exact(&[Text, Time], Decimal(None), |arguments, _| {
+            field_value(extract_time(&field(&arguments[0]), time(&arguments[1]))?)
+        }),
+        Signature::exact(&[Text, Interval], Decimal(None), |arguments, _| {
+            field_value(extract_interval(
+                &field(&arguments[0]),
+                interval(&arguments[1]),
+            )?)
+        }),
+    ];
+    let mut date_trunc = vec![Signature::exact(
+        &[Text, Interval],
+        Interval,
+        |arguments, _| {
+            let interval = trunc_interval(&field(&arguments[0]), interval(&arguments[1]))?;
+            Ok(Value::Interval(interval))
+        },
+    )];
+    // timestamps with and without time zone alike
+    for timestamp_type in [Timestamp, TimestampTz] {
+        plus.extend([
+            Signature::exact(
+                &[timestamp_type, Interval],
+                timestamp_type,
+                |arguments, _| timestamp_plus_interval(&arguments[0], interval(&arguments[1])),
+            ),
+            Signature::exact(
+                &[Interval, timestamp_type],
+                timestamp_type,
+                |arguments, _| timestamp_plus_interval(&arguments[1], interval(&arguments[0])),
+            ),
+        ]);
+        minus.extend([
+            Signature::exact(
+                &[timestamp_type, timestamp_type],
+                Interval,
+                |arguments, _| {
+                    let micros = timestamp(&arguments[0]) - timestamp(&arguments[1]);
+                    Ok(Value::Interval(self::Interval {
+                        months: 0,
+                        days: micros / MICROS_PER_DAY,
+                        micros: micros % MICROS_PER_DAY,
+                    }))
+                },
+            ),
+            Signature::exact(
+                &[timestamp_type, Interval],
+                timestamp_type,
+                |arguments, _| {
+                    timestamp_plus_interval(&arguments[0], interval(&arguments[1]).negate())
+                },
+            ),
+        ]);
+        extract.push(Signature::exact(
+            &[Text, timestamp_type],
+            Decimal(None),
+            |arguments, _| {
+                field_value(extract_timestamp(
+                    &field(&arguments[0]),
+                    timestamp(&arguments[1]),
+                )?)
+            },
+        ));
+        date_trunc.push(Signature::exact(
+            &[Text, timestamp_type],
+            timestamp_type,
+            |arguments, _| {
+                let micros = trunc_timestamp(&field(&arguments[0]), timestamp(&arguments[1]))?;
+                Ok(timestamp_like(&arguments[1], micros))
+            },
+        ));
+    }
+    vec![
+        ScalarFunction::new("now", vec![Signature::exact(&[], Timestamp, now)])
+            .volatility(Volatility::Stable),
+        ScalarFunction::new("extract", extract),
+        ScalarFunction::new("date_trunc", date_trunc),
+        ScalarFunction::new("+", plus),
+        ScalarFunction::new("-", minus),
+        ScalarFunction::new(
+            "*",
+            vec![
+                Signature::exact(&[Interval, Double], Interval, |arguments, _| {
+                    scale(&arguments[0], number_argument(&arguments[1]))
+                }),
+                Signature::exact(&[Double, Interval], Interval, |arguments, _| {
+                    scale(&arguments[1], number_argument(&arguments[0]))
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "/",
+            vec![Signature::exact(
+                &[Interval, Double],
+                Interval,
+                |arguments, _| match number_argument(&arguments[1]) {
+                    0.0 => Err(ExecutionError::DivisionByZero),
+                    divisor => scale(&arguments[0], 1.0 / divisor),
+                },
+            )],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::tests::{call, call_typed};
+
+    /// The value of the text as a value of the type.
+    fn value(text: &str, data_type: DataType) -> Value {
+        crate::value::temporal::parse(text, data_type).unwrap()
+    }
+
+    fn typed(name: &str, arguments: &[(&str, DataType)]) -> Result<Value, ExecutionError> {
+        let values: Vec<_> = arguments
+            .iter()
+            .map(|(text, _)| Value::from(*text))
+            .collect();
+        let types: Vec<_> = arguments.iter().map(|(_, t)| Some(*t)).collect();
+        call_typed(name, &values, &types)
+    }
+
+    #[test]
+    fn returns_statement_start() {
+        assert_eq!(
+            call("now", &[]),
+            Ok(value("2024-03-01 12:30:00", DataType::Timestamp))
+        );
+    }
+
+    #[test]
+    fn extracts_fields() {
+        use DataType::{Interval, Text, Time, Timestamp};
+        let extract = |field: &str, value: &str, data_type| {
+            typed("extract", &[(field, Text), (value, data_type)])
+        };
+        let timestamp = "2024-03-01 13:05:30.25";
+        assert_eq!(extract("year", timestamp, Timestamp), Ok(2024.0.into()));
+        assert_eq!(extract("MONTH", timestamp, Timestamp), Ok(3.0.into()));
+        assert_eq!(extract("second", timestamp, Timestamp), Ok(30.25.into()));
+        assert_eq!(extract("dow", timestamp, Timestamp), Ok(5.0.into()));
+        assert_eq!(extract("doy", timestamp, Timestamp), Ok(61.0.into()));
+        assert_eq!(extract("quarter", timestamp, Timestamp), Ok(1.0.into()));
+        assert_eq!(
+            extract("epoch", "1970-01-02", Timestamp),
+            Ok(86400.0.into())
+        );
+        assert_eq!(extract("hour", "13:05", Time), Ok(13.0.into()));
+        assert_eq!(extract("day", "1 mon 3 days", Interval), Ok(3.0.into()));
+        assert_eq!(
+            extract("epoch", "1 day 01:00", Interval),
+            Ok(90000.0.into())
+        );
+        assert_eq!(
+            extract("fortnight", timestamp, Timestamp),
+            Err(ExecutionError::InvalidArgument(
+                "unit \"fortnight\" not recognized for type timestamp".to_string()
+            ))
+        );
+        // a date is a timestamp at midnight
+        assert_eq!(
+            call_typed(
+                "extract",
+                &["day".into(), "2024-03-01".into()],
+                &[None, Some(DataType::Date)]
+            ),
+            Ok(1.0.into())
+        );
+    }
+
+    #[test]
+    fn truncates() {
+        use DataType::{Interval, Text, Timestamp};
+        let trunc = |field: &str, value: &str, data_type| {
+            typed("date_trunc", &[(field, Text), (value, data_type)])
+        };
+        let timestamp = "2024-05-17 13:05:30.25";
+        assert_eq!(
+            trunc("year", timestamp, Timestamp),
+            Ok(value("2024-01-01 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            trunc("quarter", timestamp, Timestamp),
+            Ok(value("2024-04-01 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            trunc("month", timestamp, Timestamp),
+            Ok(value("2024-05-01 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            trunc("week", timestamp, Timestamp),
+            Ok(value("2024-05-13 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            trunc("hour", timestamp, Timestamp),
+            Ok(value("2024-05-17 13:00:00", Timestamp))
+        );
+        assert_eq!(
+            trunc("second", timestamp, Timestamp),
+            Ok(value("2024-05-17 13:05:30", Timestamp))
+        );
+        assert_eq!(
+            trunc("day", "3 days 04:05", Interval),
+            Ok(value("3 days", Interval))
+        );
+        assert!(matches!(
+            trunc("parsec", timestamp, Timestamp),
+            Err(ExecutionError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn computes_interval_arithmetic() {
+        use DataType::{Date, Double, Integer, Interval, Time, Timestamp};
+        assert_eq!(
+            call_typed(
+                "+",
+                &["2024-02-28".into(), 2.into()],
+                &[Some(Date), Some(Integer)]
+            ),
+            Ok(value("2024-03-01", Date))
+        );
+        assert_eq!(
+            typed("-", &[("2024-03-01", Date), ("2024-02-01", Date)]),
+            Ok(29.into())
+        );
+        assert_eq!(
+            typed("+", &[("2024-01-31", Date), ("1 month", Interval)]),
+            Ok(value("2024-02-29 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            typed(
+                "+",
+                &[("1 day 02:00", Interval), ("2024-03-01 23:00", Timestamp)]
+            ),
+            Ok(value("2024-03-03 01:00:00", Timestamp))
+        );
+        assert_eq!(
+            typed(
+                "-",
+                &[
+                    ("2024-03-01 12:00", Timestamp),
+                    ("2024-02-28 00:00", Timestamp)
+                ]
+            ),
+            Ok(value("2 days 12:00:00", Interval))
+        );
+        assert_eq!(
+            typed("-", &[("2024-03-01", Timestamp), ("1 year", Interval)]),
+            Ok(value("2023-03-01 00:00:00", Timestamp))
+        );
+        assert_eq!(
+            typed("+", &[("23:30", Time), ("01:00", Interval)]),
+            Ok(value("00:30:00", Time))
+        );
+        assert_eq!(
+            typed("-", &[("1 day", Interval), ("02:00", Interval)]),
+            Ok(value("1 day -02:00:00", Interval))
+        );
+        assert_eq!(
+            call_typed(
+                "*",
+                &["1 day".into(), 1.5.into()],
+                &[Some(Interval), Some(Double)]
+            ),
+            Ok(value("1 day 12:00:00", Interval))
+        );
+        assert_eq!(
+            call_typed(
+                "/",
+                &["1 day".into(), 0.into()],
+                &[Some(Interval), Some(Double)]
+            ),
+            Err(ExecutionError::DivisionByZero)
+        );
+        assert_eq!(
+            typed("+", &[("9999-12-31", Timestamp), ("1 day", Interval)]),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::Timestamp
+            })
+        );
+        assert_eq!(
+            call_typed(
+                "+",
+                &[Value::Null, "1 day".into()],
+                &[Some(Date), Some(Interval)]
+            ),
+            Ok(Value::Null)
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Mathematical functions, integers keep their type, decimals are computed exactly where
+//! the result is a decimal and the rest as floats.
+
+use crate::execution::expr::cast;
+use crate::execution::ExecutionError;
+use crate::functions::{ScalarFunction, Signature};
+use crate::value::{DataType, Decimal, Value};
+
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(value) => *value,
+        _ => 0,
+    }
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Float(value) => *value,
+        Value::Decimal(value) => value.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+fn decimal(value: &Value) -> Decimal {
+    match value {
+        Value::Decimal(value) => value.clone(),
+        Value::Integer(value) => Decimal::from_i64(*value),
+        Value::Float(value) => Decimal::from_f64(*value).unwrap_or_else(Decimal::zero),
+        _ => Decimal::zero(),
+    }
+}
+
+fn invalid(reason: &str) -> ExecutionError {
+    ExecutionError::InvalidArgument(reason.to_string())
+}
+
+/// The integer if it fits into the type.
+fn integer_value(value: Option<i64>, data_type: DataType) -> Result<Value, ExecutionError> {
+    match value {
+        Some(value) => cast(Value::Integer(value), data_type),
+        None => Err(ExecutionError::OutOfRange { data_type }),
+    }
+}
+
+fn float_value(value: f64) -> Result<Value, ExecutionError> {
+    Ok(Value::Float(value))
+}
+
+fn decimal_value(value: Option<Decimal>) -> Result<Value, ExecutionError> {
+    match value {
+        Some(value) => Ok(Value::Decimal(value)),
+        None => Err(ExecutionError::OutOfRange {
+            data_type: DataType::Decimal(None),
+        }),
+    }
+}
+
+/// The float result of a function of decimals as a decimal.
+fn float_decimal(result: Result<Value, ExecutionError>) -> Result<Value, ExecutionError> {
+    match result? {
+        Value::Float(value) => decimal_value(Decimal::from_f64(value)),
+        value => Ok(value),
+    }
+}
+
+fn power(base: f64, exponent: f64) -> Result<Value, ExecutionError> {
+    if base == 0.0 && exponent < 0.0 {
+        return Err(invalid("zero raised to a negative power is undefined"));
+    }
+    if base < 0.0 && exponent.fract() != 0.0 {
+        return Err(invalid(
+            "a negative number raised to a non-integer power yields a complex result",
+        ));
+    }
+    match base.powf(exponent) {
+        result if result.is_infinite() && base.is_finite() && exponent.is_finite() => {
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::Double,
+            })
+        }
+        result => float_value(result),
+    }
+}
+
+fn sqrt(value: f64) -> Result<Value, ExecutionError> {
+    if value < 0.0 {
+        return Err(invalid("cannot take square root of a negative number"));
+    }
+    float_value(value.sqrt())
+}
+
+pub(crate) fn functions() -> Vec<ScalarFunction> {
+    use DataType::{BigInt, Decimal, Double, Integer, Real, SmallInt};
+    let numeric = Decimal(None);
+    vec![
+        ScalarFunction::new(
+            "abs",
+            vec![
+                Signature::exact(&[SmallInt], SmallInt, |arguments, _| {
+                    integer_value(integer(&arguments[0]).checked_abs(), SmallInt)
+                }),
+                Signature::exact(&[Integer], Integer, |arguments, _| {
+                    integer_value(integer(&arguments[0]).checked_abs(), Integer)
+                }),
+                Signature::exact(&[BigInt], BigInt, |arguments, _| {
+                    integer_value(integer(&arguments[0]).checked_abs(), BigInt)
+                }),
+                Signature::exact(&[numeric], numeric, |arguments, _| {
+                    Ok(Value::Decimal(decimal(&arguments[0]).abs()))
+                }),
+                Signature::exact(&[Real], Real, |arguments, _| {
+                    float_value(float(&arguments[0]).abs())
+                }),
+                Signature::exact(&[Double], Double, |arguments, _| {
+                    float_value(float(&arguments[0]).abs())
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "round",
+            vec![
+                Signature::exact(&[Double], Double, |arguments, _| {
+                    float_value(float(&arguments[0]).round())
+                }),
+                Signature::exact(&[numeric], numeric, |arguments, _| {
+                    Ok(Value::Decimal(decimal(&arguments[0]).round(0)))
+                }),
+                Signature::exact(&[numeric, Integer], numeric, |arguments, _| {
+                    decimal_value(decimal(&arguments[0]).round_places(integer(&arguments[1])))
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "ceil",
+            vec![
+                Signature::exact(&[Double], Double, |arguments, _| {
+                    float_value(float(&arguments[0]).ceil())
+                }),
+                Signature::exact(&[numeric], numeric, |arguments, _| {
+                    Ok(Value::Decimal(decimal(&arguments[0]).ceil()))
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "floor",
+            vec![
+                Signature::exact(&[Double], Double, |arguments, _| {
+                    float_value(float(&arguments[0]).floor())
+                }),
+                Signature::exact(&[numeric], numeric, |arguments, _| {
+                    Ok(Value::Decimal(decimal(&arguments[0]).floor()))
+                }),
+            ],
+        ),
+        // the remainder has the sign of the dividend
+        ScalarFunction::new(
+            "mod",
+            vec![
+                Signature::exact(&[SmallInt, SmallInt], SmallInt, |arguments, _| {
+                    remainder(&arguments[0], &arguments[1])
+                }),
+                Signature::exact(&[Integer, Integer], Integer, |arguments, _| {
+                    remainder(&arguments[0], &arguments[1])
+                }),
+                Signature::exact(&[BigInt, BigInt], BigInt, |arguments, _| {
+                    remainder(&arguments[0], &arguments[1])
+                }),
+                Signature::exact(&[numeric, numeric], numeric, |arguments, _| {
+                    match decimal(&arguments[1]) {
+                        divisor if divisor.is_zero() => Err(ExecutionError::DivisionByZero),
+                        divisor => decimal_value(decimal(&arguments[0]).checked_rem(&divisor)),
+                    }
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "power",
+            vec![
+                Signature::exact(&[Double, Double], Double, |arguments, _| {
+                    power(float(&arguments[0]), float(&arguments[1]))
+                }),
+                Signature::exact(&[numeric, numeric], numeric, |arguments, _| {
+                    float_decimal(power(float(&arguments[0]), float(&arguments[1])))
+                }),
+            ],
+        ),
+        ScalarFunction::new(
+            "sqrt",
+            vec![
+                Signature::exact(&[Double], Double, |arguments, _| sqrt(float(&arguments[0]))),
+                Signature::exact(&[numeric], numeric, |arguments, _| {
+                    float_decimal(sqrt(float(&arguments[0])))
+                }),
+            ],
+        ),
+    ]
+}
+
+/// Remainder of integers of the same type, it always fits.
+fn remainder(dividend: &Value, divisor: &Value) -> Result<Value, ExecutionError> {
+    match integer(divisor) {
+        0 => Err(ExecutionError::DivisionByZero),
+        divisor => Ok(Value::Integer(integer(dividend).wrapping_rem(divisor))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::execution::ExecutionError;
+    use crate::functions::tests::{call, call_typed};
+    use crate::value::{DataType, Decimal, Value};
+
+    fn number(name: &str, arguments: &[Value]) -> Value {
+        call(name, arguments).unwrap()
+    }
+
+    #[test]
+    fn takes_absolute_values() {
+        assert_eq!(number("abs", &[(-3).into()]), 3.into());
+        assert_eq!(number("abs", &[(-2.5).into()]), 2.5.into());
+        assert_eq!(
+            call("abs", &[Value::Integer(i64::MIN)]),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::BigInt
+            })
+        );
+        assert_eq!(
+            call_typed(
+                "abs",
+                &[Value::Integer(-32768)],
+                &[Some(DataType::SmallInt)]
+            ),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::SmallInt
+            })
+        );
+    }
+
+    #[test]
+    fn rounds() {
+        assert_eq!(number("round", &[2.5.into()]), 3.0.into());
+        assert_eq!(number("round", &[(-2.5).into()]), (-3.0).into());
+        assert_eq!(number("round", &[7.into()]), 7.0.into());
+        // decimals are rounded exactly
+        assert_eq!(
+            number("round", &[1.23456.into(), 2.into()]),
+            Decimal::parse("1.23").unwrap().into()
+        );
+        assert_eq!(
+            number("round", &[1.23456.into(), 2.into()]).to_string(),
+            "1.23"
+        );
+        assert_eq!(
+            number("round", &[1234.5.into(), (-2).into()]),
+            1200.0.into()
+        );
+        assert_eq!(number("ceil", &[1.2.into()]), 2.0.into());
+        assert_eq!(number("ceil", &[(-1.2).into()]), (-1.0).into());
+        assert_eq!(number("floor", &[1.8.into()]), 1.0.into());
+        assert_eq!(number("floor", &[(-1.2).into()]), (-2.0).into());
+    }
+
+    #[test]
+    fn takes_remainders() {
+        assert_eq!(number("mod", &[7.into(), 3.into()]), 1.into());
+        assert_eq!(number("mod", &[(-7).into(), 3.into()]), (-1).into());
+        assert_eq!(number("mod", &[7.5.into(), 2.into()]), 1.5.into());
+        assert_eq!(
+            number("mod", &[Value::Integer(i64::MIN), (-1).into()]),
+            0.into()
+        );
+        assert_eq!(
+            call("mod", &[7.into(), 0.into()]),
+            Err(ExecutionError::DivisionByZero)
+        );
+        assert_eq!(
+            call("mod", &[7.5.into(), 0.into()]),
+            Err(ExecutionError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn raises_to_powers() {
+        assert_eq!(number("power", &[2.into(), 10.into()]), 1024.0.into());
+        assert_eq!(number("power", &[(-2).into(), 3.into()]), (-8.0).into());
+        assert_eq!(number("power", &[4.into(), 0.5.into()]), 2.0.into());
+        assert!(matches!(
+            call("power", &[0.into(), (-1).into()]),
+            Err(ExecutionError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            call("power", &[(-8).into(), 0.5.into()]),
+            Err(ExecutionError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            call("power", &[10.into(), 400.into()]),
+            Err(ExecutionError::OutOfRange {
+                data_type: DataType::Double
+            })
+        );
+    }
+
+    #[test]
+    fn takes_square_roots() {
+        assert_eq!(number("sqrt", &[16.into()]), 4.0.into());
+        assert_eq!(number("sqrt", &[2.25.into()]), 1.5.into());
+        assert_eq!(
+            call("sqrt", &[(-1).into()]),
+            Err(ExecutionError::InvalidArgument(
+                "cannot take square root of a negative number".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,597 @@
+//! Scalar, aggregate, window and table functions and the registry calls are resolved
+//! through. A database starts with the built-in functions, functions registered with it
+//! resolve the same way.
+//!
+//! A scalar function has signatures, each with parameter types, a result type and an
+//! implementation. A call takes the signature its arguments fit best:
+//!
+//! - arguments are implicitly cast the way they are in expressions, along the numeric
+//!   types and from a date to a timestamp; the signature with the fewest and shortest
+//!   casts wins
+//! - an argument of unknown type, `NULL` or a string literal, fits any parameter; ties
+//!   go to the signature which gives it the type of another argument, then to the one
+//!   which takes it as text
+//!
+//! `||` and the arithmetic of dates, times and intervals are operators of the registry,
+//! named by their symbol. Functions are strict unless they say otherwise: a `NULL`
+//! argument makes the result `NULL` without calling the implementation. They are
+//! immutable unless they say otherwise too, so the optimizer computes calls with
+//! constant arguments once when the query is planned.
+
+pub mod aggregate;
+pub mod conditional;
+pub mod datetime;
+pub mod math;
+pub mod string;
+pub mod table;
+pub mod window;
+
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::functions::aggregate::AggregateFunction;
+use crate::functions::table::TableFunction;
+use crate::functions::window::WindowFunction;
+use crate::parser::expr::Operator;
+use crate::value::{DataType, Value};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Computes the result of a call from the arguments, which have the parameter types of
+/// the signature.
+pub type ScalarImplementation =
+    Arc<dyn Fn(&[Value], &ExecutionContext) -> Result<Value, ExecutionError> + Send + Sync>;
+
+/// Whether the result of a function depends on more than its arguments, as in PostgreSQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Volatility {
+    /// The same arguments always give the same result, a call with constant arguments
+    /// is computed when the query is planned
+    Immutable,
+    /// The result doesn't change within a statement, e.g. `now()`
+    Stable,
+    /// The result may change from one call to the next
+    Volatile,
+}
+
+/// What a call with a `NULL` argument does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullHandling {
+    /// The result of a scalar function is `NULL` without calling it, an aggregate skips
+    /// the row and a table function returns no rows
+    Strict,
+    /// The function is called with the `NULL`s
+    CalledOnNull,
+}
+
+impl NullHandling {
+    /// Whether a call with the arguments is skipped.
+    pub fn skips(self, arguments: &[Value]) -> bool {
+        self == NullHandling::Strict && arguments.contains(&Value::Null)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterType {
+    Exact(DataType),
+    /// Any type, the argument is passed as it is
+    Any,
+    /// Any type, the arguments of all `Element` parameters are cast to their common type
+    Element,
+}
+
+#[derive(Clone)]
+pub struct Signature {
+    pub parameters: Vec<ParameterType>,
+    /// The last parameter may be repeated
+    pub variadic: bool,
+    /// `Element` for the common type of the `Element` arguments
+    pub returns: ParameterType,
+    pub implementation: ScalarImplementation,
+}
+
+impl Signature {
+    pub fn new(
+        parameters: Vec<ParameterType>,
+        returns: ParameterType,
+        implementation: impl Fn(&[Value], &ExecutionContext) -> Result<Value, ExecutionError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Signature {
+            parameters,
+            variadic: false,
+            returns,
+            implementation: Arc::new(implementation),
+        }
+    }
+
+    /// Signature with parameters and a result of fixed types.
+    pub fn exact(
+        parameters: &[DataType],
+        returns: DataType,
+        implementation: impl Fn(&[Value], &ExecutionContext) -> Result<Value, ExecutionError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Signature::new(
+            parameters
+                .iter()
+                .copied()
+                .map(ParameterType::Exact)
+                .collect(),
+            ParameterType::Exact(returns),
+            implementation,
+        )
+    }
+
+    pub fn variadic(self) -> Self {
+        Signature {
+            variadic: true,
+            ..self
+        }
+    }
+
+    /// Parameter types of a call with the number of arguments, `None` if it has too few
+    /// or too many.
+    fn parameters(&self, arguments: usize) -> Option<Vec<ParameterType>> {
+        match self.parameters.split_last() {
+            Some((last, _)) if self.variadic && arguments >= self.parameters.len() => {
+                let mut parameters = self.parameters.clone();
+                parameters.resize(arguments, *last);
+                Some(parameters)
+            }
+            _ if arguments == self.parameters.len() => Some(self.parameters.clone()),
+            _ => None,
+        }
+    }
+}
+
+pub struct ScalarFunction {
+    pub name: String,
+    pub signatures: Vec<Signature>,
+    pub volatility: Volatility,
+    pub null_handling: NullHandling,
+}
+
+impl ScalarFunction {
+    /// Immutable and strict function.
+    pub fn new(name: &str, signatures: Vec<Signature>) -> Self {
+        ScalarFunction {
+            name: name.to_string(),
+            signatures,
+            volatility: Volatility::Immutable,
+            null_handling: NullHandling::Strict,
+        }
+    }
+
+    pub fn volatility(self, volatility: Volatility) -> Self {
+        ScalarFunction { volatility, ..self }
+    }
+
+    pub fn null_handling(self, null_handling: NullHandling) -> Self {
+        ScalarFunction {
+            null_handling,
+            ..self
+        }
+    }
+
+    /// The signature a call with arguments of the types resolves to, `None` standing for
+    /// an unknown type.
+    pub fn resolve(
+        self: &Arc<Self>,
+        arguments: &[Option<DataType>],
+    ) -> Result<Resolved, ResolveError> {
+        let known: Vec<_> = arguments.iter().flatten().copied().collect();
+        let mut best: Vec<(Resolved, Rank)> = vec![];
+        for (index, signature) in self.signatures.iter().enumerate() {
+            let Some((types, returns, cost)) = fit(signature, arguments) else {
+                continue;
+            };
+            let unknown = || {
+                arguments
+                    .iter()
+                    .zip(&types)
+                    .filter(|(argument, _)| argument.is_none())
+                    .filter_map(|(_, data_type)| *data_type)
+            };
+            let rank = (
+                cost,
+                Reverse(unknown().filter(|t| known.contains(t)).count()),
+                Reverse(unknown().filter(DataType::is_string).count()),
+            );
+            if best.first().is_some_and(|(_, best)| rank > *best) {
+                continue;
+            }
+            if best.first().is_some_and(|(_, best)| rank < *best) {
+                best.clear();
+            }
+            let resolved = Resolved {
+                function: self.clone(),
+                signature: index,
+                arguments: types,
+                returns,
+            };
+            best.push((resolved, rank));
+        }
+        match best.len() {
+            0 => Err(ResolveError::Undefined),
+            1 => Ok(best.remove(0).0),
+            _ => Err(ResolveError::Ambiguous),
+        }
+    }
+
+    /// Result of the signature for the arguments.
+    pub fn call(
+        &self,
+        signature: usize,
+        arguments: &[Value],
+        context: &ExecutionContext,
+    ) -> Result<Value, ExecutionError> {
+        if self.null_handling.skips(arguments) {
+            return Ok(Value::Null);
+        }
+        (self.signatures[signature].implementation)(arguments, context)
+    }
+}
+
+impl fmt::Debug for ScalarFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ScalarFunction({})", self.name)
+    }
+}
+
+/// Signature a call resolved to.
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub function: Arc<ScalarFunction>,
+    pub signature: usize,
+    /// Types the arguments are cast to, `None` for an argument of unknown type which
+    /// keeps it
+    pub arguments: Vec<Option<DataType>>,
+    /// `None` if the result has the type of arguments of unknown type
+    pub returns: Option<DataType>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ResolveError {
+    /// No signature of a function of the name fits the arguments
+    Undefined,
+    /// More than one signature fits the arguments equally well
+    Ambiguous,
+}
+
+/// Function of the registry a call refers to.
+#[derive(Debug, Clone)]
+pub enum FunctionRef {
+    Scalar(Arc<ScalarFunction>),
+    Aggregate(Arc<dyn AggregateFunction>),
+    Window(Arc<dyn WindowFunction>),
+}
+
+#[derive(Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Arc<ScalarFunction>>,
+    aggregates: HashMap<String, Arc<dyn AggregateFunction>>,
+    windows: HashMap<String, Arc<dyn WindowFunction>>,
+    tables: HashMap<String, Arc<dyn TableFunction>>,
+}
+
+impl FunctionRegistry {
+    /// Registry of the built-in functions.
+    pub fn builtin() -> Self {
+        let mut registry = FunctionRegistry::default();
+        for function in string::functions()
+            .into_iter()
+            .chain(math::functions())
+            .chain(conditional::functions())
+            .chain(datetime::functions())
+        {
+            registry.register(function);
+        }
+        for function in aggregate::functions() {
+            registry.register_aggregate(function.into());
+        }
+        for function in window::functions() {
+            registry.register_window(function.into());
+        }
+        for function in table::functions() {
+            registry.register_table(function.into());
+        }
+        registry
+    }
+
+    /// Adds the function, it replaces a function of the same name.
+    pub fn register(&mut self, function: ScalarFunction) {
+        self.functions
+            .insert(function.name.clone(), Arc::new(function));
+    }
+
+    /// Adds the aggregate, it replaces an aggregate of the same name.
+    pub fn register_aggregate(&mut self, function: Arc<dyn AggregateFunction>) {
+        self.aggregates
+            .insert(function.name().to_string(), function);
+    }
+
+    /// Adds the window function, it replaces a window function of the same name.
+    pub fn register_window(&mut self, function: Arc<dyn WindowFunction>) {
+        self.windows.insert(function.name().to_string(), function);
+    }
+
+    /// Adds the table function, it replaces a table function of the same name.
+    pub fn register_table(&mut self, function: Arc<dyn TableFunction>) {
+        self.tables.insert(function.name().to_string(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<ScalarFunction>> {
+        self.functions.get(name)
+    }
+
+    pub fn aggregate(&self, name: &str) -> Option<&Arc<dyn AggregateFunction>> {
+        self.aggregates.get(name)
+    }
+
+    pub fn window(&self, name: &str) -> Option<&Arc<dyn WindowFunction>> {
+        self.windows.get(name)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Arc<dyn TableFunction>> {
+        self.tables.get(name)
+    }
+
+    /// Function a call of the name refers to: with `OVER` a window function or else an
+    /// aggregate, without it an aggregate or else a scalar function. A function of
+    /// another kind is the last resort, the analyser then says what is wrong with the
+    /// call.
+    pub fn lookup(&self, name: &str, over: bool) -> Option<FunctionRef> {
+        let scalar = || self.get(name).cloned().map(FunctionRef::Scalar);
+        let aggregate = || self.aggregate(name).cloned().map(FunctionRef::Aggregate);
+        let window = || self.window(name).cloned().map(FunctionRef::Window);
+        match over {
+            true => window().or_else(aggregate).or_else(scalar),
+            false => aggregate().or_else(scalar).or_else(window),
+        }
+    }
+
+    /// The signature of the function a call with arguments of the types resolves to,
+    /// `None` standing for an unknown type.
+    pub fn resolve(
+        &self,
+        name: &str,
+        arguments: &[Option<DataType>],
+    ) -> Result<Resolved, ResolveError> {
+        self.get(name)
+            .ok_or(ResolveError::Undefined)?
+            .resolve(arguments)
+    }
+}
+
+/// How well a signature fits a call, the lower the better: the cost of the casts, then
+/// the number of arguments of unknown type which take the type of another argument, and
+/// which take text.
+type Rank = (u32, Reverse<usize>, Reverse<usize>);
+
+/// Types the arguments are cast to, the result type and the cost of the casts of a call
+/// of the signature. `None` if the arguments don't fit.
+fn fit(
+    signature: &Signature,
+    arguments: &[Option<DataType>],
+) -> Option<(Vec<Option<DataType>>, Option<DataType>, u32)> {
+    let parameters = signature.parameters(arguments.len())?;
+    let mut element: Option<DataType> = None;
+    for (argument, parameter) in arguments.iter().zip(&parameters) {
+        if let (Some(argument), ParameterType::Element) = (argument, parameter) {
+            element = Some(match element {
+                Some(element) => element.common_type(*argument)?,
+                None => *argument,
+            });
+        }
+    }
+    let mut cost = 0;
+    let mut types = vec![];
+    for (argument, parameter) in arguments.iter().zip(&parameters) {
+        let target = match parameter {
+            ParameterType::Exact(data_type) => Some(*data_type),
+            ParameterType::Any => *argument,
+            ParameterType::Element => element,
+        };
+        if let (Some(argument), Some(target)) = (argument, target) {
+            cost += match parameter {
+                // an exact match is better than taking any type
+                ParameterType::Any => 1,
+                _ => coercion_cost(*argument, target)?,
+            };
+        }
+        types.push(target);
+    }
+    let returns = match signature.returns {
+        ParameterType::Exact(data_type) => Some(data_type),
+        _ => element,
+    };
+    Some((types, returns, cost))
+}
+
+/// How far a value is implicitly cast from one type to the other, `None` if it isn't.
+fn coercion_cost(from: DataType, to: DataType) -> Option<u32> {
+    match (from, to) {
+        (from, to) if from == to => Some(0),
+        (from, to) if from.is_string() && to.is_string() => Some(0),
+        (DataType::Decimal(_), DataType::Decimal(_)) => Some(0),
+        (DataType::Date | DataType::Timestamp, DataType::TimestampTz) => Some(2),
+        (DataType::Date, DataType::Timestamp) => Some(1),
+        (from, to) => {
+            let (from, to) = (from.numeric_rank()?, to.numeric_rank()?);
+            (from <= to).then(|| u32::from(to - from))
+        }
+    }
+}
+
+/// The built-in functions.
+pub fn builtins() -> &'static FunctionRegistry {
+    static BUILTINS: OnceLock<FunctionRegistry> = OnceLock::new();
+    BUILTINS.get_or_init(FunctionRegistry::builtin)
+}
+
+/// Name of the registry function of a binary operator: `||`, and arithmetic with a
+/// date, a time or an interval operand. `None` for the operators the executor computes
+/// itself.
+pub fn operator_function(
+    operator: Operator,
+    left: Option<DataType>,
+    right: Option<DataType>,
+) -> Option<&'static str> {
+    let temporal = |data_type: Option<DataType>| {
+        data_type.is_some_and(|t| t.is_temporal() || t == DataType::Interval)
+    };
+    match operator {
+        Operator::Concat => Some("||"),
+        Operator::Plus if temporal(left) || temporal(right) => Some("+"),
+        Operator::Minus if temporal(left) || temporal(right) => Some("-"),
+        Operator::Multiply if temporal(left) || temporal(right) => Some("*"),
+        Operator::Divide if temporal(left) || temporal(right) => Some("/"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+    use crate::execution::pattern::PatternCache;
+    use crate::execution::spill::QueryMemory;
+    use crate::storage::MemoryStore;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Result of a call of the built-in function with the arguments, their types are
+    /// those of the values.
+    pub(crate) fn call(name: &str, arguments: &[Value]) -> Result<Value, ExecutionError> {
+        let types: Vec<_> = arguments
+            .iter()
+            .map(|argument| match argument {
+                // as numeric literals are
+                Value::Integer(value) if i32::try_from(*value).is_ok() => Some(DataType::Integer),
+                Value::Float(_) => Some(DataType::Decimal(None)),
+                argument => argument.data_type(),
+            })
+            .collect();
+        call_typed(name, arguments, &types)
+    }
+
+    /// Result of a call with arguments of the types, they are cast to the types of the
+    /// parameters as the analyser does.
+    pub(crate) fn call_typed(
+        name: &str,
+        arguments: &[Value],
+        types: &[Option<DataType>],
+    ) -> Result<Value, ExecutionError> {
+        let resolved = builtins()
+            .resolve(name, types)
+            .unwrap_or_else(|error| panic!("{}{:?}: {:?}", name, types, error));
+        let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+        let context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            // 2024-03-01 12:30:00
+            start_time: UNIX_EPOCH + Duration::from_secs(1_709_296_200),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let arguments = arguments
+            .iter()
+            .zip(&resolved.arguments)
+            .map(|(argument, parameter)| match parameter {
+                Some(data_type) => crate::execution::expr::cast(argument.clone(), *data_type),
+                None => Ok(argument.clone()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        resolved
+            .function
+            .call(resolved.signature, &arguments, &context)
+    }
+
+    fn resolve(
+        name: &str,
+        arguments: &[Option<DataType>],
+    ) -> Result<(Vec<Option<DataType>>, Option<DataType>), ResolveError> {
+        builtins()
+            .resolve(name, arguments)
+            .map(|resolved| (resolved.arguments, resolved.returns))
+    }
+
+    #[test]
+    fn resolves_overloads() {
+        use DataType::*;
+        // exact, then the closest numeric type
+        assert_eq!(
+            resolve("abs", &[Some(Integer)]),
+            Ok((vec![Some(Integer)], Some(Integer)))
+        );
+        assert_eq!(
+            resolve("abs", &[Some(TinyInt)]),
+            Ok((vec![Some(SmallInt)], Some(SmallInt)))
+        );
+        assert_eq!(
+            resolve("round", &[Some(BigInt), Some(Integer)]),
+            Ok((
+                vec![Some(Decimal(None)), Some(Integer)],
+                Some(Decimal(None))
+            ))
+        );
+        assert_eq!(
+            resolve("extract", &[None, Some(Date)]),
+            Ok((vec![Some(Text), Some(Timestamp)], Some(Decimal(None))))
+        );
+        // element parameters take the common type
+        assert_eq!(
+            resolve("coalesce", &[Some(Integer), None, Some(Double)]),
+            Ok((vec![Some(Double), Some(Double), Some(Double)], Some(Double)))
+        );
+        assert_eq!(
+            resolve("coalesce", &[None, None]),
+            Ok((vec![None, None], None))
+        );
+        // unknown arguments prefer the type of the other operand, then text
+        assert_eq!(
+            resolve("-", &[Some(Timestamp), None]),
+            Ok((vec![Some(Timestamp), Some(Timestamp)], Some(Interval)))
+        );
+        assert_eq!(
+            resolve("||", &[None, None]),
+            Ok((vec![Some(Text), Some(Text)], Some(Text)))
+        );
+        assert_eq!(
+            resolve("||", &[Some(Text), Some(Integer)]),
+            Ok((vec![Some(Text), Some(Integer)], Some(Text)))
+        );
+        assert_eq!(resolve("abs", &[None]), Err(ResolveError::Ambiguous));
+        assert_eq!(resolve("abs", &[Some(Text)]), Err(ResolveError::Undefined));
+        assert_eq!(resolve("lower", &[]), Err(ResolveError::Undefined));
+        assert_eq!(
+            resolve("coalesce", &[Some(Integer), Some(Boolean)]),
+            Err(ResolveError::Undefined)
+        );
+        assert_eq!(
+            resolve("no_such_function", &[]),
+            Err(ResolveError::Undefined)
+        );
+    }
+
+    #[test]
+    fn propagates_nulls() {
+        assert_eq!(call("upper", &[Value::Null]), Ok(Value::Null));
+        assert_eq!(
+            call("replace", &["abc".into(), Value::Null, "x".into()]),
+            Ok(Value::Null)
+        );
+        // functions which take `NULL`s
+        assert_eq!(call("concat", &["a".into(), Value::Null]), Ok("a".into()));
+        assert_eq!(
+            call("coalesce", &[Value::Null, 2.into()]),
+            Ok(Value::Integer(2))
+        );
+    }
+}
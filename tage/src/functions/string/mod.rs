@@ -0,0 +1,275 @@
+//! String functions, positions and lengths count characters.
+
+use crate::execution::expr::text;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::functions::{NullHandling, ParameterType, ScalarFunction, Signature};
+use crate::value::{DataType, Value};
+
+fn string(value: &Value) -> &str {
+    match value {
+        Value::String(text) => text,
+        _ => "",
+    }
+}
+
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(value) => *value,
+        _ => 0,
+    }
+}
+
+fn text_value(value: String) -> Result<Value, ExecutionError> {
+    Ok(Value::String(value))
+}
+
+/// Characters from the 1-based position, all of the rest if there is no length.
+fn substring(text: &str, start: i64, length: Option<i64>) -> Result<Value, ExecutionError> {
+    let end = match length {
+        Some(length) if length < 0 => {
+            return Err(ExecutionError::InvalidArgument(
+                "negative substring length not allowed".to_string(),
+            ))
+        }
+        Some(length) => start.saturating_add(length),
+        None => i64::MAX,
+    };
+    let (start, end) = (start.max(1) - 1, end.max(1) - 1);
+    let taken = end.saturating_sub(start).max(0);
+    text_value(
+        text.chars()
+            .skip(start as usize)
+            .take(taken as usize)
+            .collect(),
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Trim {
+    Both,
+    Leading,
+    Trailing,
+}
+
+/// The text without the characters, spaces by default, at the start, the end or both.
+fn trim(arguments: &[Value], side: Trim) -> Result<Value, ExecutionError> {
+    let characters = arguments.get(1).map_or(" ", string);
+    let trimmed = |c: char| characters.contains(c);
+    let text = string(&arguments[0]);
+    text_value(
+        match side {
+            Trim::Both => text.trim_matches(trimmed),
+            Trim::Leading => text.trim_start_matches(trimmed),
+            Trim::Trailing => text.trim_end_matches(trimmed),
+        }
+        .to_string(),
+    )
+}
+
+/// `btrim`, `ltrim` and `rtrim`, which `trim([leading | trailing | both] ...)` calls.
+fn trim_function(
+    name: &str,
+    implementation: fn(&[Value], &ExecutionContext) -> Result<Value, ExecutionError>,
+) -> ScalarFunction {
+    use DataType::Text;
+    ScalarFunction::new(
+        name,
+        vec![
+            Signature::exact(&[Text], Text, implementation),
+            Signature::exact(&[Text, Text], Text, implementation),
+        ],
+    )
+}
+
+pub(crate) fn functions() -> Vec<ScalarFunction> {
+    use DataType::{Integer, Text};
+    vec![
+        ScalarFunction::new(
+            "lower",
+            vec![Signature::exact(&[Text], Text, |arguments, _| {
+                text_value(string(&arguments[0]).to_lowercase())
+            })],
+        ),
+        ScalarFunction::new(
+            "upper",
+            vec![Signature::exact(&[Text], Text, |arguments, _| {
+                text_value(string(&arguments[0]).to_uppercase())
+            })],
+        ),
+        ScalarFunction::new(
+            "substring",
+            vec![
+                Signature::exact(&[Text, Integer], Text, |arguments, _| {
+                    substring(string(&arguments[0]), integer(&arguments[1]), None)
+                }),
+                Signature::exact(&[Text, Integer, Integer], Text, |arguments, _| {
+                    let length = Some(integer(&arguments[2]));
+                    substring(string(&arguments[0]), integer(&arguments[1]), length)
+                }),
+            ],
+        ),
+        trim_function("btrim", |arguments, _| trim(arguments, Trim::Both)),
+        trim_function("ltrim", |arguments, _| trim(arguments, Trim::Leading)),
+        trim_function("rtrim", |arguments, _| trim(arguments, Trim::Trailing)),
+        // `position(substring in text)`
+        ScalarFunction::new(
+            "position",
+            vec![Signature::exact(&[Text, Text], Integer, |arguments, _| {
+                let (needle, haystack) = (string(&arguments[0]), string(&arguments[1]));
+                let position = haystack
+                    .find(needle)
+                    .map_or(0, |index| haystack[..index].chars().count() as i64 + 1);
+                Ok(Value::Integer(position))
+            })],
+        ),
+        ScalarFunction::new(
+            "replace",
+            vec![Signature::exact(
+                &[Text, Text, Text],
+                Text,
+                |arguments, _| {
+                    let (text, from) = (string(&arguments[0]), string(&arguments[1]));
+                    match from.is_empty() {
+                        true => text_value(text.to_string()),
+                        false => text_value(text.replace(from, string(&arguments[2]))),
+                    }
+                },
+            )],
+        ),
+        ScalarFunction::new(
+            "length",
+            vec![Signature::exact(&[Text], Integer, |arguments, _| {
+                Ok(Value::Integer(string(&arguments[0]).chars().count() as i64))
+            })],
+        ),
+        // `NULL`s are left out
+        ScalarFunction::new(
+            "concat",
+            vec![Signature::new(
+                vec![ParameterType::Any],
+                ParameterType::Exact(Text),
+                |arguments, _| {
+                    let parts = arguments
+                        .iter()
+                        .filter(|argument| **argument != Value::Null);
+                    text_value(parts.map(text).collect())
+                },
+            )
+            .variadic()],
+        )
+        .null_handling(NullHandling::CalledOnNull),
+        // one operand may have any type, it is concatenated as text
+        ScalarFunction::new(
+            "||",
+            vec![
+                Signature::exact(&[Text, Text], Text, concat),
+                Signature::new(
+                    vec![ParameterType::Exact(Text), ParameterType::Any],
+                    ParameterType::Exact(Text),
+                    concat,
+                ),
+                Signature::new(
+                    vec![ParameterType::Any, ParameterType::Exact(Text)],
+                    ParameterType::Exact(Text),
+                    concat,
+                ),
+            ],
+        ),
+    ]
+}
+
+fn concat(arguments: &[Value], _: &ExecutionContext) -> Result<Value, ExecutionError> {
+    text_value(format!("{}{}", text(&arguments[0]), text(&arguments[1])))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::execution::ExecutionError;
+    use crate::functions::tests::call;
+    use crate::value::Value;
+
+    fn text(name: &str, arguments: &[Value]) -> Value {
+        call(name, arguments).unwrap()
+    }
+
+    #[test]
+    fn changes_case() {
+        assert_eq!(text("lower", &["ÄbC".into()]), "äbc".into());
+        assert_eq!(text("upper", &["äbc".into()]), "ÄBC".into());
+    }
+
+    #[test]
+    fn takes_substrings() {
+        assert_eq!(
+            text("substring", &["hello".into(), 2.into(), 3.into()]),
+            "ell".into()
+        );
+        assert_eq!(text("substring", &["hello".into(), 3.into()]), "llo".into());
+        assert_eq!(
+            text("substring", &["hello".into(), 0.into(), 3.into()]),
+            "he".into()
+        );
+        assert_eq!(
+            text("substring", &["hello".into(), (-5).into(), 3.into()]),
+            "".into()
+        );
+        assert_eq!(
+            text("substring", &["héllo".into(), 2.into(), 2.into()]),
+            "él".into()
+        );
+        assert_eq!(text("substring", &["hello".into(), 9.into()]), "".into());
+        assert_eq!(
+            call("substring", &["hello".into(), 1.into(), (-1).into()]),
+            Err(ExecutionError::InvalidArgument(
+                "negative substring length not allowed".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn trims() {
+        assert_eq!(text("btrim", &["  a b  ".into()]), "a b".into());
+        assert_eq!(text("ltrim", &["  a  ".into()]), "a  ".into());
+        assert_eq!(text("rtrim", &["  a  ".into()]), "  a".into());
+        assert_eq!(text("btrim", &["xyaxy".into(), "yx".into()]), "a".into());
+        assert_eq!(text("ltrim", &["xxa".into(), "x".into()]), "a".into());
+    }
+
+    #[test]
+    fn finds_positions() {
+        assert_eq!(text("position", &["lo".into(), "hello".into()]), 4.into());
+        assert_eq!(text("position", &["lo".into(), "héllo".into()]), 4.into());
+        assert_eq!(text("position", &["x".into(), "hello".into()]), 0.into());
+        assert_eq!(text("position", &["".into(), "hello".into()]), 1.into());
+    }
+
+    #[test]
+    fn replaces() {
+        assert_eq!(
+            text("replace", &["abcabc".into(), "b".into(), "xx".into()]),
+            "axxcaxxc".into()
+        );
+        assert_eq!(
+            text("replace", &["abc".into(), "".into(), "x".into()]),
+            "abc".into()
+        );
+    }
+
+    #[test]
+    fn measures_length() {
+        assert_eq!(text("length", &["héllo".into()]), 5.into());
+        assert_eq!(text("length", &["".into()]), 0.into());
+    }
+
+    #[test]
+    fn concatenates() {
+        assert_eq!(
+            text("concat", &["a".into(), 1.into(), Value::Null, true.into()]),
+            "a1true".into()
+        );
+        assert_eq!(text("concat", &[Value::Null]), "".into());
+        assert_eq!(text("||", &["a".into(), 1.into()]), "a1".into());
+        assert_eq!(text("||", &[1.5.into(), "a".into()]), "1.5a".into());
+        assert_eq!(call("||", &["a".into(), Value::Null]), Ok(Value::Null));
+    }
+}
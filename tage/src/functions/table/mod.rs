@@ -0,0 +1,165 @@
+//! Table functions, called in `from` in place of a table, e.g.
+//! `select * from generate_series(1, 10, 2)`. The arguments are evaluated once
+//! when the scan opens, they can't reference the columns of other tables.
+
+use crate::execution::{ExecutionContext, ExecutionError, Row};
+use crate::functions::{NullHandling, ResolveError};
+use crate::value::{DataType, Value};
+use std::fmt;
+
+/// Rows of a table function call, produced as they are read.
+pub type TableRows = Box<dyn Iterator<Item = Result<Row, ExecutionError>> + Send>;
+
+pub trait TableFunction: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Names and types of the columns of the result for arguments of the types, `None`
+    /// standing for an unknown type.
+    fn columns(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Vec<(String, DataType)>, ResolveError>;
+
+    /// A strict table function returns no rows for a `NULL` argument without being
+    /// called.
+    fn null_handling(&self) -> NullHandling {
+        NullHandling::Strict
+    }
+
+    /// Rows of the result, with the columns `columns` gives for the argument types.
+    fn call(
+        &self,
+        arguments: &[Value],
+        context: &ExecutionContext,
+    ) -> Result<TableRows, ExecutionError>;
+}
+
+impl fmt::Debug for dyn TableFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TableFunction({})", self.name())
+    }
+}
+
+pub(crate) fn functions() -> Vec<Box<dyn TableFunction>> {
+    vec![Box::new(GenerateSeries)]
+}
+
+/// `generate_series(start, stop [, step])`, the integers from `start` to `stop` by `step`,
+/// which is 1 unless given.
+struct GenerateSeries;
+
+impl TableFunction for GenerateSeries {
+    fn name(&self) -> &str {
+        "generate_series"
+    }
+
+    fn columns(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Vec<(String, DataType)>, ResolveError> {
+        if !matches!(arguments.len(), 2 | 3) {
+            return Err(ResolveError::Undefined);
+        }
+        let mut data_type = DataType::Integer;
+        for argument in arguments.iter().flatten() {
+            if !argument.is_integer() {
+                return Err(ResolveError::Undefined);
+            }
+            data_type = data_type
+                .common_type(*argument)
+                .ok_or(ResolveError::Undefined)?;
+        }
+        Ok(vec![(self.name().to_string(), data_type)])
+    }
+
+    fn call(&self, arguments: &[Value], _: &ExecutionContext) -> Result<TableRows, ExecutionError> {
+        let integer = |value: Option<&Value>| match value {
+            Some(Value::Integer(value)) => Ok(*value),
+            _ => Err(ExecutionError::InvalidArgument(
+                "arguments of generate_series must be integers".to_string(),
+            )),
+        };
+        let (start, stop) = (integer(arguments.first())?, integer(arguments.get(1))?);
+        let step = match arguments.get(2) {
+            Some(step) => integer(Some(step))?,
+            None => 1,
+        };
+        if step == 0 {
+            return Err(ExecutionError::InvalidArgument(
+                "step size cannot equal zero".to_string(),
+            ));
+        }
+        let series = std::iter::successors(Some(start), move |value| value.checked_add(step))
+            .take_while(move |value| match step > 0 {
+                true => *value <= stop,
+                false => *value >= stop,
+            })
+            .map(|value| Ok(vec![Value::Integer(value)]));
+        Ok(Box::new(series))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+    use crate::execution::pattern::PatternCache;
+    use crate::execution::spill::QueryMemory;
+    use crate::functions::builtins;
+    use crate::storage::MemoryStore;
+    use std::time::SystemTime;
+
+    fn series(arguments: &[i64]) -> Result<Vec<i64>, ExecutionError> {
+        let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+        let context = ExecutionContext {
+            store: &mut store,
+            catalog: &catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            start_time: SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let arguments: Vec<_> = arguments
+            .iter()
+            .map(|value| Value::Integer(*value))
+            .collect();
+        let rows = builtins()
+            .table("generate_series")
+            .unwrap()
+            .call(&arguments, &context)?;
+        rows.map(|row| match row?.as_slice() {
+            [Value::Integer(value)] => Ok(*value),
+            row => panic!("unexpected row {:?}", row),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn generates_series() {
+        assert_eq!(series(&[1, 4]), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(series(&[10, 1, -4]), Ok(vec![10, 6, 2]));
+        assert_eq!(series(&[3, 1]), Ok(vec![]));
+        assert_eq!(series(&[i64::MAX - 1, i64::MAX, 2]), Ok(vec![i64::MAX - 1]));
+        assert_eq!(
+            series(&[1, 2, 0]),
+            Err(ExecutionError::InvalidArgument(
+                "step size cannot equal zero".to_string()
+            ))
+        );
+        let columns = |arguments: &[Option<DataType>]| {
+            builtins()
+                .table("generate_series")
+                .unwrap()
+                .columns(arguments)
+        };
+        assert_eq!(
+            columns(&[Some(DataType::Integer), Some(DataType::BigInt)]),
+            Ok(vec![("generate_series".to_string(), DataType::BigInt)])
+        );
+        assert_eq!(
+            columns(&[Some(DataType::Text), None]),
+            Err(ResolveError::Undefined)
+        );
+    }
+}
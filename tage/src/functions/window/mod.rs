@@ -0,0 +1,365 @@
+//! Window functions, computed over the rows of a partition at once. Aggregates called
+//! with `OVER` are computed over the frame of every row by the window operator instead.
+
+use crate::execution::ExecutionError;
+use crate::execution::Row;
+use crate::functions::ResolveError;
+use crate::value::{DataType, Value};
+use std::fmt;
+use std::ops::Range;
+
+/// The rows of a partition in the order of the window.
+pub struct WindowPartition<'p> {
+    /// Argument values of every row
+    pub arguments: &'p [Row],
+    /// Rows of every row's peer group, the rows equal in the `ORDER BY` of the window
+    pub peers: &'p [Range<usize>],
+    /// Rows of every row's frame
+    pub frames: &'p [Range<usize>],
+}
+
+impl WindowPartition<'_> {
+    pub fn len(&self) -> usize {
+        self.arguments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arguments.is_empty()
+    }
+}
+
+pub trait WindowFunction: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Type of the result for arguments of the types, `None` standing for an unknown type.
+    fn return_type(&self, arguments: &[Option<DataType>])
+        -> Result<Option<DataType>, ResolveError>;
+
+    /// Result for every row of the partition.
+    fn evaluate(&self, partition: &WindowPartition) -> Result<Vec<Value>, ExecutionError>;
+}
+
+impl fmt::Debug for dyn WindowFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WindowFunction({})", self.name())
+    }
+}
+
+pub(crate) fn functions() -> Vec<Box<dyn WindowFunction>> {
+    vec![
+        Box::new(Ranking::RowNumber),
+        Box::new(Ranking::Rank),
+        Box::new(Ranking::DenseRank),
+        Box::new(Ntile),
+        Box::new(Offset::new("lag", -1)),
+        Box::new(Offset::new("lead", 1)),
+        Box::new(FrameValue::new("first_value", true)),
+        Box::new(FrameValue::new("last_value", false)),
+    ]
+}
+
+fn integer_argument(data_type: Option<DataType>) -> bool {
+    data_type.is_none_or(|t| t.is_integer())
+}
+
+/// `row_number`, `rank` and `dense_rank`, without arguments.
+enum Ranking {
+    RowNumber,
+    Rank,
+    DenseRank,
+}
+
+impl WindowFunction for Ranking {
+    fn name(&self) -> &str {
+        match self {
+            Ranking::RowNumber => "row_number",
+            Ranking::Rank => "rank",
+            Ranking::DenseRank => "dense_rank",
+        }
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [] => Ok(Some(DataType::BigInt)),
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn evaluate(&self, partition: &WindowPartition) -> Result<Vec<Value>, ExecutionError> {
+        let mut dense = 0;
+        Ok(partition
+            .peers
+            .iter()
+            .enumerate()
+            .map(|(row, peers)| {
+                if peers.start == row {
+                    dense += 1;
+                }
+                Value::Integer(match self {
+                    Ranking::RowNumber => row as i64 + 1,
+                    Ranking::Rank => peers.start as i64 + 1,
+                    Ranking::DenseRank => dense,
+                })
+            })
+            .collect())
+    }
+}
+
+/// `ntile(n)` divides the partition into `n` buckets as even as possible, the first
+/// ones taking the remaining rows.
+struct Ntile;
+
+impl WindowFunction for Ntile {
+    fn name(&self) -> &str {
+        "ntile"
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [buckets] if integer_argument(*buckets) => Ok(Some(DataType::Integer)),
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn evaluate(&self, partition: &WindowPartition) -> Result<Vec<Value>, ExecutionError> {
+        let Some(first) = partition.arguments.first() else {
+            return Ok(vec![]);
+        };
+        let buckets = match &first[0] {
+            Value::Integer(buckets) if *buckets > 0 => *buckets as usize,
+            Value::Null => return Ok(vec![Value::Null; partition.len()]),
+            _ => {
+                return Err(ExecutionError::InvalidArgument(
+                    "argument of ntile must be greater than zero".to_string(),
+                ))
+            }
+        };
+        let (size, larger) = (partition.len() / buckets, partition.len() % buckets);
+        Ok((0..partition.len())
+            .map(|row| {
+                let bucket = match row < larger * (size + 1) {
+                    true => row / (size + 1),
+                    false => larger + (row - larger * (size + 1)) / size.max(1),
+                };
+                Value::Integer(bucket as i64 + 1)
+            })
+            .collect())
+    }
+}
+
+/// `lag(value [, offset [, default]])` and `lead(…)`, the value of the row `offset` rows
+/// before or after, `default` if there is none.
+struct Offset {
+    name: &'static str,
+    direction: i64,
+}
+
+impl Offset {
+    fn new(name: &'static str, direction: i64) -> Self {
+        Offset { name, direction }
+    }
+}
+
+impl WindowFunction for Offset {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [value] => Ok(*value),
+            [value, offset] if integer_argument(*offset) => Ok(*value),
+            [value, offset, default] if integer_argument(*offset) => match (value, default) {
+                (Some(value), Some(default)) => value
+                    .common_type(*default)
+                    .map(Some)
+                    .ok_or(ResolveError::Undefined),
+                (value, default) => Ok(value.or(*default)),
+            },
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn evaluate(&self, partition: &WindowPartition) -> Result<Vec<Value>, ExecutionError> {
+        partition
+            .arguments
+            .iter()
+            .enumerate()
+            .map(|(row, arguments)| {
+                let offset = match arguments.get(1) {
+                    Some(Value::Integer(offset)) => *offset,
+                    Some(_) => return Ok(Value::Null),
+                    None => 1,
+                };
+                let target = offset
+                    .checked_mul(self.direction)
+                    .and_then(|offset| (row as i64).checked_add(offset))
+                    .and_then(|target| usize::try_from(target).ok())
+                    .filter(|target| *target < partition.len());
+                Ok(match target {
+                    Some(target) => partition.arguments[target][0].clone(),
+                    None => arguments.get(2).cloned().unwrap_or(Value::Null),
+                })
+            })
+            .collect()
+    }
+}
+
+/// `first_value` or `last_value` of the frame.
+struct FrameValue {
+    name: &'static str,
+    first: bool,
+}
+
+impl FrameValue {
+    fn new(name: &'static str, first: bool) -> Self {
+        FrameValue { name, first }
+    }
+}
+
+impl WindowFunction for FrameValue {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn return_type(
+        &self,
+        arguments: &[Option<DataType>],
+    ) -> Result<Option<DataType>, ResolveError> {
+        match arguments {
+            [value] => Ok(*value),
+            _ => Err(ResolveError::Undefined),
+        }
+    }
+
+    fn evaluate(&self, partition: &WindowPartition) -> Result<Vec<Value>, ExecutionError> {
+        Ok(partition
+            .frames
+            .iter()
+            .map(|frame| {
+                let row = match self.first {
+                    true => frame.clone().next(),
+                    false => frame.clone().next_back(),
+                };
+                row.map_or(Value::Null, |row| partition.arguments[row][0].clone())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::builtins;
+
+    /// Results of the window function over rows of the arguments, the rows with equal
+    /// keys are peers and the frame of a row runs from the start of the partition to
+    /// its last peer.
+    fn evaluate(name: &str, keys: &[i64], arguments: &[Row]) -> Result<Vec<Value>, ExecutionError> {
+        let peers: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                let start = keys.iter().position(|other| other == key).unwrap();
+                let end = keys.iter().rposition(|other| other == key).unwrap() + 1;
+                start..end
+            })
+            .collect();
+        let frames: Vec<_> = peers.iter().map(|peers| 0..peers.end).collect();
+        let arguments = match arguments {
+            [] => vec![vec![]; keys.len()],
+            arguments => arguments.to_vec(),
+        };
+        builtins().window(name).unwrap().evaluate(&WindowPartition {
+            arguments: &arguments,
+            peers: &peers,
+            frames: &frames,
+        })
+    }
+
+    fn values(values: &[i64]) -> Vec<Value> {
+        values.iter().map(|value| Value::Integer(*value)).collect()
+    }
+
+    #[test]
+    fn ranks_rows() {
+        let keys = [1, 1, 2, 3, 3, 3, 4];
+        assert_eq!(
+            evaluate("row_number", &keys, &[]),
+            Ok(values(&[1, 2, 3, 4, 5, 6, 7]))
+        );
+        assert_eq!(
+            evaluate("rank", &keys, &[]),
+            Ok(values(&[1, 1, 3, 4, 4, 4, 7]))
+        );
+        assert_eq!(
+            evaluate("dense_rank", &keys, &[]),
+            Ok(values(&[1, 1, 2, 3, 3, 3, 4]))
+        );
+    }
+
+    #[test]
+    fn divides_into_buckets() {
+        let ntile = |buckets: i64, rows: usize| {
+            let keys: Vec<_> = (0..rows as i64).collect();
+            evaluate("ntile", &keys, &vec![vec![buckets.into()]; rows])
+        };
+        assert_eq!(ntile(3, 7), Ok(values(&[1, 1, 1, 2, 2, 3, 3])));
+        assert_eq!(ntile(2, 4), Ok(values(&[1, 1, 2, 2])));
+        assert_eq!(ntile(5, 3), Ok(values(&[1, 2, 3])));
+        assert_eq!(
+            ntile(0, 2),
+            Err(ExecutionError::InvalidArgument(
+                "argument of ntile must be greater than zero".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn offsets_rows() {
+        let keys = [1, 2, 3, 4];
+        let rows = |arguments: &[Value]| -> Vec<Row> {
+            (1..=4)
+                .map(|value| {
+                    let mut row = vec![Value::Integer(value * 10)];
+                    row.extend_from_slice(arguments);
+                    row
+                })
+                .collect()
+        };
+        assert_eq!(
+            evaluate("lag", &keys, &rows(&[])),
+            Ok(vec![Value::Null, 10.into(), 20.into(), 30.into()])
+        );
+        assert_eq!(
+            evaluate("lead", &keys, &rows(&[2.into(), 0.into()])),
+            Ok(values(&[30, 40, 0, 0]))
+        );
+        assert_eq!(
+            evaluate("lag", &keys, &rows(&[(-1).into()])),
+            Ok(vec![20.into(), 30.into(), 40.into(), Value::Null])
+        );
+    }
+
+    #[test]
+    fn takes_values_of_frames() {
+        let keys = [1, 2, 2, 3];
+        let rows: Vec<Row> = (1..=4).map(|value| vec![Value::Integer(value)]).collect();
+        assert_eq!(
+            evaluate("first_value", &keys, &rows),
+            Ok(values(&[1, 1, 1, 1]))
+        );
+        assert_eq!(
+            evaluate("last_value", &keys, &rows),
+            Ok(values(&[1, 3, 3, 4]))
+        );
+    }
+}
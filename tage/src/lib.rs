@@ -0,0 +1,12 @@
+pub mod analyser;
+pub mod catalog;
+pub mod config;
+pub mod database;
+pub mod execution;
+pub mod functions;
+pub mod optimizer;
+pub mod parser;
+pub mod planner;
+pub mod prepared;
+pub mod storage;
+pub mod value;
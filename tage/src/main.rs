@@ -1,5 +1,87 @@
-mod parser;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::{env, fs};
+use tage::parser::formatter::{format, CommaStyle, FormatOptions, KeywordCase};
 
-fn main() {
-    print!("{}", 1);
-}
\ No newline at end of file
+const USAGE: &str = "usage: tage fmt [--check] [--lowercase] [--indent <spaces>] \
+                     [--line-width <columns>] [--leading-commas] [<file>...]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "fmt" => fmt(args),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Formats the files in place, or stdin to stdout when no files are given.
+/// With `--check` nothing is written and the files which are not formatted are listed.
+fn fmt(args: &[String]) -> Result<ExitCode, String> {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--lowercase" => options.keyword_case = KeywordCase::Lower,
+            "--leading-commas" => options.comma_style = CommaStyle::Leading,
+            "--indent" => options.indent = number(arg, args.next())?,
+            "--line-width" => options.line_width = number(arg, args.next())?,
+            flag if flag.starts_with("--") => return Err(USAGE.to_string()),
+            file => files.push(file),
+        }
+    }
+
+    if files.is_empty() {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| e.to_string())?;
+        let formatted = format(&input, &options).map_err(|e| format!("<stdin>: {}", e))?;
+        if check {
+            return Ok(exit_code(formatted == input));
+        }
+        io::stdout()
+            .write_all(formatted.as_bytes())
+            .map_err(|e| e.to_string())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut formatted_files = true;
+    for file in files {
+        let input = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        let formatted = format(&input, &options).map_err(|e| format!("{}: {}", file, e))?;
+        if formatted == input {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            formatted_files = false;
+        } else {
+            fs::write(file, formatted).map_err(|e| format!("{}: {}", file, e))?;
+        }
+    }
+    Ok(exit_code(formatted_files))
+}
+
+fn number(flag: &str, value: Option<&String>) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{} expects a number\n{}", flag, USAGE))
+}
+
+fn exit_code(success: bool) -> ExitCode {
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,417 @@
+//! Estimates the number of rows a plan returns from the statistics of its tables.
+//!
+//! A filter keeps the fraction of the rows its predicate selects. The selectivity of a
+//! comparison with a constant comes from the statistics of the column: `NULL`s never
+//! pass, an equality selects one of the distinct values and a range the fraction of the
+//! histogram it covers. Conditions are assumed to be independent, and the values of a
+//! join key on the side with fewer distinct values to all appear on the other side.
+//! Tables which were never analyzed get default estimates.
+
+use crate::analyser::bound::{BoundColumn, BoundExpr};
+use crate::catalog::statistics::ColumnStatistics;
+use crate::execution::expr::cast;
+use crate::optimizer::OptimizerContext;
+use crate::parser::expr::{Literal, Operator, UnaryOperator};
+use crate::planner::{JoinType, LogicalPlan};
+use crate::value::Value;
+
+/// Rows of a table which was never analyzed.
+pub const DEFAULT_ROW_COUNT: f64 = 1000.0;
+/// Distinct values of a column which was never analyzed.
+pub const DEFAULT_DISTINCT_COUNT: f64 = 200.0;
+/// Selectivity of a condition the estimator knows nothing about.
+pub const DEFAULT_SELECTIVITY: f64 = 1.0 / 3.0;
+/// Selectivity of `LIKE` and of `IS NULL` without statistics.
+pub const DEFAULT_MATCH_SELECTIVITY: f64 = 0.1;
+
+/// Estimated number of rows the plan returns.
+pub fn estimate_rows(plan: &LogicalPlan, context: &OptimizerContext) -> f64 {
+    match plan {
+        LogicalPlan::Scan { relation, .. } => context
+            .statistics(relation.table.id)
+            .map_or(DEFAULT_ROW_COUNT, |statistics| statistics.row_count as f64),
+        LogicalPlan::Filter { input, predicate } => {
+            estimate_rows(input, context) * selectivity(predicate, context)
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            on,
+            ..
+        } => {
+            let (left, right) = (estimate_rows(left, context), estimate_rows(right, context));
+            let selectivity = on.as_ref().map_or(1.0, |on| selectivity(on, context));
+            let inner = left * right * selectivity;
+            match join_type {
+                JoinType::Inner | JoinType::Cross => inner,
+                JoinType::Left => inner.max(left),
+                JoinType::Right => inner.max(right),
+                JoinType::Full => inner.max(left).max(right),
+                // fraction of the left rows which find a match
+                JoinType::Semi => left * (right * selectivity).min(1.0),
+                JoinType::Anti => left * (1.0 - (right * selectivity).min(1.0)),
+            }
+        }
+        LogicalPlan::Aggregate {
+            input, group_by, ..
+        } => match group_by.is_empty() {
+            true => 1.0,
+            false => group_count(group_by, estimate_rows(input, context), context),
+        },
+        LogicalPlan::Distinct { input } => {
+            let exprs: Vec<_> = input
+                .schema()
+                .iter()
+                .map(|column| column.expr.clone())
+                .collect();
+            group_count(&exprs, estimate_rows(input, context), context)
+        }
+        LogicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } => {
+            let rows = (estimate_rows(input, context) - *offset as f64).max(0.0);
+            limit.map_or(rows, |limit| rows.min(limit as f64))
+        }
+        LogicalPlan::Union { inputs, .. } => inputs
+            .iter()
+            .map(|input| estimate_rows(input, context))
+            .sum(),
+        LogicalPlan::Values { rows, .. } => rows.len() as f64,
+        // the other nodes return a row for each row of their input
+        _ => plan
+            .inputs()
+            .first()
+            .map_or(1.0, |input| estimate_rows(input, context)),
+    }
+}
+
+/// Estimated fraction of the rows for which the condition is true.
+pub fn selectivity(condition: &BoundExpr, context: &OptimizerContext) -> f64 {
+    let selectivity = match condition {
+        BoundExpr::Literal(Literal::Boolean(value)) => f64::from(u8::from(*value)),
+        BoundExpr::Literal(Literal::Null) => 0.0,
+        BoundExpr::Binary {
+            left,
+            operator: Operator::And,
+            right,
+            ..
+        } => selectivity(left, context) * selectivity(right, context),
+        BoundExpr::Binary {
+            left,
+            operator: Operator::Or,
+            right,
+            ..
+        } => {
+            let (left, right) = (selectivity(left, context), selectivity(right, context));
+            left + right - left * right
+        }
+        BoundExpr::Unary {
+            operator: UnaryOperator::Not,
+            expr,
+            ..
+        } => 1.0 - selectivity(expr, context),
+        BoundExpr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } => comparison(left, *operator, right, context),
+        BoundExpr::IsNull { expr, negated } => {
+            let nulls = column_statistics(expr, context)
+                .map_or(DEFAULT_MATCH_SELECTIVITY, |column| column.null_fraction);
+            if *negated {
+                1.0 - nulls
+            } else {
+                nulls
+            }
+        }
+        BoundExpr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let selected = (list.len() as f64 * equality(expr, context)).min(1.0);
+            if *negated {
+                not_null(expr, context) - selected
+            } else {
+                selected
+            }
+        }
+        BoundExpr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let between = match (constant(low), constant(high)) {
+                (Some(low), Some(high)) => range(expr, Some(&low), Some(&high), context),
+                _ => None,
+            }
+            .unwrap_or(DEFAULT_SELECTIVITY);
+            if *negated {
+                not_null(expr, context) - between
+            } else {
+                between
+            }
+        }
+        BoundExpr::Like { negated, .. } => {
+            if *negated {
+                1.0 - DEFAULT_MATCH_SELECTIVITY
+            } else {
+                DEFAULT_MATCH_SELECTIVITY
+            }
+        }
+        _ => DEFAULT_SELECTIVITY,
+    };
+    selectivity.clamp(0.0, 1.0)
+}
+
+/// Estimated number of distinct values of a column, `None` for expressions which are no
+/// column of a table.
+pub fn distinct_count(expr: &BoundExpr, context: &OptimizerContext) -> Option<f64> {
+    let BoundExpr::Column(column) = strip_casts(expr) else {
+        return None;
+    };
+    match context.statistics(column.table_id) {
+        Some(statistics) => statistics
+            .column(column.column_id)
+            .map(|column| (column.distinct_count as f64).max(1.0)),
+        None => Some(DEFAULT_DISTINCT_COUNT),
+    }
+}
+
+fn comparison(
+    left: &BoundExpr,
+    operator: Operator,
+    right: &BoundExpr,
+    context: &OptimizerContext,
+) -> f64 {
+    // the column on the left
+    let (expr, operator, value) = match (constant(left), constant(right)) {
+        (None, Some(value)) => (left, operator, Some(value)),
+        (Some(value), None) => (right, flip(operator), Some(value)),
+        _ => (left, operator, None),
+    };
+    match (operator, value) {
+        (Operator::Equal, Some(_)) => equality(expr, context),
+        (Operator::Equal, None) => {
+            // a join condition, each value of the side with fewer values finds its matches
+            let distinct = match (
+                distinct_count(left, context),
+                distinct_count(right, context),
+            ) {
+                (Some(left), Some(right)) => left.max(right),
+                (Some(count), None) | (None, Some(count)) => count,
+                (None, None) => DEFAULT_DISTINCT_COUNT,
+            };
+            not_null(left, context) * not_null(right, context) / distinct
+        }
+        (Operator::NotEqual, Some(_)) => not_null(expr, context) - equality(expr, context),
+        (Operator::LessThan | Operator::LessThanOrEqual, Some(value)) => {
+            range(expr, None, Some(&value), context).unwrap_or(DEFAULT_SELECTIVITY)
+        }
+        (Operator::GreaterThan | Operator::GreaterThanOrEqual, Some(value)) => {
+            range(expr, Some(&value), None, context).unwrap_or(DEFAULT_SELECTIVITY)
+        }
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+/// The comparison with its operands swapped.
+fn flip(operator: Operator) -> Operator {
+    match operator {
+        Operator::LessThan => Operator::GreaterThan,
+        Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+        Operator::GreaterThan => Operator::LessThan,
+        Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+        operator => operator,
+    }
+}
+
+/// Selectivity of the expression being equal to a constant.
+fn equality(expr: &BoundExpr, context: &OptimizerContext) -> f64 {
+    let distinct = distinct_count(expr, context).unwrap_or(DEFAULT_DISTINCT_COUNT);
+    not_null(expr, context) / distinct
+}
+
+/// Fraction of the values between the bounds, `None` without a histogram.
+fn range(
+    expr: &BoundExpr,
+    low: Option<&Value>,
+    high: Option<&Value>,
+    context: &OptimizerContext,
+) -> Option<f64> {
+    let column = column_statistics(expr, context)?;
+    let below_high = match high {
+        Some(high) => column.fraction_below(high)?,
+        None => 1.0,
+    };
+    let below_low = match low {
+        Some(low) => column.fraction_below(low)?,
+        None => 0.0,
+    };
+    Some((below_high - below_low).max(0.0) * (1.0 - column.null_fraction))
+}
+
+/// Fraction of the rows where the expression isn't `NULL`.
+fn not_null(expr: &BoundExpr, context: &OptimizerContext) -> f64 {
+    column_statistics(expr, context).map_or(1.0, |column| 1.0 - column.null_fraction)
+}
+
+/// Groups of the rows by the keys, at most one per row.
+fn group_count(keys: &[BoundExpr], rows: f64, context: &OptimizerContext) -> f64 {
+    let groups: f64 = keys
+        .iter()
+        .map(|key| distinct_count(key, context).unwrap_or(rows))
+        .product();
+    groups.min(rows).max(1.0)
+}
+
+fn column_statistics<'a>(
+    expr: &BoundExpr,
+    context: &OptimizerContext<'a>,
+) -> Option<&'a ColumnStatistics> {
+    let BoundExpr::Column(BoundColumn {
+        table_id,
+        column_id,
+        ..
+    }) = strip_casts(expr)
+    else {
+        return None;
+    };
+    context.statistics(*table_id)?.column(*column_id)
+}
+
+fn strip_casts(expr: &BoundExpr) -> &BoundExpr {
+    match expr {
+        BoundExpr::Cast { expr, .. } => strip_casts(expr),
+        expr => expr,
+    }
+}
+
+/// Value of a constant expression which isn't `NULL`, casts are applied so that e.g. a
+/// date literal is a date like the values of a date column.
+fn constant(expr: &BoundExpr) -> Option<Value> {
+    match expr {
+        BoundExpr::Cast { expr, data_type } => cast(constant(expr)?, *data_type).ok(),
+        BoundExpr::Literal(Literal::Null) => None,
+        BoundExpr::Literal(literal) => Some(Value::from_literal(literal)),
+        BoundExpr::Unary {
+            operator: UnaryOperator::Minus,
+            expr,
+            ..
+        } => match constant(expr)? {
+            Value::Integer(value) => Some(Value::Integer(-value)),
+            Value::Float(value) => Some(Value::Float(-value)),
+            Value::Decimal(value) => Some(Value::Decimal(value.neg())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::catalog::statistics::TableStatistics;
+    use crate::catalog::{Catalog, MemoryCatalog};
+    use crate::optimizer::tests::{catalog, plan_in};
+
+    /// 1000 users with ages 0 to 99 and 10 orders for each of 100 users.
+    pub(crate) fn analyzed_catalog() -> MemoryCatalog {
+        let mut catalog = catalog();
+        let users = catalog.table("public", "users").unwrap().clone();
+        let orders = catalog.table("public", "orders").unwrap().clone();
+        let statistics = [
+            TableStatistics::gather(
+                &users,
+                (0..1000).map(|id| {
+                    let age = if id % 10 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Integer(id % 100)
+                    };
+                    vec![
+                        Value::Integer(id),
+                        Value::String(format!("user {}", id)),
+                        age,
+                    ]
+                }),
+            ),
+            TableStatistics::gather(
+                &orders,
+                (0..1000).map(|id| {
+                    vec![
+                        Value::Integer(id),
+                        Value::Integer(id % 100),
+                        Value::Float(id as f64),
+                    ]
+                }),
+            ),
+        ];
+        for statistics in statistics {
+            catalog.set_statistics(statistics);
+        }
+        catalog
+    }
+
+    fn rows(catalog: &MemoryCatalog, input: &str) -> f64 {
+        let plan = plan_in(catalog, input);
+        let rows = estimate_rows(&plan, &OptimizerContext::new(&plan, catalog));
+        // rounded, the estimates are products of fractions
+        (rows * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn estimates_filters() {
+        let catalog = analyzed_catalog();
+        let estimate =
+            |filter: &str| rows(&catalog, &format!("select * from users where {}", filter));
+        assert_eq!(estimate("true"), 1000.0);
+        assert_eq!(estimate("age is null"), 100.0);
+        assert_eq!(estimate("age = 3"), 10.0);
+        assert_eq!(estimate("3 = age and id = 1"), 0.01);
+        assert_eq!(estimate("id in (1, 2, 3)"), 3.0);
+        assert_eq!(estimate("name like 'a%'"), 100.0);
+        assert!((estimate("age < 50") - 450.0).abs() < 10.0);
+        assert!((estimate("50 >= age") - 450.0).abs() < 10.0);
+        assert!((estimate("age between 10 and 19 or id > 899") - 181.0).abs() < 10.0);
+        assert!((estimate("not age > 89") - 910.0).abs() < 10.0);
+        assert_eq!(estimate("age > id"), 333.333);
+
+        // without statistics
+        let catalog = crate::optimizer::tests::catalog();
+        assert_eq!(rows(&catalog, "select * from users where id = 1"), 5.0);
+        assert_eq!(rows(&catalog, "select * from users"), DEFAULT_ROW_COUNT);
+    }
+
+    #[test]
+    fn estimates_joins_and_aggregates() {
+        let catalog = analyzed_catalog();
+        let estimate = |input: &str| rows(&catalog, input);
+        // each of the 100 users with orders has 10
+        assert_eq!(
+            estimate("select * from users u join orders o on u.id = o.user_id"),
+            1000.0
+        );
+        assert_eq!(estimate("select * from users, orders"), 1_000_000.0);
+        assert_eq!(
+            estimate("select * from users u left join orders o on u.id = o.user_id and o.id = 1"),
+            1000.0
+        );
+        assert_eq!(
+            estimate("select age, count(*) from users group by age"),
+            90.0
+        );
+        assert_eq!(
+            estimate("select age, id, count(*) from users group by age, id"),
+            1000.0
+        );
+        assert_eq!(estimate("select count(*) from users"), 1.0);
+        assert_eq!(estimate("select distinct user_id from orders"), 100.0);
+        assert_eq!(estimate("select id from users limit 10 offset 995"), 5.0);
+    }
+}
@@ -0,0 +1,396 @@
+//! Evaluates the parts of expressions which don't depend on the rows.
+//!
+//! Integer arithmetic and comparisons of literals are computed, boolean operators with a
+//! constant operand are simplified and operators with a `NULL` operand become `NULL`.
+//! Anything which could fail at run time, e.g. an overflow or a division by zero, is
+//! left to the executor so the error is reported when the query runs. Calls of immutable
+//! functions with constant arguments are computed, stable and volatile ones are not.
+
+use crate::analyser::bound::*;
+use crate::analyser::typecheck::literal_type;
+use crate::catalog::MemoryCatalog;
+use crate::execution::expr::ScalarExpr;
+use crate::execution::pattern::PatternCache;
+use crate::execution::spill::QueryMemory;
+use crate::execution::ExecutionContext;
+use crate::functions::Volatility;
+use crate::optimizer::{map_children, map_exprs, OptimizerContext, OptimizerRule};
+use crate::parser::expr::{Literal, Operator, UnaryOperator};
+use crate::planner::LogicalPlan;
+use crate::storage::MemoryStore;
+use crate::value::DataType;
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+pub struct ConstantFolding;
+
+impl OptimizerRule for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constant_folding"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        let folded = match map_exprs(plan, &mut fold) {
+            LogicalPlan::Filter { input, predicate } if is_true(&predicate) => *input,
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                on: Some(on),
+                ..
+            } if is_true(&on) => LogicalPlan::join(*left, *right, join_type, None),
+            folded => folded,
+        };
+        (&folded != plan).then_some(folded)
+    }
+}
+
+/// The expression with constant parts evaluated, its type doesn't change.
+pub fn fold(expr: &BoundExpr) -> BoundExpr {
+    let expr = map_children(expr, &mut fold);
+    let folded = match &expr {
+        BoundExpr::Unary { operator, expr, .. } => fold_unary(*operator, expr),
+        BoundExpr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } => fold_binary(left, *operator, right),
+        BoundExpr::IsNull { expr, negated } => match expr.as_ref() {
+            expr if is_null(expr) => Some(boolean(!negated)),
+            BoundExpr::Literal(_) => Some(boolean(*negated)),
+            _ => None,
+        },
+        BoundExpr::IsDistinctFrom {
+            left,
+            right,
+            negated,
+        } => fold_distinct(left, right, *negated),
+        BoundExpr::Function(function) => fold_call(&expr, function),
+        _ => None,
+    };
+    match folded {
+        // a `NULL` keeps the type of the expression it replaces
+        Some(folded) if is_null(&folded) => null(expr.data_type()),
+        Some(folded) => folded,
+        None => expr,
+    }
+}
+
+/// `IS [NOT] DISTINCT FROM` of literals, a comparison with `NULL` is an `IS [NOT] NULL`.
+fn fold_distinct(left: &BoundExpr, right: &BoundExpr, negated: bool) -> Option<BoundExpr> {
+    match (left, right) {
+        (left, right) if is_null(left) && is_null(right) => Some(boolean(negated)),
+        (expr, null) | (null, expr) if is_null(null) => Some(fold(&BoundExpr::IsNull {
+            expr: Box::new(expr.clone()),
+            negated: !negated,
+        })),
+        (BoundExpr::Literal(left), BoundExpr::Literal(right)) => Some(boolean(
+            (compare(left, right)? != Ordering::Equal) != negated,
+        )),
+        _ => None,
+    }
+}
+
+fn fold_unary(operator: UnaryOperator, expr: &BoundExpr) -> Option<BoundExpr> {
+    if is_null(expr) {
+        return Some(null(None));
+    }
+    match (operator, expr) {
+        (UnaryOperator::Not, BoundExpr::Literal(Literal::Boolean(value))) => Some(boolean(!value)),
+        (
+            UnaryOperator::Not,
+            BoundExpr::Unary {
+                operator: UnaryOperator::Not,
+                expr,
+                ..
+            },
+        ) => Some((**expr).clone()),
+        (UnaryOperator::Plus, expr) if is_numeric(expr) => Some(expr.clone()),
+        (UnaryOperator::Minus, BoundExpr::Literal(literal)) => {
+            let value = integer(literal)?;
+            integer_literal(value.checked_neg()?, literal_type(literal)?)
+        }
+        _ => None,
+    }
+}
+
+fn fold_binary(left: &BoundExpr, operator: Operator, right: &BoundExpr) -> Option<BoundExpr> {
+    match operator {
+        Operator::And => match (left, right) {
+            (BoundExpr::Literal(Literal::Boolean(false)), _)
+            | (_, BoundExpr::Literal(Literal::Boolean(false))) => Some(boolean(false)),
+            (BoundExpr::Literal(Literal::Boolean(true)), other)
+            | (other, BoundExpr::Literal(Literal::Boolean(true))) => Some(other.clone()),
+            _ if is_null(left) && is_null(right) => Some(null(None)),
+            _ => None,
+        },
+        Operator::Or => match (left, right) {
+            (BoundExpr::Literal(Literal::Boolean(true)), _)
+            | (_, BoundExpr::Literal(Literal::Boolean(true))) => Some(boolean(true)),
+            (BoundExpr::Literal(Literal::Boolean(false)), other)
+            | (other, BoundExpr::Literal(Literal::Boolean(false))) => Some(other.clone()),
+            _ if is_null(left) && is_null(right) => Some(null(None)),
+            _ => None,
+        },
+        _ if is_null(left) || is_null(right) => Some(null(None)),
+        _ => {
+            let (BoundExpr::Literal(left), BoundExpr::Literal(right)) = (left, right) else {
+                return None;
+            };
+            match operator {
+                Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo => fold_arithmetic(left, operator, right),
+                Operator::Concat => match (left, right) {
+                    (Literal::String(left), Literal::String(right)) => Some(BoundExpr::Literal(
+                        Literal::String(format!("{}{}", left, right)),
+                    )),
+                    _ => None,
+                },
+                _ => compare(left, right).map(|ordering| {
+                    boolean(match operator {
+                        Operator::Equal => ordering == Ordering::Equal,
+                        Operator::NotEqual => ordering != Ordering::Equal,
+                        Operator::GreaterThan => ordering == Ordering::Greater,
+                        Operator::LessThan => ordering == Ordering::Less,
+                        Operator::GreaterThanOrEqual => ordering != Ordering::Less,
+                        _ => ordering != Ordering::Greater,
+                    })
+                }),
+            }
+        }
+    }
+}
+
+/// Result of a call of an immutable function with constant arguments, with the type of
+/// the call.
+fn fold_call(expr: &BoundExpr, function: &BoundFunction) -> Option<BoundExpr> {
+    let scalar = function.scalar()?;
+    if scalar.volatility != Volatility::Immutable || !function.arguments.iter().all(is_constant) {
+        return None;
+    }
+    let (mut store, catalog) = (MemoryStore::new(), MemoryCatalog::new());
+    // immutable functions don't read the context
+    let mut context = ExecutionContext {
+        store: &mut store,
+        catalog: &catalog,
+        memory: QueryMemory::unlimited(),
+        worker: None,
+        start_time: SystemTime::now(),
+        patterns: PatternCache::default(),
+        parameters: &[],
+    };
+    let literal = ScalarExpr::constant(expr, &mut context)
+        .ok()?
+        .to_literal()?;
+    let data_type = expr.data_type();
+    if literal == Literal::Null || literal_type(&literal) == data_type {
+        return Some(BoundExpr::Literal(literal));
+    }
+    Some(BoundExpr::Cast {
+        expr: Box::new(BoundExpr::Literal(literal)),
+        data_type: data_type?,
+    })
+}
+
+/// A literal, possibly cast to a type.
+fn is_constant(expr: &BoundExpr) -> bool {
+    match expr {
+        BoundExpr::Literal(_) => true,
+        BoundExpr::Cast { expr, .. } => is_constant(expr),
+        _ => false,
+    }
+}
+
+/// Only operands of the same integer type are folded, the result must have that type.
+fn fold_arithmetic(left: &Literal, operator: Operator, right: &Literal) -> Option<BoundExpr> {
+    let data_type = literal_type(left)?;
+    if literal_type(right)? != data_type {
+        return None;
+    }
+    let (left, right) = (integer(left)?, integer(right)?);
+    let value = match operator {
+        Operator::Plus => left.checked_add(right),
+        Operator::Minus => left.checked_sub(right),
+        Operator::Multiply => left.checked_mul(right),
+        Operator::Divide => left.checked_div(right),
+        _ => left.checked_rem(right),
+    }?;
+    integer_literal(value, data_type)
+}
+
+fn compare(left: &Literal, right: &Literal) -> Option<Ordering> {
+    match (left, right) {
+        (Literal::String(left), Literal::String(right)) => Some(left.cmp(right)),
+        (Literal::Boolean(left), Literal::Boolean(right)) => Some(left.cmp(right)),
+        _ => Some(integer(left)?.cmp(&integer(right)?)),
+    }
+}
+
+fn integer(literal: &Literal) -> Option<i64> {
+    match (literal, literal_type(literal)) {
+        (Literal::Numeric(value), Some(DataType::Integer | DataType::BigInt)) => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The literal, `None` if the value doesn't have the type.
+fn integer_literal(value: i64, data_type: DataType) -> Option<BoundExpr> {
+    let literal = Literal::Numeric(value.to_string());
+    (literal_type(&literal) == Some(data_type)).then_some(BoundExpr::Literal(literal))
+}
+
+fn boolean(value: bool) -> BoundExpr {
+    BoundExpr::Literal(Literal::Boolean(value))
+}
+
+fn null(data_type: Option<DataType>) -> BoundExpr {
+    let null = BoundExpr::Literal(Literal::Null);
+    match data_type {
+        Some(data_type) => BoundExpr::Cast {
+            expr: Box::new(null),
+            data_type,
+        },
+        None => null,
+    }
+}
+
+/// `NULL`, with the type of the expression it replaced or as it was written.
+pub(crate) fn is_null(expr: &BoundExpr) -> bool {
+    match expr {
+        BoundExpr::Literal(Literal::Null) => true,
+        BoundExpr::Cast { expr, .. } => is_null(expr),
+        _ => false,
+    }
+}
+
+pub(crate) fn is_true(expr: &BoundExpr) -> bool {
+    expr == &boolean(true)
+}
+
+fn is_numeric(expr: &BoundExpr) -> bool {
+    matches!(expr, BoundExpr::Literal(Literal::Numeric(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::optimize_with;
+
+    #[test]
+    fn folds_arithmetic_and_comparisons() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select id * (2 + 3), 'a' || 'b', -(4) from users where age > 10 * 2 - 2"
+            ),
+            "Project: users.id * 5, 'ab', -4
+  Filter: users.age > 18
+    Scan: public.users"
+        );
+        // errors are left to the executor
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select 1 / 0, 2147483647 + 1, 1 + 2.5 from users"
+            ),
+            "Project: 1 / 0, 2147483647 + 1, CAST(1 AS DECIMAL) + 2.5
+  Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn simplifies_boolean_operators() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select * from users where 1 = 1 and (age > 3 or 2 < 1) and not (id = 1 and false)"
+            ),
+            "Project: users.id, users.name, users.age
+  Filter: users.age > 3
+    Scan: public.users"
+        );
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select * from users u join orders o on 2 > 1 where null is null"
+            ),
+            "Project: u.id, u.name, u.age, o.id, o.user_id, o.total
+  Join: INNER
+    Scan: public.users AS u
+    Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select * from users where 'a' = 'b' or id > 1"
+            ),
+            "Project: users.id, users.name, users.age
+  Filter: users.id > 1
+    Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn propagates_null() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select id + null, null = 1, 1 is not null from users where age > null or true"
+            ),
+            "Project: CAST(NULL AS INTEGER), CAST(NULL AS BOOLEAN), TRUE
+  Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn folds_distinct_from() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select null is distinct from null, 1 is not distinct from 1, 'a' is distinct from 'b',
+                    age is distinct from null, null is not distinct from age, id is distinct from 1
+                 from users"
+            ),
+            "Project: FALSE, TRUE, TRUE, users.age IS NOT NULL, users.age IS NULL, users.id IS DISTINCT FROM 1
+  Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn folds_immutable_calls() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select upper('a' || 'b'), abs(-3), round(2.5), sqrt(4), now(), \
+                 length(name), substring(null, 1) from users"
+            ),
+            "Project: 'AB' AS upper, 3 AS abs, CAST(3 AS DECIMAL) AS round, \
+             CAST(2 AS DECIMAL) AS sqrt, now(), length(users.name), CAST(NULL AS TEXT) AS substring
+  Scan: public.users"
+        );
+        // errors are left to the executor
+        assert_eq!(
+            optimize_with(ConstantFolding, "select sqrt(-1), power(0, -1) from users"),
+            "Project: sqrt(CAST(-1 AS DECIMAL)), power(CAST(0 AS DECIMAL), CAST(-1 AS DECIMAL))
+  Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn keeps_group_keys_matching() {
+        assert_eq!(
+            optimize_with(
+                ConstantFolding,
+                "select age + (1 + 1), count(*) from users group by age + (1 + 1) order by 1"
+            ),
+            "Project: users.age + 2, count(*)
+  Sort: users.age + 2
+    Aggregate: group_by=[users.age + 2], aggregates=[count(*)]
+      Scan: public.users"
+        );
+    }
+}
@@ -0,0 +1,802 @@
+//! Unnests subqueries into joins, so a correlated subquery runs once instead of once per
+//! row of the outer query.
+//!
+//! The rewrites follow Neumann and Kemper, "Unnesting Arbitrary Queries": the conditions
+//! of the subquery which read columns of the outer query become conditions of the join.
+//!
+//! - `EXISTS` and `x IN (SELECT y ...)` a filter requires become semi joins, `NOT EXISTS`
+//!   becomes an anti join.
+//! - `x NOT IN (SELECT y ...)` becomes an anti join which also matches when `x` or `y` is
+//!   `NULL`, the `NOT IN` is `NULL` then and no filter passes it.
+//! - A scalar subquery which aggregates becomes a left join with the subquery grouped by
+//!   the inner sides of its `inner = outer` conditions. An outer row without a group gets
+//!   the value of the aggregate over no rows, e.g. `0` for `count`.
+//!
+//! Other subqueries, e.g. under `OR` or with a limit, stay in the plan and run per row.
+
+use crate::analyser::aggregates::is_aggregate;
+use crate::analyser::bound::*;
+use crate::optimizer::constant_folding::{fold, is_null};
+use crate::optimizer::{
+    conjunction, conjuncts, evaluable, map_children, referenced_columns, OptimizerContext,
+    OptimizerRule,
+};
+use crate::parser::expr::{Literal, Operator, OperatorSpan, UnaryOperator};
+use crate::planner::builder::build_select;
+use crate::planner::{output_name, JoinType, LogicalPlan};
+use crate::value::DataType;
+
+pub struct Decorrelation;
+
+impl OptimizerRule for Decorrelation {
+    fn name(&self) -> &'static str {
+        "decorrelation"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, context: &OptimizerContext) -> Option<LogicalPlan> {
+        match plan {
+            LogicalPlan::Filter { input, predicate } => {
+                let mut conjuncts = conjuncts(predicate);
+                if let Some((position, join)) = conjuncts
+                    .iter()
+                    .enumerate()
+                    .find_map(|(position, conjunct)| Some((position, semi_join(input, conjunct)?)))
+                {
+                    conjuncts.remove(position);
+                    return Some(match conjunction(conjuncts) {
+                        Some(predicate) => LogicalPlan::filter(join, predicate),
+                        None => join,
+                    });
+                }
+                let (join, subquery, value) = unnest_scalar(input, &[predicate], context)?;
+                // the columns of the joined subquery are dropped again
+                let items = input
+                    .schema()
+                    .iter()
+                    .map(|column| ProjectionItem {
+                        expr: column.expr.clone(),
+                        name: column.name.clone(),
+                    })
+                    .collect();
+                Some(LogicalPlan::project(
+                    LogicalPlan::filter(join, replace(predicate, &subquery, &value)),
+                    items,
+                ))
+            }
+            LogicalPlan::Project { input, items, .. } => {
+                // `order by` is planned below the select list, a subquery of both is
+                // joined below the sort
+                let (input, order_by) = match input.as_ref() {
+                    LogicalPlan::Sort { input, order_by } => (input.as_ref(), Some(order_by)),
+                    input => (input, None),
+                };
+                let exprs: Vec<_> = items
+                    .iter()
+                    .map(|item| &item.expr)
+                    .chain(order_by.into_iter().flatten().map(|(expr, _)| expr))
+                    .collect();
+                let (mut join, subquery, value) = unnest_scalar(input, &exprs, context)?;
+                if let Some(order_by) = order_by {
+                    let order_by = order_by
+                        .iter()
+                        .map(|(expr, order)| (replace(expr, &subquery, &value), *order))
+                        .collect();
+                    join = LogicalPlan::sort(join, order_by);
+                }
+                let items = items
+                    .iter()
+                    .map(|item| ProjectionItem {
+                        expr: replace(&item.expr, &subquery, &value),
+                        name: item.name.clone(),
+                    })
+                    .collect();
+                Some(LogicalPlan::project(join, items))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Semi or anti join of the input with the `EXISTS` or `IN` subquery of the condition.
+fn semi_join(input: &LogicalPlan, condition: &BoundExpr) -> Option<LogicalPlan> {
+    let (operand, subquery, negated) = match condition {
+        BoundExpr::Exists { subquery, negated } => (None, subquery, *negated),
+        BoundExpr::InSubquery {
+            expr,
+            subquery,
+            negated,
+        } => (Some(expr.as_ref()), subquery, *negated),
+        BoundExpr::Unary {
+            operator: UnaryOperator::Not,
+            expr,
+            ..
+        } => {
+            return match expr.as_ref() {
+                BoundExpr::Exists { subquery, negated } => semi_join(
+                    input,
+                    &BoundExpr::Exists {
+                        subquery: subquery.clone(),
+                        negated: !negated,
+                    },
+                ),
+                BoundExpr::InSubquery {
+                    expr,
+                    subquery,
+                    negated,
+                } => semi_join(
+                    input,
+                    &BoundExpr::InSubquery {
+                        expr: expr.clone(),
+                        subquery: subquery.clone(),
+                        negated: !negated,
+                    },
+                ),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    if matches!(operand, Some(BoundExpr::Tuple(_))) {
+        return None;
+    }
+    // `EXISTS` only asks for one row, `IN` compares with all of them
+    let limited = match subquery.limit {
+        Some(limit) => operand.is_some() || limit == 0,
+        None => false,
+    };
+    let mut aggregates = vec![];
+    for item in &subquery.projection {
+        collect_aggregates(&item.expr, &mut aggregates);
+    }
+    if limited
+        || subquery.offset.is_some_and(|offset| offset > 0)
+        || !subquery.group_by.is_empty()
+        || subquery.having.is_some()
+        || !subquery.unions.is_empty()
+        || !aggregates.is_empty()
+    {
+        return None;
+    }
+
+    let inner = Inner::new(subquery);
+    if inner.correlated_outside_filter(operand.is_some()) {
+        return None;
+    }
+    let (mut on, filter) = inner.split_filter(input)?;
+    let join_type = match operand {
+        Some(operand) => {
+            let [item] = subquery.projection.as_slice() else {
+                return None;
+            };
+            if has_window(&item.expr)
+                || !inner.evaluable_on_join(input, operand)
+                || !inner.evaluable_on_join(input, &item.expr)
+            {
+                return None;
+            }
+            let equal = binary(operand.clone(), Operator::Equal, item.expr.clone());
+            if negated {
+                // a row is kept only if the `NOT IN` is true, it is `NULL` instead of
+                // false when no value is equal and one of the compared values is `NULL`
+                let mut matches = equal;
+                for side in [operand, &item.expr] {
+                    if may_be_null(side, input, subquery) {
+                        matches = binary(
+                            matches,
+                            Operator::Or,
+                            BoundExpr::IsNull {
+                                expr: Box::new(side.clone()),
+                                negated: false,
+                            },
+                        );
+                    }
+                }
+                on.push(matches);
+                JoinType::Anti
+            } else {
+                on.push(equal);
+                JoinType::Semi
+            }
+        }
+        None if negated => JoinType::Anti,
+        None => JoinType::Semi,
+    };
+
+    let right = inner.plan(&on, filter, vec![], vec![])?;
+    Some(LogicalPlan::join(
+        input.clone(),
+        right,
+        join_type,
+        conjunction(on),
+    ))
+}
+
+/// Left join of the input with the first scalar subquery of the expressions that can be
+/// unnested, the subquery and the expression which replaces it.
+fn unnest_scalar(
+    input: &LogicalPlan,
+    exprs: &[&BoundExpr],
+    context: &OptimizerContext,
+) -> Option<(LogicalPlan, BoundExpr, BoundExpr)> {
+    let mut subqueries = vec![];
+    for expr in exprs {
+        collect_subqueries(expr, &mut subqueries);
+    }
+    subqueries.into_iter().find_map(|subquery| {
+        let (join, value) = aggregate_join(input, subquery, context)?;
+        Some((join, BoundExpr::Subquery(Box::new(subquery.clone())), value))
+    })
+}
+
+/// Left join of the input with the subquery grouped by its correlated columns, and the
+/// expression which computes the value of the subquery from the joined rows.
+fn aggregate_join(
+    input: &LogicalPlan,
+    subquery: &BoundSelect,
+    context: &OptimizerContext,
+) -> Option<(LogicalPlan, BoundExpr)> {
+    let [item] = subquery.projection.as_slice() else {
+        return None;
+    };
+    let mut aggregates = vec![];
+    collect_aggregates(&item.expr, &mut aggregates);
+    if aggregates.is_empty()
+        || has_window(&item.expr)
+        || !subquery.group_by.is_empty()
+        || subquery.having.is_some()
+        || !subquery.unions.is_empty()
+        || subquery.limit.is_some()
+        || subquery.offset.is_some()
+    {
+        return None;
+    }
+    let inner = Inner::new(subquery);
+    if inner.correlated_outside_filter(false) {
+        return None;
+    }
+    let (correlated, filter) = inner.split_filter(input)?;
+    if correlated.is_empty() {
+        return None;
+    }
+    let mut keys = vec![];
+    for condition in correlated {
+        let BoundExpr::Binary {
+            left,
+            operator: Operator::Equal,
+            right,
+            ..
+        } = condition
+        else {
+            return None;
+        };
+        keys.push(match (inner.is_inner(&left), inner.is_inner(&right)) {
+            (true, false) if evaluable(input, &right) => (*left, *right),
+            (false, true) if evaluable(input, &left) => (*right, *left),
+            _ => return None,
+        });
+    }
+
+    let group_by: Vec<_> = keys.iter().map(|(inner, _)| inner.clone()).collect();
+    let plan = inner.plan(&[], filter, group_by, vec![item.clone()])?;
+    let alias =
+        LogicalPlan::subquery_alias(plan, "subquery".to_string(), context.new_relation_id());
+    let columns: Vec<_> = alias
+        .schema()
+        .iter()
+        .map(|column| column.expr.clone())
+        .collect();
+    let on = keys
+        .into_iter()
+        .zip(&columns)
+        .map(|((_, outer), column)| binary(outer, Operator::Equal, column.clone()))
+        .collect();
+    let join = LogicalPlan::join(input.clone(), alias, JoinType::Left, conjunction(on));
+
+    let value = columns.last().expect("value of the subquery").clone();
+    let empty = fold(&over_no_rows(&item.expr));
+    if is_null(&empty) {
+        return Some((join, value));
+    }
+    // the group keys of a joined row are never `NULL`, they are equal to outer values
+    let unmatched = BoundExpr::IsNull {
+        expr: Box::new(columns[0].clone()),
+        negated: false,
+    };
+    let value = BoundExpr::Case {
+        operand: None,
+        when_then: vec![(unmatched, empty)],
+        else_result: Some(Box::new(value)),
+    };
+    Some((join, value))
+}
+
+/// The relations of a subquery, and which of its columns belong to them.
+struct Inner<'a> {
+    select: &'a BoundSelect,
+    relations: Vec<&'a BoundRelation>,
+}
+
+impl<'a> Inner<'a> {
+    fn new(select: &'a BoundSelect) -> Self {
+        let relations = select
+            .from
+            .iter()
+            .flat_map(|from| {
+                from.relations
+                    .iter()
+                    .chain(from.joins.iter().map(|join| &join.relation))
+            })
+            .collect();
+        Inner { select, relations }
+    }
+
+    fn contains(&self, column: &BoundColumn) -> bool {
+        self.relations
+            .iter()
+            .any(|relation| relation.id == column.relation)
+    }
+
+    fn is_correlated(&self, expr: &BoundExpr) -> bool {
+        referenced_columns(expr)
+            .iter()
+            .any(|column| !self.contains(column))
+    }
+
+    /// Reads columns of the subquery and none of the outer query.
+    fn is_inner(&self, expr: &BoundExpr) -> bool {
+        let columns = referenced_columns(expr);
+        !columns.is_empty() && columns.iter().all(|column| self.contains(column))
+    }
+
+    /// Whether a clause other than `where`, or the select list if it isn't allowed to,
+    /// reads columns of the outer query.
+    fn correlated_outside_filter(&self, projection_allowed: bool) -> bool {
+        let select = self.select;
+        select
+            .from
+            .iter()
+            .flat_map(|from| &from.joins)
+            .filter_map(|join| join.on.as_ref())
+            .chain(
+                select
+                    .projection
+                    .iter()
+                    .map(|item| &item.expr)
+                    .filter(|_| !projection_allowed),
+            )
+            .chain(&select.group_by)
+            .chain(&select.having)
+            .chain(select.order_by.iter().map(|(expr, _)| expr))
+            .any(|expr| self.is_correlated(expr))
+    }
+
+    /// The correlated conditions of the filter and the others, `None` if a correlated
+    /// condition reads columns the input doesn't have.
+    fn split_filter(&self, input: &LogicalPlan) -> Option<(Vec<BoundExpr>, Vec<BoundExpr>)> {
+        let (correlated, filter): (Vec<_>, Vec<_>) = self
+            .select
+            .filter
+            .iter()
+            .flat_map(conjuncts)
+            .partition(|condition| self.is_correlated(condition));
+        correlated
+            .iter()
+            .all(|condition| self.evaluable_on_join(input, condition))
+            .then_some((correlated, filter))
+    }
+
+    /// Whether the expression reads only columns of the input and of the subquery.
+    fn evaluable_on_join(&self, input: &LogicalPlan, expr: &BoundExpr) -> bool {
+        referenced_columns(expr)
+            .into_iter()
+            .all(|column| self.contains(&column) || evaluable(input, &BoundExpr::Column(column)))
+    }
+
+    /// Plan of the subquery without its correlated conditions. It returns the columns
+    /// of the subquery the join conditions read, or the grouped projection.
+    fn plan(
+        &self,
+        on: &[BoundExpr],
+        filter: Vec<BoundExpr>,
+        group_by: Vec<BoundExpr>,
+        projection: Vec<ProjectionItem>,
+    ) -> Option<LogicalPlan> {
+        let mut columns = vec![];
+        for column in on.iter().flat_map(referenced_columns) {
+            if self.contains(&column) && !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+        let projection = group_by
+            .iter()
+            .map(|key| ProjectionItem {
+                expr: key.clone(),
+                name: output_name(key),
+            })
+            .chain(columns.into_iter().map(|column| ProjectionItem {
+                name: column.name.clone(),
+                expr: BoundExpr::Column(column),
+            }))
+            .chain(projection)
+            .collect();
+        build_select(&BoundSelect {
+            from: self.select.from.clone(),
+            projection,
+            distinct: false,
+            filter: conjunction(filter),
+            group_by,
+            having: None,
+            unions: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        })
+        .ok()
+    }
+}
+
+/// Whether the expression may be `NULL`, a column of a table may be if it is nullable.
+fn may_be_null(expr: &BoundExpr, input: &LogicalPlan, subquery: &BoundSelect) -> bool {
+    let BoundExpr::Column(column) = expr else {
+        return !matches!(expr, BoundExpr::Literal(literal) if literal != &Literal::Null);
+    };
+    let inner = Inner::new(subquery);
+    let mut relations = inner.relations;
+    let mut scans = vec![input];
+    while let Some(plan) = scans.pop() {
+        match plan {
+            LogicalPlan::Scan { relation, .. } => relations.push(relation),
+            plan => scans.extend(plan.inputs()),
+        }
+    }
+    let Some(relation) = relations
+        .iter()
+        .find(|relation| relation.id == column.relation)
+    else {
+        return true;
+    };
+    relation
+        .table
+        .columns
+        .iter()
+        .find(|table_column| table_column.id == column.column_id)
+        .is_none_or(|table_column| table_column.nullable)
+}
+
+/// Value of the expression when the aggregates in it saw no rows: `count` is 0 and the
+/// other aggregates are `NULL`.
+fn over_no_rows(expr: &BoundExpr) -> BoundExpr {
+    match expr {
+        BoundExpr::Function(function) if is_aggregate(function) => {
+            let value = if function.name == "count" {
+                Literal::Numeric("0".to_string())
+            } else {
+                Literal::Null
+            };
+            BoundExpr::Cast {
+                expr: Box::new(BoundExpr::Literal(value)),
+                data_type: expr.data_type().unwrap_or(DataType::Text),
+            }
+        }
+        expr => map_children(expr, &mut over_no_rows),
+    }
+}
+
+fn replace(expr: &BoundExpr, target: &BoundExpr, replacement: &BoundExpr) -> BoundExpr {
+    if expr == target {
+        return replacement.clone();
+    }
+    map_children(expr, &mut |child| replace(child, target, replacement))
+}
+
+/// Scalar subqueries of the expression, not those nested in other subqueries.
+fn collect_subqueries<'a>(expr: &'a BoundExpr, subqueries: &mut Vec<&'a BoundSelect>) {
+    if let BoundExpr::Subquery(subquery) = expr {
+        subqueries.push(subquery);
+    }
+    for child in expr.children() {
+        collect_subqueries(child, subqueries);
+    }
+}
+
+fn collect_aggregates<'a>(expr: &'a BoundExpr, aggregates: &mut Vec<&'a BoundFunction>) {
+    if let BoundExpr::Function(function) = expr {
+        if is_aggregate(function) && function.over.is_none() {
+            aggregates.push(function);
+        }
+    }
+    for child in expr.children() {
+        collect_aggregates(child, aggregates);
+    }
+}
+
+fn has_window(expr: &BoundExpr) -> bool {
+    matches!(expr, BoundExpr::Function(function) if function.over.is_some())
+        || expr.children().into_iter().any(has_window)
+}
+
+fn binary(left: BoundExpr, operator: Operator, right: BoundExpr) -> BoundExpr {
+    BoundExpr::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+        span: OperatorSpan::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Catalog, MemoryCatalog};
+    use crate::execution::pattern::PatternCache;
+    use crate::execution::physical::PhysicalPlan;
+    use crate::execution::spill::QueryMemory;
+    use crate::execution::table::insert_row;
+    use crate::execution::{ExecutionContext, Row};
+    use crate::optimizer::tests::{catalog, plan_in};
+    use crate::optimizer::Optimizer;
+    use crate::planner::explain;
+    use crate::storage::MemoryStore;
+    use crate::value::Value;
+
+    /// Users 3 has no age and no orders, order 4 has no user.
+    fn store(catalog: &MemoryCatalog) -> MemoryStore {
+        let mut store = MemoryStore::new();
+        let user = |id: i64, name: &str, age: Option<i64>| {
+            vec![id.into(), name.into(), age.map_or(Value::Null, Value::from)]
+        };
+        let order = |id: i64, user_id: Option<i64>, total: f64| {
+            vec![
+                id.into(),
+                user_id.map_or(Value::Null, Value::from),
+                total.into(),
+            ]
+        };
+        let users = catalog.table("public", "users").unwrap();
+        for row in [
+            user(1, "ann", Some(20)),
+            user(2, "bob", Some(30)),
+            user(3, "cid", None),
+            user(4, "dan", Some(20)),
+        ] {
+            insert_row(&mut store, users, &row).unwrap();
+        }
+        let orders = catalog.table("public", "orders").unwrap();
+        for row in [
+            order(1, Some(1), 10.0),
+            order(2, Some(1), 20.0),
+            order(3, Some(2), 5.0),
+            order(4, None, 7.0),
+            order(5, Some(5), 1.0),
+        ] {
+            insert_row(&mut store, orders, &row).unwrap();
+        }
+        store
+    }
+
+    /// Rows the engine returns for the plan, sorted so results can be compared as
+    /// multisets. A plan which isn't decorrelated executes its subqueries once per row.
+    fn execute(plan: &LogicalPlan, catalog: &MemoryCatalog) -> Vec<Row> {
+        let mut store = store(catalog);
+        let mut context = ExecutionContext {
+            store: &mut store,
+            catalog,
+            memory: QueryMemory::unlimited(),
+            worker: None,
+            start_time: std::time::SystemTime::now(),
+            patterns: PatternCache::default(),
+            parameters: &[],
+        };
+        let physical = PhysicalPlan::build(plan, catalog).unwrap();
+        let mut rows = crate::execution::execute(physical, &mut context).unwrap();
+        rows.sort_by_key(|row| format!("{:?}", row));
+        rows
+    }
+
+    /// Plan of the query after decorrelation, once the rows it returns were checked
+    /// against the rows of the original plan.
+    fn unnest(query: &str) -> String {
+        let catalog = catalog();
+        let plan = plan_in(&catalog, query);
+        let unnested =
+            Optimizer::new(vec![Box::new(Decorrelation)]).optimize(plan.clone(), &catalog);
+        assert_eq!(
+            execute(&unnested, &catalog),
+            execute(&plan, &catalog),
+            "{}\n{}",
+            query,
+            explain(&unnested)
+        );
+        explain(&unnested)
+    }
+
+    fn names(query: &str) -> Vec<Value> {
+        let catalog = catalog();
+        let plan = Optimizer::new(vec![Box::new(Decorrelation)])
+            .optimize(plan_in(&catalog, query), &catalog);
+        execute(&plan, &catalog)
+            .into_iter()
+            .map(|mut row| row.remove(0))
+            .collect()
+    }
+
+    #[test]
+    fn unnests_exists() {
+        assert_eq!(
+            unnest(
+                "select name from users u where exists \
+                 (select 1 from orders o where o.user_id = u.id and o.total > 6) and u.age > 1"
+            ),
+            "Project: u.name
+  Filter: u.age > 1
+    Join: SEMI ON o.user_id = u.id
+      Scan: public.users AS u
+      Project: o.user_id
+        Filter: o.total > CAST(6 AS DOUBLE)
+          Scan: public.orders AS o"
+        );
+        assert_eq!(
+            unnest(
+                "select name from users u where not exists \
+                 (select * from orders o where o.user_id = u.id)"
+            ),
+            "Project: u.name
+  Join: ANTI ON o.user_id = u.id
+    Scan: public.users AS u
+    Project: o.user_id
+      Scan: public.orders AS o"
+        );
+        assert_eq!(
+            names(
+                "select name from users u where not exists \
+                 (select * from orders o where o.user_id = u.id)"
+            ),
+            vec![Value::from("cid"), Value::from("dan")]
+        );
+        unnest("select name from users u where exists (select 1 from orders o limit 1)");
+        unnest("select name from users u where exists (select 1 where u.age > 25)");
+    }
+
+    #[test]
+    fn unnests_in() {
+        assert_eq!(
+            unnest(
+                "select name from users u where u.id in \
+                 (select o.user_id from orders o where o.total > u.age / 4)"
+            ),
+            "Project: u.name
+  Join: SEMI ON o.total > CAST(u.age / 4 AS DOUBLE) AND u.id = o.user_id
+    Scan: public.users AS u
+    Project: o.total, o.user_id
+      Scan: public.orders AS o"
+        );
+        unnest("select name from users where id in (select user_id from orders)");
+        unnest("select name from users where age in (select user_id * 20 from orders)");
+    }
+
+    #[test]
+    fn keeps_null_semantics_of_not_in() {
+        assert_eq!(
+            unnest("select name from users where id not in (select user_id from orders)"),
+            "Project: users.name
+  Join: ANTI ON users.id = orders.user_id OR users.id IS NULL OR orders.user_id IS NULL
+    Scan: public.users
+    Project: orders.user_id
+      Scan: public.orders"
+        );
+        assert_eq!(
+            names("select name from users where id not in (select user_id from orders)"),
+            vec![]
+        );
+        assert_eq!(
+            names(
+                "select name from users \
+                 where id not in (select user_id from orders where user_id is not null)"
+            ),
+            vec![Value::from("cid"), Value::from("dan")]
+        );
+        assert_eq!(
+            names("select name from users where age not in (select id * 20 from orders)"),
+            vec![Value::from("bob")]
+        );
+        assert_eq!(
+            names("select name from users where age not in (select id from orders where id > 9)"),
+            vec![
+                Value::from("ann"),
+                Value::from("bob"),
+                Value::from("cid"),
+                Value::from("dan")
+            ]
+        );
+        unnest(
+            "select name from users u where not (u.age in \
+             (select o.user_id * 10 from orders o where o.id <> u.id))",
+        );
+    }
+
+    #[test]
+    fn unnests_scalar_aggregates() {
+        assert_eq!(
+            unnest(
+                "select name, (select count(*) from orders o where o.user_id = u.id) \
+                 from users u"
+            ),
+            "Project: u.name, CASE WHEN subquery.user_id IS NULL THEN CAST(0 AS BIGINT) ELSE subquery.count END
+  Join: LEFT ON u.id = subquery.user_id
+    Scan: public.users AS u
+    SubqueryAlias: subquery
+      Project: o.user_id, count(*)
+        Aggregate: group_by=[o.user_id], aggregates=[count(*)]
+          Scan: public.orders AS o"
+        );
+        assert_eq!(
+            unnest(
+                "select name from users u \
+                 where (select sum(o.total) from orders o where u.id = o.user_id) > 6"
+            ),
+            "Project: u.name
+  Project: u.id, u.name, u.age
+    Filter: subquery.sum > CAST(6 AS DOUBLE)
+      Join: LEFT ON u.id = subquery.user_id
+        Scan: public.users AS u
+        SubqueryAlias: subquery
+          Project: o.user_id, sum(o.total)
+            Aggregate: group_by=[o.user_id], aggregates=[sum(o.total)]
+              Scan: public.orders AS o"
+        );
+        unnest(
+            "select name, (select count(o.id) + 1 from orders o \
+             where o.user_id = u.id and o.total < 15) from users u",
+        );
+        unnest(
+            "select name, (select max(o.total) from orders o where o.user_id = u.id) \
+             from users u where u.age > 1",
+        );
+    }
+
+    #[test]
+    fn unnests_with_the_other_rules() {
+        let catalog = catalog();
+        for query in [
+            "select name from users u where exists \
+             (select 1 from orders o where o.user_id = u.id and o.total > 6) and u.age > 1",
+            "select name from users where id not in (select user_id from orders)",
+            "select u.name, o.total from users u join orders o on u.id = o.user_id \
+             where o.total > (select avg(p.total) from orders p where p.user_id = u.id)",
+            "select name, (select count(*) from orders o where o.user_id = u.id) as orders \
+             from users u order by orders desc, name limit 2",
+        ] {
+            let plan = plan_in(&catalog, query);
+            let optimized = crate::optimizer::optimize(plan.clone(), &catalog);
+            assert!(
+                !explain(&optimized).contains("SubPlan"),
+                "{}",
+                explain(&optimized)
+            );
+            assert_eq!(
+                execute(&optimized, &catalog),
+                execute(&plan, &catalog),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_subqueries_it_cannot_unnest() {
+        for query in [
+            "select name from users u \
+             where exists (select 1 from orders o where o.user_id = u.id) or u.age > 25",
+            "select name from users u \
+             where u.id in (select o.user_id from orders o where o.total > 6 limit 1)",
+            "select name from users u \
+             where (select count(*) from orders o where o.user_id < u.id) > 1",
+            "select name, (select o.total from orders o where o.id = u.id) from users u",
+            "select name from users u where exists \
+             (select o.user_id from orders o where o.user_id = u.id group by o.user_id)",
+        ] {
+            let plan = unnest(query);
+            assert!(plan.contains("SubPlan"), "{}", plan);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Turns a filter on a long `IN` list of constants into a semi join with the list, so
+//! the executor can look values up in a hash table instead of comparing each of them.
+//!
+//! Only a list the filter requires is rewritten: `x IN (...)` must be true for the row
+//! to pass, a `NOT IN` or a list under `OR` stays in the filter.
+
+use crate::analyser::bound::*;
+use crate::optimizer::{conjunction, conjuncts, OptimizerContext, OptimizerRule};
+use crate::parser::expr::{Literal, Operator, OperatorSpan};
+use crate::planner::{JoinType, LogicalPlan};
+
+/// Shorter lists are cheaper to compare one by one.
+pub const MIN_LIST_LENGTH: usize = 4;
+
+pub struct InListToSemiJoin;
+
+impl OptimizerRule for InListToSemiJoin {
+    fn name(&self) -> &'static str {
+        "in_list_to_semi_join"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, context: &OptimizerContext) -> Option<LogicalPlan> {
+        let LogicalPlan::Filter { input, predicate } = plan else {
+            return None;
+        };
+        let mut conjuncts = conjuncts(predicate);
+        let position = conjuncts.iter().position(|conjunct| {
+            matches!(
+                conjunct,
+                BoundExpr::InList { list, negated: false, .. }
+                    if list.len() >= MIN_LIST_LENGTH && list.iter().all(is_constant)
+            )
+        })?;
+        let BoundExpr::InList { expr, list, .. } = conjuncts.remove(position) else {
+            unreachable!("position of an in list");
+        };
+
+        let values = LogicalPlan::values(list.into_iter().map(|value| vec![value]).collect());
+        let relation = context.new_relation_id();
+        let list = LogicalPlan::subquery_alias(values, "in_list".to_string(), relation);
+        let on = BoundExpr::Binary {
+            left: expr,
+            operator: Operator::Equal,
+            right: Box::new(list.schema()[0].expr.clone()),
+            span: OperatorSpan::default(),
+        };
+        let join = LogicalPlan::join((**input).clone(), list, JoinType::Semi, Some(on));
+        Some(match conjunction(conjuncts) {
+            Some(predicate) => LogicalPlan::filter(join, predicate),
+            None => join,
+        })
+    }
+}
+
+fn is_constant(expr: &BoundExpr) -> bool {
+    match expr {
+        BoundExpr::Literal(literal) => literal != &Literal::Null,
+        BoundExpr::Cast { expr, .. } | BoundExpr::Unary { expr, .. } => is_constant(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::optimize_with;
+
+    #[test]
+    fn joins_with_long_lists() {
+        assert_eq!(
+            optimize_with(
+                InListToSemiJoin,
+                "select name from users where id in (1, 2, 3, 5) and age > 1"
+            ),
+            "Project: users.name
+  Filter: users.age > 1
+    Join: SEMI ON users.id = in_list.column1
+      Scan: public.users
+      SubqueryAlias: in_list
+        Values: (1), (2), (3), (5)"
+        );
+        assert_eq!(
+            optimize_with(
+                InListToSemiJoin,
+                "select o.id from orders o where o.total in (1, 2, 3, 4)"
+            ),
+            "Project: o.id
+  Join: SEMI ON o.total = in_list.column1
+    Scan: public.orders AS o
+    SubqueryAlias: in_list
+      Values: (CAST(1 AS DOUBLE)), (CAST(2 AS DOUBLE)), (CAST(3 AS DOUBLE)), (CAST(4 AS DOUBLE))"
+        );
+    }
+
+    #[test]
+    fn keeps_lists_the_filter_does_not_require() {
+        for query in [
+            "select name from users where id in (1, 2, 3)",
+            "select name from users where id not in (1, 2, 3, 4)",
+            "select name from users where id in (1, 2, 3, 4) or age > 1",
+            "select name from users where id in (1, 2, 3, age)",
+            "select name from users where id in (1, 2, 3, null)",
+        ] {
+            let plan = optimize_with(InListToSemiJoin, query);
+            assert!(!plan.contains("SEMI"), "{}", plan);
+        }
+    }
+}
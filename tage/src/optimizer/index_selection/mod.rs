@@ -0,0 +1,269 @@
+//! Chooses the index a filtered scan reads instead of the whole table.
+//!
+//! A scan below a filter reads the range of the first index whose first column a
+//! conjunct compares with a constant, `=`, `<`, `<=`, `>` and `>=`, or matches with a
+//! `LIKE` pattern which starts with a literal prefix. The filter stays above the scan
+//! and checks every row the range returns.
+
+use crate::analyser::bound::{BoundExpr, BoundRelation};
+use crate::catalog::Index;
+use crate::execution::pattern::{escape_char, like_range};
+use crate::optimizer::{conjuncts, referenced_columns, OptimizerContext, OptimizerRule};
+use crate::parser::expr::{Literal, Operator, PatternKind};
+use crate::planner::{IndexRange, LogicalPlan};
+use crate::value::DataType;
+use std::ops::Bound;
+
+pub struct IndexSelection;
+
+impl OptimizerRule for IndexSelection {
+    fn name(&self) -> &'static str {
+        "index_selection"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, context: &OptimizerContext) -> Option<LogicalPlan> {
+        let LogicalPlan::Filter { input, predicate } = plan else {
+            return None;
+        };
+        let LogicalPlan::Scan {
+            relation,
+            schema,
+            index,
+        } = input.as_ref()
+        else {
+            return None;
+        };
+        // the predicate may have changed since the range was chosen
+        let range = context
+            .indexes(relation.table.id)
+            .into_iter()
+            .find_map(|candidate| index_range(relation, predicate, candidate));
+        (range != *index).then(|| {
+            let scan = LogicalPlan::Scan {
+                relation: relation.clone(),
+                schema: schema.clone(),
+                index: range,
+            };
+            plan.with_inputs(vec![scan])
+        })
+    }
+}
+
+/// The range of the index the conjuncts of the predicate limit the first indexed column
+/// to, `None` when none does.
+fn index_range(
+    relation: &BoundRelation,
+    predicate: &BoundExpr,
+    index: &Index,
+) -> Option<IndexRange> {
+    let mut low = Bound::Unbounded;
+    let mut high = Bound::Unbounded;
+    for conjunct in conjuncts(predicate) {
+        let (low_bound, high_bound) = match compared_constant(&conjunct, relation, index) {
+            Some((operator, constant)) => match operator {
+                Operator::Equal => (Bound::Included(constant.clone()), Bound::Included(constant)),
+                Operator::GreaterThan => (Bound::Excluded(constant), Bound::Unbounded),
+                Operator::GreaterThanOrEqual => (Bound::Included(constant), Bound::Unbounded),
+                Operator::LessThan => (Bound::Unbounded, Bound::Excluded(constant)),
+                Operator::LessThanOrEqual => (Bound::Unbounded, Bound::Included(constant)),
+                _ => continue,
+            },
+            None => match like_prefix(&conjunct, relation, index) {
+                Some(bounds) => bounds,
+                None => continue,
+            },
+        };
+        if matches!(low, Bound::Unbounded) {
+            low = low_bound;
+        }
+        if matches!(high, Bound::Unbounded) {
+            high = high_bound;
+        }
+    }
+    (!matches!((&low, &high), (Bound::Unbounded, Bound::Unbounded))).then(|| IndexRange {
+        index: index.clone(),
+        low,
+        high,
+    })
+}
+
+/// The comparison of the first indexed column with a constant, as `column <op> constant`.
+/// The constant has a value of the kind of the column, so it is encoded like the
+/// indexed values.
+fn compared_constant(
+    conjunct: &BoundExpr,
+    relation: &BoundRelation,
+    index: &Index,
+) -> Option<(Operator, BoundExpr)> {
+    let BoundExpr::Binary {
+        left,
+        operator,
+        right,
+        ..
+    } = conjunct
+    else {
+        return None;
+    };
+    let is_indexed = |expr: &BoundExpr| match expr {
+        BoundExpr::Column(column) => {
+            column.relation == relation.id && Some(&column.column_id) == index.columns.first()
+        }
+        _ => false,
+    };
+    let (column, operator, constant) = if is_indexed(left) {
+        (left, *operator, right)
+    } else if is_indexed(right) {
+        let flipped = match operator {
+            Operator::LessThan => Operator::GreaterThan,
+            Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+            Operator::GreaterThan => Operator::LessThan,
+            Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+            operator => *operator,
+        };
+        (right, flipped, left)
+    } else {
+        return None;
+    };
+    let kind = |data_type: DataType| match data_type {
+        data_type if data_type.is_integer() => 0,
+        DataType::Real | DataType::Double | DataType::Decimal(_) => 1,
+        data_type if data_type.is_string() => 2,
+        DataType::Boolean => 3,
+        _ => 4,
+    };
+    let same_kind = match (column.data_type(), constant.data_type()) {
+        (Some(column), Some(constant)) => kind(column) == kind(constant) && kind(column) < 4,
+        _ => false,
+    };
+    (same_kind && referenced_columns(constant).is_empty() && is_constant(constant))
+        .then(|| (operator, (**constant).clone()))
+}
+
+/// The range of the first indexed column which `column LIKE 'prefix%'` matches in.
+fn like_prefix(
+    conjunct: &BoundExpr,
+    relation: &BoundRelation,
+    index: &Index,
+) -> Option<(Bound<BoundExpr>, Bound<BoundExpr>)> {
+    let BoundExpr::Like {
+        expr,
+        pattern,
+        escape,
+        kind: PatternKind::Like,
+        negated: false,
+    } = conjunct
+    else {
+        return None;
+    };
+    let BoundExpr::Column(column) = expr.as_ref() else {
+        return None;
+    };
+    if column.relation != relation.id
+        || Some(&column.column_id) != index.columns.first()
+        || !column.data_type.is_string()
+    {
+        return None;
+    }
+    let BoundExpr::Literal(Literal::String(pattern)) = pattern.as_ref() else {
+        return None;
+    };
+    let escape = match escape.as_deref() {
+        None => escape_char(None),
+        Some(BoundExpr::Literal(Literal::String(escape))) => escape_char(Some(escape)),
+        Some(_) => return None,
+    };
+    let (prefix, end) = like_range(pattern, escape.ok()?)?;
+    let text = |text: String| BoundExpr::Literal(Literal::String(text));
+    Some((
+        Bound::Included(text(prefix)),
+        end.map_or(Bound::Unbounded, |end| Bound::Excluded(text(end))),
+    ))
+}
+
+/// Whether the expression is computed without reading rows.
+fn is_constant(expr: &BoundExpr) -> bool {
+    match expr {
+        BoundExpr::Literal(_) => true,
+        BoundExpr::Cast { expr, .. } | BoundExpr::Unary { expr, .. } => is_constant(expr),
+        BoundExpr::Binary { left, right, .. } => is_constant(left) && is_constant(right),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Catalog, MemoryCatalog, ObjectId, Table, TableId};
+    use crate::optimizer::tests::{catalog, plan_in};
+    use crate::optimizer::Optimizer;
+    use crate::planner::explain;
+
+    /// The test catalog with an index on `users(age)`.
+    struct IndexedCatalog {
+        catalog: MemoryCatalog,
+        index: Index,
+    }
+
+    impl Catalog for IndexedCatalog {
+        fn table(&self, schema_name: &str, table_name: &str) -> Option<&Table> {
+            self.catalog.table(schema_name, table_name)
+        }
+
+        fn indexes(&self, table: TableId) -> Vec<&Index> {
+            match table == self.index.table {
+                true => vec![&self.index],
+                false => vec![],
+            }
+        }
+    }
+
+    fn optimized(input: &str) -> String {
+        let catalog = catalog();
+        let users = catalog.table("public", "users").unwrap().clone();
+        let plan = plan_in(&catalog, input);
+        let catalog = IndexedCatalog {
+            index: Index {
+                id: ObjectId(100),
+                schema_name: "public".to_string(),
+                name: "users_age".to_string(),
+                table: users.id,
+                columns: vec![users.column("age").unwrap().id],
+                unique: false,
+            },
+            catalog,
+        };
+        explain(&Optimizer::new(vec![Box::new(IndexSelection)]).optimize(plan, &catalog))
+    }
+
+    #[test]
+    fn chooses_index_ranges() {
+        assert_eq!(
+            optimized("select name from users where age >= 18 and 65 > age and name = 'a'"),
+            "Project: users.name
+  Filter: users.age >= 18 AND 65 > users.age AND users.name = 'a'
+    Scan: public.users [index users_age: users.age >= 18 AND users.age < 65]"
+        );
+        assert_eq!(
+            optimized("select name from users where age = 3"),
+            "Project: users.name
+  Filter: users.age = 3
+    Scan: public.users [index users_age: users.age = 3]"
+        );
+    }
+
+    #[test]
+    fn keeps_scans_without_a_range() {
+        assert_eq!(
+            optimized("select name from users where age <> 3 or age < 2"),
+            "Project: users.name
+  Filter: users.age <> 3 OR users.age < 2
+    Scan: public.users"
+        );
+        assert_eq!(
+            optimized("select name from users where id = 3"),
+            "Project: users.name
+  Filter: users.id = 3
+    Scan: public.users"
+        );
+    }
+}
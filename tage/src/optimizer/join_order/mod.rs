@@ -0,0 +1,764 @@
+//! Chooses the order of joins and the algorithm of each join by their estimated cost.
+//!
+//! Inner and cross joins can be executed in any order. The inputs of a tree of them are
+//! the leaves of a join graph in which each condition connects the leaves it reads.
+//! Dynamic programming over the connected subgraphs (DPccp, Moerkotte and Neumann) finds
+//! the cheapest tree for up to `MAX_DP_LEAVES` leaves, larger graphs are joined greedily,
+//! always the two parts with the smallest result. Parts of the graph without a condition
+//! between them are joined with cross products.
+//!
+//! Each join gets the algorithm with the lowest cost: a hash join builds a table of its
+//! right input, a merge join sorts inputs which aren't already sorted on the key, both
+//! need a condition equating the inputs, a nested loop join compares all pairs of rows.
+//! Outer, semi and anti joins keep their order and only get an algorithm.
+//!
+//! The node above a tree of joins reorders it, so the tree is reordered as a whole.
+
+use crate::analyser::bound::{BoundExpr, ProjectionItem, RelationId};
+use crate::optimizer::cardinality::{estimate_rows, selectivity};
+use crate::optimizer::{
+    conjunction, conjuncts, evaluable, referenced_columns, OptimizerContext, OptimizerRule,
+};
+use crate::parser::expr::Operator;
+use crate::planner::{JoinAlgorithm, JoinType, LogicalPlan, PlanColumn};
+
+/// Join graphs with more leaves are joined greedily.
+pub const MAX_DP_LEAVES: usize = 12;
+
+pub struct JoinOrder;
+
+impl OptimizerRule for JoinOrder {
+    fn name(&self) -> &'static str {
+        "join_order"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, context: &OptimizerContext) -> Option<LogicalPlan> {
+        if is_reorderable(plan) {
+            return None;
+        }
+        // projections and aggregates name the columns they read, other nodes return the
+        // columns of the tree in its order
+        let keep_order = !matches!(
+            plan,
+            LogicalPlan::Project { .. } | LogicalPlan::Aggregate { .. }
+        );
+        let inputs = plan.inputs();
+        let reordered: Vec<_> = inputs
+            .iter()
+            .map(|input| {
+                is_join_tree(input)
+                    .then(|| reorder(input, keep_order, context))
+                    .flatten()
+            })
+            .collect();
+        let mut rewritten = None;
+        if reordered.iter().any(Option::is_some) {
+            let inputs = inputs
+                .into_iter()
+                .zip(reordered)
+                .map(|(input, reordered)| reordered.unwrap_or_else(|| input.clone()))
+                .collect();
+            rewritten = Some(plan.with_inputs(inputs));
+        }
+        let join = rewritten.as_ref().unwrap_or(plan);
+        if let LogicalPlan::Join {
+            left,
+            right,
+            on,
+            algorithm: None,
+            ..
+        } = join
+        {
+            let conditions: Vec<_> = on.iter().flat_map(conjuncts).collect();
+            let rows = (
+                estimate_rows(left, context),
+                estimate_rows(right, context),
+                estimate_rows(join, context),
+            );
+            let (algorithm, _) = algorithm_costs(left, right, rows, &conditions)
+                .into_iter()
+                .reduce(|best, next| if next.1 < best.1 { next } else { best })
+                .expect("a nested loop join is always possible");
+            rewritten = Some(join.clone().with_algorithm(Some(algorithm)));
+        }
+        rewritten
+    }
+}
+
+fn is_reorderable(plan: &LogicalPlan) -> bool {
+    matches!(
+        plan,
+        LogicalPlan::Join {
+            join_type: JoinType::Inner | JoinType::Cross,
+            ..
+        }
+    )
+}
+
+/// Whether the plan is a tree of inner joins, maybe with the columns reordered.
+fn is_join_tree(plan: &LogicalPlan) -> bool {
+    match plan {
+        LogicalPlan::Project { input, .. } => is_permutation(plan) && is_reorderable(input),
+        plan => is_reorderable(plan),
+    }
+}
+
+/// Whether the node is a projection which returns the columns of its input in another
+/// order, as the rule adds them above reordered joins.
+fn is_permutation(plan: &LogicalPlan) -> bool {
+    let LogicalPlan::Project { input, items, .. } = plan else {
+        return false;
+    };
+    let schema = input.schema();
+    items.len() == schema.len()
+        && items.iter().all(|item| {
+            matches!(item.expr, BoundExpr::Column(_))
+                && schema
+                    .iter()
+                    .any(|column| column.expr == item.expr && column.name == item.name)
+        })
+        && schema
+            .iter()
+            .all(|column| items.iter().any(|item| item.expr == column.expr))
+}
+
+/// The cheapest tree of joins of the leaves of the tree, `None` if it is the tree itself.
+/// Its columns stay in the order of the tree if `keep_order` is set.
+fn reorder(
+    tree: &LogicalPlan,
+    keep_order: bool,
+    context: &OptimizerContext,
+) -> Option<LogicalPlan> {
+    let mut leaves = vec![];
+    let mut conditions = vec![];
+    flatten(tree, &mut leaves, &mut conditions);
+    let graph = JoinGraph::new(leaves, conditions, context)?;
+    let joined = match graph.leaves.len() {
+        count if count <= MAX_DP_LEAVES => graph.dynamic_programming(),
+        _ => graph.greedy(),
+    };
+    let plan = match keep_order {
+        true => with_schema(joined.plan, tree.schema()),
+        false => joined.plan,
+    };
+    (&plan != tree).then_some(plan)
+}
+
+fn flatten(plan: &LogicalPlan, leaves: &mut Vec<LogicalPlan>, conditions: &mut Vec<BoundExpr>) {
+    match plan {
+        LogicalPlan::Join {
+            left, right, on, ..
+        } if is_reorderable(plan) => {
+            flatten(left, leaves, conditions);
+            flatten(right, leaves, conditions);
+            conditions.extend(on.iter().flat_map(conjuncts));
+        }
+        LogicalPlan::Project { input, .. } if is_join_tree(plan) => {
+            flatten(input, leaves, conditions)
+        }
+        leaf => leaves.push(leaf.clone()),
+    }
+}
+
+/// The plan with its columns in the order of the schema.
+fn with_schema(plan: LogicalPlan, schema: &[PlanColumn]) -> LogicalPlan {
+    if plan.schema() == schema {
+        return plan;
+    }
+    let items = schema
+        .iter()
+        .map(|column| ProjectionItem {
+            expr: column.expr.clone(),
+            name: column.name.clone(),
+        })
+        .collect();
+    LogicalPlan::project(plan, items)
+}
+
+/// Relation which identifies a leaf, the leaves are numbered in its order so the result
+/// doesn't depend on the order of the joins in the query.
+fn leaf_key(plan: &LogicalPlan) -> Option<RelationId> {
+    match plan {
+        LogicalPlan::Scan { relation, .. } => Some(relation.id),
+        LogicalPlan::SubqueryAlias { relation, .. } => Some(*relation),
+        LogicalPlan::Filter { input, .. } => leaf_key(input),
+        plan => plan.schema().iter().find_map(|column| match &column.expr {
+            BoundExpr::Column(column) => Some(column.relation),
+            _ => None,
+        }),
+    }
+}
+
+/// A plan for a set of leaves.
+#[derive(Debug, Clone)]
+struct Candidate {
+    plan: LogicalPlan,
+    rows: f64,
+    /// Of all joins of the plan
+    cost: f64,
+}
+
+/// Leaves of a tree of joins, the sets of leaves are bitsets of their positions.
+struct JoinGraph {
+    leaves: Vec<LogicalPlan>,
+    rows: Vec<f64>,
+    /// Conditions on at least two leaves with the leaves they read
+    conditions: Vec<(u32, BoundExpr)>,
+    selectivities: Vec<f64>,
+    /// Leaves connected with each leaf by a condition or a cross product
+    neighbors: Vec<u32>,
+}
+
+impl JoinGraph {
+    /// Conditions on a single leaf filter it, conditions on none the first leaf. `None` if
+    /// a leaf has columns which don't belong to a relation.
+    fn new(
+        leaves: Vec<LogicalPlan>,
+        conditions: Vec<BoundExpr>,
+        context: &OptimizerContext,
+    ) -> Option<Self> {
+        let mut leaves = leaves
+            .into_iter()
+            .map(|leaf| Some((leaf_key(&leaf)?, leaf)))
+            .collect::<Option<Vec<_>>>()?;
+        leaves.sort_by_key(|(key, _)| *key);
+        let mut leaves: Vec<_> = leaves.into_iter().map(|(_, leaf)| leaf).collect();
+        let relations = leaves
+            .iter()
+            .map(|leaf| {
+                leaf.schema()
+                    .iter()
+                    .map(|column| match &column.expr {
+                        BoundExpr::Column(column) => Some(column.relation),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut filters = vec![vec![]; leaves.len()];
+        let mut edges = vec![];
+        for condition in conditions {
+            // columns of no leaf are columns of an outer query
+            let set = referenced_columns(&condition)
+                .iter()
+                .filter_map(|column| {
+                    relations
+                        .iter()
+                        .position(|relations| relations.contains(&column.relation))
+                })
+                .fold(0u32, |set, leaf| set | 1 << leaf);
+            match set.count_ones() {
+                0 => filters[0].push(condition),
+                1 => filters[set.trailing_zeros() as usize].push(condition),
+                _ => edges.push((set, condition)),
+            }
+        }
+        // the conditions of a join in a fixed order
+        edges.sort_by_key(|(set, _)| *set);
+        for (leaf, filters) in leaves.iter_mut().zip(filters) {
+            if !filters.is_empty() {
+                *leaf = filtered(leaf.clone(), filters);
+            }
+        }
+
+        let mut neighbors = vec![0; leaves.len()];
+        for (set, _) in &edges {
+            for (leaf, neighbors) in neighbors.iter_mut().enumerate() {
+                if set & 1 << leaf != 0 {
+                    *neighbors |= set & !(1 << leaf);
+                }
+            }
+        }
+        connect_components(&mut neighbors);
+        Some(JoinGraph {
+            rows: leaves
+                .iter()
+                .map(|leaf| estimate_rows(leaf, context))
+                .collect(),
+            selectivities: edges
+                .iter()
+                .map(|(_, condition)| selectivity(condition, context))
+                .collect(),
+            leaves,
+            conditions: edges,
+            neighbors,
+        })
+    }
+
+    fn leaf(&self, leaf: usize) -> Candidate {
+        Candidate {
+            plan: self.leaves[leaf].clone(),
+            rows: self.rows[leaf],
+            cost: 0.0,
+        }
+    }
+
+    /// Estimated rows of the join of the leaves, it doesn't depend on the join order.
+    fn rows(&self, set: u32) -> f64 {
+        let leaves: f64 = bits(set).map(|leaf| self.rows[leaf]).product();
+        let selectivity: f64 = self
+            .conditions
+            .iter()
+            .zip(&self.selectivities)
+            .filter(|((condition, _), _)| condition & set == *condition)
+            .map(|(_, selectivity)| selectivity)
+            .product();
+        leaves * selectivity
+    }
+
+    /// The cheapest join of the two plans, either of them may be the left input.
+    fn join(&self, left: (u32, &Candidate), right: (u32, &Candidate)) -> Candidate {
+        let set = left.0 | right.0;
+        let conditions: Vec<_> = self
+            .conditions
+            .iter()
+            .filter(|(condition, _)| {
+                condition & set == *condition
+                    && condition & left.0 != *condition
+                    && condition & right.0 != *condition
+            })
+            .map(|(_, condition)| condition.clone())
+            .collect();
+        let join_type = match conditions.is_empty() {
+            true => JoinType::Cross,
+            false => JoinType::Inner,
+        };
+        let rows = self.rows(set);
+        let mut best: Option<Candidate> = None;
+        for (left, right) in [(left.1, right.1), (right.1, left.1)] {
+            let inputs = (left.rows, right.rows, rows);
+            for (algorithm, cost) in algorithm_costs(&left.plan, &right.plan, inputs, &conditions) {
+                let cost = left.cost + right.cost + cost;
+                if best.as_ref().is_some_and(|best| best.cost <= cost) {
+                    continue;
+                }
+                let plan = LogicalPlan::join(
+                    left.plan.clone(),
+                    right.plan.clone(),
+                    join_type,
+                    conjunction(conditions.clone()),
+                );
+                best = Some(Candidate {
+                    plan: plan.with_algorithm(Some(algorithm)),
+                    rows,
+                    cost,
+                });
+            }
+        }
+        best.expect("a nested loop join is always possible")
+    }
+
+    /// The cheapest plan of each connected set of leaves from the cheapest plans of the
+    /// two sets it joins, sets are joined after their subsets.
+    fn dynamic_programming(&self) -> Candidate {
+        let mut best: Vec<Option<Candidate>> = vec![None; 1 << self.leaves.len()];
+        for leaf in 0..self.leaves.len() {
+            best[1 << leaf] = Some(self.leaf(leaf));
+        }
+        let mut pairs = vec![];
+        connected_pairs(&self.neighbors, &mut |left, right| {
+            pairs.push((left, right))
+        });
+        pairs.sort_by_key(|(left, right)| (left | right).count_ones());
+        for (left, right) in pairs {
+            let (Some(left_plan), Some(right_plan)) = (&best[left as usize], &best[right as usize])
+            else {
+                unreachable!("subsets are planned first");
+            };
+            let joined = self.join((left, left_plan), (right, right_plan));
+            let set = (left | right) as usize;
+            if best[set]
+                .as_ref()
+                .is_none_or(|best| joined.cost < best.cost)
+            {
+                best[set] = Some(joined);
+            }
+        }
+        best.pop().flatten().expect("the join graph is connected")
+    }
+
+    /// Joins the two connected parts with the smallest result until one part is left.
+    fn greedy(&self) -> Candidate {
+        let mut parts: Vec<_> = (0..self.leaves.len())
+            .map(|leaf| (1u32 << leaf, self.leaf(leaf)))
+            .collect();
+        while parts.len() > 1 {
+            let mut smallest: Option<(usize, usize, f64)> = None;
+            for first in 0..parts.len() {
+                for second in first + 1..parts.len() {
+                    let (left, right) = (parts[first].0, parts[second].0);
+                    if neighborhood(&self.neighbors, left) & right == 0 {
+                        continue;
+                    }
+                    let rows = self.rows(left | right);
+                    if smallest.is_none_or(|(_, _, smallest)| rows < smallest) {
+                        smallest = Some((first, second, rows));
+                    }
+                }
+            }
+            let (first, second, _) = smallest.expect("the join graph is connected");
+            let (right, right_plan) = parts.remove(second);
+            let (left, left_plan) = &parts[first];
+            let joined = self.join((*left, left_plan), (right, &right_plan));
+            parts[first] = (left | right, joined);
+        }
+        let (_, plan) = parts.pop().expect("a join has leaves");
+        plan
+    }
+}
+
+/// Costs of the algorithms which can join the inputs with the conditions, from the rows of
+/// the inputs and of the result.
+fn algorithm_costs(
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+    (left_rows, right_rows, rows): (f64, f64, f64),
+    conditions: &[BoundExpr],
+) -> Vec<(JoinAlgorithm, f64)> {
+    let mut costs = vec![];
+    if let Some((left_key, right_key)) = equi_key(left, right, conditions) {
+        costs.push((JoinAlgorithm::Hash, 1.5 * right_rows + left_rows + rows));
+        let sort = |plan: &LogicalPlan, key: &BoundExpr, rows: f64| match sorted_on(plan, key) {
+            true => 0.0,
+            false => rows * rows.max(2.0).log2(),
+        };
+        let sorts = sort(left, left_key, left_rows) + sort(right, right_key, right_rows);
+        costs.push((JoinAlgorithm::Merge, sorts + left_rows + right_rows + rows));
+    }
+    costs.push((JoinAlgorithm::NestedLoop, left_rows * right_rows + rows));
+    costs
+}
+
+/// Expressions of the left and of the right input a condition equates.
+fn equi_key<'a>(
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+    conditions: &'a [BoundExpr],
+) -> Option<(&'a BoundExpr, &'a BoundExpr)> {
+    conditions.iter().find_map(|condition| {
+        let BoundExpr::Binary {
+            left: first,
+            operator: Operator::Equal,
+            right: second,
+            ..
+        } = condition
+        else {
+            return None;
+        };
+        let reads = |plan: &LogicalPlan, expr: &BoundExpr| {
+            !referenced_columns(expr).is_empty() && evaluable(plan, expr)
+        };
+        if reads(left, first) && reads(right, second) {
+            Some((first.as_ref(), second.as_ref()))
+        } else if reads(left, second) && reads(right, first) {
+            Some((second.as_ref(), first.as_ref()))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the rows are sorted on the key. A scan returns the rows in the order of a
+/// primary key of one column.
+fn sorted_on(plan: &LogicalPlan, key: &BoundExpr) -> bool {
+    match (plan, key) {
+        (LogicalPlan::Filter { input, .. }, key) => sorted_on(input, key),
+        (LogicalPlan::Scan { relation, .. }, BoundExpr::Column(column)) => {
+            column.relation == relation.id && relation.table.primary_key() == [column.column_id]
+        }
+        _ => false,
+    }
+}
+
+fn filtered(plan: LogicalPlan, mut conditions: Vec<BoundExpr>) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Filter { input, predicate } => {
+            let mut all = conjuncts(&predicate);
+            all.append(&mut conditions);
+            filtered(*input, all)
+        }
+        plan => match conjunction(conditions) {
+            Some(predicate) => LogicalPlan::filter(plan, predicate),
+            None => plan,
+        },
+    }
+}
+
+/// Connects all leaves of different components of the graph, they are joined with cross
+/// products.
+fn connect_components(neighbors: &mut [u32]) {
+    let mut components = vec![];
+    let mut reached = 0u32;
+    for leaf in 0..neighbors.len() {
+        if reached & 1 << leaf != 0 {
+            continue;
+        }
+        let mut component = 1u32 << leaf;
+        loop {
+            let grown = component | neighborhood(neighbors, component);
+            if grown == component {
+                break;
+            }
+            component = grown;
+        }
+        reached |= component;
+        components.push(component);
+    }
+    if components.len() == 1 {
+        return;
+    }
+    for component in components {
+        for leaf in bits(component) {
+            neighbors[leaf] |= reached & !component;
+        }
+    }
+}
+
+/// Leaves connected with a leaf of the set which aren't in the set.
+fn neighborhood(neighbors: &[u32], set: u32) -> u32 {
+    bits(set).fold(0, |neighborhood, leaf| neighborhood | neighbors[leaf]) & !set
+}
+
+/// Positions of the bits of the set, ascending.
+fn bits(set: u32) -> impl DoubleEndedIterator<Item = usize> {
+    (0..u32::BITS as usize).filter(move |bit| set & 1 << bit != 0)
+}
+
+/// Non-empty subsets of the set.
+fn subsets(set: u32) -> impl Iterator<Item = u32> {
+    let mut next = set;
+    std::iter::from_fn(move || {
+        let subset = next;
+        next = subset.wrapping_sub(1) & set;
+        (subset != 0).then_some(subset)
+    })
+}
+
+/// Calls `emit` once for each pair of disjoint connected sets of leaves which are
+/// connected with each other (DPccp), the first set has the lowest leaf of the two.
+fn connected_pairs(neighbors: &[u32], emit: &mut impl FnMut(u32, u32)) {
+    for leaf in (0..neighbors.len()).rev() {
+        let set = 1 << leaf;
+        // leaves up to this one start sets of their own
+        let excluded = (1 << (leaf + 1)) - 1;
+        emit_complements(neighbors, set, emit);
+        enumerate_sets(neighbors, set, excluded, &mut |set| {
+            emit_complements(neighbors, set, emit)
+        });
+    }
+}
+
+/// Calls `found` with each connected superset of the set which has no excluded leaves.
+fn enumerate_sets(neighbors: &[u32], set: u32, excluded: u32, found: &mut impl FnMut(u32)) {
+    let neighborhood = neighborhood(neighbors, set) & !excluded;
+    for subset in subsets(neighborhood) {
+        found(set | subset);
+    }
+    for subset in subsets(neighborhood) {
+        enumerate_sets(neighbors, set | subset, excluded | neighborhood, found);
+    }
+}
+
+/// Emits the set with each connected set which is connected with it and only has leaves
+/// above the lowest leaf of the set.
+fn emit_complements(neighbors: &[u32], set: u32, emit: &mut impl FnMut(u32, u32)) {
+    let lowest = set.trailing_zeros();
+    let excluded = set | ((1 << (lowest + 1)) - 1);
+    let neighborhood = neighborhood(neighbors, set) & !excluded;
+    for leaf in bits(neighborhood).rev() {
+        let complement = 1 << leaf;
+        emit(set, complement);
+        let below = neighborhood & ((1 << (leaf + 1)) - 1);
+        enumerate_sets(neighbors, complement, excluded | below, &mut |complement| {
+            emit(set, complement)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::statistics::{ColumnStatistics, TableStatistics};
+    use crate::catalog::{Catalog, ColumnId, MemoryCatalog};
+    use crate::optimizer::cardinality::tests::analyzed_catalog;
+    use crate::optimizer::predicate_pushdown::PredicatePushdown;
+    use crate::optimizer::tests::plan_in;
+    use crate::optimizer::Optimizer;
+    use crate::planner::explain;
+    use crate::value::DataType;
+
+    fn pairs(neighbors: &[u32]) -> Vec<(u32, u32)> {
+        let mut pairs = vec![];
+        connected_pairs(neighbors, &mut |left, right| pairs.push((left, right)));
+        pairs
+    }
+
+    #[test]
+    fn enumerates_connected_pairs() {
+        for count in 2..=10usize {
+            let chain: Vec<u32> = (0..count)
+                .map(|leaf| (1 << leaf >> 1 | 1 << leaf << 1) & ((1 << count) - 1))
+                .collect();
+            assert_eq!(pairs(&chain).len(), (count.pow(3) - count) / 6);
+
+            let star: Vec<u32> = (0..count)
+                .map(|leaf| match leaf {
+                    0 => (1 << count) - 2,
+                    _ => 1,
+                })
+                .collect();
+            assert_eq!(pairs(&star).len(), (count - 1) << (count - 2));
+
+            let clique: Vec<u32> = (0..count)
+                .map(|leaf| ((1 << count) - 1) & !(1 << leaf))
+                .collect();
+            assert_eq!(
+                pairs(&clique).len(),
+                (3usize.pow(count as u32) - 1) / 2 - (1 << count) + 1
+            );
+        }
+
+        // each pair once, as found by trying all pairs of sets
+        let cycle = [0b0110, 0b1001, 0b1001, 0b0110];
+        let connected = |set: u32| {
+            let mut reached = 1 << set.trailing_zeros();
+            while reached != reached | neighborhood(&cycle, reached) & set {
+                reached |= neighborhood(&cycle, reached) & set;
+            }
+            reached == set
+        };
+        let mut expected = vec![];
+        for left in 1..16u32 {
+            for right in 1..16u32 {
+                if left & right == 0
+                    && left.trailing_zeros() < right.trailing_zeros()
+                    && connected(left)
+                    && connected(right)
+                    && neighborhood(&cycle, left) & right != 0
+                {
+                    expected.push((left, right));
+                }
+            }
+        }
+        let mut pairs = pairs(&cycle);
+        pairs.sort();
+        assert_eq!(pairs, expected);
+    }
+
+    /// Tables `t1` to `tn` with `id` and `next` columns, `next` references the next table.
+    /// Each table has the number of rows of its position in `rows`.
+    fn chain(rows: &[u64]) -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        for (position, rows) in rows.iter().enumerate() {
+            let name = format!("t{}", position + 1);
+            let table = catalog.create_table(
+                "public",
+                &name,
+                &[("id", DataType::Integer), ("next", DataType::Integer)],
+            );
+            catalog.set_primary_key(table, &["id"]);
+            let column = |column, distinct_count| ColumnStatistics {
+                column: ColumnId(column),
+                distinct_count,
+                null_fraction: 0.0,
+                min: None,
+                max: None,
+                histogram: vec![],
+            };
+            catalog.set_statistics(TableStatistics {
+                table: catalog.table("public", &name).unwrap().id,
+                row_count: *rows,
+                columns: vec![column(1, *rows), column(2, (*rows).min(10))],
+            });
+        }
+        catalog
+    }
+
+    fn chain_query(tables: usize) -> String {
+        let mut query = "select t1.id from t1".to_string();
+        for table in 2..=tables {
+            query += &format!(" join t{} on t{}.next = t{}.id", table, table - 1, table);
+        }
+        query
+    }
+
+    fn optimize_in(catalog: &MemoryCatalog, input: &str) -> String {
+        let optimizer = Optimizer::new(vec![Box::new(PredicatePushdown), Box::new(JoinOrder)]);
+        let plan = optimizer.optimize(plan_in(catalog, input), catalog);
+        // reordered joins don't change any more
+        assert_eq!(optimizer.optimize(plan.clone(), catalog), plan);
+        explain(&plan)
+    }
+
+    #[test]
+    fn orders_joins_by_cost() {
+        // the small tables are joined first, the large one is probed last
+        let catalog = chain(&[100_000, 1000, 10]);
+        assert_eq!(
+            optimize_in(&catalog, &chain_query(3)),
+            "Project: t1.id
+  Join: INNER ON t1.next = t2.id [hash]
+    Scan: public.t1
+    Join: INNER ON t2.next = t3.id [hash]
+      Scan: public.t2
+      Scan: public.t3"
+        );
+        assert_eq!(
+            optimize_in(
+                &catalog,
+                "select t1.id from t3, t1, t2 where t1.next = t2.id and t2.next = t3.id"
+            ),
+            optimize_in(&catalog, &chain_query(3))
+        );
+    }
+
+    #[test]
+    fn joins_unconnected_tables_with_cross_products() {
+        let catalog = chain(&[1000, 1000, 10]);
+        assert_eq!(
+            optimize_in(
+                &catalog,
+                "select * from t1 cross join t2 join t3 on t1.next = t3.id"
+            ),
+            "Project: t1.id, t1.next, t2.id, t2.next, t3.id, t3.next
+  Join: INNER ON t1.next = t3.id [hash]
+    Join: CROSS [nested loop]
+      Scan: public.t2
+      Scan: public.t3
+    Scan: public.t1"
+        );
+    }
+
+    #[test]
+    fn chooses_join_algorithms() {
+        let catalog = analyzed_catalog();
+        // both inputs are sorted on their primary keys
+        assert!(optimize_in(
+            &catalog,
+            "select u.id from users u join users v on u.id = v.id"
+        )
+        .contains("[merge]"));
+        let plan = optimize_in(
+            &catalog,
+            "select u.id from users u join orders o on u.age < o.total",
+        );
+        assert!(plan.contains("< o.total [nested loop]"), "{}", plan);
+        assert!(optimize_in(
+            &catalog,
+            "select u.id from users u left join orders o on u.id = o.user_id"
+        )
+        .contains("Join: LEFT ON u.id = o.user_id [hash]"));
+    }
+
+    #[test]
+    fn joins_many_tables_greedily() {
+        let rows: Vec<u64> = (1..=MAX_DP_LEAVES as u64 + 2)
+            .map(|table| table * 100)
+            .collect();
+        let catalog = chain(&rows);
+        let plan = optimize_in(&catalog, &chain_query(rows.len()));
+        assert_eq!(plan.matches("Join: INNER").count(), rows.len() - 1);
+        assert!(!plan.contains("CROSS"), "{}", plan);
+    }
+}
@@ -0,0 +1,211 @@
+//! Moves limits towards the scans, so the inputs stop producing rows early.
+//!
+//! A limit moves below a projection and merges with a limit below it. Inputs which
+//! return at least one row for each of their rows, the inputs of `union all`, the
+//! preserved side of an outer join and both sides of a cross join, get a copy of the
+//! limit which includes the offset, the limit itself stays on top.
+
+use crate::optimizer::{OptimizerContext, OptimizerRule};
+use crate::planner::{JoinType, LogicalPlan};
+
+pub struct LimitPushdown;
+
+impl OptimizerRule for LimitPushdown {
+    fn name(&self) -> &'static str {
+        "limit_pushdown"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        let LogicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } = plan
+        else {
+            return None;
+        };
+        match input.as_ref() {
+            LogicalPlan::Project { input: below, .. } => {
+                let limited = LogicalPlan::limit((**below).clone(), *limit, *offset);
+                Some(input.with_inputs(vec![limited]))
+            }
+            LogicalPlan::Limit {
+                input: below,
+                limit: inner_limit,
+                offset: inner_offset,
+            } => {
+                // the outer offset skips rows the inner limit returns
+                let remaining = inner_limit.map(|inner| inner.saturating_sub(*offset));
+                let limit = match (remaining, limit) {
+                    (Some(remaining), Some(limit)) => Some(remaining.min(*limit)),
+                    (remaining, limit) => remaining.or(*limit),
+                };
+                Some(LogicalPlan::limit(
+                    (**below).clone(),
+                    limit,
+                    inner_offset + offset,
+                ))
+            }
+            LogicalPlan::Union {
+                inputs, all: true, ..
+            } => {
+                let rows = (*limit)? + offset;
+                let pushed: Vec<_> = inputs.iter().map(|input| limited(input, rows)).collect();
+                if pushed.iter().all(Option::is_none) {
+                    return None;
+                }
+                let inputs = inputs
+                    .iter()
+                    .zip(pushed)
+                    .map(|(input, pushed)| pushed.unwrap_or_else(|| input.clone()))
+                    .collect();
+                Some(plan.with_inputs(vec![input.with_inputs(inputs)]))
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                ..
+            } => {
+                let rows = (*limit)? + offset;
+                let (left, right) = match join_type {
+                    JoinType::Left => (limited(left, rows), None),
+                    JoinType::Right => (None, limited(right, rows)),
+                    JoinType::Cross => (limited(left, rows), limited(right, rows)),
+                    _ => return None,
+                };
+                if left.is_none() && right.is_none() {
+                    return None;
+                }
+                let inputs = input
+                    .inputs()
+                    .into_iter()
+                    .zip([left, right])
+                    .map(|(input, pushed)| pushed.unwrap_or_else(|| input.clone()))
+                    .collect();
+                Some(plan.with_inputs(vec![input.with_inputs(inputs)]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The input limited to the rows, `None` if it already returns no more.
+fn limited(input: &LogicalPlan, rows: u64) -> Option<LogicalPlan> {
+    match max_rows(input) {
+        Some(max_rows) if max_rows <= rows => None,
+        _ => Some(LogicalPlan::limit(input.clone(), Some(rows), 0)),
+    }
+}
+
+/// Number of rows the plan returns at most, as far as its limits tell.
+fn max_rows(plan: &LogicalPlan) -> Option<u64> {
+    match plan {
+        LogicalPlan::Limit { limit, .. } => *limit,
+        LogicalPlan::Project { input, .. } => max_rows(input),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::{catalog, optimize_with, plan};
+    use crate::optimizer::Optimizer;
+    use crate::planner::explain;
+
+    #[test]
+    fn moves_limits_below_projections() {
+        assert_eq!(
+            optimize_with(LimitPushdown, "select name from users limit 5 offset 3"),
+            "Project: users.name
+  Limit: 5 OFFSET 3
+    Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn copies_limits_into_inputs() {
+        assert_eq!(
+            optimize_with(
+                LimitPushdown,
+                "select u.name from users u left join orders o on u.id = o.user_id limit 5 offset 1"
+            ),
+            "Project: u.name
+  Limit: 5 OFFSET 1
+    Join: LEFT ON u.id = o.user_id
+      Limit: 6
+        Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                LimitPushdown,
+                "select u.name from users u, orders o limit 3"
+            ),
+            "Project: u.name
+  Limit: 3
+    Join: CROSS
+      Limit: 3
+        Scan: public.users AS u
+      Limit: 3
+        Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                LimitPushdown,
+                "select u.name from users u join orders o on u.id = o.user_id limit 3"
+            ),
+            "Project: u.name
+  Limit: 3
+    Join: INNER ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+    }
+
+    #[test]
+    fn copies_limits_into_union_inputs() {
+        let union = LogicalPlan::union(
+            vec![
+                plan("select name from users"),
+                plan("select name from users limit 2"),
+                plan("select name from users limit 10"),
+            ],
+            true,
+        );
+        let plan = LogicalPlan::limit(union, Some(3), 1);
+        assert_eq!(
+            explain(&Optimizer::new(vec![Box::new(LimitPushdown)]).optimize(plan, &catalog())),
+            "Limit: 3 OFFSET 1
+  Union: ALL
+    Project: users.name
+      Limit: 4
+        Scan: public.users
+    Project: users.name
+      Limit: 2
+        Scan: public.users
+    Project: users.name
+      Limit: 4
+        Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn merges_limits() {
+        let limit =
+            |limit, offset| LogicalPlan::limit(LogicalPlan::values(vec![vec![]]), limit, offset);
+        let merged = |outer: (Option<u64>, u64), inner: (Option<u64>, u64)| {
+            let plan = LogicalPlan::limit(limit(inner.0, inner.1), outer.0, outer.1);
+            match LimitPushdown.rewrite(&plan, &OptimizerContext::new(&plan, &catalog())) {
+                Some(LogicalPlan::Limit { limit, offset, .. }) => (limit, offset),
+                plan => panic!("expected a limit, got {:?}", plan),
+            }
+        };
+        assert_eq!(merged((Some(5), 3), (Some(10), 2)), (Some(5), 5));
+        assert_eq!(merged((Some(5), 8), (Some(10), 0)), (Some(2), 8));
+        assert_eq!(merged((None, 1), (Some(10), 0)), (Some(9), 1));
+        assert_eq!(merged((Some(4), 0), (None, 6)), (Some(4), 6));
+        assert_eq!(merged((None, 2), (None, 6)), (None, 8));
+    }
+}
@@ -0,0 +1,709 @@
+//! Rewrites a logical plan into an equivalent plan which is cheaper to execute.
+//!
+//! Every rewrite is a rule. The optimizer applies each rule to every node of the plan,
+//! inputs before the node, and repeats until no rule changes the plan any more.
+
+pub mod cardinality;
+pub mod constant_folding;
+pub mod decorrelation;
+pub mod in_list_to_semi_join;
+pub mod index_selection;
+pub mod join_order;
+pub mod limit_pushdown;
+pub mod outer_to_inner_join;
+pub mod predicate_pushdown;
+pub mod projection_pruning;
+pub mod sort_removal;
+
+use crate::analyser::aggregates::is_aggregate;
+use crate::analyser::bound::*;
+use crate::catalog::statistics::TableStatistics;
+use crate::catalog::{Catalog, Index, TableId};
+use crate::parser::expr::{Operator, OperatorSpan};
+use crate::planner::LogicalPlan;
+use std::cell::Cell;
+
+pub trait OptimizerRule {
+    fn name(&self) -> &'static str;
+
+    /// Rewritten node, `None` when the rule doesn't change it. The inputs of the node are
+    /// already rewritten.
+    fn rewrite(&self, plan: &LogicalPlan, context: &OptimizerContext) -> Option<LogicalPlan>;
+}
+
+/// State shared by the rules while a plan is optimized.
+pub struct OptimizerContext<'a> {
+    next_relation_id: Cell<usize>,
+    catalog: &'a dyn Catalog,
+}
+
+impl<'a> OptimizerContext<'a> {
+    pub fn new(plan: &LogicalPlan, catalog: &'a dyn Catalog) -> Self {
+        OptimizerContext {
+            next_relation_id: Cell::new(unused_relation_id(plan).0),
+            catalog,
+        }
+    }
+
+    /// Statistics of the table, `None` if it was never analyzed.
+    pub fn statistics(&self, table: TableId) -> Option<&'a TableStatistics> {
+        self.catalog.statistics(table)
+    }
+
+    /// Indexes of the table a scan may read instead.
+    pub fn indexes(&self, table: TableId) -> Vec<&'a Index> {
+        self.catalog.indexes(table)
+    }
+
+    /// Id of a relation a rule adds to the plan, no other relation of the plan has it.
+    pub fn new_relation_id(&self) -> RelationId {
+        let id = self.next_relation_id.get();
+        self.next_relation_id.set(id + 1);
+        RelationId(id)
+    }
+}
+
+/// A plan reaches the fixpoint in a few passes, the limit only guards against rules
+/// which undo each other.
+const MAX_PASSES: usize = 32;
+
+pub struct Optimizer {
+    rules: Vec<Box<dyn OptimizerRule>>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer::new(vec![
+            Box::new(constant_folding::ConstantFolding),
+            Box::new(decorrelation::Decorrelation),
+            Box::new(outer_to_inner_join::OuterToInnerJoin),
+            Box::new(predicate_pushdown::PredicatePushdown),
+            Box::new(in_list_to_semi_join::InListToSemiJoin),
+            Box::new(limit_pushdown::LimitPushdown),
+            Box::new(sort_removal::RedundantSortRemoval),
+            Box::new(projection_pruning::ProjectionPruning),
+            Box::new(join_order::JoinOrder),
+            Box::new(index_selection::IndexSelection),
+        ])
+    }
+}
+
+impl Optimizer {
+    pub fn new(rules: Vec<Box<dyn OptimizerRule>>) -> Self {
+        Optimizer { rules }
+    }
+
+    /// The catalog has the statistics the cost based rules estimate with.
+    pub fn optimize(&self, plan: LogicalPlan, catalog: &dyn Catalog) -> LogicalPlan {
+        let context = OptimizerContext::new(&plan, catalog);
+        let mut plan = plan;
+        for _ in 0..MAX_PASSES {
+            let mut changed = false;
+            for rule in &self.rules {
+                if let Some(rewritten) = apply(rule.as_ref(), &plan, &context) {
+                    plan = rewritten;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        plan
+    }
+}
+
+/// Optimizes the plan with the default rules.
+pub fn optimize(plan: LogicalPlan, catalog: &dyn Catalog) -> LogicalPlan {
+    Optimizer::default().optimize(plan, catalog)
+}
+
+/// Applies the rule to the inputs and then to the node, `None` if nothing changed.
+fn apply(
+    rule: &dyn OptimizerRule,
+    plan: &LogicalPlan,
+    context: &OptimizerContext,
+) -> Option<LogicalPlan> {
+    let inputs = plan.inputs();
+    let rewritten: Vec<_> = inputs
+        .iter()
+        .map(|input| apply(rule, input, context))
+        .collect();
+    let node = if rewritten.iter().any(Option::is_some) {
+        let inputs = inputs
+            .into_iter()
+            .zip(rewritten)
+            .map(|(input, rewritten)| rewritten.unwrap_or_else(|| input.clone()))
+            .collect();
+        Some(plan.with_inputs(inputs))
+    } else {
+        None
+    };
+    match rule.rewrite(node.as_ref().unwrap_or(plan), context) {
+        Some(plan) => Some(plan),
+        None => node,
+    }
+}
+
+/// Operands of a chain of `AND`s.
+pub(crate) fn conjuncts(expr: &BoundExpr) -> Vec<BoundExpr> {
+    match expr {
+        BoundExpr::Binary {
+            left,
+            operator: Operator::And,
+            right,
+            ..
+        } => {
+            let mut operands = conjuncts(left);
+            operands.extend(conjuncts(right));
+            operands
+        }
+        expr => vec![expr.clone()],
+    }
+}
+
+/// The conditions joined with `AND`, `None` if there are none.
+pub(crate) fn conjunction(conditions: Vec<BoundExpr>) -> Option<BoundExpr> {
+    conditions
+        .into_iter()
+        .reduce(|left, right| BoundExpr::Binary {
+            left: Box::new(left),
+            operator: Operator::And,
+            right: Box::new(right),
+            span: OperatorSpan::default(),
+        })
+}
+
+/// Columns the expression reads, columns of enclosing queries which its subqueries
+/// reference included.
+pub(crate) fn referenced_columns(expr: &BoundExpr) -> Vec<BoundColumn> {
+    let mut columns = vec![];
+    collect_columns(expr, &mut columns);
+    columns
+}
+
+fn collect_columns(expr: &BoundExpr, columns: &mut Vec<BoundColumn>) {
+    match expr {
+        BoundExpr::Column(column) if !columns.contains(column) => columns.push(column.clone()),
+        BoundExpr::InSubquery { subquery, .. }
+        | BoundExpr::Exists { subquery, .. }
+        | BoundExpr::Subquery(subquery) => {
+            let relations = select_relations(subquery);
+            let mut inner = vec![];
+            for expr in select_exprs(subquery) {
+                collect_columns(expr, &mut inner);
+            }
+            for column in inner {
+                if !relations.contains(&column.relation) && !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        collect_columns(child, columns);
+    }
+}
+
+/// The expression with its operands replaced, subqueries are left as they are.
+pub(crate) fn map_children(
+    expr: &BoundExpr,
+    f: &mut dyn FnMut(&BoundExpr) -> BoundExpr,
+) -> BoundExpr {
+    match expr {
+        BoundExpr::Column(_)
+        | BoundExpr::Literal(_)
+        | BoundExpr::Wildcard
+        | BoundExpr::Default
+        | BoundExpr::Parameter(_)
+        | BoundExpr::Exists { .. }
+        | BoundExpr::Subquery(_) => expr.clone(),
+        BoundExpr::Function(function) => BoundExpr::Function(BoundFunction {
+            arguments: function.arguments.iter().map(&mut *f).collect(),
+            within_group: function
+                .within_group
+                .iter()
+                .map(|(expr, order)| (f(expr), *order))
+                .collect(),
+            over: function.over.as_ref().map(|window| {
+                Box::new(BoundWindow {
+                    partition_by: window.partition_by.iter().map(&mut *f).collect(),
+                    order_by: window
+                        .order_by
+                        .iter()
+                        .map(|(expr, order)| (f(expr), *order))
+                        .collect(),
+                    frame: window.frame.clone(),
+                })
+            }),
+            ..function.clone()
+        }),
+        BoundExpr::Unary {
+            operator,
+            expr,
+            span,
+        } => BoundExpr::Unary {
+            operator: *operator,
+            expr: Box::new(f(expr)),
+            span: *span,
+        },
+        BoundExpr::Binary {
+            left,
+            operator,
+            right,
+            span,
+        } => BoundExpr::Binary {
+            left: Box::new(f(left)),
+            operator: *operator,
+            right: Box::new(f(right)),
+            span: *span,
+        },
+        BoundExpr::IsNull { expr, negated } => BoundExpr::IsNull {
+            expr: Box::new(f(expr)),
+            negated: *negated,
+        },
+        BoundExpr::IsDistinctFrom {
+            left,
+            right,
+            negated,
+        } => BoundExpr::IsDistinctFrom {
+            left: Box::new(f(left)),
+            right: Box::new(f(right)),
+            negated: *negated,
+        },
+        BoundExpr::InList {
+            expr,
+            list,
+            negated,
+        } => BoundExpr::InList {
+            expr: Box::new(f(expr)),
+            list: list.iter().map(&mut *f).collect(),
+            negated: *negated,
+        },
+        BoundExpr::InSubquery {
+            expr,
+            subquery,
+            negated,
+        } => BoundExpr::InSubquery {
+            expr: Box::new(f(expr)),
+            subquery: subquery.clone(),
+            negated: *negated,
+        },
+        BoundExpr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => BoundExpr::Between {
+            expr: Box::new(f(expr)),
+            low: Box::new(f(low)),
+            high: Box::new(f(high)),
+            negated: *negated,
+        },
+        BoundExpr::Like {
+            expr,
+            pattern,
+            escape,
+            kind,
+            negated,
+        } => BoundExpr::Like {
+            expr: Box::new(f(expr)),
+            pattern: Box::new(f(pattern)),
+            escape: escape.as_deref().map(|escape| Box::new(f(escape))),
+            kind: *kind,
+            negated: *negated,
+        },
+        BoundExpr::Tuple(exprs) => BoundExpr::Tuple(exprs.iter().map(&mut *f).collect()),
+        BoundExpr::Case {
+            operand,
+            when_then,
+            else_result,
+        } => BoundExpr::Case {
+            operand: operand.as_deref().map(|expr| Box::new(f(expr))),
+            when_then: when_then
+                .iter()
+                .map(|(when, then)| (f(when), f(then)))
+                .collect(),
+            else_result: else_result.as_deref().map(|expr| Box::new(f(expr))),
+        },
+        BoundExpr::Cast { expr, data_type } => BoundExpr::Cast {
+            expr: Box::new(f(expr)),
+            data_type: *data_type,
+        },
+    }
+}
+
+/// Whether the expression can be computed from the rows of the plan: it is a column of
+/// the plan, or it is computed from columns of the plan without aggregates and window
+/// functions.
+pub(crate) fn evaluable(plan: &LogicalPlan, expr: &BoundExpr) -> bool {
+    if plan.schema().iter().any(|column| &column.expr == expr) {
+        return true;
+    }
+    match expr {
+        BoundExpr::Column(_) => false,
+        BoundExpr::Function(function) if is_aggregate(function) || function.over.is_some() => false,
+        BoundExpr::InSubquery { .. } | BoundExpr::Exists { .. } | BoundExpr::Subquery(_) => {
+            referenced_columns(expr)
+                .into_iter()
+                .all(|column| evaluable(plan, &BoundExpr::Column(column)))
+        }
+        _ => expr
+            .children()
+            .into_iter()
+            .all(|child| evaluable(plan, child)),
+    }
+}
+
+fn select_relations(select: &BoundSelect) -> Vec<RelationId> {
+    select
+        .from
+        .iter()
+        .flat_map(|from| {
+            from.relations
+                .iter()
+                .chain(from.joins.iter().map(|join| &join.relation))
+        })
+        .map(|relation| relation.id)
+        .chain(
+            select
+                .unions
+                .iter()
+                .flat_map(|union| select_relations(&union.query)),
+        )
+        .collect()
+}
+
+fn select_exprs(select: &BoundSelect) -> Vec<&BoundExpr> {
+    select
+        .from
+        .iter()
+        .flat_map(|from| &from.joins)
+        .filter_map(|join| join.on.as_ref())
+        .chain(select.projection.iter().map(|item| &item.expr))
+        .chain(&select.filter)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(select.order_by.iter().map(|(expr, _)| expr))
+        .chain(
+            select
+                .unions
+                .iter()
+                .flat_map(|union| select_exprs(&union.query)),
+        )
+        .collect()
+}
+
+/// An id no relation of the plan or of its subqueries has.
+fn unused_relation_id(plan: &LogicalPlan) -> RelationId {
+    fn max_in_expr(expr: &BoundExpr, max: &mut usize) {
+        match expr {
+            BoundExpr::Column(column) => *max = (*max).max(column.relation.0),
+            BoundExpr::InSubquery { subquery, .. }
+            | BoundExpr::Exists { subquery, .. }
+            | BoundExpr::Subquery(subquery) => {
+                for relation in select_relations(subquery) {
+                    *max = (*max).max(relation.0);
+                }
+                for expr in select_exprs(subquery) {
+                    max_in_expr(expr, max);
+                }
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            max_in_expr(child, max);
+        }
+    }
+
+    fn max_in_plan(plan: &LogicalPlan, max: &mut usize) {
+        match plan {
+            LogicalPlan::Scan { relation, .. }
+            | LogicalPlan::TableFunction { relation, .. }
+            | LogicalPlan::Insert {
+                table: relation, ..
+            }
+            | LogicalPlan::Update {
+                table: relation, ..
+            }
+            | LogicalPlan::Delete {
+                table: relation, ..
+            } => *max = (*max).max(relation.id.0),
+            LogicalPlan::SubqueryAlias { relation, .. } => *max = (*max).max(relation.0),
+            _ => {}
+        }
+        for expr in plan.expressions() {
+            max_in_expr(expr, max);
+        }
+        for input in plan.inputs() {
+            max_in_plan(input, max);
+        }
+    }
+
+    let mut max = 0;
+    max_in_plan(plan, &mut max);
+    RelationId(max + 1)
+}
+
+/// The node with every expression it evaluates replaced.
+pub(crate) fn map_exprs(
+    plan: &LogicalPlan,
+    f: &mut impl FnMut(&BoundExpr) -> BoundExpr,
+) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Filter { input, predicate } => {
+            LogicalPlan::filter((**input).clone(), f(predicate))
+        }
+        LogicalPlan::Project {
+            input, items: list, ..
+        } => LogicalPlan::project((**input).clone(), map_items(list, f)),
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            on,
+            algorithm,
+            ..
+        } => LogicalPlan::join(
+            (**left).clone(),
+            (**right).clone(),
+            *join_type,
+            on.as_ref().map(&mut *f),
+        )
+        .with_algorithm(*algorithm),
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            aggregates,
+            ..
+        } => LogicalPlan::aggregate(
+            (**input).clone(),
+            group_by.iter().map(&mut *f).collect(),
+            aggregates.iter().map(&mut *f).collect(),
+        ),
+        LogicalPlan::Sort { input, order_by } => LogicalPlan::sort(
+            (**input).clone(),
+            order_by
+                .iter()
+                .map(|(expr, order)| (f(expr), *order))
+                .collect(),
+        ),
+        LogicalPlan::Values { rows, .. } => LogicalPlan::values(
+            rows.iter()
+                .map(|row| row.iter().map(&mut *f).collect())
+                .collect(),
+        ),
+        LogicalPlan::TableFunction { relation, schema } => {
+            let mut relation = relation.clone();
+            for argument in relation.function.iter_mut().flat_map(|f| &mut f.arguments) {
+                *argument = f(argument);
+            }
+            LogicalPlan::TableFunction {
+                relation,
+                schema: schema.clone(),
+            }
+        }
+        LogicalPlan::Window {
+            input, functions, ..
+        } => LogicalPlan::window((**input).clone(), functions.iter().map(&mut *f).collect()),
+        LogicalPlan::Insert {
+            input,
+            table,
+            columns,
+            on_conflict,
+            returning,
+            ..
+        } => {
+            let on_conflict = on_conflict.as_ref().map(|on_conflict| BoundOnConflict {
+                columns: on_conflict.columns.clone(),
+                action: match &on_conflict.action {
+                    BoundConflictAction::DoNothing => BoundConflictAction::DoNothing,
+                    BoundConflictAction::DoUpdate {
+                        excluded,
+                        assignments,
+                        filter,
+                    } => BoundConflictAction::DoUpdate {
+                        excluded: excluded.clone(),
+                        assignments: map_assignments(assignments, f),
+                        filter: filter.as_ref().map(&mut *f),
+                    },
+                },
+            });
+            let returning = map_items(returning, f);
+            LogicalPlan::insert(
+                (**input).clone(),
+                table.clone(),
+                columns.clone(),
+                on_conflict,
+                returning,
+            )
+        }
+        LogicalPlan::Update {
+            input,
+            table,
+            assignments,
+            returning,
+            ..
+        } => {
+            let returning = map_items(returning, f);
+            LogicalPlan::update(
+                (**input).clone(),
+                table.clone(),
+                map_assignments(assignments, f),
+                returning,
+            )
+        }
+        LogicalPlan::Delete {
+            input,
+            table,
+            returning,
+            ..
+        } => LogicalPlan::delete((**input).clone(), table.clone(), map_items(returning, f)),
+        LogicalPlan::Scan { .. }
+        | LogicalPlan::Limit { .. }
+        | LogicalPlan::Distinct { .. }
+        | LogicalPlan::Union { .. }
+        | LogicalPlan::SubqueryAlias { .. } => plan.clone(),
+    }
+}
+
+fn map_items(
+    items: &[ProjectionItem],
+    f: &mut impl FnMut(&BoundExpr) -> BoundExpr,
+) -> Vec<ProjectionItem> {
+    items
+        .iter()
+        .map(|item| ProjectionItem {
+            expr: f(&item.expr),
+            name: item.name.clone(),
+        })
+        .collect()
+}
+
+fn map_assignments(
+    assignments: &[BoundAssignment],
+    f: &mut impl FnMut(&BoundExpr) -> BoundExpr,
+) -> Vec<BoundAssignment> {
+    assignments
+        .iter()
+        .map(|assignment| BoundAssignment {
+            columns: assignment.columns.clone(),
+            value: f(&assignment.value),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::analyser::analyse;
+    use crate::catalog::MemoryCatalog;
+    use crate::parser::Queries;
+    use crate::planner::{build_plan, explain};
+    use crate::value::DataType;
+
+    /// `users(id primary key, name, age)` and `orders(id, user_id, total)`.
+    pub(crate) fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        let users = catalog.create_table(
+            "public",
+            "users",
+            &[
+                ("id", DataType::Integer),
+                ("name", DataType::Text),
+                ("age", DataType::Integer),
+            ],
+        );
+        catalog.set_primary_key(users, &["id"]);
+        catalog.create_table(
+            "public",
+            "orders",
+            &[
+                ("id", DataType::Integer),
+                ("user_id", DataType::Integer),
+                ("total", DataType::Double),
+            ],
+        );
+        catalog
+    }
+
+    pub(crate) fn plan_in(catalog: &MemoryCatalog, input: &str) -> LogicalPlan {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        build_plan(&analyse(&query, catalog).unwrap()).unwrap()
+    }
+
+    pub(crate) fn plan(input: &str) -> LogicalPlan {
+        plan_in(&catalog(), input)
+    }
+
+    /// Plan of the query after the rule ran to its fixpoint.
+    pub(crate) fn optimize_with(rule: impl OptimizerRule + 'static, input: &str) -> String {
+        let catalog = catalog();
+        explain(&Optimizer::new(vec![Box::new(rule)]).optimize(plan_in(&catalog, input), &catalog))
+    }
+
+    struct RemoveDistinct;
+
+    impl OptimizerRule for RemoveDistinct {
+        fn name(&self) -> &'static str {
+            "remove_distinct"
+        }
+
+        fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+            match plan {
+                LogicalPlan::Distinct { input } => Some((**input).clone()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn applies_rules_to_every_node() {
+        assert_eq!(
+            optimize_with(
+                RemoveDistinct,
+                "select distinct id from users where id in (select distinct user_id from orders)"
+            ),
+            "Project: users.id
+  Filter: users.id IN (SubPlan 1)
+    SubPlan 1
+      Distinct
+        Project: orders.user_id
+          Scan: public.orders
+    Scan: public.users"
+        );
+        assert_eq!(
+            explain(
+                &Optimizer::new(vec![]).optimize(plan("select distinct id from users"), &catalog())
+            ),
+            "Distinct\n  Project: users.id\n    Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn optimizes_with_all_rules() {
+        assert_eq!(
+            explain(&optimize(
+                plan(
+                    "select u.name from users u left join orders o on u.id = o.user_id \
+                 where o.total > 10 and u.age > 1 + 17 and u.id in (1, 2, 3, 4) \
+                 order by u.name limit 5"
+                ),
+                &catalog()
+            )),
+            "Project: u.name
+  Limit: 5
+    Sort: u.name
+      Project: u.id, u.name, u.age, o.user_id, o.total
+        Join: INNER ON u.id = o.user_id [hash]
+          Filter: o.total > CAST(10 AS DOUBLE)
+            Scan: public.orders AS o (user_id, total)
+          Join: SEMI ON u.id = in_list.column1 [hash]
+            Filter: u.age > 18
+              Scan: public.users AS u
+            SubqueryAlias: in_list
+              Values: (1), (2), (3), (4)"
+        );
+    }
+}
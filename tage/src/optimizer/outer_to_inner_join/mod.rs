@@ -0,0 +1,220 @@
+//! Turns an outer join into an inner join when a filter above it removes the rows the
+//! join pads with `NULL`s.
+//!
+//! A condition rejects nulls of one side of the join if it can't be true when all
+//! columns of that side are `NULL`, e.g. `o.total > 10` rejects the rows of a
+//! `users u left join orders o` which have no order. A full join loses the padding of
+//! one side at a time and becomes a left or a right join first.
+
+use crate::analyser::bound::*;
+use crate::optimizer::{conjuncts, OptimizerContext, OptimizerRule};
+use crate::parser::expr::{Operator, UnaryOperator};
+use crate::planner::{JoinType, LogicalPlan};
+
+pub struct OuterToInnerJoin;
+
+impl OptimizerRule for OuterToInnerJoin {
+    fn name(&self) -> &'static str {
+        "outer_to_inner_join"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        let LogicalPlan::Filter { input, predicate } = plan else {
+            return None;
+        };
+        let LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            on,
+            ..
+        } = input.as_ref()
+        else {
+            return None;
+        };
+        let rejects = |side: &LogicalPlan| {
+            let side = relations(side);
+            conjuncts(predicate)
+                .iter()
+                .any(|conjunct| rejects_null(conjunct, &side))
+        };
+        let join_type = match join_type {
+            JoinType::Left if rejects(right) => JoinType::Inner,
+            JoinType::Right if rejects(left) => JoinType::Inner,
+            JoinType::Full => match (rejects(left), rejects(right)) {
+                (true, true) => JoinType::Inner,
+                (true, false) => JoinType::Left,
+                (false, true) => JoinType::Right,
+                (false, false) => return None,
+            },
+            _ => return None,
+        };
+        let join = LogicalPlan::join((**left).clone(), (**right).clone(), join_type, on.clone());
+        Some(LogicalPlan::filter(join, predicate.clone()))
+    }
+}
+
+/// Relations whose columns the plan returns.
+fn relations(plan: &LogicalPlan) -> Vec<RelationId> {
+    let mut relations = vec![];
+    for column in plan.schema() {
+        if let BoundExpr::Column(column) = &column.expr {
+            if !relations.contains(&column.relation) {
+                relations.push(column.relation);
+            }
+        }
+    }
+    relations
+}
+
+/// Whether the condition is `NULL` or false when the columns of the relations are `NULL`.
+fn rejects_null(condition: &BoundExpr, relations: &[RelationId]) -> bool {
+    match condition {
+        BoundExpr::Binary {
+            left,
+            operator: Operator::And,
+            right,
+            ..
+        } => rejects_null(left, relations) || rejects_null(right, relations),
+        BoundExpr::Binary {
+            left,
+            operator: Operator::Or,
+            right,
+            ..
+        } => rejects_null(left, relations) && rejects_null(right, relations),
+        BoundExpr::Binary {
+            left,
+            operator:
+                Operator::Equal
+                | Operator::NotEqual
+                | Operator::GreaterThan
+                | Operator::LessThan
+                | Operator::GreaterThanOrEqual
+                | Operator::LessThanOrEqual,
+            right,
+            ..
+        } => is_null_if_null(left, relations) || is_null_if_null(right, relations),
+        BoundExpr::IsNull {
+            expr,
+            negated: true,
+        }
+        | BoundExpr::InList { expr, .. }
+        | BoundExpr::Between { expr, .. }
+        | BoundExpr::Like { expr, .. } => is_null_if_null(expr, relations),
+        BoundExpr::Unary {
+            operator: UnaryOperator::Not,
+            expr,
+            ..
+        } => {
+            matches!(
+                expr.as_ref(),
+                BoundExpr::Binary { operator, .. } if !matches!(operator, Operator::And | Operator::Or)
+            ) && rejects_null(expr, relations)
+        }
+        BoundExpr::Column(column) => relations.contains(&column.relation),
+        _ => false,
+    }
+}
+
+/// Whether the expression is `NULL` when the columns of the relations are `NULL`.
+fn is_null_if_null(expr: &BoundExpr, relations: &[RelationId]) -> bool {
+    match expr {
+        BoundExpr::Column(column) => relations.contains(&column.relation),
+        BoundExpr::Cast { expr, .. } | BoundExpr::Unary { expr, .. } => {
+            is_null_if_null(expr, relations)
+        }
+        BoundExpr::Binary {
+            left,
+            operator:
+                Operator::Plus
+                | Operator::Minus
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::Concat,
+            right,
+            ..
+        } => is_null_if_null(left, relations) || is_null_if_null(right, relations),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::optimize_with;
+
+    #[test]
+    fn converts_joins_filtered_on_padded_side() {
+        assert_eq!(
+            optimize_with(
+                OuterToInnerJoin,
+                "select u.name from users u left join orders o on u.id = o.user_id \
+                 where o.total + 1 > 10"
+            ),
+            "Project: u.name
+  Filter: o.total + CAST(1 AS DOUBLE) > CAST(10 AS DOUBLE)
+    Join: INNER ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                OuterToInnerJoin,
+                "select u.name from users u right join orders o on u.id = o.user_id \
+                 where u.age is not null or u.name like 'a%'"
+            ),
+            "Project: u.name
+  Filter: u.age IS NOT NULL OR u.name LIKE 'a%'
+    Join: INNER ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+    }
+
+    #[test]
+    fn narrows_full_joins() {
+        assert_eq!(
+            optimize_with(
+                OuterToInnerJoin,
+                "select * from users u full join orders o on u.id = o.user_id where u.age = 3"
+            ),
+            "Project: u.id, u.name, u.age, o.id, o.user_id, o.total
+  Filter: u.age = 3
+    Join: LEFT ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                OuterToInnerJoin,
+                "select * from users u full join orders o on u.id = o.user_id \
+                 where u.age = 3 and o.id in (1, 2)"
+            ),
+            "Project: u.id, u.name, u.age, o.id, o.user_id, o.total
+  Filter: u.age = 3 AND o.id IN (1, 2)
+    Join: INNER ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+    }
+
+    #[test]
+    fn keeps_joins_when_nulls_can_pass() {
+        for filter in [
+            "o.total is null",
+            "o.total > 10 or u.age > 1",
+            "coalesce(o.id, 0) = 0",
+            "u.age > 1",
+        ] {
+            let plan = optimize_with(
+                OuterToInnerJoin,
+                &format!(
+                    "select u.name from users u left join orders o on u.id = o.user_id where {}",
+                    filter
+                ),
+            );
+            assert!(plan.contains("Join: LEFT"), "{}", plan);
+        }
+    }
+}
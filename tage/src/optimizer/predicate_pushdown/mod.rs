@@ -0,0 +1,298 @@
+//! Moves filters towards the scans, so fewer rows reach the joins and aggregates.
+//!
+//! A filter moves below projections, sorts and `distinct`, and below an aggregate when
+//! it only reads group keys. Above a join the conditions are split by the side they
+//! read: a condition on one side filters that side before the join, a condition on
+//! both sides becomes part of the join condition. The conditions of the join itself
+//! move into its inputs the same way. Sides padded with `NULL`s by an outer join are
+//! never filtered before the join, that would turn filtered rows into padded ones.
+
+use crate::analyser::bound::BoundExpr;
+use crate::optimizer::{conjunction, conjuncts, evaluable, OptimizerContext, OptimizerRule};
+use crate::planner::{JoinType, LogicalPlan};
+
+pub struct PredicatePushdown;
+
+impl OptimizerRule for PredicatePushdown {
+    fn name(&self) -> &'static str {
+        "predicate_pushdown"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        match plan {
+            LogicalPlan::Filter { input, predicate } => push_filter(input, predicate),
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                on: Some(on),
+                ..
+            } => {
+                let (left_filters, right_filters, on) = split(left, right, *join_type, on);
+                if left_filters.is_empty() && right_filters.is_empty() {
+                    return None;
+                }
+                Some(LogicalPlan::join(
+                    filtered((**left).clone(), left_filters),
+                    filtered((**right).clone(), right_filters),
+                    *join_type,
+                    conjunction(on),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn push_filter(input: &LogicalPlan, predicate: &BoundExpr) -> Option<LogicalPlan> {
+    match input {
+        LogicalPlan::Filter {
+            input,
+            predicate: inner,
+        } => {
+            let mut conditions = conjuncts(inner);
+            conditions.extend(conjuncts(predicate));
+            Some(filtered((**input).clone(), conditions))
+        }
+        LogicalPlan::Project { input: below, .. }
+        | LogicalPlan::Sort { input: below, .. }
+        | LogicalPlan::Distinct { input: below } => {
+            let (pushed, kept): (Vec<_>, Vec<_>) = conjuncts(predicate)
+                .into_iter()
+                .partition(|condition| evaluable(below, condition));
+            if pushed.is_empty() {
+                return None;
+            }
+            let plan = input.with_inputs(vec![filtered((**below).clone(), pushed)]);
+            Some(filtered(plan, kept))
+        }
+        LogicalPlan::Aggregate {
+            input: below,
+            group_by,
+            ..
+        } if !group_by.is_empty() => {
+            // conditions on group keys, every row of a group has the same keys
+            let (pushed, kept): (Vec<_>, Vec<_>) =
+                conjuncts(predicate).into_iter().partition(|condition| {
+                    evaluable(below, condition) && on_group_keys(condition, group_by)
+                });
+            if pushed.is_empty() {
+                return None;
+            }
+            let plan = input.with_inputs(vec![filtered((**below).clone(), pushed)]);
+            Some(filtered(plan, kept))
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            on,
+            ..
+        } => {
+            let mut conditions = conjuncts(predicate);
+            let join_type = match join_type {
+                // a condition on both sides is a join condition of an inner join
+                JoinType::Inner | JoinType::Cross => {
+                    conditions.extend(on.iter().flat_map(conjuncts));
+                    JoinType::Inner
+                }
+                join_type => *join_type,
+            };
+            let (left_filters, right_filters, rest) = match join_type {
+                JoinType::Inner => split(left, right, join_type, &conjunction(conditions)?),
+                _ => {
+                    let (pushed, kept) =
+                        conditions
+                            .into_iter()
+                            .partition(|condition| match join_type {
+                                JoinType::Left | JoinType::Semi | JoinType::Anti => {
+                                    evaluable(left, condition)
+                                }
+                                JoinType::Right => evaluable(right, condition),
+                                _ => false,
+                            });
+                    match join_type {
+                        JoinType::Right => (vec![], pushed, kept),
+                        _ => (pushed, vec![], kept),
+                    }
+                }
+            };
+            // the conditions of an inner join always leave the filter
+            if join_type != JoinType::Inner && left_filters.is_empty() && right_filters.is_empty() {
+                return None;
+            }
+            let (on, kept) = match join_type {
+                JoinType::Inner => (conjunction(rest), vec![]),
+                _ => (on.clone(), rest),
+            };
+            let join = LogicalPlan::join(
+                filtered((**left).clone(), left_filters),
+                filtered((**right).clone(), right_filters),
+                join_type,
+                on,
+            );
+            Some(filtered(join, kept))
+        }
+        _ => None,
+    }
+}
+
+/// Conditions of an inner join which only read the left side, those which only read the
+/// right side and the rest. For outer joins only the conditions on the side which isn't
+/// padded move.
+fn split(
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+    join_type: JoinType,
+    on: &BoundExpr,
+) -> (Vec<BoundExpr>, Vec<BoundExpr>, Vec<BoundExpr>) {
+    let (to_left, to_right) = match join_type {
+        JoinType::Inner | JoinType::Semi => (true, true),
+        JoinType::Left | JoinType::Anti => (false, true),
+        JoinType::Right => (true, false),
+        JoinType::Full | JoinType::Cross => (false, false),
+    };
+    let (mut left_filters, mut right_filters, mut rest) = (vec![], vec![], vec![]);
+    for condition in conjuncts(on) {
+        if to_left && evaluable(left, &condition) {
+            left_filters.push(condition);
+        } else if to_right && evaluable(right, &condition) {
+            right_filters.push(condition);
+        } else {
+            rest.push(condition);
+        }
+    }
+    (left_filters, right_filters, rest)
+}
+
+fn on_group_keys(condition: &BoundExpr, group_by: &[BoundExpr]) -> bool {
+    group_by.contains(condition)
+        || match condition {
+            BoundExpr::Column(_) => false,
+            condition => condition
+                .children()
+                .into_iter()
+                .all(|child| on_group_keys(child, group_by)),
+        }
+}
+
+/// The plan with the conditions as a filter on top, the plan itself if there are none.
+fn filtered(plan: LogicalPlan, conditions: Vec<BoundExpr>) -> LogicalPlan {
+    match conjunction(conditions) {
+        Some(predicate) => LogicalPlan::filter(plan, predicate),
+        None => plan,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::optimize_with;
+
+    #[test]
+    fn pushes_filters_into_joins() {
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select u.name from users u, orders o \
+                 where u.id = o.user_id and u.age > 18 and o.total > 10 and u.name = 'a'"
+            ),
+            "Project: u.name
+  Join: INNER ON u.id = o.user_id
+    Filter: u.age > 18 AND u.name = 'a'
+      Scan: public.users AS u
+    Filter: o.total > CAST(10 AS DOUBLE)
+      Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select u.name from users u join orders o on u.id = o.user_id and o.total > 10 \
+                 where u.age > 18"
+            ),
+            "Project: u.name
+  Join: INNER ON u.id = o.user_id
+    Filter: u.age > 18
+      Scan: public.users AS u
+    Filter: o.total > CAST(10 AS DOUBLE)
+      Scan: public.orders AS o"
+        );
+    }
+
+    #[test]
+    fn keeps_padded_sides_of_outer_joins() {
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select u.name from users u left join orders o \
+                 on u.id = o.user_id and o.total > 10 and u.age > 1 \
+                 where u.age > 18 and o.id is null"
+            ),
+            "Project: u.name
+  Filter: o.id IS NULL
+    Join: LEFT ON u.id = o.user_id AND u.age > 1
+      Filter: u.age > 18
+        Scan: public.users AS u
+      Filter: o.total > CAST(10 AS DOUBLE)
+        Scan: public.orders AS o"
+        );
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select u.name from users u full join orders o on u.id = o.user_id \
+                 where u.age > 18"
+            ),
+            "Project: u.name
+  Filter: u.age > 18
+    Join: FULL ON u.id = o.user_id
+      Scan: public.users AS u
+      Scan: public.orders AS o"
+        );
+    }
+
+    #[test]
+    fn pushes_filters_below_aggregates_and_sorts() {
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select name, count(*) from users group by name \
+                 having name <> 'a' and count(*) > 1"
+            ),
+            "Project: users.name, count(*)
+  Filter: count(*) > CAST(1 AS BIGINT)
+    Aggregate: group_by=[users.name], aggregates=[count(*)]
+      Filter: users.name <> 'a'
+        Scan: public.users"
+        );
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select count(*) from users having count(*) > 1 and 1 = 2"
+            ),
+            "Project: count(*)
+  Filter: count(*) > CAST(1 AS BIGINT) AND 1 = 2
+    Aggregate: group_by=[], aggregates=[count(*)]
+      Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn pushes_filters_with_subqueries() {
+        assert_eq!(
+            optimize_with(
+                PredicatePushdown,
+                "select u.name from users u, orders o where u.id = o.user_id \
+                 and exists (select 1 from orders x where x.user_id = u.id)"
+            ),
+            "Project: u.name
+  Join: INNER ON u.id = o.user_id
+    Filter: EXISTS (SubPlan 1)
+      SubPlan 1
+        Project: 1
+          Filter: x.user_id = u.id
+            Scan: public.orders AS x
+      Scan: public.users AS u
+    Scan: public.orders AS o"
+        );
+    }
+}
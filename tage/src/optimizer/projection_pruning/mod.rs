@@ -0,0 +1,142 @@
+//! Removes the columns nothing reads from the scans.
+//!
+//! A projection or an aggregate only passes on the columns its expressions read. The
+//! nodes between it and the scans add the columns they read themselves, the scans then
+//! only return the columns which are still needed.
+
+use crate::analyser::bound::{BoundColumn, BoundExpr};
+use crate::optimizer::{referenced_columns, OptimizerContext, OptimizerRule};
+use crate::planner::LogicalPlan;
+
+pub struct ProjectionPruning;
+
+impl OptimizerRule for ProjectionPruning {
+    fn name(&self) -> &'static str {
+        "projection_pruning"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        let (LogicalPlan::Project { input, .. } | LogicalPlan::Aggregate { input, .. }) = plan
+        else {
+            return None;
+        };
+        let pruned = prune(input, add_columns(plan.expressions(), vec![]))?;
+        Some(plan.with_inputs(vec![pruned]))
+    }
+}
+
+/// The plan with the columns which aren't required removed from its scans, `None` if
+/// none could be removed.
+fn prune(plan: &LogicalPlan, required: Vec<BoundColumn>) -> Option<LogicalPlan> {
+    match plan {
+        LogicalPlan::Scan {
+            relation,
+            schema,
+            index,
+        } => {
+            let pruned: Vec<_> = schema
+                .iter()
+                .filter(|column| match &column.expr {
+                    BoundExpr::Column(column) => required.contains(column),
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+            (pruned.len() < schema.len()).then(|| LogicalPlan::Scan {
+                relation: relation.clone(),
+                schema: pruned,
+                index: index.clone(),
+            })
+        }
+        LogicalPlan::Filter { .. }
+        | LogicalPlan::Sort { .. }
+        | LogicalPlan::Limit { .. }
+        | LogicalPlan::Window { .. }
+        | LogicalPlan::Join { .. } => {
+            let required = add_columns(plan.expressions(), required);
+            let inputs = plan.inputs();
+            let pruned: Vec<_> = inputs
+                .iter()
+                .map(|input| prune(input, required.clone()))
+                .collect();
+            if pruned.iter().all(Option::is_none) {
+                return None;
+            }
+            let inputs = inputs
+                .into_iter()
+                .zip(pruned)
+                .map(|(input, pruned)| pruned.unwrap_or_else(|| input.clone()))
+                .collect();
+            Some(plan.with_inputs(inputs))
+        }
+        // the other nodes read all columns of their inputs or compute their own
+        _ => None,
+    }
+}
+
+/// The required columns with the columns the expressions read.
+fn add_columns(exprs: Vec<&BoundExpr>, mut required: Vec<BoundColumn>) -> Vec<BoundColumn> {
+    for expr in exprs {
+        for column in referenced_columns(expr) {
+            if !required.contains(&column) {
+                required.push(column);
+            }
+        }
+    }
+    required
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::optimize_with;
+
+    #[test]
+    fn prunes_scans() {
+        assert_eq!(
+            optimize_with(
+                ProjectionPruning,
+                "select u.name from users u join orders o on u.id = o.user_id \
+                 where o.total > 1 order by u.age limit 3"
+            ),
+            "Limit: 3
+  Project: u.name
+    Sort: u.age
+      Filter: o.total > CAST(1 AS DOUBLE)
+        Join: INNER ON u.id = o.user_id
+          Scan: public.users AS u
+          Scan: public.orders AS o (user_id, total)"
+        );
+        assert_eq!(
+            optimize_with(ProjectionPruning, "select count(*), max(age) from users"),
+            "Project: count(*), max(users.age)
+  Aggregate: group_by=[], aggregates=[count(*), max(users.age)]
+    Scan: public.users (age)"
+        );
+    }
+
+    #[test]
+    fn keeps_columns_of_subqueries() {
+        // subqueries are planned when they run, only the outer plan is pruned
+        assert_eq!(
+            optimize_with(
+                ProjectionPruning,
+                "select o.id from orders o \
+                 where exists (select 1 from users u where u.id = o.user_id)"
+            ),
+            "Project: o.id
+  Filter: EXISTS (SubPlan 1)
+    SubPlan 1
+      Project: 1
+        Filter: u.id = o.user_id
+          Scan: public.users AS u
+    Scan: public.orders AS o (id, user_id)"
+        );
+        assert_eq!(
+            optimize_with(ProjectionPruning, "delete from users where age > 1"),
+            "Delete: public.users
+  Filter: users.age > 1
+    Scan: public.users"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Removes sorts whose order nothing depends on and sort keys which don't change it.
+//!
+//! The order of rows is lost in aggregates, `distinct`, inserts and under another sort,
+//! so a sort below them is dropped. Constant keys don't order anything and a
+//! key which is repeated is already ordered by its first occurrence.
+
+use crate::analyser::bound::BoundExpr;
+use crate::optimizer::{referenced_columns, OptimizerContext, OptimizerRule};
+use crate::planner::LogicalPlan;
+
+pub struct RedundantSortRemoval;
+
+impl OptimizerRule for RedundantSortRemoval {
+    fn name(&self) -> &'static str {
+        "redundant_sort_removal"
+    }
+
+    fn rewrite(&self, plan: &LogicalPlan, _context: &OptimizerContext) -> Option<LogicalPlan> {
+        match plan {
+            LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Insert { input, .. } => match unsorted(input) {
+                Some(input) => Some(plan.with_inputs(vec![input])),
+                None => remove_keys(plan),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The plan without the sort at its top, projections and filters above the sort keep
+/// the order and are looked through.
+fn unsorted(plan: &LogicalPlan) -> Option<LogicalPlan> {
+    match plan {
+        LogicalPlan::Sort { input, .. } => Some((**input).clone()),
+        LogicalPlan::Project { input, .. } | LogicalPlan::Filter { input, .. } => {
+            unsorted(input).map(|input| plan.with_inputs(vec![input]))
+        }
+        _ => None,
+    }
+}
+
+fn remove_keys(plan: &LogicalPlan) -> Option<LogicalPlan> {
+    let LogicalPlan::Sort { input, order_by } = plan else {
+        return None;
+    };
+    let mut keys: Vec<_> = vec![];
+    for (expr, order) in order_by {
+        if !is_constant(expr) && !keys.iter().any(|(key, _)| key == expr) {
+            keys.push((expr.clone(), *order));
+        }
+    }
+    if keys.is_empty() {
+        Some((**input).clone())
+    } else if keys.len() == order_by.len() {
+        None
+    } else {
+        Some(LogicalPlan::sort((**input).clone(), keys))
+    }
+}
+
+/// Whether the expression has the same value for every row.
+fn is_constant(expr: &BoundExpr) -> bool {
+    match expr {
+        BoundExpr::Literal(_) => true,
+        BoundExpr::Column(_)
+        | BoundExpr::Function(_)
+        | BoundExpr::Parameter(_)
+        | BoundExpr::InSubquery { .. }
+        | BoundExpr::Exists { .. }
+        | BoundExpr::Subquery(_) => false,
+        expr => referenced_columns(expr).is_empty() && expr.children().into_iter().all(is_constant),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::{catalog, optimize_with, plan};
+    use crate::optimizer::Optimizer;
+    use crate::planner::explain;
+
+    #[test]
+    fn removes_sorts_below_unordered_nodes() {
+        assert_eq!(
+            optimize_with(
+                RedundantSortRemoval,
+                "insert into orders (id, user_id, total) select id, id, age from users order by name"
+            ),
+            "Insert: public.orders (id, user_id, total)
+  Project: users.id, users.id, CAST(users.age AS DOUBLE) AS age
+    Scan: public.users"
+        );
+        let sorted = plan("select name from users where id > 1 order by name");
+        let plan = LogicalPlan::sort(LogicalPlan::aggregate(sorted, vec![], vec![]), vec![]);
+        assert_eq!(
+            explain(
+                &Optimizer::new(vec![Box::new(RedundantSortRemoval)]).optimize(plan, &catalog())
+            ),
+            "Aggregate: group_by=[], aggregates=[]
+  Project: users.name
+    Filter: users.id > 1
+      Scan: public.users"
+        );
+    }
+
+    #[test]
+    fn removes_constant_and_repeated_keys() {
+        assert_eq!(
+            optimize_with(
+                RedundantSortRemoval,
+                "select name from users order by 'a', name, 1 + 2, age desc, name desc"
+            ),
+            "Project: users.name
+  Sort: users.name, users.age DESC
+    Scan: public.users"
+        );
+        assert_eq!(
+            optimize_with(RedundantSortRemoval, "select name from users order by 'a'"),
+            "Project: users.name
+  Scan: public.users"
+        );
+    }
+}
@@ -0,0 +1,1799 @@
+//! Prints the AST back as canonical SQL: keywords in upper case, one statement per line and
+//! only the parentheses required by operator precedence. Parsing the output gives the same AST.
+
+use crate::parser::expr::{
+    ColumnIdentifier, Expr, FrameBound, FrameUnits, Function, Literal, Operator, Parameter,
+    UnaryOperator, Window, WindowFrame, ATOM_PRECEDENCE, COMPARISON_PRECEDENCE, NOT_PRECEDENCE,
+    UNARY_PRECEDENCE,
+};
+use crate::parser::lexer::is_keyword;
+use crate::parser::*;
+use std::fmt;
+
+/// A name which is quoted when it would not be read back as the same identifier.
+pub struct Identifier<'a>(pub &'a str);
+
+impl fmt::Display for Identifier<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.0;
+        let is_plain = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_alphabetic() || c.is_numeric() || c == '_');
+        if is_plain && !is_keyword(name) {
+            f.write_str(name)
+        } else {
+            write!(f, "\"{}\"", name.replace('"', "\"\""))
+        }
+    }
+}
+
+struct CommaSeparated<'a, T>(&'a [T]);
+
+impl<T: fmt::Display> fmt::Display for CommaSeparated<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+struct Identifiers<'a>(&'a [String]);
+
+impl fmt::Display for Identifiers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<Identifier> = self.0.iter().map(|name| Identifier(name)).collect();
+        write!(f, "{}", CommaSeparated(&names))
+    }
+}
+
+impl Expr {
+    /// Precedence of the outermost operator, used to decide where parentheses are required.
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary { operator, .. } => operator.precedence(),
+            Expr::IsNull { .. }
+            | Expr::IsDistinctFrom { .. }
+            | Expr::InList { .. }
+            | Expr::InSubquery { .. }
+            | Expr::Between { .. }
+            | Expr::Like { .. } => COMPARISON_PRECEDENCE,
+            Expr::Unary {
+                operator: UnaryOperator::Not,
+                ..
+            } => NOT_PRECEDENCE,
+            Expr::Unary { .. } => UNARY_PRECEDENCE,
+            _ => ATOM_PRECEDENCE,
+        }
+    }
+}
+
+/// Wraps the operand into parentheses if it binds weaker than `min_precedence`.
+pub(crate) fn operand(expr: &Expr, min_precedence: u8) -> String {
+    if expr.precedence() < min_precedence {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let not = |negated: &bool| if *negated { "NOT " } else { "" };
+        match self {
+            Expr::Column(column) => write!(f, "{}", column),
+            Expr::Literal(literal) => write!(f, "{}", literal),
+            Expr::TypedString { data_type, value } => {
+                write!(f, "{} {}", data_type, Literal::String(value.clone()))
+            }
+            Expr::Function(function) => write!(f, "{}", function),
+            Expr::Wildcard => f.write_str("*"),
+            Expr::Default => f.write_str("DEFAULT"),
+            Expr::Parameter(parameter) => write!(f, "{}", parameter),
+            Expr::Unary {
+                operator: UnaryOperator::Not,
+                expr,
+                ..
+            } => {
+                let operand = operand(expr, NOT_PRECEDENCE);
+                // otherwise it would be read back as `NOT EXISTS`
+                if operand.starts_with("EXISTS") {
+                    write!(f, "NOT ({})", operand)
+                } else {
+                    write!(f, "NOT {}", operand)
+                }
+            }
+            Expr::Unary { operator, expr, .. } => {
+                let sign = if *operator == UnaryOperator::Minus {
+                    "-"
+                } else {
+                    "+"
+                };
+                let text = operand(expr, UNARY_PRECEDENCE);
+                // `-1` would be read as a literal, `--1` as a comment and `-+1` is invalid
+                if matches!(**expr, Expr::Literal(Literal::Numeric(_)))
+                    || text.starts_with(['-', '+'])
+                {
+                    write!(f, "{}({})", sign, text)
+                } else {
+                    write!(f, "{}{}", sign, text)
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let precedence = operator.precedence();
+                write!(
+                    f,
+                    "{} {} {}",
+                    operand(left, precedence),
+                    operator,
+                    operand(right, precedence + 1)
+                )
+            }
+            Expr::IsNull { expr, negated } => write!(
+                f,
+                "{} IS {}NULL",
+                operand(expr, COMPARISON_PRECEDENCE),
+                not(negated)
+            ),
+            Expr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => write!(
+                f,
+                "{} IS {}DISTINCT FROM {}",
+                operand(left, COMPARISON_PRECEDENCE),
+                not(negated),
+                operand(right, COMPARISON_PRECEDENCE + 1)
+            ),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => write!(
+                f,
+                "{} {}IN ({})",
+                operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                CommaSeparated(list)
+            ),
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => write!(
+                f,
+                "{} {}IN ({})",
+                operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                subquery
+            ),
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => write!(
+                f,
+                "{} {}BETWEEN {} AND {}",
+                operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                operand(low, COMPARISON_PRECEDENCE + 1),
+                operand(high, COMPARISON_PRECEDENCE + 1)
+            ),
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => {
+                write!(
+                    f,
+                    "{} {} {}",
+                    operand(expr, COMPARISON_PRECEDENCE),
+                    kind.operator(*negated),
+                    operand(pattern, COMPARISON_PRECEDENCE + 1)
+                )?;
+                match escape {
+                    Some(escape) => {
+                        write!(f, " ESCAPE {}", operand(escape, COMPARISON_PRECEDENCE + 1))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Expr::Exists { subquery, negated } => {
+                write!(f, "{}EXISTS ({})", not(negated), subquery)
+            }
+            Expr::Subquery(subquery) => write!(f, "({})", subquery),
+            Expr::Tuple(items) => write!(f, "({})", CommaSeparated(items)),
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                f.write_str("CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (condition, result) in when_then {
+                    write!(f, " WHEN {} THEN {}", condition, result)?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {}", else_result)?;
+                }
+                f.write_str(" END")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ColumnIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(schema_name) = &self.schema_name {
+            write!(f, "{}.", Identifier(schema_name))?;
+        }
+        if let Some(table_name) = &self.table_name {
+            write!(f, "{}.", Identifier(table_name))?;
+        }
+        write!(f, "{}", Identifier(&self.column_name))
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let distinct = if self.distinct { "DISTINCT " } else { "" };
+        write!(
+            f,
+            "{}({}{})",
+            Identifier(&self.name),
+            distinct,
+            CommaSeparated(&self.arguments)
+        )?;
+        if !self.within_group.is_empty() {
+            write!(
+                f,
+                " WITHIN GROUP (ORDER BY {})",
+                CommaSeparated(&self.within_group)
+            )?;
+        }
+        if let Some(window) = &self.over {
+            write!(f, " OVER {}", window)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut clauses = vec![];
+        if !self.partition_by.is_empty() {
+            clauses.push(format!(
+                "PARTITION BY {}",
+                CommaSeparated(&self.partition_by)
+            ));
+        }
+        if !self.order_by.is_empty() {
+            clauses.push(format!("ORDER BY {}", CommaSeparated(&self.order_by)));
+        }
+        if let Some(frame) = &self.frame {
+            clauses.push(frame.to_string());
+        }
+        write!(f, "({})", clauses.join(" "))
+    }
+}
+
+impl fmt::Display for WindowFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = match self.units {
+            FrameUnits::Rows => "ROWS",
+            FrameUnits::Range => "RANGE",
+        };
+        write!(f, "{} BETWEEN {} AND {}", units, self.start, self.end)
+    }
+}
+
+impl fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameBound::UnboundedPreceding => f.write_str("UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(offset) => write!(f, "{} PRECEDING", offset),
+            FrameBound::CurrentRow => f.write_str("CURRENT ROW"),
+            FrameBound::Following(offset) => write!(f, "{} FOLLOWING", offset),
+            FrameBound::UnboundedFollowing => f.write_str("UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Numeric(value) => f.write_str(value),
+            Literal::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Literal::Boolean(true) => f.write_str("TRUE"),
+            Literal::Boolean(false) => f.write_str("FALSE"),
+            Literal::Null => f.write_str("NULL"),
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Parameter::Anonymous(_) => f.write_str("?"),
+            Parameter::Positional(position) => write!(f, "${}", position),
+            Parameter::Named(name) => write!(f, ":{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Equal => "=",
+            Operator::NotEqual => "<>",
+            Operator::GreaterThan => ">",
+            Operator::LessThan => "<",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::LessThanOrEqual => "<=",
+            Operator::And => "AND",
+            Operator::Or => "OR",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Concat => "||",
+        })
+    }
+}
+
+impl fmt::Display for Queries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, query) in self.queries.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{};", query)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Select(query) => write!(f, "{}", query),
+            Query::Insert(query) => write!(f, "{}", query),
+            Query::Update(query) => write!(f, "{}", query),
+            Query::Delete(query) => write!(f, "{}", query),
+            Query::Begin(query) => write!(f, "{}", query),
+            Query::Commit => f.write_str("COMMIT"),
+            Query::Rollback(query) => write!(f, "{}", query),
+            Query::Savepoint(query) => write!(f, "SAVEPOINT {}", Identifier(&query.name)),
+            Query::Release(query) => write!(f, "RELEASE SAVEPOINT {}", Identifier(&query.name)),
+            Query::SetVariable(query) => write!(f, "{}", query),
+            Query::Show(query) => write!(f, "{}", query),
+            Query::Explain(query) => write!(f, "{}", query),
+            Query::Prepare(query) => write!(f, "{}", query),
+            Query::Execute(query) => write!(f, "{}", query),
+            Query::Deallocate(query) => write!(f, "{}", query),
+            Query::CreateTable(query) => write!(f, "{}", query),
+            Query::CreateIndex(query) => write!(f, "{}", query),
+            Query::CreateView(query) => write!(f, "{}", query),
+            Query::CreateSequence(query) => write!(f, "{}", query),
+            Query::CreateSchema(query) => write!(f, "{}", query),
+            Query::Drop(query) => write!(f, "{}", query),
+            Query::Analyze(query) => write!(f, "{}", query),
+        }
+    }
+}
+
+impl fmt::Display for SelectQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.select_statement)?;
+        if let Some(from_statement) = &self.from_statement {
+            write!(f, " FROM {}", from_statement)?;
+        }
+        if let Some(where_statement) = &self.where_statement {
+            write!(f, " {}", where_statement)?;
+        }
+        if let Some(group_by_statement) = &self.group_by_statement {
+            write!(f, " {}", group_by_statement)?;
+        }
+        if let Some(having_statement) = &self.having_statement {
+            write!(f, " {}", having_statement)?;
+        }
+        for union in &self.unions {
+            let all = if union.all { " ALL" } else { "" };
+            write!(f, " UNION{} {}", all, union.query)?;
+        }
+        if let Some(order_by_statement) = &self.order_by_statement {
+            write!(f, " {}", order_by_statement)?;
+        }
+        if let Some(limit_statement) = &self.limit_statement {
+            write!(f, " {}", limit_statement)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let distinct = if self.distinct { "DISTINCT " } else { "" };
+        write!(f, "SELECT {}{}", distinct, CommaSeparated(&self.columns))
+    }
+}
+
+impl fmt::Display for ColumnStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnStatement::Wildcard => f.write_str("*"),
+            ColumnStatement::QualifiedWildcard {
+                schema_name,
+                table_name,
+                ..
+            } => {
+                if let Some(schema_name) = schema_name {
+                    write!(f, "{}.", Identifier(schema_name))?;
+                }
+                write!(f, "{}.*", Identifier(table_name))
+            }
+            ColumnStatement::Expr { expr, alias } => {
+                write!(f, "{}", expr)?;
+                if let Some(alias) = alias {
+                    write!(f, " AS {}", Identifier(alias))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Tables and joins without the leading `FROM` or `USING`.
+impl fmt::Display for FromStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", CommaSeparated(&self.tables))?;
+        for join in &self.joins {
+            write!(f, " {}", join)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TableStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(schema_name) = &self.schema_name {
+            write!(f, "{}.", Identifier(schema_name))?;
+        }
+        write!(f, "{}", Identifier(&self.table_name))?;
+        if let Some(arguments) = &self.arguments {
+            write!(f, "({})", CommaSeparated(arguments))?;
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, " AS {}", Identifier(alias))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for JoinStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.join_type, self.table)?;
+        if let Some(on) = &self.on {
+            write!(f, " ON {}", on)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for JoinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JoinType::Inner => "JOIN",
+            JoinType::Left => "LEFT JOIN",
+            JoinType::Right => "RIGHT JOIN",
+            JoinType::Full => "FULL JOIN",
+            JoinType::Cross => "CROSS JOIN",
+        })
+    }
+}
+
+impl fmt::Display for WhereStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WHERE {}", self.condition)
+    }
+}
+
+impl fmt::Display for GroupByStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GROUP BY {}", CommaSeparated(&self.columns))
+    }
+}
+
+impl fmt::Display for HavingStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HAVING {}", self.condition)
+    }
+}
+
+impl fmt::Display for OrderByStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ORDER BY {}", CommaSeparated(&self.columns))
+    }
+}
+
+impl fmt::Display for OrderByColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.expr, self.order)
+    }
+}
+
+/// Nothing for the default order, `NULLS` only when they aren't where the direction puts
+/// them.
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        match (self.nulls_first, self.descending) {
+            (true, false) => write!(f, " NULLS FIRST"),
+            (false, true) => write!(f, " NULLS LAST"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for LimitStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.limit, self.offset) {
+            (Some(limit), Some(offset)) => write!(f, "LIMIT {} OFFSET {}", limit, offset),
+            (Some(limit), None) => write!(f, "LIMIT {}", limit),
+            (None, Some(offset)) => write!(f, "OFFSET {}", offset),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for InsertQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "INSERT INTO {}", self.table)?;
+        if !self.columns.is_empty() {
+            write!(f, " ({})", Identifiers(&self.columns))?;
+        }
+        write!(f, " {}", self.source)?;
+        if let Some(on_conflict) = &self.on_conflict {
+            write!(f, " {}", on_conflict)?;
+        }
+        write_returning(f, &self.returning)
+    }
+}
+
+fn write_returning(f: &mut fmt::Formatter<'_>, returning: &[ColumnStatement]) -> fmt::Result {
+    if returning.is_empty() {
+        return Ok(());
+    }
+    write!(f, " RETURNING {}", CommaSeparated(returning))
+}
+
+impl fmt::Display for InsertSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertSource::Values(rows) => {
+                f.write_str("VALUES ")?;
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "({})", CommaSeparated(row))?;
+                }
+                Ok(())
+            }
+            InsertSource::Select(query) => write!(f, "{}", query),
+            InsertSource::DefaultValues => f.write_str("DEFAULT VALUES"),
+        }
+    }
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ON CONFLICT")?;
+        if !self.columns.is_empty() {
+            write!(f, " ({})", Identifiers(&self.columns))?;
+        }
+        match &self.action {
+            ConflictAction::DoNothing => f.write_str(" DO NOTHING"),
+            ConflictAction::DoUpdate {
+                set_statement,
+                where_statement,
+            } => {
+                write!(f, " DO UPDATE {}", set_statement)?;
+                if let Some(where_statement) = where_statement {
+                    write!(f, " {}", where_statement)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for UpdateQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UPDATE {} {}", self.table, self.set_statement)?;
+        if let Some(from_statement) = &self.from_statement {
+            write!(f, " FROM {}", from_statement)?;
+        }
+        if let Some(where_statement) = &self.where_statement {
+            write!(f, " {}", where_statement)?;
+        }
+        write_returning(f, &self.returning)
+    }
+}
+
+impl fmt::Display for SetStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SET {}", CommaSeparated(&self.assignments))
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.columns.as_slice() {
+            [column] => write!(f, "{} = {}", Identifier(column), self.value),
+            columns => write!(f, "({}) = {}", Identifiers(columns), self.value),
+        }
+    }
+}
+
+impl fmt::Display for DeleteQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DELETE FROM {}", self.table)?;
+        if let Some(using_statement) = &self.using_statement {
+            write!(f, " USING {}", using_statement)?;
+        }
+        if let Some(where_statement) = &self.where_statement {
+            write!(f, " {}", where_statement)?;
+        }
+        write_returning(f, &self.returning)
+    }
+}
+
+impl fmt::Display for BeginQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BEGIN")?;
+        if let Some(isolation_level) = self.isolation_level {
+            write!(f, " ISOLATION LEVEL {}", isolation_level)?;
+        }
+        if let Some(access_mode) = self.access_mode {
+            if self.isolation_level.is_some() {
+                f.write_str(",")?;
+            }
+            write!(f, " {}", access_mode)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        })
+    }
+}
+
+impl fmt::Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AccessMode::ReadOnly => "READ ONLY",
+            AccessMode::ReadWrite => "READ WRITE",
+        })
+    }
+}
+
+impl fmt::Display for RollbackQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ROLLBACK")?;
+        if let Some(savepoint) = &self.savepoint {
+            write!(f, " TO SAVEPOINT {}", Identifier(savepoint))?;
+        }
+        Ok(())
+    }
+}
+
+/// Setting names may be dotted, every part is quoted separately.
+struct VariableName<'a>(&'a str);
+
+impl fmt::Display for VariableName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.0.split('.').enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", Identifier(part))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SetVariableQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SET {} = {}", VariableName(&self.name), self.value)
+    }
+}
+
+impl fmt::Display for ShowQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "SHOW {}", VariableName(name)),
+            None => f.write_str("SHOW ALL"),
+        }
+    }
+}
+
+impl fmt::Display for ExplainQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let analyze = if self.analyze { "ANALYZE " } else { "" };
+        write!(f, "EXPLAIN {}{}", analyze, self.query)
+    }
+}
+
+impl fmt::Display for PrepareQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PREPARE {}", Identifier(&self.name))?;
+        if !self.parameter_types.is_empty() {
+            write!(f, " ({})", CommaSeparated(&self.parameter_types))?;
+        }
+        write!(f, " AS {}", self.statement)
+    }
+}
+
+impl fmt::Display for ExecuteQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EXECUTE {}", Identifier(&self.name))?;
+        if !self.arguments.is_empty() {
+            write!(f, " ({})", CommaSeparated(&self.arguments))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DeallocateQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "DEALLOCATE {}", Identifier(name)),
+            None => f.write_str("DEALLOCATE ALL"),
+        }
+    }
+}
+
+impl fmt::Display for AnalyzeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "ANALYZE {}", table),
+            None => f.write_str("ANALYZE"),
+        }
+    }
+}
+
+impl fmt::Display for ObjectName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(schema_name) = &self.schema_name {
+            write!(f, "{}.", Identifier(schema_name))?;
+        }
+        write!(f, "{}", Identifier(&self.name))
+    }
+}
+
+impl fmt::Display for CreateTableQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE TABLE ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        write!(f, "{} ({}", self.name, CommaSeparated(&self.columns))?;
+        for constraint in &self.constraints {
+            write!(f, ", {}", constraint)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for ColumnDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", Identifier(&self.name), self.data_type)?;
+        for constraint in &self.constraints {
+            write!(f, " {}", constraint)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ColumnConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnConstraint::NotNull => f.write_str("NOT NULL"),
+            ColumnConstraint::Null => f.write_str("NULL"),
+            ColumnConstraint::Default(expr) => write!(f, "DEFAULT {}", expr),
+            ColumnConstraint::PrimaryKey => f.write_str("PRIMARY KEY"),
+            ColumnConstraint::Unique => f.write_str("UNIQUE"),
+            ColumnConstraint::References { table, column } => {
+                write!(f, "REFERENCES {}", table)?;
+                if let Some(column) = column {
+                    write!(f, " ({})", Identifier(column))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for TableConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "CONSTRAINT {} ", Identifier(name))?;
+        }
+        match &self.kind {
+            TableConstraintKind::PrimaryKey(columns) => {
+                write!(f, "PRIMARY KEY ({})", Identifiers(columns))
+            }
+            TableConstraintKind::Unique(columns) => write!(f, "UNIQUE ({})", Identifiers(columns)),
+            TableConstraintKind::ForeignKey {
+                columns,
+                table,
+                referenced_columns,
+            } => {
+                write!(
+                    f,
+                    "FOREIGN KEY ({}) REFERENCES {}",
+                    Identifiers(columns),
+                    table
+                )?;
+                if !referenced_columns.is_empty() {
+                    write!(f, " ({})", Identifiers(referenced_columns))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for CreateIndexQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE ")?;
+        if self.unique {
+            f.write_str("UNIQUE ")?;
+        }
+        f.write_str("INDEX ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        write!(
+            f,
+            "{} ON {} ({})",
+            Identifier(&self.name),
+            self.table,
+            Identifiers(&self.columns)
+        )
+    }
+}
+
+impl fmt::Display for CreateViewQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CREATE VIEW {} AS {}", self.name, self.query)
+    }
+}
+
+impl fmt::Display for CreateSequenceQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE SEQUENCE ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(increment) = self.increment {
+            write!(f, " INCREMENT BY {}", increment)?;
+        }
+        if let Some(start) = self.start {
+            write!(f, " START WITH {}", start)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CreateSchemaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE SCHEMA ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        write!(f, "{}", Identifier(&self.name))
+    }
+}
+
+impl fmt::Display for DropQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DROP {} ", self.object_type)?;
+        if self.if_exists {
+            f.write_str("IF EXISTS ")?;
+        }
+        write!(f, "{}", CommaSeparated(&self.names))
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ObjectType::Table => "TABLE",
+            ObjectType::View => "VIEW",
+            ObjectType::Index => "INDEX",
+            ObjectType::Sequence => "SEQUENCE",
+            ObjectType::Schema => "SCHEMA",
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parser::expr::{OperatorSpan, PatternKind};
+    use crate::parser::lexer::Span;
+    use proptest::prelude::*;
+    use std::ops::Range;
+
+    fn round_trip(input: &str) -> String {
+        let queries = Queries::parse(input).unwrap();
+        let printed = queries.to_string();
+        assert_eq!(
+            Queries::parse(&printed),
+            Ok(queries),
+            "printed: {}",
+            printed
+        );
+        printed
+    }
+
+    #[test]
+    fn prints_canonical_select() {
+        assert_eq!(
+            round_trip(
+                "select distinct u.name n, count(*) from public.users u left outer join orders o \
+                 on u.id = o.user_id where (a + 1) * 2 > 3 and not b or c is not null \
+                 group by u.name having count(*) > 1 order by n desc, 2 limit 10 offset 5"
+            ),
+            "SELECT DISTINCT u.name AS n, count(*) FROM public.users AS u \
+             LEFT JOIN orders AS o ON u.id = o.user_id \
+             WHERE (a + 1) * 2 > 3 AND NOT b OR c IS NOT NULL \
+             GROUP BY u.name HAVING count(*) > 1 ORDER BY n DESC, 2 LIMIT 10 OFFSET 5;"
+        );
+    }
+
+    #[test]
+    fn prints_table_functions() {
+        assert_eq!(
+            round_trip("select * from generate_series(1, 10, 2) s cross join f()"),
+            "SELECT * FROM generate_series(1, 10, 2) AS s CROSS JOIN f();"
+        );
+    }
+
+    #[test]
+    fn quotes_identifiers_and_strings() {
+        assert_eq!(
+            round_trip("select \"order\", \"My Col\" from \"select\" where name = 'it''s'"),
+            "SELECT \"order\", \"My Col\" FROM \"select\" WHERE name = 'it''s';"
+        );
+        assert_eq!(
+            round_trip("select \"a\"\"b\".\"\"\"\" as \"x\"\"\" from \"a\"\"b\""),
+            "SELECT \"a\"\"b\".\"\"\"\" AS \"x\"\"\" FROM \"a\"\"b\";"
+        );
+    }
+
+    #[test]
+    fn keeps_required_parentheses() {
+        assert_eq!(
+            round_trip("select a - (b - c), -(-1), - -a, (not a) = b, not (exists (select 1))"),
+            "SELECT a - (b - c), -(-1), -(-a), (NOT a) = b, NOT (EXISTS (SELECT 1));"
+        );
+        assert_eq!(
+            round_trip("select a between (b and c) and d, x in (select y from t)"),
+            "SELECT a BETWEEN (b AND c) AND d, x IN (SELECT y FROM t);"
+        );
+    }
+
+    #[test]
+    fn prints_dml() {
+        assert_eq!(
+            round_trip(
+                "insert into t as x (a, b) values (1, default), (-2, 'q') \
+                 on conflict (a) do update set b = x.b || 'q' where x.a > 0 returning *; \
+                 update t set (a, b) = (1, 2) from s cross join r where t.id = s.id; \
+                 delete from t using s where t.id = s.id returning t.*"
+            ),
+            "INSERT INTO t AS x (a, b) VALUES (1, DEFAULT), (-2, 'q') \
+             ON CONFLICT (a) DO UPDATE SET b = x.b || 'q' WHERE x.a > 0 RETURNING *;\n\
+             UPDATE t SET (a, b) = (1, 2) FROM s CROSS JOIN r WHERE t.id = s.id;\n\
+             DELETE FROM t USING s WHERE t.id = s.id RETURNING t.*;"
+        );
+    }
+
+    #[test]
+    fn prints_session_statements() {
+        assert_eq!(
+            round_trip(
+                "start transaction read only isolation level serializable; savepoint s; \
+                 rollback to s; release s; commit; set app.name to 'x'; show all; \
+                 explain analyze select 1"
+            ),
+            "BEGIN ISOLATION LEVEL SERIALIZABLE, READ ONLY;\nSAVEPOINT s;\n\
+             ROLLBACK TO SAVEPOINT s;\nRELEASE SAVEPOINT s;\nCOMMIT;\nSET app.name = 'x';\n\
+             SHOW ALL;\nEXPLAIN ANALYZE SELECT 1;"
+        );
+    }
+
+    #[test]
+    fn prints_ddl() {
+        assert_eq!(
+            round_trip(
+                "create table if not exists s.t (id int primary key, \"order\" text not null \
+                 default 'x' references u (id), constraint k unique (id, \"order\")); \
+                 create unique index i on t (a); create view v as select 1; \
+                 create sequence q increment 2 start with -1; create schema s; \
+                 drop table if exists t, s.u"
+            ),
+            "CREATE TABLE IF NOT EXISTS s.t (id INTEGER PRIMARY KEY, \"order\" TEXT NOT NULL \
+             DEFAULT 'x' REFERENCES u (id), CONSTRAINT k UNIQUE (id, \"order\"));\n\
+             CREATE UNIQUE INDEX i ON t (a);\nCREATE VIEW v AS SELECT 1;\n\
+             CREATE SEQUENCE q INCREMENT BY 2 START WITH -1;\nCREATE SCHEMA s;\n\
+             DROP TABLE IF EXISTS t, s.u;"
+        );
+    }
+
+    fn identifier() -> BoxedStrategy<String> {
+        prop_oneof![
+            4 => "[a-z_][a-z0-9_]{0,6}",
+            1 => prop::sample::select(vec!["select", "Order", "My Col", "t.x", "ключ", "a\"b"])
+                .prop_map(str::to_string),
+        ]
+        .boxed()
+    }
+
+    fn literal() -> BoxedStrategy<Literal> {
+        prop_oneof![
+            "-?[0-9]{1,4}(\\.[0-9]{1,2})?".prop_map(Literal::Numeric),
+            "[ -~]{0,6}".prop_map(Literal::String),
+            any::<bool>().prop_map(Literal::Boolean),
+            Just(Literal::Null),
+        ]
+        .boxed()
+    }
+
+    fn column_identifier() -> BoxedStrategy<ColumnIdentifier> {
+        (
+            proptest::option::of(identifier()),
+            proptest::option::of(identifier()),
+            identifier(),
+        )
+            .prop_map(|(schema_name, table_name, column_name)| ColumnIdentifier {
+                schema_name: table_name.as_ref().and(schema_name),
+                table_name,
+                column_name,
+                span: Span::default(),
+            })
+            .boxed()
+    }
+
+    fn table() -> BoxedStrategy<TableStatement> {
+        (
+            proptest::option::of(identifier()),
+            identifier(),
+            proptest::option::of(identifier()),
+        )
+            .prop_map(|(schema_name, table_name, alias)| TableStatement {
+                schema_name,
+                table_name,
+                arguments: None,
+                alias,
+                span: Span::default(),
+            })
+            .boxed()
+    }
+
+    fn leaf() -> BoxedStrategy<Expr> {
+        prop_oneof![
+            column_identifier().prop_map(Expr::Column),
+            literal().prop_map(Expr::Literal),
+            (
+                prop_oneof![Just(DataType::Date), Just(DataType::Interval)],
+                "[ -~]{0,6}"
+            )
+                .prop_map(|(data_type, value)| Expr::TypedString { data_type, value }),
+            (1..20usize).prop_map(|position| Expr::Parameter(Parameter::Positional(position))),
+            "[a-z_][a-z0-9_]{0,6}".prop_map(|name| Expr::Parameter(Parameter::Named(name))),
+        ]
+        .boxed()
+    }
+
+    /// Subqueries are kept simple to bound the size of generated trees.
+    fn subquery() -> BoxedStrategy<Box<SelectQuery>> {
+        (leaf(), table(), proptest::option::of(leaf()))
+            .prop_map(|(column, table, condition)| {
+                Box::new(SelectQuery {
+                    select_statement: SelectStatement {
+                        columns: vec![ColumnStatement::Expr {
+                            expr: column,
+                            alias: None,
+                        }],
+                        distinct: false,
+                    },
+                    from_statement: Some(FromStatement {
+                        tables: vec![table],
+                        joins: vec![],
+                    }),
+                    where_statement: condition.map(|condition| WhereStatement { condition }),
+                    group_by_statement: None,
+                    having_statement: None,
+                    unions: vec![],
+                    order_by_statement: None,
+                    limit_statement: None,
+                })
+            })
+            .boxed()
+    }
+
+    fn operator() -> BoxedStrategy<Operator> {
+        prop::sample::select(vec![
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::GreaterThan,
+            Operator::LessThan,
+            Operator::GreaterThanOrEqual,
+            Operator::LessThanOrEqual,
+            Operator::And,
+            Operator::Or,
+            Operator::Plus,
+            Operator::Minus,
+            Operator::Multiply,
+            Operator::Divide,
+            Operator::Modulo,
+            Operator::Concat,
+        ])
+        .boxed()
+    }
+
+    fn order_by_columns(
+        expr: BoxedStrategy<Expr>,
+        size: Range<usize>,
+    ) -> BoxedStrategy<Vec<OrderByColumn>> {
+        prop::collection::vec((expr, any::<bool>(), any::<bool>()), size)
+            .prop_map(|columns| {
+                columns
+                    .into_iter()
+                    .map(|(expr, descending, nulls_first)| OrderByColumn {
+                        expr,
+                        order: Order {
+                            descending,
+                            nulls_first,
+                        },
+                    })
+                    .collect()
+            })
+            .boxed()
+    }
+
+    fn frame() -> BoxedStrategy<WindowFrame> {
+        let offset = || (0u8..10).prop_map(|offset| Box::new(Expr::number(&offset.to_string())));
+        let start = prop_oneof![
+            Just(FrameBound::UnboundedPreceding),
+            offset().prop_map(FrameBound::Preceding),
+            Just(FrameBound::CurrentRow),
+            offset().prop_map(FrameBound::Following),
+        ];
+        let end = prop_oneof![
+            offset().prop_map(FrameBound::Preceding),
+            Just(FrameBound::CurrentRow),
+            offset().prop_map(FrameBound::Following),
+            Just(FrameBound::UnboundedFollowing),
+        ];
+        (any::<bool>(), start, end)
+            .prop_map(|(rows, start, end)| WindowFrame {
+                units: if rows {
+                    FrameUnits::Rows
+                } else {
+                    FrameUnits::Range
+                },
+                start,
+                end,
+            })
+            .boxed()
+    }
+
+    fn window(expr: BoxedStrategy<Expr>) -> BoxedStrategy<Window> {
+        (
+            prop::collection::vec(expr.clone(), 0..3),
+            order_by_columns(expr, 0..3),
+            proptest::option::of(frame()),
+        )
+            .prop_map(|(partition_by, order_by, frame)| Window {
+                partition_by,
+                order_by,
+                frame: frame.map(Box::new),
+            })
+            .boxed()
+    }
+
+    fn expr() -> BoxedStrategy<Expr> {
+        let leaf = prop_oneof![
+            8 => leaf(),
+            1 => Just(Expr::Default),
+            1 => "[a-z][a-z_]{0,5}".prop_map(|name| Expr::Function(Function {
+                name,
+                arguments: vec![Expr::Wildcard],
+                distinct: false, within_group: vec![], over: None, span: Span::default(), })),
+            1 => (subquery(), any::<bool>())
+                .prop_map(|(subquery, negated)| Expr::Exists { subquery, negated }),
+            1 => subquery().prop_map(Expr::Subquery),
+        ];
+        leaf.prop_recursive(4, 32, 3, |inner| {
+            let boxed = inner.clone().prop_map(Box::new);
+            prop_oneof![
+                (boxed.clone(), operator(), boxed.clone()).prop_map(|(left, operator, right)| {
+                    Expr::Binary {
+                        left,
+                        operator,
+                        right,
+                        span: OperatorSpan::default(),
+                    }
+                }),
+                (
+                    prop::sample::select(vec![
+                        UnaryOperator::Not,
+                        UnaryOperator::Minus,
+                        UnaryOperator::Plus
+                    ]),
+                    boxed.clone()
+                )
+                    .prop_map(|(operator, expr)| Expr::Unary {
+                        operator,
+                        expr,
+                        span: OperatorSpan::default(),
+                    }),
+                (boxed.clone(), any::<bool>())
+                    .prop_map(|(expr, negated)| Expr::IsNull { expr, negated }),
+                (boxed.clone(), boxed.clone(), any::<bool>()).prop_map(|(left, right, negated)| {
+                    Expr::IsDistinctFrom {
+                        left,
+                        right,
+                        negated,
+                    }
+                }),
+                (
+                    boxed.clone(),
+                    prop::collection::vec(inner.clone(), 1..3),
+                    any::<bool>()
+                )
+                    .prop_map(|(expr, list, negated)| Expr::InList {
+                        expr,
+                        list,
+                        negated
+                    }),
+                (boxed.clone(), subquery(), any::<bool>()).prop_map(|(expr, subquery, negated)| {
+                    Expr::InSubquery {
+                        expr,
+                        subquery,
+                        negated,
+                    }
+                }),
+                (boxed.clone(), boxed.clone(), boxed.clone(), any::<bool>()).prop_map(
+                    |(expr, low, high, negated)| Expr::Between {
+                        expr,
+                        low,
+                        high,
+                        negated
+                    }
+                ),
+                (
+                    boxed.clone(),
+                    boxed.clone(),
+                    proptest::option::of(boxed.clone()),
+                    prop_oneof![
+                        Just(PatternKind::Like),
+                        Just(PatternKind::ILike),
+                        Just(PatternKind::SimilarTo),
+                        Just(PatternKind::Regex),
+                        Just(PatternKind::RegexIgnoreCase),
+                    ],
+                    any::<bool>()
+                )
+                    .prop_map(|(expr, pattern, escape, kind, negated)| {
+                        Expr::Like {
+                            expr,
+                            pattern,
+                            escape: escape.filter(|_| kind.escapes()),
+                            kind,
+                            negated,
+                        }
+                    }),
+                (
+                    "[a-z][a-z_]{0,5}",
+                    prop::collection::vec(inner.clone(), 0..3),
+                    any::<bool>(),
+                    order_by_columns(inner.clone(), 0..2),
+                    proptest::option::of(window(inner.clone()))
+                )
+                    .prop_map(|(name, arguments, distinct, within_group, over)| {
+                        Expr::Function(Function {
+                            distinct: distinct && !arguments.is_empty(),
+                            name,
+                            arguments,
+                            within_group,
+                            over,
+                            span: Span::default(),
+                        })
+                    }),
+                prop::collection::vec(inner.clone(), 2..4).prop_map(Expr::Tuple),
+                (
+                    proptest::option::of(boxed.clone()),
+                    prop::collection::vec((inner.clone(), inner.clone()), 1..3),
+                    proptest::option::of(boxed)
+                )
+                    .prop_map(|(operand, when_then, else_result)| Expr::Case {
+                        operand,
+                        when_then,
+                        else_result
+                    }),
+            ]
+        })
+        .boxed()
+    }
+
+    fn column_statement() -> BoxedStrategy<ColumnStatement> {
+        prop_oneof![
+            1 => Just(ColumnStatement::Wildcard),
+            1 => (proptest::option::of(identifier()), identifier()).prop_map(
+                |(schema_name, table_name)| ColumnStatement::QualifiedWildcard {
+                    schema_name,
+                    table_name,
+                    span: Span::default(),
+                }
+            ),
+            4 => (expr(), proptest::option::of(identifier()))
+                .prop_map(|(expr, alias)| ColumnStatement::Expr { expr, alias }),
+        ]
+        .boxed()
+    }
+
+    fn join() -> BoxedStrategy<JoinStatement> {
+        (
+            table(),
+            prop::sample::select(vec![
+                JoinType::Inner,
+                JoinType::Left,
+                JoinType::Right,
+                JoinType::Full,
+                JoinType::Cross,
+            ]),
+            expr(),
+        )
+            .prop_map(|(table, join_type, on)| JoinStatement {
+                table,
+                join_type,
+                on: (join_type != JoinType::Cross).then_some(on),
+            })
+            .boxed()
+    }
+
+    fn from_statement() -> BoxedStrategy<FromStatement> {
+        (
+            prop::collection::vec(table(), 1..3),
+            prop::collection::vec(join(), 0..2),
+        )
+            .prop_map(|(tables, joins)| FromStatement { tables, joins })
+            .boxed()
+    }
+
+    fn select_query() -> BoxedStrategy<SelectQuery> {
+        (
+            (
+                prop::collection::vec(column_statement(), 1..3),
+                any::<bool>(),
+                proptest::option::of(from_statement()),
+                proptest::option::of(expr()),
+            ),
+            (
+                proptest::option::of(prop::collection::vec(expr(), 1..3)),
+                proptest::option::of(expr()),
+                proptest::option::of(order_by_columns(expr(), 1..3)),
+                proptest::option::of((
+                    proptest::option::of(0..100u64),
+                    proptest::option::of(0..100u64),
+                )),
+                proptest::option::of((any::<bool>(), subquery())),
+            ),
+        )
+            .prop_map(
+                |(
+                    (columns, distinct, from_statement, condition),
+                    (group_by, having, order_by, limit, union),
+                )| {
+                    SelectQuery {
+                        select_statement: SelectStatement { columns, distinct },
+                        from_statement,
+                        where_statement: condition.map(|condition| WhereStatement { condition }),
+                        group_by_statement: group_by.map(|columns| GroupByStatement { columns }),
+                        having_statement: having.map(|condition| HavingStatement { condition }),
+                        unions: union
+                            .into_iter()
+                            .map(|(all, query)| UnionStatement { all, query: *query })
+                            .collect(),
+                        order_by_statement: order_by.map(|columns| OrderByStatement { columns }),
+                        limit_statement: limit
+                            .filter(|(limit, offset)| limit.is_some() || offset.is_some())
+                            .map(|(limit, offset)| LimitStatement { limit, offset }),
+                    }
+                },
+            )
+            .boxed()
+    }
+
+    fn assignment() -> BoxedStrategy<Assignment> {
+        (prop::collection::vec(identifier(), 1..3), expr())
+            .prop_map(|(columns, value)| Assignment { columns, value })
+            .boxed()
+    }
+
+    fn set_statement() -> BoxedStrategy<SetStatement> {
+        prop::collection::vec(assignment(), 1..3)
+            .prop_map(|assignments| SetStatement { assignments })
+            .boxed()
+    }
+
+    fn insert_query() -> BoxedStrategy<InsertQuery> {
+        let source = prop_oneof![
+            prop::collection::vec(prop::collection::vec(expr(), 1..3), 1..3)
+                .prop_map(InsertSource::Values),
+            select_query().prop_map(|query| InsertSource::Select(Box::new(query))),
+            Just(InsertSource::DefaultValues),
+        ];
+        let action = prop_oneof![
+            Just(ConflictAction::DoNothing),
+            (set_statement(), proptest::option::of(expr())).prop_map(
+                |(set_statement, condition)| ConflictAction::DoUpdate {
+                    set_statement,
+                    where_statement: condition.map(|condition| WhereStatement { condition }),
+                }
+            ),
+        ];
+        (
+            table(),
+            prop::collection::vec(identifier(), 0..3),
+            source,
+            proptest::option::of((prop::collection::vec(identifier(), 0..2), action)),
+            prop::collection::vec(column_statement(), 0..2),
+        )
+            .prop_map(
+                |(table, columns, source, on_conflict, returning)| InsertQuery {
+                    table,
+                    columns,
+                    source,
+                    on_conflict: on_conflict
+                        .map(|(columns, action)| OnConflict { columns, action }),
+                    returning,
+                },
+            )
+            .boxed()
+    }
+
+    fn update_query() -> BoxedStrategy<UpdateQuery> {
+        (
+            table(),
+            set_statement(),
+            proptest::option::of(from_statement()),
+            proptest::option::of(expr()),
+            prop::collection::vec(column_statement(), 0..2),
+        )
+            .prop_map(
+                |(table, set_statement, from_statement, condition, returning)| UpdateQuery {
+                    table,
+                    set_statement,
+                    from_statement,
+                    where_statement: condition.map(|condition| WhereStatement { condition }),
+                    returning,
+                },
+            )
+            .boxed()
+    }
+
+    fn delete_query() -> BoxedStrategy<DeleteQuery> {
+        (
+            table(),
+            proptest::option::of(from_statement()),
+            proptest::option::of(expr()),
+            prop::collection::vec(column_statement(), 0..2),
+        )
+            .prop_map(
+                |(table, using_statement, condition, returning)| DeleteQuery {
+                    table,
+                    using_statement,
+                    where_statement: condition.map(|condition| WhereStatement { condition }),
+                    returning,
+                },
+            )
+            .boxed()
+    }
+
+    /// Variable names are stored dotted, so their parts can't contain dots.
+    fn variable_name() -> BoxedStrategy<String> {
+        let part = "[a-z_][a-z0-9_]{0,6}";
+        (part, proptest::option::of(part))
+            .prop_map(|(first, second)| match second {
+                Some(second) => format!("{}.{}", first, second),
+                None => first,
+            })
+            .boxed()
+    }
+
+    fn session_query() -> BoxedStrategy<Query> {
+        let isolation_level = prop::sample::select(vec![
+            IsolationLevel::ReadUncommitted,
+            IsolationLevel::ReadCommitted,
+            IsolationLevel::RepeatableRead,
+            IsolationLevel::Serializable,
+        ]);
+        let access_mode = prop::sample::select(vec![AccessMode::ReadOnly, AccessMode::ReadWrite]);
+        prop_oneof![
+            (
+                proptest::option::of(isolation_level),
+                proptest::option::of(access_mode)
+            )
+                .prop_map(|(isolation_level, access_mode)| Query::Begin(BeginQuery {
+                    isolation_level,
+                    access_mode
+                })),
+            Just(Query::Commit),
+            proptest::option::of(identifier())
+                .prop_map(|savepoint| Query::Rollback(RollbackQuery { savepoint })),
+            identifier().prop_map(|name| Query::Savepoint(SavepointQuery { name })),
+            identifier().prop_map(|name| Query::Release(ReleaseQuery { name })),
+            (variable_name(), expr())
+                .prop_map(|(name, value)| Query::SetVariable(SetVariableQuery { name, value })),
+            proptest::option::of(variable_name()).prop_map(|name| Query::Show(ShowQuery { name })),
+        ]
+        .boxed()
+    }
+
+    fn data_type() -> BoxedStrategy<DataType> {
+        prop::sample::select(vec![
+            DataType::Boolean,
+            DataType::SmallInt,
+            DataType::Integer,
+            DataType::BigInt,
+            DataType::Real,
+            DataType::Double,
+            DataType::Decimal(None),
+            DataType::Decimal(Some((10, 2))),
+            DataType::VarChar(None),
+            DataType::VarChar(Some(20)),
+            DataType::Text,
+            DataType::Date,
+            DataType::Time,
+            DataType::Timestamp,
+            DataType::TimestampTz,
+        ])
+        .boxed()
+    }
+
+    fn prepared_query() -> BoxedStrategy<Query> {
+        let statement = prop_oneof![
+            select_query().prop_map(Query::Select),
+            insert_query().prop_map(Query::Insert),
+            update_query().prop_map(Query::Update),
+            delete_query().prop_map(Query::Delete),
+        ];
+        prop_oneof![
+            (
+                identifier(),
+                prop::collection::vec(data_type(), 0..3),
+                statement
+            )
+                .prop_map(|(name, parameter_types, statement)| {
+                    Query::Prepare(PrepareQuery {
+                        name,
+                        parameter_types,
+                        statement: Box::new(statement),
+                    })
+                }),
+            (
+                identifier(),
+                prop::collection::vec(literal().prop_map(Expr::Literal), 0..3)
+            )
+                .prop_map(|(name, arguments)| Query::Execute(ExecuteQuery { name, arguments })),
+            proptest::option::of(identifier())
+                .prop_map(|name| Query::Deallocate(DeallocateQuery { name })),
+        ]
+        .boxed()
+    }
+
+    fn object_name() -> BoxedStrategy<ObjectName> {
+        (proptest::option::of(identifier()), identifier())
+            .prop_map(|(schema_name, name)| ObjectName { schema_name, name })
+            .boxed()
+    }
+
+    fn ddl_query() -> BoxedStrategy<Query> {
+        let column_constraint = prop_oneof![
+            Just(ColumnConstraint::NotNull),
+            Just(ColumnConstraint::Null),
+            literal().prop_map(|literal| ColumnConstraint::Default(Expr::Literal(literal))),
+            Just(ColumnConstraint::PrimaryKey),
+            Just(ColumnConstraint::Unique),
+            (object_name(), proptest::option::of(identifier()))
+                .prop_map(|(table, column)| ColumnConstraint::References { table, column }),
+        ];
+        let column = (
+            identifier(),
+            data_type(),
+            prop::collection::vec(column_constraint, 0..3),
+        )
+            .prop_map(|(name, data_type, constraints)| ColumnDefinition {
+                name,
+                data_type,
+                constraints,
+            });
+        let columns = || prop::collection::vec(identifier(), 1..3);
+        let table_constraint_kind = prop_oneof![
+            columns().prop_map(TableConstraintKind::PrimaryKey),
+            columns().prop_map(TableConstraintKind::Unique),
+            (
+                columns(),
+                object_name(),
+                prop::collection::vec(identifier(), 0..3)
+            )
+                .prop_map(|(columns, table, referenced_columns)| {
+                    TableConstraintKind::ForeignKey {
+                        columns,
+                        table,
+                        referenced_columns,
+                    }
+                }),
+        ];
+        let table_constraint = (proptest::option::of(identifier()), table_constraint_kind)
+            .prop_map(|(name, kind)| TableConstraint { name, kind });
+        let object_type = prop::sample::select(vec![
+            ObjectType::Table,
+            ObjectType::View,
+            ObjectType::Index,
+            ObjectType::Sequence,
+            ObjectType::Schema,
+        ]);
+        let number = || proptest::option::of(-1000i64..1000);
+        prop_oneof![
+            (
+                object_name(),
+                any::<bool>(),
+                prop::collection::vec(column, 1..4),
+                prop::collection::vec(table_constraint, 0..2),
+            )
+                .prop_map(|(name, if_not_exists, columns, constraints)| {
+                    Query::CreateTable(CreateTableQuery {
+                        name,
+                        if_not_exists,
+                        columns,
+                        constraints,
+                    })
+                }),
+            (
+                identifier(),
+                any::<bool>(),
+                any::<bool>(),
+                object_name(),
+                columns()
+            )
+                .prop_map(|(name, unique, if_not_exists, table, columns)| {
+                    Query::CreateIndex(CreateIndexQuery {
+                        name,
+                        unique,
+                        if_not_exists,
+                        table,
+                        columns,
+                    })
+                }),
+            (object_name(), select_query())
+                .prop_map(|(name, query)| Query::CreateView(CreateViewQuery { name, query })),
+            (object_name(), any::<bool>(), number(), number()).prop_map(
+                |(name, if_not_exists, increment, start)| {
+                    Query::CreateSequence(CreateSequenceQuery {
+                        name,
+                        if_not_exists,
+                        increment,
+                        start,
+                    })
+                }
+            ),
+            (identifier(), any::<bool>()).prop_map(|(name, if_not_exists)| {
+                Query::CreateSchema(CreateSchemaQuery {
+                    name,
+                    if_not_exists,
+                })
+            }),
+            (
+                object_type,
+                any::<bool>(),
+                prop::collection::vec(object_name(), 1..3)
+            )
+                .prop_map(|(object_type, if_exists, names)| {
+                    let names = match object_type {
+                        ObjectType::Schema => names
+                            .into_iter()
+                            .map(|name| ObjectName {
+                                schema_name: None,
+                                ..name
+                            })
+                            .collect(),
+                        _ => names,
+                    };
+                    Query::Drop(DropQuery {
+                        object_type,
+                        if_exists,
+                        names,
+                    })
+                }),
+            proptest::option::of(object_name())
+                .prop_map(|table| Query::Analyze(AnalyzeQuery { table })),
+        ]
+        .boxed()
+    }
+
+    pub(crate) fn query() -> BoxedStrategy<Query> {
+        let statement = prop_oneof![
+            select_query().prop_map(Query::Select),
+            insert_query().prop_map(Query::Insert),
+            update_query().prop_map(Query::Update),
+            delete_query().prop_map(Query::Delete),
+            session_query(),
+            prepared_query(),
+            ddl_query(),
+        ];
+        (statement, proptest::option::of(any::<bool>()))
+            .prop_map(|(query, explain)| match explain {
+                // `EXPLAIN ANALYZE` is explain with analyze, analyze can't be explained
+                Some(_) if matches!(query, Query::Analyze(_)) => query,
+                Some(analyze) => Query::Explain(ExplainQuery {
+                    analyze,
+                    query: Box::new(query),
+                }),
+                None => query,
+            })
+            .boxed()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(512))]
+
+        #[test]
+        fn expr_round_trip(expr in expr()) {
+            let printed = expr.to_string();
+            let mut parser = Parser::new(&printed).unwrap();
+            prop_assert_eq!(Expr::parse(&mut parser), Ok(expr), "printed: {}", printed);
+            prop_assert!(parser.is_finished());
+        }
+
+        #[test]
+        fn queries_round_trip(queries in prop::collection::vec(query(), 1..3)) {
+            let queries = Queries { queries };
+            let printed = queries.to_string();
+            prop_assert_eq!(Queries::parse(&printed), Ok(queries), "printed: {}", printed);
+        }
+    }
+}
@@ -0,0 +1,1212 @@
+use crate::parser::lexer::{Span, Token};
+use crate::parser::{OrderByColumn, OrderByStatement, ParseError, Parser, SelectQuery};
+use crate::value::DataType;
+
+/// *Expr* is a scalar expression, it may appear in select list, conditions, values etc.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Column(ColumnIdentifier),
+    Literal(Literal),
+    /// `<type> '<text>'`, e.g. `date '2024-03-01'` or `interval '1 day'`
+    TypedString {
+        data_type: DataType,
+        value: String,
+    },
+    Function(Function),
+    /// `*` inside of a function call, e.g. `count(*)`
+    Wildcard,
+    /// `DEFAULT` keyword in `VALUES` list
+    Default,
+    /// Placeholder of a prepared statement
+    Parameter(Parameter),
+    Unary {
+        operator: UnaryOperator,
+        expr: Box<Expr>,
+        span: OperatorSpan,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: Operator,
+        right: Box<Expr>,
+        span: OperatorSpan,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// `left IS [NOT] DISTINCT FROM right`, `=` with `NULL` equal to `NULL`
+    IsDistinctFrom {
+        left: Box<Expr>,
+        right: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    InSubquery {
+        expr: Box<Expr>,
+        subquery: Box<SelectQuery>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// `LIKE`, `ILIKE`, `SIMILAR TO` or a regular expression operator such as `~`
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+        kind: PatternKind,
+        negated: bool,
+    },
+    Exists {
+        subquery: Box<SelectQuery>,
+        negated: bool,
+    },
+    Subquery(Box<SelectQuery>),
+    /// `(<expr>, <expr>, ...)`, e.g. the right side of a multi-column assignment
+    Tuple(Vec<Expr>),
+    /// case [<operand>] when <condition> then <result> ... [else <result>] end
+    Case {
+        operand: Option<Box<Expr>>,
+        when_then: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnIdentifier {
+    pub schema_name: Option<String>,
+    pub table_name: Option<String>,
+    pub column_name: String,
+    /// Position in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl PartialEq for ColumnIdentifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema_name == other.schema_name
+            && self.table_name == other.table_name
+            && self.column_name == other.column_name
+    }
+}
+
+/// Position of an operator in the query text.
+/// Enum variants can't skip a field when they are compared, so any two of them are equal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OperatorSpan(pub Span);
+
+impl PartialEq for OperatorSpan {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub arguments: Vec<Expr>,
+    pub distinct: bool,
+    /// Ordering of an ordered-set aggregate, `f(x) WITHIN GROUP (ORDER BY ...)`
+    pub within_group: Vec<OrderByColumn>,
+    /// Window of a window function call, `f(x) OVER (...)`
+    pub over: Option<Window>,
+    /// Position of the name in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.arguments == other.arguments
+            && self.distinct == other.distinct
+            && self.within_group == other.within_group
+            && self.over == other.over
+    }
+}
+
+/// `OVER ([PARTITION BY <expr>, ...] [ORDER BY <expr> [ASC|DESC], ...] [<frame>])`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Window {
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderByColumn>,
+    pub frame: Option<Box<WindowFrame>>,
+}
+
+/// `{ROWS | RANGE} BETWEEN <start> AND <end>`, `{ROWS | RANGE} <start>` ends with the
+/// current row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FrameUnits {
+    /// Offsets count rows
+    Rows,
+    /// Offsets are distances of the `ORDER BY` value, the peers of a row are in its frame
+    Range,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Box<Expr>),
+    CurrentRow,
+    Following(Box<Expr>),
+    UnboundedFollowing,
+}
+
+/// Literals keep the text of numbers, so precision is decided later by the analyser.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Literal {
+    Numeric(String),
+    String(String),
+    Boolean(bool),
+    Null,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Parameter {
+    /// `?`, numbered from 1 in the order of appearance in the statement
+    Anonymous(usize),
+    /// `$1`
+    Positional(usize),
+    /// `:name`
+    Named(String),
+}
+
+/// How the pattern of [`Expr::Like`] matches.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PatternKind {
+    Like,
+    /// `LIKE` which ignores case
+    ILike,
+    SimilarTo,
+    /// `~`, a regular expression which matches a part of the text
+    Regex,
+    /// `~*`
+    RegexIgnoreCase,
+}
+
+impl PatternKind {
+    /// Whether the pattern has an `ESCAPE` char.
+    pub fn escapes(&self) -> bool {
+        !matches!(self, PatternKind::Regex | PatternKind::RegexIgnoreCase)
+    }
+
+    /// The operator as it is written, e.g. `NOT LIKE` or `!~`.
+    pub fn operator(&self, negated: bool) -> &'static str {
+        match (self, negated) {
+            (PatternKind::Like, false) => "LIKE",
+            (PatternKind::Like, true) => "NOT LIKE",
+            (PatternKind::ILike, false) => "ILIKE",
+            (PatternKind::ILike, true) => "NOT ILIKE",
+            (PatternKind::SimilarTo, false) => "SIMILAR TO",
+            (PatternKind::SimilarTo, true) => "NOT SIMILAR TO",
+            (PatternKind::Regex, false) => "~",
+            (PatternKind::Regex, true) => "!~",
+            (PatternKind::RegexIgnoreCase, false) => "~*",
+            (PatternKind::RegexIgnoreCase, true) => "!~*",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOperator {
+    Not,
+    Minus,
+    Plus,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+    And,
+    Or,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+}
+
+pub(crate) const OR_PRECEDENCE: u8 = 1;
+pub(crate) const AND_PRECEDENCE: u8 = 2;
+pub(crate) const NOT_PRECEDENCE: u8 = 3;
+pub(crate) const COMPARISON_PRECEDENCE: u8 = 4;
+pub(crate) const ADDITIVE_PRECEDENCE: u8 = 5;
+pub(crate) const MULTIPLICATIVE_PRECEDENCE: u8 = 6;
+pub(crate) const UNARY_PRECEDENCE: u8 = 7;
+/// Literals, columns, function calls and anything else that is never split by operators.
+pub(crate) const ATOM_PRECEDENCE: u8 = 10;
+
+impl Operator {
+    fn from_token(token: &Token) -> Option<Operator> {
+        match token {
+            Token::Equals => Some(Operator::Equal),
+            Token::NotEquals => Some(Operator::NotEqual),
+            Token::GreaterThan => Some(Operator::GreaterThan),
+            Token::LessThan => Some(Operator::LessThan),
+            Token::GreaterThanOrEquals => Some(Operator::GreaterThanOrEqual),
+            Token::LessThanOrEquals => Some(Operator::LessThanOrEqual),
+            Token::And => Some(Operator::And),
+            Token::Or => Some(Operator::Or),
+            Token::Plus => Some(Operator::Plus),
+            Token::Minus => Some(Operator::Minus),
+            Token::Asterisk => Some(Operator::Multiply),
+            Token::Slash => Some(Operator::Divide),
+            Token::Percent => Some(Operator::Modulo),
+            Token::Concat => Some(Operator::Concat),
+            _ => None,
+        }
+    }
+
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => OR_PRECEDENCE,
+            Operator::And => AND_PRECEDENCE,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual => COMPARISON_PRECEDENCE,
+            Operator::Plus | Operator::Minus | Operator::Concat => ADDITIVE_PRECEDENCE,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => MULTIPLICATIVE_PRECEDENCE,
+        }
+    }
+}
+
+impl Expr {
+    pub fn column(column_name: &str) -> Expr {
+        Expr::Column(ColumnIdentifier {
+            schema_name: None,
+            table_name: None,
+            column_name: column_name.to_string(),
+            span: Span::default(),
+        })
+    }
+
+    pub fn number(value: &str) -> Expr {
+        Expr::Literal(Literal::Numeric(value.to_string()))
+    }
+
+    pub fn string(value: &str) -> Expr {
+        Expr::Literal(Literal::String(value.to_string()))
+    }
+
+    pub fn binary(left: Expr, operator: Operator, right: Expr) -> Expr {
+        Expr::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: OperatorSpan::default(),
+        }
+    }
+
+    pub fn parse(parser: &mut Parser) -> Result<Expr, ParseError> {
+        Self::parse_with_precedence(parser, 0)
+    }
+
+    fn parse_with_precedence(parser: &mut Parser, min_precedence: u8) -> Result<Expr, ParseError> {
+        parser.nested(|parser| {
+            let mut expr = Self::parse_prefix(parser)?;
+            loop {
+                let precedence = Self::next_precedence(parser);
+                if precedence <= min_precedence {
+                    return Ok(expr);
+                }
+                // the left operand nests one level deeper in each operation of a chain
+                parser.deepen()?;
+                expr = Self::parse_infix(parser, expr, precedence)?;
+            }
+        })
+    }
+
+    fn next_precedence(parser: &Parser) -> u8 {
+        match parser.peek() {
+            Some(
+                Token::Is
+                | Token::In
+                | Token::Between
+                | Token::Like
+                | Token::ILike
+                | Token::Similar
+                | Token::Tilde
+                | Token::TildeAsterisk
+                | Token::NotTilde
+                | Token::NotTildeAsterisk,
+            ) => COMPARISON_PRECEDENCE,
+            Some(Token::Not) => match parser.peek_nth(1) {
+                Some(Token::In | Token::Between | Token::Like | Token::ILike | Token::Similar) => {
+                    COMPARISON_PRECEDENCE
+                }
+                _ => 0,
+            },
+            Some(token) => Operator::from_token(token).map_or(0, |o| o.precedence()),
+            None => 0,
+        }
+    }
+
+    fn parse_infix(parser: &mut Parser, left: Expr, precedence: u8) -> Result<Expr, ParseError> {
+        let expr = Box::new(left);
+        if parser.consume(&Token::Is) {
+            let negated = parser.consume(&Token::Not);
+            if parser.consume(&Token::Distinct) {
+                parser.expect(Token::From)?;
+                let right = Self::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?;
+                return Ok(Expr::IsDistinctFrom {
+                    left: expr,
+                    right: Box::new(right),
+                    negated,
+                });
+            }
+            parser.expect(Token::Null)?;
+            return Ok(Expr::IsNull { expr, negated });
+        }
+        let negated = parser.consume(&Token::Not);
+        match parser.next_token() {
+            Some(Token::In) => {
+                parser.expect(Token::OpenParen)?;
+                let in_expr = if parser.peek() == Some(&Token::Select) {
+                    Expr::InSubquery {
+                        expr,
+                        subquery: Box::new(SelectQuery::parse(parser)?),
+                        negated,
+                    }
+                } else {
+                    Expr::InList {
+                        expr,
+                        list: parser.parse_comma_separated(Expr::parse)?,
+                        negated,
+                    }
+                };
+                parser.expect(Token::CloseParen)?;
+                Ok(in_expr)
+            }
+            Some(Token::Between) => {
+                let low = Self::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?;
+                parser.expect(Token::And)?;
+                let high = Self::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?;
+                Ok(Expr::Between {
+                    expr,
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                })
+            }
+            Some(token @ (Token::Like | Token::ILike | Token::Similar)) => {
+                let kind = match token {
+                    Token::Like => PatternKind::Like,
+                    Token::ILike => PatternKind::ILike,
+                    _ => {
+                        parser.expect(Token::To)?;
+                        PatternKind::SimilarTo
+                    }
+                };
+                let pattern = Self::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?;
+                let escape = match parser.consume_word("escape") {
+                    true => Some(Box::new(Self::parse_with_precedence(
+                        parser,
+                        COMPARISON_PRECEDENCE,
+                    )?)),
+                    false => None,
+                };
+                Ok(Expr::Like {
+                    expr,
+                    pattern: Box::new(pattern),
+                    escape,
+                    kind,
+                    negated,
+                })
+            }
+            Some(
+                token @ (Token::Tilde
+                | Token::TildeAsterisk
+                | Token::NotTilde
+                | Token::NotTildeAsterisk),
+            ) if !negated => {
+                let kind = match token {
+                    Token::Tilde | Token::NotTilde => PatternKind::Regex,
+                    _ => PatternKind::RegexIgnoreCase,
+                };
+                Ok(Expr::Like {
+                    expr,
+                    pattern: Box::new(Self::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?),
+                    escape: None,
+                    kind,
+                    negated: matches!(token, Token::NotTilde | Token::NotTildeAsterisk),
+                })
+            }
+            Some(token) => match Operator::from_token(&token) {
+                Some(operator) if !negated => {
+                    let span = OperatorSpan(parser.previous_span());
+                    Ok(Expr::Binary {
+                        left: expr,
+                        operator,
+                        right: Box::new(Self::parse_with_precedence(parser, precedence)?),
+                        span,
+                    })
+                }
+                _ => parser.unexpected_token(&token, "operator"),
+            },
+            None => parser.unexpected_end("operator"),
+        }
+    }
+
+    fn parse_prefix(parser: &mut Parser) -> Result<Expr, ParseError> {
+        let token = match parser.next_token() {
+            Some(token) => token,
+            None => return parser.unexpected_end("expression"),
+        };
+        let span = OperatorSpan(parser.previous_span());
+        match token {
+            Token::NumericLiteral(value) => Ok(Expr::Literal(Literal::Numeric(value))),
+            Token::StringLiteral(value) => Ok(Expr::Literal(Literal::String(value))),
+            Token::BooleanLiteral(value) => Ok(Expr::Literal(Literal::Boolean(value))),
+            Token::Null => Ok(Expr::Literal(Literal::Null)),
+            Token::Default => Ok(Expr::Default),
+            Token::QuestionMark => Ok(Expr::Parameter(Parameter::Anonymous(
+                parser.next_anonymous_parameter(),
+            ))),
+            Token::PositionalParameter(position) => {
+                Ok(Expr::Parameter(Parameter::Positional(position)))
+            }
+            Token::NamedParameter(name) => Ok(Expr::Parameter(Parameter::Named(name.to_string()))),
+            Token::Not if parser.peek() == Some(&Token::Exists) => {
+                parser.next_token();
+                Self::parse_exists(parser, true)
+            }
+            Token::Not => Ok(Expr::Unary {
+                operator: UnaryOperator::Not,
+                expr: Box::new(Self::parse_with_precedence(parser, NOT_PRECEDENCE)?),
+                span,
+            }),
+            Token::Minus => match parser.peek() {
+                // `-1` is a negative literal rather than an expression
+                Some(Token::NumericLiteral(value)) => {
+                    let literal = Literal::Numeric(format!("-{}", value));
+                    parser.next_token();
+                    Ok(Expr::Literal(literal))
+                }
+                _ => Ok(Expr::Unary {
+                    operator: UnaryOperator::Minus,
+                    expr: Box::new(Self::parse_with_precedence(parser, UNARY_PRECEDENCE)?),
+                    span,
+                }),
+            },
+            Token::Plus => Ok(Expr::Unary {
+                operator: UnaryOperator::Plus,
+                expr: Box::new(Self::parse_with_precedence(parser, UNARY_PRECEDENCE)?),
+                span,
+            }),
+            Token::Date | Token::Time | Token::Timestamp
+                if matches!(parser.peek(), Some(Token::StringLiteral(_)))
+                    || token == Token::Timestamp && parser.peek() == Some(&Token::With) =>
+            {
+                let data_type = match token {
+                    Token::Date => DataType::Date,
+                    Token::Time => DataType::Time,
+                    _ if DataType::parse_time_zone(parser)? => DataType::TimestampTz,
+                    _ => DataType::Timestamp,
+                };
+                Self::parse_typed_string(parser, data_type)
+            }
+            Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            } if first_name.eq_ignore_ascii_case("interval")
+                && matches!(parser.peek(), Some(Token::StringLiteral(_))) =>
+            {
+                Self::parse_typed_string(parser, DataType::Interval)
+            }
+            Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            } if first_name.eq_ignore_ascii_case("timestamptz")
+                && matches!(parser.peek(), Some(Token::StringLiteral(_))) =>
+            {
+                Self::parse_typed_string(parser, DataType::TimestampTz)
+            }
+            Token::Exists => Self::parse_exists(parser, false),
+            Token::Case => Self::parse_case(parser),
+            Token::OpenParen => {
+                let expr = if parser.peek() == Some(&Token::Select) {
+                    Expr::Subquery(Box::new(SelectQuery::parse(parser)?))
+                } else {
+                    let first = Expr::parse(parser)?;
+                    if parser.consume(&Token::Comma) {
+                        let mut items = vec![first];
+                        items.extend(parser.parse_comma_separated(Expr::parse)?);
+                        Expr::Tuple(items)
+                    } else {
+                        first
+                    }
+                };
+                parser.expect(Token::CloseParen)?;
+                Ok(expr)
+            }
+            // string functions which share the name with a join keyword
+            Token::Left | Token::Right if parser.peek() == Some(&Token::OpenParen) => {
+                let name = if token == Token::Left {
+                    "left"
+                } else {
+                    "right"
+                };
+                Ok(Expr::Function(Function::parse(parser, name)?))
+            }
+            Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            } if parser.peek() == Some(&Token::OpenParen) => {
+                Ok(Expr::Function(Function::parse(parser, &first_name)?))
+            }
+            Token::Identifier { .. } => Ok(Expr::Column(ColumnIdentifier::from_token(
+                &token,
+                parser.previous_span(),
+            ))),
+            token => parser.unexpected_token(&token, "expression"),
+        }
+    }
+
+    /// Parses the text of `<type> '<text>'`, the type is already consumed.
+    fn parse_typed_string(parser: &mut Parser, data_type: DataType) -> Result<Expr, ParseError> {
+        match parser.next_token() {
+            Some(Token::StringLiteral(value)) => Ok(Expr::TypedString { data_type, value }),
+            Some(token) => parser.unexpected_token(&token, "string literal"),
+            None => parser.unexpected_end("string literal"),
+        }
+    }
+
+    /// Parses `(<subquery>)` of `[not] exists`, the keywords are already consumed.
+    fn parse_exists(parser: &mut Parser, negated: bool) -> Result<Expr, ParseError> {
+        parser.expect(Token::OpenParen)?;
+        let subquery = SelectQuery::parse(parser)?;
+        parser.expect(Token::CloseParen)?;
+        Ok(Expr::Exists {
+            subquery: Box::new(subquery),
+            negated,
+        })
+    }
+
+    fn parse_case(parser: &mut Parser) -> Result<Expr, ParseError> {
+        let operand = if parser.peek() == Some(&Token::When) {
+            None
+        } else {
+            Some(Box::new(Expr::parse(parser)?))
+        };
+        let mut when_then = vec![];
+        while parser.consume(&Token::When) {
+            let condition = Expr::parse(parser)?;
+            parser.expect(Token::Then)?;
+            when_then.push((condition, Expr::parse(parser)?));
+        }
+        if when_then.is_empty() {
+            return parser.unexpected("when");
+        }
+        let else_result = if parser.consume(&Token::Else) {
+            Some(Box::new(Expr::parse(parser)?))
+        } else {
+            None
+        };
+        parser.expect(Token::End)?;
+        Ok(Expr::Case {
+            operand,
+            when_then,
+            else_result,
+        })
+    }
+}
+
+impl ColumnIdentifier {
+    /// Maps `column`, `table.column` and `schema.table.column` identifiers.
+    pub(crate) fn from_token(token: &Token, span: Span) -> ColumnIdentifier {
+        match token {
+            Token::Identifier {
+                first_name,
+                second_name: None,
+                ..
+            } => ColumnIdentifier {
+                schema_name: None,
+                table_name: None,
+                column_name: first_name.to_string(),
+                span,
+            },
+            Token::Identifier {
+                first_name,
+                second_name: Some(second_name),
+                third_name: None,
+            } => ColumnIdentifier {
+                schema_name: None,
+                table_name: Some(first_name.to_string()),
+                column_name: second_name.to_string(),
+                span,
+            },
+            Token::Identifier {
+                first_name,
+                second_name: Some(second_name),
+                third_name: Some(third_name),
+            } => ColumnIdentifier {
+                schema_name: Some(first_name.to_string()),
+                table_name: Some(second_name.to_string()),
+                column_name: third_name.to_string(),
+                span,
+            },
+            _ => unreachable!("column identifier is built only from identifier tokens"),
+        }
+    }
+}
+
+impl Function {
+    /// Parses arguments of the function, the name is already consumed.
+    fn parse(parser: &mut Parser, name: &str) -> Result<Function, ParseError> {
+        let span = parser.previous_span();
+        parser.expect(Token::OpenParen)?;
+        let mut function = Function {
+            name: name.to_lowercase(),
+            arguments: vec![],
+            distinct: false,
+            within_group: vec![],
+            over: None,
+            span,
+        };
+        if !parser.consume(&Token::CloseParen) {
+            if let Some((name, arguments)) = Self::parse_keyword_arguments(parser, &function.name)?
+            {
+                function.name = name.to_string();
+                function.arguments = arguments;
+            } else if parser.consume(&Token::Asterisk) {
+                function.arguments.push(Expr::Wildcard);
+            } else {
+                function.distinct = parser.consume(&Token::Distinct);
+                function.arguments = parser.parse_comma_separated(Expr::parse)?;
+            }
+            parser.expect(Token::CloseParen)?;
+        }
+        if parser.consume_word("within") {
+            parser.expect(Token::Group)?;
+            parser.expect(Token::OpenParen)?;
+            function.within_group = OrderByStatement::parse(parser)?.columns;
+            parser.expect(Token::CloseParen)?;
+        }
+        if parser.consume(&Token::Over) {
+            function.over = Some(Window::parse(parser)?);
+        }
+        Ok(function)
+    }
+
+    /// Parses the arguments of the functions which separate them by keywords, e.g.
+    /// `extract(year from created)`, into the name and arguments of a plain call:
+    ///
+    /// - `extract(<field> from <expr>)` calls `extract('<field>', <expr>)`
+    /// - `substring(<expr> [from <start>] [for <length>])`
+    /// - `position(<substring> in <expr>)`
+    /// - `trim([leading | trailing | both] [<characters>] from <expr>)` calls `ltrim`, `rtrim`
+    ///   or `btrim`, as does `trim(<expr>)`
+    ///
+    /// `None` if the function isn't one of them, nothing is consumed then.
+    #[allow(clippy::type_complexity)]
+    fn parse_keyword_arguments(
+        parser: &mut Parser,
+        name: &str,
+    ) -> Result<Option<(&'static str, Vec<Expr>)>, ParseError> {
+        Ok(Some(match name {
+            "extract" if parser.peek_nth(1) == Some(&Token::From) => {
+                let field = match parser.next_token() {
+                    Some(Token::Identifier {
+                        first_name,
+                        second_name: None,
+                        third_name: None,
+                    }) => first_name.to_lowercase(),
+                    Some(Token::StringLiteral(field)) => field,
+                    Some(token) => return parser.unexpected_token(&token, "field"),
+                    None => return parser.unexpected_end("field"),
+                };
+                parser.expect(Token::From)?;
+                ("extract", vec![Expr::string(&field), Expr::parse(parser)?])
+            }
+            "substring" => {
+                let mut arguments = vec![Expr::parse(parser)?];
+                if parser.consume(&Token::From) {
+                    arguments.push(Expr::parse(parser)?);
+                    if parser.consume_word("for") {
+                        arguments.push(Expr::parse(parser)?);
+                    }
+                } else if parser.consume_word("for") {
+                    arguments.push(Expr::number("1"));
+                    arguments.push(Expr::parse(parser)?);
+                } else {
+                    while parser.consume(&Token::Comma) {
+                        arguments.push(Expr::parse(parser)?);
+                    }
+                }
+                ("substring", arguments)
+            }
+            "position" => {
+                let substring = Expr::parse_with_precedence(parser, COMPARISON_PRECEDENCE)?;
+                let text = if parser.consume(&Token::In) || parser.consume(&Token::Comma) {
+                    Expr::parse(parser)?
+                } else {
+                    return parser.unexpected("in");
+                };
+                ("position", vec![substring, text])
+            }
+            "trim" => {
+                let name = if parser.consume_word("leading") {
+                    "ltrim"
+                } else if parser.consume_word("trailing") {
+                    "rtrim"
+                } else {
+                    parser.consume_word("both");
+                    "btrim"
+                };
+                let arguments = if parser.consume(&Token::From) {
+                    vec![Expr::parse(parser)?]
+                } else {
+                    let first = Expr::parse(parser)?;
+                    if parser.consume(&Token::From) {
+                        vec![Expr::parse(parser)?, first]
+                    } else if parser.consume(&Token::Comma) {
+                        vec![first, Expr::parse(parser)?]
+                    } else {
+                        vec![first]
+                    }
+                };
+                (name, arguments)
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+impl Window {
+    fn parse(parser: &mut Parser) -> Result<Window, ParseError> {
+        parser.expect(Token::OpenParen)?;
+        let mut window = Window::default();
+        if parser.consume(&Token::Partition) {
+            parser.expect(Token::By)?;
+            window.partition_by = parser.parse_comma_separated(Expr::parse)?;
+        }
+        if parser.peek() == Some(&Token::Order) {
+            window.order_by = OrderByStatement::parse(parser)?.columns;
+        }
+        let units = if parser.consume_word("rows") {
+            Some(FrameUnits::Rows)
+        } else if parser.consume_word("range") {
+            Some(FrameUnits::Range)
+        } else {
+            None
+        };
+        if let Some(units) = units {
+            let (start, end) = if parser.consume(&Token::Between) {
+                let start = FrameBound::parse(parser, true)?;
+                parser.expect(Token::And)?;
+                (start, FrameBound::parse(parser, false)?)
+            } else {
+                (FrameBound::parse(parser, true)?, FrameBound::CurrentRow)
+            };
+            window.frame = Some(Box::new(WindowFrame { units, start, end }));
+        }
+        parser.expect(Token::CloseParen)?;
+        Ok(window)
+    }
+}
+
+impl FrameBound {
+    /// Parses the start or the end of a frame, a frame starts with `UNBOUNDED PRECEDING`
+    /// and ends with `UNBOUNDED FOLLOWING`.
+    fn parse(parser: &mut Parser, start: bool) -> Result<FrameBound, ParseError> {
+        if parser.consume_word("unbounded") {
+            return match start {
+                true if parser.consume_word("preceding") => Ok(FrameBound::UnboundedPreceding),
+                false if parser.consume_word("following") => Ok(FrameBound::UnboundedFollowing),
+                true => parser.unexpected("PRECEDING"),
+                false => parser.unexpected("FOLLOWING"),
+            };
+        }
+        if parser.consume_word("current") {
+            return match parser.consume_word("row") {
+                true => Ok(FrameBound::CurrentRow),
+                false => parser.unexpected("ROW"),
+            };
+        }
+        let offset = Box::new(Expr::parse(parser)?);
+        if parser.consume_word("preceding") {
+            Ok(FrameBound::Preceding(offset))
+        } else if parser.consume_word("following") {
+            Ok(FrameBound::Following(offset))
+        } else {
+            parser.unexpected("PRECEDING or FOLLOWING")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Order;
+
+    fn parse(input: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser::new(input)?;
+        let expr = Expr::parse(&mut parser)?;
+        if !parser.is_finished() {
+            return parser.unexpected("end of input");
+        }
+        Ok(expr)
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse("1"), Ok(Expr::number("1")));
+        assert_eq!(parse("-1.5"), Ok(Expr::number("-1.5")));
+        assert_eq!(parse("'a b'"), Ok(Expr::string("a b")));
+        assert_eq!(parse("'it''s'"), Ok(Expr::string("it's")));
+        assert_eq!(parse("true"), Ok(Expr::Literal(Literal::Boolean(true))));
+        assert_eq!(parse("null"), Ok(Expr::Literal(Literal::Null)));
+    }
+
+    #[test]
+    fn qualified_columns() {
+        assert_eq!(
+            parse("public.users.age"),
+            Ok(Expr::Column(ColumnIdentifier {
+                schema_name: Some("public".to_string()),
+                table_name: Some("users".to_string()),
+                column_name: "age".to_string(),
+                span: Span::default(),
+            }))
+        );
+        assert_eq!(
+            parse("users.age"),
+            Ok(Expr::Column(ColumnIdentifier {
+                schema_name: None,
+                table_name: Some("users".to_string()),
+                column_name: "age".to_string(),
+                span: Span::default(),
+            }))
+        );
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        let expected = Expr::binary(
+            Expr::number("1"),
+            Operator::Plus,
+            Expr::binary(Expr::number("2"), Operator::Multiply, Expr::number("3")),
+        );
+        assert_eq!(parse("1 + 2 * 3"), Ok(expected));
+
+        let expected = Expr::binary(
+            Expr::binary(Expr::number("1"), Operator::Plus, Expr::number("2")),
+            Operator::Multiply,
+            Expr::number("3"),
+        );
+        assert_eq!(parse("(1 + 2) * 3"), Ok(expected));
+    }
+
+    #[test]
+    fn subtraction_is_left_associative() {
+        let expected = Expr::binary(
+            Expr::binary(Expr::column("a"), Operator::Minus, Expr::number("1")),
+            Operator::Minus,
+            Expr::number("2"),
+        );
+        assert_eq!(parse("a - 1 - 2"), Ok(expected));
+    }
+
+    #[test]
+    fn logical_precedence() {
+        let expected = Expr::binary(
+            Expr::binary(Expr::column("a"), Operator::Equal, Expr::number("1")),
+            Operator::Or,
+            Expr::binary(
+                Expr::Unary {
+                    operator: UnaryOperator::Not,
+                    expr: Box::new(Expr::column("b")),
+                    span: OperatorSpan::default(),
+                },
+                Operator::And,
+                Expr::binary(
+                    Expr::column("c"),
+                    Operator::LessThanOrEqual,
+                    Expr::number("2"),
+                ),
+            ),
+        );
+        assert_eq!(parse("a = 1 or not b and c <= 2"), Ok(expected));
+    }
+
+    #[test]
+    fn between_and_in() {
+        let expected = Expr::binary(
+            Expr::Between {
+                expr: Box::new(Expr::column("a")),
+                low: Box::new(Expr::number("1")),
+                high: Box::new(Expr::number("2")),
+                negated: false,
+            },
+            Operator::And,
+            Expr::InList {
+                expr: Box::new(Expr::column("b")),
+                list: vec![Expr::string("x"), Expr::string("y")],
+                negated: true,
+            },
+        );
+        assert_eq!(
+            parse("a between 1 and 2 and b not in ('x', 'y')"),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn is_null_and_like() {
+        assert_eq!(
+            parse("name is not null"),
+            Ok(Expr::IsNull {
+                expr: Box::new(Expr::column("name")),
+                negated: true,
+            })
+        );
+        assert_eq!(
+            parse("a is not distinct from b + 1"),
+            Ok(Expr::IsDistinctFrom {
+                left: Box::new(Expr::column("a")),
+                right: Box::new(Expr::Binary {
+                    left: Box::new(Expr::column("b")),
+                    operator: Operator::Plus,
+                    right: Box::new(Expr::number("1")),
+                    span: OperatorSpan::default(),
+                }),
+                negated: true,
+            })
+        );
+        assert_eq!(
+            parse("name like 'a%'"),
+            Ok(Expr::Like {
+                expr: Box::new(Expr::column("name")),
+                pattern: Box::new(Expr::string("a%")),
+                escape: None,
+                kind: PatternKind::Like,
+                negated: false,
+            })
+        );
+        assert_eq!(
+            parse("name not similar to 'a|b' escape '#' and name !~* 'x'"),
+            Ok(Expr::binary(
+                Expr::Like {
+                    expr: Box::new(Expr::column("name")),
+                    pattern: Box::new(Expr::string("a|b")),
+                    escape: Some(Box::new(Expr::string("#"))),
+                    kind: PatternKind::SimilarTo,
+                    negated: true,
+                },
+                Operator::And,
+                Expr::Like {
+                    expr: Box::new(Expr::column("name")),
+                    pattern: Box::new(Expr::string("x")),
+                    escape: None,
+                    kind: PatternKind::RegexIgnoreCase,
+                    negated: true,
+                },
+            ))
+        );
+        assert!(parse("name not ~ 'x'").is_err());
+        assert!(parse("name similar 'x'").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            parse("count(*)"),
+            Ok(Expr::Function(Function {
+                name: "count".to_string(),
+                arguments: vec![Expr::Wildcard],
+                distinct: false,
+                within_group: vec![],
+                over: None,
+                span: Span::default(),
+            }))
+        );
+        assert_eq!(
+            parse("COUNT(DISTINCT a)"),
+            Ok(Expr::Function(Function {
+                name: "count".to_string(),
+                arguments: vec![Expr::column("a")],
+                distinct: true,
+                within_group: vec![],
+                over: None,
+                span: Span::default(),
+            }))
+        );
+        assert_eq!(
+            parse("left(name, 2)"),
+            Ok(Expr::Function(Function {
+                name: "left".to_string(),
+                arguments: vec![Expr::column("name"), Expr::number("2")],
+                distinct: false,
+                within_group: vec![],
+                over: None,
+                span: Span::default(),
+            }))
+        );
+        assert_eq!(
+            parse("rank() over (partition by a, b order by c desc)"),
+            Ok(Expr::Function(Function {
+                name: "rank".to_string(),
+                arguments: vec![],
+                distinct: false,
+                within_group: vec![],
+                over: Some(Window {
+                    partition_by: vec![Expr::column("a"), Expr::column("b")],
+                    order_by: vec![OrderByColumn {
+                        expr: Expr::column("c"),
+                        order: Order::DESC,
+                    }],
+                    frame: None,
+                }),
+                span: Span::default(),
+            }))
+        );
+        assert!(parse("sum(x) over ()").is_ok());
+        assert!(parse("sum(x) over (order by)").is_err());
+    }
+
+    #[test]
+    fn ordered_sets_and_frames() {
+        let Ok(Expr::Function(function)) =
+            parse("percentile_cont(0.5) within group (order by total desc)")
+        else {
+            panic!("expected a function");
+        };
+        assert_eq!(function.arguments, [Expr::number("0.5")]);
+        assert_eq!(
+            function.within_group,
+            [OrderByColumn {
+                expr: Expr::column("total"),
+                order: Order::DESC,
+            }]
+        );
+
+        let frame = |input: &str| match parse(input) {
+            Ok(Expr::Function(Function {
+                over: Some(Window { frame, .. }),
+                ..
+            })) => frame.map(|frame| *frame),
+            result => panic!("expected a window function, got {:?}", result),
+        };
+        assert_eq!(
+            frame("sum(x) over (order by y rows between 2 preceding and unbounded following)"),
+            Some(WindowFrame {
+                units: FrameUnits::Rows,
+                start: FrameBound::Preceding(Box::new(Expr::number("2"))),
+                end: FrameBound::UnboundedFollowing,
+            })
+        );
+        assert_eq!(
+            frame("sum(x) over (range current row)"),
+            Some(WindowFrame {
+                units: FrameUnits::Range,
+                start: FrameBound::CurrentRow,
+                end: FrameBound::CurrentRow,
+            })
+        );
+        assert!(parse("sum(x) over (rows unbounded following)").is_err());
+        assert!(parse("sum(x) over (rows between current row and unbounded preceding)").is_err());
+        assert!(parse("sum(x) over (rows 1)").is_err());
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        let same = |special: &str, plain: &str| assert_eq!(parse(special), parse(plain));
+        same("extract(YEAR from created)", "extract('year', created)");
+        same("extract('epoch' from a - b)", "extract('epoch', a - b)");
+        same("substring(name from 2 for 3)", "substring(name, 2, 3)");
+        same("substring(name from 2)", "substring(name, 2)");
+        same("substring(name for 3)", "substring(name, 1, 3)");
+        same("position('b' || c in name)", "position('b' || c, name)");
+        same("trim(name)", "btrim(name)");
+        same("trim(both from name)", "btrim(name)");
+        same("trim(leading 'x' from name)", "ltrim(name, 'x')");
+        same("trim(trailing from name)", "rtrim(name)");
+        assert!(parse("position('a')").is_err());
+        assert!(parse("extract(year created)").is_err());
+    }
+
+    #[test]
+    fn typed_strings() {
+        assert_eq!(
+            parse("date '2024-03-01'"),
+            Ok(Expr::TypedString {
+                data_type: DataType::Date,
+                value: "2024-03-01".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("now() - INTERVAL '1 day'"),
+            Ok(Expr::binary(
+                parse("now()").unwrap(),
+                Operator::Minus,
+                Expr::TypedString {
+                    data_type: DataType::Interval,
+                    value: "1 day".to_string(),
+                }
+            ))
+        );
+        assert_eq!(parse("interval"), Ok(Expr::column("interval")));
+    }
+
+    #[test]
+    fn tuples() {
+        assert_eq!(
+            parse("(a, 1) in ((b, 2))"),
+            Ok(Expr::InList {
+                expr: Box::new(Expr::Tuple(vec![Expr::column("a"), Expr::number("1")])),
+                list: vec![Expr::Tuple(vec![Expr::column("b"), Expr::number("2")])],
+                negated: false,
+            })
+        );
+    }
+
+    #[test]
+    fn case_expression() {
+        assert_eq!(
+            parse("case when a > 0 then 'positive' else 'other' end"),
+            Ok(Expr::Case {
+                operand: None,
+                when_then: vec![(
+                    Expr::binary(Expr::column("a"), Operator::GreaterThan, Expr::number("0")),
+                    Expr::string("positive"),
+                )],
+                else_result: Some(Box::new(Expr::string("other"))),
+            })
+        );
+    }
+
+    #[test]
+    fn unexpected_token() {
+        assert_eq!(
+            parse("1 +"),
+            Err(ParseError::UnexpectedEnd {
+                expected: "expression".to_string()
+            })
+        );
+        assert!(parse("a not = 1").is_err());
+    }
+}
@@ -0,0 +1,548 @@
+//! Lays out SQL scripts such as migration files. A statement which does not fit the line
+//! width is broken into one clause per line, and a clause which still does not fit gets one
+//! item per line. Comments are kept: the ones written inside a statement are moved above it.
+
+use crate::parser::display::{operand, Identifier};
+use crate::parser::expr::{Expr, Operator, AND_PRECEDENCE};
+use crate::parser::lexer::{Lexer, Token};
+use crate::parser::*;
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KeywordCase {
+    Upper,
+    Lower,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CommaStyle {
+    /// `a,` at the end of the line
+    Trailing,
+    /// `, a` at the start of the line
+    Leading,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FormatOptions {
+    pub keyword_case: KeywordCase,
+    /// Number of spaces per indentation level.
+    pub indent: usize,
+    pub line_width: usize,
+    pub comma_style: CommaStyle,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            keyword_case: KeywordCase::Upper,
+            indent: 4,
+            line_width: 80,
+            comma_style: CommaStyle::Trailing,
+        }
+    }
+}
+
+/// Formats a whole script, every statement is terminated by `;`.
+pub fn format(input: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    let mut lines = vec![];
+    for (i, statement) in split_statements(input)?.into_iter().enumerate() {
+        if i > 0 && statement.blank_line_before {
+            lines.push(String::new());
+        }
+        lines.extend(statement.comments);
+        if let Some(query) = statement.query {
+            let mut text = format_query(&query, options);
+            text.push(';');
+            if let Some(comment) = statement.trailing_comment {
+                text.push(' ');
+                text.push_str(&comment);
+            }
+            lines.push(text);
+        }
+    }
+    if lines.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Formats a single statement without the terminating `;`.
+pub fn format_query(query: &Query, options: &FormatOptions) -> String {
+    let single_line = query.to_string();
+    let text = if single_line.chars().count() < options.line_width {
+        single_line
+    } else {
+        let mut layout = Layout {
+            options,
+            lines: vec![],
+        };
+        layout.query(query, 0);
+        layout.lines.join("\n")
+    };
+    match options.keyword_case {
+        KeywordCase::Upper => text,
+        KeywordCase::Lower => lowercase_keywords(&text),
+    }
+}
+
+/// Keywords are the alphabetic tokens which are neither identifiers nor string literals.
+fn lowercase_keywords(text: &str) -> String {
+    let tokens = match Lexer::new(text).tokenize_with_spans() {
+        Ok(tokens) => tokens,
+        Err(_) => return text.to_string(),
+    };
+    let mut result = text.to_string();
+    for (token, span) in tokens {
+        let word = &text[span.start..span.end];
+        let is_keyword = !matches!(token, Token::Identifier { .. } | Token::StringLiteral(_))
+            && word.chars().all(|c| c.is_ascii_alphabetic());
+        if is_keyword {
+            result.replace_range(span.start..span.end, &word.to_ascii_lowercase());
+        }
+    }
+    result
+}
+
+#[derive(Default)]
+struct Statement {
+    query: Option<Query>,
+    /// Comments written before or inside the statement, one entry per line.
+    comments: Vec<String>,
+    /// Comment on the same line after the `;`.
+    trailing_comment: Option<String>,
+    blank_line_before: bool,
+}
+
+/// Splits the script on `;` and sorts out which statement every comment belongs to.
+fn split_statements(input: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut statements: Vec<Statement> = vec![];
+    let mut current = Statement::default();
+    let mut start = None;
+    let mut previous_end = 0;
+    let mut after_semicolon = false;
+    for (token, span) in Lexer::new(input).tokenize_with_spans()? {
+        let gap = &input[previous_end..span.start];
+        let is_first = start.is_none() && current.comments.is_empty();
+        match token {
+            Token::SingleLineComment(_) | Token::MultiLineComment(_) => {
+                let comment = input[span.start..span.end].trim_end().to_string();
+                let previous = statements.last_mut().filter(|previous| {
+                    after_semicolon && !gap.contains('\n') && previous.trailing_comment.is_none()
+                });
+                match previous {
+                    Some(previous) if is_first => previous.trailing_comment = Some(comment),
+                    _ => {
+                        if is_first {
+                            current.blank_line_before = gap.matches('\n').count() > 1;
+                        }
+                        current.comments.extend(comment.lines().map(str::to_string));
+                    }
+                }
+            }
+            Token::Semicolon => {
+                if let Some(start) = start.take() {
+                    current.query = Some(parse_statement(&input[start..span.start])?);
+                    statements.push(std::mem::take(&mut current));
+                }
+            }
+            _ => {
+                if is_first {
+                    current.blank_line_before = gap.matches('\n').count() > 1;
+                }
+                start.get_or_insert(span.start);
+            }
+        }
+        after_semicolon = token == Token::Semicolon;
+        previous_end = span.end;
+    }
+    if let Some(start) = start {
+        current.query = Some(parse_statement(&input[start..])?);
+    }
+    if current.query.is_some() || !current.comments.is_empty() {
+        statements.push(current);
+    }
+    Ok(statements)
+}
+
+fn parse_statement(input: &str) -> Result<Query, ParseError> {
+    let mut parser = Parser::new(input)?;
+    let query = Query::parse(&mut parser)?;
+    match parser.peek() {
+        Some(_) => parser.unexpected(";"),
+        None => Ok(query),
+    }
+}
+
+enum Separator {
+    Comma,
+    And,
+}
+
+struct Layout<'a> {
+    options: &'a FormatOptions,
+    lines: Vec<String>,
+}
+
+impl Layout<'_> {
+    fn query(&mut self, query: &Query, depth: usize) {
+        match query {
+            Query::Select(query) => self.select(query, depth),
+            Query::Insert(query) => self.insert(query, depth),
+            Query::Update(query) => self.update(query, depth),
+            Query::Delete(query) => self.delete(query, depth),
+            Query::Explain(explain) => {
+                let keyword = if explain.analyze {
+                    "EXPLAIN ANALYZE"
+                } else {
+                    "EXPLAIN"
+                };
+                self.clause(depth, keyword, &[], Separator::Comma);
+                self.query(&explain.query, depth);
+            }
+            Query::Prepare(prepare) => {
+                let mut keyword = format!("PREPARE {}", Identifier(&prepare.name));
+                if !prepare.parameter_types.is_empty() {
+                    keyword.push_str(&format!(
+                        " ({})",
+                        strings(&prepare.parameter_types).join(", ")
+                    ));
+                }
+                keyword.push_str(" AS");
+                self.clause(depth, &keyword, &[], Separator::Comma);
+                self.query(&prepare.statement, depth + 1);
+            }
+            query => self.clause(depth, &query.to_string(), &[], Separator::Comma),
+        }
+    }
+
+    fn select(&mut self, query: &SelectQuery, depth: usize) {
+        let keyword = if query.select_statement.distinct {
+            "SELECT DISTINCT"
+        } else {
+            "SELECT"
+        };
+        let columns = strings(&query.select_statement.columns);
+        self.clause(depth, keyword, &columns, Separator::Comma);
+        if let Some(from_statement) = &query.from_statement {
+            self.from(depth, "FROM", from_statement);
+        }
+        if let Some(where_statement) = &query.where_statement {
+            self.condition(depth, "WHERE", &where_statement.condition);
+        }
+        if let Some(group_by_statement) = &query.group_by_statement {
+            let columns = strings(&group_by_statement.columns);
+            self.clause(depth, "GROUP BY", &columns, Separator::Comma);
+        }
+        if let Some(having_statement) = &query.having_statement {
+            self.condition(depth, "HAVING", &having_statement.condition);
+        }
+        for union in &query.unions {
+            let keyword = if union.all { "UNION ALL" } else { "UNION" };
+            self.clause(depth, keyword, &[], Separator::Comma);
+            self.select(&union.query, depth);
+        }
+        if let Some(order_by_statement) = &query.order_by_statement {
+            let columns = strings(&order_by_statement.columns);
+            self.clause(depth, "ORDER BY", &columns, Separator::Comma);
+        }
+        if let Some(limit_statement) = &query.limit_statement {
+            self.clause(depth, &limit_statement.to_string(), &[], Separator::Comma);
+        }
+    }
+
+    fn insert(&mut self, query: &InsertQuery, depth: usize) {
+        let mut keyword = format!("INSERT INTO {}", query.table);
+        if !query.columns.is_empty() {
+            keyword.push_str(&format!(" ({})", identifiers(&query.columns)));
+        }
+        self.clause(depth, &keyword, &[], Separator::Comma);
+        match &query.source {
+            InsertSource::Values(rows) => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| format!("({})", strings(row).join(", ")))
+                    .collect();
+                self.clause(depth, "VALUES", &rows, Separator::Comma);
+            }
+            InsertSource::Select(select) => self.select(select, depth),
+            InsertSource::DefaultValues => {
+                self.clause(depth, "DEFAULT VALUES", &[], Separator::Comma)
+            }
+        }
+        if let Some(on_conflict) = &query.on_conflict {
+            let mut keyword = "ON CONFLICT".to_string();
+            if !on_conflict.columns.is_empty() {
+                keyword.push_str(&format!(" ({})", identifiers(&on_conflict.columns)));
+            }
+            match &on_conflict.action {
+                ConflictAction::DoNothing => {
+                    keyword.push_str(" DO NOTHING");
+                    self.clause(depth, &keyword, &[], Separator::Comma);
+                }
+                ConflictAction::DoUpdate {
+                    set_statement,
+                    where_statement,
+                } => {
+                    keyword.push_str(" DO UPDATE");
+                    self.clause(depth, &keyword, &[], Separator::Comma);
+                    self.set(depth + 1, set_statement);
+                    if let Some(where_statement) = where_statement {
+                        self.condition(depth + 1, "WHERE", &where_statement.condition);
+                    }
+                }
+            }
+        }
+        self.returning(depth, &query.returning);
+    }
+
+    fn update(&mut self, query: &UpdateQuery, depth: usize) {
+        let keyword = format!("UPDATE {}", query.table);
+        self.clause(depth, &keyword, &[], Separator::Comma);
+        self.set(depth, &query.set_statement);
+        if let Some(from_statement) = &query.from_statement {
+            self.from(depth, "FROM", from_statement);
+        }
+        if let Some(where_statement) = &query.where_statement {
+            self.condition(depth, "WHERE", &where_statement.condition);
+        }
+        self.returning(depth, &query.returning);
+    }
+
+    fn delete(&mut self, query: &DeleteQuery, depth: usize) {
+        let keyword = format!("DELETE FROM {}", query.table);
+        self.clause(depth, &keyword, &[], Separator::Comma);
+        if let Some(using_statement) = &query.using_statement {
+            self.from(depth, "USING", using_statement);
+        }
+        if let Some(where_statement) = &query.where_statement {
+            self.condition(depth, "WHERE", &where_statement.condition);
+        }
+        self.returning(depth, &query.returning);
+    }
+
+    fn from(&mut self, depth: usize, keyword: &str, from_statement: &FromStatement) {
+        let tables = strings(&from_statement.tables);
+        self.clause(depth, keyword, &tables, Separator::Comma);
+        for join in &from_statement.joins {
+            let keyword = format!("{} {}", join.join_type, join.table);
+            let on: Vec<String> = join.on.iter().map(|on| format!("ON {}", on)).collect();
+            self.clause(depth, &keyword, &on, Separator::Comma);
+        }
+    }
+
+    fn set(&mut self, depth: usize, set_statement: &SetStatement) {
+        let assignments = strings(&set_statement.assignments);
+        self.clause(depth, "SET", &assignments, Separator::Comma);
+    }
+
+    fn returning(&mut self, depth: usize, returning: &[ColumnStatement]) {
+        if !returning.is_empty() {
+            self.clause(depth, "RETURNING", &strings(returning), Separator::Comma);
+        }
+    }
+
+    /// A condition is split on its top level `AND`s.
+    fn condition(&mut self, depth: usize, keyword: &str, condition: &Expr) {
+        let mut conjuncts = vec![];
+        match condition {
+            Expr::Binary {
+                operator: Operator::And,
+                ..
+            } => collect_conjuncts(condition, &mut conjuncts),
+            condition => conjuncts.push(condition.to_string()),
+        }
+        self.clause(depth, keyword, &conjuncts, Separator::And);
+    }
+
+    fn clause(&mut self, depth: usize, keyword: &str, items: &[String], separator: Separator) {
+        let indent = " ".repeat(self.options.indent * depth);
+        let joined = match separator {
+            Separator::Comma => items.join(", "),
+            Separator::And => items.join(" AND "),
+        };
+        let line = if items.is_empty() {
+            format!("{}{}", indent, keyword)
+        } else {
+            format!("{}{} {}", indent, keyword, joined)
+        };
+        if items.is_empty() || line.chars().count() <= self.options.line_width {
+            self.lines.push(line);
+            return;
+        }
+        self.lines.push(format!("{}{}", indent, keyword));
+        let indent = " ".repeat(self.options.indent * (depth + 1));
+        for (i, item) in items.iter().enumerate() {
+            let line = match separator {
+                Separator::And if i > 0 => format!("{}AND {}", indent, item),
+                Separator::Comma if i > 0 && self.options.comma_style == CommaStyle::Leading => {
+                    format!("{}, {}", indent, item)
+                }
+                Separator::Comma
+                    if i + 1 < items.len() && self.options.comma_style == CommaStyle::Trailing =>
+                {
+                    format!("{}{},", indent, item)
+                }
+                _ => format!("{}{}", indent, item),
+            };
+            self.lines.push(line);
+        }
+    }
+}
+
+/// `a AND b AND c` is `(a AND b) AND c`, so only the left operand is split further.
+fn collect_conjuncts(expr: &Expr, conjuncts: &mut Vec<String>) {
+    match expr {
+        Expr::Binary {
+            left,
+            operator: Operator::And,
+            right,
+            ..
+        } => {
+            collect_conjuncts(left, conjuncts);
+            conjuncts.push(operand(right, AND_PRECEDENCE + 1));
+        }
+        expr => conjuncts.push(operand(expr, AND_PRECEDENCE)),
+    }
+}
+
+fn strings<T: Display>(items: &[T]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+fn identifiers(names: &[String]) -> String {
+    let names: Vec<String> = names
+        .iter()
+        .map(|name| Identifier(name).to_string())
+        .collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::display::tests::query;
+    use proptest::prelude::*;
+
+    fn assert_formats(input: &str, options: &FormatOptions, expected: &str) {
+        let formatted = format(input, options).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(Queries::parse(&formatted), Queries::parse(input));
+        assert_eq!(format(&formatted, options).unwrap(), formatted);
+    }
+
+    fn narrow() -> FormatOptions {
+        FormatOptions {
+            line_width: 40,
+            ..FormatOptions::default()
+        }
+    }
+
+    #[test]
+    fn keeps_short_statements_on_one_line() {
+        assert_formats(
+            "select a from t where b = 1;\ninsert into t values (1)",
+            &FormatOptions::default(),
+            "SELECT a FROM t WHERE b = 1;\nINSERT INTO t VALUES (1);\n",
+        );
+    }
+
+    #[test]
+    fn breaks_long_statements_into_clauses() {
+        assert_formats(
+            "select u.name, count(*) as orders_count from users u \
+             left join orders o on o.user_id = u.id \
+             where u.active and o.total > 100 or u.admin and u.id <> 1 \
+             group by u.name order by orders_count desc limit 10",
+            &narrow(),
+            "SELECT u.name, count(*) AS orders_count\nFROM users AS u\n\
+             LEFT JOIN orders AS o\n    ON o.user_id = u.id\n\
+             WHERE\n    u.active AND o.total > 100 OR u.admin AND u.id <> 1\n\
+             GROUP BY u.name\nORDER BY orders_count DESC\nLIMIT 10;\n",
+        );
+    }
+
+    #[test]
+    fn splits_conditions_on_and() {
+        assert_formats(
+            "delete from sessions where expires_at < now() and user_id in (1, 2, 3) \
+             and (revoked or stale)",
+            &narrow(),
+            "DELETE FROM sessions\nWHERE\n    expires_at < now()\n    AND user_id IN (1, 2, 3)\n\
+             \x20   AND (revoked OR stale);\n",
+        );
+    }
+
+    #[test]
+    fn applies_keyword_case_indent_and_comma_style() {
+        let options = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            indent: 2,
+            line_width: 30,
+            comma_style: CommaStyle::Leading,
+        };
+        assert_formats(
+            "UPDATE \"user list\" SET name = 'NULL AND', \"Select\" = TRUE, age = age + 1 \
+             WHERE id = 1 RETURNING id",
+            &options,
+            "update \"user list\"\nset\n  name = 'NULL AND'\n  , \"Select\" = true\n  , age = age + 1\n\
+             where id = 1\nreturning id;\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        assert_formats(
+            "-- create the users\n\
+             insert into users (id, name) values (1, 'a'); -- first user\n\n\
+             /* cleanup */\n\
+             delete from users where id = 1 -- inline\n  and name = 'a';\n\
+             -- the end\n",
+            &FormatOptions::default(),
+            "-- create the users\n\
+             INSERT INTO users (id, name) VALUES (1, 'a'); -- first user\n\n\
+             /* cleanup */\n\
+             -- inline\n\
+             DELETE FROM users WHERE id = 1 AND name = 'a';\n\
+             -- the end\n",
+        );
+    }
+
+    #[test]
+    fn reports_invalid_statements() {
+        assert!(format("select a from;", &FormatOptions::default()).is_err());
+        assert!(format("select 'a", &FormatOptions::default()).is_err());
+    }
+
+    fn options() -> impl Strategy<Value = FormatOptions> {
+        (any::<bool>(), 0..8usize, 10..100usize, any::<bool>()).prop_map(
+            |(lower, indent, line_width, leading)| FormatOptions {
+                keyword_case: if lower {
+                    KeywordCase::Lower
+                } else {
+                    KeywordCase::Upper
+                },
+                indent,
+                line_width,
+                comma_style: if leading {
+                    CommaStyle::Leading
+                } else {
+                    CommaStyle::Trailing
+                },
+            },
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn formatted_queries_parse_back(
+            queries in prop::collection::vec(query(), 1..3),
+            options in options(),
+        ) {
+            let queries = Queries { queries };
+            let formatted = format(&queries.to_string(), &options).unwrap();
+            prop_assert_eq!(Queries::parse(&formatted), Ok(queries), "formatted: {}", formatted);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use lazy_static::lazy_static;
 
@@ -25,12 +26,20 @@ lazy_static! {
         m.insert("column", Token::Column);
         m.insert("constraint", Token::Constraint);
         m.insert("index", Token::Index);
+        m.insert("unique", Token::Unique);
+        m.insert("view", Token::View);
+        m.insert("sequence", Token::Sequence);
+        m.insert("schema", Token::Schema);
+        m.insert("increment", Token::Increment);
+        m.insert("with", Token::With);
+        m.insert("if", Token::If);
         m.insert("join", Token::Join);
         m.insert("inner", Token::Inner);
         m.insert("left", Token::Left);
         m.insert("right", Token::Right);
         m.insert("full", Token::Full);
         m.insert("outer", Token::Outer);
+        m.insert("cross", Token::Cross);
         m.insert("on", Token::On);
         m.insert("group", Token::Group);
         m.insert("by", Token::By);
@@ -52,6 +61,8 @@ lazy_static! {
         m.insert("in", Token::In);
         m.insert("between", Token::Between);
         m.insert("like", Token::Like);
+        m.insert("ilike", Token::ILike);
+        m.insert("similar", Token::Similar);
         m.insert("exists", Token::Exists);
         m.insert("any", Token::Any);
         m.insert("case", Token::Case);
@@ -60,6 +71,36 @@ lazy_static! {
         m.insert("else", Token::Else);
         m.insert("end", Token::End);
         m.insert("default", Token::Default);
+        m.insert("returning", Token::Returning);
+        m.insert("conflict", Token::Conflict);
+        m.insert("do", Token::Do);
+        m.insert("nothing", Token::Nothing);
+        m.insert("using", Token::Using);
+        m.insert("begin", Token::Begin);
+        m.insert("start", Token::Start);
+        m.insert("transaction", Token::Transaction);
+        m.insert("commit", Token::Commit);
+        m.insert("rollback", Token::Rollback);
+        m.insert("savepoint", Token::Savepoint);
+        m.insert("release", Token::Release);
+        m.insert("to", Token::To);
+        m.insert("isolation", Token::Isolation);
+        m.insert("level", Token::Level);
+        m.insert("read", Token::Read);
+        m.insert("write", Token::Write);
+        m.insert("only", Token::Only);
+        m.insert("committed", Token::Committed);
+        m.insert("uncommitted", Token::Uncommitted);
+        m.insert("repeatable", Token::Repeatable);
+        m.insert("serializable", Token::Serializable);
+        m.insert("show", Token::Show);
+        m.insert("explain", Token::Explain);
+        m.insert("analyze", Token::Analyze);
+        m.insert("prepare", Token::Prepare);
+        m.insert("execute", Token::Execute);
+        m.insert("deallocate", Token::Deallocate);
+        m.insert("over", Token::Over);
+        m.insert("partition", Token::Partition);
         m.insert("true", Token::BooleanLiteral(true));
         m.insert("false", Token::BooleanLiteral(false));
         m.insert("int", Token::Int);
@@ -84,10 +125,15 @@ lazy_static! {
     };
 }
 
+/// Returns true if the word is reserved, such words have to be quoted to be used as names.
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains_key(word.to_lowercase().as_str())
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum LexError {
     InvalidCharacter(char),
+    UnexpectedEndOfInput,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -114,12 +160,20 @@ pub enum Token<'a> {
     Column,
     Constraint,
     Index,
+    Unique,
+    View,
+    Sequence,
+    Schema,
+    Increment,
+    With,
+    If,
     Join,
     Inner,
     Left,
     Right,
     Full,
     Outer,
+    Cross,
     On,
     Group,
     By,
@@ -141,6 +195,8 @@ pub enum Token<'a> {
     In,
     Between,
     Like,
+    ILike,
+    Similar,
     Exists,
     Any,
     Case,
@@ -149,6 +205,36 @@ pub enum Token<'a> {
     Else,
     End,
     Default,
+    Returning,
+    Conflict,
+    Do,
+    Nothing,
+    Using,
+    Begin,
+    Start,
+    Transaction,
+    Commit,
+    Rollback,
+    Savepoint,
+    Release,
+    To,
+    Isolation,
+    Level,
+    Read,
+    Write,
+    Only,
+    Committed,
+    Uncommitted,
+    Repeatable,
+    Serializable,
+    Show,
+    Explain,
+    Analyze,
+    Prepare,
+    Execute,
+    Deallocate,
+    Over,
+    Partition,
     // Data Types
     Int,
     Integer,
@@ -185,73 +271,100 @@ pub enum Token<'a> {
     Slash,
     Percent,
     Concat,
+    /// `~`
+    Tilde,
+    /// `~*`
+    TildeAsterisk,
+    /// `!~`
+    NotTilde,
+    /// `!~*`
+    NotTildeAsterisk,
+    Dot,
     SingleQuote,
     DoubleQuote,
     // Identifiers and Literals
     Identifier {
-        first_name: &'a str,
-        second_name: Option<&'a str>,
-        third_name: Option<&'a str>,
+        first_name: Cow<'a, str>,
+        second_name: Option<Cow<'a, str>>,
+        third_name: Option<Cow<'a, str>>,
     },
     StringLiteral(String),
     NumericLiteral(String),
     BooleanLiteral(bool),
+    // Parameters
+    /// `?`
+    QuestionMark,
+    /// `$1`
+    PositionalParameter(usize),
+    /// `:name`
+    NamedParameter(&'a str),
     // Comments
     SingleLineComment(String),
     MultiLineComment(String),
 }
 
 impl<'a> Token<'a> {
-    pub fn identifier(first_name: &'a str) -> Token<'a> {
+    pub fn identifier(first_name: impl Into<Cow<'a, str>>) -> Token<'a> {
         Token::Identifier {
-            first_name,
+            first_name: first_name.into(),
             second_name: None,
             third_name: None,
         }
     }
 }
 
-struct Lexer<'a> {
+/// Byte offsets of a token in the input, `end` is exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct Lexer<'a> {
     input: &'a str,
-    input_iterator: std::str::Chars<'a>,
+    input_iterator: std::str::CharIndices<'a>,
     current_position: usize,
-    is_finished: bool,
+    token_start: usize,
 }
 
 impl<'a> Iterator for Lexer<'a> {
     type Item = Result<Token<'a>, LexError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let c = self.skip_whitespace()?;
-            return match c {
-                '*' => Some(self.single(Token::Asterisk)),
-                ',' => Some(self.single(Token::Comma)),
-                '=' => Some(self.single(Token::Equals)),
-                '+' => Some(self.single(Token::Plus)),
-                '%' => Some(self.single(Token::Percent)),
-                '|' => Some(self.single(Token::Concat)),
-
-                // may be longer
-                '<' => Some(self.may_be_longer(Token::LessThan)),
-                '>' => Some(self.may_be_longer(Token::GreaterThan)),
-                '/' => Some(self.may_be_longer(Token::Slash)),
-                '\'' => Some(self.may_be_longer(Token::SingleQuote)),
-                '-' => Some(self.may_be_longer(Token::Minus)),
-                '"' => Some(self.may_be_longer(Token::DoubleQuote)),
-
-                ';' => Some(Ok(Token::Semicolon)),
-                '(' => Some(Ok(Token::OpenParen)),
-                ')' => Some(Ok(Token::CloseParen)),
-                '!' => Some(Ok(Token::NotEquals)),
-                c => {
-                    if c.is_alphabetic() || c == '_' {
-                        Some(self.word_started())
-                    } else if c.is_numeric() {
-                        Some(self.numeric_started(false))
-                    } else {
-                        Some(Err(LexError::InvalidCharacter(c)))
-                    }
+        let c = self.skip_whitespace()?;
+        self.token_start = self.get_last_token_start();
+        match c {
+            '*' => Some(self.single(Token::Asterisk)),
+            ',' => Some(self.single(Token::Comma)),
+            '=' => Some(self.single(Token::Equals)),
+            '+' => Some(self.single(Token::Plus)),
+            '%' => Some(self.single(Token::Percent)),
+
+            // may be longer
+            '<' => Some(self.may_be_longer(Token::LessThan)),
+            '>' => Some(self.may_be_longer(Token::GreaterThan)),
+            '/' => Some(self.may_be_longer(Token::Slash)),
+            '\'' => Some(self.may_be_longer(Token::SingleQuote)),
+            '-' => Some(self.may_be_longer(Token::Minus)),
+            '"' => Some(self.quoted_identifier_started()),
+            '|' => Some(self.may_be_longer(Token::Concat)),
+            '!' => Some(self.may_be_longer(Token::NotEquals)),
+            '~' => Some(self.may_be_longer(Token::Tilde)),
+
+            ';' => Some(Ok(Token::Semicolon)),
+            '(' => Some(Ok(Token::OpenParen)),
+            ')' => Some(Ok(Token::CloseParen)),
+            '.' => Some(Ok(Token::Dot)),
+            '?' => Some(Ok(Token::QuestionMark)),
+            '$' => Some(self.positional_parameter_started()),
+            ':' => Some(self.named_parameter_started()),
+            c => {
+                if c.is_alphabetic() || c == '_' {
+                    Some(self.word_started())
+                } else if c.is_numeric() {
+                    Some(self.numeric_started())
+                } else {
+                    Some(Err(LexError::InvalidCharacter(c)))
                 }
             }
         }
@@ -262,26 +375,33 @@ impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
             input,
-            input_iterator: input.chars(),
+            input_iterator: input.char_indices(),
             current_position: 0,
-            is_finished: false,
+            token_start: 0,
         }
     }
+
     fn get_next_and_increment(&mut self) -> Option<char> {
-        let c = self.input_iterator.next();
-        if c.is_some() {
-            self.current_position += 1;
-        } else {
-            self.is_finished = true;
-        }
-        c
+        let (position, c) = self.input_iterator.next()?;
+        self.current_position = position + c.len_utf8();
+        Some(c)
     }
 
-    fn get_last_token_end(&self) -> usize {
-        if self.is_finished {
-            return self.current_position;
-        }
-        self.current_position - 1
+    fn peek(&self) -> Option<char> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.input_iterator.clone().nth(n).map(|(_, c)| c)
+    }
+
+    /// Byte offset of the char that was consumed last.
+    fn get_last_token_start(&self) -> usize {
+        let last_len = self.input[..self.current_position]
+            .chars()
+            .next_back()
+            .map_or(0, char::len_utf8);
+        self.current_position - last_len
     }
 
     fn skip_whitespace(&mut self) -> Option<char> {
@@ -293,103 +413,115 @@ impl<'a> Lexer<'a> {
         None
     }
 
-    fn numeric_started(&mut self, has_sign: bool) -> Result<Token<'a>, LexError> {
-        let is_numeric = |c: Option<char>| -> bool {
-            match c {
-                Some(n) => n.is_numeric(),
-                _ => false,
-            }
-        };
-        let started_position = self.get_last_token_end();
+    fn numeric_started(&mut self) -> Result<Token<'a>, LexError> {
+        let started_position = self.get_last_token_start();
         let mut seen_dot = false;
-        loop {
-            let c = self.get_next_and_increment();
-            if is_numeric(c) {
-                continue;
-            } else if c == Some('.') {
+        while let Some(c) = self.peek() {
+            if c.is_numeric() {
+                self.get_next_and_increment();
+            } else if c == '.' {
+                self.get_next_and_increment();
                 if seen_dot {
                     return Err(LexError::InvalidCharacter('.'));
                 }
                 seen_dot = true;
-                continue;
             } else {
                 break;
             }
         }
-        let literal = &self.input[started_position..self.get_last_token_end()];
-        Ok(Token::NumericLiteral(if has_sign { format!("-{}", literal) } else { literal.to_string() }))
-    }
-
-    fn identifier_dot_started(&mut self, started_position: usize) -> Result<Token<'a>, LexError> {
-        let first_dot_position = self.current_position;
-        let mut second_dot_position = 0;
-        let mut third_dot_position = 0;
-        let mut is_word = |c: Option<char>, cp: usize| -> bool {
-            match c {
-                Some('.') => {
-                    if second_dot_position == 0 {
-                        second_dot_position = cp;
-                        return true;
-                    }
-                    if third_dot_position == 0 {
-                        third_dot_position = cp;
-                    }
-                    return true;
-                }
-                Some(n) => n.is_alphabetic() || n.is_numeric() || n == '_',
-                _ => false,
-            }
+        // an exponent needs digits, `1e` is the number 1 followed by a word
+        let exponent_digit = match self.peek_nth(1) {
+            Some('+' | '-') => self.peek_nth(2),
+            c => c,
         };
-        loop {
-            let c = self.get_next_and_increment();
-            if is_word(c, self.current_position) {
-                continue;
+        if matches!(self.peek(), Some('e' | 'E'))
+            && exponent_digit.is_some_and(|c| c.is_ascii_digit())
+        {
+            self.get_next_and_increment();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.get_next_and_increment();
+            }
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.get_next_and_increment();
+            }
+        }
+        let literal = &self.input[started_position..self.current_position];
+        Ok(Token::NumericLiteral(literal.to_string()))
+    }
+
+    /// Consumes the rest of a word and returns it, the first char is already consumed.
+    fn consume_word(&mut self, started_position: usize) -> &'a str {
+        while let Some(c) = self.peek() {
+            if c.is_alphabetic() || c.is_numeric() || c == '_' {
+                self.get_next_and_increment();
             } else {
                 break;
             }
         }
-        if third_dot_position > 0 {
-            return Err(LexError::InvalidCharacter('.'));
-        }
-        let first_name = &self.input[started_position..first_dot_position - 1];
-        let second_name = if second_dot_position > 0 {
-            &self.input[first_dot_position..second_dot_position - 1]
-        } else {
-            &self.input[first_dot_position..self.get_last_token_end()]
-        };
-        let third_name = if second_dot_position > 0 {
-            Some(&self.input[second_dot_position..self.get_last_token_end()])
-        } else {
-            None
-        };
-        return Ok(Token::Identifier {
-            first_name,
-            second_name: Some(second_name),
-            third_name,
-        });
+        &self.input[started_position..self.current_position]
     }
-    fn word_started(&mut self) -> Result<Token<'a>, LexError> {
-        let started_position = self.get_last_token_end();
 
-        let is_word = |c: Option<char>| -> bool {
-            match c {
-                Some(n) => n.is_alphabetic() || n.is_numeric() || n == '_',
-                _ => false,
+    /// Consumes the rest of a `"quoted"` name and returns it without quotes, the opening quote
+    /// is already consumed. A doubled quote is an escaped quote, e.g. `"a""b"` is `a"b`.
+    fn consume_quoted_name(&mut self) -> Result<Cow<'a, str>, LexError> {
+        let started_position = self.current_position;
+        let mut is_escaped = false;
+        loop {
+            match self.get_next_and_increment() {
+                Some('"') if self.peek() == Some('"') => {
+                    self.get_next_and_increment();
+                    is_escaped = true;
+                }
+                Some('"') => break,
+                Some(_) => continue,
+                None => return Err(LexError::UnexpectedEndOfInput),
             }
-        };
+        }
+        if self.current_position - 1 == started_position {
+            return Err(LexError::InvalidCharacter('"'));
+        }
+        let name = &self.input[started_position..self.current_position - 1];
+        match is_escaped {
+            true => Ok(Cow::Owned(name.replace("\"\"", "\""))),
+            false => Ok(Cow::Borrowed(name)),
+        }
+    }
 
-        loop {
-            let c = self.get_next_and_increment();
-            if is_word(c) {
-                continue;
+    /// A dot followed by a word continues the identifier, anything else (e.g. `t.*`) is left
+    /// for the next token.
+    fn dot_continues_identifier(&self) -> bool {
+        self.peek() == Some('.')
+            && matches!(self.peek_nth(1), Some(c) if c.is_alphabetic() || c == '_' || c == '"')
+    }
+
+    fn identifier_dot_started(&mut self, first_name: Cow<'a, str>) -> Result<Token<'a>, LexError> {
+        let mut names = vec![];
+        while self.dot_continues_identifier() {
+            self.get_next_and_increment();
+            if self.get_next_and_increment() == Some('"') {
+                names.push(self.consume_quoted_name()?);
             } else {
-                if c == Some('.') {
-                    return self.identifier_dot_started(started_position);
-                }
-                break;
+                let started_position = self.get_last_token_start();
+                names.push(Cow::Borrowed(self.consume_word(started_position)));
             }
         }
-        let word = &self.input[started_position..self.get_last_token_end()];
+        if names.len() > 2 {
+            return Err(LexError::InvalidCharacter('.'));
+        }
+        let mut names = names.into_iter();
+        Ok(Token::Identifier {
+            first_name,
+            second_name: names.next(),
+            third_name: names.next(),
+        })
+    }
+
+    fn word_started(&mut self) -> Result<Token<'a>, LexError> {
+        let started_position = self.get_last_token_start();
+        let word = self.consume_word(started_position);
+        if self.dot_continues_identifier() {
+            return self.identifier_dot_started(Cow::Borrowed(word));
+        }
         let lower_case_word = word.to_lowercase();
 
         if let Some(token) = KEYWORDS.get(lower_case_word.as_str()) {
@@ -399,63 +531,96 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Quoted names are never keywords, e.g. `"order"` is an identifier.
+    fn quoted_identifier_started(&mut self) -> Result<Token<'a>, LexError> {
+        let name = self.consume_quoted_name()?;
+        if self.dot_continues_identifier() {
+            return self.identifier_dot_started(name);
+        }
+        Ok(Token::identifier(name))
+    }
 
-    fn single(&mut self, token: Token<'a>) -> Result<Token<'a>, LexError> {
-        let c = self.get_next_and_increment();
-        match c {
-            Some(c) => {
-                if c.is_whitespace() {
-                    Ok(token)
-                } else {
-                    Err(LexError::InvalidCharacter(c))
-                }
+    /// Operators can't be glued to each other, e.g. `**` or `=<`.
+    fn ensure_separated(&mut self, token: Token<'a>) -> Result<Token<'a>, LexError> {
+        match self.peek() {
+            Some(c) if "*=+%<>/!|".contains(c) => {
+                self.get_next_and_increment();
+                Err(LexError::InvalidCharacter(c))
             }
-            None => Ok(token),
+            _ => Ok(token),
         }
     }
 
-    fn quote_started(&mut self, quote: Token<'a>) -> Result<Token<'a>, LexError> {
-        let same_quote = |c: Option<char>| -> bool {
-            match c {
-                Some('\'') => quote == Token::SingleQuote,
-                Some('"') => quote == Token::DoubleQuote,
-                _ => false,
+    /// `$` has to be followed by a position starting from 1.
+    fn positional_parameter_started(&mut self) -> Result<Token<'a>, LexError> {
+        let started_position = self.current_position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.get_next_and_increment();
+        }
+        match self.input[started_position..self.current_position].parse() {
+            Ok(position) if position > 0 => Ok(Token::PositionalParameter(position)),
+            _ => Err(LexError::InvalidCharacter('$')),
+        }
+    }
+
+    fn named_parameter_started(&mut self) -> Result<Token<'a>, LexError> {
+        match self.get_next_and_increment() {
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let started_position = self.get_last_token_start();
+                Ok(Token::NamedParameter(self.consume_word(started_position)))
             }
+            _ => Err(LexError::InvalidCharacter(':')),
+        }
+    }
+
+    fn single(&mut self, token: Token<'a>) -> Result<Token<'a>, LexError> {
+        self.ensure_separated(token)
+    }
+
+    fn quote_started(&mut self, quote: Token<'a>) -> Result<Token<'a>, LexError> {
+        let quote_char = if quote == Token::SingleQuote {
+            '\''
+        } else {
+            '"'
         };
-        let started_position = self.current_position;
+        let mut literal = String::new();
         loop {
-            let c = self.get_next_and_increment();
-            if !same_quote(c) {
-                continue;
-            } else {
-                break;
+            match self.get_next_and_increment() {
+                // a doubled quote is an escaped quote
+                Some(c) if c == quote_char && self.peek() == Some(quote_char) => {
+                    self.get_next_and_increment();
+                    literal.push(c);
+                }
+                Some(c) if c == quote_char => break,
+                Some(c) => literal.push(c),
+                None => return Err(LexError::UnexpectedEndOfInput),
             }
         }
-        let literal = &self.input[started_position..self.get_last_token_end()];
-        Ok(Token::StringLiteral(literal.to_string()))
+        Ok(Token::StringLiteral(literal))
     }
 
     fn single_line_comment_started(&mut self) -> Result<Token<'a>, LexError> {
         let started_position = self.current_position;
-        loop {
-            let c = self.get_next_and_increment();
-            if c == Some('\n') || c == None {
+        while let Some(c) = self.peek() {
+            if c == '\n' {
                 break;
             }
+            self.get_next_and_increment();
         }
-        let comment = &self.input[started_position..self.get_last_token_end()];
+        let comment = &self.input[started_position..self.current_position];
         Ok(Token::SingleLineComment(comment.to_string()))
     }
 
     fn multi_line_comment(&mut self) -> Result<Token<'a>, LexError> {
         let started_position = self.current_position;
         loop {
-            let c = self.get_next_and_increment();
-            if c == Some('*') {
-                let next = self.get_next_and_increment();
-                if next == Some('/') {
+            match self.get_next_and_increment() {
+                Some('*') if self.peek() == Some('/') => {
+                    self.get_next_and_increment();
                     break;
                 }
+                Some(_) => continue,
+                None => return Err(LexError::UnexpectedEndOfInput),
             }
         }
         let comment = &self.input[started_position..self.current_position - 2];
@@ -463,50 +628,54 @@ impl<'a> Lexer<'a> {
     }
 
     fn may_be_longer(&mut self, first: Token<'a>) -> Result<Token<'a>, LexError> {
-        match first {
-            Token::SingleQuote | Token::DoubleQuote => return self.quote_started(first),
-            _ => (),
+        if first == Token::SingleQuote {
+            return self.quote_started(first);
         }
 
-        let second = self.get_next_and_increment();
-        match first {
-            Token::LessThan => match second {
-                Some('=') => Ok(Token::LessThanOrEquals),
-                Some('>') => Ok(Token::NotEquals),
-                Some(' ') => Ok(Token::LessThan),
-                Some(c) => Err(LexError::InvalidCharacter(c)),
-                None => Ok(Token::LessThan),
-            },
-            Token::GreaterThan => match second {
-                Some('=') => Ok(Token::GreaterThanOrEquals),
-                Some(' ') => Ok(Token::GreaterThan),
-                Some(c) => Err(LexError::InvalidCharacter(c)),
-                None => Ok(Token::GreaterThan),
-            },
-            Token::Slash => match second {
-                Some(' ') => Ok(Token::Slash),
-                Some('*') => self.multi_line_comment(),
-                Some(c) => Err(LexError::InvalidCharacter(c)),
-                None => Ok(Token::Slash),
-            },
-            Token::Minus => match second {
-                Some(' ') => Ok(Token::Minus),
-                Some('-') => self.single_line_comment_started(),
-                Some(c) => {
-                    if c.is_numeric() {
-                        self.numeric_started(true)
-                    } else {
-                        Err(LexError::InvalidCharacter(c))
-                    }
-                }
-                None => Ok(Token::Minus),
-            },
-            _ => Err(LexError::InvalidCharacter(second.unwrap())),
-        }
+        let second = self.peek();
+        let longer = match (&first, second) {
+            (Token::LessThan, Some('=')) => Token::LessThanOrEquals,
+            (Token::LessThan, Some('>')) => Token::NotEquals,
+            (Token::GreaterThan, Some('=')) => Token::GreaterThanOrEquals,
+            (Token::NotEquals, Some('=')) => Token::NotEquals,
+            (Token::NotEquals, Some('~')) => {
+                self.get_next_and_increment();
+                return self.may_be_longer(Token::NotTilde);
+            }
+            (Token::Tilde, Some('*')) => Token::TildeAsterisk,
+            (Token::NotTilde, Some('*')) => Token::NotTildeAsterisk,
+            (Token::Concat, Some('|')) => Token::Concat,
+            (Token::Slash, Some('*')) => {
+                self.get_next_and_increment();
+                return self.multi_line_comment();
+            }
+            (Token::Minus, Some('-')) => {
+                self.get_next_and_increment();
+                return self.single_line_comment_started();
+            }
+            (Token::Concat, _) => return Err(LexError::InvalidCharacter('|')),
+            _ => return self.ensure_separated(first),
+        };
+        self.get_next_and_increment();
+        self.ensure_separated(longer)
     }
 
-    pub fn tokenize(&self) -> Vec<Token<'a>> {
-        vec![]
+    /// Collects all tokens, stops on the first error.
+    pub fn tokenize(self) -> Result<Vec<Token<'a>>, LexError> {
+        self.collect()
+    }
+
+    /// Collects all tokens together with their positions, stops on the first error.
+    pub fn tokenize_with_spans(mut self) -> Result<Vec<(Token<'a>, Span)>, LexError> {
+        let mut tokens = vec![];
+        while let Some(token) = self.next() {
+            let span = Span {
+                start: self.token_start,
+                end: self.current_position,
+            };
+            tokens.push((token?, span));
+        }
+        Ok(tokens)
     }
 }
 
@@ -561,8 +730,7 @@ mod tests {
             Ok(Token::identifier("age")),
             Ok(Token::Equals),
             Ok(Token::NumericLiteral("1".to_string())),
-            // Ok(Token::Semicolon),
-            // Token::EOF,
+            Ok(Token::Semicolon),
         ];
         assert_eq!(tokens, expected);
     }
@@ -703,6 +871,145 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn lex_without_whitespace() {
+        let input = "values(1,'a'),(-2.5);count(*)<=t.*,a||x";
+        let lexer = Lexer::new(input);
+        let tokens: Vec<Result<Token, LexError>> = lexer.collect();
+        let expected = vec![
+            Ok(Token::Values),
+            Ok(Token::OpenParen),
+            Ok(Token::NumericLiteral("1".to_string())),
+            Ok(Token::Comma),
+            Ok(Token::StringLiteral("a".to_string())),
+            Ok(Token::CloseParen),
+            Ok(Token::Comma),
+            Ok(Token::OpenParen),
+            Ok(Token::Minus),
+            Ok(Token::NumericLiteral("2.5".to_string())),
+            Ok(Token::CloseParen),
+            Ok(Token::Semicolon),
+            Ok(Token::identifier("count")),
+            Ok(Token::OpenParen),
+            Ok(Token::Asterisk),
+            Ok(Token::CloseParen),
+            Ok(Token::LessThanOrEquals),
+            Ok(Token::identifier("t")),
+            Ok(Token::Dot),
+            Ok(Token::Asterisk),
+            Ok(Token::Comma),
+            Ok(Token::identifier("a")),
+            Ok(Token::Concat),
+            Ok(Token::identifier("x")),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn lex_pattern_operators() {
+        let input = "a ~ 'x' ~*'y' !~b !~* c != d ilike similar";
+        let tokens: Vec<Result<Token, LexError>> = Lexer::new(input).collect();
+        let expected = vec![
+            Ok(Token::identifier("a")),
+            Ok(Token::Tilde),
+            Ok(Token::StringLiteral("x".to_string())),
+            Ok(Token::TildeAsterisk),
+            Ok(Token::StringLiteral("y".to_string())),
+            Ok(Token::NotTilde),
+            Ok(Token::identifier("b")),
+            Ok(Token::NotTildeAsterisk),
+            Ok(Token::identifier("c")),
+            Ok(Token::NotEquals),
+            Ok(Token::identifier("d")),
+            Ok(Token::ILike),
+            Ok(Token::Similar),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn string_literal_with_doubled_quote() {
+        let input = "'it''s' 'unterminated";
+        let lexer = Lexer::new(input);
+        let tokens: Vec<Result<Token, LexError>> = lexer.collect();
+        let expected = vec![
+            Ok(Token::StringLiteral("it's".to_string())),
+            Err(LexError::UnexpectedEndOfInput),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        let input = "\"order\" \"My Table\".\"select\" public.\"t\"";
+        let lexer = Lexer::new(input);
+        let tokens: Vec<Result<Token, LexError>> = lexer.collect();
+        let expected = vec![
+            Ok(Token::identifier("order")),
+            Ok(Token::Identifier {
+                first_name: "My Table".into(),
+                second_name: Some("select".into()),
+                third_name: None,
+            }),
+            Ok(Token::Identifier {
+                first_name: "public".into(),
+                second_name: Some("t".into()),
+                third_name: None,
+            }),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn quoted_identifiers_with_doubled_quote() {
+        let input = "\"a\"\"b\" \"\"\"\".\"c\"\"\" \"unterminated\"\"";
+        let lexer = Lexer::new(input);
+        let tokens: Vec<Result<Token, LexError>> = lexer.collect();
+        let expected = vec![
+            Ok(Token::identifier("a\"b")),
+            Ok(Token::Identifier {
+                first_name: "\"".into(),
+                second_name: Some("c\"".into()),
+                third_name: None,
+            }),
+            Err(LexError::UnexpectedEndOfInput),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn tokens_with_spans() {
+        let input = "select a -- comment\n from \"t\"";
+        let tokens = Lexer::new(input).tokenize_with_spans().unwrap();
+        let slices: Vec<&str> = tokens
+            .iter()
+            .map(|(_, span)| &input[span.start..span.end])
+            .collect();
+        assert_eq!(slices, vec!["select", "a", "-- comment", "from", "\"t\""]);
+    }
+
+    #[test]
+    fn parameters() {
+        let input = "?, $1=$12 :name,:_x";
+        let tokens: Vec<Result<Token, LexError>> = Lexer::new(input).collect();
+        let expected = vec![
+            Ok(Token::QuestionMark),
+            Ok(Token::Comma),
+            Ok(Token::PositionalParameter(1)),
+            Ok(Token::Equals),
+            Ok(Token::PositionalParameter(12)),
+            Ok(Token::NamedParameter("name")),
+            Ok(Token::Comma),
+            Ok(Token::NamedParameter("_x")),
+        ];
+        assert_eq!(tokens, expected);
+
+        for input in ["$", "$0", "$a", ": name", ":1"] {
+            let tokens: Result<Vec<Token>, LexError> = Lexer::new(input).tokenize();
+            assert!(tokens.is_err(), "{}", input);
+        }
+    }
+
     #[test]
     fn numeric_literals() {
         let input = "1 2 3.45";
@@ -717,6 +1024,24 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn numeric_literals_with_exponents() {
+        let input = "1e5 2.5E-3 1e+308 1e e5 1e-";
+        let tokens: Vec<Result<Token, LexError>> = Lexer::new(input).collect();
+        let expected = vec![
+            Ok(Token::NumericLiteral("1e5".to_string())),
+            Ok(Token::NumericLiteral("2.5E-3".to_string())),
+            Ok(Token::NumericLiteral("1e+308".to_string())),
+            Ok(Token::NumericLiteral("1".to_string())),
+            Ok(Token::identifier("e")),
+            Ok(Token::identifier("e5")),
+            Ok(Token::NumericLiteral("1".to_string())),
+            Ok(Token::identifier("e")),
+            Ok(Token::Minus),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
     #[test]
     fn boolean_literals() {
         let input = "true false";
@@ -942,20 +1267,20 @@ mod tests {
             Ok(Token::identifier("table1")),
             Ok(Token::identifier("column1")),
             Ok(Token::Identifier {
-                first_name: "PUBLIC",
-                second_name: Some("table2"),
+                first_name: "PUBLIC".into(),
+                second_name: Some("table2".into()),
                 third_name: None,
             }),
             Ok(Token::identifier("my_col_3")),
             Ok(Token::Identifier {
-                first_name: "PUBLIC_4",
-                second_name: Some("table_5"),
+                first_name: "PUBLIC_4".into(),
+                second_name: Some("table_5".into()),
                 third_name: None,
             }),
             Ok(Token::Identifier {
-                first_name: "public",
-                second_name: Some("t6able"),
-                third_name: Some("column_7"),
+                first_name: "public".into(),
+                second_name: Some("t6able".into()),
+                third_name: Some("column_7".into()),
             }),
         ];
         assert_eq!(actual, expected);
@@ -1,550 +1,2731 @@
+pub mod display;
+pub mod expr;
+pub mod formatter;
 pub mod lexer;
+pub mod visitor;
 
-// use crate::parser::Query::{Insert, Select};
-// use nom::branch::alt;
-// use nom::character::complete::{alpha1, alphanumeric1, i32, space1};
-// use nom::combinator::{map, opt};
-// use nom::multi::separated_list1;
-// use nom::sequence::terminated;
-// use nom::{bytes::complete::tag, IResult};
-//
-// #[derive(Debug, PartialEq)]
-// struct Queries {
-//     queries: Vec<Query>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// enum Query {
-//     Select(SelectQuery),
-//     Insert(InsertQuery),
-//     Update(UpdateQuery),
-//     Delete(DeleteQuery),
-//     CreateTable(CreateTableQuery),
-//     DropTable(DropTableQuery),
-//     AlterTable(AlterTableQuery),
-// }
-//
-// /// *SelectQuery* is a struct that represents a select query
-// /// select <select_statement>
-// /// from <from_statement>
-// /// where <where_statement>
-// /// group by <group_by_statement>
-// /// having <having_statement>
-// /// order by <order_by_statement>
-// /// limit <limit_statement>
-// #[derive(Debug, PartialEq)]
-// struct SelectQuery {
-//     select_statement: SelectStatement,
-//     from_statement: FromStatement,
-//     where_statement: Option<WhereStatement>,
-//     group_by_statement: Option<GroupByStatement>,
-//     having_statement: Option<HavingStatement>,
-//     order_by_statement: Option<OrderByStatement>,
-//     limit_statement: Option<LimitStatement>,
-// }
-//
-// /// *InsertQuery* is a struct that represents an insert query
-// /// insert into <table_name>
-// /// (<columns>)
-// /// values (<values>)
-// #[derive(Debug, PartialEq)]
-// struct InsertQuery {
-//     table_name: String,
-//     columns: Vec<String>,
-//     values: Vec<String>,
-// }
-//
-// /// *UpdateQuery* is a struct that represents an update query
-// /// update <table_name>
-// /// set <set_statement>
-// /// where <where_statement>
-// #[derive(Debug, PartialEq)]
-// struct UpdateQuery {
-//     table_name: String,
-//     set_statement: SetStatement,
-//     where_statement: Option<WhereStatement>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct SetStatement {
-//     columns: Vec<String>,
-// }
-//
-// /// *CreateTableQuery* is a struct that represents a create table query
-// /// create table <table_name>
-// /// (<columns_definitions>)
-// /// <constraints>
-// #[derive(Debug, PartialEq)]
-// struct CreateTableQuery {
-//     table_name: String,
-//     // todo: columns_definitions and constraints as struct
-//     columns_definitions: Vec<String>,
-//     constraints: Vec<String>,
-// }
-//
-// /// *DeleteStatement* is a struct that represents a drop table query
-// /// delete from <table_name>
-// /// where <where_statement>
-// #[derive(Debug, PartialEq)]
-// struct DeleteQuery {
-//     table_name: String,
-//     where_statement: WhereStatement,
-// }
-//
-// /// *DropTableStatement* is a struct that represents a drop table query
-// /// drop table <table_name>
-// #[derive(Debug, PartialEq)]
-// struct DropTableQuery {
-//     table_name: String,
-// }
-//
-// /// *AlterTableStatement* is a struct that represents an alter table query
-// /// alter table <table_name>
-// /// <alter_table_statement>
-// #[derive(Debug, PartialEq)]
-// struct AlterTableQuery {
-//     table_name: String,
-//     // todo: alter_table_statement actions
-//     alter_table_statement: String,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct SelectStatement {
-//     columns: Vec<ColumnStatement>,
-//     distinct: bool,
-// }
-// #[derive(Debug, PartialEq)]
-// struct FromStatement {
-//     tables: Vec<TableStatement>,
-//     joins: Vec<JoinStatement>,
-// }
-// #[derive(Debug, PartialEq)]
-// struct WhereStatement {
-//     conditions: Vec<Condition>,
-// }
-// #[derive(Debug, PartialEq)]
-// struct OrderByStatement {
-//     columns: Vec<ColumnStatement>,
-//     order: Order,
-// }
-// #[derive(Debug, PartialEq)]
-// enum Order {
-//     Asc,
-//     Desc,
-// }
-// #[derive(Debug, PartialEq)]
-// struct LimitStatement {
-//     limit: i32,
-//     offset: i32,
-// }
-// #[derive(Debug, PartialEq)]
-// struct HavingStatement {
-//     conditions: Vec<Condition>,
-// }
-// #[derive(Debug, PartialEq)]
-// struct GroupByStatement {
-//     columns: Vec<ColumnStatement>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// enum ColumnStatement {
-//     ColumnStatement(ColumnIdentifier),
-//     ColumnStatementFunction(Function),
-//     ColumnStatementLiteral(Literal),
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct ColumnIdentifier {
-//     table_name: Option<String>,
-//     column_name: String,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct Function {
-//     name: String,
-//     arguments: Vec<ColumnStatement>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// enum Literal {
-//     Integer(i32),
-//     String(String),
-//     Boolean(bool),
-//     Float(f32),
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct ColumnFunction {
-//     function: String,
-//     column: ColumnIdentifier,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct TableStatement {
-//     table_name: String,
-//     alias: Option<String>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct JoinStatement {
-//     table: TableStatement,
-//     // todo: join type as enum
-//     join_type: String,
-//     on: Vec<Condition>,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct Condition {
-//     left: ColumnStatement,
-//     operator: Operator,
-//     right: ColumnStatement,
-// }
-//
-// #[derive(Debug, PartialEq)]
-// enum Operator {
-//     Equal,
-//     NotEqual, // Todo: add more operators
-//               // GreaterThan,
-//               // LessThan,
-//               // GreaterThanOrEqual,
-//               // LessThanOrEqual,
-//               // Like,
-//               // In,
-//               // NotIn,
-//               // Between,
-//               // NotBetween,
-//               // IsNull,
-//               // IsNotNull,
-//               // And,
-//               // Or
-// }
-//
-// impl Queries {
-//     fn parse(input: &str) -> IResult<&str, Queries> {
-//         let (input, queries) = separated_list1(tag(";"), Query::parse)(input)?;
-//
-//         Ok((input, Queries { queries }))
-//     }
-// }
-//
-// impl Query {
-//     fn parse(input: &str) -> IResult<&str, Query> {
-//         alt((
-//             map(SelectQuery::parse, Select),
-//             map(InsertQuery::parse, Insert),
-//         ))(input)
-//     }
-// }
-//
-// impl SelectQuery {
-//     fn parse(input: &str) -> IResult<&str, SelectQuery> {
-//         let (input, _) = tag("select")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, select_statement) = SelectStatement::parse(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, _) = tag("from")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, from_statement) = FromStatement::parse(input)?;
-//         let (input, _) = tag(";")(input)?;
-//
-//         // TODO: Implement the rest of the parsing
-//         // let (input, from_statement) = FromStatement::parse(input)?;
-//         // let (input, where_statement) = WhereStatement::parse(input)?;
-//         // let (input, group_by_statement) = GroupByStatement::parse(input)?;
-//         // let (input, having_statement) = HavingStatement::parse(input)?;
-//         // let (input, order_by_statement) = OrderByStatement::parse(input)?;
-//         // let (input, limit_statement) = LimitStatement::parse(input)?;
-//
-//         Ok((
-//             input,
-//             SelectQuery {
-//                 select_statement,
-//                 from_statement,
-//                 where_statement: None,
-//                 group_by_statement: None,
-//                 having_statement: None,
-//                 order_by_statement: None,
-//                 limit_statement: None,
-//             },
-//         ))
-//     }
-// }
-//
-// impl SelectStatement {
-//     fn parse(input: &str) -> IResult<&str, SelectStatement> {
-//         let (input, columns) = separated_list1(tag(", "), ColumnStatement::parse)(input)?;
-//
-//         Ok((
-//             input,
-//             SelectStatement {
-//                 columns,
-//                 distinct: false,
-//             },
-//         ))
-//     }
-// }
-// impl Query {
-//     fn parse(input: &str) -> IResult<&str, Query> {
-//         alt((
-//             map(SelectQuery::parse, Select),
-//             map(InsertQuery::parse, Insert),
-//         ))(input)
-//     }
-// }
-//
-// impl ColumnStatement {
-//     fn parse(input: &str) -> IResult<&str, ColumnStatement> {
-//         alt((
-//             map(ColumnIdentifier::parse, ColumnStatement::ColumnStatement),
-//             map(Function::parse, ColumnStatement::ColumnStatementFunction),
-//             map(Literal::parse, ColumnStatement::ColumnStatementLiteral),
-//         ))(input)
-//     }
-// }
-//
-// impl ColumnIdentifier {
-//     fn parse(input: &str) -> IResult<&str, ColumnIdentifier> {
-//         let (input, table_name) = opt(terminated(alphanumeric1, tag(".")))(input)?;
-//         let (input, column_name) = alphanumeric1(input)?;
-//
-//         Ok((
-//             input,
-//             ColumnIdentifier {
-//                 table_name: table_name.map(|s| s.to_string()),
-//                 column_name: column_name.to_string(),
-//             },
-//         ))
-//     }
-// }
-//
-// impl Function {
-//     fn parse(input: &str) -> IResult<&str, Function> {
-//         let (input, name) = alphanumeric1(input)?;
-//         let (input, _) = tag("(")(input)?;
-//         let (input, arguments) = separated_list1(tag(", "), ColumnStatement::parse)(input)?;
-//         let (input, _) = tag(")")(input)?;
-//
-//         Ok((
-//             input,
-//             Function {
-//                 name: name.to_string(),
-//                 arguments,
-//             },
-//         ))
-//     }
-// }
-//
-// impl Literal {
-//     fn parse(input: &str) -> IResult<&str, Literal> {
-//         let res = i32(input);
-//         if res.is_ok() {
-//             return res.map(|(input, i_value)| (input, Literal::Integer(i_value)));
-//         }
-//         let (input, _) = tag("'")(input)?;
-//         let (input, s) = alpha1(input)?;
-//         let (input, _) = tag("'")(input)?;
-//         Ok((input, Literal::String(s.to_string())))
-//     }
-// }
-//
-// impl FromStatement {
-//     fn parse(input: &str) -> IResult<&str, FromStatement> {
-//         let (input, table) = TableStatement::parse(input)?;
-//         // let (input, _) = space1(input)?;
-//         // let (input, joins) = separated_list1(tag(" "), JoinStatement::parse)(input)?;
-//         Ok((
-//             input,
-//             FromStatement {
-//                 tables: vec![table],
-//                 // TODO: Implement joins
-//                 joins: vec![],
-//             },
-//         ))
-//     }
-// }
-//
-// impl TableStatement {
-//     fn parse(input: &str) -> IResult<&str, TableStatement> {
-//         let (input, table_name) = alphanumeric1(input)?;
-//         Ok((
-//             input,
-//             TableStatement {
-//                 table_name: table_name.to_string(),
-//                 // TODO: Implement alias
-//                 alias: None,
-//             },
-//         ))
-//     }
-// }
-//
-// impl InsertQuery {
-//     fn parse(input: &str) -> IResult<&str, InsertQuery> {
-//         let (input, _) = tag("insert")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, _) = tag("into")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, table_name) = alphanumeric1(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, _) = tag("(")(input)?;
-//         let (input, columns) = Self::parse_column_list(input)?;
-//         let (input, _) = tag(")")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, _) = tag("values")(input)?;
-//         let (input, _) = space1(input)?;
-//         let (input, _) = tag("(")(input)?;
-//         let (input, values) = Self::parse_column_list(input)?;
-//         let (input, _) = tag(")")(input)?;
-//         let (input, _) = tag(";")(input)?;
-//
-//         Ok((
-//             input,
-//             InsertQuery {
-//                 table_name: table_name.to_string(),
-//                 columns: columns.iter().map(ToString::to_string).collect(),
-//                 values: values.iter().map(ToString::to_string).collect(),
-//             },
-//         ))
-//     }
-//     fn parse_column_list(input: &str) -> IResult<&str, Vec<&str>> {
-//         separated_list1(tag(", "), alphanumeric1)(input)
-//     }
-// }
-//
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//
-//     #[test]
-//     fn test_parse_select_in_queries_with_table_and_column() {
-//         let (input, queries) = Queries::parse("select t1.col1, t1.col2 from t1;").unwrap();
-//
-//         assert!(input.is_empty());
-//         assert_eq!(
-//             queries,
-//             Queries {
-//                 queries: vec![Select(SelectQuery {
-//                     select_statement: SelectStatement {
-//                         columns: vec![
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: Some("t1".to_string()),
-//                                 column_name: "col1".to_string()
-//                             }),
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: Some("t1".to_string()),
-//                                 column_name: "col2".to_string()
-//                             })
-//                         ],
-//                         distinct: false
-//                     },
-//                     from_statement: FromStatement {
-//                         tables: vec![TableStatement {
-//                             table_name: "t1".to_string(),
-//                             alias: None
-//                         }],
-//                         joins: vec![]
-//                     },
-//                     where_statement: None,
-//                     group_by_statement: None,
-//                     having_statement: None,
-//                     order_by_statement: None,
-//                     limit_statement: None
-//                 })]
-//             }
-//         );
-//     }
-//
-//     #[test]
-//     fn test_parse_select_in_queries() {
-//         let (input, queries) = Queries::parse("select col1, col2 from t1;").unwrap();
-//
-//         assert_eq!(input, "");
-//         assert_eq!(
-//             queries,
-//             Queries {
-//                 queries: vec![Query::Select(SelectQuery {
-//                     select_statement: SelectStatement {
-//                         columns: vec![
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: None,
-//                                 column_name: "col1".to_string()
-//                             }),
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: None,
-//                                 column_name: "col2".to_string()
-//                             })
-//                         ],
-//                         distinct: false
-//                     },
-//                     from_statement: FromStatement {
-//                         tables: vec![TableStatement {
-//                             table_name: "t1".to_string(),
-//                             alias: None
-//                         }],
-//                         joins: vec![]
-//                     },
-//                     where_statement: None,
-//                     group_by_statement: None,
-//                     having_statement: None,
-//                     order_by_statement: None,
-//                     limit_statement: None
-//                 })]
-//             }
-//         );
-//     }
-//
-//     #[test]
-//     fn test_parse_select_query() {
-//         let input = "select column1, column2 from table1;";
-//         let result = SelectQuery::parse(input);
-//         assert_eq!(
-//             result,
-//             Ok((
-//                 "",
-//                 SelectQuery {
-//                     select_statement: SelectStatement {
-//                         columns: vec![
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: None,
-//                                 column_name: "column1".to_string()
-//                             }),
-//                             ColumnStatement::ColumnStatement(ColumnIdentifier {
-//                                 table_name: None,
-//                                 column_name: "column2".to_string()
-//                             })
-//                         ],
-//                         distinct: false
-//                     },
-//                     from_statement: FromStatement {
-//                         tables: vec![TableStatement {
-//                             table_name: "table1".to_string(),
-//                             alias: None
-//                         }],
-//                         joins: vec![]
-//                     },
-//                     where_statement: None,
-//                     group_by_statement: None,
-//                     having_statement: None,
-//                     order_by_statement: None,
-//                     limit_statement: None
-//                 }
-//             ))
-//         );
-//     }
-//
-//     #[test]
-//     fn insert_single_column() {
-//         let (remainder, insert_query) =
-//             Queries::parse("insert into table1 (col1) values (1);").unwrap();
-//         assert_eq!(remainder, "");
-//         assert_eq!(
-//             insert_query,
-//             Queries {
-//                 queries: vec![Query::Insert(InsertQuery {
-//                     table_name: "table1".to_string(),
-//                     columns: vec!["col1".to_string()],
-//                     values: vec!["1".to_string()]
-//                 })]
-//             }
-//         );
-//     }
-// }
+use crate::parser::expr::Expr;
+use crate::parser::lexer::{LexError, Lexer, Span, Token};
+use crate::value::DataType;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    Lex(LexError),
+    UnexpectedToken {
+        expected: String,
+        found: String,
+    },
+    UnexpectedEnd {
+        expected: String,
+    },
+    /// Expressions and statements nest deeper than `MAX_NESTING`.
+    TooDeep,
+}
+
+impl From<LexError> for ParseError {
+    fn from(error: LexError) -> Self {
+        ParseError::Lex(error)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Lex(error) => write!(f, "lexer error: {:?}", error),
+            ParseError::UnexpectedToken { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            ParseError::UnexpectedEnd { expected } => {
+                write!(f, "expected {}, found end of input", expected)
+            }
+            ParseError::TooDeep => {
+                write!(
+                    f,
+                    "statement is nested more than {} levels deep",
+                    MAX_NESTING
+                )
+            }
+        }
+    }
+}
+
+/// Levels a statement may nest, the parser and the later stages recurse over them.
+pub const MAX_NESTING: usize = 100;
+
+/// *Parser* walks over the tokens of the lexer, comments are skipped.
+pub struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    spans: Vec<Span>,
+    position: usize,
+    /// Number of `?` parameters seen in the current statement.
+    anonymous_parameters: usize,
+    /// Levels of nesting at the current token
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Result<Self, ParseError> {
+        let (tokens, spans) = Lexer::new(input)
+            .tokenize_with_spans()?
+            .into_iter()
+            .filter(|(t, _)| !matches!(t, Token::SingleLineComment(_) | Token::MultiLineComment(_)))
+            .unzip();
+        Ok(Self {
+            tokens,
+            spans,
+            position: 0,
+            anonymous_parameters: 0,
+            depth: 0,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    pub(crate) fn next_anonymous_parameter(&mut self) -> usize {
+        self.anonymous_parameters += 1;
+        self.anonymous_parameters
+    }
+
+    /// Parses one level deeper, the levels the parse adds itself are dropped after it.
+    pub(crate) fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let depth = self.depth;
+        let result = self.deepen().and_then(|()| parse(self));
+        self.depth = depth;
+        result
+    }
+
+    /// Adds a level of nesting, too many fail the statement.
+    pub(crate) fn deepen(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        match self.depth > MAX_NESTING {
+            true => Err(ParseError::TooDeep),
+            false => Ok(()),
+        }
+    }
+
+    /// Span of the last consumed token.
+    pub(crate) fn previous_span(&self) -> Span {
+        self.position
+            .checked_sub(1)
+            .and_then(|position| self.spans.get(position))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.position)
+    }
+
+    pub(crate) fn peek_nth(&self, n: usize) -> Option<&Token<'a>> {
+        self.tokens.get(self.position + n)
+    }
+
+    pub(crate) fn next_token(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Consumes the next token only if it is the expected one.
+    pub(crate) fn consume(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next token only if it is the unreserved word, e.g. `interval`, in any case.
+    pub(crate) fn consume_word(&mut self, word: &str) -> bool {
+        if self.peek_word(word, 0) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the nth token from the current one is the unreserved word.
+    pub(crate) fn peek_word(&self, word: &str, n: usize) -> bool {
+        matches!(
+            self.peek_nth(n),
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            }) if first_name.eq_ignore_ascii_case(word)
+        )
+    }
+
+    pub(crate) fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.consume(&token) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("{:?}", token))
+        }
+    }
+
+    /// Reports the next token (or the end of input) as unexpected.
+    pub(crate) fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        match self.peek() {
+            Some(token) => self.unexpected_token(token, expected),
+            None => self.unexpected_end(expected),
+        }
+    }
+
+    pub(crate) fn unexpected_token<T>(
+        &self,
+        token: &Token,
+        expected: &str,
+    ) -> Result<T, ParseError> {
+        Err(ParseError::UnexpectedToken {
+            expected: expected.to_string(),
+            found: format!("{:?}", token),
+        })
+    }
+
+    pub(crate) fn unexpected_end<T>(&self, expected: &str) -> Result<T, ParseError> {
+        Err(ParseError::UnexpectedEnd {
+            expected: expected.to_string(),
+        })
+    }
+
+    /// Parses a name without dots, e.g. a column or an alias.
+    pub(crate) fn parse_identifier(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                ..
+            }) => {
+                let name = first_name.to_string();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("identifier"),
+        }
+    }
+
+    pub(crate) fn parse_comma_separated<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut items = vec![f(self)?];
+        while self.consume(&Token::Comma) {
+            items.push(f(self)?);
+        }
+        Ok(items)
+    }
+
+    /// Parses `(<item>, <item>, ...)`.
+    pub(crate) fn parse_parenthesized<T>(
+        &mut self,
+        f: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        self.expect(Token::OpenParen)?;
+        let items = self.parse_comma_separated(f)?;
+        self.expect(Token::CloseParen)?;
+        Ok(items)
+    }
+
+    /// Runs `f` only if the next token is `token`, `f` consumes the token itself.
+    pub(crate) fn parse_if<T>(
+        &mut self,
+        token: &Token,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Option<T>, ParseError> {
+        if self.peek() == Some(token) {
+            f(self).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Parses a possibly dotted name of a setting, e.g. `app.user_id`.
+    fn parse_variable_name(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Identifier {
+                first_name,
+                second_name,
+                third_name: None,
+            }) => {
+                let name = match second_name {
+                    Some(second_name) => format!("{}.{}", first_name, second_name),
+                    None => first_name.to_string(),
+                };
+                self.position += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("setting name"),
+        }
+    }
+
+    /// Parses an optional `returning <columns>` clause.
+    fn parse_returning(&mut self) -> Result<Vec<ColumnStatement>, ParseError> {
+        if self.consume(&Token::Returning) {
+            self.parse_comma_separated(ColumnStatement::parse)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Parses an optional `[as] <alias>`.
+    fn parse_alias(&mut self) -> Result<Option<String>, ParseError> {
+        if self.consume(&Token::As) {
+            return self.parse_identifier().map(Some);
+        }
+        match self.peek() {
+            Some(Token::Identifier {
+                second_name: None, ..
+            }) => self.parse_identifier().map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Queries {
+    pub queries: Vec<Query>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Query {
+    Select(SelectQuery),
+    Insert(InsertQuery),
+    Update(UpdateQuery),
+    Delete(DeleteQuery),
+    Begin(BeginQuery),
+    Commit,
+    Rollback(RollbackQuery),
+    Savepoint(SavepointQuery),
+    Release(ReleaseQuery),
+    SetVariable(SetVariableQuery),
+    Show(ShowQuery),
+    Explain(ExplainQuery),
+    Prepare(PrepareQuery),
+    Execute(ExecuteQuery),
+    Deallocate(DeallocateQuery),
+    CreateTable(CreateTableQuery),
+    CreateIndex(CreateIndexQuery),
+    CreateView(CreateViewQuery),
+    CreateSequence(CreateSequenceQuery),
+    CreateSchema(CreateSchemaQuery),
+    Drop(DropQuery),
+    Analyze(AnalyzeQuery),
+}
+
+/// *SelectQuery* is a struct that represents a select query
+/// select <select_statement>
+/// from <from_statement>
+/// where <where_statement>
+/// group by <group_by_statement>
+/// having <having_statement>
+/// union [all] <select> ...
+/// order by <order_by_statement>
+/// limit <limit_statement>
+#[derive(Debug, PartialEq, Clone)]
+pub struct SelectQuery {
+    pub select_statement: SelectStatement,
+    pub from_statement: Option<FromStatement>,
+    pub where_statement: Option<WhereStatement>,
+    pub group_by_statement: Option<GroupByStatement>,
+    pub having_statement: Option<HavingStatement>,
+    /// Selects whose rows are added to the rows of this one, `order by` and `limit` apply
+    /// to the rows of all of them
+    pub unions: Vec<UnionStatement>,
+    pub order_by_statement: Option<OrderByStatement>,
+    pub limit_statement: Option<LimitStatement>,
+}
+
+/// union [all | distinct] <select>
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnionStatement {
+    pub all: bool,
+    /// Without unions, `order by` and `limit` of its own
+    pub query: SelectQuery,
+}
+
+/// *InsertQuery* is a struct that represents an insert query
+/// insert into <table>
+/// [(<columns>)]
+/// values (<values>), ... | <select_query> | default values
+/// [on conflict [(<columns>)] do nothing | do update set <assignments> [where <condition>]]
+/// [returning <columns>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct InsertQuery {
+    pub table: TableStatement,
+    pub columns: Vec<String>,
+    pub source: InsertSource,
+    pub on_conflict: Option<OnConflict>,
+    pub returning: Vec<ColumnStatement>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Select(Box<SelectQuery>),
+    DefaultValues,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OnConflict {
+    pub columns: Vec<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConflictAction {
+    DoNothing,
+    DoUpdate {
+        set_statement: SetStatement,
+        where_statement: Option<WhereStatement>,
+    },
+}
+
+/// *UpdateQuery* is a struct that represents an update query
+/// update <table>
+/// set <set_statement>
+/// [from <from_statement>]
+/// [where <where_statement>]
+/// [returning <columns>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpdateQuery {
+    pub table: TableStatement,
+    pub set_statement: SetStatement,
+    pub from_statement: Option<FromStatement>,
+    pub where_statement: Option<WhereStatement>,
+    pub returning: Vec<ColumnStatement>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetStatement {
+    pub assignments: Vec<Assignment>,
+}
+
+/// <column> = <value> | (<column>, ...) = (<value>, ...)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Assignment {
+    pub columns: Vec<String>,
+    pub value: Expr,
+}
+
+/// *DeleteQuery* is a struct that represents a delete query
+/// delete from <table>
+/// [using <using_statement>]
+/// [where <where_statement>]
+/// [returning <columns>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeleteQuery {
+    pub table: TableStatement,
+    pub using_statement: Option<FromStatement>,
+    pub where_statement: Option<WhereStatement>,
+    pub returning: Vec<ColumnStatement>,
+}
+
+/// *BeginQuery* is a struct that represents a start of a transaction
+/// begin [transaction] | start transaction
+/// [isolation level <isolation_level>]
+/// [read only | read write]
+#[derive(Debug, PartialEq, Clone)]
+pub struct BeginQuery {
+    pub isolation_level: Option<IsolationLevel>,
+    pub access_mode: Option<AccessMode>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AccessMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// rollback [transaction] [to [savepoint] <savepoint>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct RollbackQuery {
+    pub savepoint: Option<String>,
+}
+
+/// savepoint <name>
+#[derive(Debug, PartialEq, Clone)]
+pub struct SavepointQuery {
+    pub name: String,
+}
+
+/// release [savepoint] <name>
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReleaseQuery {
+    pub name: String,
+}
+
+/// set <name> {= | to} <value>
+/// a bare word value (e.g. `set search_path = public`) is kept as a column expression
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetVariableQuery {
+    pub name: String,
+    pub value: Expr,
+}
+
+/// show <name> | show all
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShowQuery {
+    /// `None` for `show all`
+    pub name: Option<String>,
+}
+
+/// explain [analyze] <query>
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExplainQuery {
+    pub analyze: bool,
+    pub query: Box<Query>,
+}
+
+/// prepare <name> [(<data_type>, ...)] as <select | insert | update | delete>
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrepareQuery {
+    pub name: String,
+    /// Types of the first parameters, the rest are inferred
+    pub parameter_types: Vec<DataType>,
+    pub statement: Box<Query>,
+}
+
+/// execute <name> [(<argument>, ...)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExecuteQuery {
+    pub name: String,
+    pub arguments: Vec<Expr>,
+}
+
+/// deallocate [prepare] <name | all>
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeallocateQuery {
+    /// `None` for `deallocate all`
+    pub name: Option<String>,
+}
+
+/// create table [if not exists] <table>
+/// (<column> <data_type> [<column_constraint> ...], ... [, <table_constraint>, ...])
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateTableQuery {
+    pub name: ObjectName,
+    pub if_not_exists: bool,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+}
+
+/// Name of a table, view, index or sequence, optionally qualified with the schema.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectName {
+    pub schema_name: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub data_type: DataType,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColumnConstraint {
+    NotNull,
+    Null,
+    Default(Expr),
+    PrimaryKey,
+    Unique,
+    /// references <table> [(<column>)], the primary key when the column is omitted
+    References {
+        table: ObjectName,
+        column: Option<String>,
+    },
+}
+
+/// [constraint <name>] <kind>
+#[derive(Debug, PartialEq, Clone)]
+pub struct TableConstraint {
+    pub name: Option<String>,
+    pub kind: TableConstraintKind,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TableConstraintKind {
+    PrimaryKey(Vec<String>),
+    Unique(Vec<String>),
+    /// foreign key (<column>, ...) references <table> [(<column>, ...)]
+    ForeignKey {
+        columns: Vec<String>,
+        table: ObjectName,
+        referenced_columns: Vec<String>,
+    },
+}
+
+/// create [unique] index [if not exists] <name> on <table> (<column>, ...)
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateIndexQuery {
+    /// The index is created in the schema of its table
+    pub name: String,
+    pub unique: bool,
+    pub if_not_exists: bool,
+    pub table: ObjectName,
+    pub columns: Vec<String>,
+}
+
+/// create view <name> as <select>
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateViewQuery {
+    pub name: ObjectName,
+    pub query: SelectQuery,
+}
+
+/// create sequence [if not exists] <name> [increment [by] <number>] [start [with] <number>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateSequenceQuery {
+    pub name: ObjectName,
+    pub if_not_exists: bool,
+    pub increment: Option<i64>,
+    pub start: Option<i64>,
+}
+
+/// create schema [if not exists] <name>
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateSchemaQuery {
+    pub name: String,
+    pub if_not_exists: bool,
+}
+
+/// drop {table | view | index | sequence | schema} [if exists] <name>, ...
+#[derive(Debug, PartialEq, Clone)]
+pub struct DropQuery {
+    pub object_type: ObjectType,
+    pub if_exists: bool,
+    pub names: Vec<ObjectName>,
+}
+
+/// analyze [<table>]
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnalyzeQuery {
+    /// `None` analyzes every table
+    pub table: Option<ObjectName>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ObjectType {
+    Table,
+    View,
+    Index,
+    Sequence,
+    Schema,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SelectStatement {
+    pub columns: Vec<ColumnStatement>,
+    pub distinct: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FromStatement {
+    pub tables: Vec<TableStatement>,
+    pub joins: Vec<JoinStatement>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WhereStatement {
+    pub condition: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OrderByStatement {
+    pub columns: Vec<OrderByColumn>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OrderByColumn {
+    pub expr: Expr,
+    pub order: Order,
+}
+
+/// Direction of an `ORDER BY` column and the place of its `NULL`s, which sort as larger
+/// than any value unless `NULLS FIRST` or `NULLS LAST` is given.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Order {
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl Order {
+    pub const ASC: Order = Order {
+        descending: false,
+        nulls_first: false,
+    };
+    pub const DESC: Order = Order {
+        descending: true,
+        nulls_first: true,
+    };
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LimitStatement {
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HavingStatement {
+    pub condition: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GroupByStatement {
+    pub columns: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ColumnStatement {
+    /// `*`
+    Wildcard,
+    /// `[<schema>.]<table>.*`
+    QualifiedWildcard {
+        schema_name: Option<String>,
+        table_name: String,
+        span: Span,
+    },
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+    },
+}
+
+/// Spans of names aren't compared
+impl PartialEq for ColumnStatement {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ColumnStatement::Wildcard, ColumnStatement::Wildcard) => true,
+            (
+                ColumnStatement::QualifiedWildcard {
+                    schema_name,
+                    table_name,
+                    ..
+                },
+                ColumnStatement::QualifiedWildcard {
+                    schema_name: other_schema_name,
+                    table_name: other_table_name,
+                    ..
+                },
+            ) => schema_name == other_schema_name && table_name == other_table_name,
+            (
+                ColumnStatement::Expr { expr, alias },
+                ColumnStatement::Expr {
+                    expr: other_expr,
+                    alias: other_alias,
+                },
+            ) => expr == other_expr && alias == other_alias,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableStatement {
+    pub schema_name: Option<String>,
+    pub table_name: String,
+    /// Arguments of a table function called in `from`, `None` for a table
+    pub arguments: Option<Vec<Expr>>,
+    pub alias: Option<String>,
+    /// Position of the table name in the query text, it isn't compared
+    pub span: Span,
+}
+
+impl PartialEq for TableStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema_name == other.schema_name
+            && self.table_name == other.table_name
+            && self.arguments == other.arguments
+            && self.alias == other.alias
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JoinStatement {
+    pub table: TableStatement,
+    pub join_type: JoinType,
+    /// `None` only for cross joins
+    pub on: Option<Expr>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+}
+
+impl Queries {
+    /// Parses queries separated by `;`, the last `;` is optional.
+    pub fn parse(input: &str) -> Result<Queries, ParseError> {
+        let mut parser = Parser::new(input)?;
+        let mut queries = vec![];
+        loop {
+            while parser.consume(&Token::Semicolon) {}
+            if parser.is_finished() {
+                return Ok(Queries { queries });
+            }
+            queries.push(Query::parse(&mut parser)?);
+            if !parser.is_finished() {
+                parser.expect(Token::Semicolon)?;
+            }
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(parser: &mut Parser) -> Result<Query, ParseError> {
+        parser.anonymous_parameters = 0;
+        match parser.peek() {
+            Some(Token::Select) => SelectQuery::parse(parser).map(Query::Select),
+            Some(Token::Insert) => InsertQuery::parse(parser).map(Query::Insert),
+            Some(Token::Update) => UpdateQuery::parse(parser).map(Query::Update),
+            Some(Token::Delete) => DeleteQuery::parse(parser).map(Query::Delete),
+            Some(Token::Begin | Token::Start) => BeginQuery::parse(parser).map(Query::Begin),
+            Some(Token::Commit) => {
+                parser.next_token();
+                parser.consume(&Token::Transaction);
+                Ok(Query::Commit)
+            }
+            Some(Token::Rollback) => RollbackQuery::parse(parser).map(Query::Rollback),
+            Some(Token::Savepoint) => SavepointQuery::parse(parser).map(Query::Savepoint),
+            Some(Token::Release) => ReleaseQuery::parse(parser).map(Query::Release),
+            Some(Token::Set) => SetVariableQuery::parse(parser).map(Query::SetVariable),
+            Some(Token::Show) => ShowQuery::parse(parser).map(Query::Show),
+            Some(Token::Explain) => ExplainQuery::parse(parser).map(Query::Explain),
+            Some(Token::Prepare) => PrepareQuery::parse(parser).map(Query::Prepare),
+            Some(Token::Execute) => ExecuteQuery::parse(parser).map(Query::Execute),
+            Some(Token::Deallocate) => DeallocateQuery::parse(parser).map(Query::Deallocate),
+            Some(Token::Create) => Self::parse_create(parser),
+            Some(Token::Drop) => DropQuery::parse(parser).map(Query::Drop),
+            Some(Token::Analyze) => AnalyzeQuery::parse(parser).map(Query::Analyze),
+            _ => parser.unexpected("query"),
+        }
+    }
+}
+
+impl Query {
+    fn parse_create(parser: &mut Parser) -> Result<Query, ParseError> {
+        match parser.peek_nth(1) {
+            Some(Token::Table) => CreateTableQuery::parse(parser).map(Query::CreateTable),
+            Some(Token::Unique | Token::Index) => {
+                CreateIndexQuery::parse(parser).map(Query::CreateIndex)
+            }
+            Some(Token::View) => CreateViewQuery::parse(parser).map(Query::CreateView),
+            Some(Token::Sequence) => CreateSequenceQuery::parse(parser).map(Query::CreateSequence),
+            Some(Token::Schema) => CreateSchemaQuery::parse(parser).map(Query::CreateSchema),
+            Some(token) => parser.unexpected_token(token, "table, index, view, sequence or schema"),
+            None => parser.unexpected_end("table, index, view, sequence or schema"),
+        }
+    }
+}
+
+impl SelectQuery {
+    pub fn parse(parser: &mut Parser) -> Result<SelectQuery, ParseError> {
+        parser.expect(Token::Select)?;
+        parser.nested(|parser| {
+            let mut query = SelectQuery::parse_clauses(parser)?;
+            while parser.consume(&Token::Union) {
+                let all = parser.consume(&Token::All);
+                if !all {
+                    parser.consume(&Token::Distinct);
+                }
+                parser.expect(Token::Select)?;
+                query.unions.push(UnionStatement {
+                    all,
+                    query: SelectQuery::parse_clauses(parser)?,
+                });
+            }
+            query.order_by_statement = parser.parse_if(&Token::Order, OrderByStatement::parse)?;
+            query.limit_statement = LimitStatement::parse(parser)?;
+            Ok(query)
+        })
+    }
+
+    /// The clauses from the select list to `having`, `select` is already consumed.
+    fn parse_clauses(parser: &mut Parser) -> Result<SelectQuery, ParseError> {
+        Ok(SelectQuery {
+            select_statement: SelectStatement::parse(parser)?,
+            from_statement: parser.parse_if(&Token::From, FromStatement::parse)?,
+            where_statement: parser.parse_if(&Token::Where, WhereStatement::parse)?,
+            group_by_statement: parser.parse_if(&Token::Group, GroupByStatement::parse)?,
+            having_statement: parser.parse_if(&Token::Having, HavingStatement::parse)?,
+            unions: vec![],
+            order_by_statement: None,
+            limit_statement: None,
+        })
+    }
+}
+
+impl SelectStatement {
+    fn parse(parser: &mut Parser) -> Result<SelectStatement, ParseError> {
+        let distinct = parser.consume(&Token::Distinct);
+        if !distinct {
+            parser.consume(&Token::All);
+        }
+        let columns = parser.parse_comma_separated(ColumnStatement::parse)?;
+
+        Ok(SelectStatement { columns, distinct })
+    }
+}
+
+impl ColumnStatement {
+    fn parse(parser: &mut Parser) -> Result<ColumnStatement, ParseError> {
+        if parser.consume(&Token::Asterisk) {
+            return Ok(ColumnStatement::Wildcard);
+        }
+        if let (
+            Some(Token::Identifier {
+                first_name,
+                second_name,
+                third_name: None,
+            }),
+            Some(Token::Dot),
+            Some(Token::Asterisk),
+        ) = (parser.peek(), parser.peek_nth(1), parser.peek_nth(2))
+        {
+            let span = Span {
+                start: parser.spans[parser.position].start,
+                end: parser.spans[parser.position + 2].end,
+            };
+            let column = match second_name {
+                Some(table_name) => ColumnStatement::QualifiedWildcard {
+                    schema_name: Some(first_name.to_string()),
+                    table_name: table_name.to_string(),
+                    span,
+                },
+                None => ColumnStatement::QualifiedWildcard {
+                    schema_name: None,
+                    table_name: first_name.to_string(),
+                    span,
+                },
+            };
+            parser.position += 3;
+            return Ok(column);
+        }
+        let expr = Expr::parse(parser)?;
+        let alias = parser.parse_alias()?;
+        Ok(ColumnStatement::Expr { expr, alias })
+    }
+}
+
+impl FromStatement {
+    fn parse(parser: &mut Parser) -> Result<FromStatement, ParseError> {
+        parser.expect(Token::From)?;
+        Self::parse_tables(parser)
+    }
+
+    /// Parses tables and joins, the leading keyword (`from` or `using`) is already consumed.
+    fn parse_tables(parser: &mut Parser) -> Result<FromStatement, ParseError> {
+        let tables = parser.parse_comma_separated(TableStatement::parse_from)?;
+        let mut joins = vec![];
+        while let Some(join) = JoinStatement::parse(parser)? {
+            joins.push(join);
+        }
+        Ok(FromStatement { tables, joins })
+    }
+}
+
+impl TableStatement {
+    fn parse(parser: &mut Parser) -> Result<TableStatement, ParseError> {
+        let (schema_name, table_name, span) = Self::parse_name(parser)?;
+        Ok(TableStatement {
+            schema_name,
+            table_name,
+            arguments: None,
+            alias: parser.parse_alias()?,
+            span,
+        })
+    }
+
+    /// Parses a table or a table function call, `<name>([<argument>, ...])`.
+    fn parse_from(parser: &mut Parser) -> Result<TableStatement, ParseError> {
+        let (schema_name, table_name, span) = Self::parse_name(parser)?;
+        let arguments = match parser.peek() {
+            Some(Token::OpenParen) if schema_name.is_none() => {
+                parser.next_token();
+                let arguments = match parser.consume(&Token::CloseParen) {
+                    true => vec![],
+                    false => {
+                        let arguments = parser.parse_comma_separated(Expr::parse)?;
+                        parser.expect(Token::CloseParen)?;
+                        arguments
+                    }
+                };
+                Some(arguments)
+            }
+            _ => None,
+        };
+        Ok(TableStatement {
+            schema_name,
+            table_name,
+            arguments,
+            alias: parser.parse_alias()?,
+            span,
+        })
+    }
+
+    fn parse_name(parser: &mut Parser) -> Result<(Option<String>, String, Span), ParseError> {
+        let (schema_name, table_name) = match parser.next_token() {
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                ..
+            }) => (None, first_name.to_string()),
+            Some(Token::Identifier {
+                first_name,
+                second_name: Some(second_name),
+                third_name: None,
+            }) => (Some(first_name.to_string()), second_name.to_string()),
+            Some(token) => return parser.unexpected_token(&token, "table name"),
+            None => return parser.unexpected_end("table name"),
+        };
+        Ok((schema_name, table_name, parser.previous_span()))
+    }
+}
+
+impl JoinStatement {
+    /// Returns `None` if the next token doesn't start a join.
+    fn parse(parser: &mut Parser) -> Result<Option<JoinStatement>, ParseError> {
+        let join_type = match parser.peek() {
+            Some(Token::Join) => JoinType::Inner,
+            Some(Token::Inner) => {
+                parser.next_token();
+                JoinType::Inner
+            }
+            Some(Token::Cross) => {
+                parser.next_token();
+                JoinType::Cross
+            }
+            Some(Token::Left | Token::Right | Token::Full) => {
+                let join_type = match parser.next_token() {
+                    Some(Token::Left) => JoinType::Left,
+                    Some(Token::Right) => JoinType::Right,
+                    _ => JoinType::Full,
+                };
+                parser.consume(&Token::Outer);
+                join_type
+            }
+            _ => return Ok(None),
+        };
+        parser.expect(Token::Join)?;
+        let table = TableStatement::parse_from(parser)?;
+        let on = if join_type == JoinType::Cross {
+            None
+        } else {
+            parser.expect(Token::On)?;
+            Some(Expr::parse(parser)?)
+        };
+        Ok(Some(JoinStatement {
+            table,
+            join_type,
+            on,
+        }))
+    }
+}
+
+impl WhereStatement {
+    fn parse(parser: &mut Parser) -> Result<WhereStatement, ParseError> {
+        parser.expect(Token::Where)?;
+        Ok(WhereStatement {
+            condition: Expr::parse(parser)?,
+        })
+    }
+}
+
+impl GroupByStatement {
+    fn parse(parser: &mut Parser) -> Result<GroupByStatement, ParseError> {
+        parser.expect(Token::Group)?;
+        parser.expect(Token::By)?;
+        Ok(GroupByStatement {
+            columns: parser.parse_comma_separated(Expr::parse)?,
+        })
+    }
+}
+
+impl HavingStatement {
+    fn parse(parser: &mut Parser) -> Result<HavingStatement, ParseError> {
+        parser.expect(Token::Having)?;
+        Ok(HavingStatement {
+            condition: Expr::parse(parser)?,
+        })
+    }
+}
+
+impl OrderByStatement {
+    fn parse(parser: &mut Parser) -> Result<OrderByStatement, ParseError> {
+        parser.expect(Token::Order)?;
+        parser.expect(Token::By)?;
+        let columns = parser.parse_comma_separated(|parser| {
+            let expr = Expr::parse(parser)?;
+            let mut order = if parser.consume(&Token::Desc) {
+                Order::DESC
+            } else {
+                parser.consume(&Token::Asc);
+                Order::ASC
+            };
+            if parser.consume_word("nulls") {
+                order.nulls_first = match parser.consume_word("first") {
+                    true => true,
+                    false if parser.consume_word("last") => false,
+                    false => return parser.unexpected("FIRST or LAST"),
+                };
+            }
+            Ok(OrderByColumn { expr, order })
+        })?;
+        Ok(OrderByStatement { columns })
+    }
+}
+
+impl LimitStatement {
+    /// limit <count> [offset <count>] | offset <count>
+    fn parse(parser: &mut Parser) -> Result<Option<LimitStatement>, ParseError> {
+        let limit = if parser.consume(&Token::Limit) {
+            Some(Self::parse_count(parser)?)
+        } else {
+            None
+        };
+        let offset = if parser.consume(&Token::Offset) {
+            Some(Self::parse_count(parser)?)
+        } else {
+            None
+        };
+        if limit.is_none() && offset.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(LimitStatement { limit, offset }))
+    }
+
+    fn parse_count(parser: &mut Parser) -> Result<u64, ParseError> {
+        if let Some(Token::NumericLiteral(value)) = parser.peek() {
+            if let Ok(count) = value.parse() {
+                parser.next_token();
+                return Ok(count);
+            }
+        }
+        parser.unexpected("non-negative integer")
+    }
+}
+
+impl InsertQuery {
+    pub fn parse(parser: &mut Parser) -> Result<InsertQuery, ParseError> {
+        parser.expect(Token::Insert)?;
+        parser.expect(Token::Into)?;
+        let table = TableStatement::parse(parser)?;
+        let columns = if parser.peek() == Some(&Token::OpenParen)
+            && parser.peek_nth(1) != Some(&Token::Select)
+        {
+            parser.parse_parenthesized(Parser::parse_identifier)?
+        } else {
+            vec![]
+        };
+        let source = InsertSource::parse(parser)?;
+        let on_conflict = parser.parse_if(&Token::On, OnConflict::parse)?;
+        let returning = parser.parse_returning()?;
+
+        Ok(InsertQuery {
+            table,
+            columns,
+            source,
+            on_conflict,
+            returning,
+        })
+    }
+}
+
+impl InsertSource {
+    fn parse(parser: &mut Parser) -> Result<InsertSource, ParseError> {
+        match parser.peek() {
+            Some(Token::Values) => {
+                parser.next_token();
+                let rows = parser
+                    .parse_comma_separated(|parser| parser.parse_parenthesized(Expr::parse))?;
+                Ok(InsertSource::Values(rows))
+            }
+            Some(Token::Default) => {
+                parser.next_token();
+                parser.expect(Token::Values)?;
+                Ok(InsertSource::DefaultValues)
+            }
+            Some(Token::Select) => Ok(InsertSource::Select(Box::new(SelectQuery::parse(parser)?))),
+            Some(Token::OpenParen) => {
+                parser.next_token();
+                let query = SelectQuery::parse(parser)?;
+                parser.expect(Token::CloseParen)?;
+                Ok(InsertSource::Select(Box::new(query)))
+            }
+            _ => parser.unexpected("values, select or default values"),
+        }
+    }
+}
+
+impl OnConflict {
+    fn parse(parser: &mut Parser) -> Result<OnConflict, ParseError> {
+        parser.expect(Token::On)?;
+        parser.expect(Token::Conflict)?;
+        let columns = if parser.peek() == Some(&Token::OpenParen) {
+            parser.parse_parenthesized(Parser::parse_identifier)?
+        } else {
+            vec![]
+        };
+        parser.expect(Token::Do)?;
+        let action = if parser.consume(&Token::Nothing) {
+            ConflictAction::DoNothing
+        } else {
+            parser.expect(Token::Update)?;
+            ConflictAction::DoUpdate {
+                set_statement: SetStatement::parse(parser)?,
+                where_statement: parser.parse_if(&Token::Where, WhereStatement::parse)?,
+            }
+        };
+        Ok(OnConflict { columns, action })
+    }
+}
+
+impl UpdateQuery {
+    pub fn parse(parser: &mut Parser) -> Result<UpdateQuery, ParseError> {
+        parser.expect(Token::Update)?;
+        Ok(UpdateQuery {
+            table: TableStatement::parse(parser)?,
+            set_statement: SetStatement::parse(parser)?,
+            from_statement: parser.parse_if(&Token::From, FromStatement::parse)?,
+            where_statement: parser.parse_if(&Token::Where, WhereStatement::parse)?,
+            returning: parser.parse_returning()?,
+        })
+    }
+}
+
+impl SetStatement {
+    fn parse(parser: &mut Parser) -> Result<SetStatement, ParseError> {
+        parser.expect(Token::Set)?;
+        Ok(SetStatement {
+            assignments: parser.parse_comma_separated(Assignment::parse)?,
+        })
+    }
+}
+
+impl Assignment {
+    fn parse(parser: &mut Parser) -> Result<Assignment, ParseError> {
+        let columns = if parser.peek() == Some(&Token::OpenParen) {
+            parser.parse_parenthesized(Parser::parse_identifier)?
+        } else {
+            vec![parser.parse_identifier()?]
+        };
+        parser.expect(Token::Equals)?;
+        Ok(Assignment {
+            columns,
+            value: Expr::parse(parser)?,
+        })
+    }
+}
+
+impl DeleteQuery {
+    pub fn parse(parser: &mut Parser) -> Result<DeleteQuery, ParseError> {
+        parser.expect(Token::Delete)?;
+        parser.expect(Token::From)?;
+        let table = TableStatement::parse(parser)?;
+        let using_statement = if parser.consume(&Token::Using) {
+            Some(FromStatement::parse_tables(parser)?)
+        } else {
+            None
+        };
+        Ok(DeleteQuery {
+            table,
+            using_statement,
+            where_statement: parser.parse_if(&Token::Where, WhereStatement::parse)?,
+            returning: parser.parse_returning()?,
+        })
+    }
+}
+
+impl BeginQuery {
+    pub fn parse(parser: &mut Parser) -> Result<BeginQuery, ParseError> {
+        if parser.consume(&Token::Start) {
+            parser.expect(Token::Transaction)?;
+        } else {
+            parser.expect(Token::Begin)?;
+            parser.consume(&Token::Transaction);
+        }
+        let mut query = BeginQuery {
+            isolation_level: None,
+            access_mode: None,
+        };
+        loop {
+            match parser.peek() {
+                Some(Token::Isolation) if query.isolation_level.is_none() => {
+                    query.isolation_level = Some(IsolationLevel::parse(parser)?);
+                }
+                Some(Token::Read) if query.access_mode.is_none() => {
+                    query.access_mode = Some(AccessMode::parse(parser)?);
+                }
+                _ => return Ok(query),
+            }
+            parser.consume(&Token::Comma);
+        }
+    }
+}
+
+impl IsolationLevel {
+    fn parse(parser: &mut Parser) -> Result<IsolationLevel, ParseError> {
+        parser.expect(Token::Isolation)?;
+        parser.expect(Token::Level)?;
+        match parser.next_token() {
+            Some(Token::Serializable) => Ok(IsolationLevel::Serializable),
+            Some(Token::Repeatable) => {
+                parser.expect(Token::Read)?;
+                Ok(IsolationLevel::RepeatableRead)
+            }
+            Some(Token::Read) => match parser.next_token() {
+                Some(Token::Committed) => Ok(IsolationLevel::ReadCommitted),
+                Some(Token::Uncommitted) => Ok(IsolationLevel::ReadUncommitted),
+                Some(token) => parser.unexpected_token(&token, "committed or uncommitted"),
+                None => parser.unexpected_end("committed or uncommitted"),
+            },
+            Some(token) => parser.unexpected_token(&token, "isolation level"),
+            None => parser.unexpected_end("isolation level"),
+        }
+    }
+}
+
+impl AccessMode {
+    fn parse(parser: &mut Parser) -> Result<AccessMode, ParseError> {
+        parser.expect(Token::Read)?;
+        match parser.next_token() {
+            Some(Token::Only) => Ok(AccessMode::ReadOnly),
+            Some(Token::Write) => Ok(AccessMode::ReadWrite),
+            Some(token) => parser.unexpected_token(&token, "only or write"),
+            None => parser.unexpected_end("only or write"),
+        }
+    }
+}
+
+impl RollbackQuery {
+    pub fn parse(parser: &mut Parser) -> Result<RollbackQuery, ParseError> {
+        parser.expect(Token::Rollback)?;
+        parser.consume(&Token::Transaction);
+        let savepoint = if parser.consume(&Token::To) {
+            parser.consume(&Token::Savepoint);
+            Some(parser.parse_identifier()?)
+        } else {
+            None
+        };
+        Ok(RollbackQuery { savepoint })
+    }
+}
+
+impl SavepointQuery {
+    pub fn parse(parser: &mut Parser) -> Result<SavepointQuery, ParseError> {
+        parser.expect(Token::Savepoint)?;
+        Ok(SavepointQuery {
+            name: parser.parse_identifier()?,
+        })
+    }
+}
+
+impl ReleaseQuery {
+    pub fn parse(parser: &mut Parser) -> Result<ReleaseQuery, ParseError> {
+        parser.expect(Token::Release)?;
+        parser.consume(&Token::Savepoint);
+        Ok(ReleaseQuery {
+            name: parser.parse_identifier()?,
+        })
+    }
+}
+
+impl SetVariableQuery {
+    pub fn parse(parser: &mut Parser) -> Result<SetVariableQuery, ParseError> {
+        parser.expect(Token::Set)?;
+        let name = parser.parse_variable_name()?;
+        if !parser.consume(&Token::To) {
+            parser.expect(Token::Equals)?;
+        }
+        Ok(SetVariableQuery {
+            name,
+            value: Expr::parse(parser)?,
+        })
+    }
+}
+
+impl ShowQuery {
+    pub fn parse(parser: &mut Parser) -> Result<ShowQuery, ParseError> {
+        parser.expect(Token::Show)?;
+        if parser.consume(&Token::All) {
+            return Ok(ShowQuery { name: None });
+        }
+        Ok(ShowQuery {
+            name: Some(parser.parse_variable_name()?),
+        })
+    }
+}
+
+impl ExplainQuery {
+    pub fn parse(parser: &mut Parser) -> Result<ExplainQuery, ParseError> {
+        parser.expect(Token::Explain)?;
+        let analyze = parser.consume(&Token::Analyze);
+        Ok(ExplainQuery {
+            analyze,
+            query: Box::new(parser.nested(Query::parse)?),
+        })
+    }
+}
+
+impl PrepareQuery {
+    pub fn parse(parser: &mut Parser) -> Result<PrepareQuery, ParseError> {
+        parser.expect(Token::Prepare)?;
+        let name = parser.parse_identifier()?;
+        let parameter_types = if parser.peek() == Some(&Token::OpenParen) {
+            parser.parse_parenthesized(DataType::parse)?
+        } else {
+            vec![]
+        };
+        parser.expect(Token::As)?;
+        let statement = match parser.peek() {
+            Some(Token::Select) => Query::Select(SelectQuery::parse(parser)?),
+            Some(Token::Insert) => Query::Insert(InsertQuery::parse(parser)?),
+            Some(Token::Update) => Query::Update(UpdateQuery::parse(parser)?),
+            Some(Token::Delete) => Query::Delete(DeleteQuery::parse(parser)?),
+            _ => return parser.unexpected("select, insert, update or delete"),
+        };
+        Ok(PrepareQuery {
+            name,
+            parameter_types,
+            statement: Box::new(statement),
+        })
+    }
+}
+
+impl DataType {
+    pub(crate) fn parse(parser: &mut Parser) -> Result<DataType, ParseError> {
+        let data_type = match parser.next_token() {
+            Some(Token::Boolean) => DataType::Boolean,
+            Some(Token::TinyInt) => DataType::TinyInt,
+            Some(Token::SmallInt) => DataType::SmallInt,
+            Some(Token::Int | Token::Integer) => DataType::Integer,
+            Some(Token::BigInt) => DataType::BigInt,
+            Some(Token::Real) => DataType::Real,
+            Some(Token::Float | Token::Double) => DataType::Double,
+            Some(Token::Decimal | Token::Numeric) => {
+                DataType::Decimal(match Self::parse_modifiers(parser)?.as_slice() {
+                    [] => None,
+                    [precision] => Some((*precision, 0)),
+                    [precision, scale] if scale <= precision => Some((*precision, *scale)),
+                    _ => return parser.unexpected("precision and scale"),
+                })
+            }
+            Some(Token::VarChar | Token::Char) => {
+                DataType::VarChar(match Self::parse_modifiers(parser)?.as_slice() {
+                    [] => None,
+                    [length] => Some(*length),
+                    _ => return parser.unexpected("length"),
+                })
+            }
+            Some(Token::Text) => DataType::Text,
+            Some(Token::Date) => DataType::Date,
+            Some(Token::Time) => match Self::parse_time_zone(parser)? {
+                true => return parser.unexpected("WITHOUT TIME ZONE"),
+                false => DataType::Time,
+            },
+            Some(Token::Timestamp | Token::Datetime) => match Self::parse_time_zone(parser)? {
+                true => DataType::TimestampTz,
+                false => DataType::Timestamp,
+            },
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            }) if first_name.eq_ignore_ascii_case("interval") => DataType::Interval,
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                third_name: None,
+            }) if first_name.eq_ignore_ascii_case("timestamptz") => DataType::TimestampTz,
+            Some(token) => return parser.unexpected_token(&token, "data type"),
+            None => return parser.unexpected_end("data type"),
+        };
+        Ok(data_type)
+    }
+
+    /// `WITH TIME ZONE`, `WITHOUT TIME ZONE` or nothing after `time` or `timestamp`,
+    /// whether it is with time zone.
+    pub(crate) fn parse_time_zone(parser: &mut Parser) -> Result<bool, ParseError> {
+        let with_zone = match parser.peek() {
+            Some(Token::With) if parser.peek_nth(1) == Some(&Token::Time) => true,
+            _ if parser.peek_word("without", 0) => false,
+            _ => return Ok(false),
+        };
+        parser.next_token();
+        parser.expect(Token::Time)?;
+        if !parser.consume_word("zone") {
+            return parser.unexpected("ZONE");
+        }
+        Ok(with_zone)
+    }
+
+    /// `(<number>, ...)` after the type name, e.g. length of `varchar(20)`
+    fn parse_modifiers(parser: &mut Parser) -> Result<Vec<u32>, ParseError> {
+        if parser.peek() != Some(&Token::OpenParen) {
+            return Ok(vec![]);
+        }
+        parser.parse_parenthesized(|parser| match parser.next_token() {
+            Some(Token::NumericLiteral(value)) => match value.parse() {
+                Ok(value) => Ok(value),
+                Err(_) => parser.unexpected_token(&Token::NumericLiteral(value), "integer"),
+            },
+            Some(token) => parser.unexpected_token(&token, "integer"),
+            None => parser.unexpected_end("integer"),
+        })
+    }
+}
+
+impl ExecuteQuery {
+    pub fn parse(parser: &mut Parser) -> Result<ExecuteQuery, ParseError> {
+        parser.expect(Token::Execute)?;
+        let name = parser.parse_identifier()?;
+        let arguments = if parser.peek() == Some(&Token::OpenParen) {
+            parser.parse_parenthesized(Expr::parse)?
+        } else {
+            vec![]
+        };
+        Ok(ExecuteQuery { name, arguments })
+    }
+}
+
+impl DeallocateQuery {
+    pub fn parse(parser: &mut Parser) -> Result<DeallocateQuery, ParseError> {
+        parser.expect(Token::Deallocate)?;
+        parser.consume(&Token::Prepare);
+        if parser.consume(&Token::All) {
+            return Ok(DeallocateQuery { name: None });
+        }
+        Ok(DeallocateQuery {
+            name: Some(parser.parse_identifier()?),
+        })
+    }
+}
+
+impl Parser<'_> {
+    /// `if not exists` before the name of a created object.
+    fn parse_if_not_exists(&mut self) -> Result<bool, ParseError> {
+        if !self.consume(&Token::If) {
+            return Ok(false);
+        }
+        self.expect(Token::Not)?;
+        self.expect(Token::Exists)?;
+        Ok(true)
+    }
+
+    /// Integer with an optional minus sign
+    fn parse_integer(&mut self) -> Result<i64, ParseError> {
+        let negative = self.consume(&Token::Minus);
+        match self.next_token() {
+            Some(Token::NumericLiteral(value)) => match value.parse::<i64>() {
+                Ok(value) if negative => Ok(-value),
+                Ok(value) => Ok(value),
+                Err(_) => self.unexpected_token(&Token::NumericLiteral(value), "integer"),
+            },
+            Some(token) => self.unexpected_token(&token, "integer"),
+            None => self.unexpected_end("integer"),
+        }
+    }
+}
+
+impl AnalyzeQuery {
+    pub fn parse(parser: &mut Parser) -> Result<AnalyzeQuery, ParseError> {
+        parser.expect(Token::Analyze)?;
+        let table = match parser.peek() {
+            Some(Token::Identifier { .. }) => Some(ObjectName::parse(parser)?),
+            _ => None,
+        };
+        Ok(AnalyzeQuery { table })
+    }
+}
+
+impl ObjectName {
+    pub fn parse(parser: &mut Parser) -> Result<ObjectName, ParseError> {
+        match parser.next_token() {
+            Some(Token::Identifier {
+                first_name,
+                second_name: None,
+                ..
+            }) => Ok(ObjectName {
+                schema_name: None,
+                name: first_name.to_string(),
+            }),
+            Some(Token::Identifier {
+                first_name,
+                second_name: Some(second_name),
+                third_name: None,
+            }) => Ok(ObjectName {
+                schema_name: Some(first_name.to_string()),
+                name: second_name.to_string(),
+            }),
+            Some(token) => parser.unexpected_token(&token, "name"),
+            None => parser.unexpected_end("name"),
+        }
+    }
+}
+
+impl CreateTableQuery {
+    pub fn parse(parser: &mut Parser) -> Result<CreateTableQuery, ParseError> {
+        parser.expect(Token::Create)?;
+        parser.expect(Token::Table)?;
+        let if_not_exists = parser.parse_if_not_exists()?;
+        let name = ObjectName::parse(parser)?;
+        let mut query = CreateTableQuery {
+            name,
+            if_not_exists,
+            columns: vec![],
+            constraints: vec![],
+        };
+        parser.expect(Token::OpenParen)?;
+        loop {
+            match parser.peek() {
+                Some(Token::Constraint | Token::Primary | Token::Unique | Token::Foreign) => {
+                    query.constraints.push(TableConstraint::parse(parser)?)
+                }
+                _ if query.constraints.is_empty() => {
+                    query.columns.push(ColumnDefinition::parse(parser)?)
+                }
+                _ => return parser.unexpected("table constraint"),
+            }
+            if !parser.consume(&Token::Comma) {
+                break;
+            }
+        }
+        parser.expect(Token::CloseParen)?;
+        Ok(query)
+    }
+}
+
+impl ColumnDefinition {
+    fn parse(parser: &mut Parser) -> Result<ColumnDefinition, ParseError> {
+        let name = parser.parse_identifier()?;
+        let data_type = DataType::parse(parser)?;
+        let mut constraints = vec![];
+        while let Some(constraint) = ColumnConstraint::parse(parser)? {
+            constraints.push(constraint);
+        }
+        Ok(ColumnDefinition {
+            name,
+            data_type,
+            constraints,
+        })
+    }
+}
+
+impl ColumnConstraint {
+    fn parse(parser: &mut Parser) -> Result<Option<ColumnConstraint>, ParseError> {
+        let constraint = match parser.peek() {
+            Some(Token::Not) => {
+                parser.next_token();
+                parser.expect(Token::Null)?;
+                ColumnConstraint::NotNull
+            }
+            Some(Token::Null) => {
+                parser.next_token();
+                ColumnConstraint::Null
+            }
+            Some(Token::Default) => {
+                parser.next_token();
+                ColumnConstraint::Default(Expr::parse(parser)?)
+            }
+            Some(Token::Primary) => {
+                parser.next_token();
+                parser.expect(Token::Key)?;
+                ColumnConstraint::PrimaryKey
+            }
+            Some(Token::Unique) => {
+                parser.next_token();
+                ColumnConstraint::Unique
+            }
+            Some(Token::References) => {
+                parser.next_token();
+                let table = ObjectName::parse(parser)?;
+                let column = if parser.consume(&Token::OpenParen) {
+                    let column = parser.parse_identifier()?;
+                    parser.expect(Token::CloseParen)?;
+                    Some(column)
+                } else {
+                    None
+                };
+                ColumnConstraint::References { table, column }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(constraint))
+    }
+}
+
+impl TableConstraint {
+    fn parse(parser: &mut Parser) -> Result<TableConstraint, ParseError> {
+        let name = if parser.consume(&Token::Constraint) {
+            Some(parser.parse_identifier()?)
+        } else {
+            None
+        };
+        let kind = match parser.next_token() {
+            Some(Token::Primary) => {
+                parser.expect(Token::Key)?;
+                TableConstraintKind::PrimaryKey(
+                    parser.parse_parenthesized(Parser::parse_identifier)?,
+                )
+            }
+            Some(Token::Unique) => {
+                TableConstraintKind::Unique(parser.parse_parenthesized(Parser::parse_identifier)?)
+            }
+            Some(Token::Foreign) => {
+                parser.expect(Token::Key)?;
+                let columns = parser.parse_parenthesized(Parser::parse_identifier)?;
+                parser.expect(Token::References)?;
+                let table = ObjectName::parse(parser)?;
+                let referenced_columns = if parser.peek() == Some(&Token::OpenParen) {
+                    parser.parse_parenthesized(Parser::parse_identifier)?
+                } else {
+                    vec![]
+                };
+                TableConstraintKind::ForeignKey {
+                    columns,
+                    table,
+                    referenced_columns,
+                }
+            }
+            Some(token) => {
+                return parser.unexpected_token(&token, "primary key, unique or foreign key")
+            }
+            None => return parser.unexpected_end("primary key, unique or foreign key"),
+        };
+        Ok(TableConstraint { name, kind })
+    }
+}
+
+impl CreateIndexQuery {
+    pub fn parse(parser: &mut Parser) -> Result<CreateIndexQuery, ParseError> {
+        parser.expect(Token::Create)?;
+        let unique = parser.consume(&Token::Unique);
+        parser.expect(Token::Index)?;
+        let if_not_exists = parser.parse_if_not_exists()?;
+        let name = parser.parse_identifier()?;
+        parser.expect(Token::On)?;
+        let table = ObjectName::parse(parser)?;
+        let columns = parser.parse_parenthesized(Parser::parse_identifier)?;
+        Ok(CreateIndexQuery {
+            name,
+            unique,
+            if_not_exists,
+            table,
+            columns,
+        })
+    }
+}
+
+impl CreateViewQuery {
+    pub fn parse(parser: &mut Parser) -> Result<CreateViewQuery, ParseError> {
+        parser.expect(Token::Create)?;
+        parser.expect(Token::View)?;
+        let name = ObjectName::parse(parser)?;
+        parser.expect(Token::As)?;
+        Ok(CreateViewQuery {
+            name,
+            query: SelectQuery::parse(parser)?,
+        })
+    }
+}
+
+impl CreateSequenceQuery {
+    pub fn parse(parser: &mut Parser) -> Result<CreateSequenceQuery, ParseError> {
+        parser.expect(Token::Create)?;
+        parser.expect(Token::Sequence)?;
+        let if_not_exists = parser.parse_if_not_exists()?;
+        let mut query = CreateSequenceQuery {
+            name: ObjectName::parse(parser)?,
+            if_not_exists,
+            increment: None,
+            start: None,
+        };
+        loop {
+            if query.increment.is_none() && parser.consume(&Token::Increment) {
+                parser.consume(&Token::By);
+                query.increment = Some(parser.parse_integer()?);
+            } else if query.start.is_none() && parser.consume(&Token::Start) {
+                parser.consume(&Token::With);
+                query.start = Some(parser.parse_integer()?);
+            } else {
+                return Ok(query);
+            }
+        }
+    }
+}
+
+impl CreateSchemaQuery {
+    pub fn parse(parser: &mut Parser) -> Result<CreateSchemaQuery, ParseError> {
+        parser.expect(Token::Create)?;
+        parser.expect(Token::Schema)?;
+        let if_not_exists = parser.parse_if_not_exists()?;
+        Ok(CreateSchemaQuery {
+            name: parser.parse_identifier()?,
+            if_not_exists,
+        })
+    }
+}
+
+impl DropQuery {
+    pub fn parse(parser: &mut Parser) -> Result<DropQuery, ParseError> {
+        parser.expect(Token::Drop)?;
+        let object_type = match parser.next_token() {
+            Some(Token::Table) => ObjectType::Table,
+            Some(Token::View) => ObjectType::View,
+            Some(Token::Index) => ObjectType::Index,
+            Some(Token::Sequence) => ObjectType::Sequence,
+            Some(Token::Schema) => ObjectType::Schema,
+            Some(token) => {
+                return parser.unexpected_token(&token, "table, view, index, sequence or schema")
+            }
+            None => return parser.unexpected_end("table, view, index, sequence or schema"),
+        };
+        let if_exists = if parser.consume(&Token::If) {
+            parser.expect(Token::Exists)?;
+            true
+        } else {
+            false
+        };
+        Ok(DropQuery {
+            object_type,
+            if_exists,
+            names: parser.parse_comma_separated(ObjectName::parse)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::expr::{ColumnIdentifier, Literal, Operator};
+
+    fn table(table_name: &str) -> TableStatement {
+        TableStatement {
+            schema_name: None,
+            table_name: table_name.to_string(),
+            arguments: None,
+            alias: None,
+            span: Span::default(),
+        }
+    }
+
+    fn column(column_name: &str) -> ColumnStatement {
+        ColumnStatement::Expr {
+            expr: Expr::column(column_name),
+            alias: None,
+        }
+    }
+
+    fn qualified(table_name: &str, column_name: &str) -> Expr {
+        Expr::Column(ColumnIdentifier {
+            schema_name: None,
+            table_name: Some(table_name.to_string()),
+            column_name: column_name.to_string(),
+            span: Span::default(),
+        })
+    }
+
+    fn parse_insert(input: &str) -> InsertQuery {
+        match Queries::parse(input).unwrap().queries.remove(0) {
+            Query::Insert(insert) => insert,
+            query => panic!("expected insert, got {:?}", query),
+        }
+    }
+
+    #[test]
+    fn test_parse_select_in_queries_with_table_and_column() {
+        let queries = Queries::parse("select t1.col1, t1.col2 from t1;").unwrap();
+
+        assert_eq!(
+            queries,
+            Queries {
+                queries: vec![Query::Select(SelectQuery {
+                    select_statement: SelectStatement {
+                        columns: vec![
+                            ColumnStatement::Expr {
+                                expr: Expr::Column(ColumnIdentifier {
+                                    schema_name: None,
+                                    table_name: Some("t1".to_string()),
+                                    column_name: "col1".to_string(),
+                                    span: Span::default(),
+                                }),
+                                alias: None
+                            },
+                            ColumnStatement::Expr {
+                                expr: Expr::Column(ColumnIdentifier {
+                                    schema_name: None,
+                                    table_name: Some("t1".to_string()),
+                                    column_name: "col2".to_string(),
+                                    span: Span::default(),
+                                }),
+                                alias: None
+                            }
+                        ],
+                        distinct: false
+                    },
+                    from_statement: Some(FromStatement {
+                        tables: vec![table("t1")],
+                        joins: vec![]
+                    }),
+                    where_statement: None,
+                    group_by_statement: None,
+                    having_statement: None,
+                    unions: vec![],
+                    order_by_statement: None,
+                    limit_statement: None
+                })]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_select_in_queries() {
+        let queries = Queries::parse("select col1, col2 from t1; select * from t2").unwrap();
+
+        assert_eq!(queries.queries.len(), 2);
+        assert_eq!(
+            queries.queries[0],
+            Query::Select(SelectQuery {
+                select_statement: SelectStatement {
+                    columns: vec![column("col1"), column("col2")],
+                    distinct: false
+                },
+                from_statement: Some(FromStatement {
+                    tables: vec![table("t1")],
+                    joins: vec![]
+                }),
+                where_statement: None,
+                group_by_statement: None,
+                having_statement: None,
+                unions: vec![],
+                order_by_statement: None,
+                limit_statement: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_select_query() {
+        let input = "SELECT DISTINCT u.name AS n, count(*) total FROM public.users u \
+            LEFT JOIN orders o ON u.id = o.user_id \
+            WHERE u.age >= 18 GROUP BY u.name HAVING count(*) > 1 \
+            ORDER BY total DESC NULLS LAST, n LIMIT 10 OFFSET 5";
+        let mut parser = Parser::new(input).unwrap();
+        let query = SelectQuery::parse(&mut parser).unwrap();
+        assert!(parser.is_finished());
+
+        let u_name = Expr::Column(ColumnIdentifier {
+            schema_name: None,
+            table_name: Some("u".to_string()),
+            column_name: "name".to_string(),
+            span: Span::default(),
+        });
+        let count = Expr::Function(expr::Function {
+            name: "count".to_string(),
+            arguments: vec![Expr::Wildcard],
+            distinct: false,
+            within_group: vec![],
+            over: None,
+            span: Span::default(),
+        });
+        assert!(query.select_statement.distinct);
+        assert_eq!(
+            query.select_statement.columns,
+            vec![
+                ColumnStatement::Expr {
+                    expr: u_name.clone(),
+                    alias: Some("n".to_string())
+                },
+                ColumnStatement::Expr {
+                    expr: count.clone(),
+                    alias: Some("total".to_string())
+                },
+            ]
+        );
+        let from_statement = query.from_statement.unwrap();
+        assert_eq!(
+            from_statement.tables,
+            vec![TableStatement {
+                schema_name: Some("public".to_string()),
+                table_name: "users".to_string(),
+                arguments: None,
+                alias: Some("u".to_string()),
+                span: Span::default(),
+            }]
+        );
+        assert_eq!(from_statement.joins.len(), 1);
+        assert_eq!(from_statement.joins[0].join_type, JoinType::Left);
+        assert_eq!(query.group_by_statement.unwrap().columns, vec![u_name]);
+        assert_eq!(
+            query.having_statement.unwrap().condition,
+            Expr::binary(count, Operator::GreaterThan, Expr::number("1"))
+        );
+        assert_eq!(
+            query.order_by_statement.unwrap().columns,
+            vec![
+                OrderByColumn {
+                    expr: Expr::column("total"),
+                    order: Order {
+                        descending: true,
+                        nulls_first: false
+                    }
+                },
+                OrderByColumn {
+                    expr: Expr::column("n"),
+                    order: Order::ASC
+                },
+            ]
+        );
+        assert_eq!(
+            query.limit_statement,
+            Some(LimitStatement {
+                limit: Some(10),
+                offset: Some(5)
+            })
+        );
+    }
+
+    #[test]
+    fn select_qualified_wildcard() {
+        let mut parser = Parser::new("select u.*, public.o.* from users u, public.o").unwrap();
+        let query = SelectQuery::parse(&mut parser).unwrap();
+        assert_eq!(
+            query.select_statement.columns,
+            vec![
+                ColumnStatement::QualifiedWildcard {
+                    schema_name: None,
+                    table_name: "u".to_string(),
+                    span: Span::default(),
+                },
+                ColumnStatement::QualifiedWildcard {
+                    schema_name: Some("public".to_string()),
+                    table_name: "o".to_string(),
+                    span: Span::default(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn select_union() {
+        let mut parser = Parser::new(
+            "select a from t union all select b from u union distinct select c from v \
+             order by 1 limit 2",
+        )
+        .unwrap();
+        let query = SelectQuery::parse(&mut parser).unwrap();
+        assert_eq!(query.select_statement.columns, vec![column("a")]);
+        let unions: Vec<_> = query
+            .unions
+            .iter()
+            .map(|union| (union.all, union.query.select_statement.columns.clone()))
+            .collect();
+        assert_eq!(
+            unions,
+            vec![(true, vec![column("b")]), (false, vec![column("c")])]
+        );
+        assert!(query
+            .unions
+            .iter()
+            .all(|union| union.query.order_by_statement.is_none()));
+        assert!(query.order_by_statement.is_some());
+        assert_eq!(query.limit_statement.and_then(|l| l.limit), Some(2));
+        assert!(Queries::parse("select a from t order by a union select b from u").is_err());
+    }
+
+    #[test]
+    fn select_from_table_function() {
+        let mut parser =
+            Parser::new("select * from generate_series(1, n) s, users join now() on true").unwrap();
+        let from = SelectQuery::parse(&mut parser)
+            .unwrap()
+            .from_statement
+            .unwrap();
+        assert_eq!(
+            from.tables,
+            vec![
+                TableStatement {
+                    arguments: Some(vec![Expr::number("1"), Expr::column("n")]),
+                    alias: Some("s".to_string()),
+                    ..table("generate_series")
+                },
+                table("users"),
+            ]
+        );
+        assert_eq!(from.joins[0].table.arguments, Some(vec![]));
+        // the column list of an insert isn't taken for arguments
+        let insert_query = parse_insert("insert into t (a) values (1)");
+        assert_eq!(insert_query.table, table("t"));
+    }
+
+    #[test]
+    fn insert_single_column() {
+        let insert_query = parse_insert("insert into table1 (col1) values (1);");
+        assert_eq!(
+            insert_query,
+            InsertQuery {
+                table: table("table1"),
+                columns: vec!["col1".to_string()],
+                source: InsertSource::Values(vec![vec![Expr::number("1")]]),
+                on_conflict: None,
+                returning: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn insert_multiple_rows_with_expressions() {
+        let insert_query = parse_insert(
+            "INSERT INTO t (a, b, c) VALUES ('a b', -1, 2 * 3), ('it''s', DEFAULT, NULL)",
+        );
+        assert_eq!(
+            insert_query.source,
+            InsertSource::Values(vec![
+                vec![
+                    Expr::string("a b"),
+                    Expr::number("-1"),
+                    Expr::binary(Expr::number("2"), Operator::Multiply, Expr::number("3")),
+                ],
+                vec![
+                    Expr::string("it's"),
+                    Expr::Default,
+                    Expr::Literal(Literal::Null)
+                ],
+            ])
+        );
+    }
+
+    #[test]
+    fn insert_without_columns() {
+        let insert_query = parse_insert("insert into t values (1, 'x')");
+        assert!(insert_query.columns.is_empty());
+        assert_eq!(
+            insert_query.source,
+            InsertSource::Values(vec![vec![Expr::number("1"), Expr::string("x")]])
+        );
+    }
+
+    #[test]
+    fn insert_select() {
+        let insert_query =
+            parse_insert("insert into archive (id) select id from users where age > 1");
+        match insert_query.source {
+            InsertSource::Select(query) => {
+                assert_eq!(query.select_statement.columns, vec![column("id")]);
+                assert!(query.where_statement.is_some());
+            }
+            source => panic!("expected select, got {:?}", source),
+        }
+
+        let insert_query = parse_insert("insert into archive (select * from users)");
+        assert!(insert_query.columns.is_empty());
+        assert!(matches!(insert_query.source, InsertSource::Select(_)));
+    }
+
+    #[test]
+    fn insert_default_values() {
+        let insert_query = parse_insert("insert into t default values returning id");
+        assert_eq!(insert_query.source, InsertSource::DefaultValues);
+        assert_eq!(insert_query.returning, vec![column("id")]);
+    }
+
+    #[test]
+    fn insert_on_conflict_do_nothing() {
+        let insert_query =
+            parse_insert("insert into t (id) values (1) on conflict (id) do nothing");
+        assert_eq!(
+            insert_query.on_conflict,
+            Some(OnConflict {
+                columns: vec!["id".to_string()],
+                action: ConflictAction::DoNothing,
+            })
+        );
+    }
+
+    #[test]
+    fn insert_on_conflict_do_update() {
+        let insert_query = parse_insert(
+            "insert into counters as c (id, hits) values (1, 1) \
+             on conflict (id) do update set hits = c.hits + 1 where c.hits < 100 \
+             returning id, hits as total",
+        );
+        let c_hits = Expr::Column(ColumnIdentifier {
+            schema_name: None,
+            table_name: Some("c".to_string()),
+            column_name: "hits".to_string(),
+            span: Span::default(),
+        });
+        assert_eq!(insert_query.table.alias, Some("c".to_string()));
+        assert_eq!(
+            insert_query.on_conflict,
+            Some(OnConflict {
+                columns: vec!["id".to_string()],
+                action: ConflictAction::DoUpdate {
+                    set_statement: SetStatement {
+                        assignments: vec![Assignment {
+                            columns: vec!["hits".to_string()],
+                            value: Expr::binary(c_hits.clone(), Operator::Plus, Expr::number("1")),
+                        }]
+                    },
+                    where_statement: Some(WhereStatement {
+                        condition: Expr::binary(c_hits, Operator::LessThan, Expr::number("100")),
+                    }),
+                },
+            })
+        );
+        assert_eq!(
+            insert_query.returning,
+            vec![
+                column("id"),
+                ColumnStatement::Expr {
+                    expr: Expr::column("hits"),
+                    alias: Some("total".to_string())
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_errors() {
+        assert_eq!(
+            Queries::parse("insert into t (a) values (1"),
+            Err(ParseError::UnexpectedEnd {
+                expected: "CloseParen".to_string()
+            })
+        );
+        assert!(Queries::parse("insert into t (a) 1").is_err());
+        assert!(Queries::parse("insert into t values (1) on conflict do").is_err());
+    }
+
+    #[test]
+    fn update_with_assignments() {
+        let query =
+            Queries::parse("update users set name = 'x', (a, b) = (a + 1, default)").unwrap();
+        assert_eq!(
+            query.queries,
+            vec![Query::Update(UpdateQuery {
+                table: table("users"),
+                set_statement: SetStatement {
+                    assignments: vec![
+                        Assignment {
+                            columns: vec!["name".to_string()],
+                            value: Expr::string("x"),
+                        },
+                        Assignment {
+                            columns: vec!["a".to_string(), "b".to_string()],
+                            value: Expr::Tuple(vec![
+                                Expr::binary(Expr::column("a"), Operator::Plus, Expr::number("1")),
+                                Expr::Default,
+                            ]),
+                        },
+                    ]
+                },
+                from_statement: None,
+                where_statement: None,
+                returning: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn update_from() {
+        let query = Queries::parse(
+            "UPDATE accounts AS a SET balance = a.balance - p.amount \
+             FROM payments p WHERE p.account_id = a.id RETURNING a.id, a.balance",
+        )
+        .unwrap();
+        let update = match &query.queries[0] {
+            Query::Update(update) => update,
+            query => panic!("expected update, got {:?}", query),
+        };
+        assert_eq!(update.table.alias, Some("a".to_string()));
+        assert_eq!(
+            update.from_statement,
+            Some(FromStatement {
+                tables: vec![TableStatement {
+                    schema_name: None,
+                    table_name: "payments".to_string(),
+                    arguments: None,
+                    alias: Some("p".to_string()),
+                    span: Span::default(),
+                }],
+                joins: vec![],
+            })
+        );
+        assert_eq!(
+            update.where_statement,
+            Some(WhereStatement {
+                condition: Expr::binary(
+                    qualified("p", "account_id"),
+                    Operator::Equal,
+                    qualified("a", "id")
+                ),
+            })
+        );
+        assert_eq!(update.returning.len(), 2);
+    }
+
+    #[test]
+    fn delete_without_where() {
+        let query = Queries::parse("delete from sessions;").unwrap();
+        assert_eq!(
+            query.queries,
+            vec![Query::Delete(DeleteQuery {
+                table: table("sessions"),
+                using_statement: None,
+                where_statement: None,
+                returning: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn delete_using() {
+        let query = Queries::parse(
+            "delete from orders o using users u \
+             where o.user_id = u.id and u.deleted returning o.*",
+        )
+        .unwrap();
+        assert_eq!(
+            query.queries,
+            vec![Query::Delete(DeleteQuery {
+                table: TableStatement {
+                    schema_name: None,
+                    table_name: "orders".to_string(),
+                    arguments: None,
+                    alias: Some("o".to_string()),
+                    span: Span::default(),
+                },
+                using_statement: Some(FromStatement {
+                    tables: vec![TableStatement {
+                        schema_name: None,
+                        table_name: "users".to_string(),
+                        arguments: None,
+                        alias: Some("u".to_string()),
+                        span: Span::default(),
+                    }],
+                    joins: vec![],
+                }),
+                where_statement: Some(WhereStatement {
+                    condition: Expr::binary(
+                        Expr::binary(
+                            qualified("o", "user_id"),
+                            Operator::Equal,
+                            qualified("u", "id")
+                        ),
+                        Operator::And,
+                        qualified("u", "deleted"),
+                    ),
+                }),
+                returning: vec![ColumnStatement::QualifiedWildcard {
+                    schema_name: None,
+                    table_name: "o".to_string(),
+                    span: Span::default(),
+                }],
+            })]
+        );
+    }
+
+    #[test]
+    fn update_and_delete_errors() {
+        assert!(Queries::parse("update t where a = 1").is_err());
+        assert!(Queries::parse("update t set (a, b) 1").is_err());
+        assert!(Queries::parse("delete t").is_err());
+    }
+
+    fn parse_query(input: &str) -> Query {
+        let mut queries = Queries::parse(input).unwrap().queries;
+        assert_eq!(queries.len(), 1);
+        queries.remove(0)
+    }
+
+    #[test]
+    fn begin_transaction() {
+        assert_eq!(
+            parse_query("begin"),
+            Query::Begin(BeginQuery {
+                isolation_level: None,
+                access_mode: None
+            })
+        );
+        assert_eq!(
+            parse_query("START TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY"),
+            Query::Begin(BeginQuery {
+                isolation_level: Some(IsolationLevel::RepeatableRead),
+                access_mode: Some(AccessMode::ReadOnly)
+            })
+        );
+        assert_eq!(
+            parse_query("begin transaction read write isolation level read committed"),
+            Query::Begin(BeginQuery {
+                isolation_level: Some(IsolationLevel::ReadCommitted),
+                access_mode: Some(AccessMode::ReadWrite)
+            })
+        );
+        assert!(Queries::parse("begin isolation level read").is_err());
+        assert!(Queries::parse("start read only").is_err());
+    }
+
+    #[test]
+    fn commit_rollback_and_savepoints() {
+        let queries = Queries::parse(
+            "begin; savepoint before_import; rollback to savepoint before_import; \
+             release savepoint before_import; rollback to s2; rollback; commit",
+        )
+        .unwrap();
+        assert_eq!(
+            queries.queries[1..],
+            [
+                Query::Savepoint(SavepointQuery {
+                    name: "before_import".to_string()
+                }),
+                Query::Rollback(RollbackQuery {
+                    savepoint: Some("before_import".to_string())
+                }),
+                Query::Release(ReleaseQuery {
+                    name: "before_import".to_string()
+                }),
+                Query::Rollback(RollbackQuery {
+                    savepoint: Some("s2".to_string())
+                }),
+                Query::Rollback(RollbackQuery { savepoint: None }),
+                Query::Commit,
+            ]
+        );
+    }
+
+    #[test]
+    fn set_and_show() {
+        assert_eq!(
+            parse_query("SET max_parallel_workers = 8"),
+            Query::SetVariable(SetVariableQuery {
+                name: "max_parallel_workers".to_string(),
+                value: Expr::number("8")
+            })
+        );
+        assert_eq!(
+            parse_query("set app.user_name to 'admin'"),
+            Query::SetVariable(SetVariableQuery {
+                name: "app.user_name".to_string(),
+                value: Expr::string("admin")
+            })
+        );
+        assert_eq!(
+            parse_query("show search_path"),
+            Query::Show(ShowQuery {
+                name: Some("search_path".to_string())
+            })
+        );
+        assert_eq!(
+            parse_query("show all"),
+            Query::Show(ShowQuery { name: None })
+        );
+    }
+
+    #[test]
+    fn explain() {
+        assert_eq!(
+            parse_query("explain analyze delete from t"),
+            Query::Explain(ExplainQuery {
+                analyze: true,
+                query: Box::new(Query::Delete(DeleteQuery {
+                    table: table("t"),
+                    using_statement: None,
+                    where_statement: None,
+                    returning: vec![],
+                }))
+            })
+        );
+        match parse_query("explain select 1") {
+            Query::Explain(ExplainQuery { analyze, query }) => {
+                assert!(!analyze);
+                assert!(matches!(*query, Query::Select(_)));
+            }
+            query => panic!("expected explain, got {:?}", query),
+        }
+    }
+
+    #[test]
+    fn parameters() {
+        use crate::parser::expr::Parameter;
+        let Query::Select(query) = parse_query("select ?, $2, :name from t where a = ?") else {
+            panic!("expected select");
+        };
+        let columns: Vec<ColumnStatement> = [
+            Parameter::Anonymous(1),
+            Parameter::Positional(2),
+            Parameter::Named("name".to_string()),
+        ]
+        .into_iter()
+        .map(|parameter| ColumnStatement::Expr {
+            expr: Expr::Parameter(parameter),
+            alias: None,
+        })
+        .collect();
+        assert_eq!(query.select_statement.columns, columns);
+        assert_eq!(
+            query.where_statement.unwrap().condition,
+            Expr::binary(
+                Expr::column("a"),
+                Operator::Equal,
+                Expr::Parameter(Parameter::Anonymous(2))
+            )
+        );
+        // numbering starts again in every statement
+        let queries = Queries::parse("select ?; select ?").unwrap();
+        assert_eq!(queries.queries[0], queries.queries[1]);
+    }
+
+    #[test]
+    fn prepare_execute_deallocate() {
+        assert_eq!(
+            parse_query("prepare q (int, varchar(20), numeric(10, 2), double) as delete from t"),
+            Query::Prepare(PrepareQuery {
+                name: "q".to_string(),
+                parameter_types: vec![
+                    DataType::Integer,
+                    DataType::VarChar(Some(20)),
+                    DataType::Decimal(Some((10, 2))),
+                    DataType::Double,
+                ],
+                statement: Box::new(Query::Delete(DeleteQuery {
+                    table: table("t"),
+                    using_statement: None,
+                    where_statement: None,
+                    returning: vec![],
+                })),
+            })
+        );
+        assert_eq!(
+            parse_query("execute q (1, 'a')"),
+            Query::Execute(ExecuteQuery {
+                name: "q".to_string(),
+                arguments: vec![Expr::number("1"), Expr::string("a")],
+            })
+        );
+        assert_eq!(
+            parse_query("execute q"),
+            Query::Execute(ExecuteQuery {
+                name: "q".to_string(),
+                arguments: vec![],
+            })
+        );
+        assert_eq!(
+            parse_query("deallocate prepare q"),
+            Query::Deallocate(DeallocateQuery {
+                name: Some("q".to_string())
+            })
+        );
+        assert_eq!(
+            parse_query("deallocate all"),
+            Query::Deallocate(DeallocateQuery { name: None })
+        );
+        assert!(Queries::parse("prepare q as commit").is_err());
+        assert!(Queries::parse("prepare q (blob) as select 1").is_err());
+        assert!(Queries::parse("prepare q (numeric(2, 5)) as select 1").is_err());
+        assert!(Queries::parse("prepare q (varchar(x)) as select 1").is_err());
+    }
+
+    fn object_name(schema_name: Option<&str>, name: &str) -> ObjectName {
+        ObjectName {
+            schema_name: schema_name.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn create_table() {
+        assert_eq!(
+            parse_query(
+                "create table if not exists sales.orders (
+                    id bigint primary key,
+                    user_id int not null references users (id),
+                    note text null default 'none' unique,
+                    constraint one_note unique (user_id, note),
+                    foreign key (user_id) references public.users
+                )"
+            ),
+            Query::CreateTable(CreateTableQuery {
+                name: object_name(Some("sales"), "orders"),
+                if_not_exists: true,
+                columns: vec![
+                    ColumnDefinition {
+                        name: "id".to_string(),
+                        data_type: DataType::BigInt,
+                        constraints: vec![ColumnConstraint::PrimaryKey],
+                    },
+                    ColumnDefinition {
+                        name: "user_id".to_string(),
+                        data_type: DataType::Integer,
+                        constraints: vec![
+                            ColumnConstraint::NotNull,
+                            ColumnConstraint::References {
+                                table: object_name(None, "users"),
+                                column: Some("id".to_string()),
+                            },
+                        ],
+                    },
+                    ColumnDefinition {
+                        name: "note".to_string(),
+                        data_type: DataType::Text,
+                        constraints: vec![
+                            ColumnConstraint::Null,
+                            ColumnConstraint::Default(Expr::string("none")),
+                            ColumnConstraint::Unique,
+                        ],
+                    },
+                ],
+                constraints: vec![
+                    TableConstraint {
+                        name: Some("one_note".to_string()),
+                        kind: TableConstraintKind::Unique(vec![
+                            "user_id".to_string(),
+                            "note".to_string()
+                        ]),
+                    },
+                    TableConstraint {
+                        name: None,
+                        kind: TableConstraintKind::ForeignKey {
+                            columns: vec!["user_id".to_string()],
+                            table: object_name(Some("public"), "users"),
+                            referenced_columns: vec![],
+                        },
+                    },
+                ],
+            })
+        );
+        assert!(Queries::parse("create table t ()").is_err());
+        assert!(Queries::parse("create table t (a)").is_err());
+        assert!(Queries::parse("create table t (primary key (a), a int)").is_err());
+        assert!(Queries::parse("create table t (a int not)").is_err());
+    }
+
+    #[test]
+    fn create_other_objects() {
+        assert_eq!(
+            parse_query("create unique index if not exists users_name on users (name, id)"),
+            Query::CreateIndex(CreateIndexQuery {
+                name: "users_name".to_string(),
+                unique: true,
+                if_not_exists: true,
+                table: object_name(None, "users"),
+                columns: vec!["name".to_string(), "id".to_string()],
+            })
+        );
+        match parse_query("create view public.adults as select name from users") {
+            Query::CreateView(query) => {
+                assert_eq!(query.name, object_name(Some("public"), "adults"));
+                assert!(query.query.from_statement.is_some());
+            }
+            query => panic!("expected create view, got {:?}", query),
+        }
+        assert_eq!(
+            parse_query("create sequence ids increment by -2 start 10"),
+            Query::CreateSequence(CreateSequenceQuery {
+                name: object_name(None, "ids"),
+                if_not_exists: false,
+                increment: Some(-2),
+                start: Some(10),
+            })
+        );
+        assert_eq!(
+            parse_query("create schema if not exists sales"),
+            Query::CreateSchema(CreateSchemaQuery {
+                name: "sales".to_string(),
+                if_not_exists: true,
+            })
+        );
+        assert!(Queries::parse("create index on t (a)").is_err());
+        assert!(Queries::parse("create sequence s increment by x").is_err());
+        assert!(Queries::parse("create view v as delete from t").is_err());
+    }
+
+    #[test]
+    fn drop_objects() {
+        assert_eq!(
+            parse_query("drop table if exists users, sales.orders"),
+            Query::Drop(DropQuery {
+                object_type: ObjectType::Table,
+                if_exists: true,
+                names: vec![
+                    object_name(None, "users"),
+                    object_name(Some("sales"), "orders")
+                ],
+            })
+        );
+        assert_eq!(
+            parse_query("drop schema sales"),
+            Query::Drop(DropQuery {
+                object_type: ObjectType::Schema,
+                if_exists: false,
+                names: vec![object_name(None, "sales")],
+            })
+        );
+        assert!(Queries::parse("drop users").is_err());
+        assert!(Queries::parse("drop table").is_err());
+    }
+
+    #[test]
+    fn analyze() {
+        assert_eq!(
+            parse_query("analyze sales.orders"),
+            Query::Analyze(AnalyzeQuery {
+                table: Some(object_name(Some("sales"), "orders"))
+            })
+        );
+        assert_eq!(
+            Queries::parse("analyze; analyze users").unwrap().queries,
+            vec![
+                Query::Analyze(AnalyzeQuery { table: None }),
+                Query::Analyze(AnalyzeQuery {
+                    table: Some(object_name(None, "users"))
+                })
+            ]
+        );
+        assert!(Queries::parse("analyze table users").is_err());
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let parenthesized = |depth: usize| {
+            let input = format!("select {}1{}", "(".repeat(depth), ")".repeat(depth));
+            Queries::parse(&input).err()
+        };
+        assert_eq!(parenthesized(MAX_NESTING - 2), None);
+        assert_eq!(parenthesized(MAX_NESTING - 1), Some(ParseError::TooDeep));
+        assert_eq!(parenthesized(10_000), Some(ParseError::TooDeep));
+        for input in [
+            format!("select 1{}", " + 1".repeat(10_000)),
+            format!("select {}1", "- ".repeat(10_000)),
+            format!("select {}1", "(select ".repeat(10_000)),
+            format!("{}select 1", "explain ".repeat(10_000)),
+        ] {
+            assert_eq!(Queries::parse(&input).err(), Some(ParseError::TooDeep));
+        }
+    }
+}
@@ -0,0 +1,794 @@
+//! Walks the AST. A pass implements `Visitor` (or `VisitorMut` to rewrite the tree in place)
+//! and overrides only the hooks it needs; the default methods call the matching `walk_*`
+//! function which visits the children. An overridden hook calls `walk_*` itself to keep
+//! descending.
+
+use crate::parser::expr::{ColumnIdentifier, Expr, FrameBound, Function, Literal, Parameter};
+use crate::parser::*;
+
+pub trait Visitor {
+    fn visit_query(&mut self, query: &Query) {
+        walk_query(self, query)
+    }
+
+    fn visit_select(&mut self, query: &SelectQuery) {
+        walk_select(self, query)
+    }
+
+    fn visit_insert(&mut self, query: &InsertQuery) {
+        walk_insert(self, query)
+    }
+
+    fn visit_update(&mut self, query: &UpdateQuery) {
+        walk_update(self, query)
+    }
+
+    fn visit_delete(&mut self, query: &DeleteQuery) {
+        walk_delete(self, query)
+    }
+
+    fn visit_column_statement(&mut self, column: &ColumnStatement) {
+        walk_column_statement(self, column)
+    }
+
+    fn visit_from(&mut self, from_statement: &FromStatement) {
+        walk_from(self, from_statement)
+    }
+
+    fn visit_table(&mut self, _table: &TableStatement) {}
+
+    fn visit_join(&mut self, join: &JoinStatement) {
+        walk_join(self, join)
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        walk_assignment(self, assignment)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_column(&mut self, _column: &ColumnIdentifier) {}
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_parameter(&mut self, _parameter: &Parameter) {}
+}
+
+pub fn walk_query<V: Visitor + ?Sized>(visitor: &mut V, query: &Query) {
+    match query {
+        Query::Select(query) => visitor.visit_select(query),
+        Query::Insert(query) => visitor.visit_insert(query),
+        Query::Update(query) => visitor.visit_update(query),
+        Query::Delete(query) => visitor.visit_delete(query),
+        Query::SetVariable(query) => visitor.visit_expr(&query.value),
+        Query::Explain(query) => visitor.visit_query(&query.query),
+        Query::Prepare(query) => visitor.visit_query(&query.statement),
+        Query::Execute(query) => {
+            for argument in &query.arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Query::CreateTable(query) => {
+            for column in &query.columns {
+                for constraint in &column.constraints {
+                    if let ColumnConstraint::Default(expr) = constraint {
+                        visitor.visit_expr(expr);
+                    }
+                }
+            }
+        }
+        Query::CreateView(query) => visitor.visit_select(&query.query),
+        Query::CreateIndex(query) => visit_named_table(visitor, &query.table),
+        Query::Drop(query) => {
+            if matches!(query.object_type, ObjectType::Table | ObjectType::View) {
+                for name in &query.names {
+                    visit_named_table(visitor, name);
+                }
+            }
+        }
+        Query::Analyze(query) => {
+            if let Some(table) = &query.table {
+                visit_named_table(visitor, table);
+            }
+        }
+        Query::Begin(_)
+        | Query::Commit
+        | Query::Rollback(_)
+        | Query::Savepoint(_)
+        | Query::Release(_)
+        | Query::Show(_)
+        | Query::Deallocate(_)
+        | Query::CreateSequence(_)
+        | Query::CreateSchema(_) => {}
+    }
+}
+
+/// DDL refers to tables by name only, they're visited as a `from` entry without an alias.
+fn named_table(name: &ObjectName) -> TableStatement {
+    TableStatement {
+        schema_name: name.schema_name.clone(),
+        table_name: name.name.clone(),
+        arguments: None,
+        alias: None,
+        span: Span::default(),
+    }
+}
+
+fn visit_named_table<V: Visitor + ?Sized>(visitor: &mut V, name: &ObjectName) {
+    visitor.visit_table(&named_table(name));
+}
+
+pub fn walk_select<V: Visitor + ?Sized>(visitor: &mut V, query: &SelectQuery) {
+    for column in &query.select_statement.columns {
+        visitor.visit_column_statement(column);
+    }
+    if let Some(from_statement) = &query.from_statement {
+        visitor.visit_from(from_statement);
+    }
+    if let Some(where_statement) = &query.where_statement {
+        visitor.visit_expr(&where_statement.condition);
+    }
+    if let Some(group_by_statement) = &query.group_by_statement {
+        for expr in &group_by_statement.columns {
+            visitor.visit_expr(expr);
+        }
+    }
+    if let Some(having_statement) = &query.having_statement {
+        visitor.visit_expr(&having_statement.condition);
+    }
+    for union in &query.unions {
+        visitor.visit_select(&union.query);
+    }
+    if let Some(order_by_statement) = &query.order_by_statement {
+        for column in &order_by_statement.columns {
+            visitor.visit_expr(&column.expr);
+        }
+    }
+}
+
+pub fn walk_insert<V: Visitor + ?Sized>(visitor: &mut V, query: &InsertQuery) {
+    visitor.visit_table(&query.table);
+    match &query.source {
+        InsertSource::Values(rows) => {
+            for expr in rows.iter().flatten() {
+                visitor.visit_expr(expr);
+            }
+        }
+        InsertSource::Select(select) => visitor.visit_select(select),
+        InsertSource::DefaultValues => {}
+    }
+    if let Some(on_conflict) = &query.on_conflict {
+        if let ConflictAction::DoUpdate {
+            set_statement,
+            where_statement,
+        } = &on_conflict.action
+        {
+            for assignment in &set_statement.assignments {
+                visitor.visit_assignment(assignment);
+            }
+            if let Some(where_statement) = where_statement {
+                visitor.visit_expr(&where_statement.condition);
+            }
+        }
+    }
+    for column in &query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_update<V: Visitor + ?Sized>(visitor: &mut V, query: &UpdateQuery) {
+    visitor.visit_table(&query.table);
+    for assignment in &query.set_statement.assignments {
+        visitor.visit_assignment(assignment);
+    }
+    if let Some(from_statement) = &query.from_statement {
+        visitor.visit_from(from_statement);
+    }
+    if let Some(where_statement) = &query.where_statement {
+        visitor.visit_expr(&where_statement.condition);
+    }
+    for column in &query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_delete<V: Visitor + ?Sized>(visitor: &mut V, query: &DeleteQuery) {
+    visitor.visit_table(&query.table);
+    if let Some(using_statement) = &query.using_statement {
+        visitor.visit_from(using_statement);
+    }
+    if let Some(where_statement) = &query.where_statement {
+        visitor.visit_expr(&where_statement.condition);
+    }
+    for column in &query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_column_statement<V: Visitor + ?Sized>(visitor: &mut V, column: &ColumnStatement) {
+    if let ColumnStatement::Expr { expr, .. } = column {
+        visitor.visit_expr(expr);
+    }
+}
+
+pub fn walk_from<V: Visitor + ?Sized>(visitor: &mut V, from_statement: &FromStatement) {
+    for table in &from_statement.tables {
+        visitor.visit_table(table);
+        walk_table_arguments(visitor, table);
+    }
+    for join in &from_statement.joins {
+        visitor.visit_join(join);
+    }
+}
+
+/// Arguments of a table function in `from`.
+fn walk_table_arguments<V: Visitor + ?Sized>(visitor: &mut V, table: &TableStatement) {
+    for argument in table.arguments.iter().flatten() {
+        visitor.visit_expr(argument);
+    }
+}
+
+pub fn walk_join<V: Visitor + ?Sized>(visitor: &mut V, join: &JoinStatement) {
+    visitor.visit_table(&join.table);
+    walk_table_arguments(visitor, &join.table);
+    if let Some(on) = &join.on {
+        visitor.visit_expr(on);
+    }
+}
+
+pub fn walk_assignment<V: Visitor + ?Sized>(visitor: &mut V, assignment: &Assignment) {
+    visitor.visit_expr(&assignment.value);
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Column(column) => visitor.visit_column(column),
+        Expr::Literal(literal) => visitor.visit_literal(literal),
+        Expr::Parameter(parameter) => visitor.visit_parameter(parameter),
+        Expr::Function(function) => visitor.visit_function(function),
+        Expr::Wildcard | Expr::Default | Expr::TypedString { .. } => {}
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { left, right, .. } | Expr::IsDistinctFrom { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Like {
+            expr,
+            pattern,
+            escape,
+            ..
+        } => {
+            visitor.visit_expr(expr);
+            visitor.visit_expr(pattern);
+            if let Some(escape) = escape {
+                visitor.visit_expr(escape);
+            }
+        }
+        Expr::InList { expr, list, .. } => {
+            visitor.visit_expr(expr);
+            for item in list {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            visitor.visit_expr(expr);
+            visitor.visit_select(subquery);
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            visitor.visit_expr(expr);
+            visitor.visit_expr(low);
+            visitor.visit_expr(high);
+        }
+        Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => visitor.visit_select(subquery),
+        Expr::Tuple(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::Case {
+            operand,
+            when_then,
+            else_result,
+        } => {
+            if let Some(operand) = operand {
+                visitor.visit_expr(operand);
+            }
+            for (condition, result) in when_then {
+                visitor.visit_expr(condition);
+                visitor.visit_expr(result);
+            }
+            if let Some(else_result) = else_result {
+                visitor.visit_expr(else_result);
+            }
+        }
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for argument in &function.arguments {
+        visitor.visit_expr(argument);
+    }
+    for column in &function.within_group {
+        visitor.visit_expr(&column.expr);
+    }
+    if let Some(window) = &function.over {
+        for expr in &window.partition_by {
+            visitor.visit_expr(expr);
+        }
+        for column in &window.order_by {
+            visitor.visit_expr(&column.expr);
+        }
+        if let Some(frame) = &window.frame {
+            for bound in [&frame.start, &frame.end] {
+                if let FrameBound::Preceding(offset) | FrameBound::Following(offset) = bound {
+                    visitor.visit_expr(offset);
+                }
+            }
+        }
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_query(&mut self, query: &mut Query) {
+        walk_query_mut(self, query)
+    }
+
+    fn visit_select(&mut self, query: &mut SelectQuery) {
+        walk_select_mut(self, query)
+    }
+
+    fn visit_insert(&mut self, query: &mut InsertQuery) {
+        walk_insert_mut(self, query)
+    }
+
+    fn visit_update(&mut self, query: &mut UpdateQuery) {
+        walk_update_mut(self, query)
+    }
+
+    fn visit_delete(&mut self, query: &mut DeleteQuery) {
+        walk_delete_mut(self, query)
+    }
+
+    fn visit_column_statement(&mut self, column: &mut ColumnStatement) {
+        walk_column_statement_mut(self, column)
+    }
+
+    fn visit_from(&mut self, from_statement: &mut FromStatement) {
+        walk_from_mut(self, from_statement)
+    }
+
+    fn visit_table(&mut self, _table: &mut TableStatement) {}
+
+    fn visit_join(&mut self, join: &mut JoinStatement) {
+        walk_join_mut(self, join)
+    }
+
+    fn visit_assignment(&mut self, assignment: &mut Assignment) {
+        walk_assignment_mut(self, assignment)
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_column(&mut self, _column: &mut ColumnIdentifier) {}
+
+    fn visit_function(&mut self, function: &mut Function) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_literal(&mut self, _literal: &mut Literal) {}
+
+    fn visit_parameter(&mut self, _parameter: &mut Parameter) {}
+}
+
+pub fn walk_query_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut Query) {
+    match query {
+        Query::Select(query) => visitor.visit_select(query),
+        Query::Insert(query) => visitor.visit_insert(query),
+        Query::Update(query) => visitor.visit_update(query),
+        Query::Delete(query) => visitor.visit_delete(query),
+        Query::SetVariable(query) => visitor.visit_expr(&mut query.value),
+        Query::Explain(query) => visitor.visit_query(&mut query.query),
+        Query::Prepare(query) => visitor.visit_query(&mut query.statement),
+        Query::Execute(query) => {
+            for argument in &mut query.arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Query::CreateTable(query) => {
+            for column in &mut query.columns {
+                for constraint in &mut column.constraints {
+                    if let ColumnConstraint::Default(expr) = constraint {
+                        visitor.visit_expr(expr);
+                    }
+                }
+            }
+        }
+        Query::CreateView(query) => visitor.visit_select(&mut query.query),
+        Query::CreateIndex(query) => visit_named_table_mut(visitor, &mut query.table),
+        Query::Drop(query) => {
+            if matches!(query.object_type, ObjectType::Table | ObjectType::View) {
+                for name in &mut query.names {
+                    visit_named_table_mut(visitor, name);
+                }
+            }
+        }
+        Query::Analyze(query) => {
+            if let Some(table) = &mut query.table {
+                visit_named_table_mut(visitor, table);
+            }
+        }
+        Query::Begin(_)
+        | Query::Commit
+        | Query::Rollback(_)
+        | Query::Savepoint(_)
+        | Query::Release(_)
+        | Query::Show(_)
+        | Query::Deallocate(_)
+        | Query::CreateSequence(_)
+        | Query::CreateSchema(_) => {}
+    }
+}
+
+fn visit_named_table_mut<V: VisitorMut + ?Sized>(visitor: &mut V, name: &mut ObjectName) {
+    let mut table = named_table(name);
+    visitor.visit_table(&mut table);
+    name.schema_name = table.schema_name;
+    name.name = table.table_name;
+}
+
+pub fn walk_select_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut SelectQuery) {
+    for column in &mut query.select_statement.columns {
+        visitor.visit_column_statement(column);
+    }
+    if let Some(from_statement) = &mut query.from_statement {
+        visitor.visit_from(from_statement);
+    }
+    if let Some(where_statement) = &mut query.where_statement {
+        visitor.visit_expr(&mut where_statement.condition);
+    }
+    if let Some(group_by_statement) = &mut query.group_by_statement {
+        for expr in &mut group_by_statement.columns {
+            visitor.visit_expr(expr);
+        }
+    }
+    if let Some(having_statement) = &mut query.having_statement {
+        visitor.visit_expr(&mut having_statement.condition);
+    }
+    for union in &mut query.unions {
+        visitor.visit_select(&mut union.query);
+    }
+    if let Some(order_by_statement) = &mut query.order_by_statement {
+        for column in &mut order_by_statement.columns {
+            visitor.visit_expr(&mut column.expr);
+        }
+    }
+}
+
+pub fn walk_insert_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut InsertQuery) {
+    visitor.visit_table(&mut query.table);
+    match &mut query.source {
+        InsertSource::Values(rows) => {
+            for expr in rows.iter_mut().flatten() {
+                visitor.visit_expr(expr);
+            }
+        }
+        InsertSource::Select(select) => visitor.visit_select(select),
+        InsertSource::DefaultValues => {}
+    }
+    if let Some(on_conflict) = &mut query.on_conflict {
+        if let ConflictAction::DoUpdate {
+            set_statement,
+            where_statement,
+        } = &mut on_conflict.action
+        {
+            for assignment in &mut set_statement.assignments {
+                visitor.visit_assignment(assignment);
+            }
+            if let Some(where_statement) = where_statement {
+                visitor.visit_expr(&mut where_statement.condition);
+            }
+        }
+    }
+    for column in &mut query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_update_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut UpdateQuery) {
+    visitor.visit_table(&mut query.table);
+    for assignment in &mut query.set_statement.assignments {
+        visitor.visit_assignment(assignment);
+    }
+    if let Some(from_statement) = &mut query.from_statement {
+        visitor.visit_from(from_statement);
+    }
+    if let Some(where_statement) = &mut query.where_statement {
+        visitor.visit_expr(&mut where_statement.condition);
+    }
+    for column in &mut query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_delete_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut DeleteQuery) {
+    visitor.visit_table(&mut query.table);
+    if let Some(using_statement) = &mut query.using_statement {
+        visitor.visit_from(using_statement);
+    }
+    if let Some(where_statement) = &mut query.where_statement {
+        visitor.visit_expr(&mut where_statement.condition);
+    }
+    for column in &mut query.returning {
+        visitor.visit_column_statement(column);
+    }
+}
+
+pub fn walk_column_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    column: &mut ColumnStatement,
+) {
+    if let ColumnStatement::Expr { expr, .. } = column {
+        visitor.visit_expr(expr);
+    }
+}
+
+pub fn walk_from_mut<V: VisitorMut + ?Sized>(visitor: &mut V, from_statement: &mut FromStatement) {
+    for table in &mut from_statement.tables {
+        visitor.visit_table(table);
+        walk_table_arguments_mut(visitor, table);
+    }
+    for join in &mut from_statement.joins {
+        visitor.visit_join(join);
+    }
+}
+
+fn walk_table_arguments_mut<V: VisitorMut + ?Sized>(visitor: &mut V, table: &mut TableStatement) {
+    for argument in table.arguments.iter_mut().flatten() {
+        visitor.visit_expr(argument);
+    }
+}
+
+pub fn walk_join_mut<V: VisitorMut + ?Sized>(visitor: &mut V, join: &mut JoinStatement) {
+    visitor.visit_table(&mut join.table);
+    walk_table_arguments_mut(visitor, &mut join.table);
+    if let Some(on) = &mut join.on {
+        visitor.visit_expr(on);
+    }
+}
+
+pub fn walk_assignment_mut<V: VisitorMut + ?Sized>(visitor: &mut V, assignment: &mut Assignment) {
+    visitor.visit_expr(&mut assignment.value);
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Column(column) => visitor.visit_column(column),
+        Expr::Literal(literal) => visitor.visit_literal(literal),
+        Expr::Parameter(parameter) => visitor.visit_parameter(parameter),
+        Expr::Function(function) => visitor.visit_function(function),
+        Expr::Wildcard | Expr::Default | Expr::TypedString { .. } => {}
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { left, right, .. } | Expr::IsDistinctFrom { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Like {
+            expr,
+            pattern,
+            escape,
+            ..
+        } => {
+            visitor.visit_expr(expr);
+            visitor.visit_expr(pattern);
+            if let Some(escape) = escape {
+                visitor.visit_expr(escape);
+            }
+        }
+        Expr::InList { expr, list, .. } => {
+            visitor.visit_expr(expr);
+            for item in list {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            visitor.visit_expr(expr);
+            visitor.visit_select(subquery);
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            visitor.visit_expr(expr);
+            visitor.visit_expr(low);
+            visitor.visit_expr(high);
+        }
+        Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => visitor.visit_select(subquery),
+        Expr::Tuple(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::Case {
+            operand,
+            when_then,
+            else_result,
+        } => {
+            if let Some(operand) = operand {
+                visitor.visit_expr(operand);
+            }
+            for (condition, result) in when_then {
+                visitor.visit_expr(condition);
+                visitor.visit_expr(result);
+            }
+            if let Some(else_result) = else_result {
+                visitor.visit_expr(else_result);
+            }
+        }
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    for argument in &mut function.arguments {
+        visitor.visit_expr(argument);
+    }
+    for column in &mut function.within_group {
+        visitor.visit_expr(&mut column.expr);
+    }
+    if let Some(window) = &mut function.over {
+        for expr in &mut window.partition_by {
+            visitor.visit_expr(expr);
+        }
+        for column in &mut window.order_by {
+            visitor.visit_expr(&mut column.expr);
+        }
+        if let Some(frame) = &mut window.frame {
+            for bound in [&mut frame.start, &mut frame.end] {
+                if let FrameBound::Preceding(offset) | FrameBound::Following(offset) = bound {
+                    visitor.visit_expr(offset);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::expr::{Operator, OperatorSpan, UnaryOperator};
+
+    fn parse_query(input: &str) -> Query {
+        Queries::parse(input).unwrap().queries.remove(0)
+    }
+
+    #[derive(Default)]
+    struct Tables(Vec<String>);
+
+    impl Visitor for Tables {
+        fn visit_table(&mut self, table: &TableStatement) {
+            self.0.push(table.table_name.clone());
+        }
+    }
+
+    #[test]
+    fn collects_referenced_tables() {
+        let query = parse_query(
+            "explain select a from t join s on t.id = s.id \
+             where exists (select 1 from u where u.x in (select x from v)) \
+             and t.y = (select max(y) from w)",
+        );
+        let mut tables = Tables::default();
+        tables.visit_query(&query);
+        assert_eq!(tables.0, vec!["t", "s", "u", "v", "w"]);
+
+        let query = parse_query(
+            "insert into t select * from s on conflict (a) do update set b = (select 1 from u) \
+             returning (select 2 from v)",
+        );
+        let mut tables = Tables::default();
+        tables.visit_query(&query);
+        assert_eq!(tables.0, vec!["t", "s", "u", "v"]);
+
+        for (input, expected) in [
+            ("create index i on t (a)", vec!["t"]),
+            ("drop table t, s", vec!["t", "s"]),
+            ("drop index i", vec![]),
+            ("analyze t", vec!["t"]),
+            ("analyze", vec![]),
+        ] {
+            let mut tables = Tables::default();
+            tables.visit_query(&parse_query(input));
+            assert_eq!(tables.0, expected, "{}", input);
+        }
+    }
+
+    #[derive(Default)]
+    struct Functions(Vec<String>);
+
+    impl Visitor for Functions {
+        fn visit_function(&mut self, function: &Function) {
+            self.0.push(function.name.clone());
+            walk_function(self, function);
+        }
+    }
+
+    #[test]
+    fn visits_nested_function_calls() {
+        let query = parse_query(
+            "update t set a = lower(upper(b)) where case when c then abs(d) end > 0 \
+             returning coalesce(a, 0)",
+        );
+        let mut functions = Functions::default();
+        functions.visit_query(&query);
+        assert_eq!(functions.0, vec!["lower", "upper", "abs", "coalesce"]);
+    }
+
+    /// Rewrites `a <> b` to `NOT a = b`.
+    struct NotEqualRewriter;
+
+    impl VisitorMut for NotEqualRewriter {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            walk_expr_mut(self, expr);
+            if let Expr::Binary {
+                operator: operator @ Operator::NotEqual,
+                ..
+            } = expr
+            {
+                *operator = Operator::Equal;
+                let equal = std::mem::replace(expr, Expr::Default);
+                *expr = Expr::Unary {
+                    operator: UnaryOperator::Not,
+                    expr: Box::new(equal),
+                    span: OperatorSpan::default(),
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn rewrites_expressions_in_place() {
+        let mut query = parse_query(
+            "delete from t using s where t.a <> s.a and t.b in (select c from u where c <> 1)",
+        );
+        NotEqualRewriter.visit_query(&mut query);
+        assert_eq!(
+            query,
+            parse_query(
+                "delete from t using s where not t.a = s.a \
+                 and t.b in (select c from u where not c = 1)"
+            )
+        );
+    }
+
+    /// Qualifies unqualified tables with the `archive` schema.
+    struct SchemaQualifier;
+
+    impl VisitorMut for SchemaQualifier {
+        fn visit_table(&mut self, table: &mut TableStatement) {
+            table
+                .schema_name
+                .get_or_insert_with(|| "archive".to_string());
+        }
+    }
+
+    #[test]
+    fn rewrites_tables_named_by_ddl() {
+        for (input, expected) in [
+            ("create index i on t (a)", "create index i on archive.t (a)"),
+            ("drop table t, public.s", "drop table archive.t, public.s"),
+            ("analyze t", "analyze archive.t"),
+        ] {
+            let mut query = parse_query(input);
+            SchemaQualifier.visit_query(&mut query);
+            assert_eq!(query, parse_query(expected));
+        }
+    }
+}
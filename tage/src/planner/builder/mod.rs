@@ -0,0 +1,330 @@
+//! Builds the logical plan of a bound statement.
+//!
+//! A `select` is planned in the order its clauses are evaluated: `from` and joins,
+//! `where`, grouping and aggregates, `having`, window functions, then the select list,
+//! `distinct`, `order by` and `limit`. Subqueries in expressions stay in the bound tree,
+//! they are planned when they are executed.
+
+use crate::analyser::aggregates::is_aggregate;
+use crate::analyser::bound::*;
+use crate::optimizer::referenced_columns;
+use crate::planner::{JoinType, LogicalPlan, PlanError};
+
+pub fn build_plan(query: &BoundQuery) -> Result<LogicalPlan, PlanError> {
+    match query {
+        BoundQuery::Select(select) => build_select(select),
+        BoundQuery::Insert(insert) => {
+            let input = match &insert.source {
+                BoundInsertSource::Values(rows) => LogicalPlan::values(rows.clone()),
+                BoundInsertSource::Select(select) => build_select(select)?,
+                BoundInsertSource::DefaultValues => {
+                    LogicalPlan::values(vec![vec![BoundExpr::Default; insert.columns.len()]])
+                }
+            };
+            Ok(LogicalPlan::insert(
+                input,
+                insert.table.clone(),
+                insert.columns.clone(),
+                insert.on_conflict.clone(),
+                insert.returning.clone(),
+            ))
+        }
+        BoundQuery::Update(update) => {
+            let input = target_rows(&update.table, update.from.as_ref(), &update.filter)?;
+            Ok(LogicalPlan::update(
+                input,
+                update.table.clone(),
+                update.assignments.clone(),
+                update.returning.clone(),
+            ))
+        }
+        BoundQuery::Delete(delete) => {
+            let input = target_rows(&delete.table, delete.using.as_ref(), &delete.filter)?;
+            Ok(LogicalPlan::delete(
+                input,
+                delete.table.clone(),
+                delete.returning.clone(),
+            ))
+        }
+        BoundQuery::Explain { .. } | BoundQuery::Utility(_) => Err(PlanError::NotPlannable),
+    }
+}
+
+pub fn build_select(select: &BoundSelect) -> Result<LogicalPlan, PlanError> {
+    let mut plan = match &select.from {
+        Some(from) => build_from(from)?,
+        None => LogicalPlan::values(vec![vec![]]),
+    };
+    if let Some(filter) = &select.filter {
+        plan = LogicalPlan::filter(plan, filter.clone());
+    }
+
+    let outputs = || {
+        select
+            .projection
+            .iter()
+            .map(|item| &item.expr)
+            .chain(select.order_by.iter().map(|(expr, _)| expr))
+    };
+    let mut aggregates = vec![];
+    for expr in outputs().chain(&select.having) {
+        collect_aggregates(expr, &mut aggregates);
+    }
+    if !select.group_by.is_empty() || !aggregates.is_empty() {
+        plan = LogicalPlan::aggregate(plan, group_keys(select, outputs()), aggregates);
+    }
+    if let Some(having) = &select.having {
+        plan = LogicalPlan::filter(plan, having.clone());
+    }
+
+    let mut functions = vec![];
+    for expr in outputs() {
+        collect_window_functions(expr, &mut functions);
+    }
+    if !functions.is_empty() {
+        plan = LogicalPlan::window(plan, functions);
+    }
+
+    if select.distinct {
+        // the sort can only see the columns which are left after the projection
+        let projected = |expr: &BoundExpr| select.projection.iter().any(|item| &item.expr == expr);
+        if !select.order_by.iter().all(|(expr, _)| projected(expr)) {
+            return Err(PlanError::DistinctOrderBy);
+        }
+        plan = LogicalPlan::distinct(LogicalPlan::project(plan, select.projection.clone()));
+    } else {
+        if select.unions.is_empty() && !select.order_by.is_empty() {
+            plan = LogicalPlan::sort(plan, select.order_by.clone());
+        }
+        plan = LogicalPlan::project(plan, select.projection.clone());
+    }
+    // the order by of a union names its result columns
+    for union in &select.unions {
+        let query = build_select(&union.query)?;
+        plan = match plan {
+            LogicalPlan::Union {
+                mut inputs, all, ..
+            } if all == union.all => {
+                inputs.push(query);
+                LogicalPlan::union(inputs, all)
+            }
+            plan => LogicalPlan::union(vec![plan, query], union.all),
+        };
+    }
+    if (select.distinct || !select.unions.is_empty()) && !select.order_by.is_empty() {
+        plan = LogicalPlan::sort(plan, select.order_by.clone());
+    }
+
+    if select.limit.is_some() || select.offset.is_some() {
+        plan = LogicalPlan::limit(plan, select.limit, select.offset.unwrap_or(0));
+    }
+    Ok(plan)
+}
+
+/// Tables of a `from` list are cross joined, explicit joins follow in order.
+fn build_from(from: &BoundFrom) -> Result<LogicalPlan, PlanError> {
+    let mut relations = from.relations.iter().map(build_relation);
+    let first = relations.next().expect("from has a relation")?;
+    let mut plan = relations.try_fold(first, |plan, relation| {
+        Ok(LogicalPlan::join(plan, relation?, JoinType::Cross, None))
+    })?;
+    for join in &from.joins {
+        plan = LogicalPlan::join(
+            plan,
+            build_relation(&join.relation)?,
+            join.join_type.into(),
+            join.on.clone(),
+        );
+    }
+    Ok(plan)
+}
+
+/// A view is planned as its query, with the rows renamed to the relation.
+fn build_relation(relation: &BoundRelation) -> Result<LogicalPlan, PlanError> {
+    match &relation.view {
+        Some(query) => Ok(LogicalPlan::subquery_alias(
+            build_select(query)?,
+            relation.name.clone(),
+            relation.id,
+        )),
+        None => Ok(LogicalPlan::scan(relation.clone())),
+    }
+}
+
+/// Rows an `update` or `delete` changes, joined with the rows of `from` or `using`.
+fn target_rows(
+    table: &BoundRelation,
+    from: Option<&BoundFrom>,
+    filter: &Option<BoundExpr>,
+) -> Result<LogicalPlan, PlanError> {
+    let mut plan = LogicalPlan::scan_with_row_id(table.clone());
+    if let Some(from) = from {
+        plan = LogicalPlan::join(plan, build_from(from)?, JoinType::Cross, None);
+    }
+    if let Some(filter) = filter {
+        plan = LogicalPlan::filter(plan, filter.clone());
+    }
+    Ok(plan)
+}
+
+/// Grouped expressions, with the columns the query reads of the tables whose primary key
+/// is grouped. They don't split any group as the key determines them.
+fn group_keys<'s>(
+    select: &'s BoundSelect,
+    outputs: impl Iterator<Item = &'s BoundExpr>,
+) -> Vec<BoundExpr> {
+    let is_grouped = |relation: &BoundRelation, column_id| {
+        select.group_by.iter().any(|expr| {
+            matches!(expr, BoundExpr::Column(column)
+                if column.relation == relation.id && column.column_id == column_id)
+        })
+    };
+    let dependent: Vec<_> = select
+        .from
+        .iter()
+        .flat_map(|from| {
+            from.relations
+                .iter()
+                .chain(from.joins.iter().map(|join| &join.relation))
+        })
+        .filter(|relation| {
+            let primary_key = relation.table.primary_key();
+            !primary_key.is_empty() && primary_key.iter().all(|id| is_grouped(relation, *id))
+        })
+        .map(|relation| relation.id)
+        .collect();
+    let mut keys = select.group_by.clone();
+    if dependent.is_empty() {
+        return keys;
+    }
+    for expr in outputs.chain(&select.having) {
+        for column in referenced_columns(expr) {
+            if !dependent.contains(&column.relation) {
+                continue;
+            }
+            let key = BoundExpr::Column(column);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+/// Aggregate calls of the expression, without duplicates. Subqueries have their own.
+fn collect_aggregates(expr: &BoundExpr, aggregates: &mut Vec<BoundExpr>) {
+    match expr {
+        BoundExpr::Function(function) if is_aggregate(function) => {
+            if !aggregates.contains(expr) {
+                aggregates.push(expr.clone());
+            }
+        }
+        _ => {
+            for child in expr.children() {
+                collect_aggregates(child, aggregates);
+            }
+        }
+    }
+}
+
+fn collect_window_functions(expr: &BoundExpr, functions: &mut Vec<BoundExpr>) {
+    match expr {
+        BoundExpr::Function(BoundFunction { over: Some(_), .. }) => {
+            if !functions.contains(expr) {
+                functions.push(expr.clone());
+            }
+        }
+        _ => {
+            for child in expr.children() {
+                collect_window_functions(child, functions);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::analyse;
+    use crate::catalog::MemoryCatalog;
+    use crate::parser::Queries;
+    use crate::value::DataType;
+
+    fn build(input: &str) -> Result<LogicalPlan, PlanError> {
+        let mut catalog = MemoryCatalog::new();
+        let users = catalog.create_table(
+            "public",
+            "users",
+            &[("id", DataType::Integer), ("name", DataType::Text)],
+        );
+        catalog.set_primary_key(users, &["id"]);
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        build_plan(&analyse(&query, &catalog).unwrap())
+    }
+
+    fn schema(plan: &LogicalPlan) -> Vec<(&str, Option<DataType>)> {
+        plan.schema()
+            .iter()
+            .map(|column| (column.name.as_str(), column.data_type))
+            .collect()
+    }
+
+    #[test]
+    fn carries_schema_of_every_node() {
+        let plan = build("select name n, count(*), null from users group by name").unwrap();
+        assert_eq!(
+            schema(&plan),
+            vec![
+                ("n", Some(DataType::Text)),
+                ("count", Some(DataType::BigInt)),
+                ("?column?", None)
+            ]
+        );
+        let LogicalPlan::Project { input, .. } = &plan else {
+            panic!("expected a projection, got {:?}", plan);
+        };
+        assert_eq!(
+            schema(input),
+            vec![
+                ("name", Some(DataType::Text)),
+                ("count", Some(DataType::BigInt))
+            ]
+        );
+        assert_eq!(
+            schema(input.inputs()[0]),
+            vec![
+                ("id", Some(DataType::Integer)),
+                ("name", Some(DataType::Text))
+            ]
+        );
+
+        let plan = build("select a.id, b.id from users a cross join users b").unwrap();
+        assert_eq!(schema(plan.inputs()[0]).len(), 4);
+        assert!(schema(&build("delete from users").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn groups_by_columns_of_a_grouped_primary_key() {
+        let plan = build("select id, name from users group by id").unwrap();
+        let LogicalPlan::Project { input, .. } = &plan else {
+            panic!("expected a projection, got {:?}", plan);
+        };
+        assert_eq!(
+            schema(input),
+            vec![
+                ("id", Some(DataType::Integer)),
+                ("name", Some(DataType::Text))
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unplannable_queries() {
+        assert_eq!(
+            build("select distinct name from users order by id"),
+            Err(PlanError::DistinctOrderBy)
+        );
+        assert!(build("select distinct name from users order by name").is_ok());
+        assert_eq!(build("commit"), Err(PlanError::NotPlannable));
+    }
+}
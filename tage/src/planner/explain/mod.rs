@@ -0,0 +1,697 @@
+//! Text of a plan for `EXPLAIN`, one node per line with its inputs indented below it.
+//!
+//! Columns are qualified with the name of their relation. Subqueries are shown as
+//! `(SubPlan n)` and their plans are written below the node which uses them.
+
+use crate::analyser::bound::*;
+use crate::parser::expr::{
+    FrameUnits, UnaryOperator, ATOM_PRECEDENCE, COMPARISON_PRECEDENCE, NOT_PRECEDENCE,
+    UNARY_PRECEDENCE,
+};
+use crate::parser::Order;
+use crate::planner::builder::build_select;
+use crate::planner::{output_name, IndexRange, LogicalPlan, ROW_ID_COLUMN_ID};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Bound;
+
+pub fn explain(plan: &LogicalPlan) -> String {
+    let mut explain = Explain::default();
+    explain.collect_names(plan);
+    explain.node(plan, 0);
+    explain.lines.join("\n")
+}
+
+#[derive(Default)]
+struct Explain {
+    names: HashMap<RelationId, String>,
+    lines: Vec<String>,
+    subplans: usize,
+    /// Subqueries of the node being written, their plans follow the node
+    pending: Vec<(usize, BoundSelect)>,
+}
+
+impl Explain {
+    fn collect_names(&mut self, plan: &LogicalPlan) {
+        match plan {
+            LogicalPlan::Scan { relation, .. }
+            | LogicalPlan::TableFunction { relation, .. }
+            | LogicalPlan::Update {
+                table: relation, ..
+            }
+            | LogicalPlan::Delete {
+                table: relation, ..
+            } => self.add_name(relation),
+            LogicalPlan::Insert {
+                table, on_conflict, ..
+            } => {
+                self.add_name(table);
+                if let Some(BoundOnConflict {
+                    action: BoundConflictAction::DoUpdate { excluded, .. },
+                    ..
+                }) = on_conflict
+                {
+                    self.add_name(excluded);
+                }
+            }
+            LogicalPlan::SubqueryAlias {
+                alias, relation, ..
+            } => {
+                self.names.insert(*relation, alias.clone());
+            }
+            _ => {}
+        }
+        for input in plan.inputs() {
+            self.collect_names(input);
+        }
+    }
+
+    fn add_name(&mut self, relation: &BoundRelation) {
+        self.names.insert(relation.id, relation.name.clone());
+    }
+
+    fn node(&mut self, plan: &LogicalPlan, depth: usize) {
+        let line = self.describe(plan);
+        self.lines.push(format!("{}{}", "  ".repeat(depth), line));
+        for (number, subquery) in mem::take(&mut self.pending) {
+            self.lines
+                .push(format!("{}SubPlan {}", "  ".repeat(depth + 1), number));
+            match build_select(&subquery) {
+                Ok(subplan) => {
+                    self.collect_names(&subplan);
+                    self.node(&subplan, depth + 2);
+                }
+                Err(error) => self
+                    .lines
+                    .push(format!("{}{}", "  ".repeat(depth + 2), error)),
+            }
+        }
+        for input in plan.inputs() {
+            self.node(input, depth + 1);
+        }
+    }
+
+    fn describe(&mut self, plan: &LogicalPlan) -> String {
+        match plan {
+            LogicalPlan::Scan {
+                relation,
+                schema,
+                index,
+            } => {
+                // columns are listed once some of them are pruned, the row id isn't
+                let columns: Vec<_> = schema
+                    .iter()
+                    .filter(|column| {
+                        !matches!(&column.expr, BoundExpr::Column(column)
+                            if column.column_id == ROW_ID_COLUMN_ID)
+                    })
+                    .map(|column| column.name.as_str())
+                    .collect();
+                let scan = if columns.len() == relation.table.columns.len() {
+                    format!("Scan: {}", relation_name(relation))
+                } else {
+                    format!("Scan: {} ({})", relation_name(relation), columns.join(", "))
+                };
+                match index {
+                    Some(range) => {
+                        format!("{} [index {}]", scan, self.index_range(relation, range))
+                    }
+                    None => scan,
+                }
+            }
+            LogicalPlan::TableFunction { relation, .. } => {
+                let arguments = match &relation.function {
+                    Some(function) => self.exprs(&function.arguments),
+                    None => String::new(),
+                };
+                let call = format!("{}({})", relation.table.name, arguments);
+                match relation.name == relation.table.name {
+                    true => format!("Function Scan: {}", call),
+                    false => format!("Function Scan: {} AS {}", call, relation.name),
+                }
+            }
+            LogicalPlan::Filter { predicate, .. } => format!("Filter: {}", self.expr(predicate)),
+            LogicalPlan::Project { items, .. } => format!("Project: {}", self.items(items)),
+            LogicalPlan::Join {
+                join_type,
+                on,
+                algorithm,
+                ..
+            } => {
+                let join = match on {
+                    Some(on) => format!("Join: {} ON {}", join_type, self.expr(on)),
+                    None => format!("Join: {}", join_type),
+                };
+                match algorithm {
+                    Some(algorithm) => format!("{} [{}]", join, algorithm),
+                    None => join,
+                }
+            }
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => format!(
+                "Aggregate: group_by=[{}], aggregates=[{}]",
+                self.exprs(group_by),
+                self.exprs(aggregates)
+            ),
+            LogicalPlan::Sort { order_by, .. } => format!("Sort: {}", self.order_by(order_by)),
+            LogicalPlan::Limit { limit, offset, .. } => {
+                let limit = limit.map_or_else(|| "ALL".to_string(), |limit| limit.to_string());
+                match offset {
+                    0 => format!("Limit: {}", limit),
+                    offset => format!("Limit: {} OFFSET {}", limit, offset),
+                }
+            }
+            LogicalPlan::Distinct { .. } => "Distinct".to_string(),
+            LogicalPlan::Union { all: true, .. } => "Union: ALL".to_string(),
+            LogicalPlan::Union { all: false, .. } => "Union".to_string(),
+            LogicalPlan::Values { rows, .. } => {
+                let rows: Vec<_> = rows
+                    .iter()
+                    .map(|row| format!("({})", self.exprs(row)))
+                    .collect();
+                format!("Values: {}", rows.join(", "))
+            }
+            LogicalPlan::Insert {
+                table,
+                columns,
+                on_conflict,
+                returning,
+                ..
+            } => {
+                let columns: Vec<_> = columns.iter().map(|column| column.name.as_str()).collect();
+                let mut line = format!("Insert: {} ({})", relation_name(table), columns.join(", "));
+                if let Some(on_conflict) = on_conflict {
+                    let columns: Vec<_> = on_conflict
+                        .columns
+                        .iter()
+                        .map(|column| column.name.as_str())
+                        .collect();
+                    line.push_str(" ON CONFLICT");
+                    if !columns.is_empty() {
+                        line.push_str(&format!(" ({})", columns.join(", ")));
+                    }
+                    match &on_conflict.action {
+                        BoundConflictAction::DoNothing => line.push_str(" DO NOTHING"),
+                        BoundConflictAction::DoUpdate {
+                            assignments,
+                            filter,
+                            ..
+                        } => {
+                            line.push_str(&format!(
+                                " DO UPDATE SET {}",
+                                self.assignments(assignments)
+                            ));
+                            if let Some(filter) = filter {
+                                line.push_str(&format!(" WHERE {}", self.expr(filter)));
+                            }
+                        }
+                    }
+                }
+                line + &self.returning(returning)
+            }
+            LogicalPlan::Update {
+                table,
+                assignments,
+                returning,
+                ..
+            } => format!(
+                "Update: {} SET {}{}",
+                relation_name(table),
+                self.assignments(assignments),
+                self.returning(returning)
+            ),
+            LogicalPlan::Delete {
+                table, returning, ..
+            } => format!(
+                "Delete: {}{}",
+                relation_name(table),
+                self.returning(returning)
+            ),
+            LogicalPlan::Window { functions, .. } => format!("Window: {}", self.exprs(functions)),
+            LogicalPlan::SubqueryAlias { alias, .. } => format!("SubqueryAlias: {}", alias),
+        }
+    }
+
+    /// The index and the condition on its first column the range is, e.g.
+    /// `users_age: users.age >= 18 AND users.age < 65`.
+    fn index_range(&mut self, relation: &BoundRelation, range: &IndexRange) -> String {
+        let column = relation
+            .table
+            .columns
+            .iter()
+            .find(|column| Some(&column.id) == range.index.columns.first())
+            .map_or("?", |column| column.name.as_str());
+        let column = match self.names.get(&relation.id) {
+            Some(name) => format!("{}.{}", name, column),
+            None => column.to_string(),
+        };
+        let condition = match (&range.low, &range.high) {
+            (Bound::Included(low), Bound::Included(high)) if low == high => {
+                format!("{} = {}", column, self.expr(low))
+            }
+            (low, high) => {
+                let mut conditions = vec![];
+                match low {
+                    Bound::Included(low) => {
+                        conditions.push(format!("{} >= {}", column, self.expr(low)))
+                    }
+                    Bound::Excluded(low) => {
+                        conditions.push(format!("{} > {}", column, self.expr(low)))
+                    }
+                    Bound::Unbounded => {}
+                }
+                match high {
+                    Bound::Included(high) => {
+                        conditions.push(format!("{} <= {}", column, self.expr(high)))
+                    }
+                    Bound::Excluded(high) => {
+                        conditions.push(format!("{} < {}", column, self.expr(high)))
+                    }
+                    Bound::Unbounded => {}
+                }
+                conditions.join(" AND ")
+            }
+        };
+        format!("{}: {}", range.index.name, condition)
+    }
+
+    fn exprs(&mut self, exprs: &[BoundExpr]) -> String {
+        let exprs: Vec<_> = exprs.iter().map(|expr| self.expr(expr)).collect();
+        exprs.join(", ")
+    }
+
+    /// Names are shown when they are not the default ones, e.g. an alias.
+    fn items(&mut self, items: &[ProjectionItem]) -> String {
+        let items: Vec<_> = items
+            .iter()
+            .map(|item| {
+                let expr = self.expr(&item.expr);
+                if item.name == output_name(&item.expr) {
+                    expr
+                } else {
+                    format!("{} AS {}", expr, item.name)
+                }
+            })
+            .collect();
+        items.join(", ")
+    }
+
+    fn returning(&mut self, items: &[ProjectionItem]) -> String {
+        match items {
+            [] => String::new(),
+            items => format!(" RETURNING {}", self.items(items)),
+        }
+    }
+
+    fn assignments(&mut self, assignments: &[BoundAssignment]) -> String {
+        let assignments: Vec<_> = assignments
+            .iter()
+            .map(|assignment| {
+                let value = self.expr(&assignment.value);
+                match assignment.columns.as_slice() {
+                    [column] => format!("{} = {}", column.name, value),
+                    columns => {
+                        let names: Vec<_> =
+                            columns.iter().map(|column| column.name.as_str()).collect();
+                        format!("({}) = {}", names.join(", "), value)
+                    }
+                }
+            })
+            .collect();
+        assignments.join(", ")
+    }
+
+    fn order_by(&mut self, order_by: &[(BoundExpr, Order)]) -> String {
+        let order_by: Vec<_> = order_by
+            .iter()
+            .map(|(expr, order)| format!("{}{}", self.expr(expr), order))
+            .collect();
+        order_by.join(", ")
+    }
+
+    fn subplan(&mut self, subquery: &BoundSelect) -> String {
+        self.subplans += 1;
+        self.pending.push((self.subplans, subquery.clone()));
+        format!("(SubPlan {})", self.subplans)
+    }
+
+    /// The expression in parentheses if it binds weaker than `precedence`.
+    fn operand(&mut self, expr: &BoundExpr, precedence: u8) -> String {
+        let text = self.expr(expr);
+        if self::precedence(expr) < precedence {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+
+    fn frame_bound(&mut self, bound: &BoundFrameBound) -> String {
+        match bound {
+            BoundFrameBound::UnboundedPreceding => "UNBOUNDED PRECEDING".to_string(),
+            BoundFrameBound::Preceding(offset) => format!("{} PRECEDING", self.expr(offset)),
+            BoundFrameBound::CurrentRow => "CURRENT ROW".to_string(),
+            BoundFrameBound::Following(offset) => format!("{} FOLLOWING", self.expr(offset)),
+            BoundFrameBound::UnboundedFollowing => "UNBOUNDED FOLLOWING".to_string(),
+        }
+    }
+
+    fn expr(&mut self, expr: &BoundExpr) -> String {
+        let not = |negated: &bool| if *negated { "NOT " } else { "" };
+        match expr {
+            BoundExpr::Column(column) => match self.names.get(&column.relation) {
+                Some(relation) => format!("{}.{}", relation, column.name),
+                None => column.name.clone(),
+            },
+            BoundExpr::Literal(literal) => literal.to_string(),
+            BoundExpr::Function(function) => {
+                let distinct = if function.distinct { "DISTINCT " } else { "" };
+                let mut text = format!(
+                    "{}({}{})",
+                    function.name,
+                    distinct,
+                    self.exprs(&function.arguments)
+                );
+                if !function.within_group.is_empty() {
+                    let order_by = self.order_by(&function.within_group);
+                    text.push_str(&format!(" WITHIN GROUP (ORDER BY {})", order_by));
+                }
+                if let Some(window) = &function.over {
+                    let mut clauses = vec![];
+                    if !window.partition_by.is_empty() {
+                        clauses.push(format!("PARTITION BY {}", self.exprs(&window.partition_by)));
+                    }
+                    if !window.order_by.is_empty() {
+                        clauses.push(format!("ORDER BY {}", self.order_by(&window.order_by)));
+                    }
+                    if let Some(frame) = &window.frame {
+                        let units = match frame.units {
+                            FrameUnits::Rows => "ROWS",
+                            FrameUnits::Range => "RANGE",
+                        };
+                        let (start, end) =
+                            (self.frame_bound(&frame.start), self.frame_bound(&frame.end));
+                        clauses.push(format!("{} BETWEEN {} AND {}", units, start, end));
+                    }
+                    text.push_str(&format!(" OVER ({})", clauses.join(" ")));
+                }
+                text
+            }
+            BoundExpr::Wildcard => "*".to_string(),
+            BoundExpr::Default => "DEFAULT".to_string(),
+            BoundExpr::Parameter(parameter) => parameter.to_string(),
+            BoundExpr::Unary { operator, expr, .. } => {
+                let (sign, precedence) = match operator {
+                    UnaryOperator::Not => ("NOT ", NOT_PRECEDENCE),
+                    UnaryOperator::Minus => ("-", UNARY_PRECEDENCE),
+                    UnaryOperator::Plus => ("+", UNARY_PRECEDENCE),
+                };
+                format!("{}{}", sign, self.operand(expr, precedence))
+            }
+            BoundExpr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let precedence = operator.precedence();
+                format!(
+                    "{} {} {}",
+                    self.operand(left, precedence),
+                    operator,
+                    self.operand(right, precedence + 1)
+                )
+            }
+            BoundExpr::IsNull { expr, negated } => format!(
+                "{} IS {}NULL",
+                self.operand(expr, COMPARISON_PRECEDENCE),
+                not(negated)
+            ),
+            BoundExpr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => format!(
+                "{} IS {}DISTINCT FROM {}",
+                self.operand(left, COMPARISON_PRECEDENCE),
+                not(negated),
+                self.operand(right, COMPARISON_PRECEDENCE + 1)
+            ),
+            BoundExpr::InList {
+                expr,
+                list,
+                negated,
+            } => format!(
+                "{} {}IN ({})",
+                self.operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                self.exprs(list)
+            ),
+            BoundExpr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => format!(
+                "{} {}IN {}",
+                self.operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                self.subplan(subquery)
+            ),
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => format!(
+                "{} {}BETWEEN {} AND {}",
+                self.operand(expr, COMPARISON_PRECEDENCE),
+                not(negated),
+                self.operand(low, COMPARISON_PRECEDENCE + 1),
+                self.operand(high, COMPARISON_PRECEDENCE + 1)
+            ),
+            BoundExpr::Like {
+                expr,
+                pattern,
+                escape,
+                kind,
+                negated,
+            } => {
+                let mut text = format!(
+                    "{} {} {}",
+                    self.operand(expr, COMPARISON_PRECEDENCE),
+                    kind.operator(*negated),
+                    self.operand(pattern, COMPARISON_PRECEDENCE + 1)
+                );
+                if let Some(escape) = escape {
+                    text += &format!(
+                        " ESCAPE {}",
+                        self.operand(escape, COMPARISON_PRECEDENCE + 1)
+                    );
+                }
+                text
+            }
+            BoundExpr::Exists { subquery, negated } => {
+                format!("{}EXISTS {}", not(negated), self.subplan(subquery))
+            }
+            BoundExpr::Subquery(subquery) => self.subplan(subquery),
+            BoundExpr::Tuple(exprs) => format!("({})", self.exprs(exprs)),
+            BoundExpr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                let mut text = "CASE".to_string();
+                if let Some(operand) = operand {
+                    text.push_str(&format!(" {}", self.expr(operand)));
+                }
+                for (condition, result) in when_then {
+                    text.push_str(&format!(
+                        " WHEN {} THEN {}",
+                        self.expr(condition),
+                        self.expr(result)
+                    ));
+                }
+                if let Some(else_result) = else_result {
+                    text.push_str(&format!(" ELSE {}", self.expr(else_result)));
+                }
+                text + " END"
+            }
+            BoundExpr::Cast { expr, data_type } => {
+                format!("CAST({} AS {})", self.expr(expr), data_type)
+            }
+        }
+    }
+}
+
+fn precedence(expr: &BoundExpr) -> u8 {
+    match expr {
+        BoundExpr::Binary { operator, .. } => operator.precedence(),
+        BoundExpr::IsNull { .. }
+        | BoundExpr::IsDistinctFrom { .. }
+        | BoundExpr::InList { .. }
+        | BoundExpr::InSubquery { .. }
+        | BoundExpr::Between { .. }
+        | BoundExpr::Like { .. } => COMPARISON_PRECEDENCE,
+        BoundExpr::Unary {
+            operator: UnaryOperator::Not,
+            ..
+        } => NOT_PRECEDENCE,
+        BoundExpr::Unary { .. } => UNARY_PRECEDENCE,
+        _ => ATOM_PRECEDENCE,
+    }
+}
+
+fn relation_name(relation: &BoundRelation) -> String {
+    let table = &relation.table;
+    if relation.name == table.name {
+        format!("{}.{}", table.schema_name, table.name)
+    } else {
+        format!("{}.{} AS {}", table.schema_name, table.name, relation.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::analyse;
+    use crate::catalog::MemoryCatalog;
+    use crate::parser::Queries;
+    use crate::planner::build_plan;
+    use crate::value::DataType;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        let users = catalog.create_table(
+            "public",
+            "users",
+            &[
+                ("id", DataType::Integer),
+                ("name", DataType::Text),
+                ("age", DataType::Integer),
+            ],
+        );
+        catalog.set_primary_key(users, &["id"]);
+        catalog.create_table(
+            "public",
+            "orders",
+            &[
+                ("id", DataType::Integer),
+                ("user_id", DataType::Integer),
+                ("total", DataType::Double),
+            ],
+        );
+        catalog
+    }
+
+    fn plan(input: &str) -> String {
+        let query = Queries::parse(input).unwrap().queries.remove(0);
+        let bound = analyse(&query, &catalog()).unwrap();
+        explain(&build_plan(&bound).unwrap())
+    }
+
+    #[test]
+    fn explains_select() {
+        assert_eq!(
+            plan(
+                "select distinct u.name n, count(*) from users u left join orders o \
+                 on u.id = o.user_id where (u.age + 1) * 2 > 3 group by u.name \
+                 having count(*) > 1 order by n desc limit 10 offset 5"
+            ),
+            "Limit: 10 OFFSET 5
+  Sort: u.name DESC
+    Distinct
+      Project: u.name AS n, count(*)
+        Filter: count(*) > CAST(1 AS BIGINT)
+          Aggregate: group_by=[u.name], aggregates=[count(*)]
+            Filter: (u.age + 1) * 2 > 3
+              Join: LEFT ON u.id = o.user_id
+                Scan: public.users AS u
+                Scan: public.orders AS o"
+        );
+        assert_eq!(
+            plan(
+                "select name, row_number() over (partition by age order by users.id desc) \
+                 from users, orders where users.id = orders.user_id order by 1"
+            ),
+            "Project: users.name, row_number() OVER (PARTITION BY users.age ORDER BY users.id DESC)
+  Sort: users.name
+    Window: row_number() OVER (PARTITION BY users.age ORDER BY users.id DESC)
+      Filter: users.id = orders.user_id
+        Join: CROSS
+          Scan: public.users
+          Scan: public.orders"
+        );
+        assert_eq!(
+            plan(
+                "select sum(age) over (order by id rows between 1 preceding and current row), \
+                 percentile_cont(0.5) within group (order by age desc) over () from users"
+            ),
+            "Project: sum(users.age) OVER (ORDER BY users.id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW), \
+percentile_cont(0.5) WITHIN GROUP (ORDER BY users.age DESC) OVER ()
+  Window: sum(users.age) OVER (ORDER BY users.id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW), \
+percentile_cont(0.5) WITHIN GROUP (ORDER BY users.age DESC) OVER ()
+    Scan: public.users"
+        );
+        assert_eq!(
+            plan("select 1, -(2 + 3), 'a' || 'b' as ab"),
+            "Project: 1, -(2 + 3), 'a' || 'b' AS ab\n  Values: ()"
+        );
+    }
+
+    #[test]
+    fn explains_subqueries() {
+        assert_eq!(
+            plan(
+                "select name from users u where exists (select 1 from orders where user_id = u.id) \
+                 and id in (select user_id from orders) and age > (select max(age) from users)"
+            ),
+            "Project: u.name
+  Filter: EXISTS (SubPlan 1) AND u.id IN (SubPlan 2) AND u.age > (SubPlan 3)
+    SubPlan 1
+      Project: 1
+        Filter: orders.user_id = u.id
+          Scan: public.orders
+    SubPlan 2
+      Project: orders.user_id
+        Scan: public.orders
+    SubPlan 3
+      Project: max(users.age)
+        Aggregate: group_by=[], aggregates=[max(users.age)]
+          Scan: public.users
+    Scan: public.users AS u"
+        );
+    }
+
+    #[test]
+    fn explains_dml() {
+        assert_eq!(
+            plan(
+                "insert into users values (1, 'a', 2), (2, 'b', null) \
+                 on conflict (id) do update set name = excluded.name returning id"
+            ),
+            "Insert: public.users (id, name, age) ON CONFLICT (id) DO UPDATE SET name = excluded.name \
+             RETURNING users.id
+  Values: (1, 'a', 2), (2, 'b', NULL)"
+        );
+        assert_eq!(
+            plan("insert into orders default values"),
+            "Insert: public.orders (id, user_id, total)\n  Values: (DEFAULT, DEFAULT, DEFAULT)"
+        );
+        assert_eq!(
+            plan("update users set name = 'x', age = age + 1 from orders where orders.user_id = users.id"),
+            "Update: public.users SET name = 'x', age = users.age + 1
+  Filter: orders.user_id = users.id
+    Join: CROSS
+      Scan: public.users
+      Scan: public.orders"
+        );
+        assert_eq!(
+            plan("delete from orders where total is null returning id"),
+            "Delete: public.orders RETURNING orders.id
+  Filter: orders.total IS NULL
+    Scan: public.orders"
+        );
+    }
+}
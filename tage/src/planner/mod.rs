@@ -0,0 +1,712 @@
+//! Logical query plan, a tree of relational operators built from the bound tree.
+//!
+//! Every node carries the schema of the rows it returns. A column of the schema is
+//! identified by the expression which computes it, expressions of the nodes above refer
+//! to the column with an equal expression, e.g. a `Sort` above an `Aggregate` sorts by
+//! `count(*)` as it is written in the query.
+
+pub mod builder;
+pub mod explain;
+
+use crate::analyser::bound::{
+    BoundAssignment, BoundColumn, BoundConflictAction, BoundExpr, BoundOnConflict, BoundRelation,
+    ProjectionItem, RelationId,
+};
+use crate::catalog::{Column, ColumnId, Index, TableId};
+use crate::parser::lexer::Span;
+use crate::parser::{self, Order};
+use crate::value::DataType;
+use std::fmt;
+use std::ops::Bound;
+
+pub use builder::build_plan;
+pub use explain::explain;
+
+/// Table id of the columns of a relation which isn't a table, catalog ids start at 1.
+pub const DERIVED_TABLE_ID: TableId = TableId(0);
+
+/// Column id of the row id, catalog column ids start at 1.
+pub const ROW_ID_COLUMN_ID: ColumnId = ColumnId(0);
+
+/// Identifier of a stored row of the relation, named as in PostgreSQL.
+pub fn row_id_column(relation: &BoundRelation) -> BoundColumn {
+    BoundColumn {
+        relation: relation.id,
+        table_id: relation.table.id,
+        column_id: ROW_ID_COLUMN_ID,
+        name: "ctid".to_string(),
+        data_type: DataType::BigInt,
+        span: Span::default(),
+    }
+}
+
+/// Column of the rows a plan node returns.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlanColumn {
+    pub expr: BoundExpr,
+    pub name: String,
+    /// `None` for a column of unknown type, e.g. `select null`
+    pub data_type: Option<DataType>,
+}
+
+impl PlanColumn {
+    pub fn new(expr: BoundExpr, name: String) -> Self {
+        PlanColumn {
+            data_type: expr.data_type(),
+            expr,
+            name,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+    /// Rows of the left input which have a matching row, only the left columns are returned
+    Semi,
+    /// Rows of the left input which have no matching row
+    Anti,
+}
+
+/// How a join finds the matching rows, chosen by the optimizer.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JoinAlgorithm {
+    /// Builds a hash table of the right input on the equality keys and probes it with the
+    /// left rows
+    Hash,
+    /// Sorts both inputs on the equality keys and merges them
+    Merge,
+    /// Compares every left row with every right row
+    NestedLoop,
+}
+
+impl fmt::Display for JoinAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JoinAlgorithm::Hash => "hash",
+            JoinAlgorithm::Merge => "merge",
+            JoinAlgorithm::NestedLoop => "nested loop",
+        })
+    }
+}
+
+/// The rows of an index a scan reads, those whose first indexed value is within the
+/// bounds. Chosen by the optimizer, the filter above the scan still checks every row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndexRange {
+    pub index: Index,
+    pub low: Bound<BoundExpr>,
+    pub high: Bound<BoundExpr>,
+}
+
+impl From<parser::JoinType> for JoinType {
+    fn from(join_type: parser::JoinType) -> Self {
+        match join_type {
+            parser::JoinType::Inner => JoinType::Inner,
+            parser::JoinType::Left => JoinType::Left,
+            parser::JoinType::Right => JoinType::Right,
+            parser::JoinType::Full => JoinType::Full,
+            parser::JoinType::Cross => JoinType::Cross,
+        }
+    }
+}
+
+impl fmt::Display for JoinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JoinType::Inner => "INNER",
+            JoinType::Left => "LEFT",
+            JoinType::Right => "RIGHT",
+            JoinType::Full => "FULL",
+            JoinType::Cross => "CROSS",
+            JoinType::Semi => "SEMI",
+            JoinType::Anti => "ANTI",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LogicalPlan {
+    /// All rows of a table
+    Scan {
+        relation: BoundRelation,
+        schema: Vec<PlanColumn>,
+        /// `None` until the optimizer chooses an index to read instead of the table
+        index: Option<IndexRange>,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: BoundExpr,
+    },
+    Project {
+        input: Box<LogicalPlan>,
+        items: Vec<ProjectionItem>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Rows of the left input followed by the columns of the right one, only the left
+    /// columns for semi and anti joins
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        join_type: JoinType,
+        on: Option<BoundExpr>,
+        /// `None` until the optimizer chooses one
+        algorithm: Option<JoinAlgorithm>,
+        schema: Vec<PlanColumn>,
+    },
+    /// One row per group, a single row for the whole input when there is no `group by`
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<BoundExpr>,
+        aggregates: Vec<BoundExpr>,
+        schema: Vec<PlanColumn>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        order_by: Vec<(BoundExpr, Order)>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        /// `None` returns all rows after the offset
+        limit: Option<u64>,
+        offset: u64,
+    },
+    Distinct {
+        input: Box<LogicalPlan>,
+    },
+    /// Rows of a table function call, the relation has the call
+    TableFunction {
+        relation: BoundRelation,
+        schema: Vec<PlanColumn>,
+    },
+    /// Rows of all inputs, duplicates are removed unless `all` is set. The inputs have
+    /// the same number of columns, the schema is the one of the first input.
+    Union {
+        inputs: Vec<LogicalPlan>,
+        all: bool,
+        schema: Vec<PlanColumn>,
+    },
+    /// Rows given in the statement, `select` without `from` reads one empty row
+    Values {
+        rows: Vec<Vec<BoundExpr>>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Inserts the rows of the input into `columns` of the table
+    Insert {
+        input: Box<LogicalPlan>,
+        table: BoundRelation,
+        columns: Vec<Column>,
+        on_conflict: Option<BoundOnConflict>,
+        returning: Vec<ProjectionItem>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Updates the rows of the table the input returns
+    Update {
+        input: Box<LogicalPlan>,
+        table: BoundRelation,
+        assignments: Vec<BoundAssignment>,
+        returning: Vec<ProjectionItem>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Deletes the rows of the table the input returns
+    Delete {
+        input: Box<LogicalPlan>,
+        table: BoundRelation,
+        returning: Vec<ProjectionItem>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Rows of the input with the value of every window function appended
+    Window {
+        input: Box<LogicalPlan>,
+        functions: Vec<BoundExpr>,
+        schema: Vec<PlanColumn>,
+    },
+    /// Rows of the input as the rows of a relation, e.g. of a view. The columns are
+    /// referenced as columns of the relation.
+    SubqueryAlias {
+        input: Box<LogicalPlan>,
+        alias: String,
+        relation: RelationId,
+        schema: Vec<PlanColumn>,
+    },
+}
+
+impl LogicalPlan {
+    pub fn scan(relation: BoundRelation) -> Self {
+        let schema = relation
+            .table
+            .columns
+            .iter()
+            .map(|column| {
+                PlanColumn::new(
+                    BoundExpr::Column(relation.column(column)),
+                    column.name.clone(),
+                )
+            })
+            .collect();
+        match relation.function {
+            Some(_) => LogicalPlan::TableFunction { relation, schema },
+            None => LogicalPlan::Scan {
+                relation,
+                schema,
+                index: None,
+            },
+        }
+    }
+
+    /// The scan of the table an `update` or `delete` changes, it returns the row id after
+    /// the columns of the table.
+    pub fn scan_with_row_id(relation: BoundRelation) -> Self {
+        let row_id = BoundExpr::Column(row_id_column(&relation));
+        let LogicalPlan::Scan {
+            relation,
+            mut schema,
+            index,
+        } = LogicalPlan::scan(relation)
+        else {
+            unreachable!("a scan");
+        };
+        schema.push(PlanColumn::new(row_id, "ctid".to_string()));
+        LogicalPlan::Scan {
+            relation,
+            schema,
+            index,
+        }
+    }
+
+    pub fn filter(input: LogicalPlan, predicate: BoundExpr) -> Self {
+        LogicalPlan::Filter {
+            input: Box::new(input),
+            predicate,
+        }
+    }
+
+    pub fn project(input: LogicalPlan, items: Vec<ProjectionItem>) -> Self {
+        LogicalPlan::Project {
+            input: Box::new(input),
+            schema: projection_schema(&items),
+            items,
+        }
+    }
+
+    pub fn join(
+        left: LogicalPlan,
+        right: LogicalPlan,
+        join_type: JoinType,
+        on: Option<BoundExpr>,
+    ) -> Self {
+        let schema = match join_type {
+            JoinType::Semi | JoinType::Anti => left.schema().to_vec(),
+            _ => left
+                .schema()
+                .iter()
+                .chain(right.schema())
+                .cloned()
+                .collect(),
+        };
+        LogicalPlan::Join {
+            left: Box::new(left),
+            right: Box::new(right),
+            join_type,
+            on,
+            algorithm: None,
+            schema,
+        }
+    }
+
+    /// The join executed with the algorithm, other nodes are returned unchanged.
+    pub fn with_algorithm(mut self, chosen: Option<JoinAlgorithm>) -> Self {
+        if let LogicalPlan::Join { algorithm, .. } = &mut self {
+            *algorithm = chosen;
+        }
+        self
+    }
+
+    /// The schema has the group keys followed by the aggregates.
+    pub fn aggregate(
+        input: LogicalPlan,
+        group_by: Vec<BoundExpr>,
+        aggregates: Vec<BoundExpr>,
+    ) -> Self {
+        let schema = group_by
+            .iter()
+            .chain(&aggregates)
+            .map(|expr| {
+                let name = input
+                    .schema()
+                    .iter()
+                    .find(|column| &column.expr == expr)
+                    .map_or_else(|| output_name(expr), |column| column.name.clone());
+                PlanColumn::new(expr.clone(), name)
+            })
+            .collect();
+        LogicalPlan::Aggregate {
+            input: Box::new(input),
+            group_by,
+            aggregates,
+            schema,
+        }
+    }
+
+    pub fn sort(input: LogicalPlan, order_by: Vec<(BoundExpr, Order)>) -> Self {
+        LogicalPlan::Sort {
+            input: Box::new(input),
+            order_by,
+        }
+    }
+
+    pub fn limit(input: LogicalPlan, limit: Option<u64>, offset: u64) -> Self {
+        LogicalPlan::Limit {
+            input: Box::new(input),
+            limit,
+            offset,
+        }
+    }
+
+    pub fn distinct(input: LogicalPlan) -> Self {
+        LogicalPlan::Distinct {
+            input: Box::new(input),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If there are no inputs or they differ in the number of columns.
+    pub fn union(inputs: Vec<LogicalPlan>, all: bool) -> Self {
+        let schema = inputs[0].schema().to_vec();
+        assert!(
+            inputs
+                .iter()
+                .all(|input| input.schema().len() == schema.len()),
+            "inputs of a union have the same number of columns"
+        );
+        LogicalPlan::Union {
+            inputs,
+            all,
+            schema,
+        }
+    }
+
+    /// Columns are named `column1`, `column2`, ... as in PostgreSQL.
+    pub fn values(rows: Vec<Vec<BoundExpr>>) -> Self {
+        let schema = rows
+            .first()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(position, expr)| {
+                PlanColumn::new(expr.clone(), format!("column{}", position + 1))
+            })
+            .collect();
+        LogicalPlan::Values { rows, schema }
+    }
+
+    pub fn insert(
+        input: LogicalPlan,
+        table: BoundRelation,
+        columns: Vec<Column>,
+        on_conflict: Option<BoundOnConflict>,
+        returning: Vec<ProjectionItem>,
+    ) -> Self {
+        LogicalPlan::Insert {
+            input: Box::new(input),
+            table,
+            columns,
+            on_conflict,
+            schema: projection_schema(&returning),
+            returning,
+        }
+    }
+
+    pub fn update(
+        input: LogicalPlan,
+        table: BoundRelation,
+        assignments: Vec<BoundAssignment>,
+        returning: Vec<ProjectionItem>,
+    ) -> Self {
+        LogicalPlan::Update {
+            input: Box::new(input),
+            table,
+            assignments,
+            schema: projection_schema(&returning),
+            returning,
+        }
+    }
+
+    pub fn delete(
+        input: LogicalPlan,
+        table: BoundRelation,
+        returning: Vec<ProjectionItem>,
+    ) -> Self {
+        LogicalPlan::Delete {
+            input: Box::new(input),
+            table,
+            schema: projection_schema(&returning),
+            returning,
+        }
+    }
+
+    pub fn window(input: LogicalPlan, functions: Vec<BoundExpr>) -> Self {
+        let schema = input
+            .schema()
+            .iter()
+            .cloned()
+            .chain(
+                functions
+                    .iter()
+                    .map(|function| PlanColumn::new(function.clone(), output_name(function))),
+            )
+            .collect();
+        LogicalPlan::Window {
+            input: Box::new(input),
+            functions,
+            schema,
+        }
+    }
+
+    /// Columns of unknown type are text, as in the result of a query.
+    pub fn subquery_alias(input: LogicalPlan, alias: String, relation: RelationId) -> Self {
+        let schema = input
+            .schema()
+            .iter()
+            .enumerate()
+            .map(|(position, column)| {
+                let column = BoundColumn {
+                    relation,
+                    table_id: DERIVED_TABLE_ID,
+                    column_id: ColumnId(position as u32 + 1),
+                    name: column.name.clone(),
+                    data_type: column.data_type.unwrap_or(DataType::Text),
+                    span: Span::default(),
+                };
+                PlanColumn::new(BoundExpr::Column(column.clone()), column.name)
+            })
+            .collect();
+        LogicalPlan::SubqueryAlias {
+            input: Box::new(input),
+            alias,
+            relation,
+            schema,
+        }
+    }
+
+    /// Columns of the rows the node returns, DML without `returning` returns none.
+    pub fn schema(&self) -> &[PlanColumn] {
+        match self {
+            LogicalPlan::Scan { schema, .. }
+            | LogicalPlan::TableFunction { schema, .. }
+            | LogicalPlan::Project { schema, .. }
+            | LogicalPlan::Join { schema, .. }
+            | LogicalPlan::Aggregate { schema, .. }
+            | LogicalPlan::Union { schema, .. }
+            | LogicalPlan::Values { schema, .. }
+            | LogicalPlan::Insert { schema, .. }
+            | LogicalPlan::Update { schema, .. }
+            | LogicalPlan::Delete { schema, .. }
+            | LogicalPlan::Window { schema, .. }
+            | LogicalPlan::SubqueryAlias { schema, .. } => schema,
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Distinct { input } => input.schema(),
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::Scan { .. }
+            | LogicalPlan::TableFunction { .. }
+            | LogicalPlan::Values { .. } => vec![],
+            LogicalPlan::Join { left, right, .. } => vec![left, right],
+            LogicalPlan::Union { inputs, .. } => inputs.iter().collect(),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Project { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Insert { input, .. }
+            | LogicalPlan::Update { input, .. }
+            | LogicalPlan::Delete { input, .. }
+            | LogicalPlan::Window { input, .. }
+            | LogicalPlan::SubqueryAlias { input, .. } => vec![input],
+        }
+    }
+
+    /// The node with new inputs, in the order of `inputs()`. Schemas which depend on the
+    /// inputs are computed again.
+    ///
+    /// # Panics
+    ///
+    /// If the number of inputs doesn't match.
+    pub fn with_inputs(&self, inputs: Vec<LogicalPlan>) -> LogicalPlan {
+        assert_eq!(inputs.len(), self.inputs().len(), "number of inputs");
+        if let LogicalPlan::Union { all, .. } = self {
+            return LogicalPlan::union(inputs, *all);
+        }
+        let mut inputs = inputs.into_iter();
+        let mut input = || inputs.next().expect("number of inputs is checked");
+        match self {
+            LogicalPlan::Scan { .. }
+            | LogicalPlan::TableFunction { .. }
+            | LogicalPlan::Values { .. }
+            | LogicalPlan::Union { .. } => self.clone(),
+            LogicalPlan::Filter { predicate, .. } => {
+                LogicalPlan::filter(input(), predicate.clone())
+            }
+            LogicalPlan::Project { items, .. } => LogicalPlan::project(input(), items.clone()),
+            LogicalPlan::Join {
+                join_type,
+                on,
+                algorithm,
+                ..
+            } => {
+                let left = input();
+                LogicalPlan::join(left, input(), *join_type, on.clone()).with_algorithm(*algorithm)
+            }
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => LogicalPlan::aggregate(input(), group_by.clone(), aggregates.clone()),
+            LogicalPlan::Sort { order_by, .. } => LogicalPlan::sort(input(), order_by.clone()),
+            LogicalPlan::Limit { limit, offset, .. } => {
+                LogicalPlan::limit(input(), *limit, *offset)
+            }
+            LogicalPlan::Distinct { .. } => LogicalPlan::distinct(input()),
+            LogicalPlan::Insert {
+                table,
+                columns,
+                on_conflict,
+                returning,
+                ..
+            } => LogicalPlan::insert(
+                input(),
+                table.clone(),
+                columns.clone(),
+                on_conflict.clone(),
+                returning.clone(),
+            ),
+            LogicalPlan::Update {
+                table,
+                assignments,
+                returning,
+                ..
+            } => LogicalPlan::update(
+                input(),
+                table.clone(),
+                assignments.clone(),
+                returning.clone(),
+            ),
+            LogicalPlan::Delete {
+                table, returning, ..
+            } => LogicalPlan::delete(input(), table.clone(), returning.clone()),
+            LogicalPlan::Window { functions, .. } => {
+                LogicalPlan::window(input(), functions.clone())
+            }
+            LogicalPlan::SubqueryAlias {
+                alias, relation, ..
+            } => LogicalPlan::subquery_alias(input(), alias.clone(), *relation),
+        }
+    }
+
+    /// Expressions the node evaluates, subqueries are not entered.
+    pub fn expressions(&self) -> Vec<&BoundExpr> {
+        match self {
+            LogicalPlan::Scan { .. }
+            | LogicalPlan::Limit { .. }
+            | LogicalPlan::Distinct { .. }
+            | LogicalPlan::Union { .. }
+            | LogicalPlan::SubqueryAlias { .. } => vec![],
+            LogicalPlan::TableFunction { relation, .. } => relation
+                .function
+                .iter()
+                .flat_map(|function| &function.arguments)
+                .collect(),
+            LogicalPlan::Filter { predicate, .. } => vec![predicate],
+            LogicalPlan::Project { items, .. } => items.iter().map(|item| &item.expr).collect(),
+            LogicalPlan::Join { on, .. } => on.iter().collect(),
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by.iter().chain(aggregates).collect(),
+            LogicalPlan::Sort { order_by, .. } => order_by.iter().map(|(expr, _)| expr).collect(),
+            LogicalPlan::Values { rows, .. } => rows.iter().flatten().collect(),
+            LogicalPlan::Insert {
+                on_conflict,
+                returning,
+                ..
+            } => {
+                let mut exprs = vec![];
+                if let Some(BoundOnConflict {
+                    action:
+                        BoundConflictAction::DoUpdate {
+                            assignments,
+                            filter,
+                            ..
+                        },
+                    ..
+                }) = on_conflict
+                {
+                    exprs.extend(assignments.iter().map(|assignment| &assignment.value));
+                    exprs.extend(filter);
+                }
+                exprs.extend(returning.iter().map(|item| &item.expr));
+                exprs
+            }
+            LogicalPlan::Update {
+                assignments,
+                returning,
+                ..
+            } => assignments
+                .iter()
+                .map(|assignment| &assignment.value)
+                .chain(returning.iter().map(|item| &item.expr))
+                .collect(),
+            LogicalPlan::Delete { returning, .. } => {
+                returning.iter().map(|item| &item.expr).collect()
+            }
+            LogicalPlan::Window { functions, .. } => functions.iter().collect(),
+        }
+    }
+}
+
+fn projection_schema(items: &[ProjectionItem]) -> Vec<PlanColumn> {
+    items
+        .iter()
+        .map(|item| PlanColumn::new(item.expr.clone(), item.name.clone()))
+        .collect()
+}
+
+/// Name of a computed column, as the binder names select list items without an alias.
+pub(crate) fn output_name(expr: &BoundExpr) -> String {
+    match expr {
+        BoundExpr::Column(column) => column.name.clone(),
+        BoundExpr::Function(function) => function.name.clone(),
+        _ => "?column?".to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PlanError {
+    /// Transaction control and other statements which are not executed as a plan.
+    NotPlannable,
+    /// `select distinct` sorted by an expression which isn't in the select list.
+    DistinctOrderBy,
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::NotPlannable => f.write_str("statement has no query plan"),
+            PlanError::DistinctOrderBy => {
+                f.write_str("for SELECT DISTINCT, ORDER BY expressions must appear in select list")
+            }
+        }
+    }
+}
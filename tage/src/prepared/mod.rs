@@ -0,0 +1,534 @@
+//! Prepared statements are parsed and analysed once and then executed many times with
+//! different parameter values. Values are bound to the parsed tree, they are never spliced
+//! into SQL text. Types of the parameters are inferred against the catalog when the
+//! statement is prepared.
+
+use crate::analyser::parameters::infer_parameter_types;
+use crate::analyser::AnalyseError;
+use crate::catalog::Catalog;
+use crate::execution::expr::cast;
+use crate::execution::ExecutionError;
+use crate::parser::expr::{Expr, Parameter};
+use crate::parser::visitor::Visitor;
+use crate::parser::*;
+use crate::value::{DataType, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrepareError {
+    Parse(ParseError),
+    Analyse(AnalyseError),
+    /// Only a single `select`, `insert`, `update` or `delete` can be prepared.
+    UnsupportedStatement,
+    /// `?`, `$1` and `:name` can't be used together in one statement.
+    MixedParameterStyles,
+    AlreadyExists(String),
+    NotFound(String),
+}
+
+impl From<ParseError> for PrepareError {
+    fn from(error: ParseError) -> Self {
+        PrepareError::Parse(error)
+    }
+}
+
+impl From<AnalyseError> for PrepareError {
+    fn from(error: AnalyseError) -> Self {
+        PrepareError::Analyse(error)
+    }
+}
+
+impl fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrepareError::Parse(error) => write!(f, "{}", error),
+            PrepareError::Analyse(error) => write!(f, "{}", error),
+            PrepareError::UnsupportedStatement => {
+                f.write_str("only a single select, insert, update or delete can be prepared")
+            }
+            PrepareError::MixedParameterStyles => {
+                f.write_str("parameters ?, $n and :name can't be mixed in one statement")
+            }
+            PrepareError::AlreadyExists(name) => {
+                write!(f, "prepared statement {} already exists", name)
+            }
+            PrepareError::NotFound(name) => write!(f, "prepared statement {} does not exist", name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BindError {
+    WrongNumberOfValues {
+        expected: usize,
+        found: usize,
+    },
+    /// `position` counts from 1 like `$1`.
+    TypeMismatch {
+        position: usize,
+        expected: DataType,
+        found: DataType,
+    },
+    /// Arguments of `execute` have to be literals.
+    NotALiteral {
+        position: usize,
+    },
+    NotFound(String),
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindError::WrongNumberOfValues { expected, found } => {
+                write!(f, "expected {} parameter values, found {}", expected, found)
+            }
+            BindError::TypeMismatch {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "parameter {} expects {}, found {}",
+                position, expected, found
+            ),
+            BindError::NotALiteral { position } => {
+                write!(f, "argument {} of execute is not a literal", position)
+            }
+            BindError::NotFound(name) => write!(f, "prepared statement {} does not exist", name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PreparedStatement {
+    query: Query,
+    /// Named parameters in the order of their first appearance, they are bound in this order.
+    names: Vec<String>,
+    anonymous: bool,
+    /// One entry per value to bind, `None` if any type fits.
+    parameter_types: Vec<Option<DataType>>,
+}
+
+impl PreparedStatement {
+    pub fn new(input: &str, catalog: &dyn Catalog) -> Result<PreparedStatement, PrepareError> {
+        let mut queries = Queries::parse(input)?.queries;
+        if queries.len() != 1 {
+            return Err(PrepareError::UnsupportedStatement);
+        }
+        PreparedStatement::from_query(queries.remove(0), &[], catalog)
+    }
+
+    /// `declared_types` are types of the first parameters as written in `prepare`.
+    pub fn from_query(
+        query: Query,
+        declared_types: &[DataType],
+        catalog: &dyn Catalog,
+    ) -> Result<PreparedStatement, PrepareError> {
+        if !matches!(
+            query,
+            Query::Select(_) | Query::Insert(_) | Query::Update(_) | Query::Delete(_)
+        ) {
+            return Err(PrepareError::UnsupportedStatement);
+        }
+        let mut parameters = Parameters::default();
+        parameters.visit_query(&query);
+        let styles = [
+            parameters.anonymous > 0,
+            parameters.positional > 0,
+            !parameters.names.is_empty(),
+        ];
+        if styles.iter().filter(|used| **used).count() > 1 {
+            return Err(PrepareError::MixedParameterStyles);
+        }
+
+        let mut statement = PreparedStatement {
+            query,
+            names: parameters.names,
+            anonymous: parameters.anonymous > 0,
+            parameter_types: vec![],
+        };
+        let count = parameters
+            .anonymous
+            .max(parameters.positional)
+            .max(statement.names.len())
+            .max(declared_types.len());
+        let known = declared_types
+            .iter()
+            .enumerate()
+            .map(|(i, data_type)| (statement.parameter(i), *data_type))
+            .collect();
+        let types = infer_parameter_types(&statement.query, known, catalog)?;
+        statement.parameter_types = (0..count)
+            .map(|i| types.get(&statement.parameter(i)).copied())
+            .collect();
+        Ok(statement)
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    pub fn parameter_types(&self) -> &[Option<DataType>] {
+        &self.parameter_types
+    }
+
+    /// Names of `:name` parameters in the order their values are bound.
+    pub fn parameter_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Checks the values against the parameter types, see [`accepts`].
+    pub fn bind(&self, values: Vec<Value>) -> Result<BoundStatement<'_>, BindError> {
+        if values.len() != self.parameter_types.len() {
+            return Err(BindError::WrongNumberOfValues {
+                expected: self.parameter_types.len(),
+                found: values.len(),
+            });
+        }
+        for (i, (value, expected)) in values.iter().zip(&self.parameter_types).enumerate() {
+            match (expected, value.data_type()) {
+                (Some(expected), Some(found)) if !accepts(*expected, found) => {
+                    return Err(BindError::TypeMismatch {
+                        position: i + 1,
+                        expected: *expected,
+                        found,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(BoundStatement {
+            statement: self,
+            values,
+        })
+    }
+
+    /// Parameter which gets the value with the index.
+    fn parameter(&self, index: usize) -> Parameter {
+        match self.names.get(index) {
+            Some(name) => Parameter::Named(name.clone()),
+            None if self.anonymous => Parameter::Anonymous(index + 1),
+            None => Parameter::Positional(index + 1),
+        }
+    }
+
+    fn index(&self, parameter: &Parameter) -> Option<usize> {
+        match parameter {
+            Parameter::Anonymous(position) | Parameter::Positional(position) => Some(position - 1),
+            Parameter::Named(name) => self.names.iter().position(|n| n == name),
+        }
+    }
+}
+
+/// A prepared statement with the values of its parameters.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundStatement<'a> {
+    statement: &'a PreparedStatement,
+    values: Vec<Value>,
+}
+
+impl BoundStatement<'_> {
+    pub fn query(&self) -> &Query {
+        &self.statement.query
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn value(&self, parameter: &Parameter) -> Option<&Value> {
+        self.values.get(self.statement.index(parameter)?)
+    }
+
+    /// Each parameter with its value, cast to the type of the parameter, as the statement
+    /// is executed with them.
+    pub fn parameters(&self) -> Result<Vec<(Parameter, Value)>, ExecutionError> {
+        self.values
+            .iter()
+            .zip(&self.statement.parameter_types)
+            .enumerate()
+            .map(|(index, (value, data_type))| {
+                let value = match data_type {
+                    Some(data_type) => cast(value.clone(), *data_type)?,
+                    None => value.clone(),
+                };
+                Ok((self.statement.parameter(index), value))
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Parameters {
+    anonymous: usize,
+    /// The greatest position of `$n`.
+    positional: usize,
+    names: Vec<String>,
+}
+
+impl Visitor for Parameters {
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        match parameter {
+            Parameter::Anonymous(position) => self.anonymous = self.anonymous.max(*position),
+            Parameter::Positional(position) => self.positional = self.positional.max(*position),
+            Parameter::Named(name) => {
+                if !self.names.contains(name) {
+                    self.names.push(name.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Statements created by SQL `prepare`, usually one set per session.
+#[derive(Debug, Default)]
+pub struct PreparedStatements {
+    statements: HashMap<String, PreparedStatement>,
+}
+
+impl PreparedStatements {
+    pub fn prepare(
+        &mut self,
+        query: &PrepareQuery,
+        catalog: &dyn Catalog,
+    ) -> Result<(), PrepareError> {
+        if self.statements.contains_key(&query.name) {
+            return Err(PrepareError::AlreadyExists(query.name.clone()));
+        }
+        let statement = PreparedStatement::from_query(
+            (*query.statement).clone(),
+            &query.parameter_types,
+            catalog,
+        )?;
+        self.statements.insert(query.name.clone(), statement);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PreparedStatement> {
+        self.statements.get(name)
+    }
+
+    pub fn execute(&self, query: &ExecuteQuery) -> Result<BoundStatement<'_>, BindError> {
+        let statement = self
+            .get(&query.name)
+            .ok_or_else(|| BindError::NotFound(query.name.clone()))?;
+        let values = query
+            .arguments
+            .iter()
+            .enumerate()
+            .map(|(i, argument)| {
+                literal_value(argument).ok_or(BindError::NotALiteral { position: i + 1 })
+            })
+            .collect::<Result<Vec<Value>, BindError>>()?;
+        statement.bind(values)
+    }
+
+    pub fn deallocate(&mut self, query: &DeallocateQuery) -> Result<(), PrepareError> {
+        match &query.name {
+            Some(name) => match self.statements.remove(name) {
+                Some(_) => Ok(()),
+                None => Err(PrepareError::NotFound(name.clone())),
+            },
+            None => {
+                self.statements.clear();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// `-1` is a literal too, the parser folds the sign into the number.
+fn literal_value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => Some(Value::from_literal(literal)),
+        _ => None,
+    }
+}
+
+/// Integers fit any numeric parameter and floats any but integer ones,
+/// strings also fit temporal parameters, e.g. `'2024-01-01'` for a date.
+fn accepts(expected: DataType, found: DataType) -> bool {
+    match found {
+        DataType::BigInt => expected.is_numeric(),
+        DataType::Double => expected.is_numeric() && !expected.is_integer(),
+        DataType::Text => expected.is_string() || expected.is_temporal(),
+        found => expected == found,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::MemoryCatalog;
+
+    fn catalog() -> MemoryCatalog {
+        let mut catalog = MemoryCatalog::new();
+        catalog.create_table(
+            "public",
+            "users",
+            &[
+                ("id", DataType::BigInt),
+                ("name", DataType::Text),
+                ("age", DataType::Integer),
+            ],
+        );
+        catalog.create_table(
+            "public",
+            "t",
+            &[
+                ("a", DataType::Integer),
+                ("b", DataType::Integer),
+                ("c", DataType::Text),
+            ],
+        );
+        catalog
+    }
+
+    fn parse_query(input: &str) -> Query {
+        Queries::parse(input).unwrap().queries.remove(0)
+    }
+
+    #[test]
+    fn binds_values_many_times() {
+        let statement = PreparedStatement::new(
+            "select * from users where age between $1 and 65 and name like $2",
+            &catalog(),
+        )
+        .unwrap();
+        assert_eq!(
+            statement.parameter_types(),
+            &[Some(DataType::Integer), Some(DataType::Text)]
+        );
+        for (age, name) in [(1, "a%"), (2, "b%")] {
+            let bound = statement.bind(vec![age.into(), name.into()]).unwrap();
+            assert_eq!(bound.query(), statement.query());
+            assert_eq!(
+                bound.value(&Parameter::Positional(1)),
+                Some(&Value::Integer(age))
+            );
+            assert_eq!(
+                bound.value(&Parameter::Positional(2)),
+                Some(&Value::from(name))
+            );
+        }
+    }
+
+    #[test]
+    fn binds_anonymous_and_named_parameters_in_order() {
+        let statement =
+            PreparedStatement::new("update t set a = ? where b = ? or c = ?", &catalog()).unwrap();
+        let bound = statement
+            .bind(vec![Value::Null, 1.into(), "x".into()])
+            .unwrap();
+        assert_eq!(
+            bound.value(&Parameter::Anonymous(3)),
+            Some(&Value::from("x"))
+        );
+
+        let statement = PreparedStatement::new(
+            "delete from t where a = :id or c = :name or b = :id",
+            &catalog(),
+        )
+        .unwrap();
+        assert_eq!(statement.parameter_names(), &["id", "name"]);
+        let bound = statement.bind(vec![7.into(), "x".into()]).unwrap();
+        assert_eq!(
+            bound.value(&Parameter::Named("name".to_string())),
+            Some(&Value::from("x"))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_values() {
+        let statement =
+            PreparedStatement::new("select * from t where a = $1 + 0.5", &catalog()).unwrap();
+        assert!(statement.bind(vec![1.into()]).is_ok());
+        assert_eq!(
+            statement.bind(vec![]),
+            Err(BindError::WrongNumberOfValues {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert_eq!(
+            statement.bind(vec!["1".into()]),
+            Err(BindError::TypeMismatch {
+                position: 1,
+                expected: DataType::Decimal(None),
+                found: DataType::Text
+            })
+        );
+        let statement =
+            PreparedStatement::new("select * from t where a = $1 + 1", &catalog()).unwrap();
+        assert!(statement.bind(vec![1.5.into()]).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_statements() {
+        assert_eq!(
+            PreparedStatement::new("select 1; select 2", &catalog()),
+            Err(PrepareError::UnsupportedStatement)
+        );
+        assert_eq!(
+            PreparedStatement::new("commit", &catalog()),
+            Err(PrepareError::UnsupportedStatement)
+        );
+        assert_eq!(
+            PreparedStatement::new("select * from t where a = ? and b = $2", &catalog()),
+            Err(PrepareError::MixedParameterStyles)
+        );
+    }
+
+    #[test]
+    fn prepares_executes_and_deallocates() {
+        let mut statements = PreparedStatements::default();
+        let Query::Prepare(prepare) = parse_query(
+            "prepare find (bigint, text) as select * from users where id = $1 and name = $2",
+        ) else {
+            panic!("not a prepare");
+        };
+        statements.prepare(&prepare, &catalog()).unwrap();
+        assert_eq!(
+            statements.prepare(&prepare, &catalog()),
+            Err(PrepareError::AlreadyExists("find".to_string()))
+        );
+
+        let Query::Execute(execute) = parse_query("execute find (-1, 'bob')") else {
+            panic!("not an execute");
+        };
+        let bound = statements.execute(&execute).unwrap();
+        assert_eq!(bound.values(), &[Value::Integer(-1), Value::from("bob")]);
+
+        let Query::Execute(execute) = parse_query("execute find (1, lower('bob'))") else {
+            panic!("not an execute");
+        };
+        assert_eq!(
+            statements.execute(&execute),
+            Err(BindError::NotALiteral { position: 2 })
+        );
+
+        let Query::Deallocate(deallocate) = parse_query("deallocate prepare find") else {
+            panic!("not a deallocate");
+        };
+        statements.deallocate(&deallocate).unwrap();
+        assert_eq!(
+            statements.deallocate(&deallocate),
+            Err(PrepareError::NotFound("find".to_string()))
+        );
+    }
+
+    #[test]
+    fn declared_types_are_checked_against_usage() {
+        let mut statements = PreparedStatements::default();
+        let Query::Prepare(prepare) =
+            parse_query("prepare p (boolean) as select * from t where $1 = 1")
+        else {
+            panic!("not a prepare");
+        };
+        assert!(matches!(
+            statements.prepare(&prepare, &catalog()),
+            Err(PrepareError::Analyse(_))
+        ));
+    }
+}
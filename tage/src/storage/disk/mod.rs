@@ -0,0 +1,493 @@
+//! Store which keeps its entries in a single append-only log file.
+//!
+//! Every put and delete appends a record to the log. Opening the file replays it into
+//! an index of the live keys and the positions of their records, so a read is one seek.
+//! A record is a CRC-32 of the rest of it, a CRC-32 of its header, a kind byte, the
+//! lengths of the key and the value as little-endian `u32`s, the key and the value. The
+//! header is checked before its lengths are used. A record cut short by a crash at the
+//! end of the log is dropped when the file is opened, a bad record anywhere else is an
+//! error. Once most of the log is overwritten and deleted entries it is rewritten with
+//! only the live ones.
+
+use crate::storage::{KvStore, Scan, StorageError};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// First bytes of a store file.
+const MAGIC: &[u8; 8] = b"tage-kv1";
+
+const PUT: u8 = 1;
+const DELETE: u8 = 2;
+
+/// The two checksums, the kind and the two lengths.
+const RECORD_HEADER: u64 = 17;
+
+/// The log isn't compacted before it has this many bytes of dead records.
+const COMPACTION_GARBAGE: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub struct DiskStore {
+    path: PathBuf,
+    file: Mutex<File>,
+    index: Index,
+    /// Bytes of the log, the next record is written here
+    length: u64,
+    /// Bytes of the records of overwritten and deleted entries
+    garbage: u64,
+}
+
+/// Where the record of each live key is.
+type Index = BTreeMap<Vec<u8>, Location>;
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: u64,
+    key_length: u32,
+    value_length: u32,
+}
+
+impl Location {
+    fn record_length(&self) -> u64 {
+        RECORD_HEADER + self.key_length as u64 + self.value_length as u64
+    }
+}
+
+impl DiskStore {
+    /// Opens the store of the file, a missing or empty file is a new store.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(MAGIC)?;
+            file.sync_data()?;
+        }
+        let (index, length, garbage) = replay(&mut file)?;
+        Ok(DiskStore {
+            path,
+            file: Mutex::new(file),
+            index,
+            length,
+            garbage,
+        })
+    }
+
+    /// Writes what the store has written to the disk itself, not only to the OS.
+    pub fn sync(&mut self) -> Result<(), StorageError> {
+        Ok(self.file.get_mut().expect("store file").sync_data()?)
+    }
+
+    /// Rewrites the log with only the live entries, in key order.
+    pub fn compact(&mut self) -> Result<(), StorageError> {
+        let mut temp_path = OsString::from(&self.path);
+        temp_path.push(".compact");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writer.write_all(MAGIC)?;
+        let mut index = Index::new();
+        let mut length = MAGIC.len() as u64;
+        for (key, location) in &self.index {
+            let value = self.read_value(key, *location)?;
+            let record = record(PUT, key, &value);
+            writer.write_all(&record)?;
+            index.insert(
+                key.clone(),
+                Location {
+                    offset: length,
+                    ..*location
+                },
+            );
+            length += record.len() as u64;
+        }
+        writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        let file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        *self = DiskStore {
+            path: self.path.clone(),
+            file: Mutex::new(file),
+            index,
+            length,
+            garbage: 0,
+        };
+        Ok(())
+    }
+
+    fn append(&mut self, record: &[u8]) -> Result<u64, StorageError> {
+        let file = self.file.get_mut().expect("store file");
+        file.seek(SeekFrom::Start(self.length))?;
+        file.write_all(record)?;
+        let offset = self.length;
+        self.length += record.len() as u64;
+        Ok(offset)
+    }
+
+    fn compact_if_needed(&mut self) -> Result<(), StorageError> {
+        match self.garbage >= COMPACTION_GARBAGE && self.garbage * 2 > self.length {
+            true => self.compact(),
+            false => Ok(()),
+        }
+    }
+
+    /// Value of the record, which is read again and checked.
+    fn read_value(&self, key: &[u8], location: Location) -> Result<Vec<u8>, StorageError> {
+        let mut record = vec![0; location.record_length() as usize];
+        {
+            let mut file = self.file.lock().expect("store file");
+            file.seek(SeekFrom::Start(location.offset))?;
+            file.read_exact(&mut record)?;
+        }
+        let corrupted = |reason| StorageError::Corrupted {
+            offset: location.offset,
+            reason,
+        };
+        if checksum(&record[4..]) != u32::from_le_bytes(record[..4].try_into().expect("4 bytes")) {
+            return Err(corrupted("checksum mismatch"));
+        }
+        let value_start = (RECORD_HEADER as usize) + location.key_length as usize;
+        if &record[RECORD_HEADER as usize..value_start] != key {
+            return Err(corrupted("record of another key"));
+        }
+        record.drain(..value_start);
+        Ok(record)
+    }
+}
+
+impl KvStore for DiskStore {
+    fn get_by_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        self.index
+            .get(key)
+            .map(|location| self.read_value(key, *location))
+            .transpose()
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        let (key_length, value_length) =
+            match (u32::try_from(key.len()), u32::try_from(value.len())) {
+                (Ok(key_length), Ok(value_length)) => (key_length, value_length),
+                _ => {
+                    return Err(StorageError::Io {
+                        kind: ErrorKind::InvalidInput,
+                        message: "entry is too large".to_string(),
+                    })
+                }
+            };
+        let offset = self.append(&record(PUT, key, value))?;
+        let location = Location {
+            offset,
+            key_length,
+            value_length,
+        };
+        if let Some(old) = self.index.insert(key.to_vec(), location) {
+            self.garbage += old.record_length();
+        }
+        self.compact_if_needed()
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        if !self.index.contains_key(key) {
+            return Ok(());
+        }
+        let record = record(DELETE, key, &[]);
+        self.append(&record)?;
+        let old = self.index.remove(key).expect("live key");
+        self.garbage += old.record_length() + record.len() as u64;
+        self.compact_if_needed()
+    }
+
+    fn scan(&self, start_key: &[u8], end_key: &[u8]) -> Scan<'_> {
+        if start_key >= end_key {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(
+            self.index
+                .range(start_key.to_vec()..end_key.to_vec())
+                .map(|(key, location)| Ok((key.clone(), self.read_value(key, *location)?))),
+        )
+    }
+}
+
+fn record(kind: u8, key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut record = vec![0; 8];
+    record.push(kind);
+    record.extend((key.len() as u32).to_le_bytes());
+    record.extend((value.len() as u32).to_le_bytes());
+    let header_checksum = checksum(&record[8..]);
+    record[4..8].copy_from_slice(&header_checksum.to_le_bytes());
+    record.extend(key);
+    record.extend(value);
+    let checksum = checksum(&record[4..]);
+    record[..4].copy_from_slice(&checksum.to_le_bytes());
+    record
+}
+
+/// Index, length and garbage of the log. A torn record at its end is cut off the file.
+fn replay(file: &mut File) -> Result<(Index, u64, u64), StorageError> {
+    let file_length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&mut *file);
+    let mut magic = [0; MAGIC.len()];
+    if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+        return Err(StorageError::Corrupted {
+            offset: 0,
+            reason: "not a store file",
+        });
+    }
+    let mut index = Index::new();
+    let mut garbage = 0;
+    let mut offset = MAGIC.len() as u64;
+    while offset < file_length {
+        let corrupted = |reason| StorageError::Corrupted { offset, reason };
+        if offset + RECORD_HEADER > file_length {
+            break;
+        }
+        let mut header = [0; RECORD_HEADER as usize];
+        reader.read_exact(&mut header)?;
+        if checksum(&header[8..]) != u32::from_le_bytes(header[4..8].try_into().expect("4 bytes")) {
+            // a write cut short leaves a prefix of its record, or zeros where the file
+            // grew before the bytes got there
+            let mut rest = header.to_vec();
+            reader.read_to_end(&mut rest)?;
+            match rest.iter().all(|byte| *byte == 0) {
+                true => break,
+                false => return Err(corrupted("checksum mismatch of the header")),
+            }
+        }
+        let key_length = u32::from_le_bytes(header[9..13].try_into().expect("4 bytes"));
+        let value_length = u32::from_le_bytes(header[13..17].try_into().expect("4 bytes"));
+        let location = Location {
+            offset,
+            key_length,
+            value_length,
+        };
+        // the header is whole, a record longer than the file is the torn last one
+        let end = offset + location.record_length();
+        if end > file_length {
+            break;
+        }
+        let mut body = header[4..].to_vec();
+        body.resize(body.len() + key_length as usize + value_length as usize, 0);
+        reader.read_exact(&mut body[RECORD_HEADER as usize - 4..])?;
+        if checksum(&body) != u32::from_le_bytes(header[..4].try_into().expect("4 bytes")) {
+            match end == file_length {
+                true => break,
+                false => return Err(corrupted("checksum mismatch")),
+            }
+        }
+        let key = body[RECORD_HEADER as usize - 4..][..key_length as usize].to_vec();
+        match header[8] {
+            PUT => {
+                if let Some(old) = index.insert(key, location) {
+                    garbage += old.record_length();
+                }
+            }
+            DELETE => {
+                let old = index
+                    .remove(&key)
+                    .ok_or_else(|| corrupted("delete of a missing key"))?;
+                garbage += old.record_length() + location.record_length();
+            }
+            _ => return Err(corrupted("invalid record kind")),
+        }
+        offset = end;
+    }
+    drop(reader);
+    if offset < file_length {
+        file.set_len(offset)?;
+        file.sync_data()?;
+    }
+    Ok((index, offset, garbage))
+}
+
+/// CRC-32 of the bytes, as zlib computes it.
+fn checksum(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ crc >> 8
+    })
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => 0xEDB8_8320 ^ crc >> 1,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::spill::tests::temp_directory;
+    use crate::storage::tests::check_store;
+
+    fn store_path(name: &str) -> PathBuf {
+        let directory = temp_directory(&format!("store-{}", name));
+        fs::create_dir_all(&directory).unwrap();
+        directory.join("data")
+    }
+
+    fn entries(store: &DiskStore) -> Vec<(Vec<u8>, Vec<u8>)> {
+        store
+            .scan(b"", b"\xFF\xFF")
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn disk_store() {
+        let path = store_path("suite");
+        check_store(&mut DiskStore::open(&path).unwrap());
+        // and once more against the replayed log
+        let mut store = DiskStore::open(&path).unwrap();
+        for (key, _) in entries(&store) {
+            store.delete(&key).unwrap();
+        }
+        drop(store);
+        check_store(&mut DiskStore::open(&path).unwrap());
+    }
+
+    #[test]
+    fn keeps_entries_when_reopened() {
+        let path = store_path("reopen");
+        let mut store = DiskStore::open(&path).unwrap();
+        store.put(b"a", b"1").unwrap();
+        store.put(b"b", b"2").unwrap();
+        store.put(b"a", b"3").unwrap();
+        store.delete(b"b").unwrap();
+        store.put(b"c", b"").unwrap();
+        store.sync().unwrap();
+        drop(store);
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(
+            entries(&store),
+            [(b"a".to_vec(), b"3".to_vec()), (b"c".to_vec(), vec![])]
+        );
+        assert_eq!(store.garbage, 2 * (RECORD_HEADER + 2) + RECORD_HEADER + 1);
+    }
+
+    #[test]
+    fn drops_torn_records() {
+        let path = store_path("torn");
+        let mut store = DiskStore::open(&path).unwrap();
+        store.put(b"a", b"1").unwrap();
+        store.put(b"b", b"2").unwrap();
+        drop(store);
+        let length = fs::metadata(&path).unwrap().len();
+        // a crash in the middle of the write of the last record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&record(PUT, b"c", b"3")[..10]).unwrap();
+        drop(file);
+        let mut store = DiskStore::open(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+        assert_eq!(entries(&store).len(), 2);
+        store.put(b"c", b"3").unwrap();
+        drop(store);
+        // a crash before the last record was written whole
+        let mut bytes = fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(store.get_by_key(b"c"), Ok(None));
+        assert_eq!(store.get_by_key(b"b"), Ok(Some(b"2".to_vec())));
+        drop(store);
+        // the file grew but the record never got there
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0; 40]).unwrap();
+        drop(file);
+        assert_eq!(entries(&DiskStore::open(&path).unwrap()).len(), 2);
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+    }
+
+    #[test]
+    fn rejects_corrupted_files() {
+        let path = store_path("corrupted");
+        let mut store = DiskStore::open(&path).unwrap();
+        store.put(b"a", b"1").unwrap();
+        store.put(b"b", b"2").unwrap();
+        drop(store);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[MAGIC.len() + RECORD_HEADER as usize] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            DiskStore::open(&path).unwrap_err(),
+            StorageError::Corrupted {
+                offset: MAGIC.len() as u64,
+                reason: "checksum mismatch"
+            }
+        );
+        // a bad record in the middle of the log isn't cut off with the ones after it
+        let mut store = DiskStore::open(store_path("corrupted-middle")).unwrap();
+        for index in 0..5 {
+            store.put(format!("k{}", index).as_bytes(), b"v").unwrap();
+        }
+        let path = store.path.clone();
+        drop(store);
+        let bytes = fs::read(&path).unwrap();
+        let second = MAGIC.len() + RECORD_HEADER as usize + 3;
+        for (position, reason) in [
+            (13, "checksum mismatch of the header"),
+            (8, "checksum mismatch of the header"),
+            (RECORD_HEADER as usize + 2, "checksum mismatch"),
+        ] {
+            let mut corrupted = bytes.clone();
+            corrupted[second + position] ^= 1;
+            fs::write(&path, &corrupted).unwrap();
+            assert_eq!(
+                DiskStore::open(&path).unwrap_err(),
+                StorageError::Corrupted {
+                    offset: second as u64,
+                    reason
+                }
+            );
+            assert_eq!(fs::read(&path).unwrap(), corrupted);
+        }
+        fs::write(&path, b"not a store").unwrap();
+        assert!(matches!(
+            DiskStore::open(&path),
+            Err(StorageError::Corrupted { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn compacts_the_log() {
+        let path = store_path("compaction");
+        let mut store = DiskStore::open(&path).unwrap();
+        let value = vec![7; 4096];
+        for index in 0..1000u32 {
+            store.put(&(index % 10).to_be_bytes(), &value).unwrap();
+        }
+        // the log never grew much beyond the garbage it is compacted at
+        assert!(fs::metadata(&path).unwrap().len() < 2 * COMPACTION_GARBAGE);
+        store.delete(&0u32.to_be_bytes()).unwrap();
+        store.compact().unwrap();
+        let length = MAGIC.len() as u64 + 9 * (RECORD_HEADER + 4 + 4096);
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+        assert_eq!(store.garbage, 0);
+        drop(store);
+        let store = DiskStore::open(&path).unwrap();
+        assert_eq!(entries(&store).len(), 9);
+        assert_eq!(store.get_by_key(&9u32.to_be_bytes()), Ok(Some(value)));
+    }
+}
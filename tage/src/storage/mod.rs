@@ -0,0 +1,261 @@
+//! Key-value storage the rest of the database keeps its data in.
+//!
+//! Keys and values are bytes, keys are ordered bytewise so related entries can be
+//! grouped under a common prefix and read back with a range scan.
+
+pub mod disk;
+
+pub use disk::DiskStore;
+
+use std::collections::BTreeMap;
+use std::{fmt, io};
+
+/// A key and its value.
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+/// Entries of a range scan, each is read from the store when the iterator gets to it.
+pub type Scan<'a> = Box<dyn Iterator<Item = Result<Entry, StorageError>> + 'a>;
+
+pub trait KvStore: Sync {
+    fn get_by_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>;
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError>;
+
+    /// Removes the entry of the key, a key without one is no error.
+    fn delete(&mut self, key: &[u8]) -> Result<(), StorageError>;
+
+    /// Entries with `start_key <= key < end_key` in key order.
+    fn scan(&self, start_key: &[u8], end_key: &[u8]) -> Scan<'_>;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StorageError {
+    /// Reading or writing the files of the store failed.
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+    /// The files of the store have bytes it didn't write, at the offset.
+    Corrupted { offset: u64, reason: &'static str },
+}
+
+impl From<io::Error> for StorageError {
+    fn from(error: io::Error) -> Self {
+        StorageError::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io { message, .. } => write!(f, "storage I/O failed: {}", message),
+            StorageError::Corrupted { offset, reason } => {
+                write!(f, "storage is corrupted at byte {}: {}", offset, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// Store which lives only as long as the process.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn get_by_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        self.entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn scan(&self, start_key: &[u8], end_key: &[u8]) -> Scan<'_> {
+        if start_key >= end_key {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(
+            self.entries
+                .range(start_key.to_vec()..end_key.to_vec())
+                .map(|(key, value)| Ok((key.clone(), value.clone()))),
+        )
+    }
+}
+
+/// Writes through to a store and keeps the values the writes replaced, so a statement
+/// which fails halfway can take its writes back.
+pub struct UndoStore<'s> {
+    store: &'s mut dyn KvStore,
+    /// Value of every written key before its first write, `None` if it had none
+    replaced: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'s> UndoStore<'s> {
+    pub fn new(store: &'s mut dyn KvStore) -> Self {
+        UndoStore {
+            store,
+            replaced: BTreeMap::new(),
+        }
+    }
+
+    /// Puts the replaced values back and removes the keys the writes added.
+    pub fn rollback(self) -> Result<(), StorageError> {
+        for (key, value) in self.replaced {
+            match value {
+                Some(value) => self.store.put(&key, &value)?,
+                None => self.store.delete(&key)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn remember(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        if !self.replaced.contains_key(key) {
+            let value = self.store.get_by_key(key)?;
+            self.replaced.insert(key.to_vec(), value);
+        }
+        Ok(())
+    }
+}
+
+impl KvStore for UndoStore<'_> {
+    fn get_by_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        self.store.get_by_key(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        self.remember(key)?;
+        self.store.put(key, value)
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.remember(key)?;
+        self.store.delete(key)
+    }
+
+    fn scan(&self, start_key: &[u8], end_key: &[u8]) -> Scan<'_> {
+        self.store.scan(start_key, end_key)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn scanned(store: &dyn KvStore, start_key: &[u8], end_key: &[u8]) -> Vec<Entry> {
+        store
+            .scan(start_key, end_key)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    /// What every store does, each implementation runs it against an empty store.
+    pub(crate) fn check_store(store: &mut dyn KvStore) {
+        assert_eq!(store.get_by_key(b"a"), Ok(None));
+        assert_eq!(scanned(store, b"", b"\xFF"), vec![]);
+
+        store.put(b"b", b"2").unwrap();
+        store.put(b"a", b"1").unwrap();
+        store.put(b"c", b"3").unwrap();
+        assert_eq!(store.get_by_key(b"a"), Ok(Some(b"1".to_vec())));
+        store.put(b"a", b"one").unwrap();
+        assert_eq!(store.get_by_key(b"a"), Ok(Some(b"one".to_vec())));
+        store.delete(b"b").unwrap();
+        store.delete(b"missing").unwrap();
+        assert_eq!(store.get_by_key(b"b"), Ok(None));
+        assert_eq!(
+            scanned(store, b"a", b"c"),
+            vec![(b"a".to_vec(), b"one".to_vec())]
+        );
+        assert_eq!(scanned(store, b"c", b"a"), vec![]);
+        assert_eq!(scanned(store, b"a", b"a"), vec![]);
+
+        // keys are bytes, not text, and a value may be empty
+        let keys: [&[u8]; 5] = [b"", b"\x00", b"\x00\x00", b"\xFF", b"\xFF\x00"];
+        for key in keys {
+            store.put(key, &[]).unwrap();
+        }
+        store.put(b"a\x00b", &[0, 255, 0]).unwrap();
+        assert_eq!(store.get_by_key(b""), Ok(Some(vec![])));
+        assert_eq!(store.get_by_key(b"a\x00b"), Ok(Some(vec![0, 255, 0])));
+        let all: Vec<_> = scanned(store, b"", b"\xFF\xFF")
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            all,
+            [
+                &b""[..],
+                b"\x00",
+                b"\x00\x00",
+                b"a",
+                b"a\x00b",
+                b"c",
+                b"\xFF",
+                b"\xFF\x00"
+            ]
+        );
+
+        // the scan reads as far as it is iterated
+        let key = |index: u32| [&b"k/"[..], &index.to_be_bytes()].concat();
+        for index in 0..1000 {
+            store
+                .put(&key(index), &vec![index as u8; index as usize])
+                .unwrap();
+        }
+        let mut scan = store.scan(&key(10), b"k0");
+        assert_eq!(scan.next(), Some(Ok((key(10), vec![10; 10]))));
+        assert_eq!(scan.count(), 989);
+        assert_eq!(
+            store.get_by_key(&key(999)),
+            Ok(Some(vec![999u32 as u8; 999]))
+        );
+    }
+
+    #[test]
+    fn memory_store() {
+        check_store(&mut MemoryStore::new());
+    }
+
+    #[test]
+    fn undo_store() {
+        let mut inner = MemoryStore::new();
+        check_store(&mut UndoStore::new(&mut inner));
+
+        let mut store = MemoryStore::new();
+        store.put(b"a", b"1").unwrap();
+        store.put(b"b", b"2").unwrap();
+        let mut undo = UndoStore::new(&mut store);
+        undo.put(b"a", b"one").unwrap();
+        undo.put(b"a", b"uno").unwrap();
+        undo.delete(b"b").unwrap();
+        undo.put(b"c", b"3").unwrap();
+        assert_eq!(undo.get_by_key(b"a"), Ok(Some(b"uno".to_vec())));
+        undo.rollback().unwrap();
+        assert_eq!(
+            scanned(&store, b"", b"\xFF"),
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Collations, the orders of strings. Values compare strings by their bytes, which is
+//! the `binary` collation; [`Value::cmp_collated`] and [`Value::hash_collated`] compare
+//! and hash with another one.
+//!
+//! [`Value::cmp_collated`]: super::Value::cmp_collated
+//! [`Value::hash_collated`]: super::Value::hash_collated
+
+use std::cmp::Ordering;
+
+pub trait Collation: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn compare(&self, left: &str, right: &str) -> Ordering;
+
+    /// Bytes which are equal for the strings the collation finds equal and which sort
+    /// like them, strings are hashed, grouped and indexed by them.
+    fn key(&self, text: &str) -> Vec<u8>;
+}
+
+/// Strings in the order of their UTF-8 bytes, which is the order of their code points.
+pub struct Binary;
+
+impl Collation for Binary {
+    fn name(&self) -> &str {
+        "binary"
+    }
+
+    fn compare(&self, left: &str, right: &str) -> Ordering {
+        left.cmp(right)
+    }
+
+    fn key(&self, text: &str) -> Vec<u8> {
+        text.as_bytes().to_vec()
+    }
+}
+
+/// Strings in the order of their lower case letters, `'Abc'` is equal to `'aBC'`.
+pub struct CaseInsensitive;
+
+impl Collation for CaseInsensitive {
+    fn name(&self) -> &str {
+        "case_insensitive"
+    }
+
+    fn compare(&self, left: &str, right: &str) -> Ordering {
+        left.chars()
+            .flat_map(char::to_lowercase)
+            .cmp(right.chars().flat_map(char::to_lowercase))
+    }
+
+    fn key(&self, text: &str) -> Vec<u8> {
+        // not `str::to_lowercase`, which lowers a final sigma unlike `compare`
+        let lower: String = text.chars().flat_map(char::to_lowercase).collect();
+        lower.into_bytes()
+    }
+}
+
+/// The built-in collation of the name, case doesn't matter.
+pub fn lookup(name: &str) -> Option<&'static dyn Collation> {
+    match name.to_lowercase().as_str() {
+        "binary" | "c" => Some(&Binary),
+        "case_insensitive" | "nocase" => Some(&CaseInsensitive),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_strings() {
+        let nocase = lookup("NOCASE").unwrap();
+        assert_eq!(nocase.compare("Abc", "aBC"), Ordering::Equal);
+        assert_eq!(nocase.compare("abc", "ABD"), Ordering::Less);
+        assert_eq!(nocase.key("ÄB"), nocase.key("äb"));
+        assert_eq!(Binary.compare("B", "a"), Ordering::Less);
+        assert_eq!(nocase.compare("B", "a"), Ordering::Greater);
+        assert!(lookup("klingon").is_none());
+    }
+}
@@ -0,0 +1,661 @@
+//! Exact decimal numbers of any precision, the values of `decimal` and `numeric`.
+//!
+//! A decimal is an integer of decimal digits and a scale, the number of them after the
+//! point: `12.50` has the digits `1250` and the scale 2. The digits are kept least
+//! significant first and without leading zeros, so zero has none. Arithmetic is exact
+//! except division, which is rounded to a scale, and fails only when a result has more
+//! digits than a decimal may have.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Most digits after the point.
+pub const MAX_SCALE: u32 = 16383;
+/// Most digits before the point.
+pub const MAX_INTEGER_DIGITS: u32 = 131072;
+/// Digits after the point of a quotient, unless the operands have more.
+pub const DIVISION_SCALE: u32 = 16;
+
+#[derive(Clone)]
+pub struct Decimal {
+    negative: bool,
+    /// Least significant first, the last one isn't 0
+    digits: Vec<u8>,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn zero() -> Decimal {
+        Decimal {
+            negative: false,
+            digits: vec![],
+            scale: 0,
+        }
+    }
+
+    /// The decimal, `None` if it has too many digits.
+    fn new(negative: bool, mut digits: Vec<u8>, scale: u32) -> Option<Decimal> {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        let integer_digits = digits.len().saturating_sub(scale as usize);
+        if scale > MAX_SCALE || integer_digits > MAX_INTEGER_DIGITS as usize {
+            return None;
+        }
+        Some(Decimal {
+            negative: negative && !digits.is_empty(),
+            digits,
+            scale,
+        })
+    }
+
+    /// The decimal of the digits, least significant first, `None` if it has too many.
+    pub fn from_digits(negative: bool, digits: Vec<u8>, scale: u32) -> Option<Decimal> {
+        Decimal::new(negative, digits, scale)
+    }
+
+    /// Digits of the decimal, least significant first, without leading zeros.
+    pub fn digits(&self) -> &[u8] {
+        &self.digits
+    }
+
+    pub fn from_i64(value: i64) -> Decimal {
+        let mut magnitude = value.unsigned_abs();
+        let mut digits = vec![];
+        while magnitude > 0 {
+            digits.push((magnitude % 10) as u8);
+            magnitude /= 10;
+        }
+        Decimal {
+            negative: value < 0,
+            digits,
+            scale: 0,
+        }
+    }
+
+    /// The shortest decimal which is converted back to the float, `None` for infinities
+    /// and `NaN`.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        match value.is_finite() {
+            true => Decimal::parse(&value.to_string()),
+            false => None,
+        }
+    }
+
+    /// `[+-]digits[.digits][e[+-]digits]`, `.5` and `5.` included.
+    pub fn parse(text: &str) -> Option<Decimal> {
+        let (negative, text) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(position) => {
+                let exponent: i64 = text[position + 1..].parse().ok()?;
+                (&text[..position], exponent)
+            }
+            None => (text, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !(integer.bytes().chain(fraction.bytes())).all(|byte| byte.is_ascii_digit())
+            || exponent.abs() > (MAX_SCALE + MAX_INTEGER_DIGITS) as i64
+        {
+            return None;
+        }
+        let mut digits: Vec<u8> = integer
+            .bytes()
+            .chain(fraction.bytes())
+            .rev()
+            .map(|byte| byte - b'0')
+            .collect();
+        let mut scale = fraction.len() as i64 - exponent;
+        if scale < 0 {
+            digits.splice(0..0, std::iter::repeat_n(0, (-scale) as usize));
+            scale = 0;
+        }
+        // more digits after the point than a decimal has are rounded off
+        round_digits(
+            negative,
+            digits,
+            scale as u32,
+            (scale as u32).min(MAX_SCALE),
+        )
+    }
+
+    /// The exact value of the float, which has at most 1074 digits after the point.
+    /// `None` for infinities and `NaN`.
+    pub fn from_f64_exact(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        } else if value == 0.0 {
+            return Some(Decimal::zero());
+        }
+        let bits = value.to_bits();
+        let (exponent, fraction) = ((bits >> 52 & 0x7FF) as i64, bits & ((1 << 52) - 1));
+        // value = mantissa * 2^exponent
+        let (mantissa, exponent) = match exponent {
+            0 => (fraction, -1074),
+            exponent => (fraction | 1 << 52, exponent - 1075),
+        };
+        let mut digits = Decimal::from_i64(mantissa as i64).digits;
+        // 2^-k = 5^k / 10^k
+        let factor = if exponent < 0 { 5 } else { 2 };
+        for _ in 0..exponent.unsigned_abs() {
+            mul_small(&mut digits, factor);
+        }
+        let scale = if exponent < 0 { -exponent as u32 } else { 0 };
+        // the fewest digits after the point, an even mantissa leaves zeros
+        let zeros = digits
+            .iter()
+            .take(scale as usize)
+            .take_while(|digit| **digit == 0)
+            .count();
+        digits.drain(..zeros);
+        Decimal::new(value.is_sign_negative(), digits, scale - zeros as u32)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// The integer the decimal is rounded to, half away from zero. `None` if it doesn't
+    /// fit.
+    pub fn to_i64(&self) -> Option<i64> {
+        let rounded = self.round(0);
+        let mut value: i64 = 0;
+        for digit in rounded.digits.iter().rev() {
+            value = value.checked_mul(10)?.checked_sub(*digit as i64)?;
+        }
+        match rounded.negative {
+            true => Some(value),
+            false => value.checked_neg(),
+        }
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Digits before the point, without leading zeros.
+    pub fn integer_digits(&self) -> u32 {
+        (self.digits.len() as u32).saturating_sub(self.scale)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The decimal with the scale, rounded half away from zero if it had more digits
+    /// after the point.
+    pub fn round(&self, scale: u32) -> Decimal {
+        round_digits(self.negative, self.digits.clone(), self.scale, scale)
+            .unwrap_or_else(|| self.clone())
+    }
+
+    /// The decimal rounded half away from zero to the number of places after the point,
+    /// before it if negative, e.g. `1250` to -2 places is `1300`. `None` if it gets too
+    /// many digits.
+    pub fn round_places(&self, places: i64) -> Option<Decimal> {
+        if places >= 0 {
+            return Some(self.round(places.min(MAX_SCALE as i64) as u32));
+        }
+        let power = places.unsigned_abs().min(MAX_INTEGER_DIGITS as u64 + 1) as u32;
+        let rounded = round_digits(self.negative, self.digits.clone(), self.scale + power, 0)?;
+        Decimal::new(rounded.negative, shift(&rounded.digits, power), 0)
+    }
+
+    /// The decimal with the scale, the digits after it are cut off.
+    pub fn trunc(&self, scale: u32) -> Decimal {
+        self.rescale(scale, |_| false)
+    }
+
+    /// Largest integer not above the decimal.
+    pub fn floor(&self) -> Decimal {
+        self.rescale(0, |cut| {
+            self.negative && cut.iter().any(|digit| *digit != 0)
+        })
+    }
+
+    /// Smallest integer not below the decimal.
+    pub fn ceil(&self) -> Decimal {
+        self.rescale(0, |cut| {
+            !self.negative && cut.iter().any(|digit| *digit != 0)
+        })
+    }
+
+    /// The decimal with the scale, the magnitude is increased by one unit of the last
+    /// digit if `away` says so for the digits which are cut off.
+    fn rescale(&self, scale: u32, away: impl Fn(&[u8]) -> bool) -> Decimal {
+        if scale >= self.scale {
+            return Decimal {
+                digits: shift(&self.digits, scale - self.scale),
+                scale,
+                ..self.clone()
+            };
+        }
+        let cut = ((self.scale - scale) as usize).min(self.digits.len());
+        let mut digits = self.digits[cut..].to_vec();
+        if away(&self.digits[..cut]) {
+            digits = add_digits(&digits, &[1]);
+        }
+        Decimal::new(self.negative, digits, scale).unwrap_or_else(|| self.clone())
+    }
+
+    pub fn neg(&self) -> Decimal {
+        Decimal {
+            negative: !self.negative && !self.is_zero(),
+            ..self.clone()
+        }
+    }
+
+    pub fn abs(&self) -> Decimal {
+        Decimal {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let (left, right) = (self.aligned(scale), other.aligned(scale));
+        if self.negative == other.negative {
+            return Decimal::new(self.negative, add_digits(&left, &right), scale);
+        }
+        match compare_digits(&left, &right) {
+            Ordering::Less => Decimal::new(other.negative, sub_digits(&right, &left), scale),
+            _ => Decimal::new(self.negative, sub_digits(&left, &right), scale),
+        }
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        self.checked_add(&other.neg())
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.checked_add(other.scale)?;
+        if scale > MAX_SCALE {
+            let product = Decimal {
+                negative: self.negative != other.negative,
+                digits: mul_digits(&self.digits, &other.digits),
+                scale: 0,
+            };
+            return round_digits(product.negative, product.digits, scale, MAX_SCALE);
+        }
+        Decimal::new(
+            self.negative != other.negative,
+            mul_digits(&self.digits, &other.digits),
+            scale,
+        )
+    }
+
+    /// The quotient rounded half away from zero to the scale, `None` for a zero divisor.
+    pub fn checked_div(&self, other: &Decimal, scale: u32) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        // self * 10^(scale + 1 + other.scale - self.scale) / other has one digit more
+        let shift_by = scale as i64 + 1 + other.scale as i64 - self.scale as i64;
+        let (dividend, divisor) = match shift_by {
+            shift_by if shift_by >= 0 => {
+                (shift(&self.digits, shift_by as u32), other.digits.clone())
+            }
+            shift_by => (
+                self.digits.clone(),
+                shift(&other.digits, (-shift_by) as u32),
+            ),
+        };
+        let (quotient, _) = divide_digits(&dividend, &divisor);
+        round_digits(self.negative != other.negative, quotient, scale + 1, scale)
+    }
+
+    /// Remainder of the division truncated to an integer, with the sign of the dividend.
+    pub fn checked_rem(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let scale = self.scale.max(other.scale);
+        let (_, remainder) = divide_digits(&self.aligned(scale), &other.aligned(scale));
+        Decimal::new(self.negative, remainder, scale)
+    }
+
+    /// Default scale of the quotient of the decimals.
+    pub fn division_scale(&self, other: &Decimal) -> u32 {
+        DIVISION_SCALE.max(self.scale).max(other.scale)
+    }
+
+    /// Digits with the scale, which isn't below the decimal's.
+    fn aligned(&self, scale: u32) -> Vec<u8> {
+        shift(&self.digits, scale - self.scale)
+    }
+
+    /// Whether there are no digits after the point but zeros.
+    pub fn is_integral(&self) -> bool {
+        self.normalized().2 == 0
+    }
+
+    /// The decimal without zeros at the end of the fraction, equal decimals have the
+    /// same normalized form.
+    fn normalized(&self) -> (bool, &[u8], u32) {
+        if self.digits.is_empty() {
+            return (false, &[], 0);
+        }
+        let zeros = self
+            .digits
+            .iter()
+            .take(self.scale as usize)
+            .take_while(|digit| **digit == 0)
+            .count();
+        (
+            self.negative,
+            &self.digits[zeros..],
+            self.scale - zeros as u32,
+        )
+    }
+}
+
+/// Digits rounded half away from zero from one scale to a smaller one.
+fn round_digits(negative: bool, digits: Vec<u8>, from: u32, to: u32) -> Option<Decimal> {
+    if to >= from {
+        return Decimal::new(negative, shift(&digits, to - from), to);
+    }
+    let cut = (from - to) as usize;
+    if cut > digits.len() {
+        return Decimal::new(negative, vec![], to);
+    }
+    let round_up = digits[cut - 1] >= 5;
+    let mut digits = digits[cut..].to_vec();
+    if round_up {
+        digits = add_digits(&digits, &[1]);
+    }
+    Decimal::new(negative, digits, to)
+}
+
+/// The digits times 10 to the power.
+fn shift(digits: &[u8], power: u32) -> Vec<u8> {
+    if digits.is_empty() {
+        return vec![];
+    }
+    let mut shifted = vec![0; power as usize];
+    shifted.extend_from_slice(digits);
+    shifted
+}
+
+fn compare_digits(left: &[u8], right: &[u8]) -> Ordering {
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut sum = Vec::with_capacity(left.len().max(right.len()) + 1);
+    let mut carry = 0;
+    for position in 0..left.len().max(right.len()) {
+        let digit = left.get(position).unwrap_or(&0) + right.get(position).unwrap_or(&0) + carry;
+        sum.push(digit % 10);
+        carry = digit / 10;
+    }
+    if carry > 0 {
+        sum.push(carry);
+    }
+    sum
+}
+
+/// `left - right` for `left >= right`, without leading zeros.
+fn sub_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut difference = Vec::with_capacity(left.len());
+    let mut borrow = 0;
+    for (position, digit) in left.iter().enumerate() {
+        let subtrahend = right.get(position).unwrap_or(&0) + borrow;
+        borrow = (*digit < subtrahend) as u8;
+        difference.push(digit + borrow * 10 - subtrahend);
+    }
+    while difference.last() == Some(&0) {
+        difference.pop();
+    }
+    difference
+}
+
+fn mul_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    if left.is_empty() || right.is_empty() {
+        return vec![];
+    }
+    let mut product = vec![0u32; left.len() + right.len()];
+    for (i, left) in left.iter().enumerate() {
+        for (j, right) in right.iter().enumerate() {
+            product[i + j] += *left as u32 * *right as u32;
+        }
+        // keeps the sums far from overflowing
+        if i % 1000 == 999 {
+            carry_digits(&mut product);
+        }
+    }
+    carry_digits(&mut product);
+    let mut product: Vec<u8> = product.into_iter().map(|digit| digit as u8).collect();
+    while product.last() == Some(&0) {
+        product.pop();
+    }
+    product
+}
+
+fn mul_small(digits: &mut Vec<u8>, factor: u8) {
+    let mut carry = 0;
+    for digit in digits.iter_mut() {
+        let product = *digit * factor + carry;
+        *digit = product % 10;
+        carry = product / 10;
+    }
+    if carry > 0 {
+        digits.push(carry);
+    }
+}
+
+fn carry_digits(digits: &mut [u32]) {
+    let mut carry = 0;
+    for digit in digits.iter_mut() {
+        *digit += carry;
+        carry = *digit / 10;
+        *digit %= 10;
+    }
+}
+
+/// Quotient and remainder of a long division, for a divisor which isn't zero.
+fn divide_digits(dividend: &[u8], divisor: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut quotient = vec![0; dividend.len()];
+    let mut remainder: Vec<u8> = vec![];
+    for position in (0..dividend.len()).rev() {
+        remainder = shift(&remainder, 1);
+        if remainder.is_empty() {
+            if dividend[position] != 0 {
+                remainder.push(dividend[position]);
+            }
+        } else {
+            remainder[0] = dividend[position];
+        }
+        let mut digit = 0;
+        while compare_digits(&remainder, divisor) != Ordering::Less {
+            remainder = sub_digits(&remainder, divisor);
+            digit += 1;
+        }
+        quotient[position] = digit;
+    }
+    while quotient.last() == Some(&0) {
+        quotient.pop();
+    }
+    (quotient, remainder)
+}
+
+/// Decimals are equal if they are the same number, whatever their scales.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.negative != other.negative {
+            return other.negative.cmp(&self.negative);
+        }
+        let scale = self.scale.max(other.scale);
+        let ordering = compare_digits(&self.aligned(scale), &other.aligned(scale));
+        match self.negative {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+/// All digits of the scale, e.g. `1.50`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = self.scale as usize;
+        let mut text: String = self
+            .digits
+            .iter()
+            .rev()
+            .map(|digit| (b'0' + digit) as char)
+            .collect();
+        if text.len() <= scale {
+            text.insert_str(0, &"0".repeat(scale + 1 - text.len()));
+        }
+        if scale > 0 {
+            text.insert(text.len() - scale, '.');
+        }
+        if self.negative {
+            text.insert(0, '-');
+        }
+        f.write_str(&text)
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decimal({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        for text in ["0", "1.50", "-0.001", "123456789012345678901234567890.5"] {
+            assert_eq!(decimal(text).to_string(), text);
+        }
+        assert_eq!(decimal("+.5").to_string(), "0.5");
+        assert_eq!(decimal("5.").to_string(), "5");
+        assert_eq!(decimal("1.5e3").to_string(), "1500");
+        assert_eq!(decimal("15e-3").to_string(), "0.015");
+        assert_eq!(decimal("-0.00").to_string(), "0.00");
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert_eq!(
+            Decimal::from_f64_exact(0.1).unwrap().to_string(),
+            "0.1000000000000000055511151231257827021181583404541015625"
+        );
+        assert_eq!(Decimal::from_f64_exact(-1e20).unwrap(), decimal("-1e20"));
+        assert_eq!(Decimal::from_f64_exact(-0.0).unwrap().to_string(), "0");
+        assert_eq!(
+            Decimal::from_i64(i64::MIN).to_string(),
+            "-9223372036854775808"
+        );
+        for text in ["", ".", "1.2.3", "e5", "1e", "--1", "1x"] {
+            assert!(Decimal::parse(text).is_none(), "{}", text);
+        }
+        assert!(Decimal::parse("1e200000").is_none());
+    }
+
+    #[test]
+    fn compares_numerically() {
+        assert_eq!(decimal("1.50"), decimal("1.5"));
+        assert!(decimal("-2") < decimal("-1.99"));
+        assert!(decimal("0.001") > decimal("-5"));
+        assert!(decimal("10") > decimal("9.999"));
+        let hash = |decimal: &Decimal| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            decimal.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&decimal("2.500")), hash(&decimal("2.5")));
+        assert_eq!(hash(&decimal("0.0")), hash(&decimal("-0")));
+    }
+
+    #[test]
+    fn computes_exactly() {
+        let (a, b) = (decimal("0.1"), decimal("0.2"));
+        assert_eq!(a.checked_add(&b).unwrap().to_string(), "0.3");
+        assert_eq!(a.checked_sub(&b).unwrap().to_string(), "-0.1");
+        assert_eq!(
+            decimal("12345678901234567890")
+                .checked_mul(&decimal("-98765432109876543210.5"))
+                .unwrap()
+                .to_string(),
+            "-1219326311370217952243636640561880810845.0"
+        );
+        let one = Decimal::from_i64(1);
+        let three = Decimal::from_i64(3);
+        assert_eq!(
+            one.checked_div(&three, one.division_scale(&three))
+                .unwrap()
+                .to_string(),
+            "0.3333333333333333"
+        );
+        assert_eq!(
+            decimal("2").checked_div(&three, 2).unwrap().to_string(),
+            "0.67"
+        );
+        assert_eq!(
+            decimal("-7.5")
+                .checked_rem(&decimal("2"))
+                .unwrap()
+                .to_string(),
+            "-1.5"
+        );
+        assert!(one.checked_div(&Decimal::zero(), 2).is_none());
+    }
+
+    #[test]
+    fn rounds() {
+        assert_eq!(decimal("2.345").round(2).to_string(), "2.35");
+        assert_eq!(
+            decimal("1250").round_places(-2).unwrap().to_string(),
+            "1300"
+        );
+        assert_eq!(decimal("-49.9").round_places(-2).unwrap().to_string(), "0");
+        assert_eq!(decimal("-2.345").round(2).to_string(), "-2.35");
+        assert_eq!(decimal("0.004").round(2).to_string(), "0.00");
+        assert_eq!(decimal("9.99").round(1).to_string(), "10.0");
+        assert_eq!(decimal("1.5").round(3).to_string(), "1.500");
+        assert_eq!(decimal("-2.7").trunc(0).to_string(), "-2");
+        assert_eq!(decimal("-2.1").floor().to_string(), "-3");
+        assert_eq!(decimal("2.1").ceil().to_string(), "3");
+        assert_eq!(decimal("2.5").to_i64(), Some(3));
+        assert_eq!(decimal("-2.5").to_i64(), Some(-3));
+        assert_eq!(decimal("9223372036854775808").to_i64(), None);
+        assert_eq!(decimal("-9223372036854775808").to_i64(), Some(i64::MIN));
+    }
+}
@@ -0,0 +1,286 @@
+//! Compact binary encoding of values and rows, as rows are stored and spilled.
+//!
+//! A value is a tag byte and what the tag needs: booleans are their tags, integers,
+//! dates, times, timestamps and the parts of intervals are zigzag varints, floats their
+//! eight bytes, strings a varint length and their bytes, and decimals a varint of the
+//! scale and the sign, a varint number of digits and the digits two to a byte. A row is
+//! the varint number of its values and the values.
+
+use super::{Decimal, Interval, Value};
+use std::fmt;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INTEGER: u8 = 3;
+const FLOAT: u8 = 4;
+const DECIMAL: u8 = 5;
+const STRING: u8 = 6;
+const DATE: u8 = 7;
+const TIME: u8 = 8;
+const TIMESTAMP: u8 = 9;
+const TIMESTAMPTZ: u8 = 10;
+const INTERVAL: u8 = 11;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    InvalidTag(u8),
+    /// The bytes after the tag aren't a value of its kind
+    InvalidValue(&'static str),
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => f.write_str("unexpected end of value"),
+            DecodeError::InvalidTag(tag) => write!(f, "invalid value tag {}", tag),
+            DecodeError::InvalidValue(kind) => write!(f, "invalid {}", kind),
+            DecodeError::TrailingBytes => f.write_str("trailing bytes"),
+        }
+    }
+}
+
+pub fn encode_row(row: &[Value]) -> Vec<u8> {
+    let mut bytes = vec![];
+    varint(&mut bytes, row.len() as u64);
+    for value in row {
+        encode_value(&mut bytes, value);
+    }
+    bytes
+}
+
+pub fn decode_row(bytes: &[u8]) -> Result<Vec<Value>, DecodeError> {
+    let mut position = 0;
+    let length = read_varint(bytes, &mut position)?;
+    // every value has at least its tag
+    if length > bytes.len() as u64 {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let row = (0..length)
+        .map(|_| decode_value(bytes, &mut position))
+        .collect::<Result<_, _>>()?;
+    match position == bytes.len() {
+        true => Ok(row),
+        false => Err(DecodeError::TrailingBytes),
+    }
+}
+
+pub fn encode_value(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => bytes.push(NULL),
+        Value::Boolean(false) => bytes.push(FALSE),
+        Value::Boolean(true) => bytes.push(TRUE),
+        Value::Integer(value) => {
+            bytes.push(INTEGER);
+            zigzag(bytes, *value);
+        }
+        Value::Float(value) => {
+            bytes.push(FLOAT);
+            bytes.extend(value.to_le_bytes());
+        }
+        Value::Decimal(value) => {
+            bytes.push(DECIMAL);
+            varint(
+                bytes,
+                (value.scale() as u64) << 1 | value.is_negative() as u64,
+            );
+            let digits = value.digits();
+            varint(bytes, digits.len() as u64);
+            bytes.extend(
+                digits
+                    .chunks(2)
+                    .map(|pair| pair[0] | pair.get(1).unwrap_or(&0) << 4),
+            );
+        }
+        Value::String(value) => {
+            bytes.push(STRING);
+            varint(bytes, value.len() as u64);
+            bytes.extend(value.as_bytes());
+        }
+        Value::Date(days) => {
+            bytes.push(DATE);
+            zigzag(bytes, *days as i64);
+        }
+        Value::Time(micros) => {
+            bytes.push(TIME);
+            zigzag(bytes, *micros);
+        }
+        Value::Timestamp(micros) => {
+            bytes.push(TIMESTAMP);
+            zigzag(bytes, *micros);
+        }
+        Value::TimestampTz(micros) => {
+            bytes.push(TIMESTAMPTZ);
+            zigzag(bytes, *micros);
+        }
+        Value::Interval(interval) => {
+            bytes.push(INTERVAL);
+            zigzag(bytes, interval.months);
+            zigzag(bytes, interval.days);
+            zigzag(bytes, interval.micros);
+        }
+    }
+}
+
+/// The value at the position, which is moved past it.
+pub fn decode_value(bytes: &[u8], position: &mut usize) -> Result<Value, DecodeError> {
+    let tag = take(bytes, position, 1)?[0];
+    Ok(match tag {
+        NULL => Value::Null,
+        FALSE => Value::Boolean(false),
+        TRUE => Value::Boolean(true),
+        INTEGER => Value::Integer(read_zigzag(bytes, position)?),
+        FLOAT => {
+            let value = take(bytes, position, 8)?;
+            Value::Float(f64::from_le_bytes(value.try_into().expect("8 bytes")))
+        }
+        DECIMAL => {
+            let sign_and_scale = read_varint(bytes, position)?;
+            let length = read_varint(bytes, position)?;
+            let length = usize::try_from(length)
+                .ok()
+                .filter(|length| *length <= (bytes.len() - *position) * 2)
+                .ok_or(DecodeError::UnexpectedEnd)?;
+            let packed = take(bytes, position, length.div_ceil(2))?;
+            let digits: Vec<u8> = packed
+                .iter()
+                .flat_map(|pair| [pair & 0x0F, pair >> 4])
+                .take(length)
+                .collect();
+            let scale = u32::try_from(sign_and_scale >> 1)
+                .map_err(|_| DecodeError::InvalidValue("decimal"))?;
+            if digits.iter().any(|digit| *digit > 9) || digits.last() == Some(&0) {
+                return Err(DecodeError::InvalidValue("decimal"));
+            }
+            Value::Decimal(
+                Decimal::from_digits(sign_and_scale & 1 == 1, digits, scale)
+                    .ok_or(DecodeError::InvalidValue("decimal"))?,
+            )
+        }
+        STRING => {
+            let length = read_varint(bytes, position)?;
+            let length = usize::try_from(length).map_err(|_| DecodeError::UnexpectedEnd)?;
+            let text = take(bytes, position, length)?;
+            Value::String(
+                String::from_utf8(text.to_vec())
+                    .map_err(|_| DecodeError::InvalidValue("string"))?,
+            )
+        }
+        DATE => Value::Date(
+            i32::try_from(read_zigzag(bytes, position)?)
+                .map_err(|_| DecodeError::InvalidValue("date"))?,
+        ),
+        TIME => Value::Time(read_zigzag(bytes, position)?),
+        TIMESTAMP => Value::Timestamp(read_zigzag(bytes, position)?),
+        TIMESTAMPTZ => Value::TimestampTz(read_zigzag(bytes, position)?),
+        INTERVAL => Value::Interval(Interval {
+            months: read_zigzag(bytes, position)?,
+            days: read_zigzag(bytes, position)?,
+            micros: read_zigzag(bytes, position)?,
+        }),
+        tag => return Err(DecodeError::InvalidTag(tag)),
+    })
+}
+
+fn take<'a>(bytes: &'a [u8], position: &mut usize, length: usize) -> Result<&'a [u8], DecodeError> {
+    let taken = bytes
+        .get(*position..position.saturating_add(length))
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    *position += length;
+    Ok(taken)
+}
+
+/// Seven bits to a byte, the least significant first, the high bit says another follows.
+fn varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(bytes, position, 1)?[0];
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidValue("varint"))
+}
+
+/// Small negative numbers are small too: 0, -1, 1, -2 are 0, 1, 2, 3.
+fn zigzag(bytes: &mut Vec<u8>, value: i64) {
+    varint(bytes, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn read_zigzag(bytes: &[u8], position: &mut usize) -> Result<i64, DecodeError> {
+    let value = read_varint(bytes, position)?;
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_rows() {
+        let row = vec![
+            Value::Null,
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Integer(0),
+            Value::Integer(i64::MIN),
+            Value::Integer(i64::MAX),
+            Value::Float(-2.5),
+            Value::Decimal(Decimal::parse("-12345.670").unwrap()),
+            Value::Decimal(Decimal::zero()),
+            Value::from("héllo"),
+            Value::Date(-719162),
+            Value::Time(86_399_999_999),
+            Value::Timestamp(-1),
+            Value::TimestampTz(1_709_296_200_000_000),
+            Value::Interval(Interval {
+                months: 14,
+                days: -3,
+                micros: 5,
+            }),
+        ];
+        let bytes = encode_row(&row);
+        let decoded = decode_row(&bytes).unwrap();
+        assert_eq!(decoded, row);
+        // the scale is kept, not only the number
+        assert_eq!(decoded[7].to_string(), "-12345.670");
+        assert_eq!(
+            encode_row(&[Value::Integer(-1), Value::Boolean(true)]),
+            [2, 3, 1, 2]
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_rows() {
+        let bytes = encode_row(&[Value::from("abc"), Value::Integer(300)]);
+        assert_eq!(
+            decode_row(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(decode_row(&[1, 99]), Err(DecodeError::InvalidTag(99)));
+        assert_eq!(decode_row(&[1, 0, 0]), Err(DecodeError::TrailingBytes));
+        assert_eq!(
+            decode_row(&[1, STRING, 1, 0xFF]),
+            Err(DecodeError::InvalidValue("string"))
+        );
+        assert_eq!(
+            decode_row(&[1, DECIMAL, 0, 1, 0x0A]),
+            Err(DecodeError::InvalidValue("decimal"))
+        );
+        assert_eq!(
+            decode_row(&[0xFF; 11]),
+            Err(DecodeError::InvalidValue("varint"))
+        );
+    }
+}
@@ -0,0 +1,585 @@
+//! Runtime values and their types.
+
+pub mod collation;
+pub mod decimal;
+pub mod encoding;
+pub mod temporal;
+
+pub use decimal::Decimal;
+pub use temporal::Interval;
+
+use crate::parser::expr::Literal;
+use collation::{Binary, Collation};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum DataType {
+    Boolean,
+    TinyInt,
+    SmallInt,
+    Integer,
+    BigInt,
+    Real,
+    Double,
+    /// Precision and scale, `None` when they aren't constrained
+    Decimal(Option<(u32, u32)>),
+    /// Maximum length, `None` when it isn't limited
+    VarChar(Option<u32>),
+    Text,
+    Date,
+    Time,
+    Timestamp,
+    /// An instant, written in UTC
+    TimestampTz,
+    Interval,
+}
+
+impl DataType {
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.numeric_rank().is_some()
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, DataType::VarChar(_) | DataType::Text)
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        matches!(
+            self,
+            DataType::Date | DataType::Time | DataType::Timestamp | DataType::TimestampTz
+        )
+    }
+
+    /// Position in `tinyint -> smallint -> integer -> bigint -> decimal -> real -> double`,
+    /// a value is implicitly cast only to the right.
+    pub(crate) fn numeric_rank(&self) -> Option<u8> {
+        match self {
+            DataType::TinyInt => Some(0),
+            DataType::SmallInt => Some(1),
+            DataType::Integer => Some(2),
+            DataType::BigInt => Some(3),
+            DataType::Decimal(_) => Some(4),
+            DataType::Real => Some(5),
+            DataType::Double => Some(6),
+            _ => None,
+        }
+    }
+
+    /// Number of decimal digits an integer type may need.
+    fn integer_digits(&self) -> u32 {
+        match self {
+            DataType::TinyInt => 3,
+            DataType::SmallInt => 5,
+            DataType::Integer => 10,
+            DataType::BigInt => 19,
+            DataType::Decimal(Some((precision, scale))) => precision - scale,
+            _ => 0,
+        }
+    }
+
+    /// Type both types are implicitly cast to when they meet in an expression, e.g. `integer + double`.
+    /// `None` if there is no such type.
+    pub fn common_type(self, other: DataType) -> Option<DataType> {
+        if self == other {
+            return Some(self);
+        }
+        match (self, other) {
+            (DataType::VarChar(Some(left)), DataType::VarChar(Some(right))) => {
+                Some(DataType::VarChar(Some(left.max(right))))
+            }
+            (DataType::VarChar(_), DataType::VarChar(_)) => Some(DataType::VarChar(None)),
+            (left, right) if left.is_string() && right.is_string() => Some(DataType::Text),
+            (DataType::Date, DataType::Timestamp) | (DataType::Timestamp, DataType::Date) => {
+                Some(DataType::Timestamp)
+            }
+            (DataType::Date | DataType::Timestamp, DataType::TimestampTz)
+            | (DataType::TimestampTz, DataType::Date | DataType::Timestamp) => {
+                Some(DataType::TimestampTz)
+            }
+            (DataType::Decimal(left), DataType::Decimal(right)) => match (left, right) {
+                (Some((_, left_scale)), Some((_, right_scale))) => {
+                    let scale = left_scale.max(right_scale);
+                    let digits = self.integer_digits().max(other.integer_digits());
+                    Some(DataType::Decimal(Some((digits + scale, scale))))
+                }
+                _ => Some(DataType::Decimal(None)),
+            },
+            (DataType::Decimal(Some((_, scale))), integer)
+            | (integer, DataType::Decimal(Some((_, scale))))
+                if integer.is_integer() =>
+            {
+                let digits = self.integer_digits().max(other.integer_digits());
+                Some(DataType::Decimal(Some((digits + scale, scale))))
+            }
+            (left, right) => {
+                let rank = left.numeric_rank()?.max(right.numeric_rank()?);
+                Some(if left.numeric_rank() == Some(rank) {
+                    left
+                } else {
+                    right
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Boolean => f.write_str("BOOLEAN"),
+            DataType::TinyInt => f.write_str("TINYINT"),
+            DataType::SmallInt => f.write_str("SMALLINT"),
+            DataType::Integer => f.write_str("INTEGER"),
+            DataType::BigInt => f.write_str("BIGINT"),
+            DataType::Real => f.write_str("REAL"),
+            DataType::Double => f.write_str("DOUBLE"),
+            DataType::Decimal(None) => f.write_str("DECIMAL"),
+            DataType::Decimal(Some((precision, scale))) => {
+                write!(f, "DECIMAL({}, {})", precision, scale)
+            }
+            DataType::VarChar(None) => f.write_str("VARCHAR"),
+            DataType::VarChar(Some(length)) => write!(f, "VARCHAR({})", length),
+            DataType::Text => f.write_str("TEXT"),
+            DataType::Date => f.write_str("DATE"),
+            DataType::Time => f.write_str("TIME"),
+            DataType::Timestamp => f.write_str("TIMESTAMP"),
+            DataType::TimestampTz => f.write_str("TIMESTAMP WITH TIME ZONE"),
+            DataType::Interval => f.write_str("INTERVAL"),
+        }
+    }
+}
+
+/// A value of any of the types. Numbers equal and order by what they are whatever
+/// their kind, `1`, `1.0` and `1.00` are equal, other values only equal values of their
+/// kind and are ordered after the kinds before them. `NULL` is equal to itself and
+/// greater than any other value, as in a sort; SQL's comparisons, unknown with `NULL`,
+/// are `execution::expr::compare`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Decimal(Decimal),
+    String(String),
+    /// Days since 1970-01-01
+    Date(i32),
+    /// Microseconds since midnight
+    Time(i64),
+    /// Microseconds since 1970-01-01 00:00
+    Timestamp(i64),
+    /// Microseconds since 1970-01-01 00:00 UTC
+    TimestampTz(i64),
+    Interval(Interval),
+}
+
+impl Value {
+    /// `None` for `NULL` which fits any type.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Boolean(_) => Some(DataType::Boolean),
+            Value::Integer(_) => Some(DataType::BigInt),
+            Value::Float(_) => Some(DataType::Double),
+            Value::Decimal(_) => Some(DataType::Decimal(None)),
+            Value::String(_) => Some(DataType::Text),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::TimestampTz(_) => Some(DataType::TimestampTz),
+            Value::Interval(_) => Some(DataType::Interval),
+        }
+    }
+
+    /// Numbers without a fraction are integers unless they don't fit into `i64`, then
+    /// they are decimals as the others are.
+    pub fn from_literal(literal: &Literal) -> Value {
+        match literal {
+            Literal::Numeric(value) => match value.parse() {
+                Ok(integer) => Value::Integer(integer),
+                Err(_) => match Decimal::parse(value) {
+                    Some(decimal) => Value::Decimal(decimal),
+                    None => Value::Float(value.parse().unwrap_or(f64::NAN)),
+                },
+            },
+            Literal::String(value) => Value::String(value.clone()),
+            Literal::Boolean(value) => Value::Boolean(*value),
+            Literal::Null => Value::Null,
+        }
+    }
+
+    /// The literal `from_literal` turns into the value, `None` for a float which isn't
+    /// finite. Dates, times and intervals are their text, which is cast to their type.
+    pub fn to_literal(&self) -> Option<Literal> {
+        Some(match self {
+            Value::Null => Literal::Null,
+            Value::Boolean(value) => Literal::Boolean(*value),
+            Value::Integer(value) => Literal::Numeric(value.to_string()),
+            Value::Float(value) if value.is_finite() => Literal::Numeric(value.to_string()),
+            Value::Float(_) => return None,
+            Value::Decimal(value) => Literal::Numeric(value.to_string()),
+            value => Literal::String(value.text()),
+        })
+    }
+
+    /// The value as text without quotes or type, as it is shown to a client and cast
+    /// to text.
+    pub fn text(&self) -> String {
+        match self {
+            Value::String(value) => value.clone(),
+            Value::Boolean(value) => value.to_string(),
+            Value::Date(days) => temporal::format_date(*days as i64),
+            Value::Time(micros) => temporal::format_time(*micros),
+            Value::Timestamp(micros) => temporal::format_timestamp(*micros),
+            Value::TimestampTz(micros) => temporal::format_timestamptz(*micros),
+            Value::Interval(interval) => interval.to_string(),
+            value => value.to_string(),
+        }
+    }
+
+    /// Position of the kind of the value in the order of values, numbers are one kind.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Boolean(_) => 0,
+            Value::Integer(_) | Value::Float(_) | Value::Decimal(_) => 1,
+            Value::String(_) => 2,
+            Value::Date(_) => 3,
+            Value::Time(_) => 4,
+            Value::Timestamp(_) => 5,
+            Value::TimestampTz(_) => 6,
+            Value::Interval(_) => 7,
+            Value::Null => 8,
+        }
+    }
+
+    /// The order of `Ord` with strings in the order of the collation.
+    pub fn cmp_collated(&self, other: &Value, collation: &dyn Collation) -> Ordering {
+        match (self, other) {
+            (Value::Boolean(left), Value::Boolean(right)) => left.cmp(right),
+            (Value::Integer(left), Value::Integer(right)) => left.cmp(right),
+            (Value::Float(left), Value::Float(right)) => {
+                canonical(*left).total_cmp(&canonical(*right))
+            }
+            (Value::Decimal(left), Value::Decimal(right)) => left.cmp(right),
+            (Value::Integer(left), Value::Float(right)) => compare_integer_float(*left, *right),
+            (Value::Float(left), Value::Integer(right)) => {
+                compare_integer_float(*right, *left).reverse()
+            }
+            (Value::Integer(left), Value::Decimal(right)) => Decimal::from_i64(*left).cmp(right),
+            (Value::Decimal(left), Value::Integer(right)) => left.cmp(&Decimal::from_i64(*right)),
+            (Value::Float(left), Value::Decimal(right)) => compare_float_decimal(*left, right),
+            (Value::Decimal(left), Value::Float(right)) => {
+                compare_float_decimal(*right, left).reverse()
+            }
+            (Value::String(left), Value::String(right)) => collation.compare(left, right),
+            (Value::Date(left), Value::Date(right)) => left.cmp(right),
+            (Value::Time(left), Value::Time(right))
+            | (Value::Timestamp(left), Value::Timestamp(right))
+            | (Value::TimestampTz(left), Value::TimestampTz(right)) => left.cmp(right),
+            (Value::Interval(left), Value::Interval(right)) => left.span().cmp(&right.span()),
+            (left, right) => left.rank().cmp(&right.rank()),
+        }
+    }
+
+    /// The hash of `Hash` with strings hashed by the key of the collation, values equal
+    /// by `cmp_collated` have the same hash.
+    pub fn hash_collated<H: Hasher>(&self, state: &mut H, collation: &dyn Collation) {
+        match self {
+            Value::String(text) => {
+                self.rank().hash(state);
+                collation.key(text).hash(state);
+            }
+            value => value.hash(state),
+        }
+    }
+}
+
+/// `-0` is `0` and all `NaN`s are one, which is greater than any other float.
+fn canonical(value: f64) -> f64 {
+    match value {
+        0.0 => 0.0,
+        value if value.is_nan() => f64::NAN,
+        value => value,
+    }
+}
+
+/// 2^63, floats from -2^63 to below it have an integer part which fits `i64`.
+const INTEGER_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Exact order of an integer and a float, `NaN` is greater than any number.
+fn compare_integer_float(integer: i64, float: f64) -> Ordering {
+    if float.is_nan() || float >= INTEGER_LIMIT {
+        return Ordering::Less;
+    }
+    if float < -INTEGER_LIMIT {
+        return Ordering::Greater;
+    }
+    let floor = float.floor();
+    match integer.cmp(&(floor as i64)) {
+        Ordering::Equal if float > floor => Ordering::Less,
+        ordering => ordering,
+    }
+}
+
+/// Exact order of a float and a decimal, `NaN` is greater than any number.
+fn compare_float_decimal(float: f64, decimal: &Decimal) -> Ordering {
+    match Decimal::from_f64_exact(float) {
+        Some(float) => float.cmp(decimal),
+        None if float == f64::NEG_INFINITY => Ordering::Less,
+        None => Ordering::Greater,
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_collated(other, &Binary)
+    }
+}
+
+/// Equal numbers of different kinds hash alike: integers and the floats and decimals
+/// equal to one as integers, other floats and the decimals equal to one as floats.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Value::Null => {}
+            Value::Boolean(value) => value.hash(state),
+            Value::Integer(value) => (0u8, value).hash(state),
+            Value::Float(value) => {
+                let value = canonical(*value);
+                match value.fract() == 0.0 && (-INTEGER_LIMIT..INTEGER_LIMIT).contains(&value) {
+                    true => (0u8, value as i64).hash(state),
+                    false => (1u8, value.to_bits()).hash(state),
+                }
+            }
+            Value::Decimal(value) => match value.to_i64() {
+                Some(integer) if value.is_integral() => (0u8, integer).hash(state),
+                _ => {
+                    let float = value.to_f64();
+                    match Decimal::from_f64_exact(float).as_ref() == Some(value) {
+                        true => (1u8, canonical(float).to_bits()).hash(state),
+                        false => (2u8, value).hash(state),
+                    }
+                }
+            },
+            Value::String(value) => value.hash(state),
+            Value::Date(days) => days.hash(state),
+            Value::Time(micros) | Value::Timestamp(micros) | Value::TimestampTz(micros) => {
+                micros.hash(state)
+            }
+            Value::Interval(interval) => interval.span().hash(state),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(value: Decimal) -> Self {
+        Value::Decimal(value)
+    }
+}
+
+impl From<Interval> for Value {
+    fn from(value: Interval) -> Self {
+        Value::Interval(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+/// The value as a SQL literal, e.g. `'it''s'` or `DATE '2024-03-01'`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Boolean(true) => f.write_str("TRUE"),
+            Value::Boolean(false) => f.write_str("FALSE"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Decimal(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            value => write!(
+                f,
+                "{} '{}'",
+                value.data_type().expect("not null"),
+                value.text()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_types() {
+        let common = |left: DataType, right: DataType| left.common_type(right);
+        assert_eq!(
+            common(DataType::Integer, DataType::BigInt),
+            Some(DataType::BigInt)
+        );
+        assert_eq!(
+            common(DataType::Double, DataType::SmallInt),
+            Some(DataType::Double)
+        );
+        assert_eq!(
+            common(DataType::BigInt, DataType::Decimal(None)),
+            Some(DataType::Decimal(None))
+        );
+        assert_eq!(
+            common(DataType::Integer, DataType::Decimal(Some((5, 2)))),
+            Some(DataType::Decimal(Some((12, 2))))
+        );
+        assert_eq!(
+            common(
+                DataType::Decimal(Some((5, 2))),
+                DataType::Decimal(Some((6, 0)))
+            ),
+            Some(DataType::Decimal(Some((8, 2))))
+        );
+        assert_eq!(
+            common(DataType::Decimal(Some((5, 2))), DataType::Real),
+            Some(DataType::Real)
+        );
+        assert_eq!(
+            common(DataType::VarChar(Some(3)), DataType::VarChar(Some(10))),
+            Some(DataType::VarChar(Some(10)))
+        );
+        assert_eq!(
+            common(DataType::VarChar(Some(3)), DataType::Text),
+            Some(DataType::Text)
+        );
+        assert_eq!(
+            common(DataType::Date, DataType::Timestamp),
+            Some(DataType::Timestamp)
+        );
+        assert_eq!(common(DataType::Text, DataType::Integer), None);
+        assert_eq!(common(DataType::Boolean, DataType::Integer), None);
+        assert_eq!(common(DataType::Time, DataType::Date), None);
+    }
+
+    #[test]
+    fn orders_values() {
+        assert!(Value::Integer(2) < Value::Float(2.5));
+        assert!(Value::Float(-1.0) < Value::Integer(0));
+        assert!(Value::from("abc") < Value::from("abd"));
+        assert!(Value::Boolean(false) < Value::Boolean(true));
+        let decimal = |text: &str| Value::Decimal(Decimal::parse(text).unwrap());
+        assert!(decimal("2.49") < Value::Float(2.5));
+        assert!(Value::Integer(3) > decimal("2.99"));
+        // 0.1 as a float is a bit more than 0.1
+        assert!(Value::Float(0.1) > decimal("0.1"));
+        assert!(Value::Integer(i64::MAX) < Value::Float(9.3e18));
+        assert!(Value::Float(f64::NAN) > Value::Float(f64::INFINITY));
+        assert_eq!(Value::Float(-0.0), Value::Float(0.0));
+        assert_eq!(Value::Float(f64::NAN), Value::Float(-f64::NAN));
+        let interval = |text: &str| Value::Interval(temporal::parse_interval(text).unwrap());
+        assert_eq!(interval("1 mon"), interval("30 days"));
+        // kinds are ordered, NULL is last and equal to itself
+        assert!(Value::from("1") > Value::Integer(1));
+        assert!(Value::Date(0) > Value::from("2024-01-01"));
+        assert!(Value::Null > Value::Interval(Interval::default()));
+        assert_eq!(Value::Null, Value::Null);
+    }
+
+    #[test]
+    fn hashes_equal_values_alike() {
+        use std::collections::hash_map::DefaultHasher;
+        let hash = |value: &Value| {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+        let decimal = |text: &str| Value::Decimal(Decimal::parse(text).unwrap());
+        for equal in [
+            vec![Value::Integer(1), Value::Float(1.0), decimal("1.00")],
+            vec![Value::Float(0.5), decimal("0.50")],
+            vec![Value::Float(1e20), decimal("1e20")],
+            vec![Value::Float(0.0), Value::Float(-0.0), decimal("0")],
+        ] {
+            for value in &equal {
+                assert_eq!(value, &equal[0]);
+                assert_eq!(hash(value), hash(&equal[0]), "{}", value);
+            }
+        }
+        assert_ne!(Value::Float(0.1), decimal("0.1"));
+        let nocase = collation::lookup("nocase").unwrap();
+        let collated = |value: &Value| {
+            let mut hasher = DefaultHasher::new();
+            value.hash_collated(&mut hasher, nocase);
+            hasher.finish()
+        };
+        let (upper, lower) = (Value::from("ABC"), Value::from("abc"));
+        assert_ne!(upper, lower);
+        assert_eq!(upper.cmp_collated(&lower, nocase), Ordering::Equal);
+        assert_eq!(collated(&upper), collated(&lower));
+    }
+
+    #[test]
+    fn writes_literals() {
+        assert_eq!(Value::Date(19783).to_string(), "DATE '2024-03-01'");
+        assert_eq!(
+            Value::TimestampTz(0).to_string(),
+            "TIMESTAMP WITH TIME ZONE '1970-01-01 00:00:00+00'"
+        );
+        assert_eq!(Value::Time(90_000_000).text(), "00:01:30");
+        let decimal = Value::from_literal(&Literal::Numeric("1.50".to_string()));
+        assert_eq!(decimal, Value::Decimal(Decimal::parse("1.5").unwrap()));
+        assert_eq!(decimal.to_string(), "1.50");
+        assert_eq!(
+            Value::from_literal(&Literal::Numeric("9223372036854775808".to_string())).to_literal(),
+            Some(Literal::Numeric("9223372036854775808".to_string()))
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Dates, times, timestamps and intervals: their calendar, text forms and arithmetic.
+//!
+//! A date is a number of days since 1970-01-01, a time and a timestamp microseconds
+//! since midnight and since 1970-01-01 00:00. A timestamp with time zone is an instant,
+//! microseconds since 1970-01-01 00:00 UTC, it is read with the offset of its text and
+//! written in UTC as there is no session time zone. An interval has months, days and
+//! microseconds apart, as a month has no fixed number of days and a day may not have 24
+//! hours once there are time zones.
+
+use super::{DataType, Value};
+use std::{fmt, ops};
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+pub const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+pub const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+/// Days of a month and months of a year when an interval is spread or measured in seconds
+pub const DAYS_PER_MONTH: i64 = 30;
+pub const MONTHS_PER_YEAR: i64 = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Interval {
+    pub months: i64,
+    pub days: i64,
+    pub micros: i64,
+}
+
+impl Interval {
+    /// The interval times the factor, fractions of months and days are spread to the
+    /// smaller units, a month counting 30 days. `None` if it doesn't fit.
+    pub fn scale(self, factor: f64) -> Option<Interval> {
+        let months = self.months as f64 * factor;
+        let days = self.days as f64 * factor + months.fract() * DAYS_PER_MONTH as f64;
+        let micros = self.micros as f64 * factor + days.fract() * MICROS_PER_DAY as f64;
+        if !(months.abs() < i32::MAX as f64
+            && days.abs() < i32::MAX as f64
+            && micros.abs() < 9.2e18)
+        {
+            return None;
+        }
+        Some(Interval {
+            months: months.trunc() as i64,
+            days: days.trunc() as i64,
+            micros: micros.round() as i64,
+        })
+    }
+
+    pub fn negate(self) -> Interval {
+        Interval {
+            months: -self.months,
+            days: -self.days,
+            micros: -self.micros,
+        }
+    }
+
+    /// Seconds of the interval, with 30-day months and 365.25-day years.
+    pub fn epoch(&self) -> f64 {
+        let years = (self.months / MONTHS_PER_YEAR) as f64;
+        let months = (self.months % MONTHS_PER_YEAR) as f64;
+        years * 365.25 * 86_400.0
+            + months * (DAYS_PER_MONTH * 86_400) as f64
+            + (self.days * 86_400) as f64
+            + self.micros as f64 / MICROS_PER_SECOND as f64
+    }
+
+    /// Microseconds of the interval with 30-day months, intervals are compared by it:
+    /// `1 mon` is equal to `30 days` as in PostgreSQL.
+    pub fn span(&self) -> i128 {
+        (self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+}
+
+/// `1 year 2 mons 3 days 04:05:06.5`, `00:00:00` for an empty interval.
+impl ops::Add for Interval {
+    type Output = Interval;
+
+    fn add(self, other: Interval) -> Interval {
+        Interval {
+            months: self.months + other.months,
+            days: self.days + other.days,
+            micros: self.micros + other.micros,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        let (years, months) = (self.months / MONTHS_PER_YEAR, self.months % MONTHS_PER_YEAR);
+        for (value, unit, units) in [
+            (years, "year", "years"),
+            (months, "mon", "mons"),
+            (self.days, "day", "days"),
+        ] {
+            if value != 0 {
+                let unit = if value.abs() == 1 { unit } else { units };
+                parts.push(format!("{} {}", value, unit));
+            }
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!("{}{}", sign, format_time(self.micros.abs())));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+fn number(text: &str, digits: usize) -> Option<i64> {
+    if text.len() != digits || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+pub fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _ if is_leap_year(year) => 29,
+        _ => 28,
+    }
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of a number of days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// Whether the days since 1970-01-01 are within the years 1 to 9999.
+pub fn is_valid_date(days: i64) -> bool {
+    (1..=9999).contains(&civil_from_days(days).0)
+}
+
+/// `YYYY-MM-DD`
+pub fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let (year, month, day) = (number(year, 4)?, number(month, 2)?, number(day, 2)?);
+    if year == 0 || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// `HH:MM[:SS[.fraction]]`, digits of the fraction below microseconds are cut off.
+pub fn parse_time(text: &str) -> Option<i64> {
+    let (time, fraction) = match text.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (text, None),
+    };
+    let parts: Vec<_> = time.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [hours, minutes] if fraction.is_none() => (number(hours, 2)?, number(minutes, 2)?, 0),
+        [hours, minutes, seconds] => (number(hours, 2)?, number(minutes, 2)?, number(seconds, 2)?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    let micros = match fraction {
+        Some(fraction) if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            return None
+        }
+        Some(fraction) => format!("{:0<6}", &fraction[..fraction.len().min(6)])
+            .parse()
+            .ok()?,
+        None => 0,
+    };
+    Some(
+        hours * MICROS_PER_HOUR
+            + minutes * MICROS_PER_MINUTE
+            + seconds * MICROS_PER_SECOND
+            + micros,
+    )
+}
+
+/// `YYYY-MM-DD[( |T)HH:MM[:SS[.fraction]]]`
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    match text.split_once([' ', 'T']) {
+        Some((date, time)) => Some(parse_date(date)? * MICROS_PER_DAY + parse_time(time)?),
+        None => Some(parse_date(text)? * MICROS_PER_DAY),
+    }
+}
+
+/// A timestamp followed by a time zone, `Z`, `UTC` or an offset `±HH[[:]MM]`, in UTC.
+/// A timestamp without one is in UTC.
+pub fn parse_timestamptz(text: &str) -> Option<i64> {
+    let (local, offset) = split_zone(text)?;
+    let micros = parse_timestamp(local.trim_end())?.checked_sub(offset)?;
+    is_valid_date(micros.div_euclid(MICROS_PER_DAY)).then_some(micros)
+}
+
+/// The timestamp and the microseconds its zone is ahead of UTC.
+fn split_zone(text: &str) -> Option<(&str, i64)> {
+    for zone in ["Z", "z", "UTC", "utc"] {
+        if let Some(local) = text.strip_suffix(zone) {
+            return Some((local, 0));
+        }
+    }
+    // the date has signs too, an offset follows the time
+    let Some(time) = text.find([' ', 'T']) else {
+        return Some((text, 0));
+    };
+    let Some(sign) = text[time + 1..]
+        .rfind(['+', '-'])
+        .map(|sign| time + 1 + sign)
+    else {
+        return Some((text, 0));
+    };
+    let offset = text[sign + 1..].replace(':', "");
+    let (hours, minutes) = match offset.len() {
+        2 => (number(&offset, 2)?, 0),
+        4 => (number(&offset[..2], 2)?, number(&offset[2..], 2)?),
+        _ => return None,
+    };
+    if hours > 15 || minutes > 59 {
+        return None;
+    }
+    let offset = hours * MICROS_PER_HOUR + minutes * MICROS_PER_MINUTE;
+    match &text[sign..sign + 1] {
+        "-" => Some((&text[..sign], -offset)),
+        _ => Some((&text[..sign], offset)),
+    }
+}
+
+/// The text as a value of the date, time, timestamp or interval type, a date is also
+/// read from a timestamp and a time from a timestamp. `None` if it isn't one.
+pub fn parse(text: &str, data_type: DataType) -> Option<Value> {
+    Some(match data_type {
+        DataType::Date => Value::Date(parse_timestamp(text)?.div_euclid(MICROS_PER_DAY) as i32),
+        DataType::Time => Value::Time(match parse_time(text) {
+            Some(micros) => micros,
+            None => parse_timestamp(text)?.rem_euclid(MICROS_PER_DAY),
+        }),
+        DataType::Timestamp => Value::Timestamp(parse_timestamp(text)?),
+        DataType::TimestampTz => Value::TimestampTz(parse_timestamptz(text)?),
+        DataType::Interval => Value::Interval(parse_interval(text)?),
+        _ => return None,
+    })
+}
+
+pub fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `HH:MM:SS` with the fraction of the second if there is one.
+pub fn format_time(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let mut text = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    let fraction = micros % MICROS_PER_SECOND;
+    if fraction != 0 {
+        text.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+    }
+    text
+}
+
+pub fn format_timestamp(micros: i64) -> String {
+    let (days, time) = (
+        micros.div_euclid(MICROS_PER_DAY),
+        micros.rem_euclid(MICROS_PER_DAY),
+    );
+    format!("{} {}", format_date(days), format_time(time))
+}
+
+/// The timestamp in UTC with its offset, `2024-03-01 12:00:00+00`.
+pub fn format_timestamptz(micros: i64) -> String {
+    format!("{}+00", format_timestamp(micros))
+}
+
+/// Postgres' interval input: quantities with units, e.g. `1 year 2 months`, `-3 days`
+/// or `1.5 hours`, and a time `[-]HH:MM[:SS[.fraction]]`. `ago` negates the interval, a
+/// number without a unit is seconds.
+pub fn parse_interval(text: &str) -> Option<Interval> {
+    let words: Vec<_> = text.split_whitespace().collect();
+    let words = words.strip_prefix(&["@"]).unwrap_or(&words);
+    let (words, ago) = match words.split_last() {
+        Some((last, words)) if last.eq_ignore_ascii_case("ago") => (words, true),
+        _ => (words, false),
+    };
+    if words.is_empty() {
+        return None;
+    }
+    let mut interval = Interval::default();
+    let mut index = 0;
+    while index < words.len() {
+        let word = words[index];
+        index += 1;
+        if word.contains(':') {
+            let (sign, time) = match word.strip_prefix('-') {
+                Some(time) => (-1, time),
+                None => (1, word.strip_prefix('+').unwrap_or(word)),
+            };
+            // hours may have any number of digits
+            let (hours, rest) = time.split_once(':')?;
+            if hours.is_empty() || !hours.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let micros = parse_time(&format!("00:{}", rest))?;
+            interval.micros += sign * (hours.parse::<i64>().ok()? * MICROS_PER_HOUR + micros);
+            continue;
+        }
+        let quantity: f64 = word.parse().ok().filter(|q: &f64| q.is_finite())?;
+        let unit = match words.get(index) {
+            Some(unit) if unit.parse::<f64>().is_err() && !unit.contains(':') => {
+                index += 1;
+                unit.to_lowercase()
+            }
+            _ => "second".to_string(),
+        };
+        let (months, days, micros) = match unit.as_str() {
+            "microsecond" | "microseconds" | "us" | "usec" | "usecs" => (0.0, 0.0, 1.0),
+            "millisecond" | "milliseconds" | "ms" | "msec" | "msecs" => (0.0, 0.0, 1e3),
+            "second" | "seconds" | "s" | "sec" | "secs" => (0.0, 0.0, 1e6),
+            "minute" | "minutes" | "m" | "min" | "mins" => (0.0, 0.0, 60e6),
+            "hour" | "hours" | "h" | "hr" | "hrs" => (0.0, 0.0, 3600e6),
+            "day" | "days" | "d" => (0.0, 1.0, 0.0),
+            "week" | "weeks" | "w" => (0.0, 7.0, 0.0),
+            "month" | "months" | "mon" | "mons" => (1.0, 0.0, 0.0),
+            "year" | "years" | "y" | "yr" | "yrs" => (12.0, 0.0, 0.0),
+            "decade" | "decades" => (120.0, 0.0, 0.0),
+            "century" | "centuries" => (1200.0, 0.0, 0.0),
+            "millennium" | "millennia" => (12000.0, 0.0, 0.0),
+            _ => return None,
+        };
+        let unit = Interval {
+            months: months as i64,
+            days: days as i64,
+            micros: micros as i64,
+        };
+        interval = interval + unit.scale(quantity)?;
+    }
+    Some(if ago { interval.negate() } else { interval })
+}
+
+/// The timestamp moved by the interval: months first, the day is kept unless the month
+/// is shorter, then days and microseconds. `None` if it leaves the years 1 to 9999.
+pub fn add_interval(micros: i64, interval: Interval) -> Option<i64> {
+    let (days, time) = (
+        micros.div_euclid(MICROS_PER_DAY),
+        micros.rem_euclid(MICROS_PER_DAY),
+    );
+    let (year, month, day) = civil_from_days(days);
+    let months = year * MONTHS_PER_YEAR + month - 1 + interval.months;
+    let (year, month) = (
+        months.div_euclid(MONTHS_PER_YEAR),
+        months.rem_euclid(MONTHS_PER_YEAR) + 1,
+    );
+    if !(1..=9999).contains(&year) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day.min(days_in_month(year, month))) + interval.days;
+    days.checked_mul(MICROS_PER_DAY)
+        .and_then(|micros| micros.checked_add(time))
+        .and_then(|micros| micros.checked_add(interval.micros))
+        .filter(|micros| is_valid_date(micros.div_euclid(MICROS_PER_DAY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_and_times() {
+        assert!(parse_date("2024-02-29").is_some());
+        assert!(parse_date("2023-02-29").is_none());
+        assert!(parse_date("2024-1-01").is_none());
+        assert!(parse_date("2024-01-01-01").is_none());
+        assert!(parse_time("23:59").is_some());
+        assert!(parse_time("23:59:59.123").is_some());
+        assert!(parse_time("24:00").is_none());
+        assert!(parse_time("10:00.").is_none());
+        assert!(parse_timestamp("2024-01-01T10:00:00").is_some());
+        assert!(parse_timestamp("2024-01-01").is_some());
+        assert!(parse_timestamp("2024-01-01 25:00").is_none());
+        assert_eq!(parse_date("1970-01-02"), Some(1));
+        assert_eq!(format_date(parse_date("0001-01-01").unwrap()), "0001-01-01");
+        assert_eq!(
+            format_timestamp(parse_timestamp("2024-03-01T10:00:00.500").unwrap()),
+            "2024-03-01 10:00:00.5"
+        );
+        let text = |text: &str, data_type| parse(text, data_type).map(|value| value.text());
+        assert_eq!(
+            text("2024-03-01", DataType::Timestamp).as_deref(),
+            Some("2024-03-01 00:00:00")
+        );
+        assert_eq!(
+            text("2024-03-01 10:00", DataType::Date).as_deref(),
+            Some("2024-03-01")
+        );
+        assert_eq!(
+            text("2024-03-01 10:00", DataType::Time).as_deref(),
+            Some("10:00:00")
+        );
+        assert_eq!(text("10:00", DataType::Date), None);
+    }
+
+    #[test]
+    fn parses_time_zones() {
+        let utc = |text: &str| parse_timestamptz(text).map(format_timestamptz);
+        assert_eq!(
+            utc("2024-03-01 10:00:00+02").as_deref(),
+            Some("2024-03-01 08:00:00+00")
+        );
+        assert_eq!(
+            utc("2024-03-01T23:30-05:30").as_deref(),
+            Some("2024-03-02 05:00:00+00")
+        );
+        assert_eq!(
+            utc("2024-03-01 10:00 +0100").as_deref(),
+            Some("2024-03-01 09:00:00+00")
+        );
+        assert_eq!(
+            utc("2024-03-01 10:00Z").as_deref(),
+            Some("2024-03-01 10:00:00+00")
+        );
+        assert_eq!(utc("2024-03-01").as_deref(), Some("2024-03-01 00:00:00+00"));
+        assert_eq!(utc("2024-03-01 10:00+2"), None);
+        assert_eq!(utc("2024-03-01 10:00+16"), None);
+        assert_eq!(utc("0001-01-01 00:00+01"), None);
+    }
+
+    #[test]
+    fn parses_intervals() {
+        let interval = |text: &str| parse_interval(text).map(|interval| interval.to_string());
+        assert_eq!(
+            interval("1 year 2 months 3 days").as_deref(),
+            Some("1 year 2 mons 3 days")
+        );
+        assert_eq!(
+            interval("1 day 04:05:06.5").as_deref(),
+            Some("1 day 04:05:06.5")
+        );
+        assert_eq!(interval("1.5 hours").as_deref(), Some("01:30:00"));
+        assert_eq!(interval("1.5 months").as_deref(), Some("1 mon 15 days"));
+        assert_eq!(interval("2 weeks ago").as_deref(), Some("-14 days"));
+        assert_eq!(interval("@ 30").as_deref(), Some("00:00:30"));
+        assert_eq!(interval("-01:30").as_deref(), Some("-01:30:00"));
+        assert_eq!(interval("0 days").as_deref(), Some("00:00:00"));
+        assert_eq!(interval("1 fortnight"), None);
+        assert_eq!(interval(""), None);
+        let span = |text: &str| parse_interval(text).unwrap().span();
+        assert_eq!(span("1 mon"), span("30 days"));
+        assert!(span("1 day") < span("25 hours"));
+    }
+}